@@ -21,6 +21,15 @@ fn main() {
     )
     .unwrap();
 
+    // RAII wrapper around the C API, which doesn't need the cxx bridge
+    println!("cargo:rerun-if-changed=dora-node.hpp");
+    std::fs::copy("dora-node.hpp", target_dir.join("dora-node.hpp")).unwrap();
+    std::fs::write(
+        target_dir.join("node_api.h"),
+        dora_node_api_c::HEADER_NODE_API,
+    )
+    .unwrap();
+
     #[cfg(feature = "ros2-bridge")]
     ros2::generate_ros2_message_header(bridge_files.last().unwrap());
 
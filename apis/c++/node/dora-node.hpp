@@ -0,0 +1,238 @@
+// C++ wrapper around the C node API of dora.
+//
+// Contexts and events are freed automatically and errors are reported as
+// `dora::Error` exceptions:
+//
+//     dora::Node node;
+//     while (auto event = node.next())
+//     {
+//         if (event.type() == DoraEventType_Input)
+//         {
+//             std::uint64_t len = event.data().size();
+//             node.send_output("length", &len, 1);
+//         }
+//     }
+//
+// Payloads are passed as `dora::Span`, which is `std::span` when compiling
+// with C++20. Requires C++17.
+
+#pragma once
+
+#include "node_api.h"
+
+#include <chrono>
+#include <cstddef>
+#include <cstdint>
+#include <stdexcept>
+#include <string>
+#include <string_view>
+#include <utility>
+
+#if __cplusplus >= 202002L && __has_include(<span>)
+#include <span>
+#endif
+
+namespace dora
+{
+
+#if defined(__cpp_lib_span)
+    template <typename T>
+    using Span = std::span<T>;
+#else
+    /// Minimal replacement for `std::span`, which requires C++20.
+    template <typename T>
+    class Span
+    {
+    public:
+        constexpr Span() noexcept = default;
+        constexpr Span(T *data, std::size_t size) noexcept : data_(data), size_(size) {}
+
+        constexpr T *data() const noexcept { return data_; }
+        constexpr std::size_t size() const noexcept { return size_; }
+        constexpr std::size_t size_bytes() const noexcept { return size_ * sizeof(T); }
+        constexpr bool empty() const noexcept { return size_ == 0; }
+        constexpr T *begin() const noexcept { return data_; }
+        constexpr T *end() const noexcept { return data_ + size_; }
+        constexpr T &operator[](std::size_t i) const noexcept { return data_[i]; }
+
+    private:
+        T *data_ = nullptr;
+        std::size_t size_ = 0;
+    };
+#endif
+
+    using Bytes = Span<const std::uint8_t>;
+
+    class Error : public std::runtime_error
+    {
+    public:
+        using std::runtime_error::runtime_error;
+    };
+
+    /// An event received through `Node::next`. Empty once the event stream ended.
+    class Event
+    {
+    public:
+        explicit Event(void *event) noexcept : event_(event) {}
+        Event(Event &&other) noexcept : event_(std::exchange(other.event_, nullptr)) {}
+        Event &operator=(Event &&other) noexcept
+        {
+            std::swap(event_, other.event_);
+            return *this;
+        }
+        Event(const Event &) = delete;
+        Event &operator=(const Event &) = delete;
+        ~Event()
+        {
+            if (event_ != nullptr)
+            {
+                free_dora_event(event_);
+            }
+        }
+
+        explicit operator bool() const noexcept { return event_ != nullptr; }
+
+        DoraEventType type() const { return read_dora_event_type(raw()); }
+
+        /// ID of an `Input` or `InputClosed` event.
+        std::string_view id() const
+        {
+            char *ptr;
+            std::size_t len;
+            read_dora_input_id(raw(), &ptr, &len);
+            return {ptr, len};
+        }
+
+        /// Raw data of an `Input` event, valid as long as the event.
+        Bytes data() const
+        {
+            char *ptr;
+            std::size_t len;
+            read_dora_input_data(raw(), &ptr, &len);
+            return {reinterpret_cast<const std::uint8_t *>(ptr), len};
+        }
+
+        /// Data of an `Input` event as an array of a fixed-width type, e.g. `float`.
+        template <typename T>
+        Span<const T> data_as() const
+        {
+            Bytes bytes = data();
+            if (bytes.size() % sizeof(T) != 0)
+            {
+                throw Error("input data size is not a multiple of the element size");
+            }
+            return {reinterpret_cast<const T *>(bytes.data()), bytes.size() / sizeof(T)};
+        }
+
+        /// Message of an `Error` event.
+        std::string_view error() const
+        {
+            char *ptr;
+            std::size_t len;
+            read_dora_event_error(raw(), &ptr, &len);
+            return {ptr, len};
+        }
+
+        std::uint64_t timestamp() const { return read_dora_input_timestamp(raw()); }
+
+    private:
+        void *raw() const
+        {
+            if (event_ == nullptr)
+            {
+                throw Error("event stream has ended");
+            }
+            return event_;
+        }
+
+        void *event_;
+    };
+
+    /// Sends outputs of a node. Must not outlive the `Node` that created it.
+    class OutputSender
+    {
+    public:
+        explicit OutputSender(void *context) noexcept : context_(context) {}
+
+        void send(std::string_view output_id, Bytes data) const
+        {
+            int result = dora_send_output(
+                context_,
+                output_id.data(),
+                output_id.size(),
+                reinterpret_cast<const char *>(data.data()),
+                data.size());
+            if (result != 0)
+            {
+                throw Error("failed to send output `" + std::string(output_id) + "`");
+            }
+        }
+
+        template <typename T>
+        void send(std::string_view output_id, const T *values, std::size_t len) const
+        {
+            send(output_id, Bytes(reinterpret_cast<const std::uint8_t *>(values), len * sizeof(T)));
+        }
+
+    private:
+        void *context_;
+    };
+
+    class Node
+    {
+    public:
+        /// Initializes the node from the environment set by `dora start`.
+        Node() : Node(init_dora_context_from_env()) {}
+
+        /// Initializes a dynamic node with the given ID.
+        explicit Node(std::string_view node_id)
+            : Node(init_dora_context_from_node_id(node_id.data(), node_id.size())) {}
+
+        Node(Node &&other) noexcept : context_(std::exchange(other.context_, nullptr)) {}
+        Node &operator=(Node &&other) noexcept
+        {
+            std::swap(context_, other.context_);
+            return *this;
+        }
+        Node(const Node &) = delete;
+        Node &operator=(const Node &) = delete;
+        ~Node()
+        {
+            if (context_ != nullptr)
+            {
+                free_dora_context(context_);
+            }
+        }
+
+        /// Waits for the next event. The returned event is empty once all inputs are closed.
+        Event next() { return Event(dora_next_event(context_)); }
+
+        /// Like `next`, but returns an `Error` event after the timeout.
+        Event next(std::chrono::milliseconds timeout)
+        {
+            return Event(dora_next_event_timeout(context_, static_cast<std::uint64_t>(timeout.count())));
+        }
+
+        OutputSender sender() const noexcept { return OutputSender(context_); }
+
+        void send_output(std::string_view output_id, Bytes data) const { sender().send(output_id, data); }
+
+        template <typename T>
+        void send_output(std::string_view output_id, const T *values, std::size_t len) const
+        {
+            sender().send(output_id, values, len);
+        }
+
+    private:
+        explicit Node(void *context) : context_(context)
+        {
+            if (context_ == nullptr)
+            {
+                throw Error("failed to initialize dora node");
+            }
+        }
+
+        void *context_;
+    };
+
+} // namespace dora
@@ -1,4 +1,4 @@
-use std::{any::Any, vec};
+use std::any::Any;
 
 use dora_node_api::{
     self,
@@ -61,6 +61,8 @@ mod ffi {
         fn next_event(events: &mut Box<Events>) -> Box<DoraEvent>;
         fn event_type(event: &Box<DoraEvent>) -> DoraEventType;
         fn event_as_input(event: Box<DoraEvent>) -> Result<DoraInput>;
+        fn event_input_id(event: &Box<DoraEvent>) -> Result<String>;
+        fn event_input_data(event: &Box<DoraEvent>) -> Result<&[u8]>;
         fn send_output(
             output_sender: &mut Box<OutputSender>,
             id: String,
@@ -131,6 +133,7 @@ fn event_type(event: &DoraEvent) -> ffi::DoraEventType {
             Event::Input { .. } => ffi::DoraEventType::Input,
             Event::InputClosed { .. } => ffi::DoraEventType::InputClosed,
             Event::Error(_) => ffi::DoraEventType::Error,
+            Event::AllInputsClosed => ffi::DoraEventType::AllInputsClosed,
             _ => ffi::DoraEventType::Unknown,
         },
         None => ffi::DoraEventType::AllInputsClosed,
@@ -138,27 +141,42 @@ fn event_type(event: &DoraEvent) -> ffi::DoraEventType {
 }
 
 fn event_as_input(event: Box<DoraEvent>) -> eyre::Result<ffi::DoraInput> {
-    let Some(Event::Input { id, metadata, data }) = event.0 else {
+    let data = event_input_data(&event)?.to_vec();
+    let Some(Event::Input { id, .. }) = event.0 else {
         bail!("not an input event");
     };
-    let data = match metadata.type_info.data_type {
+
+    Ok(ffi::DoraInput {
+        id: id.into(),
+        data,
+    })
+}
+
+fn event_input_id(event: &DoraEvent) -> eyre::Result<String> {
+    let Some(Event::Input { id, .. }) = &event.0 else {
+        bail!("not an input event");
+    };
+    Ok(id.to_string())
+}
+
+/// Returns the data of an input event without copying it.
+///
+/// The data is only valid as long as the event is.
+fn event_input_data(event: &DoraEvent) -> eyre::Result<&[u8]> {
+    let Some(Event::Input { metadata, data, .. }) = &event.0 else {
+        bail!("not an input event");
+    };
+    match metadata.type_info.data_type {
         dora_node_api::arrow::datatypes::DataType::UInt8 => {
             let array: &UInt8Array = data.as_primitive();
-            array.values().to_vec()
-        }
-        dora_node_api::arrow::datatypes::DataType::Null => {
-            vec![]
+            Ok(array.values())
         }
+        dora_node_api::arrow::datatypes::DataType::Null => Ok(&[]),
         _ => {
             todo!("dora C++ Node does not yet support higher level type of arrow. Only UInt8. 
                 The ultimate solution should be based on arrow FFI interface. Feel free to contribute :)")
         }
-    };
-
-    Ok(ffi::DoraInput {
-        id: id.into(),
-        data,
-    })
+    }
 }
 
 pub struct OutputSender(dora_node_api::DoraNode);
@@ -1,4 +1,43 @@
+use std::path::{Path, PathBuf};
+
 fn main() {
     let _ = cxx_build::bridge("src/lib.rs");
     println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=dora-operator.hpp");
+
+    // collect the headers for C++ operators in a single directory
+    let header_dir = target_dir().join("cxxbridge").join("dora-operator-api-cxx");
+    let bridge_dir = header_dir.join("src");
+    std::fs::copy(
+        bridge_dir.join("lib.rs.h"),
+        header_dir.join("dora-operator-api.h"),
+    )
+    .unwrap();
+    std::fs::copy(
+        bridge_dir.join("lib.rs.cc"),
+        header_dir.join("dora-operator-api.cc"),
+    )
+    .unwrap();
+
+    // headers of the RAII wrapper, which is based on the C API
+    std::fs::copy("dora-operator.hpp", header_dir.join("dora-operator.hpp")).unwrap();
+    std::fs::write(
+        header_dir.join("operator_api.h"),
+        dora_operator_api_c::HEADER_OPERATOR_API,
+    )
+    .unwrap();
+    dora_operator_api_types::generate_headers(&header_dir.join("operator_types.h"))
+        .expect("failed to create operator_types.h");
+}
+
+fn target_dir() -> PathBuf {
+    std::env::var("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .ancestors()
+                .nth(3)
+                .unwrap();
+            root.join("target")
+        })
 }
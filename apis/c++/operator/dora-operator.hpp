@@ -0,0 +1,239 @@
+// C++ wrapper around the C operator API of dora.
+//
+// Implement an operator by deriving from `dora::Operator` and registering the
+// class through `DORA_REGISTER_OPERATOR`:
+//
+//     class MyOperator : public dora::Operator
+//     {
+//     public:
+//         dora::Status on_input(const dora::Input &input, dora::OutputSender &output) override
+//         {
+//             output.send("out", input.data(), input.size());
+//             return dora::Status::Continue;
+//         }
+//     };
+//
+//     DORA_REGISTER_OPERATOR(MyOperator)
+//
+// Exceptions thrown by the operator methods are reported as operator errors.
+// Requires C++17.
+
+#pragma once
+
+#include "operator_api.h"
+
+#include <cstddef>
+#include <cstdint>
+#include <exception>
+#include <stdexcept>
+#include <string>
+#include <string_view>
+#include <vector>
+
+namespace dora
+{
+    enum class Status : uint8_t
+    {
+        Continue = DORA_STATUS_CONTINUE,
+        Stop = DORA_STATUS_STOP,
+        StopAll = DORA_STATUS_STOP_ALL,
+    };
+
+    /// An input received by the operator. Frees its ID on destruction.
+    ///
+    /// The data is not copied, it points into the received message. So it is
+    /// only valid while the event is handled and must be copied to keep it.
+    class Input
+    {
+    public:
+        explicit Input(Input_t *raw) : id_(dora_read_input_id(raw)), data_(dora_read_data_ref(raw)) {}
+        ~Input()
+        {
+            dora_free_input_id(id_);
+        }
+        Input(const Input &) = delete;
+        Input &operator=(const Input &) = delete;
+
+        std::string_view id() const { return id_; }
+        /// Returns `nullptr` if the input data is not a byte array.
+        const uint8_t *data() const { return data_.len > 0 ? data_.ptr : nullptr; }
+        size_t size() const { return data_.len; }
+
+    private:
+        char *id_;
+        slice_ref_uint8_t data_;
+    };
+
+    namespace detail
+    {
+        inline std::string take_error(DoraResult_t result)
+        {
+            std::string message(reinterpret_cast<const char *>(result.error->ptr), result.error->len);
+            dora_free_result(result);
+            return message;
+        }
+
+        /// Runs the given function and converts thrown exceptions to an error result.
+        template <typename F>
+        DoraResult_t guarded(F &&f)
+        {
+            try
+            {
+                f();
+                return DoraResult_t{nullptr};
+            }
+            catch (const std::exception &e)
+            {
+                return dora_result_from_error(e.what());
+            }
+            catch (...)
+            {
+                return dora_result_from_error("operator threw an unknown exception");
+            }
+        }
+    }
+
+    /// Sends outputs of the operator. Only valid during the current `on_input` call.
+    class OutputSender
+    {
+    public:
+        explicit OutputSender(const SendOutput_t *raw) : raw_(raw) {}
+
+        /// Sends the given bytes. Throws `std::runtime_error` on failure.
+        void send(const std::string &id, const uint8_t *data, size_t len)
+        {
+            DoraResult_t result = dora_send_operator_output(raw_, id.c_str(), data, len);
+            if (result.error != nullptr)
+            {
+                throw std::runtime_error(detail::take_error(result));
+            }
+        }
+
+        void send(const std::string &id, const std::vector<uint8_t> &data)
+        {
+            send(id, data.data(), data.size());
+        }
+
+    private:
+        const SendOutput_t *raw_;
+    };
+
+    class Operator
+    {
+    public:
+        virtual ~Operator() = default;
+
+        /// Receives the `_unstable_parameters` of the operator as a JSON object.
+        virtual void on_configure(std::string_view config) { (void)config; }
+        /// Called once before the first input.
+        virtual void on_start() {}
+        virtual Status on_input(const Input &input, OutputSender &output) = 0;
+        virtual Status on_input_closed(std::string_view id)
+        {
+            (void)id;
+            return Status::Continue;
+        }
+        /// Called when one of the `_unstable_timers` of the operator fires.
+        virtual Status on_timer(std::string_view id, OutputSender &output)
+        {
+            (void)id;
+            (void)output;
+            return Status::Continue;
+        }
+        /// Called once when the operator stops gracefully.
+        virtual void on_stop() {}
+    };
+
+    namespace detail
+    {
+        template <typename T>
+        DoraInitResult_t init()
+        {
+            DoraInitResult_t result{};
+            result.result = guarded([&]
+                                    { result.operator_context = static_cast<Operator *>(new T()); });
+            return result;
+        }
+
+        inline DoraResult_t drop(void *operator_context)
+        {
+            return guarded([&]
+                           { delete static_cast<Operator *>(operator_context); });
+        }
+
+        inline OnEventResult_t on_event(RawEvent_t *event, const SendOutput_t *send_output, void *operator_context)
+        {
+            auto *op = static_cast<Operator *>(operator_context);
+            OnEventResult_t result{};
+            result.status = DORA_STATUS_CONTINUE;
+            result.result = guarded([&]
+                                    {
+                Status status = Status::Continue;
+                if (event->input != nullptr)
+                {
+                    Input input(event->input);
+                    OutputSender output(send_output);
+                    status = op->on_input(input, output);
+                }
+                else if (event->timer.ptr != nullptr)
+                {
+                    std::string_view id(reinterpret_cast<const char *>(event->timer.ptr), event->timer.len);
+                    OutputSender output(send_output);
+                    status = op->on_timer(id, output);
+                }
+                else if (event->input_closed.ptr != nullptr)
+                {
+                    std::string_view id(reinterpret_cast<const char *>(event->input_closed.ptr), event->input_closed.len);
+                    status = op->on_input_closed(id);
+                }
+                result.status = static_cast<DoraStatus_t>(status); });
+            return result;
+        }
+
+        inline DoraResult_t on_configure(const char *config, void *operator_context)
+        {
+            return guarded([&]
+                           { static_cast<Operator *>(operator_context)->on_configure(config); });
+        }
+
+        inline DoraResult_t on_start(void *operator_context)
+        {
+            return guarded([&]
+                           { static_cast<Operator *>(operator_context)->on_start(); });
+        }
+
+        inline DoraResult_t on_stop(void *operator_context)
+        {
+            return guarded([&]
+                           { static_cast<Operator *>(operator_context)->on_stop(); });
+        }
+    }
+}
+
+/// Exports the C functions that the dora runtime uses to run the given operator class.
+#define DORA_REGISTER_OPERATOR(OperatorType)                                                    \
+    extern "C" DoraInitResult_t dora_init_operator(void)                                        \
+    {                                                                                           \
+        return ::dora::detail::init<OperatorType>();                                            \
+    }                                                                                           \
+    extern "C" DoraResult_t dora_drop_operator(void *operator_context)                          \
+    {                                                                                           \
+        return ::dora::detail::drop(operator_context);                                          \
+    }                                                                                           \
+    extern "C" OnEventResult_t dora_on_event(                                                   \
+        RawEvent_t *event, const SendOutput_t *send_output, void *operator_context)             \
+    {                                                                                           \
+        return ::dora::detail::on_event(event, send_output, operator_context);                  \
+    }                                                                                           \
+    extern "C" DoraResult_t dora_on_configure(char const *config, void *operator_context)       \
+    {                                                                                           \
+        return ::dora::detail::on_configure(config, operator_context);                          \
+    }                                                                                           \
+    extern "C" DoraResult_t dora_on_start(void *operator_context)                               \
+    {                                                                                           \
+        return ::dora::detail::on_start(operator_context);                                      \
+    }                                                                                           \
+    extern "C" DoraResult_t dora_on_stop(void *operator_context)                                \
+    {                                                                                           \
+        return ::dora::detail::on_stop(operator_context);                                       \
+    }
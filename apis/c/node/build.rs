@@ -0,0 +1,183 @@
+//! Generates the `node_api.h` header from the `extern "C"` functions and
+//! `#[repr(C)]` enums of `src/lib.rs`, including their doc comments.
+
+use std::fmt::Write;
+
+const SOURCE: &str = "src/lib.rs";
+const HEADER: &str = "node_api.h";
+
+fn main() {
+    let source = std::fs::read_to_string(SOURCE).expect("failed to read src/lib.rs");
+    let file = syn::parse_file(&source).expect("failed to parse src/lib.rs");
+    let header = generate_header(&file);
+
+    // only write on changes, so that the header's modification time stays
+    // the same for C builds
+    if std::fs::read_to_string(HEADER).ok().as_deref() != Some(header.as_str()) {
+        std::fs::write(HEADER, header).expect("failed to write node_api.h");
+    }
+    println!("cargo:rerun-if-changed={SOURCE}");
+    println!("cargo:rerun-if-changed=build.rs");
+}
+
+fn generate_header(file: &syn::File) -> String {
+    let mut enums = String::new();
+    let mut functions = String::new();
+    for item in &file.items {
+        match item {
+            syn::Item::Enum(item) if is_public(&item.vis) && has_attr(&item.attrs, "repr") => {
+                write_enum(&mut enums, item);
+            }
+            syn::Item::Fn(item)
+                if is_public(&item.vis)
+                    && has_attr(&item.attrs, "no_mangle")
+                    && item.sig.abi.is_some() =>
+            {
+                write_function(&mut functions, item);
+            }
+            _ => {}
+        }
+    }
+
+    format!(
+        "/* Generated from `src/lib.rs` by `build.rs`, don't edit manually. */
+
+#ifndef DORA_NODE_API_H
+#define DORA_NODE_API_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern \"C\"
+{{
+#endif
+
+{enums}{functions}#ifdef __cplusplus
+}}
+#endif
+
+#endif /* DORA_NODE_API_H */
+"
+    )
+}
+
+/// Enums are prefixed with `Dora` in C, and so are their variants as C
+/// enums don't have their own namespace.
+fn write_enum(out: &mut String, item: &syn::ItemEnum) {
+    let name = format!("Dora{}", item.ident);
+    write_docs(out, &item.attrs);
+    writeln!(out, "    enum {name}\n    {{").unwrap();
+    for variant in &item.variants {
+        writeln!(out, "        {name}_{},", variant.ident).unwrap();
+    }
+    writeln!(out, "    }};\n").unwrap();
+}
+
+fn write_function(out: &mut String, item: &syn::ItemFn) {
+    write_docs(out, &item.attrs);
+    let return_type = match &item.sig.output {
+        syn::ReturnType::Default => "void ".to_owned(),
+        syn::ReturnType::Type(_, ty) => with_name(&c_type(ty), ""),
+    };
+    let args: Vec<_> = item
+        .sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            syn::FnArg::Typed(arg) => {
+                let syn::Pat::Ident(name) = &*arg.pat else {
+                    panic!("unsupported argument pattern in `{}`", item.sig.ident);
+                };
+                with_name(&c_type(&arg.ty), &name.ident.to_string())
+            }
+            syn::FnArg::Receiver(_) => panic!("unexpected receiver in `{}`", item.sig.ident),
+        })
+        .collect();
+    let args = if args.is_empty() {
+        "void".to_owned()
+    } else {
+        args.join(", ")
+    };
+    writeln!(out, "    {return_type}{}({args});\n", item.sig.ident).unwrap();
+}
+
+/// Writes the doc comments of an item as a C comment.
+fn write_docs(out: &mut String, attrs: &[syn::Attribute]) {
+    let lines: Vec<_> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(doc),
+                ..
+            })) => Some(doc.value()),
+            _ => None,
+        })
+        .collect();
+    if lines.is_empty() {
+        return;
+    }
+    writeln!(out, "    /**").unwrap();
+    for line in lines {
+        // intra-doc links like [`free_dora_event`] have no meaning in C
+        let line = line.replace("[`", "`").replace("`]", "`");
+        let line = line.strip_prefix(' ').unwrap_or(&line).trim_end();
+        if line.is_empty() {
+            writeln!(out, "     *").unwrap();
+        } else {
+            writeln!(out, "     * {line}").unwrap();
+        }
+    }
+    writeln!(out, "     */").unwrap();
+}
+
+/// Joins a C type and a name, e.g. `char *` and `ptr` to `char *ptr`.
+fn with_name(ty: &str, name: &str) -> String {
+    if ty.ends_with('*') {
+        format!("{ty}{name}")
+    } else {
+        format!("{ty} {name}")
+    }
+}
+
+/// Maps a Rust type of the FFI functions to the corresponding C type.
+///
+/// Bytes are passed as `char` pointers, as usual for strings in C.
+fn c_type(ty: &syn::Type) -> String {
+    match ty {
+        syn::Type::Ptr(ptr) => {
+            let pointee = c_type(&ptr.elem);
+            let pointee = if ptr.const_token.is_some() && !pointee.ends_with('*') {
+                format!("const {pointee}")
+            } else {
+                pointee
+            };
+            with_name(&pointee, "*")
+        }
+        syn::Type::Tuple(tuple) if tuple.elems.is_empty() => "void".to_owned(),
+        syn::Type::Path(path) => {
+            let ident = &path.path.segments.last().expect("empty type path").ident;
+            match ident.to_string().as_str() {
+                "c_void" => "void",
+                "c_char" | "u8" => "char",
+                "c_int" => "int",
+                "c_ulonglong" => "unsigned long long",
+                "u64" => "uint64_t",
+                "usize" => "size_t",
+                "EventType" => "enum DoraEventType",
+                other => panic!("unsupported type `{other}` in C API"),
+            }
+            .to_owned()
+        }
+        _ => panic!("unsupported type in C API"),
+    }
+}
+
+fn is_public(vis: &syn::Visibility) -> bool {
+    matches!(vis, syn::Visibility::Public(_))
+}
+
+fn has_attr(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path.is_ident(name))
+}
@@ -1,22 +1,216 @@
-#include <stddef.h>
+/* Generated from `src/lib.rs` by `build.rs`, don't edit manually. */
 
-void *init_dora_context_from_env();
-void free_dora_context(void *dora_context);
+#ifndef DORA_NODE_API_H
+#define DORA_NODE_API_H
 
-void *dora_next_event(void *dora_context);
-void free_dora_event(void *dora_event);
+#include <stddef.h>
+#include <stdint.h>
 
-enum DoraEventType
+#ifdef __cplusplus
+extern "C"
 {
-    DoraEventType_Stop,
-    DoraEventType_Input,
-    DoraEventType_InputClosed,
-    DoraEventType_Error,
-    DoraEventType_Unknown,
-};
-enum DoraEventType read_dora_event_type(void *dora_event);
-
-void read_dora_input_id(void *dora_event, char **out_ptr, size_t *out_len);
-void read_dora_input_data(void *dora_event, char **out_ptr, size_t *out_len);
-unsigned long long read_dora_input_timestamp(void *dora_event);
-int dora_send_output(void *dora_context, char *id_ptr, size_t id_len, char *data_ptr, size_t data_len);
+#endif
+
+    /**
+     * Type of an event, see `read_dora_event_type`.
+     */
+    enum DoraEventType
+    {
+        DoraEventType_Stop,
+        DoraEventType_Input,
+        DoraEventType_InputClosed,
+        DoraEventType_Error,
+        DoraEventType_Unknown,
+        DoraEventType_AllInputsClosed,
+    };
+
+    /**
+     * Returns the version of the dora node API as a NUL-terminated string.
+     *
+     * Nodes can compare it against the version that they were built for to
+     * detect mismatching libraries.
+     */
+    const char *dora_node_api_version(void);
+
+    /**
+     * Initializes a dora context from the environment variables that were set by
+     * the dora-coordinator.
+     *
+     * Returns a pointer to the dora context on success. This pointer can be
+     * used to call dora API functions that expect a `context` argument. Any
+     * other use is prohibited. To free the dora context when it is no longer
+     * needed, use the `free_dora_context` function.
+     *
+     * On error, a null pointer is returned.
+     */
+    void *init_dora_context_from_env(void);
+
+    /**
+     * Initializes a dora context for the node with the given ID.
+     *
+     * This function is meant for nodes that are started manually instead of
+     * through `dora start`, e.g. dynamic nodes. If the environment variables of
+     * `dora start` are set, they take precedence over the given ID.
+     *
+     * Returns a null pointer on error, like `init_dora_context_from_env`.
+     *
+     * ## Safety
+     *
+     * The `id_ptr` and `id_len` fields must be the start pointer and length of an
+     * UTF8-encoded string.
+     */
+    void *init_dora_context_from_node_id(const char *id_ptr, size_t id_len);
+
+    /**
+     * Frees the given dora context.
+     *
+     * ## Safety
+     *
+     * Only pointers created through `init_dora_context_from_env` are allowed
+     * as arguments. Each context pointer must be freed exactly once. After
+     * freeing, the pointer must not be used anymore.
+     */
+    void free_dora_context(void *context);
+
+    /**
+     * Waits for the next incoming event for the node.
+     *
+     * Returns a pointer to the event on success. This pointer must not be used
+     * directly. Instead, use the `read_dora_event_*` functions to read out the
+     * type and payload of the event. When the event is not needed anymore, use
+     * `free_dora_event` to free it again.
+     *
+     * Returns a null pointer when all event streams were closed. This means that
+     * no more event will be available. Nodes typically react by stopping.
+     *
+     * ## Safety
+     *
+     * The `context` argument must be a dora context created through
+     * `init_dora_context_from_env`. The context must be still valid, i.e., not
+     * freed yet.
+     */
+    void *dora_next_event(void *context);
+
+    /**
+     * Waits for the next incoming event, but at most `timeout_ms` milliseconds.
+     *
+     * Behaves like `dora_next_event`, except that an `Error` event is returned
+     * when the timeout expires.
+     *
+     * ## Safety
+     *
+     * See `dora_next_event`.
+     */
+    void *dora_next_event_timeout(void *context, uint64_t timeout_ms);
+
+    /**
+     * Reads out the type of the given event.
+     *
+     * ## Safety
+     *
+     * The `event` argument must be a dora event received through
+     * `dora_next_event`. The event must be still valid, i.e., not
+     * freed yet.
+     */
+    enum DoraEventType read_dora_event_type(const void *event);
+
+    /**
+     * Reads out the ID of the given input or input closed event.
+     *
+     * Writes the `out_ptr` and `out_len` with the start pointer and length of the
+     * ID string of the input. The ID is guaranteed to be valid UTF-8.
+     *
+     * Writes a null pointer and length `0` for all other event types.
+     *
+     * ## Safety
+     *
+     * The `event` argument must be a dora event received through
+     * `dora_next_event`. The event must be still valid, i.e., not
+     * freed yet. The returned `out_ptr` must not be used after
+     * freeing the `event`, since it points directly into the event's
+     * memory.
+     */
+    void read_dora_input_id(const void *event, char **out_ptr, size_t *out_len);
+
+    /**
+     * Reads out the message of the given error event.
+     *
+     * Writes the `out_ptr` and `out_len` with the start pointer and length of the
+     * UTF-8 encoded error message, or a null pointer and length `0` if the event
+     * is not an error event.
+     *
+     * ## Safety
+     *
+     * Same as for `read_dora_input_id`.
+     */
+    void read_dora_event_error(const void *event, char **out_ptr, size_t *out_len);
+
+    /**
+     * Reads out the data of the given input event.
+     *
+     * Writes the `out_ptr` and `out_len` with the start pointer and length in
+     * bytes of the input's data array. The data array is a raw byte array, whose
+     * format depends on the source operator/node. Arrays of fixed-width
+     * primitive types, e.g. integers or floats, are passed through as their raw
+     * values in native byte order.
+     *
+     * Writes a null pointer and length `0` if the given event is not an input event,
+     * when an input event has no associated data, or when the data is not a
+     * primitive array without null values.
+     *
+     * ## Safety
+     *
+     * The `event` argument must be a dora event received through
+     * `dora_next_event`. The event must be still valid, i.e., not
+     * freed yet. The returned `out_ptr` must not be used after
+     * freeing the `event`, since it points directly into the event's
+     * memory.
+     */
+    void read_dora_input_data(const void *event, char **out_ptr, size_t *out_len);
+
+    /**
+     * Reads out the timestamp of the given input event from metadata.
+     *
+     * ## Safety
+     *
+     * Return `0` if the given event is not an input event.
+     */
+    unsigned long long read_dora_input_timestamp(const void *event);
+
+    /**
+     * Frees the given dora event.
+     *
+     * ## Safety
+     *
+     * Only pointers created through `dora_next_event` are allowed
+     * as arguments. Each context pointer must be freed exactly once. After
+     * freeing, the pointer and all derived pointers must not be used anymore.
+     * This also applies to the `read_dora_event_*` functions, which return
+     * pointers into the original event structure.
+     */
+    void free_dora_event(void *event);
+
+    /**
+     * Sends the given output to subscribed dora nodes/operators.
+     *
+     * The `id_ptr` and `id_len` fields must be the start pointer and length of an
+     * UTF8-encoded string. The ID string must correspond to one of the node's
+     * outputs specified in the dataflow YAML file.
+     *
+     * The `data_ptr` and `data_len` fields must be the start pointer and length
+     * a byte array. The dora API sends this data as-is, without any processing.
+     *
+     * ## Safety
+     *
+     * - The `id_ptr` and `id_len` fields must be the start pointer and length of an
+     *   UTF8-encoded string.
+     * - The `data_ptr` and `data_len` fields must be the start pointer and length
+     *   a byte array.
+     */
+    int dora_send_output(void *context, const char *id_ptr, size_t id_len, const char *data_ptr, size_t data_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DORA_NODE_API_H */
@@ -1,12 +1,24 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 
-use arrow_array::UInt8Array;
-use dora_node_api::{arrow::array::AsArray, DoraNode, Event, EventStream};
+use dora_node_api::{arrow::array::Array, DoraNode, Event, EventStream};
 use eyre::Context;
-use std::{ffi::c_void, ptr, slice};
+use std::{
+    ffi::{c_char, c_int, c_void},
+    ptr, slice,
+    time::Duration,
+};
 
 pub const HEADER_NODE_API: &str = include_str!("../node_api.h");
 
+/// Returns the version of the dora node API as a NUL-terminated string.
+///
+/// Nodes can compare it against the version that they were built for to
+/// detect mismatching libraries.
+#[no_mangle]
+pub extern "C" fn dora_node_api_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
 struct DoraContext {
     node: &'static mut DoraNode,
     events: EventStream,
@@ -23,15 +35,43 @@ struct DoraContext {
 /// On error, a null pointer is returned.
 #[no_mangle]
 pub extern "C" fn init_dora_context_from_env() -> *mut c_void {
-    let context = || {
-        let (node, events) = DoraNode::init_from_env()?;
-        let node = Box::leak(Box::new(node));
-        Result::<_, eyre::Report>::Ok(DoraContext { node, events })
+    into_context(DoraNode::init_from_env())
+}
+
+/// Initializes a dora context for the node with the given ID.
+///
+/// This function is meant for nodes that are started manually instead of
+/// through `dora start`, e.g. dynamic nodes. If the environment variables of
+/// `dora start` are set, they take precedence over the given ID.
+///
+/// Returns a null pointer on error, like [`init_dora_context_from_env`].
+///
+/// ## Safety
+///
+/// The `id_ptr` and `id_len` fields must be the start pointer and length of an
+/// UTF8-encoded string.
+#[no_mangle]
+pub unsafe extern "C" fn init_dora_context_from_node_id(
+    id_ptr: *const u8,
+    id_len: usize,
+) -> *mut c_void {
+    let id = match std::str::from_utf8(unsafe { slice::from_raw_parts(id_ptr, id_len) }) {
+        Ok(id) => id,
+        Err(err) => {
+            tracing::error!("node ID is not valid UTF-8: {err}");
+            return ptr::null_mut();
+        }
     };
-    let context = match context().context("failed to initialize node") {
-        Ok(n) => n,
+    into_context(DoraNode::init_flexible(id.to_owned().into()))
+}
+
+fn into_context(init: eyre::Result<(DoraNode, EventStream)>) -> *mut c_void {
+    let context = match init.context("failed to initialize node") {
+        Ok((node, events)) => DoraContext {
+            node: Box::leak(Box::new(node)),
+            events,
+        },
         Err(err) => {
-            let err: eyre::Error = err;
             tracing::error!("{err:?}");
             return ptr::null_mut();
         }
@@ -80,6 +120,29 @@ pub unsafe extern "C" fn dora_next_event(context: *mut c_void) -> *mut c_void {
     }
 }
 
+/// Waits for the next incoming event, but at most `timeout_ms` milliseconds.
+///
+/// Behaves like [`dora_next_event`], except that an `Error` event is returned
+/// when the timeout expires.
+///
+/// ## Safety
+///
+/// See [`dora_next_event`].
+#[no_mangle]
+pub unsafe extern "C" fn dora_next_event_timeout(
+    context: *mut c_void,
+    timeout_ms: u64,
+) -> *mut c_void {
+    let context: &mut DoraContext = unsafe { &mut *context.cast() };
+    match context
+        .events
+        .recv_timeout(Duration::from_millis(timeout_ms))
+    {
+        Some(event) => Box::into_raw(Box::new(event)).cast(),
+        None => ptr::null_mut(),
+    }
+}
+
 /// Reads out the type of the given event.
 ///
 /// ## Safety
@@ -95,10 +158,12 @@ pub unsafe extern "C" fn read_dora_event_type(event: *const ()) -> EventType {
         Event::Input { .. } => EventType::Input,
         Event::InputClosed { .. } => EventType::InputClosed,
         Event::Error(_) => EventType::Error,
+        Event::AllInputsClosed => EventType::AllInputsClosed,
         _ => EventType::Unknown,
     }
 }
 
+/// Type of an event, see [`read_dora_event_type`].
 #[repr(C)]
 pub enum EventType {
     Stop,
@@ -106,14 +171,15 @@ pub enum EventType {
     InputClosed,
     Error,
     Unknown,
+    AllInputsClosed,
 }
 
-/// Reads out the ID of the given input event.
+/// Reads out the ID of the given input or input closed event.
 ///
 /// Writes the `out_ptr` and `out_len` with the start pointer and length of the
 /// ID string of the input. The ID is guaranteed to be valid UTF-8.
 ///
-/// Writes a null pointer and length `0` if the given event is not an input event.
+/// Writes a null pointer and length `0` for all other event types.
 ///
 /// ## Safety
 ///
@@ -125,35 +191,66 @@ pub enum EventType {
 #[no_mangle]
 pub unsafe extern "C" fn read_dora_input_id(
     event: *const (),
-    out_ptr: *mut *const u8,
+    out_ptr: *mut *mut u8,
     out_len: *mut usize,
 ) {
     let event: &Event = unsafe { &*event.cast() };
-    match event {
-        Event::Input { id, .. } => {
-            let id = id.as_str().as_bytes();
-            let ptr = id.as_ptr();
-            let len = id.len();
-            unsafe {
-                *out_ptr = ptr;
-                *out_len = len;
-            }
-        }
-        _ => unsafe {
-            *out_ptr = ptr::null();
-            *out_len = 0;
-        },
+    let id = match event {
+        Event::Input { id, .. } | Event::InputClosed { id } => Some(id.as_str().as_bytes()),
+        _ => None,
+    };
+    unsafe { write_bytes(id, out_ptr, out_len) }
+}
+
+/// Reads out the message of the given error event.
+///
+/// Writes the `out_ptr` and `out_len` with the start pointer and length of the
+/// UTF-8 encoded error message, or a null pointer and length `0` if the event
+/// is not an error event.
+///
+/// ## Safety
+///
+/// Same as for [`read_dora_input_id`].
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_event_error(
+    event: *const (),
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) {
+    let event: &Event = unsafe { &*event.cast() };
+    let message = match event {
+        Event::Error(message) => Some(message.as_bytes()),
+        _ => None,
+    };
+    unsafe { write_bytes(message, out_ptr, out_len) }
+}
+
+/// Writes a null pointer and length `0` for `None`.
+///
+/// The written pointer is mutable for compatibility with C code that reads
+/// into `char *` variables, but the bytes must not be modified.
+unsafe fn write_bytes(bytes: Option<&[u8]>, out_ptr: *mut *mut u8, out_len: *mut usize) {
+    let (ptr, len) = match bytes {
+        Some(bytes) => (bytes.as_ptr().cast_mut(), bytes.len()),
+        None => (ptr::null_mut(), 0),
+    };
+    unsafe {
+        *out_ptr = ptr;
+        *out_len = len;
     }
 }
 
 /// Reads out the data of the given input event.
 ///
-/// Writes the `out_ptr` and `out_len` with the start pointer and length of the
-/// input's data array. The data array is a raw byte array, whose format
-/// depends on the source operator/node.
+/// Writes the `out_ptr` and `out_len` with the start pointer and length in
+/// bytes of the input's data array. The data array is a raw byte array, whose
+/// format depends on the source operator/node. Arrays of fixed-width
+/// primitive types, e.g. integers or floats, are passed through as their raw
+/// values in native byte order.
 ///
-/// Writes a null pointer and length `0` if the given event is not an input event
-/// or when an input event has no associated data.
+/// Writes a null pointer and length `0` if the given event is not an input event,
+/// when an input event has no associated data, or when the data is not a
+/// primitive array without null values.
 ///
 /// ## Safety
 ///
@@ -165,34 +262,38 @@ pub unsafe extern "C" fn read_dora_input_id(
 #[no_mangle]
 pub unsafe extern "C" fn read_dora_input_data(
     event: *const (),
-    out_ptr: *mut *const u8,
+    out_ptr: *mut *mut u8,
     out_len: *mut usize,
 ) {
     let event: &Event = unsafe { &*event.cast() };
-    match event {
-        Event::Input { data, metadata, .. } => match metadata.type_info.data_type {
-            dora_node_api::arrow::datatypes::DataType::UInt8 => {
-                let array: &UInt8Array = data.as_primitive();
-                let ptr = array.values().as_ptr();
-                unsafe {
-                    *out_ptr = ptr;
-                    *out_len = metadata.type_info.len;
+    let bytes = match event {
+        Event::Input { id, data, .. } => {
+            let array = data.to_data();
+            match array.data_type().primitive_width() {
+                Some(width) if array.null_count() == 0 && !array.buffers().is_empty() => {
+                    // the buffers are reference-counted, so they stay valid as
+                    // long as the event
+                    let values = &array.buffers()[0].as_slice()[array.offset() * width..];
+                    let values = &values[..array.len() * width];
+                    Some(unsafe { slice::from_raw_parts(values.as_ptr(), values.len()) })
+                }
+                _ => {
+                    if !matches!(
+                        array.data_type(),
+                        dora_node_api::arrow::datatypes::DataType::Null
+                    ) {
+                        tracing::warn!(
+                            "input `{id}` of type {} cannot be read as raw data",
+                            array.data_type()
+                        );
+                    }
+                    None
                 }
             }
-            dora_node_api::arrow::datatypes::DataType::Null => unsafe {
-                *out_ptr = ptr::null();
-                *out_len = 0;
-            },
-            _ => {
-                todo!("dora C++ Node does not yet support higher level type of arrow. Only UInt8. 
-                The ultimate solution should be based on arrow FFI interface. Feel free to contribute :)")
-            }
-        },
-        _ => unsafe {
-            *out_ptr = ptr::null();
-            *out_len = 0;
-        },
-    }
+        }
+        _ => None,
+    };
+    unsafe { write_bytes(bytes, out_ptr, out_len) }
 }
 
 /// Reads out the timestamp of the given input event from metadata.
@@ -245,7 +346,7 @@ pub unsafe extern "C" fn dora_send_output(
     id_len: usize,
     data_ptr: *const u8,
     data_len: usize,
-) -> isize {
+) -> c_int {
     match unsafe { try_send_output(context, id_ptr, id_len, data_ptr, data_len) } {
         Ok(()) => 0,
         Err(err) => {
@@ -14,8 +14,27 @@ extern "C"
 #define EXPORT __attribute__((visibility("default")))
 #endif
 
+#ifdef _WIN32
+#define DORA_WEAK __declspec(selectany)
+#else
+#define DORA_WEAK __attribute__((weak))
+#endif
+
+/* Version of the operator ABI that these headers describe. */
+#define DORA_OPERATOR_ABI_VERSION 1
+
+    /* Lets the runtime check that the operator was built against compatible
+     * headers before calling any of the functions below. The definition is
+     * weak, so this header can be included in multiple source files. */
+    EXPORT DORA_WEAK const DoraOperatorAbi_t dora_operator_abi = {DORA_OPERATOR_ABI_VERSION, {0}};
+
     EXPORT DoraInitResult_t dora_init_operator(void);
 
+    /* Optional alternative to `dora_init_operator`. If it's defined, the
+     * runtime calls it instead, with the `config` of the operator in the
+     * dataflow descriptor as a JSON object. */
+    EXPORT DoraInitResult_t dora_init_operator_with_config(char const *config);
+
     EXPORT DoraResult_t dora_drop_operator(void *operator_context);
 
     EXPORT OnEventResult_t dora_on_event(
@@ -23,6 +42,22 @@ extern "C"
         const SendOutput_t *send_output,
         void *operator_context);
 
+    /* Optional lifecycle hooks. The runtime calls them only if they are defined.
+     *
+     * `dora_on_configure` receives the operator parameters as a JSON object and
+     * is called before `dora_on_start`, which is called before the first event.
+     * `dora_on_stop` is called when the operator stops gracefully. */
+    EXPORT DoraResult_t dora_on_configure(char const *config, void *operator_context);
+
+    EXPORT DoraResult_t dora_on_start(void *operator_context);
+
+    EXPORT DoraResult_t dora_on_stop(void *operator_context);
+
+    /* Optional hook that receives the callback for recording metrics, before
+     * `dora_on_configure` is called. The pointer stays valid until the
+     * operator is dropped, so it can be stored to call `dora_record_metric`. */
+    EXPORT DoraResult_t dora_on_metrics(RecordMetric_t const *record_metric, void *operator_context);
+
     static void __dora_type_assertions()
     {
         DoraInitOperator_t __dora_init_operator = {.init_operator = dora_init_operator};
@@ -58,6 +58,25 @@ typedef struct DoraInitOperator {
     DoraInitResult_t (*init_operator)(void);
 } DoraInitOperator_t;
 
+/** \brief
+ *  Optional alternative to `init_operator` that receives the `config` of the
+ *  operator in the dataflow descriptor as a JSON object. It's used instead of
+ *  `init_operator` if it's defined.
+ */
+typedef struct DoraInitOperatorWithConfig {
+    /** <No documentation available> */
+    DoraInitResult_t (*init_operator_with_config)(char const *);
+} DoraInitOperatorWithConfig_t;
+
+/** \brief
+ *  Optional hook that receives the operator parameters of the dataflow
+ *  descriptor as a JSON object. It's called before `on_start`.
+ */
+typedef struct DoraOnConfigure {
+    /** <No documentation available> */
+    DoraResult_t (*on_configure)(char const *, void *);
+} DoraOnConfigure_t;
+
 /** <No documentation available> */
 /** \remark Has the same ABI as `uint8_t` **/
 #ifdef DOXYGEN
@@ -104,6 +123,12 @@ typedef struct RawEvent {
 
     /** <No documentation available> */
     Vec_uint8_t error;
+
+    /** \brief
+     *  ID of the timer that triggered this event, as configured in the
+     *  `_unstable_timers` field of the dataflow.
+     */
+    Vec_uint8_t timer;
 } RawEvent_t;
 
 /** <No documentation available> */
@@ -138,6 +163,110 @@ typedef struct DoraOnEvent {
     OnEventResult_t (*on_event)(RawEvent_t *, SendOutput_t const *, void *);
 } DoraOnEvent_t;
 
+/** <No documentation available> */
+/** \remark Has the same ABI as `uint8_t` **/
+#ifdef DOXYGEN
+typedef
+#endif
+enum MetricKind {
+    /** \brief
+     *  Adds the value to a monotonic sum, e.g. the number of detections.
+     */
+    METRIC_KIND_COUNTER = 0,
+    /** \brief
+     *  Replaces the current value, e.g. a queue length.
+     */
+    METRIC_KIND_GAUGE = 1,
+    /** \brief
+     *  Records the value in a distribution, e.g. a planning time.
+     */
+    METRIC_KIND_HISTOGRAM = 2,
+}
+#ifndef DOXYGEN
+; typedef uint8_t
+#endif
+MetricKind_t;
+
+/** <No documentation available> */
+typedef struct MetricUpdate {
+    /** <No documentation available> */
+    Vec_uint8_t name;
+
+    /** <No documentation available> */
+    MetricKind_t kind;
+
+    /** <No documentation available> */
+    double value;
+} MetricUpdate_t;
+
+/** \brief
+ *  `Arc<dyn Send + Sync + Fn(A1) -> Ret>`
+ */
+typedef struct ArcDynFn1_DoraResult_MetricUpdate {
+    /** <No documentation available> */
+    void * env_ptr;
+
+    /** <No documentation available> */
+    DoraResult_t (*call)(void *, MetricUpdate_t);
+
+    /** <No documentation available> */
+    void (*release)(void *);
+
+    /** <No documentation available> */
+    void (*retain)(void *);
+} ArcDynFn1_DoraResult_MetricUpdate_t;
+
+/** <No documentation available> */
+typedef struct RecordMetric {
+    /** <No documentation available> */
+    ArcDynFn1_DoraResult_MetricUpdate_t record_metric;
+} RecordMetric_t;
+
+/** \brief
+ *  Optional hook that receives the callback for recording operator metrics.
+ *  It's called before `on_configure`. Operators may keep a clone of the
+ *  callback for the rest of their lifetime.
+ */
+typedef struct DoraOnMetrics {
+    /** <No documentation available> */
+    DoraResult_t (*on_metrics)(RecordMetric_t const *, void *);
+} DoraOnMetrics_t;
+
+/** \brief
+ *  Optional hook that is called once before the first event.
+ */
+typedef struct DoraOnStart {
+    /** <No documentation available> */
+    DoraResult_t (*on_start)(void *);
+} DoraOnStart_t;
+
+/** \brief
+ *  Optional hook that is called once when the operator stops gracefully.
+ */
+typedef struct DoraOnStop {
+    /** <No documentation available> */
+    DoraResult_t (*on_stop)(void *);
+} DoraOnStop_t;
+
+typedef struct {
+    uint8_t idx[32];
+} uint8_32_array_t;
+
+/** \brief
+ *  Exported by operators as `dora_operator_abi`, so that the runtime can check
+ *  the ABI version before calling into the operator.
+ */
+typedef struct DoraOperatorAbi {
+    /** <No documentation available> */
+    uint32_t abi_version;
+
+    /** \brief
+     *  NUL-terminated version of the dora operator API that the operator was
+     *  built against. Empty if unknown.
+     */
+    uint8_32_array_t dora_version;
+} DoraOperatorAbi_t;
+
 /** <No documentation available> */
 typedef struct Metadata {
     /** <No documentation available> */
@@ -154,16 +283,84 @@ void
 dora_free_input_id (
     char * _input_id);
 
+/** \brief
+ *  Frees a result that is not passed back to the runtime.
+ */
+void
+dora_free_result (
+    DoraResult_t _result);
+
 /** <No documentation available> */
 Vec_uint8_t
 dora_read_data (
     Input_t * input);
 
+/** \brief
+ *  `&'lt [T]` but with a guaranteed `#[repr(C)]` layout.
+ *
+ *  # C layout (for some given type T)
+ *
+ *  ```c
+ *  typedef struct {
+ *  // Cannot be NULL
+ *  T * ptr;
+ *  size_t len;
+ *  } slice_T;
+ *  ```
+ *
+ *  # Nullable pointer?
+ *
+ *  If you want to support the above typedef, but where the `ptr` field is
+ *  allowed to be `NULL` (with the contents of `len` then being undefined)
+ *  use the `Option< slice_ptr<_> >` type.
+ */
+typedef struct slice_ref_uint8 {
+    /** \brief
+     *  Pointer to the first element (if any).
+     */
+    uint8_t const * ptr;
+
+    /** \brief
+     *  Element count
+     */
+    size_t len;
+} slice_ref_uint8_t;
+
+/** \brief
+ *  Returns the data of the input without copying it.
+ *
+ *  The returned slice points into the input and is only valid as long as the
+ *  input is. It is empty if the input has no data or if the data is not a byte
+ *  array without null values. Unlike `dora_read_data`, this function can be
+ *  called multiple times for the same input.
+ */
+slice_ref_uint8_t
+dora_read_data_ref (
+    Input_t const * input);
+
 /** <No documentation available> */
 char *
 dora_read_input_id (
     Input_t const * input);
 
+/** \brief
+ *  Records a metric value through the callback passed to `dora_on_metrics`.
+ */
+DoraResult_t
+dora_record_metric (
+    RecordMetric_t const * record_metric,
+    char const * name,
+    MetricKind_t kind,
+    double value);
+
+/** \brief
+ *  Creates a result with the given error message, e.g. to report a failure
+ *  from a lifecycle hook.
+ */
+DoraResult_t
+dora_result_from_error (
+    char const * error);
+
 /** <No documentation available> */
 DoraResult_t
 dora_send_operator_output (
@@ -12,6 +12,7 @@ from enum import Enum
 from .dora import *
 
 from .dora import (
+    AsyncNode,
     Node,
     Ros2Context,
     Ros2Node,
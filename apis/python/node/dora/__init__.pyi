@@ -1,7 +1,85 @@
 import dora
+import dora.events
 import pyarrow
 import typing
 
+@typing.final
+class AsyncNode:
+    """Variant of `Node` for `asyncio` applications.
+
+Events are received on a background thread and handed to the event loop,
+so waiting for the next event doesn't block other tasks:
+
+```python
+from dora import AsyncNode
+
+async def main():
+node = AsyncNode()
+async for event in node:
+if event["type"] == "INPUT":
+await node.send_output("output", event["value"])
+```
+
+Cancelling a pending `next()`, e.g. through `asyncio.wait_for`, drops the
+event that it would have returned."""
+
+    def __init__(self, node_id: str=None) -> None:
+        """Variant of `Node` for `asyncio` applications.
+
+Events are received on a background thread and handed to the event loop,
+so waiting for the next event doesn't block other tasks:
+
+```python
+from dora import AsyncNode
+
+async def main():
+node = AsyncNode()
+async for event in node:
+if event["type"] == "INPUT":
+await node.send_output("output", event["value"])
+```
+
+Cancelling a pending `next()`, e.g. through `asyncio.wait_for`, drops the
+event that it would have returned."""
+
+    def dataflow_descriptor(self) -> dict:
+        """Returns the full dataflow descriptor that this node is part of."""
+
+    def dataflow_id(self) -> str:
+        """Returns the dataflow id."""
+
+    def graph(self) -> dict:
+        """Returns the topology of the dataflow, see `Node.graph`."""
+
+    def next(self) -> typing.Awaitable[dict]:
+        """Waits for the next event without blocking the event loop.
+
+The returned awaitable resolves to the same dicts as `Node.next()`. It
+raises `StopAsyncIteration` when all senders have been dropped.
+
+```python
+event = await node.next()
+```"""
+
+    def send_output(self, output_id: str, data: pyarrow.Array, metadata: dict=None) -> typing.Awaitable[None]:
+        """Sends an output, see `Node.send_output` for the supported `data` types.
+
+The output is sent before this method returns, the returned awaitable
+only exists for symmetry with other `asyncio` APIs.
+
+```python
+await node.send_output("string", b"string")
+```"""
+
+    def send_output_json(self, output_id: str, obj: typing.Any, metadata: dict=None) -> typing.Awaitable[None]:
+        """Sends a JSON-serializable object, see `Node.send_output_json`."""
+
+    def __aiter__(self) -> typing.Any:
+        """Return an awaitable asynchronous iterator."""
+
+    def __anext__(self) -> typing.Any:
+        """Return a value or raise StopAsyncIteration."""
+
 @typing.final
 class Enum:
     """Generic enumeration.
@@ -9,6 +87,30 @@ class Enum:
 Derive from this class to define new enumerations."""
     __members__: mappingproxy = ...
 
+@typing.final
+class Logger:
+    """Emits structured log records of the operator, available as `self.logger`.
+
+`e.g.:  self.logger.log("warn", "no detections", {"frame": 42})`"""
+
+    def log(self, level: str, message: str, fields: dict=None) -> None:
+        """Emits a log record through the daemon, see `Node.log`."""
+
+@typing.final
+class Metrics:
+    """Records metrics of the operator, available as `self.metrics`.
+
+`e.g.:  self.metrics.record_histogram("planning_time", elapsed)`"""
+
+    def add_counter(self, name: str, value: float=None) -> None:
+        """Adds `value` to the counter with the given name."""
+
+    def record_histogram(self, name: str, value: float) -> None:
+        """Records `value` in the histogram with the given name."""
+
+    def set_gauge(self, name: str, value: float) -> None:
+        """Sets the gauge with the given name to `value`."""
+
 @typing.final
 class Node:
     """The custom node API lets you integrate `dora` into your application.
@@ -42,6 +144,30 @@ This method returns the parsed dataflow YAML file."""
     def dataflow_id(self) -> str:
         """Returns the dataflow id."""
 
+    def graph(self) -> dict:
+        """Returns the topology of the dataflow as seen by this node.
+
+The returned dict contains the `node_id`, the declared `inputs` and
+`outputs` of this node, and all `nodes` of the dataflow with aliases
+resolved and defaults applied:
+
+```python
+graph = node.graph()
+for input_id, input in graph["inputs"].items():
+print(input_id, input)
+```"""
+
+    def log(self, level: str, message: str, fields: dict=None) -> None:
+        """Emits a structured log record through the daemon.
+
+Unlike `print`, the record is tagged with the dataflow and node ID and
+keeps its `fields` as key/value pairs. It shows up in `dora logs` and
+in the log file of the node.
+
+```python
+node.log("warn", "camera frame arrived late", {"frame": 42})
+```"""
+
     def merge_external_events(self, subscription: dora.Ros2Subscription) -> None:
         """Merge an external event stream with dora main loop.
 This currently only work with ROS2."""
@@ -64,6 +190,18 @@ match event["type"]:
 case "INPUT":
 match event["id"]:
 case "image":
+```"""
+
+    def next_event(self, timeout: float=None) -> dora.events.Event:
+        """Like `.next()`, but returns a typed event of the `dora.events` module
+instead of a dict.
+
+```python
+from dora.events import Input
+
+event = node.next_event()
+if isinstance(event, Input) and event.id == "image":
+frame = event.data
 ```"""
 
     def send_output(self, output_id: str, data: pyarrow.Array, metadata: dict=None) -> None:
@@ -80,6 +218,40 @@ ex:
 
 ```python
 node.send_output("string", b"string", {"open_telemetry_context": "7632e76"})
+```
+
+Without an `open_telemetry_context`, the output continues the trace of
+the last received input.
+
+A `deadline` is the absolute time after which the message is dropped
+instead of delivered, in nanoseconds since the UNIX epoch, e.g.
+`time.time_ns() + 50_000_000`. `0` means no deadline. The `priority`
+is one of `"low"`, `"normal"`, `"high"`, and `"realtime"`.
+
+Other metadata keys are sent as typed entries. Supported values are
+`bool`, `int`, `float`, `str`, `bytes`, and `datetime.datetime`.
+Receivers get them back with the same type in `event["metadata"]`.
+
+Record batches are sent as a `pyarrow.StructArray` with one field per
+column. Receivers get it back as a batch in `event["batch"]`.
+
+Numpy arrays, `memoryview`, and `bytearray` objects are sent without
+an intermediate copy. Numpy arrays are flattened, so the shape needs to
+be passed in the metadata if receivers need it:
+
+```python
+node.send_output("image", frame, {"shape": str(frame.shape)})
+```"""
+
+    def send_output_json(self, output_id: str, obj: typing.Any, metadata: dict=None) -> None:
+        """Sends a JSON-serializable object, e.g. a `dict`, as UTF-8 encoded JSON.
+
+The object is serialized without going through `json.dumps`. The
+`encoding` metadata entry is set to `"json"`, so that receivers can
+decode the input through `Input.json()` of `dora.events`:
+
+```python
+node.send_output_json("status", {"battery": 0.8, "mode": "auto"})
 ```"""
 
     def __iter__(self) -> typing.Any:
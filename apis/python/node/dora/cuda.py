@@ -0,0 +1,207 @@
+"""
+Share CUDA tensors between dora nodes and operators on the same machine.
+
+Instead of copying GPU data to host memory, `to_ipc_tensor` exports a CUDA IPC
+handle of the tensor memory, which is sent as a small Arrow struct. Receivers
+call `open_ipc_tensor` to map the same device memory:
+
+```python
+from dora.cuda import to_ipc_tensor, open_ipc_tensor
+
+# sender (keep `tensor` alive until the receivers are done with it)
+send_output("image", to_ipc_tensor(tensor))
+
+# receiver
+with open_ipc_tensor(dora_event["value"]) as gpu_array:
+    tensor = torch.as_tensor(gpu_array, device="cuda")
+```
+
+The opened array implements `__cuda_array_interface__`, so it can be wrapped
+by PyTorch, CuPy, or Numba without a copy. Only the CUDA runtime library is
+required.
+"""
+
+import contextlib
+import ctypes
+import ctypes.util
+import os
+
+import pyarrow as pa
+
+_IPC_HANDLE_SIZE = 64
+_CUDA_IPC_MEM_LAZY_ENABLE_PEER_ACCESS = 1
+
+IPC_TENSOR_TYPE = pa.struct(
+    [
+        pa.field("ipc_handle", pa.binary(), nullable=False),
+        pa.field("offset", pa.uint64(), nullable=False),
+        pa.field("device_id", pa.int32(), nullable=False),
+        pa.field("dtype", pa.utf8(), nullable=False),
+        pa.field("shape", pa.list_(pa.int64()), nullable=False),
+        pa.field("strides", pa.list_(pa.int64()), nullable=False),
+        pa.field("pid", pa.uint32(), nullable=False),
+        pa.field("device_ptr", pa.uint64(), nullable=False),
+    ]
+)
+
+
+class _IpcMemHandle(ctypes.Structure):
+    _fields_ = [("reserved", ctypes.c_char * _IPC_HANDLE_SIZE)]
+
+
+def _load(names):
+    for name in names:
+        if name is None:
+            continue
+        try:
+            return ctypes.CDLL(name)
+        except OSError:
+            continue
+    raise RuntimeError(f"failed to load CUDA library (tried {names})")
+
+
+_cudart = None
+_cuda = None
+
+
+def _libs():
+    global _cudart, _cuda
+    if _cudart is None:
+        _cudart = _load(
+            [
+                ctypes.util.find_library("cudart"),
+                "libcudart.so",
+                "libcudart.so.12",
+                "libcudart.so.11.0",
+            ]
+        )
+        _cuda = _load([ctypes.util.find_library("cuda"), "libcuda.so", "libcuda.so.1"])
+    return _cudart, _cuda
+
+
+def _check(result, function):
+    if result != 0:
+        raise RuntimeError(f"{function} failed with CUDA error {result}")
+
+
+def _set_device(device_id):
+    cudart, _ = _libs()
+    _check(cudart.cudaSetDevice(ctypes.c_int(device_id)), "cudaSetDevice")
+
+
+def to_ipc_tensor(tensor) -> pa.StructArray:
+    """Exports a CUDA tensor as an IPC tensor payload.
+
+    The tensor can be any object that implements `__cuda_array_interface__`,
+    e.g. a PyTorch CUDA tensor or a CuPy array. The tensor memory must stay
+    valid until all receivers are done with it.
+    """
+    interface = tensor.__cuda_array_interface__
+    device_ptr, _ = interface["data"]
+    shape = list(interface["shape"])
+    dtype = interface["typestr"]
+    itemsize = int(dtype[2:])
+    strides = interface.get("strides")
+    if strides is None:
+        # C-contiguous
+        strides = []
+        stride = itemsize
+        for dim in reversed(shape):
+            strides.insert(0, stride)
+            stride *= dim
+    device_id = tensor.device.index if hasattr(tensor, "device") else 0
+    device_id = device_id or 0
+
+    cudart, cuda = _libs()
+    _set_device(device_id)
+    handle = _IpcMemHandle()
+    _check(
+        cudart.cudaIpcGetMemHandle(ctypes.byref(handle), ctypes.c_void_p(device_ptr)),
+        "cudaIpcGetMemHandle",
+    )
+    # the IPC handle refers to the whole allocation, so we need the offset
+    # of the tensor within it
+    base = ctypes.c_uint64()
+    size = ctypes.c_size_t()
+    _check(
+        cuda.cuMemGetAddressRange_v2(
+            ctypes.byref(base), ctypes.byref(size), ctypes.c_uint64(device_ptr)
+        ),
+        "cuMemGetAddressRange",
+    )
+
+    return pa.array(
+        [
+            {
+                "ipc_handle": bytes(handle.reserved),
+                "offset": device_ptr - base.value,
+                "device_id": device_id,
+                "dtype": dtype,
+                "shape": shape,
+                "strides": list(strides),
+                "pid": os.getpid(),
+                "device_ptr": device_ptr,
+            }
+        ],
+        type=IPC_TENSOR_TYPE,
+    )
+
+
+class CudaArray:
+    """A view of device memory that was opened from an IPC tensor payload."""
+
+    def __init__(self, device_ptr, device_id, dtype, shape, strides):
+        self.device_ptr = device_ptr
+        self.device_id = device_id
+        self.shape = tuple(shape)
+        self.__cuda_array_interface__ = {
+            "shape": self.shape,
+            "typestr": dtype,
+            "data": (device_ptr, False),
+            "strides": tuple(strides),
+            "version": 3,
+        }
+
+
+@contextlib.contextmanager
+def open_ipc_tensor(value: pa.StructArray):
+    """Maps the device memory of an IPC tensor payload.
+
+    The returned `CudaArray` is only valid inside the `with` block.
+    """
+    tensor = value[0].as_py()
+    device_id = tensor["device_id"]
+    if tensor["pid"] == os.getpid():
+        # CUDA doesn't allow opening IPC handles in the exporting process
+        yield CudaArray(
+            tensor["device_ptr"],
+            device_id,
+            tensor["dtype"],
+            tensor["shape"],
+            tensor["strides"],
+        )
+        return
+
+    cudart, _ = _libs()
+    _set_device(device_id)
+    handle = _IpcMemHandle()
+    handle.reserved = tensor["ipc_handle"]
+    base = ctypes.c_void_p()
+    _check(
+        cudart.cudaIpcOpenMemHandle(
+            ctypes.byref(base),
+            handle,
+            ctypes.c_uint(_CUDA_IPC_MEM_LAZY_ENABLE_PEER_ACCESS),
+        ),
+        "cudaIpcOpenMemHandle",
+    )
+    try:
+        yield CudaArray(
+            base.value + tensor["offset"],
+            device_id,
+            tensor["dtype"],
+            tensor["shape"],
+            tensor["strides"],
+        )
+    finally:
+        cudart.cudaIpcCloseMemHandle(base)
@@ -0,0 +1,177 @@
+"""
+Typed events of the dora node API.
+
+`Node.next_event` returns instances of these classes instead of the plain
+dictionaries of `Node.next`, so that IDEs and type checkers catch mistakes
+such as misspelled keys:
+
+```python
+from dora import Node
+from dora.events import Input, Stop
+
+node = Node()
+while (event := node.next_event()) is not None:
+    if isinstance(event, Input) and event.id == "image":
+        frame = event.data  # memoryview of the raw bytes, not copied
+    elif isinstance(event, Stop):
+        break
+```
+
+Event dictionaries, e.g. the ones that operators receive in `on_event`, are
+converted through `from_dict`.
+"""
+
+import datetime
+import json
+from dataclasses import dataclass, field
+from typing import TYPE_CHECKING, Any, Dict, Optional, Union
+
+import pyarrow as pa
+
+if TYPE_CHECKING:
+    import numpy
+
+MetadataValue = Union[bool, int, float, str, bytes, datetime.datetime]
+
+
+@dataclass(frozen=True)
+class Metadata:
+    """Metadata that the sender attached to an input."""
+
+    open_telemetry_context: str = ""
+    parameters: Dict[str, MetadataValue] = field(default_factory=dict)
+
+    @classmethod
+    def from_dict(cls, metadata: Dict[str, Any]) -> "Metadata":
+        parameters = dict(metadata)
+        context = parameters.pop("open_telemetry_context", "")
+        return cls(open_telemetry_context=context, parameters=parameters)
+
+
+@dataclass(frozen=True)
+class Input:
+    """A message that was received on one of the inputs of the node."""
+
+    id: str
+    value: pa.Array
+    metadata: Metadata = field(default_factory=Metadata)
+
+    @property
+    def data(self) -> memoryview:
+        """The raw bytes of the input, without copying.
+
+        Only available for arrays of fixed-width primitive types without null
+        values, e.g. the `uint8` arrays that are sent as `bytes`.
+        """
+        array = self.value
+        if not pa.types.is_primitive(array.type) or array.type.bit_width % 8 != 0:
+            raise TypeError(f"input `{self.id}` of type {array.type} has no raw data")
+        if array.null_count > 0:
+            raise ValueError(f"input `{self.id}` contains null values")
+        buffer = array.buffers()[1]
+        if buffer is None:
+            return memoryview(b"")
+        width = array.type.bit_width // 8
+        start = array.offset * width
+        return memoryview(buffer)[start : start + len(array) * width]
+
+    @property
+    def batch(self) -> Optional[pa.RecordBatch]:
+        """The input as a record batch if it's a struct array without nulls,
+        e.g. if the sender passed a record batch to `send_output`."""
+        if isinstance(self.value, pa.StructArray) and self.value.null_count == 0:
+            return pa.RecordBatch.from_struct_array(self.value)
+        return None
+
+    def json(self) -> Any:
+        """Decodes an input that was sent through `Node.send_output_json`."""
+        encoding = self.metadata.parameters.get("encoding")
+        if encoding != "json":
+            raise ValueError(f"input `{self.id}` is not JSON encoded (encoding: {encoding})")
+        return json.loads(self.data.tobytes())
+
+    def numpy(self) -> "numpy.ndarray":
+        """The input as a numpy array that shares the memory of the received
+        message, without copying.
+
+        Raises `pyarrow.ArrowInvalid` if the type of the input requires a copy,
+        e.g. if it contains null values.
+        """
+        return self.value.to_numpy(zero_copy_only=True)
+
+
+@dataclass(frozen=True)
+class InputClosed:
+    """The input with the given ID was closed, e.g. because its source stopped."""
+
+    id: str
+
+
+@dataclass(frozen=True)
+class AllInputsClosed:
+    """All inputs of the node were closed."""
+
+
+@dataclass(frozen=True)
+class Stop:
+    """The dataflow is stopping, the node should exit."""
+
+
+@dataclass(frozen=True)
+class Timer:
+    """One of the `_unstable_timers` of an operator fired.
+
+    Only passed to the `on_event` method of operators without `on_timer`.
+    """
+
+    id: str
+
+
+@dataclass(frozen=True)
+class Error:
+    """An error that occurred while receiving events."""
+
+    error: str
+
+
+@dataclass(frozen=True)
+class External:
+    """An event of an external stream, see `Node.merge_external_events`."""
+
+    value: Any
+
+
+@dataclass(frozen=True)
+class Unknown:
+    """An event that this version of the API doesn't know."""
+
+    type: str
+
+
+Event = Union[
+    Input, InputClosed, AllInputsClosed, Stop, Timer, Error, External, Unknown
+]
+
+
+def from_dict(event: Dict[str, Any]) -> Event:
+    """Converts an event dictionary of `Node.next` into a typed event."""
+    if event.get("kind") == "external":
+        return External(event["value"])
+    ty = event["type"]
+    if ty == "INPUT":
+        return Input(
+            id=event["id"],
+            value=event["value"],
+            metadata=Metadata.from_dict(event.get("metadata", {})),
+        )
+    if ty == "INPUT_CLOSED":
+        return InputClosed(event["id"])
+    if ty == "ALL_INPUTS_CLOSED":
+        return AllInputsClosed()
+    if ty == "STOP":
+        return Stop()
+    if ty == "TIMER":
+        return Timer(event["id"])
+    if ty == "ERROR":
+        return Error(event["error"])
+    return Unknown(ty)
@@ -0,0 +1,212 @@
+use dora_node_api::DoraNode;
+use eyre::Context;
+use pyo3::{exceptions::PyStopAsyncIteration, prelude::*, types::PyDict};
+
+use crate::{init_node, send_output, send_output_json, Events};
+
+/// Variant of `Node` for `asyncio` applications.
+///
+/// Events are received on a background thread and handed to the event loop,
+/// so waiting for the next event doesn't block other tasks:
+///
+/// ```python
+/// from dora import AsyncNode
+///
+/// async def main():
+///     node = AsyncNode()
+///     async for event in node:
+///         if event["type"] == "INPUT":
+///             await node.send_output("output", event["value"])
+/// ```
+///
+/// Cancelling a pending `next()`, e.g. through `asyncio.wait_for`, drops the
+/// event that it would have returned.
+///
+/// :type node_id: str, optional
+#[pyclass]
+pub struct AsyncNode {
+    node: DoraNode,
+    requests: flume::Sender<EventRequest>,
+}
+
+/// A pending `next()` call.
+struct EventRequest {
+    event_loop: PyObject,
+    future: PyObject,
+}
+
+#[pymethods]
+impl AsyncNode {
+    #[new]
+    pub fn new(node_id: Option<String>) -> eyre::Result<Self> {
+        let (node, events) = init_node(node_id)?;
+
+        let (requests, requests_rx) = flume::unbounded();
+        std::thread::Builder::new()
+            .name("dora-async-events".into())
+            .spawn(move || receive_loop(Events::Dora(events), requests_rx))
+            .context("failed to spawn event thread")?;
+
+        Ok(Self { node, requests })
+    }
+
+    /// Waits for the next event without blocking the event loop.
+    ///
+    /// The returned awaitable resolves to the same dicts as `Node.next()`. It
+    /// raises `StopAsyncIteration` when all senders have been dropped.
+    ///
+    /// ```python
+    /// event = await node.next()
+    /// ```
+    ///
+    /// :rtype: typing.Awaitable[dict]
+    pub fn next(&self, py: Python) -> PyResult<PyObject> {
+        let event_loop = py
+            .import_bound("asyncio")?
+            .call_method0("get_running_loop")?;
+        let future = event_loop.call_method0("create_future")?;
+        let request = EventRequest {
+            event_loop: event_loop.unbind(),
+            future: future.clone().unbind(),
+        };
+        if self.requests.send(request).is_err() {
+            future.call_method1(
+                "set_exception",
+                (py.get_type_bound::<PyStopAsyncIteration>(),),
+            )?;
+        }
+        Ok(future.unbind())
+    }
+
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__(&self, py: Python) -> PyResult<Option<PyObject>> {
+        self.next(py).map(Some)
+    }
+
+    /// Sends an output, see `Node.send_output` for the supported `data` types.
+    ///
+    /// The output is sent before this method returns, the returned awaitable
+    /// only exists for symmetry with other `asyncio` APIs.
+    ///
+    /// ```python
+    /// await node.send_output("string", b"string")
+    /// ```
+    ///
+    /// :type output_id: str
+    /// :type data: pyarrow.Array
+    /// :type metadata: dict, optional
+    /// :rtype: typing.Awaitable[None]
+    pub fn send_output(
+        &mut self,
+        output_id: String,
+        data: PyObject,
+        metadata: Option<Bound<'_, PyDict>>,
+        py: Python,
+    ) -> eyre::Result<PyObject> {
+        send_output(&mut self.node, output_id, data.bind(py), metadata)?;
+        completed_future(py)
+    }
+
+    /// Sends a JSON-serializable object, see `Node.send_output_json`.
+    ///
+    /// :type output_id: str
+    /// :type obj: typing.Any
+    /// :type metadata: dict, optional
+    /// :rtype: typing.Awaitable[None]
+    pub fn send_output_json(
+        &mut self,
+        output_id: String,
+        obj: Bound<'_, PyAny>,
+        metadata: Option<Bound<'_, PyDict>>,
+        py: Python,
+    ) -> eyre::Result<PyObject> {
+        send_output_json(&mut self.node, output_id, obj, metadata)?;
+        completed_future(py)
+    }
+
+    /// Returns the full dataflow descriptor that this node is part of.
+    ///
+    /// :rtype: dict
+    pub fn dataflow_descriptor(&self, py: Python) -> pythonize::Result<PyObject> {
+        pythonize::pythonize(py, self.node.dataflow_descriptor())
+    }
+
+    /// Returns the topology of the dataflow, see `Node.graph`.
+    ///
+    /// :rtype: dict
+    pub fn graph(&self, py: Python) -> eyre::Result<PyObject> {
+        let graph = self.node.graph()?;
+        Ok(pythonize::pythonize(py, &graph)?)
+    }
+
+    /// Returns the dataflow id.
+    ///
+    /// :rtype: str
+    pub fn dataflow_id(&self) -> String {
+        self.node.dataflow_id().to_string()
+    }
+}
+
+/// Returns an awaitable that resolves to `None` right away.
+fn completed_future(py: Python) -> eyre::Result<PyObject> {
+    let future = py
+        .import_bound("asyncio")?
+        .call_method0("get_running_loop")?
+        .call_method0("create_future")?;
+    future.call_method1("set_result", (py.None(),))?;
+    Ok(future.unbind())
+}
+
+/// Receives one event per `next()` call and resolves its future on the event
+/// loop. Exits once the `AsyncNode` is dropped.
+fn receive_loop(mut events: Events, requests: flume::Receiver<EventRequest>) {
+    for EventRequest { event_loop, future } in requests {
+        let event = events.recv(None);
+        Python::with_gil(|py| {
+            let result = (|| -> PyResult<()> {
+                let resolve = match event {
+                    Some(event) => Resolve {
+                        value: event.to_py_dict(py)?.into_py(py),
+                        exception: false,
+                    },
+                    None => Resolve {
+                        value: py.get_type_bound::<PyStopAsyncIteration>().into_py(py),
+                        exception: true,
+                    },
+                };
+                event_loop.call_method1(py, "call_soon_threadsafe", (resolve, future))?;
+                Ok(())
+            })();
+            // the event loop might be closed already
+            if let Err(err) = result {
+                err.print(py);
+            }
+        });
+    }
+}
+
+/// Sets the result of a future, unless it was cancelled in the meantime.
+#[pyclass]
+struct Resolve {
+    value: PyObject,
+    exception: bool,
+}
+
+#[pymethods]
+impl Resolve {
+    fn __call__(&self, future: &Bound<'_, PyAny>) -> PyResult<()> {
+        if future.call_method0("done")?.is_truthy()? {
+            return Ok(());
+        }
+        let method = if self.exception {
+            "set_exception"
+        } else {
+            "set_result"
+        };
+        future.call_method1(method, (self.value.clone_ref(future.py()),))?;
+        Ok(())
+    }
+}
@@ -2,11 +2,14 @@
 
 use std::time::Duration;
 
+mod async_node;
+
 use arrow::pyarrow::{FromPyArrow, ToPyArrow};
 use dora_node_api::dora_core::config::NodeId;
 use dora_node_api::merged::{MergeExternalSend, MergedEvent};
+use dora_node_api::typed::Encoding;
 use dora_node_api::{DoraNode, EventStream};
-use dora_operator_api_python::{pydict_to_metadata, PyEvent};
+use dora_operator_api_python::{buffer_to_pyarrow, py_log_record, pydict_to_metadata, PyEvent};
 use dora_ros2_bridge_python::Ros2Subscription;
 use eyre::Context;
 use futures::{Stream, StreamExt};
@@ -38,12 +41,7 @@ pub struct Node {
 impl Node {
     #[new]
     pub fn new(node_id: Option<String>) -> eyre::Result<Self> {
-        let (node, events) = if let Some(node_id) = node_id {
-            DoraNode::init_flexible(NodeId::from(node_id))
-                .context("Could not setup node from node id. Make sure to have a running dataflow with this dynamic node")?
-        } else {
-            DoraNode::init_from_env().context("Couldn not initiate node from environment variable. For dynamic node, please add a node id in the initialization function.")?
-        };
+        let (node, events) = init_node(node_id)?;
 
         Ok(Node {
             events: Events::Dora(events),
@@ -85,6 +83,30 @@ impl Node {
         }
     }
 
+    /// Like `.next()`, but returns a typed event of the `dora.events` module
+    /// instead of a dict.
+    ///
+    /// ```python
+    /// from dora.events import Input
+    ///
+    /// event = node.next_event()
+    /// if isinstance(event, Input) and event.id == "image":
+    ///     frame = event.data
+    /// ```
+    ///
+    /// :type timeout: float, optional
+    /// :rtype: dora.events.Event
+    pub fn next_event(&mut self, py: Python, timeout: Option<f32>) -> PyResult<Option<PyObject>> {
+        let Some(event) = self.next(py, timeout)? else {
+            return Ok(None);
+        };
+        let event = py
+            .import_bound("dora.events")?
+            .getattr("from_dict")?
+            .call1((event,))?;
+        Ok(Some(event.unbind()))
+    }
+
     /// You can iterate over the event stream with a loop
     ///
     /// ```python
@@ -132,6 +154,29 @@ impl Node {
     /// node.send_output("string", b"string", {"open_telemetry_context": "7632e76"})
     /// ```
     ///
+    /// Without an `open_telemetry_context`, the output continues the trace of
+    /// the last received input.
+    ///
+    /// A `deadline` is the absolute time after which the message is dropped
+    /// instead of delivered, in nanoseconds since the UNIX epoch, e.g.
+    /// `time.time_ns() + 50_000_000`. `0` means no deadline. The `priority`
+    /// is one of `"low"`, `"normal"`, `"high"`, and `"realtime"`.
+    ///
+    /// Other metadata keys are sent as typed entries. Supported values are
+    /// `bool`, `int`, `float`, `str`, `bytes`, and `datetime.datetime`.
+    /// Receivers get them back with the same type in `event["metadata"]`.
+    ///
+    /// Record batches are sent as a `pyarrow.StructArray` with one field per
+    /// column. Receivers get it back as a batch in `event["batch"]`.
+    ///
+    /// Numpy arrays, `memoryview`, and `bytearray` objects are sent without
+    /// an intermediate copy. Numpy arrays are flattened, so the shape needs to
+    /// be passed in the metadata if receivers need it:
+    ///
+    /// ```python
+    /// node.send_output("image", frame, {"shape": str(frame.shape)})
+    /// ```
+    ///
     /// :type output_id: str
     /// :type data: pyarrow.Array
     /// :type metadata: dict, optional
@@ -143,24 +188,54 @@ impl Node {
         metadata: Option<Bound<'_, PyDict>>,
         py: Python,
     ) -> eyre::Result<()> {
-        let parameters = pydict_to_metadata(metadata)?;
-
-        if let Ok(py_bytes) = data.downcast_bound::<PyBytes>(py) {
-            let data = py_bytes.as_bytes();
-            self.node
-                .send_output_bytes(output_id.into(), parameters, data.len(), data)
-                .wrap_err("failed to send output")?;
-        } else if let Ok(arrow_array) = arrow::array::ArrayData::from_pyarrow_bound(data.bind(py)) {
-            self.node.send_output(
-                output_id.into(),
-                parameters,
-                arrow::array::make_array(arrow_array),
-            )?;
-        } else {
-            eyre::bail!("invalid `data` type, must by `PyBytes` or arrow array")
-        }
+        send_output(&mut self.node, output_id, data.bind(py), metadata)
+    }
 
-        Ok(())
+    /// Sends a JSON-serializable object, e.g. a `dict`, as UTF-8 encoded JSON.
+    ///
+    /// The object is serialized without going through `json.dumps`. The
+    /// `encoding` metadata entry is set to `"json"`, so that receivers can
+    /// decode the input through `Input.json()` of `dora.events`:
+    ///
+    /// ```python
+    /// node.send_output_json("status", {"battery": 0.8, "mode": "auto"})
+    /// ```
+    ///
+    /// :type output_id: str
+    /// :type obj: typing.Any
+    /// :type metadata: dict, optional
+    /// :rtype: None
+    pub fn send_output_json(
+        &mut self,
+        output_id: String,
+        obj: Bound<'_, PyAny>,
+        metadata: Option<Bound<'_, PyDict>>,
+    ) -> eyre::Result<()> {
+        send_output_json(&mut self.node, output_id, obj, metadata)
+    }
+
+    /// Emits a structured log record through the daemon.
+    ///
+    /// Unlike `print`, the record is tagged with the dataflow and node ID and
+    /// keeps its `fields` as key/value pairs. It shows up in `dora logs` and
+    /// in the log file of the node.
+    ///
+    /// ```python
+    /// node.log("warn", "camera frame arrived late", {"frame": 42})
+    /// ```
+    ///
+    /// :type level: str
+    /// :type message: str
+    /// :type fields: dict, optional
+    /// :rtype: None
+    pub fn log(
+        &mut self,
+        level: &str,
+        message: String,
+        fields: Option<Bound<'_, PyDict>>,
+    ) -> eyre::Result<()> {
+        let record = py_log_record(level, message, fields, None)?;
+        self.node.log_record(record)
     }
 
     /// Returns the full dataflow descriptor that this node is part of.
@@ -172,6 +247,24 @@ impl Node {
         pythonize::pythonize(py, self.node.dataflow_descriptor())
     }
 
+    /// Returns the topology of the dataflow as seen by this node.
+    ///
+    /// The returned dict contains the `node_id`, the declared `inputs` and
+    /// `outputs` of this node, and all `nodes` of the dataflow with aliases
+    /// resolved and defaults applied:
+    ///
+    /// ```python
+    /// graph = node.graph()
+    /// for input_id, input in graph["inputs"].items():
+    ///     print(input_id, input)
+    /// ```
+    ///
+    /// :rtype: dict
+    pub fn graph(&self, py: Python) -> eyre::Result<PyObject> {
+        let graph = self.node.graph()?;
+        Ok(pythonize::pythonize(py, &graph)?)
+    }
+
     /// Returns the dataflow id.
     ///
     /// :rtype: str
@@ -217,13 +310,13 @@ impl Node {
     }
 }
 
-enum Events {
+pub(crate) enum Events {
     Dora(EventStream),
     Merged(Box<dyn Stream<Item = MergedEvent<PyObject>> + Unpin + Send>),
 }
 
 impl Events {
-    fn recv(&mut self, timeout: Option<Duration>) -> Option<PyEvent> {
+    pub(crate) fn recv(&mut self, timeout: Option<Duration>) -> Option<PyEvent> {
         match self {
             Events::Dora(events) => match timeout {
                 Some(timeout) => events.recv_timeout(timeout).map(PyEvent::from),
@@ -260,6 +353,71 @@ impl Node {
     }
 }
 
+fn init_node(node_id: Option<String>) -> eyre::Result<(DoraNode, EventStream)> {
+    if let Some(node_id) = node_id {
+        DoraNode::init_flexible(NodeId::from(node_id))
+            .context("Could not setup node from node id. Make sure to have a running dataflow with this dynamic node")
+    } else {
+        DoraNode::init_from_env().context("Couldn not initiate node from environment variable. For dynamic node, please add a node id in the initialization function.")
+    }
+}
+
+/// Sends the given Python object, see `Node.send_output` for the supported types.
+fn send_output(
+    node: &mut DoraNode,
+    output_id: String,
+    data: &Bound<'_, PyAny>,
+    metadata: Option<Bound<'_, PyDict>>,
+) -> eyre::Result<()> {
+    let parameters = pydict_to_metadata(metadata)?;
+
+    if let Ok(py_bytes) = data.downcast::<PyBytes>() {
+        let data = py_bytes.as_bytes();
+        node.send_output_bytes(output_id.into(), parameters, data.len(), data)
+            .wrap_err("failed to send output")?;
+    } else if let Ok(arrow_array) = arrow::array::ArrayData::from_pyarrow_bound(data) {
+        node.send_output(
+            output_id.into(),
+            parameters,
+            arrow::array::make_array(arrow_array),
+        )?;
+    } else if let Some(array) = buffer_to_pyarrow(data)? {
+        let arrow_array = arrow::array::ArrayData::from_pyarrow_bound(&array)?;
+        node.send_output(
+            output_id.into(),
+            parameters,
+            arrow::array::make_array(arrow_array),
+        )?;
+    } else if let Ok(batch) = arrow::record_batch::RecordBatch::from_pyarrow_bound(data) {
+        node.send_record_batch(output_id.into(), parameters, batch)?;
+    } else {
+        eyre::bail!(
+            "invalid `data` type, must by `PyBytes`, arrow array, arrow record batch, \
+            numpy array, `memoryview`, or `bytearray`"
+        )
+    }
+
+    Ok(())
+}
+
+/// Value of the `encoding` metadata entry of outputs sent by `send_output_json`.
+const JSON_ENCODING: &str = "json";
+
+fn send_output_json(
+    node: &mut DoraNode,
+    output_id: String,
+    obj: Bound<'_, PyAny>,
+    metadata: Option<Bound<'_, PyDict>>,
+) -> eyre::Result<()> {
+    let mut parameters = pydict_to_metadata(metadata)?;
+    parameters.insert("encoding", JSON_ENCODING);
+    let value: serde_json::Value =
+        pythonize::depythonize_bound(obj).wrap_err("object is not JSON-serializable")?;
+    let data = Encoding::Json.encode(&value)?;
+    node.send_output_bytes(output_id.into(), parameters, data.len(), &data)
+        .wrap_err("failed to send output")
+}
+
 /// Start a runtime for Operators
 ///
 /// :rtype: None
@@ -274,6 +432,9 @@ fn dora(_py: Python, m: Bound<'_, PyModule>) -> PyResult<()> {
 
     m.add_function(wrap_pyfunction!(start_runtime, &m)?)?;
     m.add_class::<Node>()?;
+    m.add_class::<async_node::AsyncNode>()?;
+    m.add_class::<dora_runtime::PythonMetrics>()?;
+    m.add_class::<dora_runtime::PythonLogger>()?;
     m.setattr("__version__", env!("CARGO_PKG_VERSION"))?;
     m.setattr("__author__", "Dora-rs Authors")?;
 
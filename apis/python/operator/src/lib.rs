@@ -1,12 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use arrow::pyarrow::ToPyArrow;
-use dora_node_api::{merged::MergedEvent, Event, Metadata, MetadataParameters};
-use eyre::{Context, Result};
+use arrow::{
+    array::{Array, AsArray},
+    pyarrow::ToPyArrow,
+    record_batch::RecordBatch,
+};
+use dora_node_api::{
+    dora_core::{config::OperatorId, daemon_messages::NodeLogRecord},
+    merged::MergedEvent,
+    Event, LogLevel, Metadata, MetadataParameters, Parameter,
+};
+use eyre::{eyre, Context, Result};
 use pyo3::{
     prelude::*,
     pybacked::PyBackedStr,
-    types::{IntoPyDict, PyDict},
+    types::{
+        IntoPyDict, PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyInt, PyMemoryView, PyString,
+    },
 };
 
 /// Dora Event
@@ -32,6 +42,9 @@ impl PyEvent {
                 if let Some(value) = self.value(py)? {
                     pydict.insert("value", value);
                 }
+                if let Some(batch) = self.batch(py)? {
+                    pydict.insert("batch", batch);
+                }
                 if let Some(metadata) = Self::metadata(event, py) {
                     pydict.insert("metadata", metadata);
                 }
@@ -52,6 +65,7 @@ impl PyEvent {
             Event::Stop => "STOP",
             Event::Input { .. } => "INPUT",
             Event::InputClosed { .. } => "INPUT_CLOSED",
+            Event::AllInputsClosed => "ALL_INPUTS_CLOSED",
             Event::Error(_) => "ERROR",
             _other => "UNKNOWN",
         }
@@ -77,6 +91,21 @@ impl PyEvent {
         }
     }
 
+    /// Returns struct inputs, e.g. sent record batches, as a `pyarrow.RecordBatch`.
+    ///
+    /// Like `value`, the batch shares the memory of the received message.
+    fn batch(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        match &self.event {
+            MergedEvent::Dora(Event::Input { data, .. }) => match data.as_struct_opt() {
+                Some(array) if array.null_count() == 0 => {
+                    Ok(Some(RecordBatch::from(array).to_pyarrow(py)?))
+                }
+                _ => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+
     fn metadata(event: &Event, py: Python<'_>) -> Option<PyObject> {
         match event {
             Event::Input { metadata, .. } => Some(metadata_to_pydict(metadata, py).to_object(py)),
@@ -108,11 +137,10 @@ pub fn pydict_to_metadata(dict: Option<Bound<'_, PyDict>>) -> Result<MetadataPar
     let mut default_metadata = MetadataParameters::default();
     if let Some(metadata) = dict {
         for (key, value) in metadata.iter() {
-            match key
+            let key = key
                 .extract::<PyBackedStr>()
-                .context("Parsing metadata keys")?
-                .as_ref()
-            {
+                .context("Parsing metadata keys")?;
+            match key.as_ref() {
                 "watermark" => {
                     default_metadata.watermark =
                         value.extract().context("parsing watermark failed")?;
@@ -121,19 +149,87 @@ pub fn pydict_to_metadata(dict: Option<Bound<'_, PyDict>>) -> Result<MetadataPar
                     default_metadata.deadline =
                         value.extract().context("parsing deadline failed")?;
                 }
+                "priority" => {
+                    let priority: PyBackedStr =
+                        value.extract().context("parsing priority failed")?;
+                    default_metadata.priority = priority.parse()?;
+                }
                 "open_telemetry_context" => {
                     let otel_context: PyBackedStr = value
                         .extract()
                         .context("parsing open telemetry context failed")?;
                     default_metadata.open_telemetry_context = otel_context.to_string();
                 }
-                _ => (),
+                other => {
+                    let value = py_to_parameter(&value)
+                        .with_context(|| format!("failed to parse metadata entry `{other}`"))?;
+                    default_metadata.entries.insert(other.to_owned(), value);
+                }
             }
         }
     }
     Ok(default_metadata)
 }
 
+/// Creates a log record from the arguments of `log(level, message, fields)`.
+///
+/// The `level` is one of `"error"`, `"warn"`, `"info"`, `"debug"`, and
+/// `"trace"`. The `fields` support the same value types as metadata entries.
+pub fn py_log_record(
+    level: &str,
+    message: String,
+    fields: Option<Bound<'_, PyDict>>,
+    operator_id: Option<OperatorId>,
+) -> Result<NodeLogRecord> {
+    let level: LogLevel = level
+        .parse()
+        .map_err(|_| eyre!("invalid log level `{level}`, expected e.g. `info` or `warn`"))?;
+    let mut record_fields = BTreeMap::new();
+    for (key, value) in fields.iter().flat_map(|fields| fields.iter()) {
+        let key = key
+            .extract::<PyBackedStr>()
+            .context("log field keys must be strings")?;
+        let value = py_to_parameter(&value)
+            .with_context(|| format!("failed to parse log field `{}`", &*key))?;
+        record_fields.insert(key.to_string(), value);
+    }
+    Ok(NodeLogRecord {
+        level,
+        message,
+        operator_id,
+        fields: record_fields,
+    })
+}
+
+/// Wraps numpy arrays and `memoryview`/`bytearray` objects in a pyarrow array
+/// that shares their memory, so that they can be sent without an intermediate
+/// `bytes` copy.
+///
+/// Numpy arrays are flattened, which only copies if they are not contiguous.
+/// Returns `None` for other types.
+pub fn buffer_to_pyarrow<'py>(data: &Bound<'py, PyAny>) -> PyResult<Option<Bound<'py, PyAny>>> {
+    let py = data.py();
+    if data.hasattr("__array_interface__")? {
+        let flat = data.call_method1("reshape", (-1,))?;
+        let array = py.import_bound("pyarrow")?.call_method1("array", (flat,))?;
+        Ok(Some(array))
+    } else if data.is_instance_of::<PyMemoryView>() || data.is_instance_of::<PyByteArray>() {
+        let pyarrow = py.import_bound("pyarrow")?;
+        let buffer = pyarrow.call_method1("py_buffer", (data,))?;
+        let array = pyarrow.getattr("Array")?.call_method1(
+            "from_buffers",
+            (
+                pyarrow.call_method0("uint8")?,
+                buffer.getattr("size")?,
+                (py.None(), buffer),
+            ),
+        )?;
+        Ok(Some(array))
+    } else {
+        Ok(None)
+    }
+}
+
 pub fn metadata_to_pydict<'a>(metadata: &'a Metadata, py: Python<'a>) -> pyo3::Bound<'a, PyDict> {
     let dict = PyDict::new_bound(py);
     dict.set_item(
@@ -142,9 +238,72 @@ pub fn metadata_to_pydict<'a>(metadata: &'a Metadata, py: Python<'a>) -> pyo3::B
     )
     .wrap_err("could not make metadata a python dictionary item")
     .unwrap();
+    for (key, value) in &metadata.parameters.entries {
+        let value = parameter_to_py(value, py)
+            .wrap_err_with(|| format!("could not convert metadata entry `{key}`"))
+            .unwrap();
+        dict.set_item(key, value)
+            .wrap_err("could not make metadata a python dictionary item")
+            .unwrap();
+    }
     dict
 }
 
+/// Converts a Python value to a typed metadata entry.
+///
+/// `datetime` values are stored as timestamps. Naive `datetime` values are
+/// interpreted in the local timezone, like `datetime.timestamp()` does.
+fn py_to_parameter(value: &Bound<'_, PyAny>) -> Result<Parameter> {
+    // check `bool` first because it's a subclass of `int`
+    let parameter = if let Ok(value) = value.downcast::<PyBool>() {
+        Parameter::Bool(value.is_true())
+    } else if value.is_instance_of::<PyInt>() {
+        Parameter::Integer(value.extract().context("integer out of range")?)
+    } else if value.is_instance_of::<PyFloat>() {
+        Parameter::Float(value.extract()?)
+    } else if value.is_instance_of::<PyString>() {
+        Parameter::String(value.extract()?)
+    } else if let Ok(value) = value.downcast::<PyBytes>() {
+        Parameter::Bytes(value.as_bytes().to_vec())
+    } else if value.is_instance(&datetime_type(value.py())?)? {
+        let seconds: f64 = value.call_method0("timestamp")?.extract()?;
+        if seconds < 0.0 {
+            eyre::bail!("datetime values before 1970 are not supported");
+        }
+        Parameter::Timestamp((seconds * 1e9).round() as u64)
+    } else {
+        eyre::bail!(
+            "unsupported type `{}`, expected bool, int, float, str, bytes, or datetime",
+            value.get_type().name()?
+        );
+    };
+    Ok(parameter)
+}
+
+fn parameter_to_py(parameter: &Parameter, py: Python<'_>) -> Result<PyObject> {
+    let value = match parameter {
+        Parameter::Bool(value) => value.to_object(py),
+        Parameter::Integer(value) => value.to_object(py),
+        Parameter::Float(value) => value.to_object(py),
+        Parameter::String(value) => value.to_object(py),
+        Parameter::Bytes(value) => PyBytes::new_bound(py, value).into_py(py),
+        Parameter::Timestamp(nanos) => {
+            let utc = py
+                .import_bound("datetime")?
+                .getattr("timezone")?
+                .getattr("utc")?;
+            datetime_type(py)?
+                .call_method1("fromtimestamp", (*nanos as f64 / 1e9, utc))?
+                .into_py(py)
+        }
+    };
+    Ok(value)
+}
+
+fn datetime_type(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    py.import_bound("datetime")?.getattr("datetime")
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
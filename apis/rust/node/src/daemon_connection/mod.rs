@@ -1,3 +1,5 @@
+#[cfg(unix)]
+use dora_core::topics::LocalSocketAddr;
 use dora_core::{
     config::NodeId,
     daemon_messages::{DaemonReply, DaemonRequest, DataflowId, Timestamped},
@@ -15,6 +17,8 @@ mod tcp;
 pub enum DaemonChannel {
     Shmem(ShmemClient<Timestamped<DaemonRequest>, DaemonReply>),
     Tcp(TcpStream),
+    #[cfg(unix)]
+    UnixDomain(std::os::unix::net::UnixStream),
 }
 
 impl DaemonChannel {
@@ -25,6 +29,28 @@ impl DaemonChannel {
         Ok(DaemonChannel::Tcp(stream))
     }
 
+    #[cfg(unix)]
+    #[tracing::instrument(level = "trace")]
+    pub fn new_unix_socket(addr: &LocalSocketAddr) -> eyre::Result<Self> {
+        use std::os::unix::net::UnixStream;
+
+        let stream = match addr {
+            LocalSocketAddr::Path(path) => UnixStream::connect(path),
+            #[cfg(target_os = "linux")]
+            LocalSocketAddr::Abstract(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())
+                    .and_then(|addr| UnixStream::connect_addr(&addr))
+            }
+            #[cfg(not(target_os = "linux"))]
+            LocalSocketAddr::Abstract(_) => {
+                bail!("abstract unix sockets are only supported on Linux")
+            }
+        }
+        .wrap_err_with(|| format!("failed to connect to unix socket `{addr}`"))?;
+        Ok(DaemonChannel::UnixDomain(stream))
+    }
+
     #[tracing::instrument(level = "trace")]
     pub unsafe fn new_shmem(daemon_control_region_id: &str) -> eyre::Result<Self> {
         let daemon_events_region = ShmemConf::new()
@@ -48,7 +74,7 @@ impl DaemonChannel {
             inner: DaemonRequest::Register {
                 dataflow_id,
                 node_id,
-                dora_version: env!("CARGO_PKG_VERSION").to_owned(),
+                dora_version: dora_core::version::current(),
             },
             timestamp,
         };
@@ -69,6 +95,8 @@ impl DaemonChannel {
         match self {
             DaemonChannel::Shmem(client) => client.request(request),
             DaemonChannel::Tcp(stream) => tcp::request(stream, request),
+            #[cfg(unix)]
+            DaemonChannel::UnixDomain(stream) => tcp::request(stream, request),
         }
     }
 }
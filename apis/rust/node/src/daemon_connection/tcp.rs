@@ -1,16 +1,13 @@
 use dora_core::daemon_messages::{DaemonReply, DaemonRequest, Timestamped};
 use eyre::{eyre, Context};
-use std::{
-    io::{Read, Write},
-    net::TcpStream,
-};
+use std::io::{Read, Write};
 
 enum Serializer {
     Bincode,
     SerdeJson,
 }
 pub fn request(
-    connection: &mut TcpStream,
+    connection: &mut (impl Read + Write + Unpin),
     request: &Timestamped<DaemonRequest>,
 ) -> eyre::Result<DaemonReply> {
     send_message(connection, request)?;
@@ -27,7 +24,7 @@ pub fn request(
 }
 
 fn send_message(
-    connection: &mut TcpStream,
+    connection: &mut (impl Read + Write + Unpin),
     message: &Timestamped<DaemonRequest>,
 ) -> eyre::Result<()> {
     let serialized = bincode::serialize(&message).wrap_err("failed to serialize DaemonRequest")?;
@@ -36,7 +33,7 @@ fn send_message(
 }
 
 fn receive_reply(
-    connection: &mut TcpStream,
+    connection: &mut (impl Read + Write + Unpin),
     serializer: Serializer,
 ) -> eyre::Result<Option<DaemonReply>> {
     let raw = match tcp_receive(connection) {
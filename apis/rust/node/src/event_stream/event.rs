@@ -4,6 +4,7 @@ use aligned_vec::{AVec, ConstAlign};
 use dora_arrow_convert::{ArrowData, IntoArrow};
 use dora_core::{
     config::{DataId, OperatorId},
+    descriptor::OperatorDefinition,
     message::{ArrowTypeInfo, BufferOffset, Metadata},
 };
 use eyre::{Context, Result};
@@ -12,21 +13,53 @@ use shared_memory_extended::{Shmem, ShmemConf};
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Event {
+    /// The dataflow is stopping, e.g. because of `dora stop` or a ctrl-c.
+    ///
+    /// Nodes should finish their current work and exit. Inputs might still
+    /// arrive after this event until the upstream nodes have exited.
     Stop,
     Reload {
         operator_id: Option<OperatorId>,
     },
+    /// Requests a runtime node to load an additional operator.
+    LoadOperator {
+        operator: OperatorDefinition,
+    },
+    /// Requests a runtime node to stop and remove one of its operators.
+    UnloadOperator {
+        operator_id: OperatorId,
+    },
     Input {
         id: DataId,
         metadata: Metadata,
         data: ArrowData,
     },
+    /// The upstream output of the given input was closed, e.g. because the
+    /// producing node exited. No more data will arrive on this input.
     InputClosed {
         id: DataId,
     },
+    /// All inputs of the node are closed.
+    ///
+    /// This is the last event before the event stream ends. Nodes without
+    /// other work should exit.
+    AllInputsClosed,
     Error(String),
 }
 
+impl Event {
+    /// Time elapsed since the input was sent, see [`Metadata::latency`].
+    ///
+    /// Returns `None` for other events and for inputs without send time,
+    /// e.g. timer ticks.
+    pub fn latency(&self) -> Option<std::time::Duration> {
+        match self {
+            Event::Input { metadata, .. } => metadata.latency(),
+            _ => None,
+        }
+    }
+}
+
 pub enum RawData {
     Empty,
     Vec(AVec<u8, ConstAlign<128>>),
@@ -60,7 +93,7 @@ pub struct SharedMemoryData {
     pub _drop: flume::Sender<()>,
 }
 
-fn buffer_into_arrow_array(
+pub(crate) fn buffer_into_arrow_array(
     raw_buffer: &arrow::buffer::Buffer,
     type_info: &ArrowTypeInfo,
 ) -> eyre::Result<arrow::array::ArrayData> {
@@ -1,5 +1,10 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{Arc, Mutex},
+    task::Poll,
+    time::Duration,
+};
 
+pub(crate) use event::buffer_into_arrow_array;
 pub use event::{Event, MappedInputData, RawData};
 use futures::{
     future::{select, Either},
@@ -7,11 +12,12 @@ use futures::{
 };
 use futures_timer::Delay;
 
-use self::{
-    event::SharedMemoryData,
-    thread::{EventItem, EventStreamThreadHandle},
+pub(crate) use self::thread::EventItem;
+use self::{event::SharedMemoryData, thread::EventStreamThreadHandle};
+use crate::{
+    daemon_connection::DaemonChannel,
+    typed::{TypedInput, TypedInputs},
 };
-use crate::daemon_connection::DaemonChannel;
 use dora_core::{
     config::NodeId,
     daemon_messages::{
@@ -28,9 +34,32 @@ mod thread;
 pub struct EventStream {
     node_id: NodeId,
     receiver: flume::r#async::RecvStream<'static, EventItem>,
-    _thread_handle: EventStreamThreadHandle,
-    close_channel: DaemonChannel,
+    _thread_handle: Option<EventStreamThreadHandle>,
+    /// Not set for event streams that are not connected to a daemon.
+    close_channel: Option<DaemonChannel>,
     clock: Arc<uhlc::HLC>,
+    input_context: InputContext,
+}
+
+/// OpenTelemetry context of the last input that the node received.
+///
+/// Outputs that are sent without a context of their own continue the trace of
+/// this input, so that traces pass through nodes that don't forward the
+/// context explicitly.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InputContext(Arc<Mutex<String>>);
+
+impl InputContext {
+    fn set(&self, context: &str) {
+        if let Ok(mut current) = self.0.lock() {
+            current.clear();
+            current.push_str(context);
+        }
+    }
+
+    pub(crate) fn get(&self) -> String {
+        self.0.lock().map(|c| c.clone()).unwrap_or_default()
+    }
 }
 
 impl EventStream {
@@ -100,12 +129,34 @@ impl EventStream {
         Ok(EventStream {
             node_id: node_id.clone(),
             receiver: rx.into_stream(),
-            _thread_handle: thread_handle,
-            close_channel,
+            _thread_handle: Some(thread_handle),
+            close_channel: Some(close_channel),
             clock,
+            input_context: Default::default(),
         })
     }
 
+    /// Creates an event stream whose events are produced locally instead of
+    /// being received from a daemon.
+    pub(crate) fn init_local(
+        node_id: NodeId,
+        receiver: flume::Receiver<EventItem>,
+        clock: Arc<uhlc::HLC>,
+    ) -> Self {
+        EventStream {
+            node_id,
+            receiver: receiver.into_stream(),
+            _thread_handle: None,
+            close_channel: None,
+            clock,
+            input_context: Default::default(),
+        }
+    }
+
+    pub(crate) fn input_context(&self) -> InputContext {
+        self.input_context.clone()
+    }
+
     /// wait for the next event on the events stream.
     pub fn recv(&mut self) -> Option<Event> {
         futures::executor::block_on(self.recv_async())
@@ -116,8 +167,17 @@ impl EventStream {
         futures::executor::block_on(self.recv_async_timeout(dur))
     }
 
+    /// Returns an iterator over the decoded values of the given input.
+    ///
+    /// Other events are skipped. The iterator ends when the input is closed or
+    /// the node is stopped.
+    pub fn typed_inputs<'a, T>(&'a mut self, input: &'a TypedInput<T>) -> TypedInputs<'a, T> {
+        TypedInputs::new(self, input)
+    }
+
     pub async fn recv_async(&mut self) -> Option<Event> {
-        self.receiver.next().await.map(Self::convert_event_item)
+        let item = self.receiver.next().await;
+        item.map(|item| self.convert_event_item(item))
     }
 
     pub async fn recv_async_timeout(&mut self, dur: Duration) -> Option<Event> {
@@ -127,16 +187,23 @@ impl EventStream {
             }
             Either::Right((event, _)) => event,
         };
-        next_event.map(Self::convert_event_item)
+        next_event.map(|item| self.convert_event_item(item))
     }
 
-    fn convert_event_item(item: EventItem) -> Event {
+    fn convert_event_item(&self, item: EventItem) -> Event {
         match item {
             EventItem::NodeEvent { event, ack_channel } => match event {
                 NodeEvent::Stop => Event::Stop,
                 NodeEvent::Reload { operator_id } => Event::Reload { operator_id },
+                NodeEvent::LoadOperator { operator } => match serde_json::from_str(&operator) {
+                    Ok(operator) => Event::LoadOperator { operator },
+                    Err(err) => Event::Error(format!("invalid operator definition: {err}")),
+                },
+                NodeEvent::UnloadOperator { operator_id } => Event::UnloadOperator { operator_id },
                 NodeEvent::InputClosed { id } => Event::InputClosed { id },
                 NodeEvent::Input { id, metadata, data } => {
+                    self.input_context
+                        .set(&metadata.parameters.open_telemetry_context);
                     let data = match data {
                         None => Ok(None),
                         Some(daemon_messages::DataMessage::Vec(v)) => Ok(Some(RawData::Vec(v))),
@@ -168,13 +235,7 @@ impl EventStream {
                         Err(err) => Event::Error(format!("{err:?}")),
                     }
                 }
-                NodeEvent::AllInputsClosed => {
-                    let err = eyre!(
-                        "received `AllInputsClosed` event, which should be handled by background task"
-                    );
-                    tracing::error!("{err:?}");
-                    Event::Error(err.wrap_err("internal error").to_string())
-                }
+                NodeEvent::AllInputsClosed => Event::AllInputsClosed,
             },
 
             EventItem::FatalError(err) => {
@@ -194,21 +255,24 @@ impl Stream for EventStream {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.receiver
-            .poll_next_unpin(cx)
-            .map(|item| item.map(Self::convert_event_item))
+        match self.receiver.poll_next_unpin(cx) {
+            Poll::Ready(item) => Poll::Ready(item.map(|item| self.convert_event_item(item))),
+            Poll::Pending => Poll::Pending,
+        }
     }
 }
 
 impl Drop for EventStream {
     #[tracing::instrument(skip(self), fields(%self.node_id))]
     fn drop(&mut self) {
+        let Some(close_channel) = &mut self.close_channel else {
+            return;
+        };
         let request = Timestamped {
             inner: DaemonRequest::EventStreamDropped,
             timestamp: self.clock.new_timestamp(),
         };
-        let result = self
-            .close_channel
+        let result = close_channel
             .request(&request)
             .map_err(|e| eyre!(e))
             .wrap_err("failed to signal event stream closure to dora-daemon")
@@ -124,14 +124,9 @@ fn event_stream_loop(
                 NodeEvent::Input {
                     data: Some(data), ..
                 } => data.drop_token(),
-                NodeEvent::AllInputsClosed => {
-                    // close the event stream
-                    tx = None;
-                    // skip this internal event
-                    continue;
-                }
                 _ => None,
             };
+            let all_inputs_closed = matches!(inner, NodeEvent::AllInputsClosed);
 
             if let Some(tx) = tx.as_ref() {
                 let (drop_tx, drop_rx) = flume::bounded(0);
@@ -156,6 +151,10 @@ fn event_stream_loop(
             } else {
                 tracing::warn!("dropping event because event `tx` was already closed: `{inner:?}`");
             }
+            if all_inputs_closed {
+                // close the event stream after delivering the event
+                tx = None;
+            }
         }
     };
     if let Err(err) = result {
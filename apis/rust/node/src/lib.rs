@@ -16,11 +16,18 @@
 pub use arrow;
 pub use dora_arrow_convert::*;
 pub use dora_core;
-pub use dora_core::message::{uhlc, Metadata, MetadataParameters};
+pub use dora_core::daemon_messages::{HealthStatus, NodeHealth};
+pub use dora_core::message::{
+    uhlc, MessagePriority, Metadata, MetadataParameters, Parameter, SendTime,
+};
 pub use event_stream::{merged, Event, EventStream, MappedInputData, RawData};
 pub use flume::Receiver;
-pub use node::{arrow_utils, DataSample, DoraNode, ZERO_COPY_THRESHOLD};
+pub use log::Level as LogLevel;
+pub use node::{arrow_utils, AsyncDoraNode, DataSample, DoraNode, ZERO_COPY_THRESHOLD};
 
 mod daemon_connection;
 mod event_stream;
 mod node;
+pub mod replay;
+pub mod service;
+pub mod typed;
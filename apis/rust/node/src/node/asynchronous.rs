@@ -0,0 +1,171 @@
+use super::DoraNode;
+use crate::{EventStream, MetadataParameters};
+use arrow::array::{make_array, Array, ArrayData};
+use dora_core::config::{DataId, NodeId};
+use eyre::{eyre, Context};
+use std::thread::JoinHandle;
+
+/// Async version of [`DoraNode`], for nodes that run on an async executor such
+/// as `tokio`.
+///
+/// The underlying node runs on a dedicated thread, so sending an output never
+/// blocks the executor. The `send_*` futures complete once the daemon has
+/// accepted the output, which applies backpressure to fast producers. The
+/// [`EventStream`] implements [`futures::Stream`], so it can be used from async
+/// code directly.
+///
+/// All methods take `&self`, so the node can be shared between tasks through an
+/// `Arc`.
+///
+/// ```no_run
+/// use dora_node_api::{AsyncDoraNode, Event, MetadataParameters};
+/// use futures::StreamExt;
+///
+/// # async fn run() -> eyre::Result<()> {
+/// let (node, mut events) = AsyncDoraNode::init_from_env()?;
+/// while let Some(event) = events.next().await {
+///     if let Event::Input { id, data, .. } = event {
+///         node.send_output(id, MetadataParameters::default(), data.0).await?;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncDoraNode {
+    id: NodeId,
+    requests: Option<flume::Sender<Request>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+enum Request {
+    SendOutput {
+        output_id: DataId,
+        parameters: MetadataParameters,
+        data: ArrayData,
+        reply: flume::Sender<eyre::Result<()>>,
+    },
+    CloseOutputs {
+        outputs: Vec<DataId>,
+        reply: flume::Sender<eyre::Result<()>>,
+    },
+}
+
+impl AsyncDoraNode {
+    /// Initiates a node from environment variables set by `dora-coordinator`,
+    /// see [`DoraNode::init_from_env`].
+    pub fn init_from_env() -> eyre::Result<(Self, EventStream)> {
+        let (node, events) = DoraNode::init_from_env()?;
+        Ok((Self::new(node)?, events))
+    }
+
+    /// Spawns the thread that owns the given node.
+    pub fn new(node: DoraNode) -> eyre::Result<Self> {
+        let id = node.id().clone();
+        let (requests, requests_rx) = flume::bounded(1);
+        let worker = std::thread::Builder::new()
+            .name(format!("dora-node-{id}"))
+            .spawn(move || run_worker(node, requests_rx))
+            .wrap_err("failed to spawn node thread")?;
+        Ok(Self {
+            id,
+            requests: Some(requests),
+            worker: Some(worker),
+        })
+    }
+
+    pub fn id(&self) -> &NodeId {
+        &self.id
+    }
+
+    pub async fn send_output(
+        &self,
+        output_id: DataId,
+        parameters: MetadataParameters,
+        data: impl Array,
+    ) -> eyre::Result<()> {
+        let data = data.to_data();
+        self.request(|reply| Request::SendOutput {
+            output_id,
+            parameters,
+            data,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn send_output_bytes(
+        &self,
+        output_id: DataId,
+        parameters: MetadataParameters,
+        data: &[u8],
+    ) -> eyre::Result<()> {
+        let data = arrow::array::UInt8Array::from(data.to_vec());
+        self.send_output(output_id, parameters, data).await
+    }
+
+    /// Sends the given byte slices as a single output message, in order.
+    pub async fn send_output_vectored(
+        &self,
+        output_id: DataId,
+        parameters: MetadataParameters,
+        parts: &[&[u8]],
+    ) -> eyre::Result<()> {
+        let data = arrow::array::UInt8Array::from(parts.concat());
+        self.send_output(output_id, parameters, data).await
+    }
+
+    pub async fn close_outputs(&self, outputs: Vec<DataId>) -> eyre::Result<()> {
+        self.request(|reply| Request::CloseOutputs { outputs, reply })
+            .await
+    }
+
+    async fn request(
+        &self,
+        request: impl FnOnce(flume::Sender<eyre::Result<()>>) -> Request,
+    ) -> eyre::Result<()> {
+        let (reply, reply_rx) = flume::bounded(1);
+        let requests = self
+            .requests
+            .as_ref()
+            .expect("requests sender is set until drop");
+        requests
+            .send_async(request(reply))
+            .await
+            .map_err(|_| eyre!("node thread exited unexpectedly"))?;
+        reply_rx
+            .recv_async()
+            .await
+            .map_err(|_| eyre!("node thread exited before replying"))?
+    }
+}
+
+impl Drop for AsyncDoraNode {
+    fn drop(&mut self) {
+        // closing the channel stops the worker, which then drops the node
+        self.requests = None;
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                tracing::warn!("node thread panicked");
+            }
+        }
+    }
+}
+
+fn run_worker(mut node: DoraNode, requests: flume::Receiver<Request>) {
+    for request in requests {
+        match request {
+            Request::SendOutput {
+                output_id,
+                parameters,
+                data,
+                reply,
+            } => {
+                let result = node.send_output(output_id, parameters, make_array(data));
+                let _ = reply.send(result);
+            }
+            Request::CloseOutputs { outputs, reply } => {
+                let _ = reply.send(node.close_outputs(outputs));
+            }
+        }
+    }
+}
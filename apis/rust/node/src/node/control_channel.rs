@@ -1,15 +1,23 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crate::daemon_connection::DaemonChannel;
 use dora_core::{
     config::{DataId, NodeId},
-    daemon_messages::{DaemonCommunication, DaemonRequest, DataMessage, DataflowId, Timestamped},
+    daemon_messages::{
+        DaemonCommunication, DaemonReply, DaemonRequest, DataMessage, DataflowId, NodeHealth,
+        NodeLogRecord, OperatorError, Timestamped,
+    },
     message::{uhlc::HLC, Metadata},
 };
 use eyre::{bail, eyre, Context};
 
+/// Sends requests to the daemon.
+///
+/// Clones share the same connection, e.g. for sending heartbeats from a
+/// background thread.
+#[derive(Clone)]
 pub(crate) struct ControlChannel {
-    channel: DaemonChannel,
+    channel: Arc<Mutex<DaemonChannel>>,
     clock: Arc<HLC>,
 }
 
@@ -43,16 +51,25 @@ impl ControlChannel {
     ) -> eyre::Result<Self> {
         channel.register(dataflow_id, node_id.clone(), clock.new_timestamp())?;
 
-        Ok(Self { channel, clock })
+        Ok(Self {
+            channel: Arc::new(Mutex::new(channel)),
+            clock,
+        })
     }
 
-    pub fn report_outputs_done(&mut self) -> eyre::Result<()> {
-        let reply = self
-            .channel
+    fn request(&self, request: DaemonRequest) -> eyre::Result<DaemonReply> {
+        self.channel
+            .lock()
+            .map_err(|_| eyre!("control channel mutex is poisoned"))?
             .request(&Timestamped {
-                inner: DaemonRequest::OutputsDone,
+                inner: request,
                 timestamp: self.clock.new_timestamp(),
             })
+    }
+
+    pub fn report_outputs_done(&mut self) -> eyre::Result<()> {
+        let reply = self
+            .request(DaemonRequest::OutputsDone)
             .wrap_err("failed to report outputs done to dora-daemon")?;
         match reply {
             dora_core::daemon_messages::DaemonReply::Result(result) => result
@@ -65,11 +82,7 @@ impl ControlChannel {
 
     pub fn report_closed_outputs(&mut self, outputs: Vec<DataId>) -> eyre::Result<()> {
         let reply = self
-            .channel
-            .request(&Timestamped {
-                inner: DaemonRequest::CloseOutputs(outputs),
-                timestamp: self.clock.new_timestamp(),
-            })
+            .request(DaemonRequest::CloseOutputs(outputs))
             .wrap_err("failed to report closed outputs to dora-daemon")?;
         match reply {
             dora_core::daemon_messages::DaemonReply::Result(result) => result
@@ -92,15 +105,41 @@ impl ControlChannel {
             data,
         };
         let reply = self
-            .channel
-            .request(&Timestamped {
-                inner: request,
-                timestamp: self.clock.new_timestamp(),
-            })
+            .request(request)
             .wrap_err("failed to send SendMessage request to dora-daemon")?;
         match reply {
             dora_core::daemon_messages::DaemonReply::Empty => Ok(()),
             other => bail!("unexpected SendMessage reply: {other:?}"),
         }
     }
+
+    pub fn send_heartbeat(&self, health: NodeHealth) -> eyre::Result<()> {
+        let reply = self
+            .request(DaemonRequest::Heartbeat { health })
+            .wrap_err("failed to send heartbeat to dora-daemon")?;
+        match reply {
+            DaemonReply::Empty => Ok(()),
+            other => bail!("unexpected heartbeat reply: {other:?}"),
+        }
+    }
+
+    pub fn report_operator_error(&self, error: OperatorError) -> eyre::Result<()> {
+        let reply = self
+            .request(DaemonRequest::ReportOperatorError(error))
+            .wrap_err("failed to report operator error to dora-daemon")?;
+        match reply {
+            DaemonReply::Empty => Ok(()),
+            other => bail!("unexpected operator error reply: {other:?}"),
+        }
+    }
+
+    pub fn send_log(&self, record: NodeLogRecord) -> eyre::Result<()> {
+        let reply = self
+            .request(DaemonRequest::Log(record))
+            .wrap_err("failed to send log record to dora-daemon")?;
+        match reply {
+            DaemonReply::Empty => Ok(()),
+            other => bail!("unexpected log reply: {other:?}"),
+        }
+    }
 }
@@ -0,0 +1,54 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use super::control_channel::ControlChannel;
+use dora_core::{config::NodeId, daemon_messages::NodeHealth};
+use flume::RecvTimeoutError;
+
+/// Background thread that sends heartbeats to the daemon.
+///
+/// The thread stops when the handle is dropped.
+pub(crate) struct HeartbeatThread {
+    stop: Option<flume::Sender<()>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl HeartbeatThread {
+    pub fn spawn(
+        node_id: NodeId,
+        channel: ControlChannel,
+        health: Arc<Mutex<NodeHealth>>,
+        interval: Duration,
+    ) -> Self {
+        let (stop, stop_rx) = flume::bounded(0);
+        let handle = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                let health = match health.lock() {
+                    Ok(health) => health.clone(),
+                    Err(_) => break,
+                };
+                if let Err(err) = channel.send_heartbeat(health) {
+                    tracing::warn!("node `{node_id}` failed to send heartbeat: {err:?}");
+                }
+            }
+        });
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for HeartbeatThread {
+    fn drop(&mut self) {
+        // disconnects the stop channel, which wakes up the thread
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                tracing::warn!("heartbeat thread panicked");
+            }
+        }
+    }
+}
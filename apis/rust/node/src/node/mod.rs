@@ -1,36 +1,56 @@
-use crate::{daemon_connection::DaemonChannel, EventStream};
+use crate::{
+    daemon_connection::DaemonChannel,
+    event_stream::{buffer_into_arrow_array, InputContext},
+    EventStream,
+};
 
 use self::{
     arrow_utils::{copy_array_into_sample, required_data_size},
     control_channel::ControlChannel,
     drop_stream::DropStream,
+    heartbeat::HeartbeatThread,
 };
 use aligned_vec::{AVec, ConstAlign};
-use arrow::array::Array;
+use arrow::{
+    array::{Array, StructArray},
+    record_batch::RecordBatch,
+};
+#[cfg(unix)]
+use dora_core::topics::{LocalSocketAddr, DORA_DAEMON_LOCAL_SOCKET_ENV};
 use dora_core::{
     config::{DataId, NodeId, NodeRunConfig},
-    daemon_messages::{DaemonRequest, DataMessage, DataflowId, DropToken, NodeConfig, Timestamped},
-    descriptor::Descriptor,
-    message::{uhlc, ArrowTypeInfo, Metadata, MetadataParameters},
+    daemon_messages::{
+        DaemonRequest, DataMessage, DataflowId, DropToken, NodeConfig, NodeHealth, NodeLogRecord,
+        OperatorError, Timestamped,
+    },
+    descriptor::{DataflowGraph, Descriptor},
+    message::{uhlc, ArrowTypeInfo, Metadata, MetadataParameters, Parameter, SendTime},
     topics::{DORA_DAEMON_LOCAL_LISTEN_PORT_DEFAULT, LOCALHOST},
 };
 
 use eyre::{bail, WrapErr};
 use shared_memory_extended::{Shmem, ShmemConf};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
     ops::{Deref, DerefMut},
-    sync::Arc,
+    ptr::NonNull,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 use tracing::info;
 
+use crate::LogLevel;
+
 #[cfg(feature = "tracing")]
 use dora_tracing::set_up_tracing;
 
 pub mod arrow_utils;
+mod asynchronous;
 mod control_channel;
 mod drop_stream;
+mod heartbeat;
+
+pub use asynchronous::AsyncDoraNode;
 
 pub const ZERO_COPY_THRESHOLD: usize = 4096;
 
@@ -46,6 +66,12 @@ pub struct DoraNode {
     cache: VecDeque<ShmemHandle>,
 
     dataflow_descriptor: Descriptor,
+
+    /// Health that is sent with the heartbeats.
+    health: Arc<Mutex<NodeHealth>>,
+    heartbeat: Option<HeartbeatThread>,
+    /// Trace context for outputs that are sent without one.
+    input_context: InputContext,
 }
 
 impl DoraNode {
@@ -65,11 +91,27 @@ impl DoraNode {
             serde_yaml::from_str(&raw).context("failed to deserialize operator config")?
         };
         #[cfg(feature = "tracing")]
-        set_up_tracing(&node_config.node_id.to_string())
+        set_up_tracing(node_config.node_id.as_ref())
             .context("failed to set up tracing subscriber")?;
         Self::init(node_config)
     }
 
+    /// Connects to the daemon of the local machine, using the unix socket set in
+    /// the `DORA_DAEMON_LOCAL_SOCKET` env variable if there is one.
+    fn connect_to_local_daemon() -> eyre::Result<DaemonChannel> {
+        #[cfg(unix)]
+        if let Ok(addr) = std::env::var(DORA_DAEMON_LOCAL_SOCKET_ENV) {
+            let addr: LocalSocketAddr = addr
+                .parse()
+                .wrap_err_with(|| format!("invalid `{DORA_DAEMON_LOCAL_SOCKET_ENV}` value"))?;
+            return DaemonChannel::new_unix_socket(&addr)
+                .context("Could not connect to the daemon");
+        }
+
+        let daemon_address = (LOCALHOST, DORA_DAEMON_LOCAL_LISTEN_PORT_DEFAULT).into();
+        DaemonChannel::new_tcp(daemon_address).context("Could not connect to the daemon")
+    }
+
     /// Initiate a node from a dataflow id and a node id.
     ///
     /// ```no_run
@@ -81,10 +123,7 @@ impl DoraNode {
     ///
     pub fn init_from_node_id(node_id: NodeId) -> eyre::Result<(Self, EventStream)> {
         // Make sure that the node is initialized outside of dora start.
-        let daemon_address = (LOCALHOST, DORA_DAEMON_LOCAL_LISTEN_PORT_DEFAULT).into();
-
-        let mut channel =
-            DaemonChannel::new_tcp(daemon_address).context("Could not connect to the daemon")?;
+        let mut channel = Self::connect_to_local_daemon()?;
         let clock = Arc::new(uhlc::HLC::default());
 
         let reply = channel
@@ -135,6 +174,21 @@ impl DoraNode {
             ControlChannel::init(dataflow_id, &node_id, &daemon_communication, clock.clone())
                 .wrap_err("failed to init control channel")?;
 
+        let health = Arc::new(Mutex::new(NodeHealth::default()));
+        let heartbeat = dataflow_descriptor
+            .nodes
+            .iter()
+            .find(|n| n.id == node_id)
+            .and_then(|n| n.heartbeat.as_ref())
+            .map(|config| {
+                HeartbeatThread::spawn(
+                    node_id.clone(),
+                    control_channel.clone(),
+                    health.clone(),
+                    config.interval(),
+                )
+            });
+
         let node = Self {
             id: node_id,
             dataflow_id,
@@ -145,6 +199,9 @@ impl DoraNode {
             drop_stream,
             cache: VecDeque::new(),
             dataflow_descriptor,
+            health,
+            heartbeat,
+            input_context: event_stream.input_context(),
         };
         Ok((node, event_stream))
     }
@@ -209,6 +266,19 @@ impl DoraNode {
         Ok(())
     }
 
+    /// Sends tabular data as a struct array with one field per column.
+    ///
+    /// Receivers can convert the data back into a [`RecordBatch`] through
+    /// `RecordBatch::try_from(&data)`, which doesn't copy the columns.
+    pub fn send_record_batch(
+        &mut self,
+        output_id: DataId,
+        parameters: MetadataParameters,
+        batch: RecordBatch,
+    ) -> eyre::Result<()> {
+        self.send_output(output_id, parameters, StructArray::from(batch))
+    }
+
     pub fn send_output_bytes(
         &mut self,
         output_id: DataId,
@@ -221,6 +291,26 @@ impl DoraNode {
         })
     }
 
+    /// Sends the given byte slices as a single output message, in order.
+    ///
+    /// The slices are copied directly into the output sample, which avoids
+    /// concatenating e.g. a header and a large frame into a temporary buffer.
+    pub fn send_output_vectored(
+        &mut self,
+        output_id: DataId,
+        parameters: MetadataParameters,
+        parts: &[&[u8]],
+    ) -> eyre::Result<()> {
+        let data_len = parts.iter().map(|part| part.len()).sum();
+        self.send_output_raw(output_id, parameters, data_len, |sample| {
+            let mut offset = 0;
+            for part in parts {
+                sample[offset..][..part.len()].copy_from_slice(part);
+                offset += part.len();
+            }
+        })
+    }
+
     pub fn send_typed_output<F>(
         &mut self,
         output_id: DataId,
@@ -250,11 +340,13 @@ impl DoraNode {
         if !self.node_config.outputs.contains(&output_id) {
             eyre::bail!("unknown output");
         }
-        let metadata = Metadata::from_parameters(
-            self.clock.new_timestamp(),
-            type_info,
-            parameters.into_owned(),
-        );
+        let mut parameters = parameters.into_owned();
+        if parameters.open_telemetry_context.is_empty() {
+            parameters.open_telemetry_context = self.input_context.get();
+        }
+        let mut metadata =
+            Metadata::from_parameters(self.clock.new_timestamp(), type_info, parameters);
+        metadata.set_send_time(Some(SendTime::now()));
 
         let (data, shmem) = match sample {
             Some(sample) => sample.finalize(),
@@ -287,10 +379,89 @@ impl DoraNode {
         Ok(())
     }
 
+    /// Reports the health of this node to the daemon.
+    ///
+    /// The health is sent immediately and with all following heartbeats, until
+    /// it is changed again. It's shown in the node statistics, e.g. to report
+    /// a disconnected sensor.
+    pub fn report_health(&mut self, health: NodeHealth) -> eyre::Result<()> {
+        *self
+            .health
+            .lock()
+            .map_err(|_| eyre::eyre!("health mutex is poisoned"))? = health.clone();
+        self.control_channel
+            .send_heartbeat(health)
+            .wrap_err("failed to report health to daemon")
+    }
+
+    /// Forwards a structured operator error to the daemon, which passes it on
+    /// to the coordinator.
+    ///
+    /// Used by runtime nodes when one of their operators fails.
+    pub fn report_operator_error(&mut self, error: OperatorError) -> eyre::Result<()> {
+        self.control_channel.report_operator_error(error)
+    }
+
+    /// Emits a structured log record through the daemon.
+    ///
+    /// Unlike messages printed to stdout, the record is tagged with the
+    /// dataflow and node ID and keeps its `fields` as key/value pairs. It is
+    /// written to the log file of the node, so it shows up in `dora logs`,
+    /// and forwarded to the coordinator.
+    ///
+    /// ```no_run
+    /// use dora_node_api::{DoraNode, LogLevel, Parameter};
+    ///
+    /// let (mut node, mut events) = DoraNode::init_from_env().expect("Could not init node.");
+    /// let fields = [("frame".to_owned(), Parameter::Integer(42))].into();
+    /// node.log(LogLevel::Warn, "camera frame arrived late", fields)
+    ///     .expect("failed to send log record");
+    /// ```
+    pub fn log(
+        &mut self,
+        level: LogLevel,
+        message: impl Into<String>,
+        fields: BTreeMap<String, Parameter>,
+    ) -> eyre::Result<()> {
+        self.log_record(NodeLogRecord {
+            level,
+            message: message.into(),
+            operator_id: None,
+            fields,
+        })
+    }
+
+    /// Sends a log record to the daemon, see [`log`](Self::log).
+    ///
+    /// Used by runtime nodes to forward the records of their operators.
+    pub fn log_record(&mut self, record: NodeLogRecord) -> eyre::Result<()> {
+        self.control_channel.send_log(record)
+    }
+
+    /// Registers additional outputs of this node.
+    ///
+    /// Used by runtime nodes for the outputs of operators that are loaded after
+    /// the node was started.
+    pub fn add_outputs(&mut self, outputs: impl IntoIterator<Item = DataId>) {
+        self.node_config.outputs.extend(outputs);
+    }
+
     pub fn id(&self) -> &NodeId {
         &self.id
     }
 
+    /// Moves the node to a background thread for use from async code.
+    ///
+    /// See [`AsyncDoraNode`] for details.
+    pub fn into_async(self) -> eyre::Result<AsyncDoraNode> {
+        AsyncDoraNode::new(self)
+    }
+
+    /// Returns the clock that is used to timestamp the outputs of this node.
+    pub fn clock(&self) -> Arc<uhlc::HLC> {
+        self.clock.clone()
+    }
+
     pub fn dataflow_id(&self) -> &DataflowId {
         &self.dataflow_id
     }
@@ -375,11 +546,19 @@ impl DoraNode {
     pub fn dataflow_descriptor(&self) -> &Descriptor {
         &self.dataflow_descriptor
     }
+
+    /// Returns the resolved nodes of the dataflow together with the declared
+    /// inputs and outputs of this node.
+    pub fn graph(&self) -> eyre::Result<DataflowGraph> {
+        DataflowGraph::for_node(&self.dataflow_descriptor, &self.id)
+    }
 }
 
 impl Drop for DoraNode {
     #[tracing::instrument(skip(self), fields(self.id = %self.id), level = "trace")]
     fn drop(&mut self) {
+        self.heartbeat.take();
+
         // close all outputs first to notify subscribers as early as possible
         if let Err(err) = self
             .control_channel
@@ -436,6 +615,20 @@ pub struct DataSample {
 }
 
 impl DataSample {
+    /// Converts the sample into an Arrow array without copying its data.
+    ///
+    /// This is useful for passing outputs to receivers in the same process.
+    pub fn into_arrow_array(
+        self,
+        type_info: &ArrowTypeInfo,
+    ) -> eyre::Result<arrow::array::ArrayData> {
+        let ptr = NonNull::from(self.deref()).cast::<u8>();
+        let len = self.len;
+        let buffer =
+            unsafe { arrow::buffer::Buffer::from_custom_allocation(ptr, len, Arc::new(self)) };
+        buffer_into_arrow_array(&buffer, type_info)
+    }
+
     fn finalize(self) -> (Option<DataMessage>, Option<(ShmemHandle, DropToken)>) {
         match self.inner {
             DataSampleInner::Shmem(shared_memory) => {
@@ -0,0 +1,168 @@
+//! Runs a node against a recording instead of a live dataflow.
+//!
+//! The recorded outputs are delivered as [`Event::Input`](crate::Event::Input)
+//! events on a regular [`EventStream`], followed by `InputClosed` events and
+//! `AllInputsClosed` once the recording is exhausted. This makes it possible to
+//! test the logic of a node binary without starting a daemon:
+//!
+//! ```no_run
+//! use dora_node_api::{replay, Event};
+//!
+//! let mut events = replay::open("out/<dataflow-id>/record")?;
+//! while let Some(event) = events.recv() {
+//!     if let Event::Input { id, .. } = event {
+//!         println!("replayed input `{id}`");
+//!     }
+//! }
+//! # Ok::<(), eyre::Report>(())
+//! ```
+
+use crate::{event_stream::EventItem, EventStream};
+use dora_core::{
+    config::{DataId, NodeId},
+    daemon_messages::{DataMessage, NodeEvent},
+    message::{uhlc, Metadata},
+    record::{RecordReader, RecordedMessage},
+};
+use eyre::Context;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::Arc,
+    time::Instant,
+};
+
+/// Configures how a recording is replayed, see [`open_with`].
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOptions {
+    inputs: BTreeMap<(NodeId, DataId), DataId>,
+    speed: Option<f64>,
+}
+
+impl ReplayOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delivers the recorded messages of the given output as input `input_id`.
+    ///
+    /// Once an input is mapped, recorded outputs without a mapping are skipped.
+    /// Without any mappings, all recorded outputs are delivered and the output
+    /// ID is used as input ID.
+    pub fn input(mut self, input_id: DataId, node_id: NodeId, output_id: DataId) -> Self {
+        self.inputs.insert((node_id, output_id), input_id);
+        self
+    }
+
+    /// Keeps the relative timing of the recorded messages, scaled by `speed`.
+    ///
+    /// By default, messages are delivered as fast as the node receives them.
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+}
+
+/// Opens the recording in the given record directory with the default options.
+pub fn open(recording_path: impl AsRef<Path>) -> eyre::Result<EventStream> {
+    open_with(recording_path, ReplayOptions::default())
+}
+
+/// Opens the recording in the given record directory.
+pub fn open_with(
+    recording_path: impl AsRef<Path>,
+    options: ReplayOptions,
+) -> eyre::Result<EventStream> {
+    if let Some(speed) = options.speed {
+        if !(speed > 0.0 && speed.is_finite()) {
+            eyre::bail!("invalid replay speed `{speed}`, must be positive");
+        }
+    }
+    let reader = RecordReader::open(recording_path.as_ref())
+        .wrap_err("failed to open recording for replay")?;
+
+    let clock = Arc::new(uhlc::HLC::default());
+    let (tx, rx) = flume::bounded(0);
+    let thread_clock = clock.clone();
+    std::thread::spawn(move || replay_loop(reader, options, tx, thread_clock));
+
+    Ok(EventStream::init_local(
+        NodeId::from("replay".to_owned()),
+        rx,
+        clock,
+    ))
+}
+
+fn replay_loop(
+    reader: RecordReader,
+    options: ReplayOptions,
+    tx: flume::Sender<EventItem>,
+    clock: Arc<uhlc::HLC>,
+) {
+    let start = Instant::now();
+    let mut first_timestamp = None;
+    let mut inputs: BTreeSet<DataId> = options.inputs.values().cloned().collect();
+
+    for message in reader {
+        let RecordedMessage {
+            node_id,
+            output_id,
+            metadata,
+            data,
+        } = match message {
+            Ok(message) => message,
+            Err(err) => {
+                let _ = tx.send(EventItem::FatalError(
+                    err.wrap_err("failed to read recording"),
+                ));
+                return;
+            }
+        };
+        let input_id = if options.inputs.is_empty() {
+            output_id
+        } else {
+            match options.inputs.get(&(node_id, output_id)) {
+                Some(input_id) => input_id.clone(),
+                None => continue,
+            }
+        };
+
+        if let Some(speed) = options.speed {
+            let recorded_time = metadata.timestamp().get_time().to_duration();
+            let offset = recorded_time
+                .saturating_sub(*first_timestamp.get_or_insert(recorded_time))
+                .div_f64(speed);
+            if let Some(remaining) = (start + offset).checked_duration_since(Instant::now()) {
+                std::thread::sleep(remaining);
+            }
+        }
+
+        inputs.insert(input_id.clone());
+        let event = NodeEvent::Input {
+            id: input_id,
+            metadata: Metadata::from_parameters(
+                clock.new_timestamp(),
+                metadata.type_info,
+                metadata.parameters,
+            ),
+            data: data.map(DataMessage::Vec),
+        };
+        if !send(&tx, event) {
+            return;
+        }
+    }
+
+    for id in inputs {
+        if !send(&tx, NodeEvent::InputClosed { id }) {
+            return;
+        }
+    }
+    send(&tx, NodeEvent::AllInputsClosed);
+}
+
+/// Returns `false` if the event stream was dropped.
+fn send(tx: &flume::Sender<EventItem>, event: NodeEvent) -> bool {
+    // the event owns its data, so there is no drop token to acknowledge
+    let (ack_channel, _) = flume::bounded(0);
+    tx.send(EventItem::NodeEvent { event, ack_channel }).is_ok()
+}
@@ -0,0 +1,210 @@
+//! Request/reply communication on top of regular inputs and outputs.
+//!
+//! A [`Client`] sends requests on one of its outputs, which is connected to an
+//! input of the [`Service`] node. The service sends its responses on an output
+//! that is connected back to an input of the client. Requests and responses
+//! are matched through a correlation ID in the metadata entry
+//! [`REQUEST_ID_KEY`], so multiple clients can share the response output of a
+//! service.
+//!
+//! Both sides are driven by the regular event loop of the node, so other inputs
+//! keep being processed while a request is pending.
+//!
+//! ```no_run
+//! use dora_node_api::{service::Client, DoraNode, Event};
+//! use std::time::Duration;
+//!
+//! let (mut node, mut events) = DoraNode::init_from_env()?;
+//! let mut map_client = Client::new(
+//!     "map_request".to_owned().into(),
+//!     "map_response".to_owned().into(),
+//!     Duration::from_secs(1),
+//! );
+//!
+//! let request = map_client.request(&mut node, arrow::array::UInt8Array::from(vec![0]))?;
+//! while let Some(event) = events.recv_timeout(Duration::from_millis(100)) {
+//!     if let Some(response) = map_client.parse_response(&event) {
+//!         println!("got response to request {}: {:?}", response.request_id, response.data);
+//!     }
+//!     for request_id in map_client.timed_out() {
+//!         println!("request {request_id} timed out");
+//!     }
+//! }
+//! # Ok::<(), eyre::Report>(())
+//! ```
+
+use crate::{DoraNode, Event, Metadata, MetadataParameters, Parameter};
+use arrow::array::Array;
+use dora_arrow_convert::ArrowData;
+use dora_core::config::DataId;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// Metadata key of the correlation ID that links responses to requests.
+pub const REQUEST_ID_KEY: &str = "dora.request_id";
+
+/// Sends requests to a [`Service`] and matches the responses.
+pub struct Client {
+    request_output: DataId,
+    response_input: DataId,
+    timeout: Duration,
+    next_id: u64,
+    /// Deadlines of the pending requests, by request ID.
+    pending: BTreeMap<String, Instant>,
+}
+
+/// A response to a request of a [`Client`].
+#[derive(Debug)]
+pub struct Response {
+    pub request_id: String,
+    pub metadata: Metadata,
+    pub data: ArrowData,
+}
+
+impl Client {
+    /// Creates a client that sends requests on `request_output` and receives
+    /// the responses on `response_input`.
+    ///
+    /// Requests without a response after `timeout` are reported by
+    /// [`timed_out`](Self::timed_out).
+    pub fn new(request_output: DataId, response_input: DataId, timeout: Duration) -> Self {
+        Self {
+            request_output,
+            response_input,
+            timeout,
+            next_id: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Sends a request and returns its ID.
+    pub fn request(&mut self, node: &mut DoraNode, data: impl Array) -> eyre::Result<String> {
+        self.request_with_parameters(node, MetadataParameters::default(), data)
+    }
+
+    pub fn request_with_parameters(
+        &mut self,
+        node: &mut DoraNode,
+        mut parameters: MetadataParameters,
+        data: impl Array,
+    ) -> eyre::Result<String> {
+        // the node ID makes the ID unique among all clients of a service
+        let request_id = format!("{}/{}", node.id(), self.next_id);
+        self.next_id += 1;
+        parameters.insert(REQUEST_ID_KEY, request_id.as_str());
+        node.send_output(self.request_output.clone(), parameters, data)?;
+        self.pending
+            .insert(request_id.clone(), Instant::now() + self.timeout);
+        Ok(request_id)
+    }
+
+    /// Returns the response if the event is the response to a pending request.
+    ///
+    /// Responses to other clients and late responses to timed out requests
+    /// are ignored.
+    pub fn parse_response(&mut self, event: &Event) -> Option<Response> {
+        let Event::Input { id, metadata, data } = event else {
+            return None;
+        };
+        if *id != self.response_input {
+            return None;
+        }
+        let request_id = metadata.parameters.get(REQUEST_ID_KEY)?.as_str()?;
+        self.pending.remove(request_id)?;
+        Some(Response {
+            request_id: request_id.to_owned(),
+            metadata: metadata.clone(),
+            data: ArrowData(data.0.clone()),
+        })
+    }
+
+    /// Removes the pending requests whose timeout expired and returns their IDs.
+    pub fn timed_out(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        let expired: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &expired {
+            self.pending.remove(id);
+        }
+        expired
+    }
+
+    /// Number of requests that are still waiting for a response.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Receives requests from [`Client`]s and sends the responses.
+pub struct Service {
+    request_inputs: Vec<DataId>,
+    response_output: DataId,
+}
+
+/// A request received by a [`Service`].
+#[derive(Debug)]
+pub struct Request {
+    pub request_id: String,
+    /// The input on which the request was received.
+    pub input_id: DataId,
+    pub metadata: Metadata,
+    pub data: ArrowData,
+}
+
+impl Service {
+    /// Creates a service that receives requests on the given inputs, one per
+    /// client node, and sends all responses on `response_output`.
+    pub fn new(request_inputs: Vec<DataId>, response_output: DataId) -> Self {
+        Self {
+            request_inputs,
+            response_output,
+        }
+    }
+
+    /// Returns the request if the event is an input event of a request input.
+    ///
+    /// Inputs without a request ID are ignored with a warning.
+    pub fn parse_request(&self, event: &Event) -> Option<Request> {
+        let Event::Input { id, metadata, data } = event else {
+            return None;
+        };
+        if !self.request_inputs.contains(id) {
+            return None;
+        }
+        let Some(request_id) = metadata
+            .parameters
+            .get(REQUEST_ID_KEY)
+            .and_then(|id| id.as_str())
+        else {
+            tracing::warn!("ignoring input `{id}` without request ID");
+            return None;
+        };
+        Some(Request {
+            request_id: request_id.to_owned(),
+            input_id: id.clone(),
+            metadata: metadata.clone(),
+            data: ArrowData(data.0.clone()),
+        })
+    }
+
+    /// Sends the response to the given request.
+    pub fn respond(
+        &self,
+        node: &mut DoraNode,
+        request: &Request,
+        data: impl Array,
+    ) -> eyre::Result<()> {
+        let mut parameters = MetadataParameters::default();
+        parameters.insert(
+            REQUEST_ID_KEY,
+            Parameter::String(request.request_id.clone()),
+        );
+        node.send_output(self.response_output.clone(), parameters, data)
+    }
+}
@@ -0,0 +1,171 @@
+//! Typed inputs and outputs that (de)serialize their data through `serde`.
+//!
+//! Values are sent as byte arrays, encoded in the [`Encoding`] that is chosen
+//! for the output. Since the encoding is not part of the message, the
+//! receiving [`TypedInput`] must use the same encoding as the sender.
+//!
+//! ```no_run
+//! use dora_node_api::{typed::{Encoding, TypedInput, TypedPublisher}, DoraNode};
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Pose {
+//!     x: f64,
+//!     y: f64,
+//! }
+//!
+//! let (mut node, mut events) = DoraNode::init_from_env()?;
+//! let pose_out = TypedPublisher::<Pose>::new("pose".to_owned().into(), Encoding::Cdr);
+//! let pose_in = TypedInput::<Pose>::new("odometry".to_owned().into(), Encoding::Cdr);
+//!
+//! for pose in events.typed_inputs(&pose_in) {
+//!     pose_out.publish(&mut node, &pose?)?;
+//! }
+//! # Ok::<(), eyre::Report>(())
+//! ```
+
+use crate::{DoraNode, Event, EventStream, MetadataParameters};
+use dora_core::config::DataId;
+use eyre::Context;
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+
+/// Serialization format of a typed output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Compact binary format of the [`bincode`] crate.
+    #[default]
+    Bincode,
+    /// UTF-8 encoded JSON, e.g. for receivers in other languages.
+    Json,
+    /// Little-endian CDR as used by DDS, without encapsulation header.
+    Cdr,
+}
+
+impl Encoding {
+    pub fn encode<T: Serialize + ?Sized>(self, value: &T) -> eyre::Result<Vec<u8>> {
+        match self {
+            Encoding::Bincode => bincode::serialize(value).wrap_err("failed to encode as bincode"),
+            Encoding::Json => serde_json::to_vec(value).wrap_err("failed to encode as JSON"),
+            // `&T` is sized even if `T` isn't
+            Encoding::Cdr => cdr_encoding::to_vec::<_, byteorder::LittleEndian>(&value)
+                .map_err(|err| eyre::eyre!("failed to encode as CDR: {err}")),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, data: &[u8]) -> eyre::Result<T> {
+        match self {
+            Encoding::Bincode => bincode::deserialize(data).wrap_err("failed to decode bincode"),
+            Encoding::Json => serde_json::from_slice(data).wrap_err("failed to decode JSON"),
+            Encoding::Cdr => cdr_encoding::from_bytes::<_, byteorder::LittleEndian>(data)
+                .map(|(value, _len)| value)
+                .map_err(|err| eyre::eyre!("failed to decode CDR: {err}")),
+        }
+    }
+}
+
+/// Sends values of type `T` on an output of the node.
+pub struct TypedPublisher<T: ?Sized> {
+    output_id: DataId,
+    encoding: Encoding,
+    _type: PhantomData<fn(&T)>,
+}
+
+impl<T: Serialize + ?Sized> TypedPublisher<T> {
+    pub fn new(output_id: DataId, encoding: Encoding) -> Self {
+        Self {
+            output_id,
+            encoding,
+            _type: PhantomData,
+        }
+    }
+
+    pub fn output_id(&self) -> &DataId {
+        &self.output_id
+    }
+
+    pub fn publish(&self, node: &mut DoraNode, value: &T) -> eyre::Result<()> {
+        self.publish_with_parameters(node, MetadataParameters::default(), value)
+    }
+
+    pub fn publish_with_parameters(
+        &self,
+        node: &mut DoraNode,
+        parameters: MetadataParameters,
+        value: &T,
+    ) -> eyre::Result<()> {
+        let data = self
+            .encoding
+            .encode(value)
+            .wrap_err_with(|| format!("failed to encode output `{}`", self.output_id))?;
+        node.send_output_bytes(self.output_id.clone(), parameters, data.len(), &data)
+    }
+}
+
+/// Decodes values of type `T` from an input of the node.
+pub struct TypedInput<T> {
+    input_id: DataId,
+    encoding: Encoding,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> TypedInput<T> {
+    pub fn new(input_id: DataId, encoding: Encoding) -> Self {
+        Self {
+            input_id,
+            encoding,
+            _type: PhantomData,
+        }
+    }
+
+    pub fn input_id(&self) -> &DataId {
+        &self.input_id
+    }
+
+    /// Decodes the given event if it's an input event for this input.
+    ///
+    /// Returns `None` for all other events.
+    pub fn parse(&self, event: &Event) -> Option<eyre::Result<T>> {
+        match event {
+            Event::Input { id, data, .. } if *id == self.input_id => {
+                let result = <&[u8]>::try_from(data)
+                    .wrap_err("expected a byte array")
+                    .and_then(|bytes| self.encoding.decode(bytes))
+                    .wrap_err_with(|| format!("failed to decode input `{id}`"));
+                Some(result)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Iterator over the decoded values of a single input, see
+/// [`EventStream::typed_inputs`].
+pub struct TypedInputs<'a, T> {
+    events: &'a mut EventStream,
+    input: &'a TypedInput<T>,
+}
+
+impl<'a, T> TypedInputs<'a, T> {
+    pub(crate) fn new(events: &'a mut EventStream, input: &'a TypedInput<T>) -> Self {
+        Self { events, input }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for TypedInputs<'_, T> {
+    type Item = eyre::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let event = self.events.recv()?;
+            if let Some(value) = self.input.parse(&event) {
+                return Some(value);
+            }
+            match event {
+                Event::Stop | Event::AllInputsClosed => return None,
+                Event::InputClosed { id } if id == self.input.input_id => return None,
+                Event::Error(err) => return Some(Err(eyre::eyre!("received error event: {err}"))),
+                other => tracing::debug!("ignoring event while waiting for typed input: {other:?}"),
+            }
+        }
+    }
+}
@@ -34,6 +34,19 @@ fn register_operator_impl(item: &TokenStream2) -> syn::Result<TokenStream2> {
         const _DORA_INIT_OPERATOR: dora_operator_api::types::DoraInitOperator = dora_operator_api::types::DoraInitOperator {
             init_operator: dora_init_operator,
         };
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dora_init_operator_with_config(
+            config: dora_operator_api::types::safer_ffi::char_p::char_p_ref<'_>,
+        ) -> dora_operator_api::types::DoraInitResult {
+            dora_operator_api::raw::dora_init_operator_with_config::<#operator_ty>(config.to_str())
+        }
+
+        const _DORA_INIT_OPERATOR_WITH_CONFIG: dora_operator_api::types::DoraInitOperatorWithConfig = dora_operator_api::types::DoraInitOperatorWithConfig {
+            init_operator_with_config: dora_operator_api::types::InitOperatorWithConfigFn(
+                dora_init_operator_with_config,
+            ),
+        };
     };
 
     let drop = quote! {
@@ -66,9 +79,66 @@ fn register_operator_impl(item: &TokenStream2) -> syn::Result<TokenStream2> {
         };
     };
 
+    let lifecycle = quote! {
+        #[no_mangle]
+        pub unsafe extern "C" fn dora_on_configure(
+            config: dora_operator_api::types::safer_ffi::char_p::char_p_ref<'_>,
+            operator_context: *mut std::ffi::c_void,
+        ) -> dora_operator_api::types::DoraResult {
+            dora_operator_api::raw::dora_on_configure::<#operator_ty>(config.to_str(), operator_context)
+        }
+
+        const _DORA_ON_CONFIGURE: dora_operator_api::types::DoraOnConfigure = dora_operator_api::types::DoraOnConfigure {
+            on_configure: dora_operator_api::types::OnConfigureFn(dora_on_configure),
+        };
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dora_on_start(operator_context: *mut std::ffi::c_void)
+            -> dora_operator_api::types::DoraResult
+        {
+            dora_operator_api::raw::dora_on_start::<#operator_ty>(operator_context)
+        }
+
+        const _DORA_ON_START: dora_operator_api::types::DoraOnStart = dora_operator_api::types::DoraOnStart {
+            on_start: dora_on_start,
+        };
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dora_on_stop(operator_context: *mut std::ffi::c_void)
+            -> dora_operator_api::types::DoraResult
+        {
+            dora_operator_api::raw::dora_on_stop::<#operator_ty>(operator_context)
+        }
+
+        const _DORA_ON_STOP: dora_operator_api::types::DoraOnStop = dora_operator_api::types::DoraOnStop {
+            on_stop: dora_on_stop,
+        };
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dora_on_metrics(
+            record_metric: &dora_operator_api::types::RecordMetric,
+            operator_context: *mut std::ffi::c_void,
+        ) -> dora_operator_api::types::DoraResult {
+            dora_operator_api::raw::dora_on_metrics::<#operator_ty>(record_metric, operator_context)
+        }
+
+        const _DORA_ON_METRICS: dora_operator_api::types::DoraOnMetrics = dora_operator_api::types::DoraOnMetrics {
+            on_metrics: dora_operator_api::types::OnMetricsFn(dora_on_metrics),
+        };
+    };
+
+    let abi = quote! {
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static dora_operator_abi: dora_operator_api::types::DoraOperatorAbi =
+            dora_operator_api::types::DoraOperatorAbi::CURRENT;
+    };
+
     Ok(quote! {
+        #abi
         #init
         #drop
         #on_event
+        #lifecycle
     })
 }
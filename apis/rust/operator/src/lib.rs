@@ -7,6 +7,12 @@
 //! An operator requires to be registered and implement the `DoraOperator` trait.
 //! It is composed of an `on_event` method that defines the behaviour
 //! of the operator when there is an event such as receiving an input for example.
+//! The optional `on_configure`, `on_start`, and `on_stop` methods are invoked
+//! around the event processing, e.g. to open devices or to flush buffers.
+//!
+//! Operators can record their own metrics, e.g. the number of detections or
+//! the planning time, through [`DoraOutputSender::metrics`]. The runtime
+//! exports them together with the system metrics of the node.
 //!
 //! Try it out with:
 //!
@@ -24,7 +30,8 @@ pub use dora_operator_api_types as types;
 pub use types::DoraStatus;
 use types::{
     arrow::{self, array::Array},
-    Metadata, Output, SendOutput,
+    safer_ffi::closure::ArcDynFn1,
+    DoraResult, Metadata, MetricKind, MetricUpdate, Output, SendOutput,
 };
 
 pub mod raw;
@@ -39,15 +46,57 @@ pub enum Event<'a> {
 }
 
 pub trait DoraOperator: Default {
+    /// Creates the operator from the `config` of the operator in the dataflow
+    /// descriptor, which is passed as a JSON object.
+    ///
+    /// Defaults to [`Default::default`].
+    fn from_config(config: &str) -> Result<Self, String> {
+        let _ = config;
+        Ok(Self::default())
+    }
+
+    /// Receives the `_unstable_parameters` of the operator as a JSON object.
+    ///
+    /// Called once before [`on_start`](Self::on_start).
+    fn on_configure(&mut self, config: &str) -> Result<(), String> {
+        let _ = config;
+        Ok(())
+    }
+
+    /// Called once before the first event is delivered.
+    fn on_start(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
     #[allow(clippy::result_unit_err)] // we use a () error type only for testing
     fn on_event(
         &mut self,
         event: &Event,
         output_sender: &mut DoraOutputSender,
     ) -> Result<DoraStatus, String>;
+
+    /// Called when one of the `_unstable_timers` of the operator fires.
+    fn on_timer(
+        &mut self,
+        timer_id: &str,
+        output_sender: &mut DoraOutputSender,
+    ) -> Result<DoraStatus, String> {
+        let _ = (timer_id, output_sender);
+        Ok(DoraStatus::Continue)
+    }
+
+    /// Called once when the operator stops gracefully, before it is dropped.
+    ///
+    /// Not called if the operator fails.
+    fn on_stop(&mut self) -> Result<(), String> {
+        Ok(())
+    }
 }
 
-pub struct DoraOutputSender<'a>(&'a SendOutput);
+pub struct DoraOutputSender<'a> {
+    send_output: &'a SendOutput,
+    metrics: &'a Metrics,
+}
 
 impl DoraOutputSender<'_> {
     ///  Send an output from the operator:
@@ -56,14 +105,60 @@ impl DoraOutputSender<'_> {
     pub fn send(&mut self, id: String, data: impl Array) -> Result<(), String> {
         let (data_array, schema) =
             arrow::ffi::to_ffi(&data.into_data()).map_err(|err| err.to_string())?;
-        let result = self.0.send_output.call(Output {
+        let result = self.send_output.send_output.call(Output {
             id: id.into(),
             data_array,
             schema,
             metadata: Metadata {
-                open_telemetry_context: String::new().into(), // TODO
+                // set to the trace context of the current input by the runtime
+                open_telemetry_context: String::new().into(),
             },
         });
         result.into_result()
     }
+
+    /// Returns the handle for recording metrics of this operator.
+    pub fn metrics(&self) -> &Metrics {
+        self.metrics
+    }
+}
+
+/// Records metrics of an operator.
+///
+/// Metrics are identified by their name and tagged with the node and operator
+/// ID by the runtime. Recording is a no-op if the runtime doesn't support
+/// operator metrics.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    record_metric: Option<ArcDynFn1<DoraResult, MetricUpdate>>,
+}
+
+impl Metrics {
+    /// Adds `value` to the counter with the given name.
+    pub fn add_counter(&self, name: &str, value: f64) {
+        self.record(name, MetricKind::Counter, value)
+    }
+
+    /// Sets the gauge with the given name to `value`.
+    pub fn set_gauge(&self, name: &str, value: f64) {
+        self.record(name, MetricKind::Gauge, value)
+    }
+
+    /// Records `value` in the histogram with the given name.
+    pub fn record_histogram(&self, name: &str, value: f64) {
+        self.record(name, MetricKind::Histogram, value)
+    }
+
+    fn record(&self, name: &str, kind: MetricKind, value: f64) {
+        if let Some(record_metric) = &self.record_metric {
+            // metrics are best effort, so errors are ignored
+            let _ = record_metric
+                .call(MetricUpdate {
+                    name: name.to_owned().into(),
+                    kind,
+                    value,
+                })
+                .into_result();
+        }
+    }
 }
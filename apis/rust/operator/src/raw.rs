@@ -1,6 +1,6 @@
-use crate::{DoraOperator, DoraOutputSender, DoraStatus, Event};
+use crate::{DoraOperator, DoraOutputSender, DoraStatus, Event, Metrics};
 use dora_operator_api_types::{
-    arrow, DoraInitResult, DoraResult, OnEventResult, RawEvent, SendOutput,
+    arrow, DoraInitResult, DoraResult, OnEventResult, RawEvent, RecordMetric, SendOutput,
 };
 use std::ffi::c_void;
 
@@ -12,9 +12,34 @@ pub type OutputFnRaw = unsafe extern "C" fn(
     output_context: *const c_void,
 ) -> isize;
 
+/// The operator together with the state that the API keeps for it.
+///
+/// A pointer to this struct is passed to the runtime as operator context.
+struct OperatorState<O> {
+    operator: O,
+    metrics: Metrics,
+}
+
 pub unsafe fn dora_init_operator<O: DoraOperator>() -> DoraInitResult {
-    let operator: O = Default::default();
-    let ptr: *mut O = Box::leak(Box::new(operator));
+    init_result(O::default())
+}
+
+pub unsafe fn dora_init_operator_with_config<O: DoraOperator>(config: &str) -> DoraInitResult {
+    match O::from_config(config) {
+        Ok(operator) => init_result(operator),
+        Err(error) => DoraInitResult {
+            result: DoraResult::from_error(error),
+            operator_context: std::ptr::null_mut(),
+        },
+    }
+}
+
+fn init_result<O>(operator: O) -> DoraInitResult {
+    let state = OperatorState {
+        operator,
+        metrics: Metrics::default(),
+    };
+    let ptr: *mut OperatorState<O> = Box::leak(Box::new(state));
     let operator_context: *mut c_void = ptr.cast();
     DoraInitResult {
         result: DoraResult { error: None },
@@ -23,19 +48,62 @@ pub unsafe fn dora_init_operator<O: DoraOperator>() -> DoraInitResult {
 }
 
 pub unsafe fn dora_drop_operator<O>(operator_context: *mut c_void) -> DoraResult {
-    let raw: *mut O = operator_context.cast();
+    let raw: *mut OperatorState<O> = operator_context.cast();
     drop(unsafe { Box::from_raw(raw) });
     DoraResult { error: None }
 }
 
+pub unsafe fn dora_on_configure<O: DoraOperator>(
+    config: &str,
+    operator_context: *mut c_void,
+) -> DoraResult {
+    let state: &mut OperatorState<O> = unsafe { &mut *operator_context.cast() };
+    into_dora_result(state.operator.on_configure(config))
+}
+
+pub unsafe fn dora_on_start<O: DoraOperator>(operator_context: *mut c_void) -> DoraResult {
+    let state: &mut OperatorState<O> = unsafe { &mut *operator_context.cast() };
+    into_dora_result(state.operator.on_start())
+}
+
+pub unsafe fn dora_on_stop<O: DoraOperator>(operator_context: *mut c_void) -> DoraResult {
+    let state: &mut OperatorState<O> = unsafe { &mut *operator_context.cast() };
+    into_dora_result(state.operator.on_stop())
+}
+
+pub unsafe fn dora_on_metrics<O: DoraOperator>(
+    record_metric: &RecordMetric,
+    operator_context: *mut c_void,
+) -> DoraResult {
+    let state: &mut OperatorState<O> = unsafe { &mut *operator_context.cast() };
+    state.metrics = Metrics {
+        record_metric: Some(record_metric.record_metric.clone()),
+    };
+    DoraResult::SUCCESS
+}
+
+fn into_dora_result(result: Result<(), String>) -> DoraResult {
+    match result {
+        Ok(()) => DoraResult::SUCCESS,
+        Err(error) => DoraResult::from_error(error),
+    }
+}
+
 pub unsafe fn dora_on_event<O: DoraOperator>(
     event: &mut RawEvent,
     send_output: &SendOutput,
     operator_context: *mut std::ffi::c_void,
 ) -> OnEventResult {
-    let mut output_sender = DoraOutputSender(send_output);
+    let state: &mut OperatorState<O> = unsafe { &mut *operator_context.cast() };
+    let operator = &mut state.operator;
+    let mut output_sender = DoraOutputSender {
+        send_output,
+        metrics: &state.metrics,
+    };
 
-    let operator: &mut O = unsafe { &mut *operator_context.cast() };
+    if let Some(timer_id) = &event.timer {
+        return into_on_event_result(operator.on_timer(timer_id, &mut output_sender));
+    }
 
     let event_variant = if let Some(input) = &mut event.input {
         let Some(data_array) = input.data_array.take() else {
@@ -67,7 +135,11 @@ pub unsafe fn dora_on_event<O: DoraOperator>(
             status: DoraStatus::Continue,
         };
     };
-    match operator.on_event(&event_variant, &mut output_sender) {
+    into_on_event_result(operator.on_event(&event_variant, &mut output_sender))
+}
+
+fn into_on_event_result(result: Result<DoraStatus, String>) -> OnEventResult {
+    match result {
         Ok(status) => OnEventResult {
             result: DoraResult { error: None },
             status,
@@ -18,6 +18,56 @@ use safer_ffi::{
 };
 use std::{ops::Deref, path::Path};
 
+/// Version of the operator ABI, i.e. of the types in this crate and the
+/// functions that operators export.
+///
+/// This must be increased on every incompatible change. The runtime refuses
+/// to load shared library operators that were built for a different version.
+pub const DORA_OPERATOR_ABI_VERSION: u32 = 1;
+
+/// Exported by operators as `dora_operator_abi`, so that the runtime can check
+/// the ABI version before calling into the operator.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+#[derive(Debug)]
+pub struct DoraOperatorAbi {
+    pub abi_version: u32,
+    /// NUL-terminated version of the dora operator API that the operator was
+    /// built against. Empty if unknown.
+    pub dora_version: [u8; 32],
+}
+
+impl DoraOperatorAbi {
+    pub const CURRENT: Self = Self {
+        abi_version: DORA_OPERATOR_ABI_VERSION,
+        dora_version: version_bytes(env!("CARGO_PKG_VERSION")),
+    };
+
+    pub fn dora_version(&self) -> Option<&str> {
+        let len = self
+            .dora_version
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.dora_version.len());
+        std::str::from_utf8(&self.dora_version[..len])
+            .ok()
+            .filter(|version| !version.is_empty())
+    }
+}
+
+const fn version_bytes(version: &str) -> [u8; 32] {
+    let version = version.as_bytes();
+    let mut bytes = [0; 32];
+    let mut i = 0;
+    // keep the last byte as NUL terminator
+    while i < version.len() && i < bytes.len() - 1 {
+        bytes[i] = version[i];
+        i += 1;
+    }
+    bytes
+}
+
 #[derive_ReprC]
 #[ffi_export]
 #[repr(C)]
@@ -25,6 +75,23 @@ pub struct DoraInitOperator {
     pub init_operator: unsafe extern "C" fn() -> DoraInitResult,
 }
 
+/// Optional alternative to `init_operator` that receives the `config` of the
+/// operator in the dataflow descriptor as a JSON object. It's used instead of
+/// `init_operator` if it's defined.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+pub struct DoraInitOperatorWithConfig {
+    pub init_operator_with_config: InitOperatorWithConfigFn,
+}
+
+#[derive_ReprC]
+#[ffi_export]
+#[repr(transparent)]
+pub struct InitOperatorWithConfigFn(
+    pub unsafe extern "C" fn(config: char_p::char_p_ref<'_>) -> DoraInitResult,
+);
+
 #[derive_ReprC]
 #[ffi_export]
 #[repr(C)]
@@ -72,6 +139,61 @@ impl DoraResult {
     }
 }
 
+/// Optional hook that is called once before the first event.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+pub struct DoraOnStart {
+    pub on_start: unsafe extern "C" fn(operator_context: *mut std::ffi::c_void) -> DoraResult,
+}
+
+/// Optional hook that is called once when the operator stops gracefully.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+pub struct DoraOnStop {
+    pub on_stop: unsafe extern "C" fn(operator_context: *mut std::ffi::c_void) -> DoraResult,
+}
+
+/// Optional hook that receives the operator parameters of the dataflow
+/// descriptor as a JSON object. It's called before `on_start`.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+pub struct DoraOnConfigure {
+    pub on_configure: OnConfigureFn,
+}
+
+#[derive_ReprC]
+#[ffi_export]
+#[repr(transparent)]
+pub struct OnConfigureFn(
+    pub  unsafe extern "C" fn(
+        config: char_p::char_p_ref<'_>,
+        operator_context: *mut std::ffi::c_void,
+    ) -> DoraResult,
+);
+
+/// Optional hook that receives the callback for recording operator metrics.
+/// It's called before `on_configure`. Operators may keep a clone of the
+/// callback for the rest of their lifetime.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+pub struct DoraOnMetrics {
+    pub on_metrics: OnMetricsFn,
+}
+
+#[derive_ReprC]
+#[ffi_export]
+#[repr(transparent)]
+pub struct OnMetricsFn(
+    pub  unsafe extern "C" fn(
+        record_metric: &RecordMetric,
+        operator_context: *mut std::ffi::c_void,
+    ) -> DoraResult,
+);
+
 #[derive_ReprC]
 #[ffi_export]
 #[repr(C)]
@@ -99,6 +221,9 @@ pub struct RawEvent {
     pub input_closed: Option<safer_ffi::String>,
     pub stop: bool,
     pub error: Option<safer_ffi::String>,
+    /// ID of the timer that triggered this event, as configured in the
+    /// `_unstable_timers` field of the dataflow.
+    pub timer: Option<safer_ffi::String>,
 }
 
 #[derive_ReprC]
@@ -126,6 +251,37 @@ pub struct SendOutput {
     pub send_output: ArcDynFn1<DoraResult, Output>,
 }
 
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+#[derive(Clone)]
+pub struct RecordMetric {
+    pub record_metric: ArcDynFn1<DoraResult, MetricUpdate>,
+}
+
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+#[derive(Debug)]
+pub struct MetricUpdate {
+    pub name: safer_ffi::String,
+    pub kind: MetricKind,
+    pub value: f64,
+}
+
+#[derive_ReprC]
+#[ffi_export]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MetricKind {
+    /// Adds the value to a monotonic sum, e.g. the number of detections.
+    Counter = 0,
+    /// Replaces the current value, e.g. a queue length.
+    Gauge = 1,
+    /// Records the value in a distribution, e.g. a planning time.
+    Histogram = 2,
+}
+
 #[derive_ReprC]
 #[repr(opaque)]
 #[derive(Debug)]
@@ -155,6 +311,17 @@ pub enum DoraStatus {
     StopAll = 2,
 }
 
+/// Creates a result with the given error message, e.g. to report a failure
+/// from a lifecycle hook.
+#[ffi_export]
+pub fn dora_result_from_error(error: char_p::char_p_ref<'_>) -> DoraResult {
+    DoraResult::from_error(error.to_str().to_owned())
+}
+
+/// Frees a result that is not passed back to the runtime.
+#[ffi_export]
+pub fn dora_free_result(_result: DoraResult) {}
+
 #[ffi_export]
 pub fn dora_read_input_id(input: &Input) -> char_p_boxed {
     char_p::new(&*input.id)
@@ -175,6 +342,32 @@ pub fn dora_read_data(input: &mut Input) -> Option<safer_ffi::Vec<u8>> {
 #[ffi_export]
 pub fn dora_free_data(_data: safer_ffi::Vec<u8>) {}
 
+/// Returns the data of the input without copying it.
+///
+/// The returned slice points into the input and is only valid as long as the
+/// input is. It is empty if the input has no data or if the data is not a byte
+/// array without null values. Unlike `dora_read_data`, this function can be
+/// called multiple times for the same input.
+#[ffi_export]
+pub fn dora_read_data_ref(input: &Input) -> safer_ffi::slice::slice_ref<'_, u8> {
+    input_bytes(input).unwrap_or_default().into()
+}
+
+fn input_bytes(input: &Input) -> Option<&[u8]> {
+    let array = input.data_array.as_ref()?;
+    // `C` is the format string of `UInt8` arrays in the C data interface
+    if input.schema.format() != "C" || array.null_count() != 0 || array.num_buffers() != 2 {
+        return None;
+    }
+    let values = array.buffer(1);
+    if values.is_null() || array.is_empty() {
+        return None;
+    }
+    // SAFETY: the values buffer holds at least `offset + len` bytes and is
+    // kept alive by the input
+    Some(unsafe { slice::from_raw_parts(values.add(array.offset()), array.len()) })
+}
+
 #[ffi_export]
 pub unsafe fn dora_send_operator_output(
     send_output: &SendOutput,
@@ -192,7 +385,8 @@ pub unsafe fn dora_send_operator_output(
             data_array,
             schema,
             metadata: Metadata {
-                open_telemetry_context: String::new().into(), // TODO
+                // set to the trace context of the current input by the runtime
+                open_telemetry_context: String::new().into(),
             },
         };
         Result::<_, String>::Ok(output)
@@ -205,6 +399,21 @@ pub unsafe fn dora_send_operator_output(
     }
 }
 
+/// Records a metric value through the callback passed to `dora_on_metrics`.
+#[ffi_export]
+pub fn dora_record_metric(
+    record_metric: &RecordMetric,
+    name: char_p::char_p_ref<'_>,
+    kind: MetricKind,
+    value: f64,
+) -> DoraResult {
+    record_metric.record_metric.call(MetricUpdate {
+        name: name.to_str().to_owned().into(),
+        kind,
+        value,
+    })
+}
+
 pub fn generate_headers(target_file: &Path) -> ::std::io::Result<()> {
     ::safer_ffi::headers::builder()
         .to_file(target_file)?
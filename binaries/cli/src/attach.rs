@@ -18,12 +18,14 @@ use uuid::Uuid;
 
 use crate::handle_dataflow_result;
 
+#[allow(clippy::too_many_arguments)]
 pub fn attach_dataflow(
     dataflow: Descriptor,
     dataflow_path: PathBuf,
     dataflow_id: Uuid,
     session: &mut TcpRequestReplyConnection,
     hot_reload: bool,
+    grace_duration: Option<Duration>,
     coordinator_socket: SocketAddr,
     log_level: log::LevelFilter,
 ) -> Result<(), eyre::ErrReport> {
@@ -114,7 +116,7 @@ pub fn attach_dataflow(
             if ctrlc_tx
                 .send(AttachEvent::Control(ControlRequest::Stop {
                     dataflow_uuid: dataflow_id,
-                    grace_duration: None,
+                    grace_duration,
                 }))
                 .is_err()
             {
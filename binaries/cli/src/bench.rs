@@ -0,0 +1,333 @@
+//! Latency and throughput benchmark of the local transports, see `dora bench`.
+//!
+//! The benchmark runs a dataflow of two nodes, which are both implemented by
+//! the `dora` executable itself through the hidden `bench-node` command. The
+//! sink node writes its measurements to a file in the working directory of
+//! the dataflow, from which `dora bench` reads them once the dataflow
+//! finished.
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use dora_core::{config::DataId, topics::DataflowResult};
+use dora_daemon::Daemon;
+use dora_node_api::{DoraNode, Event, MetadataParameters};
+use eyre::{bail, Context};
+use tabwriter::TabWriter;
+use uuid::Uuid;
+
+use crate::{
+    formatting::{format_bytes, print_structured, OutputFormat},
+    handle_dataflow_result,
+};
+
+const DEFAULT_SIZES: [usize; 4] = [8, 4096, 64 * 1024, 1024 * 1024];
+const RESULTS_FILE: &str = "results.json";
+const SIZE_PARAMETER: &str = "bench.size";
+
+/// Transport between the nodes of the benchmark dataflow, see
+/// `LocalCommunicationConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Tcp,
+    Shmem,
+}
+
+/// The nodes of the benchmark dataflow.
+#[derive(Debug, clap::Subcommand)]
+pub enum BenchNode {
+    Source {
+        #[clap(long = "size")]
+        sizes: Vec<usize>,
+        #[clap(long)]
+        messages: usize,
+        #[clap(long)]
+        rate: f64,
+    },
+    Sink,
+}
+
+/// Measurements of a single message size.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SizeResult {
+    pub size: usize,
+    /// Latencies of the messages that were sent at the configured rate.
+    pub latency_p50: Duration,
+    pub latency_p99: Duration,
+    /// Messages per second if the source sends as fast as possible.
+    pub max_throughput: f64,
+    /// Number of messages of the throughput measurement that reached the sink.
+    pub received: usize,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct TransportResult {
+    transport: Transport,
+    results: Vec<SizeResult>,
+}
+
+pub fn run(
+    sizes: Vec<usize>,
+    messages: usize,
+    rate: f64,
+    transports: Vec<Transport>,
+    output: OutputFormat,
+) -> eyre::Result<()> {
+    if messages == 0 {
+        bail!("`--messages` must be at least 1");
+    }
+    if !(rate > 0.0 && rate.is_finite()) {
+        bail!("invalid rate `{rate}`: expected a positive number");
+    }
+    let mut sizes = if sizes.is_empty() {
+        DEFAULT_SIZES.to_vec()
+    } else {
+        sizes
+    };
+    sizes.sort_unstable();
+    sizes.dedup();
+    let transports = if transports.is_empty() {
+        vec![Transport::Tcp, Transport::Shmem]
+    } else {
+        transports
+    };
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("tokio runtime failed")?;
+    let mut results = Vec::new();
+    for transport in transports {
+        if output == OutputFormat::Table {
+            eprintln!("running benchmark with {transport:?} transport...");
+        }
+        let working_dir = std::env::temp_dir().join(format!("dora-bench-{}", Uuid::now_v7()));
+        std::fs::create_dir_all(&working_dir)
+            .wrap_err("failed to create benchmark working directory")?;
+        let dataflow = write_dataflow(&working_dir, transport, &sizes, messages, rate)?;
+        let result = rt
+            .block_on(Daemon::run_dataflow(&dataflow))
+            .and_then(|result: DataflowResult| handle_dataflow_result(result, None));
+        if let Err(err) = result {
+            // keep the logs of the nodes for debugging
+            return Err(err).wrap_err_with(|| {
+                format!(
+                    "benchmark with {transport:?} transport failed (logs in `{}`)",
+                    working_dir.join("out").display()
+                )
+            });
+        }
+        let raw = std::fs::read(working_dir.join(RESULTS_FILE))
+            .wrap_err("failed to read benchmark results")?;
+        let _ = std::fs::remove_dir_all(&working_dir);
+        results.push(TransportResult {
+            transport,
+            results: serde_json::from_slice(&raw).wrap_err("failed to parse benchmark results")?,
+        });
+    }
+
+    if print_structured(&results, output)? {
+        return Ok(());
+    }
+    let mut tw = TabWriter::new(vec![]);
+    tw.write_all(b"TRANSPORT\tSIZE\tLATENCY P50\tLATENCY P99\tMAX THROUGHPUT\tBANDWIDTH\n")?;
+    for TransportResult { transport, results } in &results {
+        for result in results {
+            let dropped = messages.saturating_sub(result.received);
+            let dropped = if dropped > 0 {
+                format!(" ({dropped} dropped)")
+            } else {
+                String::new()
+            };
+            writeln!(
+                tw,
+                "{transport:?}\t{}\t{:.1?}\t{:.1?}\t{:.0} msg/s{dropped}\t{}/s",
+                format_bytes(result.size as f64),
+                result.latency_p50,
+                result.latency_p99,
+                result.max_throughput,
+                format_bytes(result.max_throughput * result.size as f64),
+            )?;
+        }
+    }
+    tw.flush()?;
+    print!("{}", String::from_utf8(tw.into_inner()?)?);
+    Ok(())
+}
+
+fn write_dataflow(
+    working_dir: &Path,
+    transport: Transport,
+    sizes: &[usize],
+    messages: usize,
+    rate: f64,
+) -> eyre::Result<PathBuf> {
+    let exe = std::env::current_exe().wrap_err("failed to get current executable path")?;
+    let sizes: String = sizes
+        .iter()
+        .map(|size| format!(" --size {size}"))
+        .collect::<Vec<_>>()
+        .concat();
+    let local = match transport {
+        Transport::Tcp => "Tcp",
+        Transport::Shmem => "Shmem",
+    };
+    // queue all messages of the throughput measurement, so that none are dropped
+    let dataflow = format!(
+        r#"communication:
+  _unstable_local: {local}
+nodes:
+  - id: bench-source
+    path: {exe:?}
+    args: bench-node source --messages {messages} --rate {rate}{sizes}
+    outputs:
+      - latency
+      - throughput
+  - id: bench-sink
+    path: {exe:?}
+    args: bench-node sink
+    inputs:
+      latency:
+        source: bench-source/latency
+        queue_size: {messages}
+      throughput:
+        source: bench-source/throughput
+        queue_size: {messages}
+"#
+    );
+    let path = working_dir.join("dataflow.yml");
+    std::fs::write(&path, dataflow).wrap_err("failed to write benchmark dataflow")?;
+    Ok(path)
+}
+
+pub fn run_node(node: BenchNode) -> eyre::Result<()> {
+    match node {
+        BenchNode::Source {
+            sizes,
+            messages,
+            rate,
+        } => source(&sizes, messages, rate),
+        BenchNode::Sink => sink(),
+    }
+}
+
+fn source(sizes: &[usize], messages: usize, rate: f64) -> eyre::Result<()> {
+    let latency = DataId::from("latency".to_owned());
+    let throughput = DataId::from("throughput".to_owned());
+    let (mut node, _events) = DoraNode::init_from_env()?;
+
+    let interval = Duration::from_secs_f64(1.0 / rate);
+    for &size in sizes {
+        let mut parameters = MetadataParameters::default();
+        parameters.insert(SIZE_PARAMETER, size as i64);
+
+        let mut next = Instant::now();
+        for _ in 0..messages {
+            std::thread::sleep(next.saturating_duration_since(Instant::now()));
+            next += interval;
+            node.send_output_raw(latency.clone(), parameters.clone(), size, |out| {
+                out.fill(0xAB)
+            })?;
+        }
+        // let the sink catch up before measuring the throughput
+        std::thread::sleep(Duration::from_millis(500));
+        for _ in 0..messages {
+            node.send_output_raw(throughput.clone(), parameters.clone(), size, |out| {
+                out.fill(0xAB)
+            })?;
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct SizeMeasurement {
+    latencies: Vec<Duration>,
+    first_sent: Option<SystemTime>,
+    last_received: Option<SystemTime>,
+    received: usize,
+}
+
+fn sink() -> eyre::Result<()> {
+    let (_node, mut events) = DoraNode::init_from_env()?;
+    let mut measurements: BTreeMap<usize, SizeMeasurement> = BTreeMap::new();
+
+    while let Some(event) = events.recv() {
+        let Event::Input { id, metadata, .. } = event else {
+            continue;
+        };
+        let now = SystemTime::now();
+        let sent = metadata.timestamp().get_time().to_system_time();
+        let Some(size) = metadata
+            .parameters
+            .get(SIZE_PARAMETER)
+            .and_then(|p| p.as_integer())
+        else {
+            bail!("message without `{SIZE_PARAMETER}` parameter");
+        };
+        let measurement = measurements.entry(size as usize).or_default();
+        match id.as_str() {
+            "latency" => measurement
+                .latencies
+                .push(now.duration_since(sent).unwrap_or_default()),
+            "throughput" => {
+                measurement.first_sent.get_or_insert(sent);
+                measurement.last_received = Some(now);
+                measurement.received += 1;
+            }
+            other => bail!("unexpected input `{other}`"),
+        }
+    }
+
+    let results: Vec<_> = measurements
+        .into_iter()
+        .map(|(size, mut measurement)| {
+            measurement.latencies.sort_unstable();
+            let duration = measurement
+                .first_sent
+                .zip(measurement.last_received)
+                .and_then(|(first, last)| last.duration_since(first).ok())
+                .unwrap_or_default();
+            SizeResult {
+                size,
+                latency_p50: percentile(&measurement.latencies, 0.5),
+                latency_p99: percentile(&measurement.latencies, 0.99),
+                max_throughput: measurement.received as f64 / duration.as_secs_f64().max(1e-9),
+                received: measurement.received,
+            }
+        })
+        .collect();
+    std::fs::write(RESULTS_FILE, serde_json::to_vec(&results)?)
+        .wrap_err("failed to write benchmark results")
+}
+
+/// Returns the value at the given quantile of the sorted values.
+fn percentile(sorted: &[Duration], quantile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+    sorted[index]
+}
+
+/// Parses a size in bytes with an optional binary unit suffix, e.g. `64K`.
+pub fn parse_size(size: &str) -> Result<usize, String> {
+    let size = size.trim();
+    let (number, factor) = match size.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&size[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&size[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    number
+        .trim()
+        .parse::<usize>()
+        .map(|n| n * factor)
+        .map_err(|_| format!("invalid size `{size}`: expected e.g. `512`, `64K`, or `4M`"))
+}
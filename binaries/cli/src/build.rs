@@ -1,11 +1,122 @@
 use dora_core::{
-    config::OperatorId,
-    descriptor::{Descriptor, SINGLE_OPERATOR_DEFAULT_ID},
+    adjust_shared_library_path, adjust_shared_library_path_for_target,
+    descriptor::{
+        resolve_path, source_is_url, CoreNodeKind, Descriptor, OperatorSource, ResolvedDeploy,
+        ResolvedNode, DYNAMIC_SOURCE, SHELL_SOURCE,
+    },
 };
-use eyre::{eyre, Context};
-use std::{path::Path, process::Command};
+use eyre::{bail, eyre, Context};
+use std::{
+    collections::BTreeMap,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+/// Stores the working directory fingerprint of the last successful run of each
+/// build command.
+const CACHE_FILE: &str = "build-cache.json";
+
+/// Directories that are ignored when fingerprinting the working directory,
+/// because they contain build outputs or logs.
+pub(crate) const IGNORED_DIRS: &[&str] = &["out", "target", "build", "node_modules", "__pycache__"];
 
-pub fn build(dataflow: &Path) -> eyre::Result<()> {
+/// A build command, together with the nodes and operators that declare it.
+struct BuildTask {
+    command: String,
+    /// Rust target triple to build for, passed to cargo as `CARGO_BUILD_TARGET`.
+    target: Option<String>,
+    ids: Vec<String>,
+    /// Files that the build is expected to produce.
+    artifacts: Vec<PathBuf>,
+}
+
+impl BuildTask {
+    fn name(&self) -> String {
+        let ids = self.ids.join(", ");
+        match &self.target {
+            Some(target) => format!("{ids} [{target}]"),
+            None => ids,
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        match &self.target {
+            Some(target) => format!("{} [{target}]", self.command),
+            None => self.command.clone(),
+        }
+    }
+}
+
+/// A built file that is needed on the machine of a node.
+struct MachineArtifact {
+    machine: String,
+    target: Option<String>,
+    /// Where the build command placed the file.
+    built: PathBuf,
+    /// Path of the file relative to the working directory on the machine, as
+    /// expected by the descriptor.
+    path: PathBuf,
+}
+
+/// Rust targets to cross-compile for, set through `--target [MACHINE=]TRIPLE`.
+#[derive(Debug, Clone, Default)]
+pub struct BuildTargets {
+    /// Target of the machines that have no other target configured.
+    default: Option<String>,
+    machines: BTreeMap<String, String>,
+}
+
+impl BuildTargets {
+    pub fn parse(args: &[String]) -> eyre::Result<Self> {
+        let mut targets = Self::default();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((machine, target)) => {
+                    let previous = targets
+                        .machines
+                        .insert(machine.to_owned(), target.to_owned());
+                    if previous.is_some() {
+                        bail!("multiple targets given for machine `{machine}`");
+                    }
+                }
+                None => {
+                    if targets.default.replace(arg.clone()).is_some() {
+                        bail!("multiple targets given without a machine");
+                    }
+                }
+            }
+        }
+        Ok(targets)
+    }
+
+    /// A target for the specific machine takes precedence over the `target` of
+    /// the `_unstable_deploy` config, which takes precedence over the default.
+    fn target<'a>(&'a self, deploy: &'a ResolvedDeploy) -> Option<&'a str> {
+        self.machines
+            .get(&deploy.machine)
+            .or(deploy.target.as_ref())
+            .or(self.default.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// Runs the `build` commands of all nodes and operators of the dataflow.
+///
+/// Commands that are declared by multiple nodes run only once per target.
+/// Commands are skipped if they succeeded before, their artifacts exist, and
+/// no file in the dataflow directory changed since, unless `force` is set.
+///
+/// If a node is built for a target, the build artifacts are also copied to a
+/// directory per machine, see [`write_machine_artifacts`].
+pub fn build(
+    dataflow: &Path,
+    jobs: Option<NonZeroUsize>,
+    force: bool,
+    targets: &BuildTargets,
+) -> eyre::Result<()> {
     let descriptor = Descriptor::blocking_read(dataflow)?;
     let dataflow_absolute = if dataflow.is_relative() {
         std::env::current_dir().unwrap().join(dataflow)
@@ -14,68 +125,365 @@ pub fn build(dataflow: &Path) -> eyre::Result<()> {
     };
     let working_dir = dataflow_absolute.parent().unwrap();
 
-    let default_op_id = OperatorId::from(SINGLE_OPERATOR_DEFAULT_ID.to_string());
+    let nodes = descriptor.resolve_aliases_and_set_defaults()?;
+    let (tasks, machine_artifacts) = build_tasks(&nodes, working_dir, targets);
+    if tasks.is_empty() {
+        println!("no build commands defined");
+        return Ok(());
+    }
+
+    let cache_path = working_dir.join("out").join(CACHE_FILE);
+    let mut cache = if force {
+        BTreeMap::new()
+    } else {
+        read_cache(&cache_path)
+    };
+    let fingerprint = fingerprint(working_dir);
+    let (cached, tasks): (Vec<_>, Vec<_>) = tasks.into_iter().partition(|task| {
+        cache.get(&task.cache_key()) == Some(&fingerprint)
+            && task.artifacts.iter().all(|path| path.exists())
+    });
+    for task in &cached {
+        println!("{}: up to date", task.name());
+    }
+
+    let jobs = jobs
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(tasks.len().max(1));
+    let results = run_tasks(&tasks, working_dir, jobs);
 
-    for node in descriptor.nodes {
-        match node.kind()? {
-            dora_core::descriptor::NodeKind::Standard(_) => {
-                run_build_command(node.build.as_deref(), working_dir).with_context(|| {
-                    format!("build command failed for standard node `{}`", node.id)
-                })?
+    let mut succeeded: Vec<_> = cached.iter().map(BuildTask::cache_key).collect();
+    let mut failed = Vec::new();
+    for (task, result) in tasks.iter().zip(results) {
+        match result {
+            Ok(()) => succeeded.push(task.cache_key()),
+            Err(err) => {
+                cache.remove(&task.cache_key());
+                failed.push(format!("{}: {err:?}", task.name()));
             }
-            dora_core::descriptor::NodeKind::Runtime(runtime_node) => {
-                for operator in &runtime_node.operators {
-                    run_build_command(operator.config.build.as_deref(), working_dir).with_context(
-                        || {
-                            format!(
-                                "build command failed for operator `{}/{}`",
-                                node.id, operator.id
-                            )
-                        },
-                    )?;
+        }
+    }
+
+    // builds might change files in the working dir, so take a new fingerprint
+    let fingerprint = self::fingerprint(working_dir);
+    for key in succeeded {
+        cache.insert(key, fingerprint.clone());
+    }
+    if let Err(err) = write_cache(&cache_path, &cache) {
+        tracing::warn!("failed to write build cache: {err:?}");
+    }
+
+    if !failed.is_empty() {
+        bail!("build failed for:\n{}", failed.join("\n"));
+    }
+    if machine_artifacts.iter().any(|a| a.target.is_some()) {
+        write_machine_artifacts(working_dir, &machine_artifacts)?;
+    }
+    Ok(())
+}
+
+fn build_tasks(
+    nodes: &[ResolvedNode],
+    working_dir: &Path,
+    targets: &BuildTargets,
+) -> (Vec<BuildTask>, Vec<MachineArtifact>) {
+    let mut tasks: Vec<BuildTask> = Vec::new();
+    let mut machine_artifacts = Vec::new();
+    let mut add =
+        |command: Option<&String>, target: Option<&str>, id: String, artifact: Option<PathBuf>| {
+            let Some(command) = command else { return };
+            let index = match tasks
+                .iter()
+                .position(|t| &t.command == command && t.target.as_deref() == target)
+            {
+                Some(index) => index,
+                None => {
+                    tasks.push(BuildTask {
+                        command: command.clone(),
+                        target: target.map(str::to_owned),
+                        ids: Vec::new(),
+                        artifacts: Vec::new(),
+                    });
+                    tasks.len() - 1
                 }
+            };
+            let task = &mut tasks[index];
+            task.ids.push(id);
+            task.artifacts.extend(artifact);
+        };
+
+    for node in nodes {
+        let target = targets.target(&node.deploy);
+        let mut add_machine_artifact = |path: PathBuf, built: &PathBuf| {
+            if path.is_relative() {
+                machine_artifacts.push(MachineArtifact {
+                    machine: node.deploy.machine.clone(),
+                    target: target.map(str::to_owned),
+                    built: built.clone(),
+                    path,
+                });
             }
-            dora_core::descriptor::NodeKind::Custom(custom_node) => {
-                run_build_command(custom_node.build.as_deref(), working_dir).with_context(|| {
-                    format!("build command failed for custom node `{}`", node.id)
-                })?
+        };
+        match &node.kind {
+            CoreNodeKind::Custom(custom) => {
+                let source = custom.source.as_str();
+                let artifact = if source == SHELL_SOURCE
+                    || source == DYNAMIC_SOURCE
+                    || source_is_url(source)
+                {
+                    None
+                } else {
+                    let path = executable_path(source, target);
+                    let built = match target {
+                        Some(target) => cross_artifact_path(&working_dir.join(&path), target),
+                        // a missing source path is treated as a missing artifact
+                        None => resolve_path(source, working_dir)
+                            .unwrap_or_else(|_| working_dir.join(source)),
+                    };
+                    if custom.build.is_some() {
+                        add_machine_artifact(path, &built);
+                    }
+                    Some(built)
+                };
+                add(custom.build.as_ref(), target, node.id.to_string(), artifact);
             }
-            dora_core::descriptor::NodeKind::Operator(operator) => {
-                run_build_command(operator.config.build.as_deref(), working_dir).with_context(
-                    || {
-                        format!(
-                            "build command failed for operator `{}/{}`",
-                            node.id,
-                            operator.id.as_ref().unwrap_or(&default_op_id)
-                        )
-                    },
-                )?
+            CoreNodeKind::Runtime(runtime) => {
+                for operator in &runtime.operators {
+                    let artifact = match &operator.config.source {
+                        OperatorSource::SharedLibrary(path) if !source_is_url(path) => {
+                            let path = match target {
+                                Some(target) => {
+                                    adjust_shared_library_path_for_target(Path::new(path), target)
+                                }
+                                None => adjust_shared_library_path(Path::new(path)),
+                            };
+                            path.ok().map(|path| {
+                                let built = match target {
+                                    Some(target) => {
+                                        cross_artifact_path(&working_dir.join(&path), target)
+                                    }
+                                    None => working_dir.join(&path),
+                                };
+                                if operator.config.build.is_some() {
+                                    add_machine_artifact(path, &built);
+                                }
+                                built
+                            })
+                        }
+                        _ => None,
+                    };
+                    add(
+                        operator.config.build.as_ref(),
+                        target,
+                        format!("{}/{}", node.id, operator.id),
+                        artifact,
+                    );
+                }
             }
         }
     }
+    (tasks, machine_artifacts)
+}
 
-    Ok(())
+/// Path of the executable of a custom node on the platform of the target.
+fn executable_path(source: &str, target: Option<&str>) -> PathBuf {
+    let path = PathBuf::from(source);
+    let windows = match target {
+        Some(target) => target.contains("windows"),
+        None => cfg!(windows),
+    };
+    if windows && path.extension().is_none() {
+        path.with_extension("exe")
+    } else {
+        path
+    }
+}
+
+/// Returns where cargo places the artifact when building for the target,
+/// e.g. `target/aarch64-unknown-linux-gnu/debug/node` for `target/debug/node`.
+///
+/// Paths outside of a `target` directory are returned unchanged.
+fn cross_artifact_path(path: &Path, target: &str) -> PathBuf {
+    let components: Vec<_> = path.components().collect();
+    let target_dir = components
+        .iter()
+        .take(components.len().saturating_sub(1))
+        .rposition(|c| c.as_os_str() == "target");
+    let Some(target_dir) = target_dir else {
+        return path.to_owned();
+    };
+    let mut result: PathBuf = components[..=target_dir].iter().collect();
+    result.push(target);
+    result.extend(&components[target_dir + 1..]);
+    result
 }
 
-fn run_build_command(build: Option<&str>, working_dir: &Path) -> eyre::Result<()> {
-    if let Some(build) = build {
-        let mut split = build.split_whitespace();
-        let mut cmd = Command::new(
-            split
-                .next()
-                .ok_or_else(|| eyre!("build command is empty"))?,
+/// Copies the build artifacts of each machine to `out/build/<MACHINE>`, in
+/// the layout that the descriptor expects in the working directory of the
+/// machine.
+///
+/// The previous artifacts of the machine are removed first.
+fn write_machine_artifacts(working_dir: &Path, artifacts: &[MachineArtifact]) -> eyre::Result<()> {
+    let mut machines: BTreeMap<&str, Vec<&MachineArtifact>> = BTreeMap::new();
+    for artifact in artifacts {
+        machines
+            .entry(&artifact.machine)
+            .or_default()
+            .push(artifact);
+    }
+    for (machine, artifacts) in machines {
+        let name = if machine.is_empty() {
+            "default"
+        } else {
+            machine
+        };
+        let dir = working_dir.join("out").join("build").join(name);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)
+                .wrap_err_with(|| format!("failed to remove `{}`", dir.display()))?;
+        }
+        for artifact in &artifacts {
+            let destination = dir.join(&artifact.path);
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)
+                    .wrap_err_with(|| format!("failed to create `{}`", parent.display()))?;
+            }
+            std::fs::copy(&artifact.built, &destination).wrap_err_with(|| {
+                format!(
+                    "failed to copy `{}` to `{}`",
+                    artifact.built.display(),
+                    destination.display()
+                )
+            })?;
+        }
+        let target = artifacts
+            .first()
+            .and_then(|a| a.target.as_deref())
+            .unwrap_or("host");
+        println!(
+            "machine `{name}` ({target}): {} artifact(s) in `{}`",
+            artifacts.len(),
+            dir.display()
         );
-        cmd.args(split);
-        cmd.current_dir(working_dir);
+    }
+    Ok(())
+}
+
+/// Runs the tasks on up to `jobs` threads.
+///
+/// The output of the build commands is only passed through directly if they
+/// run one at a time. Otherwise it is captured and printed for failed builds.
+fn run_tasks(tasks: &[BuildTask], working_dir: &Path, jobs: usize) -> Vec<eyre::Result<()>> {
+    let next = Mutex::new(0);
+    let results = Mutex::new(BTreeMap::new());
+    std::thread::scope(|s| {
+        for _ in 0..jobs {
+            s.spawn(|| loop {
+                let index = {
+                    let mut next = next.lock().unwrap();
+                    *next += 1;
+                    *next - 1
+                };
+                let Some(task) = tasks.get(index) else { break };
+                println!("{}: running `{}`", task.name(), task.command);
+                let result = run_build_command(
+                    &task.command,
+                    task.target.as_deref(),
+                    working_dir,
+                    jobs == 1,
+                )
+                .with_context(|| format!("build command `{}` failed", task.command));
+                if result.is_ok() {
+                    println!("{}: finished", task.name());
+                }
+                results.lock().unwrap().insert(index, result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_values().collect()
+}
+
+fn run_build_command(
+    build: &str,
+    target: Option<&str>,
+    working_dir: &Path,
+    inherit_output: bool,
+) -> eyre::Result<()> {
+    let mut split = build.split_whitespace();
+    let mut cmd = Command::new(
+        split
+            .next()
+            .ok_or_else(|| eyre!("build command is empty"))?,
+    );
+    cmd.args(split);
+    cmd.current_dir(working_dir);
+    if let Some(target) = target {
+        cmd.env("CARGO_BUILD_TARGET", target);
+    }
+    if inherit_output {
         let exit_status = cmd
             .status()
             .wrap_err_with(|| format!("failed to run `{}`", build))?;
-        if exit_status.success() {
-            Ok(())
-        } else {
-            Err(eyre!("build command returned an error code"))
+        if !exit_status.success() {
+            bail!("build command returned an error code");
         }
     } else {
-        Ok(())
+        let output = cmd
+            .stdin(Stdio::null())
+            .output()
+            .wrap_err_with(|| format!("failed to run `{}`", build))?;
+        if !output.status.success() {
+            bail!(
+                "build command returned an error code\n{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Summarizes the files in the working directory by their number and latest
+/// modification time, ignoring hidden directories and build outputs.
+fn fingerprint(working_dir: &Path) -> String {
+    let mut files = 0u64;
+    let mut latest = 0u128;
+    let mut dirs = vec![working_dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if file_type.is_dir() {
+                if !name.starts_with('.') && !IGNORED_DIRS.contains(&name.as_ref()) {
+                    dirs.push(entry.path());
+                }
+            } else if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+                files += 1;
+                let modified = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+                latest = latest.max(modified.as_nanos());
+            }
+        }
+    }
+    format!("{files}-{latest}")
+}
+
+fn read_cache(path: &Path) -> BTreeMap<String, String> {
+    std::fs::read(path)
+        .ok()
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn write_cache(path: &Path, cache: &BTreeMap<String, String>) -> eyre::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("failed to create cache dir")?;
     }
+    std::fs::write(path, serde_json::to_vec_pretty(cache)?).context("failed to write cache")
 }
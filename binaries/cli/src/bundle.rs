@@ -0,0 +1,482 @@
+//! Dataflow bundles, see `dora bundle` and `dora deploy`.
+//!
+//! A bundle is a `.tar.gz` archive of the dataflow descriptor, the files that
+//! its nodes and operators are loaded from, and optionally the Python wheels
+//! of their requirements. The archive root is the closest common directory of
+//! these files, so that the relative paths in the descriptor stay valid after
+//! extracting it. The `dora-bundle.json` manifest at the root lists all files
+//! together with their SHA-256 checksum.
+//!
+//! Bundles are reproducible: the entries are sorted and have no timestamps.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env::consts::EXE_EXTENSION,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    adjust_shared_library_path,
+    descriptor::{
+        lua_source_path, source_is_url, CoreNodeKind, Descriptor, OperatorSource, ResolvedNode,
+        DYNAMIC_SOURCE, SHELL_SOURCE,
+    },
+    get_python_path,
+    topics::{ControlRequest, ControlRequestReply},
+};
+use eyre::{bail, eyre, Context};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{formatting::format_bytes, start_dataflow};
+
+const MANIFEST_FILE: &str = "dora-bundle.json";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub name: String,
+    /// Version of the `dora` CLI that created the bundle.
+    pub dora_version: String,
+    /// Path of the dataflow descriptor within the bundle.
+    pub descriptor: String,
+    pub files: Vec<BundleFile>,
+    /// Sources that are not part of the bundle and need to be installed on
+    /// the target machines, e.g. executables that are found through `PATH`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub external: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Files that are referenced by the dataflow.
+#[derive(Default)]
+struct Sources {
+    /// Canonical paths of the files to bundle.
+    files: BTreeSet<PathBuf>,
+    /// `requirements.txt` files of Python operators.
+    requirements: BTreeSet<PathBuf>,
+    external: BTreeSet<String>,
+}
+
+impl Sources {
+    fn add(&mut self, id: &str, path: &Path, working_dir: &Path) -> eyre::Result<()> {
+        if path.is_absolute() {
+            self.external.insert(format!("{id}: `{}`", path.display()));
+            return Ok(());
+        }
+        match working_dir.join(path).canonicalize() {
+            Ok(path) => {
+                self.files.insert(path);
+                Ok(())
+            }
+            Err(_) if which::which(path).is_ok() => {
+                self.external.insert(format!("{id}: `{}`", path.display()));
+                Ok(())
+            }
+            Err(err) => Err(err).wrap_err_with(|| {
+                format!(
+                    "`{}` of `{id}` not found (use `--build` to build it first)",
+                    path.display()
+                )
+            }),
+        }
+    }
+
+    /// Adds the given file, or all non-hidden files below the given directory.
+    fn add_tree(&mut self, path: &Path) -> eyre::Result<()> {
+        let path = path
+            .canonicalize()
+            .wrap_err_with(|| format!("failed to find `{}`", path.display()))?;
+        let mut pending = vec![path];
+        while let Some(path) = pending.pop() {
+            if !path.is_dir() {
+                self.files.insert(path);
+                continue;
+            }
+            let entries = std::fs::read_dir(&path)
+                .wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+            for entry in entries {
+                let entry = entry?;
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    pending.push(entry.path());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Packages the dataflow into a bundle.
+pub fn bundle(
+    dataflow: &Path,
+    name: Option<String>,
+    output: Option<PathBuf>,
+    include: &[PathBuf],
+    wheels: bool,
+) -> eyre::Result<()> {
+    let descriptor = Descriptor::blocking_read(dataflow)?;
+    let dataflow = dataflow
+        .canonicalize()
+        .wrap_err_with(|| format!("failed to find `{}`", dataflow.display()))?;
+    let working_dir = dataflow.parent().unwrap();
+    let name = match name {
+        Some(name) => name,
+        None => working_dir.file_name().map_or("dataflow".into(), |name| {
+            name.to_string_lossy().into_owned()
+        }),
+    };
+    let valid_name = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid_name {
+        bail!("invalid bundle name `{name}`: use only letters, digits, `-`, `_`, and `.`");
+    }
+
+    let mut sources = Sources::default();
+    sources.files.insert(dataflow.clone());
+    for node in descriptor.resolve_aliases_and_set_defaults()? {
+        collect_sources(&node, working_dir, &mut sources)?;
+    }
+    for path in include {
+        sources.add_tree(path)?;
+    }
+
+    // the closest common directory of all files becomes the bundle root
+    let mut root = working_dir.to_owned();
+    for file in &sources.files {
+        while !file.starts_with(&root) {
+            root = root
+                .parent()
+                .ok_or_else(|| eyre!("no common directory for `{}`", file.display()))?
+                .to_owned();
+        }
+    }
+    let mut entries = BTreeMap::new();
+    for file in &sources.files {
+        entries.insert(archive_path(file.strip_prefix(&root)?), file.clone());
+    }
+    let descriptor_path = archive_path(dataflow.strip_prefix(&root)?);
+
+    let wheel_dir = std::env::temp_dir().join(format!("dora-wheels-{}", Uuid::now_v7()));
+    if wheels {
+        if sources.requirements.is_empty() {
+            eprintln!("no Python operator with `requirements`, skipping wheels");
+        }
+        build_wheels(&sources.requirements, &wheel_dir)?;
+        // the daemon installs the requirements from this directory, see
+        // `python_interpreter` in `dora-daemon`
+        let target = working_dir
+            .strip_prefix(&root)?
+            .join(".dora")
+            .join("wheels");
+        for entry in std::fs::read_dir(&wheel_dir).into_iter().flatten() {
+            let entry = entry?;
+            entries.insert(archive_path(&target.join(entry.file_name())), entry.path());
+        }
+    }
+
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{name}.tar.gz")));
+    let result = write_bundle(
+        &output,
+        &entries,
+        BundleManifest {
+            name,
+            dora_version: VERSION.into(),
+            descriptor: descriptor_path,
+            files: Vec::new(),
+            external: sources.external.into_iter().collect(),
+        },
+    );
+    let _ = std::fs::remove_dir_all(&wheel_dir);
+    let manifest = result?;
+
+    let size: u64 = manifest.files.iter().map(|file| file.size).sum();
+    println!(
+        "created `{}` with {} files ({})",
+        output.display(),
+        manifest.files.len(),
+        format_bytes(size as f64)
+    );
+    if !manifest.external.is_empty() {
+        println!("not bundled, must be installed on the target machines:");
+        for external in &manifest.external {
+            println!("  {external}");
+        }
+    }
+    Ok(())
+}
+
+fn collect_sources(
+    node: &ResolvedNode,
+    working_dir: &Path,
+    sources: &mut Sources,
+) -> eyre::Result<()> {
+    match &node.kind {
+        CoreNodeKind::Custom(custom) => {
+            let source = custom.source.as_str();
+            if source == SHELL_SOURCE || source == DYNAMIC_SOURCE || source_is_url(source) {
+                return Ok(());
+            }
+            let path = Path::new(source);
+            let path = if path.extension().is_none() {
+                path.with_extension(EXE_EXTENSION)
+            } else {
+                path.to_owned()
+            };
+            sources.add(node.id.as_ref(), &path, working_dir)?;
+        }
+        CoreNodeKind::Runtime(runtime) => {
+            for operator in &runtime.operators {
+                let id = format!("{}/{}", node.id, operator.id);
+                match &operator.config.source {
+                    OperatorSource::SharedLibrary(path) if !source_is_url(path) => {
+                        let path = adjust_shared_library_path(Path::new(path))?;
+                        sources.add(&id, &path, working_dir)?;
+                    }
+                    OperatorSource::Python(python) => {
+                        if !source_is_url(&python.source) {
+                            sources.add(&id, Path::new(&python.source), working_dir)?;
+                        }
+                        if let Some(requirements) = &python.requirements {
+                            sources.add(&id, Path::new(requirements), working_dir)?;
+                            sources.requirements.insert(working_dir.join(requirements));
+                        }
+                        if let Some(venv) = &python.venv {
+                            sources
+                                .external
+                                .insert(format!("{id}: virtual environment `{venv}`"));
+                        }
+                    }
+                    OperatorSource::Wasm(path)
+                    | OperatorSource::Julia(path)
+                    | OperatorSource::Matlab(path)
+                        if !source_is_url(path) =>
+                    {
+                        sources.add(&id, Path::new(path), working_dir)?;
+                    }
+                    OperatorSource::Lua(source) => {
+                        if let Some(path) = lua_source_path(source) {
+                            sources.add(&id, Path::new(path), working_dir)?;
+                        }
+                    }
+                    OperatorSource::Onnx(onnx) => {
+                        sources.add(&id, Path::new(&onnx.model), working_dir)?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Builds wheels of the given requirements and all their dependencies.
+///
+/// The wheels are built for the local platform and Python version, which need
+/// to match the target machines.
+fn build_wheels(requirements: &BTreeSet<PathBuf>, dir: &Path) -> eyre::Result<()> {
+    if requirements.is_empty() {
+        return Ok(());
+    }
+    let python = get_python_path()?;
+    for requirements in requirements {
+        println!("building wheels for `{}`", requirements.display());
+        let status = Command::new(&python)
+            .args(["-m", "pip", "wheel", "--wheel-dir"])
+            .arg(dir)
+            .arg("-r")
+            .arg(requirements)
+            .status()
+            .wrap_err("failed to run pip")?;
+        if !status.success() {
+            bail!("failed to build wheels for `{}`", requirements.display());
+        }
+    }
+    Ok(())
+}
+
+fn write_bundle(
+    output: &Path,
+    entries: &BTreeMap<String, PathBuf>,
+    mut manifest: BundleManifest,
+) -> eyre::Result<BundleManifest> {
+    let file = std::fs::File::create(output)
+        .wrap_err_with(|| format!("failed to create `{}`", output.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    for (path, source) in entries {
+        let data = std::fs::read(source)
+            .wrap_err_with(|| format!("failed to read `{}`", source.display()))?;
+        append(&mut archive, path, &data, file_mode(source))?;
+        manifest.files.push(BundleFile {
+            path: path.clone(),
+            size: data.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(&data)),
+        });
+    }
+    let raw = serde_json::to_vec_pretty(&manifest)?;
+    append(&mut archive, MANIFEST_FILE, &raw, 0o644)?;
+    archive
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|mut file| file.flush())
+        .wrap_err_with(|| format!("failed to write `{}`", output.display()))?;
+    Ok(manifest)
+}
+
+fn append(
+    archive: &mut tar::Builder<impl Write>,
+    path: &str,
+    data: &[u8],
+    mode: u32,
+) -> eyre::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(mode);
+    header.set_mtime(0);
+    header.set_entry_type(tar::EntryType::Regular);
+    archive
+        .append_data(&mut header, path, data)
+        .wrap_err_with(|| format!("failed to add `{path}` to bundle"))
+}
+
+#[cfg(unix)]
+fn file_mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).map_or(0o644, |metadata| metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_path: &Path) -> u32 {
+    0o755
+}
+
+/// Converts a relative path to the `/`-separated form used in the archive.
+fn archive_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Reads a bundle and verifies the checksums of its files.
+///
+/// Returns the manifest, the raw bundle, and the dataflow descriptor.
+pub fn read_bundle(path: &Path) -> eyre::Result<(BundleManifest, Vec<u8>, Descriptor)> {
+    let raw =
+        std::fs::read(path).wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+    let mut files = BTreeMap::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(raw.as_slice()));
+    for entry in archive.entries().wrap_err("failed to read bundle")? {
+        let mut entry = entry.wrap_err("failed to read bundle entry")?;
+        let entry_path = archive_path(&entry.path()?);
+        let mut data = Vec::new();
+        entry
+            .read_to_end(&mut data)
+            .wrap_err_with(|| format!("failed to read `{entry_path}` from bundle"))?;
+        files.insert(entry_path, data);
+    }
+
+    let manifest: BundleManifest = files
+        .get(MANIFEST_FILE)
+        .ok_or_else(|| eyre!("`{}` is not a dora bundle: no manifest", path.display()))
+        .and_then(|raw| serde_json::from_slice(raw).wrap_err("failed to parse bundle manifest"))?;
+    for file in &manifest.files {
+        let data = files
+            .get(&file.path)
+            .ok_or_else(|| eyre!("bundle is missing `{}`", file.path))?;
+        if format!("{:x}", Sha256::digest(data)) != file.sha256 {
+            bail!(
+                "checksum mismatch for `{}`, the bundle is corrupt",
+                file.path
+            );
+        }
+    }
+    if manifest.dora_version != VERSION {
+        eprintln!(
+            "warning: bundle was created with dora {}, but this is dora {VERSION}",
+            manifest.dora_version
+        );
+    }
+    let descriptor = files
+        .remove(&manifest.descriptor)
+        .ok_or_else(|| eyre!("bundle is missing `{}`", manifest.descriptor))?;
+    let descriptor = Descriptor::parse(descriptor)?;
+    Ok((manifest, raw, descriptor))
+}
+
+/// Extracts the bundle on the daemons of the given machines (all if empty)
+/// and optionally starts its dataflow.
+pub fn deploy(
+    session: &mut TcpRequestReplyConnection,
+    bundle: &Path,
+    machine_ids: BTreeSet<String>,
+    target_dir: Option<PathBuf>,
+    start: bool,
+) -> eyre::Result<()> {
+    let (manifest, raw, descriptor) = read_bundle(bundle)?;
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::Deploy {
+                name: manifest.name.clone(),
+                bundle: raw,
+                target_dir,
+                machine_ids,
+            })
+            .unwrap(),
+        )
+        .wrap_err("failed to send deploy message")?;
+    let deployed = match serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")? {
+        ControlRequestReply::Deployed(deployed) => deployed,
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected deploy reply: {other:?}"),
+    };
+    for (machine_id, dir) in &deployed {
+        let name = if machine_id.is_empty() {
+            "<default>"
+        } else {
+            machine_id.as_str()
+        };
+        println!(
+            "deployed `{}` to `{}` on `{name}`",
+            manifest.name,
+            dir.display()
+        );
+    }
+
+    if start {
+        // the coordinator passes a single working directory to all daemons
+        let dirs: BTreeSet<_> = deployed.values().collect();
+        let dir = match dirs.into_iter().collect::<Vec<_>>().as_slice() {
+            [dir] => dir.to_path_buf(),
+            [] => bail!("no daemon to start the dataflow on"),
+            _ => bail!(
+                "the bundle was extracted to different directories, use `--target-dir` \
+                to choose the same directory on all machines"
+            ),
+        };
+        let descriptor_path = dir.join(&manifest.descriptor);
+        let working_dir = descriptor_path
+            .parent()
+            .ok_or_else(|| eyre!("invalid descriptor path `{}`", manifest.descriptor))?;
+        start_dataflow(
+            descriptor,
+            Some(manifest.name),
+            working_dir.to_owned(),
+            session,
+        )?;
+    }
+    Ok(())
+}
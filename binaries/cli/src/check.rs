@@ -1,64 +1,371 @@
-use crate::connect_to_coordinator;
+use crate::{
+    connect_to_coordinator,
+    formatting::{print_structured, OutputFormat},
+};
 use communication_layer_request_reply::TcpRequestReplyConnection;
-use dora_core::topics::{ControlRequest, ControlRequestReply};
+use dora_core::{
+    adjust_shared_library_path,
+    config::{InputMapping, NodeId},
+    descriptor::{
+        lua_source_path, resolve_path, source_is_url, CoreNodeKind, Descriptor, OperatorSource,
+        ResolvedNode, DYNAMIC_SOURCE, SHELL_SOURCE,
+    },
+    topics::{ControlRequest, ControlRequestReply},
+};
 use eyre::{bail, Context};
+use serde::Serialize;
 use std::{
+    collections::{BTreeMap, BTreeSet},
     io::{IsTerminal, Write},
     net::SocketAddr,
+    path::{Path, PathBuf},
 };
 use termcolor::{Color, ColorChoice, ColorSpec, WriteColor};
 
-pub fn check_environment(coordinator_addr: SocketAddr) -> eyre::Result<()> {
-    let mut error_occurred = false;
+/// Exit code if the dataflow is invalid, e.g. because a node binary is missing.
+pub const EXIT_DATAFLOW_INVALID: i32 = 1;
+/// Exit code if the dataflow is valid, but the coordinator or a required
+/// daemon is not available.
+pub const EXIT_ENVIRONMENT: i32 = 2;
+
+#[derive(Debug, Default, Serialize)]
+pub struct CheckReport {
+    ok: bool,
+    checks: Vec<CheckResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct CheckResult {
+    name: String,
+    #[serde(skip)]
+    kind: CheckKind,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckKind {
+    Dataflow,
+    Environment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Status {
+    Ok,
+    Warning,
+    Error,
+    Skipped,
+}
+
+impl CheckReport {
+    fn push(&mut self, name: impl Into<String>, kind: CheckKind, result: Result<String, String>) {
+        let (status, message) = match result {
+            Ok(message) => (Status::Ok, message),
+            Err(message) => (Status::Error, message),
+        };
+        self.push_status(name, kind, status, message);
+    }
+
+    fn push_status(
+        &mut self,
+        name: impl Into<String>,
+        kind: CheckKind,
+        status: Status,
+        message: String,
+    ) {
+        self.checks.push(CheckResult {
+            name: name.into(),
+            kind,
+            status,
+            message: Some(message).filter(|m| !m.is_empty()),
+        });
+    }
+
+    /// Returns the process exit code for this report, `0` if all checks passed.
+    ///
+    /// Warnings don't fail the check.
+    pub fn exit_code(&self) -> i32 {
+        let failed = |kind| {
+            self.checks
+                .iter()
+                .any(|c| c.kind == kind && c.status == Status::Error)
+        };
+        if failed(CheckKind::Dataflow) {
+            EXIT_DATAFLOW_INVALID
+        } else if failed(CheckKind::Environment) {
+            EXIT_ENVIRONMENT
+        } else {
+            0
+        }
+    }
+
+    pub fn print(&self, format: OutputFormat) -> eyre::Result<()> {
+        if print_structured(self, format)? {
+            return Ok(());
+        }
+
+        let mut stdout = color_stdout();
+        for check in &self.checks {
+            write!(stdout, "{}: ", check.name)?;
+            write_status(&mut stdout, check.status)?;
+            match &check.message {
+                Some(message) => writeln!(stdout, " ({message})")?,
+                None => writeln!(stdout)?,
+            }
+        }
+        writeln!(stdout)?;
+        Ok(())
+    }
+}
 
+pub(crate) fn color_stdout() -> termcolor::StandardStream {
     let color_choice = if std::io::stdout().is_terminal() {
         ColorChoice::Auto
     } else {
         ColorChoice::Never
     };
-    let mut stdout = termcolor::StandardStream::stdout(color_choice);
-
-    // check whether coordinator is running
-    write!(stdout, "Dora Coordinator: ")?;
-    let mut session = match connect_to_coordinator(coordinator_addr) {
-        Ok(session) => {
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
-            writeln!(stdout, "ok")?;
-            Some(session)
+    termcolor::StandardStream::stdout(color_choice)
+}
+
+/// Writes the status in its color, if the output supports colors.
+pub(crate) fn write_status(stdout: &mut impl WriteColor, status: Status) -> std::io::Result<()> {
+    let (color, status) = match status {
+        Status::Ok => (Color::Green, "ok"),
+        Status::Warning => (Color::Yellow, "warning"),
+        Status::Error => (Color::Red, "error"),
+        Status::Skipped => (Color::White, "skipped"),
+    };
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(color)));
+    write!(stdout, "{status}")?;
+    let _ = stdout.reset();
+    Ok(())
+}
+
+/// Checks the given dataflow and, unless `offline` is set, whether the
+/// coordinator and the daemons that the dataflow is deployed to are running.
+pub fn check(
+    dataflow: Option<&Path>,
+    coordinator_addr: SocketAddr,
+    offline: bool,
+) -> eyre::Result<CheckReport> {
+    let mut report = CheckReport::default();
+
+    let dataflow = dataflow.map(|path| check_dataflow(path, &mut report));
+
+    let mut session = None;
+    if offline {
+        for name in ["Dora Coordinator", "Dora Daemon"] {
+            report.push_status(name, CheckKind::Environment, Status::Skipped, String::new());
         }
-        Err(_) => {
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)));
-            writeln!(stdout, "not running")?;
-            error_occurred = true;
-            None
+    } else {
+        session = connect_to_coordinator(coordinator_addr).ok();
+        let result = match &session {
+            Some(_) => Ok(String::new()),
+            None => Err("not running".into()),
+        };
+        report.push("Dora Coordinator", CheckKind::Environment, result);
+
+        let daemon_running = session
+            .as_deref_mut()
+            .map(daemon_running)
+            .transpose()?
+            .unwrap_or(false);
+        let result = match daemon_running {
+            true => Ok(String::new()),
+            false => Err("not running".into()),
+        };
+        report.push("Dora Daemon", CheckKind::Environment, result);
+    }
+
+    if let Some(Some(nodes)) = &dataflow {
+        match session.as_deref_mut() {
+            Some(session) => check_machines(nodes, session, &mut report)?,
+            None => report.push_status(
+                "Machines",
+                CheckKind::Environment,
+                Status::Skipped,
+                String::new(),
+            ),
+        }
+    }
+
+    report.ok = report.exit_code() == 0;
+    Ok(report)
+}
+
+/// Returns the resolved nodes of the dataflow if it could be parsed.
+fn check_dataflow(path: &Path, report: &mut CheckReport) -> Option<Vec<ResolvedNode>> {
+    let parsed = Descriptor::blocking_read(path).and_then(|descriptor| {
+        let working_dir = path
+            .canonicalize()
+            .context("failed to canonicalize dataflow path")?
+            .parent()
+            .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
+            .to_owned();
+        let nodes = descriptor.resolve_aliases_and_set_defaults()?;
+        Ok((descriptor, working_dir, nodes))
+    });
+    let (descriptor, working_dir, nodes) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            report.push("Descriptor", CheckKind::Dataflow, Err(format!("{err:#}")));
+            return None;
         }
     };
+    report.push("Descriptor", CheckKind::Dataflow, Ok(String::new()));
 
-    let _ = stdout.reset();
+    let validation = descriptor
+        .check(&working_dir)
+        .map(|()| String::new())
+        .map_err(|err| format!("{err:#}"));
+    report.push("Validation", CheckKind::Dataflow, validation);
 
-    // check whether daemon is running
-    write!(stdout, "Dora Daemon: ")?;
-    if session
-        .as_deref_mut()
-        .map(daemon_running)
-        .transpose()?
-        .unwrap_or(false)
-    {
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
-        writeln!(stdout, "ok")?;
-    } else {
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)));
-        writeln!(stdout, "not running")?;
-        error_occurred = true;
+    for node in &nodes {
+        for (name, result) in node_sources(node, &working_dir) {
+            report.push(
+                name,
+                CheckKind::Dataflow,
+                result
+                    .map(|path| path.display().to_string())
+                    .map_err(|err| format!("{err:#}")),
+            );
+        }
+    }
+
+    for output in unused_outputs(&nodes) {
+        report.push_status(
+            format!("Output `{output}`"),
+            CheckKind::Dataflow,
+            Status::Warning,
+            "not used as input by any node".into(),
+        );
     }
-    let _ = stdout.reset();
 
-    writeln!(stdout)?;
+    Some(nodes)
+}
 
-    if error_occurred {
-        bail!("Environment check failed.");
+/// Resolves the executables and source files of the node and its operators.
+///
+/// Sources that are resolved at runtime, e.g. URLs or container images, are
+/// not listed.
+fn node_sources(node: &ResolvedNode, working_dir: &Path) -> Vec<(String, eyre::Result<PathBuf>)> {
+    let mut sources = Vec::new();
+    match &node.kind {
+        CoreNodeKind::Custom(custom) => {
+            let source = custom.source.as_str();
+            let resolved_at_runtime = source == SHELL_SOURCE
+                || source == DYNAMIC_SOURCE
+                || source_is_url(source)
+                || node.container.is_some();
+            if !resolved_at_runtime {
+                sources.push((
+                    format!("Node `{}`", node.id),
+                    resolve_path(source, working_dir),
+                ));
+            }
+        }
+        CoreNodeKind::Runtime(runtime) => {
+            for operator in &runtime.operators {
+                let path = match &operator.config.source {
+                    OperatorSource::SharedLibrary(path) if !source_is_url(path) => {
+                        adjust_shared_library_path(Path::new(path))
+                    }
+                    OperatorSource::Python(python) if !source_is_url(&python.source) => {
+                        Ok(PathBuf::from(&python.source))
+                    }
+                    OperatorSource::Wasm(path)
+                    | OperatorSource::Julia(path)
+                    | OperatorSource::Matlab(path)
+                        if !source_is_url(path) =>
+                    {
+                        Ok(PathBuf::from(path))
+                    }
+                    OperatorSource::Lua(source) => match lua_source_path(source) {
+                        Some(path) => Ok(PathBuf::from(path)),
+                        None => continue,
+                    },
+                    OperatorSource::Onnx(onnx) => Ok(PathBuf::from(&onnx.model)),
+                    _ => continue,
+                };
+                let resolved = path.and_then(|path| {
+                    let path = working_dir.join(path);
+                    match path.exists() {
+                        true => Ok(path),
+                        false => bail!("no file at `{}`", path.display()),
+                    }
+                });
+                sources.push((format!("Operator `{}/{}`", node.id, operator.id), resolved));
+            }
+        }
+    }
+    sources
+}
+
+/// Lists the outputs that are not mapped to any input, in `node/output` form.
+fn unused_outputs(nodes: &[ResolvedNode]) -> Vec<String> {
+    let mut used = BTreeSet::new();
+    for node in nodes {
+        for input in node.kind.run_config().inputs.values() {
+            for mapping in input.sources() {
+                if let InputMapping::User(mapping) = mapping {
+                    used.insert((mapping.source.clone(), mapping.output.clone()));
+                }
+            }
+        }
     }
 
+    let mut unused = Vec::new();
+    for node in nodes {
+        for output in node.kind.run_config().outputs {
+            if !used.contains(&(node.id.clone(), output.clone())) {
+                unused.push(format!("{}/{output}", node.id));
+            }
+        }
+    }
+    unused
+}
+
+/// Checks that a daemon is registered for each machine of the dataflow.
+fn check_machines(
+    nodes: &[ResolvedNode],
+    session: &mut TcpRequestReplyConnection,
+    report: &mut CheckReport,
+) -> eyre::Result<()> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::ConnectedMachines).unwrap())
+        .wrap_err("failed to send ConnectedMachines message")?;
+    let connected = match serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")? {
+        ControlRequestReply::ConnectedMachines(machines) => machines,
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected reply to connected machines request: {other:?}"),
+    };
+
+    let mut machines: BTreeMap<&str, Vec<&NodeId>> = BTreeMap::new();
+    for node in nodes {
+        machines
+            .entry(node.deploy.machine.as_str())
+            .or_default()
+            .push(&node.id);
+    }
+    for (machine, nodes) in machines {
+        let name = if machine.is_empty() {
+            "Machine <default>".to_owned()
+        } else {
+            format!("Machine `{machine}`")
+        };
+        let result = if connected.contains(machine) {
+            Ok(String::new())
+        } else {
+            let nodes: Vec<_> = nodes.iter().map(|id| id.to_string()).collect();
+            Err(format!(
+                "no daemon registered, required by {}",
+                nodes.join(", ")
+            ))
+        };
+        report.push(name, CheckKind::Environment, result);
+    }
     Ok(())
 }
 
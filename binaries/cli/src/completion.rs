@@ -0,0 +1,69 @@
+use std::{io::Write, net::SocketAddr};
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+
+use crate::{connect_to_coordinator, query_running_dataflows, Args};
+
+/// Subcommands that take the name or UUID of a running dataflow.
+const DATAFLOW_SUBCOMMANDS: &str = "stop logs inspect top graph record operator";
+
+pub fn generate(shell: Shell) -> eyre::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    clap_complete::generate(shell, &mut Args::command(), "dora", &mut stdout);
+
+    // the generated scripts only know the static arguments, so we register
+    // wrappers that add the running dataflows
+    let dynamic = match shell {
+        Shell::Bash => format!(
+            r#"
+_dora_dataflows() {{
+    _dora "$@"
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [[ " {DATAFLOW_SUBCOMMANDS} " == *" ${{COMP_WORDS[1]}} "* && "$cur" != -* ]]; then
+        COMPREPLY+=($(compgen -W "$(dora complete-dataflows 2>/dev/null)" -- "$cur"))
+    fi
+}}
+complete -F _dora_dataflows -o bashdefault -o default dora
+"#
+        ),
+        Shell::Zsh => format!(
+            r#"
+_dora_dataflows() {{
+    _dora "$@"
+    if [[ " {DATAFLOW_SUBCOMMANDS} " == *" $words[2] "* && "$PREFIX" != -* ]]; then
+        local -a dataflows
+        dataflows=(${{(f)"$(dora complete-dataflows 2>/dev/null)"}})
+        compadd -a dataflows
+    fi
+}}
+compdef _dora_dataflows dora
+"#
+        ),
+        Shell::Fish => format!(
+            r#"
+complete -c dora -n "__fish_seen_subcommand_from {DATAFLOW_SUBCOMMANDS}" -f -a "(dora complete-dataflows 2>/dev/null)"
+"#
+        ),
+        _ => String::new(),
+    };
+    stdout.write_all(dynamic.as_bytes())?;
+    Ok(())
+}
+
+/// Prints nothing if the coordinator is not reachable, so that completion
+/// keeps working without a running coordinator.
+pub fn print_dataflows(coordinator_addr: SocketAddr) {
+    let Ok(mut session) = connect_to_coordinator(coordinator_addr) else {
+        return;
+    };
+    let Ok(list) = query_running_dataflows(&mut *session) else {
+        return;
+    };
+    for id in list.get_active() {
+        if let Some(name) = &id.name {
+            println!("{name}");
+        }
+        println!("{}", id.uuid);
+    }
+}
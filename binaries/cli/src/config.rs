@@ -0,0 +1,106 @@
+//! Coordinator profiles of the CLI, read from `~/.dora/config.toml`.
+//!
+//! Example:
+//!
+//! ```toml
+//! default_profile = "robot"
+//!
+//! [profiles.robot]
+//! coordinator = "robot.local:6012"
+//!
+//! [profiles.sim]
+//! coordinator = "192.168.1.20"
+//! ```
+//!
+//! The port defaults to the coordinator control port.
+
+use std::{
+    collections::BTreeMap,
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+};
+
+use dora_core::topics::DORA_COORDINATOR_PORT_CONTROL_DEFAULT;
+use eyre::{eyre, Context};
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct CliConfig {
+    /// Profile that is used if neither `--coordinator` nor `--profile` is given.
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Profile {
+    /// Coordinator control address in `host[:port]` form.
+    pub coordinator: String,
+}
+
+impl CliConfig {
+    pub fn path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".dora").join("config.toml"))
+    }
+
+    /// Returns the default config if the config file does not exist.
+    pub fn read() -> eyre::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).wrap_err_with(|| format!("failed to read `{}`", path.display()))
+            }
+        };
+        toml::from_str(&raw).wrap_err_with(|| format!("failed to parse `{}`", path.display()))
+    }
+}
+
+/// Determines the coordinator to connect to from the `--coordinator` and
+/// `--profile` arguments, falling back to the default profile of the config.
+///
+/// Returns `None` if no coordinator is configured.
+pub fn coordinator_override(
+    coordinator: Option<&str>,
+    profile: Option<&str>,
+) -> eyre::Result<Option<SocketAddr>> {
+    if let Some(coordinator) = coordinator {
+        return resolve(coordinator).map(Some);
+    }
+    let config = CliConfig::read()?;
+    let Some(name) = profile.or(config.default_profile.as_deref()) else {
+        return Ok(None);
+    };
+    let profile = config.profiles.get(name).ok_or_else(|| {
+        let path = CliConfig::path().unwrap_or_default();
+        eyre!("no profile `{name}` in `{}`", path.display())
+    })?;
+    resolve(&profile.coordinator)
+        .wrap_err_with(|| format!("invalid coordinator of profile `{name}`"))
+        .map(Some)
+}
+
+fn resolve(coordinator: &str) -> eyre::Result<SocketAddr> {
+    // IPv6 addresses need brackets when a port is given, e.g. `[::1]:6012`
+    let has_port = match coordinator.rsplit_once(':') {
+        Some((host, port)) => {
+            port.parse::<u16>().is_ok() && (!host.contains(':') || host.ends_with(']'))
+        }
+        None => false,
+    };
+    let addr = if has_port {
+        coordinator.to_owned()
+    } else {
+        let host = coordinator.trim_start_matches('[').trim_end_matches(']');
+        match host.parse::<std::net::Ipv6Addr>() {
+            Ok(_) => format!("[{host}]:{DORA_COORDINATOR_PORT_CONTROL_DEFAULT}"),
+            Err(_) => format!("{host}:{DORA_COORDINATOR_PORT_CONTROL_DEFAULT}"),
+        }
+    };
+    addr.to_socket_addrs()
+        .wrap_err_with(|| format!("failed to resolve coordinator address `{coordinator}`"))?
+        .next()
+        .ok_or_else(|| eyre!("coordinator address `{coordinator}` did not resolve"))
+}
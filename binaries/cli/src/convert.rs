@@ -0,0 +1,650 @@
+//! Conversion between dataflow descriptors and ROS2 systems, see `dora convert`.
+//!
+//! ROS2 launch files and recordings don't say which node publishes or
+//! subscribes to which topic, so the imported descriptor is a skeleton: it
+//! lists the nodes and recorded topics, but their connections need to be
+//! filled in by hand. The export maps the nodes, outputs, and inputs of a
+//! dataflow to ROS2 nodes, topics, and subscriptions, so that the ported
+//! dataflow can be compared with the original system.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use dora_core::{
+    config::{DataId, Input, InputMapping, InputPriority, NodeId, QosPreset},
+    descriptor::{CoreNodeKind, Descriptor, DYNAMIC_SOURCE},
+};
+use eyre::{bail, Context};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use tabwriter::TabWriter;
+
+use crate::formatting::{print_structured, OutputFormat};
+
+/// ID of the node that the topics of a rosbag2 recording are assigned to.
+const BAG_NODE: &str = "ros2-bag";
+
+/// Depth of ROS2 subscriptions and of dora inputs without a `queue_size`.
+const DEFAULT_DEPTH: usize = 10;
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ConvertCommand {
+    /// Create a dataflow skeleton from a ROS2 launch file and/or a rosbag2 recording.
+    ///
+    /// Each `<node>` of the XML launch file becomes a node of the dataflow, with its
+    /// parameters passed as environment variables. The recorded topics become outputs
+    /// of a `ros2-bag` node. Python launch files are not supported.
+    FromRos2 {
+        /// XML launch file
+        #[clap(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        launch: Option<PathBuf>,
+        /// rosbag2 recording directory or its `metadata.yaml`
+        #[clap(long, value_name = "PATH", value_hint = clap::ValueHint::AnyPath)]
+        bag: Option<PathBuf>,
+        /// Path of the created descriptor (default: print it)
+        #[clap(long, short, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+    /// Describe a dataflow in ROS2 terms, to compare it with an existing ROS2 system.
+    ToRos2 {
+        /// Path to the dataflow descriptor file
+        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        dataflow: PathBuf,
+        /// Output format
+        #[clap(long, value_name = "FORMAT", default_value = "table")]
+        output: OutputFormat,
+    },
+}
+
+pub fn run(command: ConvertCommand) -> eyre::Result<()> {
+    match command {
+        ConvertCommand::FromRos2 {
+            launch,
+            bag,
+            output,
+        } => {
+            if launch.is_none() && bag.is_none() {
+                bail!("at least one of `--launch` and `--bag` is required");
+            }
+            let skeleton = from_ros2(launch.as_deref(), bag.as_deref())?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, skeleton)
+                        .wrap_err_with(|| format!("failed to write `{}`", path.display()))?;
+                    println!("created dataflow skeleton `{}`", path.display());
+                }
+                None => print!("{skeleton}"),
+            }
+            Ok(())
+        }
+        ConvertCommand::ToRos2 { dataflow, output } => to_ros2(&dataflow)?.print(output),
+    }
+}
+
+/// A `<node>` element of a ROS2 XML launch file.
+#[derive(Debug)]
+struct LaunchNode {
+    package: String,
+    executable: String,
+    name: Option<String>,
+    namespace: String,
+    args: Option<String>,
+    parameters: Vec<(String, String)>,
+    parameter_files: Vec<String>,
+    remappings: Vec<(String, String)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BagMetadata {
+    rosbag2_bagfile_information: BagInformation,
+}
+
+#[derive(Debug, Deserialize)]
+struct BagInformation {
+    #[serde(default)]
+    topics_with_message_count: Vec<BagTopic>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BagTopic {
+    topic_metadata: TopicMetadata,
+    #[serde(default)]
+    message_count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopicMetadata {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+/// Creates the YAML of the dataflow skeleton.
+///
+/// The YAML is assembled by hand instead of serializing a `Descriptor`, so
+/// that the ROS2 details without a dora equivalent can be kept as comments.
+fn from_ros2(launch: Option<&Path>, bag: Option<&Path>) -> eyre::Result<String> {
+    let launch_nodes = launch.map(parse_launch).transpose()?.unwrap_or_default();
+    let bag_topics = bag.map(read_bag_metadata).transpose()?.unwrap_or_default();
+    let topic_outputs: BTreeMap<&str, DataId> = bag_topics
+        .iter()
+        .map(|topic| {
+            let name = topic.topic_metadata.name.as_str();
+            (name, topic_output_id(name))
+        })
+        .collect();
+
+    if launch_nodes.is_empty() && bag_topics.is_empty() {
+        bail!("no nodes or topics found");
+    }
+
+    let sources: Vec<_> = launch
+        .into_iter()
+        .chain(bag)
+        .map(|path| format!("`{}`", path.display()))
+        .collect();
+    let mut yaml = String::new();
+    writeln!(
+        yaml,
+        "# Generated by `dora convert from-ros2` from {}.",
+        sources.join(" and ")
+    )?;
+    yaml.push_str(
+        "#\n\
+        # The `path` of each node is a placeholder for the ported node. ROS2 launch\n\
+        # files don't declare publishers and subscriptions, so the `inputs` and\n\
+        # `outputs` of the nodes need to be filled in.\n\
+        nodes:\n",
+    );
+
+    // reserved first, so that it doesn't change if a ROS2 node has the same name
+    let mut used_ids = BTreeSet::new();
+    let bag_id = unique_id(BAG_NODE, &mut used_ids);
+    for node in &launch_nodes {
+        let id = unique_id(
+            node.name.as_deref().unwrap_or(&node.executable),
+            &mut used_ids,
+        );
+        let mut comments = vec![format!(
+            "ROS2 node `{}/{}` in namespace `{}`",
+            node.package, node.executable, node.namespace
+        )];
+        for file in &node.parameter_files {
+            comments.push(format!("parameters from `{file}`"));
+        }
+        for (from, to) in &node.remappings {
+            comments.push(format!("remaps `{from}` to `{to}`"));
+            for topic in [from, to] {
+                let topic = if topic.starts_with('/') {
+                    topic.clone()
+                } else {
+                    format!("/{topic}")
+                };
+                if let Some(output) = topic_outputs.get(topic.as_str()) {
+                    comments.push(format!(
+                        "recorded topic `{topic}`: publish it as output `{output}` or add \
+                        `{output}: {bag_id}/{output}` to the inputs"
+                    ));
+                }
+            }
+        }
+
+        let mut entry = Mapping::new();
+        entry.insert("id".into(), id.into());
+        entry.insert(
+            "description".into(),
+            format!(
+                "Port of the ROS2 node `{}/{}`",
+                node.package, node.executable
+            )
+            .into(),
+        );
+        entry.insert("path".into(), node.executable.clone().into());
+        if let Some(args) = &node.args {
+            entry.insert("args".into(), args.clone().into());
+        }
+        if !node.parameters.is_empty() {
+            let env: Mapping = node
+                .parameters
+                .iter()
+                .map(|(name, value)| (env_name(name).into(), env_value(value)))
+                .collect();
+            entry.insert("env".into(), env.into());
+        }
+        write_node(&mut yaml, &comments, entry)?;
+    }
+
+    if !bag_topics.is_empty() {
+        let comments = bag_topics
+            .iter()
+            .map(|topic| {
+                format!(
+                    "`{}` ({}, {} messages) -> `{}`",
+                    topic.topic_metadata.name,
+                    topic.topic_metadata.ty,
+                    topic.message_count,
+                    topic_outputs[topic.topic_metadata.name.as_str()]
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut entry = Mapping::new();
+        entry.insert("id".into(), bag_id.into());
+        entry.insert(
+            "description".into(),
+            "Publishes the recorded topics, e.g. by subscribing to them through the ROS2 \
+            bridge while `ros2 bag play` runs"
+                .into(),
+        );
+        entry.insert("path".into(), BAG_NODE.into());
+        entry.insert(
+            "outputs".into(),
+            topic_outputs
+                .values()
+                .map(|output| Value::from(output.to_string()))
+                .collect::<Vec<_>>()
+                .into(),
+        );
+        write_node(&mut yaml, &comments, entry)?;
+    }
+
+    Ok(yaml)
+}
+
+fn write_node(yaml: &mut String, comments: &[String], entry: Mapping) -> eyre::Result<()> {
+    for comment in comments {
+        writeln!(yaml, "  # {comment}")?;
+    }
+    let node = serde_yaml::to_string(&[entry])?;
+    for line in node.lines() {
+        writeln!(yaml, "  {line}")?;
+    }
+    Ok(())
+}
+
+fn parse_launch(path: &Path) -> eyre::Result<Vec<LaunchNode>> {
+    let text = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+    let document = roxmltree::Document::parse(&text)
+        .wrap_err_with(|| format!("failed to parse `{}` as XML", path.display()))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "launch" {
+        bail!(
+            "`{}` is not a ROS2 XML launch file: expected a `<launch>` root element",
+            path.display()
+        );
+    }
+
+    let mut nodes = Vec::new();
+    let mut skipped = BTreeSet::new();
+    for element in root.descendants().filter(|e| e.is_element()) {
+        match element.tag_name().name() {
+            "node" => nodes.push(launch_node(&document, element)?),
+            tag @ ("include" | "executable" | "node_container" | "composable_node") => {
+                skipped.insert(tag);
+            }
+            _ => {}
+        }
+    }
+    for tag in skipped {
+        eprintln!("warning: `<{tag}>` elements are not converted");
+    }
+    Ok(nodes)
+}
+
+fn launch_node(
+    document: &roxmltree::Document,
+    element: roxmltree::Node,
+) -> eyre::Result<LaunchNode> {
+    let (Some(package), Some(executable)) = (element.attribute("pkg"), element.attribute("exec"))
+    else {
+        let position = document.text_pos_at(element.range().start);
+        bail!(
+            "`<node>` in line {} needs `pkg` and `exec` attributes",
+            position.row
+        );
+    };
+
+    let mut parameters = Vec::new();
+    let mut parameter_files = Vec::new();
+    let mut remappings = Vec::new();
+    for child in element.children().filter(|c| c.is_element()) {
+        match child.tag_name().name() {
+            "param" => match (child.attribute("name"), child.attribute("value")) {
+                (Some(name), Some(value)) => parameters.push((name.to_owned(), value.to_owned())),
+                _ => parameter_files.extend(child.attribute("from").map(str::to_owned)),
+            },
+            "remap" => {
+                if let (Some(from), Some(to)) = (child.attribute("from"), child.attribute("to")) {
+                    remappings.push((from.to_owned(), to.to_owned()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(LaunchNode {
+        package: package.to_owned(),
+        executable: executable.to_owned(),
+        name: element.attribute("name").map(str::to_owned),
+        namespace: namespace(element),
+        args: element.attribute("args").map(str::to_owned),
+        parameters,
+        parameter_files,
+        remappings,
+    })
+}
+
+/// Combines the namespaces pushed by the enclosing `<group>`s with the
+/// namespace of the node itself.
+fn namespace(element: roxmltree::Node) -> String {
+    let mut parts: Vec<&str> = element
+        .ancestors()
+        .filter(|a| a.has_tag_name("group"))
+        .flat_map(|group| group.children())
+        .filter(|c| c.has_tag_name("push-ros-namespace") || c.has_tag_name("push_ros_namespace"))
+        .filter_map(|c| c.attribute("namespace"))
+        .collect();
+    // ancestors are iterated from the innermost one
+    parts.reverse();
+    parts.extend(element.attribute("namespace"));
+    let parts: Vec<_> = parts
+        .iter()
+        .map(|part| part.trim_matches('/'))
+        .filter(|part| !part.is_empty())
+        .collect();
+    format!("/{}", parts.join("/"))
+}
+
+fn read_bag_metadata(path: &Path) -> eyre::Result<Vec<BagTopic>> {
+    let path = if path.is_dir() {
+        path.join("metadata.yaml")
+    } else {
+        path.to_owned()
+    };
+    let raw =
+        std::fs::read(&path).wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+    let metadata: BagMetadata = serde_yaml::from_slice(&raw)
+        .wrap_err_with(|| format!("failed to parse rosbag2 metadata `{}`", path.display()))?;
+    Ok(metadata
+        .rosbag2_bagfile_information
+        .topics_with_message_count)
+}
+
+/// Returns a node ID based on the given name that is not in `used` yet.
+fn unique_id(name: &str, used: &mut BTreeSet<String>) -> String {
+    let base: String = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .collect();
+    let mut id = base.clone();
+    let mut counter = 2;
+    while used.contains(&id) {
+        id = format!("{base}-{counter}");
+        counter += 1;
+    }
+    used.insert(id.clone());
+    id
+}
+
+/// Output ID of a recorded topic, e.g. `camera_image` for `/camera/image`.
+fn topic_output_id(topic: &str) -> DataId {
+    let id = topic.trim_matches('/').replace('/', "_");
+    DataId::from(if id.is_empty() { "root".to_owned() } else { id })
+}
+
+/// Environment variable of a ROS2 parameter, e.g. `MAX_SPEED` for `max.speed`.
+fn env_name(parameter: &str) -> String {
+    parameter
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+fn env_value(value: &str) -> Value {
+    if let Ok(value) = value.parse::<bool>() {
+        value.into()
+    } else if let Ok(value) = value.parse::<i64>() {
+        value.into()
+    } else if let Ok(value) = value.parse::<f64>() {
+        value.into()
+    } else {
+        value.into()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Ros2Report {
+    nodes: Vec<Ros2Node>,
+    topics: Vec<Ros2Topic>,
+}
+
+#[derive(Debug, Serialize)]
+struct Ros2Node {
+    id: NodeId,
+    /// Fully qualified name of the equivalent ROS2 node.
+    ros2_name: String,
+    /// ROS2 concept that corresponds to the node.
+    kind: String,
+    publishers: Vec<String>,
+    subscriptions: Vec<Ros2Subscription>,
+    /// Timer inputs, which correspond to wall timers in ROS2.
+    timers: BTreeMap<DataId, String>,
+    /// Features of the node that have no direct ROS2 equivalent.
+    notes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Ros2Subscription {
+    input: DataId,
+    topic: String,
+    qos: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Ros2Topic {
+    name: String,
+    publisher: NodeId,
+    subscribers: Vec<NodeId>,
+}
+
+fn to_ros2(dataflow: &Path) -> eyre::Result<Ros2Report> {
+    let descriptor = Descriptor::blocking_read(dataflow)?;
+    let nodes = descriptor.resolve_aliases_and_set_defaults()?;
+
+    let mut topics: BTreeMap<String, Ros2Topic> = BTreeMap::new();
+    let mut report = Ros2Report {
+        nodes: Vec::new(),
+        topics: Vec::new(),
+    };
+    for node in &nodes {
+        let run_config = node.kind.run_config();
+        let mut notes = Vec::new();
+        let kind = match &node.kind {
+            CoreNodeKind::Custom(custom) if custom.source == DYNAMIC_SOURCE => {
+                notes.push("dynamic node that is started outside of the dataflow".to_owned());
+                "node".to_owned()
+            }
+            CoreNodeKind::Custom(custom) => format!("node (`{}`)", custom.source),
+            CoreNodeKind::Runtime(runtime) => {
+                format!(
+                    "component container with {} component(s)",
+                    runtime.operators.len()
+                )
+            }
+        };
+        if !node.deploy.machine.is_empty() {
+            notes.push(format!("runs on machine `{}`", node.deploy.machine));
+        }
+        if let Ok(Some(target)) = node.send_stdout_as() {
+            notes.push(format!(
+                "stdout is published as output `{target}`, ROS2 logs go to `/rosout` instead"
+            ));
+        }
+
+        let publishers = run_config
+            .outputs
+            .iter()
+            .map(|output| {
+                let name = ros2_name(&format!("{}/{output}", node.id));
+                topics.entry(name.clone()).or_insert_with(|| Ros2Topic {
+                    name: name.clone(),
+                    publisher: node.id.clone(),
+                    subscribers: Vec::new(),
+                });
+                name
+            })
+            .collect();
+
+        let mut subscriptions = Vec::new();
+        let mut timers = BTreeMap::new();
+        for (input_id, input) in &run_config.inputs {
+            input_notes(input_id, input, &mut notes);
+            for source in input.sources() {
+                match source {
+                    InputMapping::Timer { interval } => {
+                        timers.insert(input_id.clone(), format!("{interval:?}"));
+                    }
+                    InputMapping::User(mapping) => {
+                        let topic = ros2_name(&format!("{}/{}", mapping.source, mapping.output));
+                        subscriptions.push(Ros2Subscription {
+                            input: input_id.clone(),
+                            topic,
+                            qos: qos_profile(input),
+                        });
+                    }
+                }
+            }
+        }
+
+        report.nodes.push(Ros2Node {
+            id: node.id.clone(),
+            ros2_name: ros2_name(node.id.as_ref()),
+            kind,
+            publishers,
+            subscriptions,
+            timers,
+            notes,
+        });
+    }
+
+    for node in &report.nodes {
+        for subscription in &node.subscriptions {
+            if let Some(topic) = topics.get_mut(&subscription.topic) {
+                if !topic.subscribers.contains(&node.id) {
+                    topic.subscribers.push(node.id.clone());
+                }
+            }
+        }
+    }
+    report.topics = topics.into_values().collect();
+    Ok(report)
+}
+
+/// Settings of the input that ROS2 subscriptions don't support.
+fn input_notes(input_id: &DataId, input: &Input, notes: &mut Vec<String>) {
+    if !input.additional_sources.is_empty() {
+        notes.push(format!(
+            "input `{input_id}` merges {} sources, ROS2 needs a subscription per topic",
+            input.additional_sources.len() + 1
+        ));
+    }
+    if input.rate_limit.is_some() || input.downsample.is_some() {
+        notes.push(format!(
+            "input `{input_id}` is rate limited, e.g. use `topic_tools throttle` in ROS2"
+        ));
+    }
+    if input.compression.is_some() {
+        notes.push(format!(
+            "input `{input_id}` is compressed between machines, ROS2 has no equivalent"
+        ));
+    }
+    if let Some(priority) = input.priority.filter(|p| *p != InputPriority::Normal) {
+        notes.push(format!(
+            "input `{input_id}` has {priority:?} priority, use separate callback groups in ROS2"
+        ));
+    }
+}
+
+/// Describes the ROS2 QoS profile that matches the delivery of the input.
+fn qos_profile(input: &Input) -> String {
+    let reliability = match input.qos {
+        Some(QosPreset::BestEffort | QosPreset::SensorData) => "best_effort",
+        Some(QosPreset::Reliable | QosPreset::LatchedConfig) | None => "reliable",
+    };
+    let depth = input.effective_queue_size().unwrap_or(DEFAULT_DEPTH);
+    let mut profile = format!("{reliability}, keep_last({depth})");
+    if input.qos.is_some_and(|qos| qos.is_latched()) {
+        profile.push_str(", transient_local");
+    }
+    profile
+}
+
+/// Converts a dora ID path to a ROS2 name, which doesn't allow dashes.
+fn ros2_name(path: &str) -> String {
+    format!("/{}", path.replace('-', "_"))
+}
+
+impl Ros2Report {
+    fn print(&self, format: OutputFormat) -> eyre::Result<()> {
+        if print_structured(self, format)? {
+            return Ok(());
+        }
+
+        let mut tw = TabWriter::new(vec![]);
+        tw.write_all(b"NODE\tROS2 NODE\tKIND\n")?;
+        for node in &self.nodes {
+            writeln!(tw, "{}\t{}\t{}", node.id, node.ros2_name, node.kind)?;
+        }
+        tw.write_all(b"\nTOPIC\tPUBLISHER\tSUBSCRIBERS\n")?;
+        for topic in &self.topics {
+            let subscribers: Vec<_> = self
+                .nodes
+                .iter()
+                .flat_map(|node| {
+                    node.subscriptions
+                        .iter()
+                        .filter(|s| s.topic == topic.name)
+                        .map(move |s| format!("{}/{} ({})", node.id, s.input, s.qos))
+                })
+                .collect();
+            let subscribers = if subscribers.is_empty() {
+                "-".to_owned()
+            } else {
+                subscribers.join(", ")
+            };
+            writeln!(tw, "{}\t{}\t{subscribers}", topic.name, topic.publisher)?;
+        }
+        tw.flush()?;
+        print!("{}", String::from_utf8(tw.into_inner()?)?);
+
+        let notes: Vec<_> = self
+            .nodes
+            .iter()
+            .flat_map(|node| {
+                let timers = node.timers.iter().map(|(input, interval)| {
+                    format!("input `{input}` is a timer with a period of {interval}")
+                });
+                timers
+                    .chain(node.notes.iter().cloned())
+                    .map(move |note| format!("{}: {note}", node.id))
+            })
+            .collect();
+        if !notes.is_empty() {
+            println!("\nNOTES");
+            for note in notes {
+                println!("  {note}");
+            }
+        }
+        Ok(())
+    }
+}
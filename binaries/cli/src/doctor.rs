@@ -0,0 +1,420 @@
+//! Diagnostics of the local environment, see `dora doctor`.
+
+use std::{
+    io::Write,
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    path::Path,
+    process::Command,
+};
+
+use dora_core::{
+    adjust_shared_library_path,
+    descriptor::{source_is_url, CoreNodeKind, Descriptor, OperatorSource},
+    get_python_path,
+    topics::{
+        DORA_COORDINATOR_PORT_CONTROL_DEFAULT, DORA_COORDINATOR_PORT_DEFAULT,
+        DORA_DAEMON_LOCAL_LISTEN_PORT_DEFAULT, LOCALHOST,
+    },
+};
+use eyre::Context;
+use serde::Serialize;
+
+use crate::{
+    check::{color_stdout, daemon_running, write_status, Status},
+    connect_to_coordinator,
+    formatting::{print_structured, OutputFormat},
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Large messages are exchanged through shared memory, so less free space
+/// than this is likely to cause failures.
+const MIN_SHARED_MEMORY: u64 = 256 * 1024 * 1024;
+
+#[cfg(target_os = "windows")]
+const LOADER_PATH_VAR: &str = "PATH";
+#[cfg(target_os = "macos")]
+const LOADER_PATH_VAR: &str = "DYLD_LIBRARY_PATH";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LOADER_PATH_VAR: &str = "LD_LIBRARY_PATH";
+
+#[derive(Debug, Default, Serialize)]
+pub struct DoctorReport {
+    ok: bool,
+    checks: Vec<Diagnosis>,
+}
+
+#[derive(Debug, Serialize)]
+struct Diagnosis {
+    name: String,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    /// How to fix the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+impl DoctorReport {
+    fn push(
+        &mut self,
+        name: impl Into<String>,
+        status: Status,
+        message: impl Into<String>,
+        hint: Option<String>,
+    ) {
+        let message = message.into();
+        self.checks.push(Diagnosis {
+            name: name.into(),
+            status,
+            message: Some(message).filter(|m| !m.is_empty()),
+            hint: hint.filter(|_| matches!(status, Status::Warning | Status::Error)),
+        });
+    }
+
+    /// Returns `1` if one of the checks failed, `0` otherwise.
+    pub fn exit_code(&self) -> i32 {
+        match self.checks.iter().any(|c| c.status == Status::Error) {
+            true => 1,
+            false => 0,
+        }
+    }
+
+    pub fn print(&self, format: OutputFormat) -> eyre::Result<()> {
+        if print_structured(self, format)? {
+            return Ok(());
+        }
+
+        let mut stdout = color_stdout();
+        for check in &self.checks {
+            write!(stdout, "{}: ", check.name)?;
+            write_status(&mut stdout, check.status)?;
+            match &check.message {
+                Some(message) => writeln!(stdout, " ({message})")?,
+                None => writeln!(stdout)?,
+            }
+            if let Some(hint) = &check.hint {
+                writeln!(stdout, "  hint: {hint}")?;
+            }
+        }
+        writeln!(stdout)?;
+        Ok(())
+    }
+}
+
+/// Checks the tools and system settings that dora depends on.
+///
+/// If a dataflow is given, its shared library operators are loaded to check
+/// that the dynamic loader finds their dependencies.
+pub fn doctor(dataflow: Option<&Path>, coordinator_addr: SocketAddr) -> eyre::Result<DoctorReport> {
+    let mut report = DoctorReport::default();
+
+    check_python(&mut report);
+    check_loader_path(&mut report);
+    if let Some(dataflow) = dataflow {
+        check_operator_libraries(dataflow, &mut report)?;
+    }
+    check_shared_memory(&mut report);
+
+    let mut session = connect_to_coordinator(coordinator_addr).ok();
+    let coordinator_running = session.is_some();
+    match &session {
+        Some(_) => report.push(
+            "Coordinator",
+            Status::Ok,
+            coordinator_addr.to_string(),
+            None,
+        ),
+        None => report.push(
+            "Coordinator",
+            Status::Warning,
+            format!("not reachable at {coordinator_addr}"),
+            Some(
+                "start it with `dora up` or `dora coordinator`, or select another one \
+                through `--coordinator`"
+                    .into(),
+            ),
+        ),
+    }
+    let daemon_connected = match session.as_deref_mut().map(daemon_running) {
+        Some(Ok(true)) => {
+            report.push("Daemon", Status::Ok, "connected to coordinator", None);
+            true
+        }
+        Some(Ok(false)) => {
+            report.push(
+                "Daemon",
+                Status::Warning,
+                "no daemon connected to the coordinator",
+                Some("start one with `dora up` or `dora daemon`".into()),
+            );
+            false
+        }
+        Some(Err(err)) => {
+            report.push("Daemon", Status::Error, format!("{err:#}"), None);
+            false
+        }
+        None => {
+            report.push("Daemon", Status::Skipped, "", None);
+            false
+        }
+    };
+
+    let local_coordinator = coordinator_running && coordinator_addr.ip().is_loopback();
+    check_port(
+        &mut report,
+        "coordinator",
+        SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), DORA_COORDINATOR_PORT_DEFAULT),
+        local_coordinator,
+        "--port",
+    );
+    check_port(
+        &mut report,
+        "coordinator control",
+        SocketAddr::new(
+            Ipv4Addr::UNSPECIFIED.into(),
+            DORA_COORDINATOR_PORT_CONTROL_DEFAULT,
+        ),
+        local_coordinator,
+        "--control-port",
+    );
+    check_port(
+        &mut report,
+        "daemon",
+        SocketAddr::new(LOCALHOST, DORA_DAEMON_LOCAL_LISTEN_PORT_DEFAULT),
+        daemon_connected,
+        "--local-listen-port",
+    );
+
+    report.ok = report.exit_code() == 0;
+    Ok(report)
+}
+
+fn check_python(report: &mut DoctorReport) {
+    let python = match get_python_path() {
+        Ok(python) => python,
+        Err(err) => {
+            report.push(
+                "Python",
+                Status::Warning,
+                format!("{err:#}"),
+                Some(
+                    "Python 3 is only required for Python nodes and operators; install it \
+                    from https://www.python.org/downloads/ or your package manager"
+                        .into(),
+                ),
+            );
+            return;
+        }
+    };
+    let version = Command::new(&python)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .unwrap_or_default();
+    report.push(
+        "Python",
+        Status::Ok,
+        format!("{version} at {}", python.display()),
+        None,
+    );
+
+    let installed = Command::new(&python)
+        .args(["-c", "import dora; print(dora.__version__)"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned());
+    let install_hint = Some(format!("run `pip install dora-rs=={VERSION}`"));
+    match installed {
+        Some(version) if version == VERSION => {
+            report.push("Python package `dora-rs`", Status::Ok, version, None)
+        }
+        Some(version) => report.push(
+            "Python package `dora-rs`",
+            Status::Warning,
+            format!("version {version} does not match the CLI version {VERSION}"),
+            install_hint.map(|hint| format!("{hint} --force")),
+        ),
+        None => report.push(
+            "Python package `dora-rs`",
+            Status::Warning,
+            format!("not installed for {}", python.display()),
+            install_hint,
+        ),
+    }
+}
+
+/// Reports directories of the loader search path that don't exist, which
+/// often means that a library was moved or an environment is outdated.
+fn check_loader_path(report: &mut DoctorReport) {
+    let Some(value) = std::env::var_os(LOADER_PATH_VAR) else {
+        report.push(
+            "Dynamic loader",
+            Status::Ok,
+            format!("`{LOADER_PATH_VAR}` is not set"),
+            None,
+        );
+        return;
+    };
+    let missing: Vec<_> = std::env::split_paths(&value)
+        .filter(|dir| !dir.as_os_str().is_empty() && !dir.is_dir())
+        .map(|dir| dir.display().to_string())
+        .collect();
+    if missing.is_empty() {
+        report.push(
+            "Dynamic loader",
+            Status::Ok,
+            format!("`{LOADER_PATH_VAR}` is valid"),
+            None,
+        );
+    } else {
+        report.push(
+            "Dynamic loader",
+            Status::Warning,
+            format!(
+                "`{LOADER_PATH_VAR}` contains missing directories: {}",
+                missing.join(", ")
+            ),
+            Some(format!("remove them from `{LOADER_PATH_VAR}`")),
+        );
+    }
+}
+
+/// Loads the shared library operators of the dataflow, like the runtime does.
+fn check_operator_libraries(dataflow: &Path, report: &mut DoctorReport) -> eyre::Result<()> {
+    let descriptor = Descriptor::blocking_read(dataflow)?;
+    let working_dir = dataflow
+        .canonicalize()
+        .context("failed to canonicalize dataflow path")?
+        .parent()
+        .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
+        .to_owned();
+    for node in descriptor.resolve_aliases_and_set_defaults()? {
+        let CoreNodeKind::Runtime(runtime) = &node.kind else {
+            continue;
+        };
+        for operator in &runtime.operators {
+            let OperatorSource::SharedLibrary(source) = &operator.config.source else {
+                continue;
+            };
+            if source_is_url(source) {
+                continue;
+            }
+            let name = format!("Operator `{}/{}`", node.id, operator.id);
+            let path = match adjust_shared_library_path(Path::new(source)) {
+                Ok(path) => working_dir.join(path),
+                Err(err) => {
+                    report.push(name, Status::Error, format!("{err:#}"), None);
+                    continue;
+                }
+            };
+            match load_library(&path) {
+                Ok(()) => report.push(name, Status::Ok, path.display().to_string(), None),
+                Err(err) if !path.exists() => report.push(
+                    name,
+                    Status::Error,
+                    err,
+                    Some("build the operator with `dora build`".into()),
+                ),
+                Err(err) => report.push(
+                    name,
+                    Status::Error,
+                    err,
+                    Some(format!(
+                        "add the directories of the missing libraries to `{LOADER_PATH_VAR}`"
+                    )),
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_library(path: &Path) -> Result<(), String> {
+    // SAFETY: the runtime loads the same library when the dataflow is started
+    unsafe { libloading::Library::new(path) }
+        .map(drop)
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn check_shared_memory(report: &mut DoctorReport) {
+    use crate::formatting::format_bytes;
+    use std::ffi::CString;
+
+    let path = CString::new("/dev/shm").unwrap();
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        report.push(
+            "Shared memory",
+            Status::Error,
+            format!(
+                "failed to query `/dev/shm`: {}",
+                std::io::Error::last_os_error()
+            ),
+            Some("make sure that a tmpfs is mounted at `/dev/shm`".into()),
+        );
+        return;
+    }
+    // the field types are narrower on 32-bit targets
+    #[allow(clippy::unnecessary_cast)]
+    let (available, total) = (
+        stat.f_bavail as u64 * stat.f_frsize as u64,
+        stat.f_blocks as u64 * stat.f_frsize as u64,
+    );
+    let message = format!(
+        "{} of {} available in `/dev/shm`",
+        format_bytes(available as f64),
+        format_bytes(total as f64)
+    );
+    if available < MIN_SHARED_MEMORY {
+        report.push(
+            "Shared memory",
+            Status::Warning,
+            message,
+            Some(
+                "large messages are sent through shared memory; increase the size of \
+                `/dev/shm`, e.g. with `docker run --shm-size=1g`"
+                    .into(),
+            ),
+        );
+    } else {
+        report.push("Shared memory", Status::Ok, message, None);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn check_shared_memory(report: &mut DoctorReport) {
+    report.push(
+        "Shared memory",
+        Status::Skipped,
+        "only checked on Linux",
+        None,
+    );
+}
+
+/// Checks that the default port is free, or used by the running dora
+/// component itself.
+fn check_port(
+    report: &mut DoctorReport,
+    component: &str,
+    addr: SocketAddr,
+    used_by_dora: bool,
+    option: &str,
+) {
+    let name = format!("Port {} ({component})", addr.port());
+    match TcpListener::bind(addr) {
+        Ok(_) => report.push(name, Status::Ok, "free", None),
+        Err(_) if used_by_dora => report.push(name, Status::Ok, "used by dora", None),
+        Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => report.push(
+            name,
+            Status::Error,
+            "used by another process",
+            Some(format!(
+                "stop the other process or choose another port through `{option}`"
+            )),
+        ),
+        Err(err) => report.push(name, Status::Error, err.to_string(), None),
+    }
+}
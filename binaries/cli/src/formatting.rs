@@ -1,4 +1,5 @@
 use dora_core::topics::{DataflowResult, NodeErrorCause};
+use serde::Serialize;
 
 pub struct FormatDataflowError<'a>(pub &'a DataflowResult);
 
@@ -48,3 +49,42 @@ impl std::fmt::Display for FormatDataflowError<'_> {
         Ok(())
     }
 }
+
+/// Formats a byte count with a decimal unit prefix, e.g. `1.5 MB`.
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "kB", "MB", "GB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Output format of commands that list or inspect resources.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable table
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+/// Prints the value as JSON or YAML.
+///
+/// Returns `false` for `OutputFormat::Table`, in which case the caller prints
+/// the value itself.
+pub fn print_structured<T: Serialize>(value: &T, format: OutputFormat) -> eyre::Result<bool> {
+    match format {
+        OutputFormat::Table => return Ok(false),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(value)?),
+    }
+    Ok(true)
+}
@@ -0,0 +1,242 @@
+//! Topology of a running dataflow, annotated with message rates.
+//!
+//! The rates are computed from two snapshots of the node statistics that the
+//! daemons collect, taken `interval` apart. Each edge shows the rate at which
+//! messages are delivered to the receiving input and the queue depth of the
+//! receiving node, so edges on which data stopped flowing stand out.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    config::{DataId, Input, InputMapping, NodeId},
+    daemon_messages::NodeStats,
+    descriptor::{runtime_node_inputs, CoreNodeKind, ResolvedNode},
+    topics::{ControlRequest, ControlRequestReply, DataflowId},
+};
+use eyre::{bail, Context};
+use tabwriter::TabWriter;
+
+use crate::formatting::format_bytes;
+
+pub(crate) fn show(
+    session: &mut TcpRequestReplyConnection,
+    dataflow: DataflowId,
+    mermaid: bool,
+    interval: Duration,
+) -> eyre::Result<()> {
+    let mut previous = Snapshot::query(session, &dataflow)?;
+    loop {
+        std::thread::sleep(interval);
+        let current = Snapshot::query(session, &dataflow)?;
+        let edges = current.edges(&previous);
+
+        if mermaid {
+            println!("{}", render_mermaid(&edges));
+            println!(
+                "Paste the above output on https://mermaid.live/ or in a \
+                ```mermaid code block on GitHub to display it."
+            );
+            return Ok(());
+        }
+
+        // clear the terminal before redrawing
+        print!("\x1B[2J\x1B[H");
+        println!(
+            "Dataflow {dataflow}, rates over the last {:.1?} (press Ctrl-C to exit)\n",
+            current.time - previous.time
+        );
+        println!("{}", render_table(&edges)?);
+
+        previous = current;
+    }
+}
+
+struct Snapshot {
+    nodes: Vec<ResolvedNode>,
+    stats: BTreeMap<NodeId, NodeStats>,
+    time: Instant,
+}
+
+impl Snapshot {
+    fn query(session: &mut TcpRequestReplyConnection, dataflow: &DataflowId) -> eyre::Result<Self> {
+        let reply_raw = session
+            .request(
+                &serde_json::to_vec(&ControlRequest::Topology {
+                    dataflow_uuid: dataflow.uuid,
+                })
+                .unwrap(),
+            )
+            .wrap_err("failed to send topology request")?;
+        let time = Instant::now();
+        let reply: ControlRequestReply =
+            serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+        match reply {
+            ControlRequestReply::Topology { nodes, stats } => Ok(Self { nodes, stats, time }),
+            ControlRequestReply::Error(err) => bail!("{err}"),
+            other => bail!("unexpected topology reply: {other:?}"),
+        }
+    }
+
+    fn edges(&self, previous: &Snapshot) -> Vec<Edge> {
+        let elapsed = (self.time - previous.time).as_secs_f64();
+        let mut edges = Vec::new();
+        for node in &self.nodes {
+            let inputs = match &node.kind {
+                CoreNodeKind::Custom(custom) => custom.run_config.inputs.clone(),
+                CoreNodeKind::Runtime(runtime) => runtime_node_inputs(runtime),
+            };
+            let stats = self.stats.get(&node.id);
+            for (input_id, input) in &inputs {
+                let rates = stats.map(|stats| {
+                    let current = stats.inputs.get(input_id).copied().unwrap_or_default();
+                    let before = previous
+                        .stats
+                        .get(&node.id)
+                        .and_then(|stats| stats.inputs.get(input_id))
+                        .copied()
+                        .unwrap_or_default();
+                    Rates {
+                        messages: current.messages.saturating_sub(before.messages) as f64 / elapsed,
+                        bytes: current.bytes.saturating_sub(before.bytes) as f64 / elapsed,
+                        queue_depth: stats.queue_depth,
+                        dropped_inputs: stats.dropped_inputs,
+                    }
+                });
+                for (source, output) in sources(input) {
+                    edges.push(Edge {
+                        source,
+                        output,
+                        target: node.id.clone(),
+                        input: input_id.clone(),
+                        rates,
+                    });
+                }
+            }
+        }
+        edges.sort_by(|a, b| (&a.source, &a.output).cmp(&(&b.source, &b.output)));
+        edges
+    }
+}
+
+/// Returns the source node (or timer) and output of each mapping of the input.
+fn sources(input: &Input) -> Vec<(String, Option<String>)> {
+    input
+        .sources()
+        .map(|mapping| match mapping {
+            InputMapping::User(mapping) => {
+                (mapping.source.to_string(), Some(mapping.output.to_string()))
+            }
+            InputMapping::Timer { .. } => (mapping.to_string(), None),
+        })
+        .collect()
+}
+
+struct Edge {
+    source: String,
+    output: Option<String>,
+    target: NodeId,
+    input: DataId,
+    /// `None` if the daemon reported no statistics for the target node.
+    rates: Option<Rates>,
+}
+
+#[derive(Clone, Copy)]
+struct Rates {
+    messages: f64,
+    bytes: f64,
+    queue_depth: usize,
+    dropped_inputs: u64,
+}
+
+impl Edge {
+    fn is_stalled(&self) -> bool {
+        self.rates.is_some_and(|rates| rates.messages == 0.0)
+    }
+}
+
+fn render_table(edges: &[Edge]) -> eyre::Result<String> {
+    let mut tw = TabWriter::new(vec![]);
+    tw.write_all(b"SOURCE\tOUTPUT\t\tTARGET\tMSG/S\tBANDWIDTH\tQUEUE\n")?;
+    let mut last_source = None;
+    for edge in edges {
+        let source = if last_source == Some(&edge.source) {
+            ""
+        } else {
+            edge.source.as_str()
+        };
+        last_source = Some(&edge.source);
+        let output = edge.output.as_deref().unwrap_or("");
+        let target = format!("{}/{}", edge.target, edge.input);
+        let (messages, bytes, queue) = match edge.rates {
+            Some(rates) => {
+                let mut queue = rates.queue_depth.to_string();
+                if rates.dropped_inputs > 0 {
+                    write!(queue, " ({} dropped)", rates.dropped_inputs).unwrap();
+                }
+                (
+                    format!("{:.1}", rates.messages),
+                    format_bandwidth(rates.bytes),
+                    queue,
+                )
+            }
+            None => ("-".into(), "-".into(), "-".into()),
+        };
+        let status = if edge.is_stalled() {
+            "stalled".red().to_string()
+        } else {
+            String::new()
+        };
+        writeln!(
+            tw,
+            "{source}\t{output}\t──▶\t{target}\t{messages}\t{bytes}\t{queue}\t{status}"
+        )?;
+    }
+    tw.flush()?;
+    Ok(String::from_utf8(tw.into_inner()?)?)
+}
+
+fn render_mermaid(edges: &[Edge]) -> String {
+    let mut flowchart = "flowchart TB\n".to_owned();
+    let mut stalled = Vec::new();
+    for (index, edge) in edges.iter().enumerate() {
+        let mut label = match &edge.output {
+            Some(output) if output == edge.input.as_str() => output.clone(),
+            Some(output) => format!("{output} as {}", edge.input),
+            None => edge.input.to_string(),
+        };
+        if let Some(rates) = edge.rates {
+            write!(
+                label,
+                "<br/>{:.1} msg/s, {}<br/>queue: {}",
+                rates.messages,
+                format_bandwidth(rates.bytes),
+                rates.queue_depth
+            )
+            .unwrap();
+        }
+        if edge.is_stalled() {
+            stalled.push(index.to_string());
+        }
+        writeln!(
+            flowchart,
+            "  {} -- \"{label}\" --> {}",
+            edge.source, edge.target
+        )
+        .unwrap();
+    }
+    if !stalled.is_empty() {
+        writeln!(flowchart, "  linkStyle {} stroke:red", stalled.join(",")).unwrap();
+    }
+    flowchart
+}
+
+fn format_bandwidth(bytes_per_second: f64) -> String {
+    format!("{}/s", format_bytes(bytes_per_second))
+}
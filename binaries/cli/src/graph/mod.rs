@@ -3,6 +3,8 @@ use std::{fs::File, io::Write, path::Path};
 use dora_core::descriptor::Descriptor;
 use eyre::Context;
 
+pub(crate) mod live;
+
 const MERMAID_TEMPLATE: &str = include_str!("mermaid-template.html");
 
 pub(crate) fn create(dataflow: std::path::PathBuf, mermaid: bool, open: bool) -> eyre::Result<()> {
@@ -0,0 +1,99 @@
+use std::{io::Write, time::SystemTime};
+
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    descriptor::CoreNodeKind,
+    topics::{ControlRequest, ControlRequestReply, DataflowInspection, DataflowStatus, NodeState},
+};
+use eyre::{bail, Context};
+use tabwriter::TabWriter;
+use uuid::Uuid;
+
+use crate::formatting::{print_structured, OutputFormat};
+
+pub fn inspect(
+    session: &mut TcpRequestReplyConnection,
+    uuid: Uuid,
+    output: OutputFormat,
+) -> eyre::Result<()> {
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::Inspect {
+                dataflow_uuid: uuid,
+            })
+            .unwrap(),
+        )
+        .wrap_err("failed to send inspect request")?;
+    let reply: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    let inspection = match reply {
+        ControlRequestReply::Inspect(inspection) => inspection,
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected inspect reply: {other:?}"),
+    };
+
+    if print_structured(&inspection, output)? {
+        return Ok(());
+    }
+    print_report(&inspection)
+}
+
+fn print_report(inspection: &DataflowInspection) -> eyre::Result<()> {
+    let status = match inspection.status {
+        DataflowStatus::Running => "Running",
+        DataflowStatus::Finished => "Succeeded",
+        DataflowStatus::Failed => "Failed",
+    };
+    println!("Dataflow: {}", inspection.id);
+    println!("Status:   {status}\n");
+
+    let mut tw = TabWriter::new(vec![]);
+    tw.write_all(b"NODE\tMACHINE\tKIND\tSTATE\tRESTARTS\n")?;
+    for node in &inspection.nodes {
+        let machine = match node.node.deploy.machine.as_str() {
+            "" => "<default>",
+            machine => machine,
+        };
+        let kind = match &node.node.kind {
+            CoreNodeKind::Custom(_) => "custom".to_owned(),
+            CoreNodeKind::Runtime(runtime) => {
+                format!("runtime ({} operators)", runtime.operators.len())
+            }
+        };
+        let state = match &node.state {
+            NodeState::Unknown => "unknown".to_owned(),
+            NodeState::Running => "running".to_owned(),
+            NodeState::Finished => "finished".to_owned(),
+            // the full error is listed in the recent errors
+            NodeState::Failed(err) => {
+                format!("failed: {}", err.lines().next().unwrap_or_default())
+            }
+        };
+        writeln!(
+            tw,
+            "{}\t{machine}\t{kind}\t{state}\t{}",
+            node.node.id, node.restarts
+        )?;
+    }
+    tw.flush()?;
+    print!("{}", String::from_utf8(tw.into_inner()?)?);
+
+    if !inspection.recent_errors.is_empty() {
+        println!("\nRecent errors:");
+        for error in &inspection.recent_errors {
+            let ago = SystemTime::now()
+                .duration_since(error.time)
+                .unwrap_or_default()
+                .as_secs();
+            let node = error
+                .node_id
+                .as_ref()
+                .map(|id| format!("{id}: "))
+                .unwrap_or_default();
+            println!("  [{ago}s ago] {node}{}", error.message.trim_end());
+        }
+    }
+
+    println!("\nUse `--output yaml` to show the resolved descriptor.");
+    Ok(())
+}
@@ -1,45 +1,145 @@
+use colored::{Color, Colorize};
 use communication_layer_request_reply::TcpRequestReplyConnection;
-use dora_core::topics::{ControlRequest, ControlRequestReply};
+use dora_core::{
+    config::NodeId,
+    daemon_messages::LogChunk,
+    topics::{ControlRequest, ControlRequestReply},
+};
 use eyre::{bail, Context, Result};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    time::{Duration, SystemTime},
+};
 use uuid::Uuid;
 
 use bat::{Input, PrettyPrinter};
 
+/// How often new log messages are requested with `--follow`.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+const NODE_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
 pub fn logs(
     session: &mut TcpRequestReplyConnection,
-    uuid: Option<Uuid>,
-    name: Option<String>,
-    node: String,
+    uuid: Uuid,
+    node: Option<String>,
+    since: Option<Duration>,
+    follow: bool,
 ) -> Result<()> {
-    let logs = {
-        let reply_raw = session
-            .request(
-                &serde_json::to_vec(&ControlRequest::Logs {
-                    uuid,
-                    name,
-                    node: node.clone(),
-                })
-                .wrap_err("")?,
-            )
-            .wrap_err("failed to send Logs request message")?;
-
-        let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
-        match reply {
-            ControlRequestReply::Logs(logs) => logs,
-            other => bail!("unexpected reply to daemon logs: {other:?}"),
+    let since = since.and_then(|since| SystemTime::now().checked_sub(since));
+    let mut offsets = BTreeMap::new();
+    let logs = request_logs(session, uuid, node.clone(), since, &offsets)?;
+
+    if let (Some(node), false) = (&node, follow) {
+        let logs = logs.into_values().next().unwrap_or_default();
+        PrettyPrinter::new()
+            .header(false)
+            .grid(false)
+            .line_numbers(false)
+            .paging_mode(bat::PagingMode::QuitIfOneScreen)
+            .inputs(vec![Input::from_bytes(&logs.data)
+                .name("Logs")
+                .title(format!("Logs from {node}.").as_str())])
+            .print()
+            .wrap_err("Something went wrong with viewing log file")?;
+        return Ok(());
+    }
+
+    // prefix lines with the node ID if the logs of multiple nodes are shown
+    let mut printer = LogPrinter::new(node.is_none());
+    printer.print(&logs)?;
+    offsets.extend(logs.iter().map(|(id, chunk)| (id.clone(), chunk.end())));
+
+    if !follow {
+        return Ok(());
+    }
+    loop {
+        std::thread::sleep(FOLLOW_INTERVAL);
+        // check before requesting the logs to not miss the last messages
+        let running = crate::query_running_dataflows(session)?
+            .get_active()
+            .iter()
+            .any(|id| id.uuid == uuid);
+        let logs = request_logs(session, uuid, node.clone(), None, &offsets)?;
+        printer.print(&logs)?;
+        offsets.extend(logs.iter().map(|(id, chunk)| (id.clone(), chunk.end())));
+        if !running {
+            break;
         }
-    };
-
-    PrettyPrinter::new()
-        .header(false)
-        .grid(false)
-        .line_numbers(false)
-        .paging_mode(bat::PagingMode::QuitIfOneScreen)
-        .inputs(vec![Input::from_bytes(&logs)
-            .name("Logs")
-            .title(format!("Logs from {node}.").as_str())])
-        .print()
-        .wrap_err("Something went wrong with viewing log file")?;
+    }
 
     Ok(())
 }
+
+fn request_logs(
+    session: &mut TcpRequestReplyConnection,
+    uuid: Uuid,
+    node: Option<String>,
+    since: Option<SystemTime>,
+    offsets: &BTreeMap<NodeId, u64>,
+) -> Result<BTreeMap<NodeId, LogChunk>> {
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::Logs {
+                uuid: Some(uuid),
+                name: None,
+                node,
+                since,
+                offsets: offsets.clone(),
+            })
+            .wrap_err("")?,
+        )
+        .wrap_err("failed to send Logs request message")?;
+
+    let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::Logs(logs) => Ok(logs),
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected reply to daemon logs: {other:?}"),
+    }
+}
+
+struct LogPrinter {
+    prefix: bool,
+    colors: BTreeMap<NodeId, Color>,
+    width: usize,
+}
+
+impl LogPrinter {
+    fn new(prefix: bool) -> Self {
+        Self {
+            prefix,
+            colors: BTreeMap::new(),
+            width: 0,
+        }
+    }
+
+    fn print(&mut self, logs: &BTreeMap<NodeId, LogChunk>) -> Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        let width = logs.keys().map(|id| id.to_string().len()).max();
+        self.width = self.width.max(width.unwrap_or_default());
+        for (node_id, chunk) in logs {
+            let text = String::from_utf8_lossy(&chunk.data);
+            if !self.prefix {
+                stdout.write_all(text.as_bytes())?;
+                continue;
+            }
+            let next_color = NODE_COLORS[self.colors.len() % NODE_COLORS.len()];
+            let color = *self.colors.entry(node_id.clone()).or_insert(next_color);
+            for line in text.lines() {
+                let prefix = format!("{:<width$} |", node_id.to_string(), width = self.width);
+                writeln!(stdout, "{} {line}", prefix.color(color))?;
+            }
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+}
@@ -1,40 +1,60 @@
 use attach::attach_dataflow;
-use clap::Parser;
+use build::BuildTargets;
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches};
 use colored::Colorize;
 use communication_layer_request_reply::{RequestReplyLayer, TcpLayer, TcpRequestReplyConnection};
 use dora_coordinator::Event;
+#[cfg(unix)]
+use dora_core::topics::{LocalSocketAddr, DORA_DAEMON_LOCAL_SOCKET_ENV};
 use dora_core::{
+    config::NodeId,
     descriptor::Descriptor,
     topics::{
-        ControlRequest, ControlRequestReply, DataflowList, DORA_COORDINATOR_PORT_CONTROL_DEFAULT,
-        DORA_COORDINATOR_PORT_DEFAULT, DORA_DAEMON_LOCAL_LISTEN_PORT_DEFAULT,
+        ControlRequest, ControlRequestReply, DataflowId, DataflowList,
+        DORA_COORDINATOR_PORT_CONTROL_DEFAULT, DORA_COORDINATOR_PORT_DEFAULT,
+        DORA_DAEMON_LOCAL_LISTEN_PORT_DEFAULT,
     },
 };
-use dora_daemon::Daemon;
+use dora_daemon::{Daemon, LocalListenConfig};
 #[cfg(feature = "tracing")]
 use dora_tracing::set_up_tracing;
 use dora_tracing::set_up_tracing_opts;
 use duration_str::parse;
 use eyre::{bail, Context};
-use formatting::FormatDataflowError;
-use std::{io::Write, net::SocketAddr};
+use formatting::{FormatDataflowError, OutputFormat};
 use std::{
+    collections::BTreeSet,
     net::{IpAddr, Ipv4Addr},
+    num::NonZeroUsize,
     path::PathBuf,
     time::Duration,
 };
+use std::{io::Write, net::SocketAddr};
 use tabwriter::TabWriter;
 use tokio::runtime::Builder;
 use uuid::Uuid;
 
 mod attach;
+mod bench;
 mod build;
+mod bundle;
 mod check;
+mod completion;
+mod config;
+mod convert;
+mod doctor;
 mod formatting;
 mod graph;
+mod inspect;
 mod logs;
+mod metrics;
+mod operator;
+mod record;
 mod template;
+mod top;
+mod topic;
 mod up;
+mod watch;
 
 const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 const LISTEN_WILDCARD: IpAddr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
@@ -44,16 +64,60 @@ const LISTEN_WILDCARD: IpAddr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
 struct Args {
     #[clap(subcommand)]
     command: Command,
+    /// Address of the coordinator control server as `host[:port]`, e.g. of a
+    /// remote robot. Explicit `--coordinator-addr` or `--coordinator-port`
+    /// arguments take precedence.
+    #[clap(
+        long,
+        global = true,
+        value_name = "HOST:PORT",
+        env = "DORA_COORDINATOR"
+    )]
+    coordinator: Option<String>,
+    /// Name of a coordinator profile in `~/.dora/config.toml` (default: its
+    /// `default_profile`)
+    #[clap(long, global = true, value_name = "NAME", env = "DORA_PROFILE")]
+    profile: Option<String>,
 }
 
 /// dora-rs cli client
 #[derive(Debug, clap::Subcommand)]
 enum Command {
     /// Check if the coordinator and the daemon is running.
+    ///
+    /// With --dataflow, also validates the dataflow, resolves the sources of all
+    /// nodes and operators, and checks that a daemon is registered for each of
+    /// its machines. Exits with code 1 if the dataflow is invalid and with code 2
+    /// if the coordinator or a required daemon is not available.
     Check {
         /// Path to the dataflow descriptor file (enables additional checks)
         #[clap(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
         dataflow: Option<PathBuf>,
+        /// Only check the dataflow, without connecting to the coordinator
+        #[clap(long, action)]
+        offline: bool,
+        /// Output format
+        #[clap(long, value_name = "FORMAT", default_value = "table")]
+        output: OutputFormat,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Diagnose common problems of the local environment.
+    ///
+    /// Checks the Python installation, the dynamic loader, the shared memory
+    /// limits, the coordinator and daemon, and the default ports, and prints
+    /// hints on how to fix the problems that were found.
+    Doctor {
+        /// Also try to load the shared library operators of this dataflow
+        #[clap(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        dataflow: Option<PathBuf>,
+        /// Output format
+        #[clap(long, value_name = "FORMAT", default_value = "table")]
+        output: OutputFormat,
         /// Address of the dora coordinator
         #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
         coordinator_addr: IpAddr,
@@ -62,22 +126,108 @@ enum Command {
         coordinator_port: u16,
     },
     /// Generate a visualization of the given graph using mermaid.js. Use --open to open browser.
+    ///
+    /// With --live, shows the topology of a running dataflow instead, annotated with the
+    /// message rate and queue depth of each input.
     Graph {
-        /// Path to the dataflow descriptor file
-        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
-        dataflow: PathBuf,
+        /// Path to the dataflow descriptor file, or the UUID or name of a running dataflow
+        /// with --live
+        #[clap(value_name = "PATH|UUID_OR_NAME", value_hint = clap::ValueHint::FilePath)]
+        dataflow: Option<String>,
         /// Visualize the dataflow as a Mermaid diagram (instead of HTML)
         #[clap(long, action)]
         mermaid: bool,
         /// Open the HTML visualization in the browser
         #[clap(long, action)]
         open: bool,
+        /// Show a running dataflow with live message rates, refreshed until Ctrl-C
+        #[clap(long, action)]
+        live: bool,
+        /// Interval over which the message rates are measured
+        #[clap(long, value_name = "DURATION", default_value = "1s", requires = "live")]
+        #[arg(value_parser = parse)]
+        interval: Duration,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
     },
     /// Run build commands provided in the given dataflow.
     Build {
         /// Path to the dataflow descriptor file
         #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
         dataflow: PathBuf,
+        /// Maximum number of build commands to run in parallel (default: number of CPUs)
+        #[clap(long, short = 'j', value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+        /// Run all build commands, even if they are up to date
+        #[clap(long, action)]
+        force: bool,
+        /// Cross-compile for the given Rust target triple, for all machines or only for
+        /// the given one
+        ///
+        /// Overrides the `target` of the `_unstable_deploy` config of the machine. If a
+        /// target is set, the build artifacts of each machine are also copied to
+        /// `out/build/<MACHINE>`.
+        #[clap(long = "target", value_name = "[MACHINE=]TRIPLE")]
+        targets: Vec<String>,
+    },
+    /// Package a dataflow with its nodes and operators into a `.tar.gz` bundle.
+    ///
+    /// The bundle contains the descriptor and all local files that nodes and operators
+    /// are loaded from, together with a manifest of their checksums. Use `dora deploy`
+    /// to extract it on the daemons.
+    Bundle {
+        /// Path to the dataflow descriptor file
+        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        dataflow: PathBuf,
+        /// Name of the bundle (default: name of the dataflow directory)
+        #[clap(long)]
+        name: Option<String>,
+        /// Path of the created archive (default: `<NAME>.tar.gz`)
+        #[clap(long, short, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        output: Option<PathBuf>,
+        /// Additional files or directories to add, e.g. modules imported by Python nodes
+        #[clap(long, value_name = "PATH", value_hint = clap::ValueHint::AnyPath)]
+        include: Vec<PathBuf>,
+        /// Add wheels of the `requirements` of Python operators for offline installation
+        #[clap(long, action)]
+        wheels: bool,
+        /// Run the build commands of the dataflow first
+        #[clap(long, action)]
+        build: bool,
+    },
+    /// Convert between dataflow descriptors and ROS2 launch files or recordings.
+    Convert {
+        #[clap(subcommand)]
+        command: convert::ConvertCommand,
+    },
+    /// Extract a bundle created by `dora bundle` on the connected daemons.
+    ///
+    /// A previous deployment of the same bundle name is replaced.
+    Deploy {
+        /// Path to the bundle
+        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        bundle: PathBuf,
+        /// Only deploy to the daemons of the given machines (default: all)
+        #[clap(long = "machine-id", value_name = "MACHINE_ID")]
+        machine_ids: Vec<String>,
+        /// Absolute directory on the machines to extract to (default: `~/.dora/bundles/<NAME>`)
+        ///
+        /// Must be empty or contain a previous deployment of a bundle.
+        #[clap(long, value_name = "DIR")]
+        target_dir: Option<PathBuf>,
+        /// Start the dataflow of the bundle after deploying it
+        #[clap(long, action)]
+        start: bool,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
     },
     /// Generate a new project or node. Choose the language between Rust, Python, C or C++.
     New {
@@ -86,11 +236,34 @@ enum Command {
         #[clap(hide = true, long)]
         internal_create_with_path_dependencies: bool,
     },
+    /// Create a new dataflow project.
+    ///
+    /// Same as `dora new NAME`, unless `--interactive` is given. In that case, the
+    /// nodes, their languages, and their connections are queried step by step.
+    Init {
+        /// Name of the dataflow
+        #[clap(required_unless_present = "interactive")]
+        name: Option<String>,
+        /// Ask for the nodes and connections of the dataflow
+        #[clap(long, short, action)]
+        interactive: bool,
+        /// The programming language that should be used, if not `--interactive`
+        #[clap(long, value_enum, default_value_t = Lang::Rust, conflicts_with = "interactive")]
+        lang: Lang,
+        #[clap(hide = true, long)]
+        internal_create_with_path_dependencies: bool,
+    },
     /// Spawn coordinator and daemon in local mode (with default config)
     Up {
         /// Use a custom configuration
         #[clap(long, hide = true, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
         config: Option<PathBuf>,
+        /// Start the given dataflow, then rebuild and restart its nodes whenever files change
+        #[clap(long, value_name = "DATAFLOW", value_hint = clap::ValueHint::FilePath)]
+        watch: Option<PathBuf>,
+        /// Assign a name to the watched dataflow
+        #[clap(long, requires = "watch")]
+        name: Option<String>,
     },
     /// Destroy running coordinator and daemon. If some dataflows are still running, they will be stopped first.
     Destroy {
@@ -127,6 +300,10 @@ enum Command {
         /// Enable hot reloading (Python only)
         #[clap(long, action)]
         hot_reload: bool,
+        /// When attached, kill the dataflow if it doesn't stop within the given duration after ctrl-c
+        #[clap(long, visible_alias = "grace-period", value_name = "DURATION")]
+        #[arg(value_parser = parse)]
+        grace_duration: Option<Duration>,
     },
     /// Stop the given dataflow UUID. If no id is provided, you will be able to choose between the running dataflows.
     Stop {
@@ -136,7 +313,28 @@ enum Command {
         #[clap(long)]
         name: Option<String>,
         /// Kill the dataflow if it doesn't stop after the given duration
-        #[clap(long, value_name = "DURATION")]
+        #[clap(long, visible_alias = "grace-period", value_name = "DURATION")]
+        #[arg(value_parser = parse)]
+        grace_duration: Option<Duration>,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Restart a single node of a running dataflow.
+    ///
+    /// The node is asked to stop and started again once it exited. The inputs that it
+    /// sends to stay open in the meantime, so the rest of the dataflow keeps running.
+    RestartNode {
+        /// ID of the node to restart
+        node: String,
+        /// Name or UUID of the dataflow (default: choose interactively if multiple are running)
+        #[clap(long)]
+        dataflow: Option<String>,
+        /// Kill the node if it doesn't stop after the given duration
+        #[clap(long, visible_alias = "grace-period", value_name = "DURATION")]
         #[arg(value_parser = parse)]
         grace_duration: Option<Duration>,
         /// Address of the dora coordinator
@@ -148,6 +346,9 @@ enum Command {
     },
     /// List running dataflows.
     List {
+        /// Output format
+        #[clap(long, value_name = "FORMAT", default_value = "table")]
+        output: OutputFormat,
         /// Address of the dora coordinator
         #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
         coordinator_addr: IpAddr,
@@ -155,17 +356,213 @@ enum Command {
         #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
         coordinator_port: u16,
     },
+    /// Upgrade connected daemons to a new dora executable.
+    ///
+    /// The executable is sent to each daemon, which checks that it runs on its
+    /// machine and that its version is compatible with the coordinator. The daemon
+    /// then stops accepting new dataflows, waits until its running dataflows are
+    /// finished, replaces its executable, and restarts.
+    UpgradeDaemons {
+        /// The new `dora` executable (default: the executable of this command)
+        ///
+        /// Must be built for the platform of the upgraded machines.
+        #[clap(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        binary: Option<PathBuf>,
+        /// Only upgrade the daemons of the given machines (default: all)
+        #[clap(long = "machine-id", value_name = "MACHINE_ID")]
+        machine_ids: Vec<String>,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Load or unload operators of a running runtime node.
+    Operator {
+        #[clap(subcommand)]
+        command: operator::OperatorCommand,
+        /// Address of the dora coordinator
+        #[clap(long, global = true, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, global = true, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Print or inject the messages of a running dataflow, for debugging.
+    Topic {
+        #[clap(subcommand)]
+        command: topic::TopicCommand,
+        /// Address of the dora coordinator
+        #[clap(long, global = true, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, global = true, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Show the state, placement, and recent errors of a running or finished dataflow.
+    Inspect {
+        /// Identifier of the dataflow
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: Option<String>,
+        /// Output format
+        #[clap(long, value_name = "FORMAT", default_value = "table")]
+        output: OutputFormat,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Show the CPU and memory usage and the message rates of running nodes.
+    ///
+    /// Shows the nodes of all running dataflows if no dataflow is given.
+    Top {
+        /// Identifier of the dataflow
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: Option<String>,
+        /// Refresh interval
+        #[clap(long, value_name = "DURATION", default_value = "1s")]
+        #[arg(value_parser = parse)]
+        interval: Duration,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Print a snapshot of the daemon and node statistics, or push it once to a
+    /// monitoring system.
+    Metrics {
+        /// Only include the nodes of this dataflow
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: Option<String>,
+        /// Output format
+        #[clap(long, value_name = "FORMAT", default_value = "table")]
+        output: OutputFormat,
+        /// Push the metrics to this Prometheus Pushgateway, e.g. `http://localhost:9091`
+        #[clap(long, value_name = "URL")]
+        pushgateway: Option<String>,
+        /// Job name under which the metrics are pushed to the Pushgateway
+        #[clap(long, default_value = "dora", requires = "pushgateway")]
+        job: String,
+        /// Export the metrics to this OTLP gRPC endpoint, e.g. `http://localhost:4317`
+        #[clap(long, value_name = "URL")]
+        otlp_endpoint: Option<String>,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Record the outputs of a running dataflow.
+    Record {
+        #[clap(subcommand)]
+        command: record::RecordCommand,
+        /// Address of the dora coordinator
+        #[clap(long, global = true, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, global = true, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Start the given dataflow with some of its nodes replaced by a recording.
+    ///
+    /// The recording path is resolved by the daemons, so it must be valid on the
+    /// machines that run the replaced nodes. Relative paths that don't exist
+    /// locally are resolved against the dataflow's working directory.
+    Replay {
+        /// Path to the recording, e.g. `out/<UUID>/record`
+        #[clap(value_name = "RECORDING", value_hint = clap::ValueHint::DirPath)]
+        recording: PathBuf,
+        /// Path to the dataflow descriptor file
+        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        dataflow: PathBuf,
+        /// Replay the recorded outputs of this node instead of spawning it
+        #[clap(long = "node", value_name = "NODE", required = true)]
+        nodes: Vec<String>,
+        /// Playback speed relative to the recorded timing, e.g. `2.0`
+        #[clap(long)]
+        speed: Option<f64>,
+        /// Assign a name to the dataflow
+        #[clap(long)]
+        name: Option<String>,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+        /// Attach to the dataflow and wait for its completion
+        #[clap(long, action)]
+        attach: bool,
+        /// Run the dataflow in background
+        #[clap(long, action)]
+        detach: bool,
+    },
+    /// Generate a shell completion script.
+    ///
+    /// The bash, zsh, and fish scripts also complete the names and UUIDs of
+    /// running dataflows, by querying the coordinator on the default address.
+    ///
+    /// Example: `dora completion bash > ~/.local/share/bash-completion/completions/dora`
+    Completion {
+        #[clap(value_name = "SHELL")]
+        shell: clap_complete::Shell,
+    },
+    /// Print the names and UUIDs of running dataflows, used by the shell completions.
+    #[clap(hide = true)]
+    CompleteDataflows,
+    /// Measure the message latency and throughput on this machine.
+    ///
+    /// Runs a dataflow in which a source node sends messages of the given sizes
+    /// to a sink node, once for each transport. The latency is measured while
+    /// sending at `--rate`, the throughput while sending as fast as possible.
+    Bench {
+        /// Message size in bytes, e.g. `512`, `64K`, or `4M` (default: 8, 4K, 64K, 1M)
+        #[clap(long = "size", value_name = "BYTES", value_parser = bench::parse_size)]
+        sizes: Vec<usize>,
+        /// Number of messages per size and measurement
+        #[clap(long, default_value_t = 100)]
+        messages: usize,
+        /// Messages per second while measuring the latency
+        #[clap(long, default_value_t = 100.0)]
+        rate: f64,
+        /// Transport between the nodes (default: all)
+        #[clap(long = "transport", value_name = "TRANSPORT")]
+        transports: Vec<bench::Transport>,
+        /// Output format
+        #[clap(long, value_name = "FORMAT", default_value = "table")]
+        output: OutputFormat,
+    },
+    /// Runs a node of the `dora bench` dataflow.
+    #[clap(hide = true)]
+    BenchNode {
+        #[clap(subcommand)]
+        node: bench::BenchNode,
+    },
     // Planned for future releases:
     // Dashboard,
     /// Show logs of a given dataflow and node.
-    #[command(allow_missing_positional = true)]
+    ///
+    /// Shows the logs of all nodes of the dataflow if no node is given.
     Logs {
         /// Identifier of the dataflow
         #[clap(value_name = "UUID_OR_NAME")]
         dataflow: Option<String>,
         /// Show logs for the given node
         #[clap(value_name = "NAME")]
-        node: String,
+        node: Option<String>,
+        /// Keep printing new log messages until the dataflow finishes
+        #[clap(long, short = 'f', action)]
+        follow: bool,
+        /// Only show log messages of the given time span, e.g. `10m`
+        #[clap(long, value_name = "DURATION")]
+        #[arg(value_parser = parse)]
+        since: Option<Duration>,
         /// Address of the dora coordinator
         #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
         coordinator_addr: IpAddr,
@@ -188,6 +585,18 @@ enum Command {
         /// Local listen port for event such as dynamic node.
         #[clap(long, default_value_t = DORA_DAEMON_LOCAL_LISTEN_PORT_DEFAULT)]
         local_listen_port: u16,
+        /// Listen for dynamic nodes on a unix socket instead of the local TCP port.
+        ///
+        /// Names starting with `@` use the abstract socket namespace (Linux only).
+        /// Dynamic nodes connect to it if `DORA_DAEMON_LOCAL_SOCKET` is set to the
+        /// same value.
+        #[cfg(unix)]
+        #[clap(long, value_name = "PATH|@NAME", env = DORA_DAEMON_LOCAL_SOCKET_ENV)]
+        local_socket: Option<LocalSocketAddr>,
+        /// Permissions of the local unix socket file, in octal notation.
+        #[cfg(unix)]
+        #[clap(long, default_value = "600", value_parser = parse_permissions)]
+        local_socket_permissions: u32,
         /// Address and port number of the dora coordinator
         #[clap(long, default_value_t = SocketAddr::new(LOCALHOST, DORA_COORDINATOR_PORT_DEFAULT))]
         coordinator_addr: SocketAddr,
@@ -232,12 +641,16 @@ pub struct CommandNew {
     /// Where to create the entity
     #[clap(hide = true)]
     path: Option<PathBuf>,
+    /// Inputs and outputs of a custom node
+    #[clap(flatten)]
+    io: template::IoArgs,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 enum Kind {
     Dataflow,
     CustomNode,
+    Operator,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
@@ -245,6 +658,7 @@ enum Lang {
     Rust,
     Python,
     C,
+    #[value(name = "c++", alias = "cxx")]
     Cxx,
 }
 
@@ -257,7 +671,8 @@ fn main() {
 }
 
 fn run() -> eyre::Result<()> {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
 
     #[cfg(feature = "tracing")]
     match &args.command {
@@ -291,64 +706,228 @@ fn run() -> eyre::Result<()> {
         .build()
         .filter();
 
+    let explicit_coordinator = matches.subcommand().is_some_and(|(_, matches)| {
+        ["coordinator_addr", "coordinator_port"]
+            .into_iter()
+            // not all subcommands have these arguments, so check the ID first
+            .filter(|id| matches.try_contains_id(id).is_ok())
+            .any(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+    });
+    // only read the config for commands that connect to the control server
+    let control_addr = |addr: IpAddr, port: u16| -> eyre::Result<SocketAddr> {
+        let coordinator = if explicit_coordinator {
+            None
+        } else {
+            config::coordinator_override(args.coordinator.as_deref(), args.profile.as_deref())?
+        };
+        Ok(coordinator.unwrap_or(SocketAddr::new(addr, port)))
+    };
+
     match args.command {
         Command::Check {
             dataflow,
+            offline,
+            output,
             coordinator_addr,
             coordinator_port,
-        } => match dataflow {
-            Some(dataflow) => {
-                let working_dir = dataflow
-                    .canonicalize()
-                    .context("failed to canonicalize dataflow path")?
-                    .parent()
-                    .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
-                    .to_owned();
-                Descriptor::blocking_read(&dataflow)?.check(&working_dir)?;
-                check::check_environment((coordinator_addr, coordinator_port).into())?
+        } => {
+            let report = check::check(
+                dataflow.as_deref(),
+                control_addr(coordinator_addr, coordinator_port)?,
+                offline,
+            )?;
+            report.print(output)?;
+            match report.exit_code() {
+                0 => {}
+                code => std::process::exit(code),
             }
-            None => check::check_environment((coordinator_addr, coordinator_port).into())?,
-        },
+        }
+        Command::Doctor {
+            dataflow,
+            output,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let report = doctor::doctor(
+                dataflow.as_deref(),
+                control_addr(coordinator_addr, coordinator_port)?,
+            )?;
+            report.print(output)?;
+            match report.exit_code() {
+                0 => {}
+                code => std::process::exit(code),
+            }
+        }
         Command::Graph {
             dataflow,
             mermaid,
             open,
+            live,
+            interval,
+            coordinator_addr,
+            coordinator_port,
         } => {
-            graph::create(dataflow, mermaid, open)?;
+            if live {
+                if open {
+                    bail!("`--open` is not supported for `--live` graphs");
+                }
+                let mut session =
+                    connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                        .wrap_err("failed to connect to dora coordinator")?;
+                let dataflow = select_running_dataflow(&mut *session, dataflow, "show")?;
+                graph::live::show(&mut *session, dataflow, mermaid, interval)?;
+            } else {
+                let dataflow = dataflow
+                    .ok_or_else(|| eyre::eyre!("missing path to the dataflow descriptor file"))?;
+                graph::create(dataflow.into(), mermaid, open)?;
+            }
         }
-        Command::Build { dataflow } => {
-            build::build(&dataflow)?;
+        Command::Build {
+            dataflow,
+            jobs,
+            force,
+            targets,
+        } => {
+            build::build(&dataflow, jobs, force, &BuildTargets::parse(&targets)?)?;
+        }
+        Command::Bundle {
+            dataflow,
+            name,
+            output,
+            include,
+            wheels,
+            build,
+        } => {
+            if build {
+                build::build(&dataflow, None, false, &BuildTargets::default())?;
+            }
+            bundle::bundle(&dataflow, name, output, &include, wheels)?;
+        }
+        Command::Convert { command } => convert::run(command)?,
+        Command::Deploy {
+            bundle,
+            machine_ids,
+            target_dir,
+            start,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("could not connect to dora coordinator")?;
+            bundle::deploy(
+                &mut *session,
+                &bundle,
+                machine_ids.into_iter().collect(),
+                target_dir,
+                start,
+            )?;
         }
         Command::New {
             args,
             internal_create_with_path_dependencies,
         } => template::create(args, internal_create_with_path_dependencies)?,
-        Command::Up { config } => {
+        Command::Init {
+            name,
+            interactive,
+            lang,
+            internal_create_with_path_dependencies,
+        } => match name {
+            Some(name) if !interactive => template::create(
+                CommandNew {
+                    kind: Kind::Dataflow,
+                    lang,
+                    name,
+                    path: None,
+                    io: Default::default(),
+                },
+                internal_create_with_path_dependencies,
+            )?,
+            name => template::init_interactive(name, internal_create_with_path_dependencies)?,
+        },
+        Command::Up {
+            config,
+            watch,
+            name,
+        } => {
             up::up(config.as_deref())?;
+            if let Some(dataflow) = watch {
+                let coordinator_addr = (LOCALHOST, DORA_COORDINATOR_PORT_CONTROL_DEFAULT).into();
+                let mut session = connect_to_coordinator(coordinator_addr)
+                    .wrap_err("failed to connect to dora coordinator")?;
+                watch::watch(&dataflow, name, &mut *session)?;
+            }
+        }
+        Command::UpgradeDaemons {
+            binary,
+            machine_ids,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("could not connect to dora coordinator")?;
+            upgrade_daemons(binary, machine_ids.into_iter().collect(), &mut *session)?;
+        }
+        Command::Operator {
+            command,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("could not connect to dora coordinator")?;
+            operator::run(command, &mut *session)?;
+        }
+        Command::Topic {
+            command,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("could not connect to dora coordinator")?;
+            topic::run(command, &mut *session)?;
         }
         Command::Logs {
             dataflow,
             node,
+            follow,
+            since,
             coordinator_addr,
             coordinator_port,
         } => {
-            let mut session = connect_to_coordinator((coordinator_addr, coordinator_port).into())
-                .wrap_err("failed to connect to dora coordinator")?;
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("failed to connect to dora coordinator")?;
             let list = query_running_dataflows(&mut *session)
                 .wrap_err("failed to query running dataflows")?;
-            if let Some(dataflow) = dataflow {
-                let uuid = Uuid::parse_str(&dataflow).ok();
-                let name = if uuid.is_some() { None } else { Some(dataflow) };
-                logs::logs(&mut *session, uuid, name, node)?
-            } else {
-                let active = list.get_active();
-                let uuid = match &active[..] {
-                    [] => bail!("No dataflows are running"),
-                    [uuid] => uuid.clone(),
-                    _ => inquire::Select::new("Choose dataflow to show logs:", active).prompt()?,
-                };
-                logs::logs(&mut *session, Some(uuid.uuid), None, node)?
-            }
+            let find = |dataflow: &str| {
+                list.0
+                    .iter()
+                    .map(|entry| &entry.id)
+                    .find(|id| {
+                        id.uuid.to_string() == dataflow || id.name.as_deref() == Some(dataflow)
+                    })
+                    .map(|id| id.uuid)
+            };
+            let (uuid, node) = match dataflow {
+                Some(dataflow) => match find(&dataflow) {
+                    Some(uuid) => (uuid, node),
+                    // a single argument that is no dataflow is the node, as in
+                    // `dora logs <node>`
+                    None if node.is_none() => {
+                        let id = select_running_dataflow(&mut *session, None, "show logs")?;
+                        (id.uuid, Some(dataflow))
+                    }
+                    None => bail!("no dataflow with UUID or name `{dataflow}`"),
+                },
+                None => {
+                    let id = select_running_dataflow(&mut *session, None, "show logs")?;
+                    (id.uuid, node)
+                }
+            };
+            logs::logs(&mut *session, uuid, node, since, follow)?
         }
         Command::Start {
             dataflow,
@@ -358,60 +937,142 @@ fn run() -> eyre::Result<()> {
             attach,
             detach,
             hot_reload,
+            grace_duration,
         } => {
             let dataflow_descriptor =
                 Descriptor::blocking_read(&dataflow).wrap_err("Failed to read yaml dataflow")?;
-            let working_dir = dataflow
-                .canonicalize()
-                .context("failed to canonicalize dataflow path")?
-                .parent()
-                .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
-                .to_owned();
-            if !coordinator_addr.is_loopback() {
-                dataflow_descriptor.check_in_daemon(&working_dir, &[], true)?;
-            } else {
-                dataflow_descriptor
-                    .check(&working_dir)
-                    .wrap_err("Could not validate yaml")?;
-            }
-
-            let coordinator_socket = (coordinator_addr, coordinator_port).into();
-            let mut session = connect_to_coordinator(coordinator_socket)
-                .wrap_err("failed to connect to dora coordinator")?;
-            let dataflow_id = start_dataflow(
-                dataflow_descriptor.clone(),
+            start(
+                dataflow,
+                dataflow_descriptor,
                 name,
-                working_dir,
-                &mut *session,
-            )?;
-
-            let attach = match (attach, detach) {
-                (true, true) => eyre::bail!("both `--attach` and `--detach` are given"),
-                (true, false) => true,
-                (false, true) => false,
-                (false, false) => {
-                    println!("attaching to dataflow (use `--detach` to run in background)");
-                    true
-                }
+                control_addr(coordinator_addr, coordinator_port)?,
+                attach,
+                detach,
+                hot_reload,
+                grace_duration,
+                log_level,
+            )?
+        }
+        Command::Inspect {
+            dataflow,
+            output,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("failed to connect to dora coordinator")?;
+            // finished dataflows can be inspected too, so look them up in the full list
+            let list = query_running_dataflows(&mut *session)
+                .wrap_err("failed to query running dataflows")?;
+            let uuid = match dataflow {
+                Some(dataflow) => list
+                    .0
+                    .iter()
+                    .map(|entry| &entry.id)
+                    .find(|id| {
+                        id.uuid.to_string() == dataflow || id.name.as_deref() == Some(&dataflow)
+                    })
+                    .map(|id| id.uuid)
+                    .ok_or_else(|| eyre::eyre!("no dataflow with UUID or name `{dataflow}`"))?,
+                None => select_running_dataflow(&mut *session, None, "inspect")?.uuid,
             };
-
-            if attach {
-                attach_dataflow(
-                    dataflow_descriptor,
-                    dataflow,
-                    dataflow_id,
-                    &mut *session,
-                    hot_reload,
-                    coordinator_socket,
-                    log_level,
-                )?
+            inspect::inspect(&mut *session, uuid, output)?;
+        }
+        Command::Top {
+            dataflow,
+            interval,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("failed to connect to dora coordinator")?;
+            let dataflow = dataflow
+                .map(|dataflow| select_running_dataflow(&mut *session, Some(dataflow), "monitor"))
+                .transpose()?;
+            top::run(&mut *session, dataflow, interval)?;
+        }
+        Command::Metrics {
+            dataflow,
+            output,
+            pushgateway,
+            job,
+            otlp_endpoint,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("failed to connect to dora coordinator")?;
+            let dataflow = dataflow
+                .map(|dataflow| select_running_dataflow(&mut *session, Some(dataflow), "query"))
+                .transpose()?;
+            let snapshot = metrics::snapshot(&mut *session, dataflow.as_ref())?;
+            if let Some(gateway) = &pushgateway {
+                snapshot.push_to_gateway(gateway, &job)?;
+                eprintln!("pushed metrics to `{gateway}`");
+            }
+            if let Some(endpoint) = &otlp_endpoint {
+                snapshot.export_otlp(endpoint)?;
+                eprintln!("exported metrics to `{endpoint}`");
             }
+            if pushgateway.is_none() && otlp_endpoint.is_none() {
+                snapshot.print(output)?;
+            }
+        }
+        Command::Record {
+            command,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("could not connect to dora coordinator")?;
+            record::run(command, &mut *session)?;
+        }
+        Command::Replay {
+            recording,
+            dataflow,
+            nodes,
+            speed,
+            name,
+            coordinator_addr,
+            coordinator_port,
+            attach,
+            detach,
+        } => {
+            let mut dataflow_descriptor =
+                Descriptor::blocking_read(&dataflow).wrap_err("Failed to read yaml dataflow")?;
+            let recording = if recording.exists() {
+                recording
+                    .canonicalize()
+                    .context("failed to canonicalize recording path")?
+            } else {
+                recording
+            };
+            let replay = &mut dataflow_descriptor.replay;
+            replay.path = Some(recording);
+            replay.nodes = nodes.into_iter().map(Into::into).collect();
+            replay.speed = speed;
+            start(
+                dataflow,
+                dataflow_descriptor,
+                name,
+                control_addr(coordinator_addr, coordinator_port)?,
+                attach,
+                detach,
+                false,
+                None,
+                log_level,
+            )?
         }
         Command::List {
+            output,
             coordinator_addr,
             coordinator_port,
-        } => match connect_to_coordinator((coordinator_addr, coordinator_port).into()) {
-            Ok(mut session) => list(&mut *session)?,
+        } => match connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?) {
+            Ok(mut session) => list(&mut *session, output)?,
             Err(_) => {
                 bail!("No dora coordinator seems to be running.");
             }
@@ -423,21 +1084,35 @@ fn run() -> eyre::Result<()> {
             coordinator_addr,
             coordinator_port,
         } => {
-            let mut session = connect_to_coordinator((coordinator_addr, coordinator_port).into())
-                .wrap_err("could not connect to dora coordinator")?;
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("could not connect to dora coordinator")?;
             match (uuid, name) {
                 (Some(uuid), _) => stop_dataflow(uuid, grace_duration, &mut *session)?,
                 (None, Some(name)) => stop_dataflow_by_name(name, grace_duration, &mut *session)?,
                 (None, None) => stop_dataflow_interactive(grace_duration, &mut *session)?,
             }
         }
+        Command::RestartNode {
+            node,
+            dataflow,
+            grace_duration,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator(control_addr(coordinator_addr, coordinator_port)?)
+                    .wrap_err("could not connect to dora coordinator")?;
+            let dataflow = select_running_dataflow(&mut *session, dataflow, "restart a node of")?;
+            restart_node(dataflow.uuid, node.into(), grace_duration, &mut *session)?;
+        }
         Command::Destroy {
             config,
             coordinator_addr,
             coordinator_port,
         } => up::destroy(
             config.as_deref(),
-            (coordinator_addr, coordinator_port).into(),
+            control_addr(coordinator_addr, coordinator_port)?,
         )?,
         Command::Coordinator {
             interface,
@@ -467,10 +1142,23 @@ fn run() -> eyre::Result<()> {
             coordinator_addr,
             inter_daemon_addr,
             local_listen_port,
+            #[cfg(unix)]
+            local_socket,
+            #[cfg(unix)]
+            local_socket_permissions,
             machine_id,
             run_dataflow,
             quiet: _,
         } => {
+            #[allow(unused_mut)]
+            let mut local_listen = LocalListenConfig::Tcp(local_listen_port);
+            #[cfg(unix)]
+            if let Some(addr) = local_socket {
+                local_listen = LocalListenConfig::Unix {
+                    addr,
+                    permissions: local_socket_permissions,
+                };
+            }
             let rt = Builder::new_multi_thread()
                 .enable_all()
                 .build()
@@ -493,18 +1181,91 @@ fn run() -> eyre::Result<()> {
                         if coordinator_addr.ip() == LOCALHOST {
                             tracing::info!("Starting in local mode");
                         }
-                        Daemon::run(coordinator_addr, machine_id.unwrap_or_default(), inter_daemon_addr, local_listen_port).await
+                        Daemon::run(coordinator_addr, machine_id.unwrap_or_default(), inter_daemon_addr, local_listen).await
                     }
                 }
             })
             .context("failed to run dora-daemon")?
         }
+        Command::Completion { shell } => completion::generate(shell)?,
+        Command::CompleteDataflows => completion::print_dataflows(control_addr(
+            LOCALHOST,
+            DORA_COORDINATOR_PORT_CONTROL_DEFAULT,
+        )?),
+        Command::Bench {
+            sizes,
+            messages,
+            rate,
+            transports,
+            output,
+        } => bench::run(sizes, messages, rate, transports, output)?,
+        Command::BenchNode { node } => bench::run_node(node)?,
         Command::Runtime => dora_runtime::main().context("Failed to run dora-runtime")?,
     };
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
+fn start(
+    dataflow_path: PathBuf,
+    dataflow_descriptor: Descriptor,
+    name: Option<String>,
+    coordinator_socket: SocketAddr,
+    attach: bool,
+    detach: bool,
+    hot_reload: bool,
+    grace_duration: Option<Duration>,
+    log_level: log::LevelFilter,
+) -> eyre::Result<()> {
+    let working_dir = dataflow_path
+        .canonicalize()
+        .context("failed to canonicalize dataflow path")?
+        .parent()
+        .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
+        .to_owned();
+    if !coordinator_socket.ip().is_loopback() {
+        dataflow_descriptor.check_in_daemon(&working_dir, &[], true)?;
+    } else {
+        dataflow_descriptor
+            .check(&working_dir)
+            .wrap_err("Could not validate yaml")?;
+    }
+
+    let mut session = connect_to_coordinator(coordinator_socket)
+        .wrap_err("failed to connect to dora coordinator")?;
+    let dataflow_id = start_dataflow(
+        dataflow_descriptor.clone(),
+        name,
+        working_dir,
+        &mut *session,
+    )?;
+
+    let attach = match (attach, detach) {
+        (true, true) => eyre::bail!("both `--attach` and `--detach` are given"),
+        (true, false) => true,
+        (false, true) => false,
+        (false, false) => {
+            println!("attaching to dataflow (use `--detach` to run in background)");
+            true
+        }
+    };
+
+    if attach {
+        attach_dataflow(
+            dataflow_descriptor,
+            dataflow_path,
+            dataflow_id,
+            &mut *session,
+            hot_reload,
+            grace_duration,
+            coordinator_socket,
+            log_level,
+        )?
+    }
+    Ok(())
+}
+
 fn start_dataflow(
     dataflow: Descriptor,
     name: Option<String>,
@@ -618,19 +1379,41 @@ fn stop_dataflow_by_name(
     }
 }
 
-fn list(session: &mut TcpRequestReplyConnection) -> Result<(), eyre::ErrReport> {
+fn list(
+    session: &mut TcpRequestReplyConnection,
+    output: OutputFormat,
+) -> Result<(), eyre::ErrReport> {
     let list = query_running_dataflows(session)?;
 
+    #[derive(serde::Serialize)]
+    struct Entry {
+        uuid: Uuid,
+        name: Option<String>,
+        status: &'static str,
+    }
+    let entries: Vec<_> = list
+        .0
+        .into_iter()
+        .map(|entry| Entry {
+            uuid: entry.id.uuid,
+            name: entry.id.name,
+            status: match entry.status {
+                dora_core::topics::DataflowStatus::Running => "Running",
+                dora_core::topics::DataflowStatus::Finished => "Succeeded",
+                dora_core::topics::DataflowStatus::Failed => "Failed",
+            },
+        })
+        .collect();
+    if formatting::print_structured(&entries, output)? {
+        return Ok(());
+    }
+
     let mut tw = TabWriter::new(vec![]);
     tw.write_all(b"UUID\tName\tStatus\n")?;
-    for entry in list.0 {
-        let uuid = entry.id.uuid;
-        let name = entry.id.name.unwrap_or_default();
-        let status = match entry.status {
-            dora_core::topics::DataflowStatus::Running => "Running",
-            dora_core::topics::DataflowStatus::Finished => "Succeeded",
-            dora_core::topics::DataflowStatus::Failed => "Failed",
-        };
+    for entry in entries {
+        let uuid = entry.uuid;
+        let name = entry.name.unwrap_or_default();
+        let status = entry.status;
         tw.write_all(format!("{uuid}\t{name}\t{status}\n").as_bytes())?;
     }
     tw.flush()?;
@@ -656,6 +1439,113 @@ fn query_running_dataflows(session: &mut TcpRequestReplyConnection) -> eyre::Res
     Ok(ids)
 }
 
+/// Looks up a running dataflow by UUID or name, or lets the user choose one if
+/// no dataflow is given.
+fn select_running_dataflow(
+    session: &mut TcpRequestReplyConnection,
+    dataflow: Option<String>,
+    action: &str,
+) -> eyre::Result<DataflowId> {
+    let active = query_running_dataflows(session)
+        .wrap_err("failed to query running dataflows")?
+        .get_active();
+    match dataflow {
+        Some(dataflow) => {
+            let uuid = Uuid::parse_str(&dataflow).ok();
+            active
+                .into_iter()
+                .find(|id| Some(id.uuid) == uuid || id.name.as_ref() == Some(&dataflow))
+                .ok_or_else(|| eyre::eyre!("no running dataflow with UUID or name `{dataflow}`"))
+        }
+        None => match &active[..] {
+            [] => bail!("No dataflows are running"),
+            [id] => Ok(id.clone()),
+            _ => Ok(
+                inquire::Select::new(&format!("Choose dataflow to {action}:"), active).prompt()?,
+            ),
+        },
+    }
+}
+
+fn restart_node(
+    dataflow_uuid: Uuid,
+    node_id: NodeId,
+    grace_duration: Option<Duration>,
+    session: &mut TcpRequestReplyConnection,
+) -> eyre::Result<()> {
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::RestartNode {
+                dataflow_uuid,
+                node_id,
+                grace_duration,
+            })
+            .unwrap(),
+        )
+        .wrap_err("failed to send restart node message")?;
+    let result: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match result {
+        ControlRequestReply::NodeRestarting { uuid, node_id } => {
+            println!("restarting node `{node_id}` of dataflow `{uuid}`");
+        }
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected restart node reply: {other:?}"),
+    }
+    Ok(())
+}
+
+fn upgrade_daemons(
+    binary: Option<PathBuf>,
+    machine_ids: BTreeSet<String>,
+    session: &mut TcpRequestReplyConnection,
+) -> eyre::Result<()> {
+    let binary = match binary {
+        Some(binary) => binary,
+        None => std::env::current_exe().wrap_err("failed to get current executable")?,
+    };
+    let binary = std::fs::read(&binary)
+        .wrap_err_with(|| format!("failed to read `{}`", binary.display()))?;
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::UpgradeDaemons {
+                binary,
+                machine_ids,
+            })
+            .unwrap(),
+        )
+        .wrap_err("failed to send upgrade daemons message")?;
+    let reply: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::DaemonsUpgrading(machines) => {
+            for (machine_id, version) in machines {
+                let name = if machine_id.is_empty() {
+                    "<default>"
+                } else {
+                    machine_id.as_str()
+                };
+                println!(
+                    "daemon `{name}` upgrades to v{version} after its running dataflows finished"
+                );
+            }
+        }
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected upgrade daemons reply: {other:?}"),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn parse_permissions(s: &str) -> eyre::Result<u32> {
+    let mode = u32::from_str_radix(s.trim_start_matches("0o"), 8)
+        .wrap_err_with(|| format!("invalid octal permissions `{s}`"))?;
+    if mode > 0o777 {
+        bail!("invalid permissions `{s}`, must be at most 777");
+    }
+    Ok(mode)
+}
+
 fn connect_to_coordinator(
     coordinator_addr: SocketAddr,
 ) -> std::io::Result<Box<TcpRequestReplyConnection>> {
@@ -0,0 +1,511 @@
+//! One-off snapshot of the coordinator, daemon, and node statistics, see
+//! `dora metrics`.
+//!
+//! The snapshot is printed or pushed once to a Prometheus Pushgateway or an
+//! OTLP endpoint. Both exports are built from the same list of samples, so
+//! that they contain the same metrics.
+
+use std::{collections::BTreeMap, io::Write, sync::Arc, time::Duration};
+
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    config::NodeId,
+    topics::{ControlRequest, ControlRequestReply, DataflowId, DataflowStatus},
+};
+use eyre::{bail, Context};
+use opentelemetry::{metrics::MeterProvider as _, KeyValue};
+use serde::Serialize;
+use tabwriter::TabWriter;
+use uuid::Uuid;
+
+use crate::{
+    formatting::{format_bytes, print_structured, OutputFormat},
+    query_running_dataflows,
+};
+
+#[derive(Debug, Serialize)]
+pub struct MetricsSnapshot {
+    /// Number of daemons that are connected to the coordinator.
+    connected_daemons: usize,
+    running_dataflows: usize,
+    finished_dataflows: usize,
+    failed_dataflows: usize,
+    daemons: Vec<DaemonMetrics>,
+    nodes: Vec<NodeMetrics>,
+}
+
+/// Node statistics summed up per machine.
+#[derive(Debug, Default, Serialize)]
+struct DaemonMetrics {
+    machine: String,
+    nodes: usize,
+    cpu_usage: f32,
+    memory: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct NodeMetrics {
+    dataflow: Uuid,
+    dataflow_name: Option<String>,
+    node: NodeId,
+    machine: String,
+    cpu_usage: Option<f32>,
+    memory: Option<u64>,
+    queue_depth: usize,
+    dropped_inputs: u64,
+    mean_latency: Option<Duration>,
+    sent_messages: u64,
+    sent_bytes: u64,
+    received_messages: u64,
+    received_bytes: u64,
+}
+
+/// Queries the statistics of all running dataflows, or only of the given one.
+pub fn snapshot(
+    session: &mut TcpRequestReplyConnection,
+    dataflow: Option<&DataflowId>,
+) -> eyre::Result<MetricsSnapshot> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::ConnectedMachines).unwrap())
+        .wrap_err("failed to send connected machines request")?;
+    let reply: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    let connected_daemons = match reply {
+        ControlRequestReply::ConnectedMachines(machines) => machines.len(),
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected connected machines reply: {other:?}"),
+    };
+
+    let list = query_running_dataflows(session)?;
+    let count = |status| list.0.iter().filter(|d| d.status == status).count();
+    let mut snapshot = MetricsSnapshot {
+        connected_daemons,
+        running_dataflows: count(DataflowStatus::Running),
+        finished_dataflows: count(DataflowStatus::Finished),
+        failed_dataflows: count(DataflowStatus::Failed),
+        daemons: Vec::new(),
+        nodes: Vec::new(),
+    };
+
+    let mut daemons: BTreeMap<String, DaemonMetrics> = BTreeMap::new();
+    for id in list.get_active() {
+        if dataflow.is_some_and(|dataflow| dataflow.uuid != id.uuid) {
+            continue;
+        }
+        let reply_raw = session
+            .request(
+                &serde_json::to_vec(&ControlRequest::Topology {
+                    dataflow_uuid: id.uuid,
+                })
+                .unwrap(),
+            )
+            .wrap_err("failed to send topology request")?;
+        let reply: ControlRequestReply =
+            serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+        let (nodes, mut stats) = match reply {
+            ControlRequestReply::Topology { nodes, stats } => (nodes, stats),
+            // the dataflow might have finished in the meantime
+            ControlRequestReply::Error(_) => continue,
+            other => bail!("unexpected topology reply: {other:?}"),
+        };
+        for node in nodes {
+            let stats = stats.remove(&node.id).unwrap_or_default();
+            let machine = node.deploy.machine;
+            let daemon = daemons
+                .entry(machine.clone())
+                .or_insert_with(|| DaemonMetrics {
+                    machine: machine.clone(),
+                    ..Default::default()
+                });
+            daemon.nodes += 1;
+            daemon.cpu_usage += stats.cpu_usage.unwrap_or_default();
+            daemon.memory += stats.memory.unwrap_or_default();
+
+            snapshot.nodes.push(NodeMetrics {
+                dataflow: id.uuid,
+                dataflow_name: id.name.clone(),
+                node: node.id,
+                machine,
+                cpu_usage: stats.cpu_usage,
+                memory: stats.memory,
+                queue_depth: stats.queue_depth,
+                dropped_inputs: stats.dropped_inputs,
+                mean_latency: stats.mean_latency,
+                sent_messages: stats.outputs.values().map(|e| e.messages).sum(),
+                sent_bytes: stats.outputs.values().map(|e| e.bytes).sum(),
+                received_messages: stats.inputs.values().map(|e| e.messages).sum(),
+                received_bytes: stats.inputs.values().map(|e| e.bytes).sum(),
+            });
+        }
+    }
+    snapshot.daemons = daemons.into_values().collect();
+    Ok(snapshot)
+}
+
+impl MetricsSnapshot {
+    pub fn print(&self, format: OutputFormat) -> eyre::Result<()> {
+        if print_structured(self, format)? {
+            return Ok(());
+        }
+
+        println!(
+            "{} daemon(s) connected, {} running, {} finished, {} failed dataflow(s)\n",
+            self.connected_daemons,
+            self.running_dataflows,
+            self.finished_dataflows,
+            self.failed_dataflows
+        );
+        let mut tw = TabWriter::new(vec![]);
+        tw.write_all(b"MACHINE\tNODES\tCPU\tMEMORY\n")?;
+        for daemon in &self.daemons {
+            writeln!(
+                tw,
+                "{}\t{}\t{:.1}%\t{}",
+                machine_name(&daemon.machine),
+                daemon.nodes,
+                daemon.cpu_usage,
+                format_bytes(daemon.memory as f64)
+            )?;
+        }
+        tw.write_all(
+            b"\nDATAFLOW\tNODE\tMACHINE\tCPU\tMEMORY\tQUEUE\tDROPPED\tLATENCY\tSENT\tRECEIVED\n",
+        )?;
+        for node in &self.nodes {
+            let dataflow = match &node.dataflow_name {
+                Some(name) => name.clone(),
+                None => node.dataflow.to_string(),
+            };
+            let cpu = node
+                .cpu_usage
+                .map(|cpu| format!("{cpu:.1}%"))
+                .unwrap_or_else(|| "-".into());
+            let memory = node
+                .memory
+                .map(|memory| format_bytes(memory as f64))
+                .unwrap_or_else(|| "-".into());
+            let latency = node
+                .mean_latency
+                .map(|latency| format!("{latency:.1?}"))
+                .unwrap_or_else(|| "-".into());
+            writeln!(
+                tw,
+                "{dataflow}\t{}\t{}\t{cpu}\t{memory}\t{}\t{}\t{latency}\t{} ({})\t{} ({})",
+                node.node,
+                machine_name(&node.machine),
+                node.queue_depth,
+                node.dropped_inputs,
+                node.sent_messages,
+                format_bytes(node.sent_bytes as f64),
+                node.received_messages,
+                format_bytes(node.received_bytes as f64),
+            )?;
+        }
+        tw.flush()?;
+        print!("{}", String::from_utf8(tw.into_inner()?)?);
+        Ok(())
+    }
+
+    fn samples(&self) -> Vec<Sample> {
+        let mut samples = vec![Sample::gauge(
+            "dora_connected_daemons",
+            "Number of daemons connected to the coordinator",
+            vec![],
+            self.connected_daemons as f64,
+        )];
+        for (status, count) in [
+            ("running", self.running_dataflows),
+            ("finished", self.finished_dataflows),
+            ("failed", self.failed_dataflows),
+        ] {
+            samples.push(Sample::gauge(
+                "dora_dataflows",
+                "Number of dataflows known to the coordinator",
+                vec![("status", status.to_owned())],
+                count as f64,
+            ));
+        }
+        for daemon in &self.daemons {
+            let labels = vec![("machine", daemon.machine.clone())];
+            samples.push(Sample::gauge(
+                "dora_daemon_nodes",
+                "Number of running nodes on the machine",
+                labels.clone(),
+                daemon.nodes as f64,
+            ));
+            samples.push(Sample::gauge(
+                "dora_daemon_cpu_usage_percent",
+                "CPU usage of the nodes on the machine in percent of one core",
+                labels.clone(),
+                daemon.cpu_usage as f64,
+            ));
+            samples.push(Sample::gauge(
+                "dora_daemon_memory_bytes",
+                "Resident memory of the nodes on the machine",
+                labels,
+                daemon.memory as f64,
+            ));
+        }
+        for node in &self.nodes {
+            let labels = vec![
+                ("dataflow", node.dataflow.to_string()),
+                (
+                    "dataflow_name",
+                    node.dataflow_name.clone().unwrap_or_default(),
+                ),
+                ("node", node.node.to_string()),
+                ("machine", node.machine.clone()),
+            ];
+            let mut push = |name, help, kind, value: f64| {
+                samples.push(Sample {
+                    name,
+                    help,
+                    kind,
+                    labels: labels.clone(),
+                    value,
+                })
+            };
+            if let Some(cpu) = node.cpu_usage {
+                push(
+                    "dora_node_cpu_usage_percent",
+                    "CPU usage of the node in percent of one core",
+                    Kind::Gauge,
+                    cpu as f64,
+                );
+            }
+            if let Some(memory) = node.memory {
+                push(
+                    "dora_node_memory_bytes",
+                    "Resident memory of the node",
+                    Kind::Gauge,
+                    memory as f64,
+                );
+            }
+            if let Some(latency) = node.mean_latency {
+                push(
+                    "dora_node_mean_latency_seconds",
+                    "Mean time between sending an input and handing it to the node",
+                    Kind::Gauge,
+                    latency.as_secs_f64(),
+                );
+            }
+            push(
+                "dora_node_queue_depth",
+                "Number of events waiting to be received by the node",
+                Kind::Gauge,
+                node.queue_depth as f64,
+            );
+            push(
+                "dora_node_dropped_inputs_total",
+                "Inputs dropped because the input queue was full",
+                Kind::Counter,
+                node.dropped_inputs as f64,
+            );
+            push(
+                "dora_node_sent_messages_total",
+                "Messages sent on the outputs of the node",
+                Kind::Counter,
+                node.sent_messages as f64,
+            );
+            push(
+                "dora_node_sent_bytes_total",
+                "Data bytes sent on the outputs of the node",
+                Kind::Counter,
+                node.sent_bytes as f64,
+            );
+            push(
+                "dora_node_received_messages_total",
+                "Messages delivered to the inputs of the node",
+                Kind::Counter,
+                node.received_messages as f64,
+            );
+            push(
+                "dora_node_received_bytes_total",
+                "Data bytes delivered to the inputs of the node",
+                Kind::Counter,
+                node.received_bytes as f64,
+            );
+        }
+        samples
+    }
+
+    /// Replaces the metrics of the `job` group on the Pushgateway.
+    pub fn push_to_gateway(&self, gateway: &str, job: &str) -> eyre::Result<()> {
+        // the samples of a metric need to be grouped together
+        let mut samples = self.samples();
+        samples.sort_by_key(|sample| sample.name);
+        let mut body = String::new();
+        let mut previous = None;
+        for sample in samples {
+            if previous != Some(sample.name) {
+                body.push_str(&format!("# HELP {} {}\n", sample.name, sample.help));
+                body.push_str(&format!(
+                    "# TYPE {} {}\n",
+                    sample.name,
+                    sample.kind.as_str()
+                ));
+                previous = Some(sample.name);
+            }
+            let labels: Vec<_> = sample
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_label(value)))
+                .collect();
+            body.push_str(&format!(
+                "{}{{{}}} {}\n",
+                sample.name,
+                labels.join(","),
+                sample.value
+            ));
+        }
+
+        let url = format!("{}/metrics/job/{job}", gateway.trim_end_matches('/'));
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("tokio runtime failed")?;
+        rt.block_on(async {
+            let response = reqwest::Client::new()
+                .put(&url)
+                .header("Content-Type", "text/plain; version=0.0.4")
+                .body(body)
+                .send()
+                .await
+                .wrap_err_with(|| format!("failed to push metrics to `{url}`"))?;
+            let status = response.status();
+            if !status.is_success() {
+                let text = response.text().await.unwrap_or_default();
+                bail!(
+                    "failed to push metrics to `{url}`: {status} {}",
+                    text.trim()
+                );
+            }
+            Ok(())
+        })
+    }
+
+    /// Exports the metrics once to the OTLP gRPC endpoint.
+    pub fn export_otlp(&self, endpoint: &str) -> eyre::Result<()> {
+        let mut by_name: BTreeMap<&'static str, (Kind, &'static str, Vec<Sample>)> =
+            BTreeMap::new();
+        for sample in self.samples() {
+            by_name
+                .entry(sample.name)
+                .or_insert_with(|| (sample.kind, sample.help, Vec::new()))
+                .2
+                .push(sample);
+        }
+
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("tokio runtime failed")?;
+        // the periodic reader of the exporter is spawned on the runtime
+        let _guard = rt.enter();
+        let provider = dora_metrics::init_metrics_with_endpoint(endpoint.to_owned())
+            .context("could not create opentelemetry meter")?;
+        let meter = provider.meter("dora-cli");
+        let mut gauges = Vec::new();
+        let mut counters = Vec::new();
+        for (name, (kind, help, samples)) in by_name {
+            let observations: Arc<Vec<(f64, Vec<KeyValue>)>> = Arc::new(
+                samples
+                    .into_iter()
+                    .map(|sample| {
+                        let attributes = sample
+                            .labels
+                            .into_iter()
+                            .map(|(key, value)| KeyValue::new(key, value))
+                            .collect();
+                        (sample.value, attributes)
+                    })
+                    .collect(),
+            );
+            // OTLP counters are named without the Prometheus `_total` suffix
+            match kind {
+                Kind::Gauge => gauges.push(
+                    meter
+                        .f64_observable_gauge(name)
+                        .with_description(help)
+                        .with_callback(move |observer| {
+                            for (value, attributes) in observations.iter() {
+                                observer.observe(*value, attributes);
+                            }
+                        })
+                        .init(),
+                ),
+                Kind::Counter => counters.push(
+                    meter
+                        .f64_observable_counter(name.trim_end_matches("_total"))
+                        .with_description(help)
+                        .with_callback(move |observer| {
+                            for (value, attributes) in observations.iter() {
+                                observer.observe(*value, attributes);
+                            }
+                        })
+                        .init(),
+                ),
+            }
+        }
+        provider
+            .force_flush()
+            .wrap_err_with(|| format!("failed to export metrics to `{endpoint}`"))?;
+        provider
+            .shutdown()
+            .context("failed to shut down opentelemetry meter")?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Gauge,
+    Counter,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Gauge => "gauge",
+            Kind::Counter => "counter",
+        }
+    }
+}
+
+struct Sample {
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+    labels: Vec<(&'static str, String)>,
+    value: f64,
+}
+
+impl Sample {
+    fn gauge(
+        name: &'static str,
+        help: &'static str,
+        labels: Vec<(&'static str, String)>,
+        value: f64,
+    ) -> Self {
+        Self {
+            name,
+            help,
+            kind: Kind::Gauge,
+            labels,
+            value,
+        }
+    }
+}
+
+fn machine_name(machine: &str) -> &str {
+    match machine {
+        "" => "<default>",
+        machine => machine,
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
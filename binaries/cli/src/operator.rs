@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    config::{NodeId, OperatorId},
+    descriptor::OperatorDefinition,
+    topics::{ControlRequest, ControlRequestReply},
+};
+use eyre::{bail, Context, Result};
+use uuid::Uuid;
+
+use crate::query_running_dataflows;
+
+#[derive(Debug, clap::Subcommand)]
+pub enum OperatorCommand {
+    /// Load an operator into a running runtime node.
+    ///
+    /// The operator is defined in a YAML file, using the same fields as the
+    /// operators in a dataflow descriptor. Relative source paths are resolved
+    /// against the working directory of the dataflow. Python operators can't be
+    /// loaded.
+    Load {
+        /// Identifier of the dataflow
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: String,
+        /// ID of the runtime node that should run the operator
+        #[clap(value_name = "NODE")]
+        node: String,
+        /// Path to the operator definition
+        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        operator: PathBuf,
+    },
+    /// Stop an operator of a running runtime node and remove it from the node.
+    Unload {
+        /// Identifier of the dataflow
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: String,
+        /// ID of the runtime node
+        #[clap(value_name = "NODE")]
+        node: String,
+        /// ID of the operator that should be unloaded
+        #[clap(value_name = "OPERATOR")]
+        operator: String,
+    },
+}
+
+pub fn run(command: OperatorCommand, session: &mut TcpRequestReplyConnection) -> Result<()> {
+    let request = match command {
+        OperatorCommand::Load {
+            dataflow,
+            node,
+            operator,
+        } => {
+            let raw = std::fs::read_to_string(&operator).wrap_err_with(|| {
+                format!(
+                    "failed to read operator definition `{}`",
+                    operator.display()
+                )
+            })?;
+            let operator: OperatorDefinition =
+                serde_yaml::from_str(&raw).wrap_err("failed to parse operator definition")?;
+            ControlRequest::LoadOperator {
+                dataflow_id: resolve_dataflow(dataflow, session)?,
+                node_id: NodeId::from(node),
+                operator,
+            }
+        }
+        OperatorCommand::Unload {
+            dataflow,
+            node,
+            operator,
+        } => ControlRequest::UnloadOperator {
+            dataflow_id: resolve_dataflow(dataflow, session)?,
+            node_id: NodeId::from(node),
+            operator_id: OperatorId::from(operator),
+        },
+    };
+
+    let reply_raw = session
+        .request(&serde_json::to_vec(&request).unwrap())
+        .wrap_err("failed to send operator request")?;
+    let reply: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::OperatorLoaded { uuid } => {
+            println!("operator loaded into dataflow {uuid}")
+        }
+        ControlRequestReply::OperatorUnloaded { uuid } => {
+            println!("operator unloaded from dataflow {uuid}")
+        }
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected operator reply: {other:?}"),
+    }
+    Ok(())
+}
+
+fn resolve_dataflow(dataflow: String, session: &mut TcpRequestReplyConnection) -> Result<Uuid> {
+    if let Ok(uuid) = Uuid::parse_str(&dataflow) {
+        return Ok(uuid);
+    }
+    let list = query_running_dataflows(session).wrap_err("failed to query running dataflows")?;
+    let mut matching = list
+        .get_active()
+        .into_iter()
+        .filter(|d| d.name.as_deref() == Some(dataflow.as_str()));
+    match (matching.next(), matching.next()) {
+        (Some(d), None) => Ok(d.uuid),
+        (None, _) => bail!("no running dataflow with name `{dataflow}`"),
+        (Some(_), Some(_)) => bail!("multiple running dataflows with name `{dataflow}`"),
+    }
+}
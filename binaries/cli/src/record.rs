@@ -0,0 +1,106 @@
+use std::io::Write;
+
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::topics::{ControlRequest, ControlRequestReply};
+use eyre::{bail, Context, Result};
+use tabwriter::TabWriter;
+
+use crate::{
+    formatting::{format_bytes, print_structured, OutputFormat},
+    select_running_dataflow,
+};
+
+#[derive(Debug, clap::Subcommand)]
+pub enum RecordCommand {
+    /// Start recording the outputs of a running dataflow.
+    ///
+    /// The recording is written by the daemons into the `out/<UUID>/record`
+    /// directory of the dataflow on each machine.
+    Start {
+        /// Identifier of the dataflow
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: Option<String>,
+        /// Only record the given output, in `node/output` form (default: all outputs)
+        #[clap(long = "output", value_name = "NODE/OUTPUT")]
+        outputs: Vec<String>,
+    },
+    /// Stop recording a running dataflow.
+    Stop {
+        /// Identifier of the dataflow
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: Option<String>,
+    },
+    /// List the recordings on all connected machines.
+    List {
+        /// Output format
+        #[clap(long, value_name = "FORMAT", default_value = "table")]
+        output: OutputFormat,
+    },
+}
+
+pub fn run(command: RecordCommand, session: &mut TcpRequestReplyConnection) -> Result<()> {
+    let mut output = OutputFormat::Table;
+    let request = match command {
+        RecordCommand::Start { dataflow, outputs } => {
+            for output in &outputs {
+                if output.split_once('/').is_none() {
+                    bail!("invalid output `{output}`: expected `node/output`");
+                }
+            }
+            ControlRequest::StartRecording {
+                dataflow_uuid: select_running_dataflow(session, dataflow, "record")?.uuid,
+                outputs: outputs.into_iter().collect(),
+            }
+        }
+        RecordCommand::Stop { dataflow } => ControlRequest::StopRecording {
+            dataflow_uuid: select_running_dataflow(session, dataflow, "stop recording")?.uuid,
+        },
+        RecordCommand::List { output: format } => {
+            output = format;
+            ControlRequest::ListRecordings
+        }
+    };
+
+    let reply_raw = session
+        .request(&serde_json::to_vec(&request).unwrap())
+        .wrap_err("failed to send record request")?;
+    let reply: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::RecordingStarted { uuid } => {
+            println!("recording dataflow {uuid}")
+        }
+        ControlRequestReply::RecordingStopped { uuid } => {
+            println!("stopped recording dataflow {uuid}")
+        }
+        ControlRequestReply::Recordings(mut recordings) => {
+            recordings.sort_by(|a, b| (&a.machine, &a.path).cmp(&(&b.machine, &b.path)));
+            if print_structured(&recordings, output)? {
+                return Ok(());
+            }
+            let mut tw = TabWriter::new(vec![]);
+            tw.write_all(b"MACHINE\tDATAFLOW\tPATH\tSEGMENTS\tSIZE\tSTATUS\n")?;
+            for recording in recordings {
+                let machine = if recording.machine.is_empty() {
+                    "<default>"
+                } else {
+                    recording.machine.as_str()
+                };
+                let status = if recording.active { "recording" } else { "" };
+                writeln!(
+                    tw,
+                    "{machine}\t{}\t{}\t{}\t{}\t{status}",
+                    recording.dataflow_id,
+                    recording.path.display(),
+                    recording.segments,
+                    format_bytes(recording.size as f64),
+                )?;
+            }
+            tw.flush()?;
+            print!("{}", String::from_utf8(tw.into_inner()?)?);
+        }
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected record reply: {other:?}"),
+    }
+    Ok(())
+}
@@ -1,4 +1,6 @@
+use dora_core::node_template::NodeIo;
 use dora_node_api_c::HEADER_NODE_API;
+use dora_operator_api_c::{HEADER_OPERATOR_API, HEADER_OPERATOR_TYPES};
 use eyre::{bail, Context, ContextCompat};
 use std::{
     fs,
@@ -8,6 +10,7 @@ use std::{
 const NODE: &str = include_str!("node/node-template.c");
 const TALKER: &str = include_str!("talker/talker-template.c");
 const LISTENER: &str = include_str!("listener/listener-template.c");
+const OPERATOR: &str = include_str!("operator/operator-template.c");
 
 pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()> {
     let crate::CommandNew {
@@ -15,10 +18,23 @@ pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()>
         lang: _,
         name,
         path,
+        io,
     } = args;
+    let io = NodeIo::from(io);
 
     match kind {
+        crate::Kind::CustomNode if !io.is_empty() => {
+            create_custom_node(name.clone(), path, &io.c_node())?;
+            print_snippet(&io, &name);
+            Ok(())
+        }
         crate::Kind::CustomNode => create_custom_node(name, path, NODE),
+        crate::Kind::Operator => {
+            create_operator(name.clone(), path)?;
+            // compiled manually to a shared library that links `dora_operator_api_c`
+            super::print_operator_snippet(&name, "shared-library", &format!("bin/{name}"), None);
+            Ok(())
+        }
         crate::Kind::Dataflow => create_dataflow(name, path, use_path_deps),
     }
 }
@@ -50,12 +66,17 @@ fn create_dataflow(
     create_custom_node("talker_1".into(), Some(root.join("talker_1")), TALKER)?;
     create_custom_node("talker_2".into(), Some(root.join("talker_2")), TALKER)?;
     create_custom_node("listener_1".into(), Some(root.join("listener_1")), LISTENER)?;
+    create_operator("counter".into(), Some(root.join("counter")))?;
     create_cmakefile(root.to_path_buf(), use_path_deps)?;
 
     println!(
         "Created new C dataflow at `{name}` at {}",
         Path::new(".").join(root).display()
     );
+    println!(
+        "\nBuild it with `cmake -B build && cmake --build build && cmake --install build` \
+        and run the tests with `ctest --test-dir build`."
+    );
 
     Ok(())
 }
@@ -83,7 +104,7 @@ fn create_cmakefile(root: PathBuf, use_path_deps: bool) -> Result<(), eyre::ErrR
     Ok(())
 }
 
-fn create_custom_node(
+pub(super) fn create_custom_node(
     name: String,
     path: Option<PathBuf>,
     template_scripts: &str,
@@ -116,3 +137,42 @@ fn create_custom_node(
 
     Ok(())
 }
+
+fn create_operator(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrReport> {
+    if name.contains('/') {
+        bail!("operator name must not contain `/` separators");
+    }
+    if !name.is_ascii() {
+        bail!("operator name must be ASCII");
+    }
+
+    // create directories
+    let root = path.as_deref().unwrap_or_else(|| Path::new(&name));
+    fs::create_dir(root)
+        .with_context(|| format!("failed to create directory `{}`", root.display()))?;
+
+    let operator_path = root.join("operator.c");
+    fs::write(&operator_path, OPERATOR)
+        .with_context(|| format!("failed to write `{}`", operator_path.display()))?;
+    for (file, content) in [
+        ("operator_api.h", HEADER_OPERATOR_API),
+        ("operator_types.h", HEADER_OPERATOR_TYPES),
+    ] {
+        let header_path = root.join(file);
+        fs::write(&header_path, content)
+            .with_context(|| format!("failed to write `{}`", header_path.display()))?;
+    }
+
+    println!(
+        "Created new C operator `{name}` at {}",
+        Path::new(".").join(root).display()
+    );
+
+    Ok(())
+}
+
+fn print_snippet(io: &NodeIo, name: &str) {
+    // compiled manually, e.g. through the CMake file of a generated dataflow
+    let snippet = io.descriptor_snippet(name, &format!("bin/{name}"), None);
+    println!("\nAdd the node to the `nodes` of your dataflow:\n\n{snippet}");
+}
@@ -1,14 +1,18 @@
 #include "operator_api.h"
-#include <assert.h>
-#include <string.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
+
+// counts the received inputs and reports the count on the `status` output
+typedef struct
+{
+    unsigned long received;
+} Context_t;
 
 DoraInitResult_t dora_init_operator(void)
 {
     // allocate memory for storing context across function calls (optional)
-    void *context = malloc(10);
-    // TODO initialize context memory
+    Context_t *context = calloc(1, sizeof(Context_t));
 
     DoraInitResult_t result = {.operator_context = context};
     return result;
@@ -27,38 +31,27 @@ OnEventResult_t dora_on_event(
     const SendOutput_t *send_output,
     void *operator_context)
 {
+    Context_t *context = (Context_t *)operator_context;
+    OnEventResult_t result = {.status = DORA_STATUS_CONTINUE};
+
     if (event->input != NULL)
     {
-        char id[event->input->id.len + 1];
-        memcpy(id, event->input->id.ptr, event->input->id.len);
-        id[event->input->id.len] = 0;
+        char *id = dora_read_input_id(event->input);
+        context->received += 1;
 
-        // example for matching on input name
-        if (strcmp(id, "foo") == 0)
+        char status[128];
+        int len = snprintf(status, sizeof(status), "received %lu inputs, last one was `%s`", context->received, id);
+        if (len >= (int)sizeof(status))
         {
-            char *out_id = "bar";
-            char *out_id_heap = strdup(out_id);
-
-            int data_alloc_size = 10;
-            void *out_data = malloc(data_alloc_size);
-            // TODO initialize out_data
-
-            Output_t output = {.id = {
-                                   .ptr = (uint8_t *)out_id_heap,
-                                   .len = strlen(out_id_heap),
-                                   .cap = strlen(out_id_heap) + 1,
-                               },
-                               .data = {.ptr = (uint8_t *)out_data, .len = strlen(out_data), .cap = data_alloc_size}};
-            DoraResult_t res = (send_output->send_output.call)(send_output->send_output.env_ptr, output);
-
-            OnEventResult_t result = {.result = res, .status = DORA_STATUS_CONTINUE};
-            return result;
+            len = sizeof(status) - 1;
         }
+        result.result = dora_send_operator_output(send_output, "status", (uint8_t *)status, len);
+
+        dora_free_input_id(id);
     }
     if (event->stop)
     {
         printf("C operator received stop event\n");
     }
-    OnEventResult_t result = {.status = DORA_STATUS_CONTINUE};
     return result;
 }
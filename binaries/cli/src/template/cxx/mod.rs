@@ -1,3 +1,4 @@
+use dora_core::node_template::NodeIo;
 use eyre::{bail, Context, ContextCompat};
 use std::{
     fs,
@@ -7,6 +8,7 @@ use std::{
 const NODE: &str = include_str!("node-template.cc");
 const TALKER: &str = include_str!("talker-template.cc");
 const LISTENER: &str = include_str!("listener-template.cc");
+const OPERATOR: &str = include_str!("operator-template.cc");
 
 pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()> {
     let crate::CommandNew {
@@ -14,10 +16,27 @@ pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()>
         lang: _,
         name,
         path,
+        io,
     } = args;
+    let io = NodeIo::from(io);
 
     match kind {
+        crate::Kind::CustomNode if !io.is_empty() => {
+            create_custom_node(name.clone(), path, &io.cxx_node())?;
+            print_snippet(&io, &name);
+            Ok(())
+        }
         crate::Kind::CustomNode => create_custom_node(name, path, NODE),
+        crate::Kind::Operator => {
+            create_operator(name.clone(), path)?;
+            println!(
+                "\nCompile it to a shared library that links `dora_operator_api_c`. \
+                The `dora-operator.hpp` header is in the `apis/c++/operator` \
+                directory of the dora repository."
+            );
+            super::print_operator_snippet(&name, "shared-library", &format!("bin/{name}"), None);
+            Ok(())
+        }
         crate::Kind::Dataflow => create_dataflow(name, path, use_path_deps),
     }
 }
@@ -49,12 +68,17 @@ fn create_dataflow(
     create_custom_node("talker_1".into(), Some(root.join("talker_1")), TALKER)?;
     create_custom_node("talker_2".into(), Some(root.join("talker_2")), TALKER)?;
     create_custom_node("listener_1".into(), Some(root.join("listener_1")), LISTENER)?;
+    create_operator("counter".into(), Some(root.join("counter")))?;
     create_cmakefile(root.to_path_buf(), use_path_deps)?;
 
     println!(
         "Created new C++ dataflow at `{name}` at {}",
         Path::new(".").join(root).display()
     );
+    println!(
+        "\nBuild it with `cmake -B build && cmake --build build && cmake --install build` \
+        and run the tests with `ctest --test-dir build`."
+    );
 
     Ok(())
 }
@@ -82,7 +106,7 @@ fn create_cmakefile(root: PathBuf, use_path_deps: bool) -> Result<(), eyre::ErrR
     Ok(())
 }
 
-fn create_custom_node(
+pub(super) fn create_custom_node(
     name: String,
     path: Option<PathBuf>,
     template_scripts: &str,
@@ -112,3 +136,36 @@ fn create_custom_node(
 
     Ok(())
 }
+
+/// The operator headers are copied from the dora repository by the CMake file
+/// of the generated dataflow.
+fn create_operator(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrReport> {
+    if name.contains('/') {
+        bail!("operator name must not contain `/` separators");
+    }
+    if !name.is_ascii() {
+        bail!("operator name must be ASCII");
+    }
+
+    // create directories
+    let root = path.as_deref().unwrap_or_else(|| Path::new(&name));
+    fs::create_dir(root)
+        .with_context(|| format!("failed to create directory `{}`", root.display()))?;
+
+    let operator_path = root.join("operator.cc");
+    fs::write(&operator_path, OPERATOR)
+        .with_context(|| format!("failed to write `{}`", operator_path.display()))?;
+
+    println!(
+        "Created new C++ operator `{name}` at {}",
+        Path::new(".").join(root).display()
+    );
+
+    Ok(())
+}
+
+fn print_snippet(io: &NodeIo, name: &str) {
+    // compiled manually, e.g. through the CMake file of a generated dataflow
+    let snippet = io.descriptor_snippet(name, &format!("bin/{name}"), None);
+    println!("\nAdd the node to the `nodes` of your dataflow:\n\n{snippet}");
+}
@@ -1,23 +1,21 @@
-#include "operator.h"
-#include <iostream>
-#include <vector>
-#include "../build/dora-operator-api.h"
+#include "dora-operator.hpp"
 
-Operator::Operator() {}
+#include <string>
 
-std::unique_ptr<Operator> new_operator()
+// counts the received inputs and reports the count on the `status` output
+class ExampleOperator : public dora::Operator
 {
-    return std::make_unique<Operator>();
-}
+public:
+    dora::Status on_input(const dora::Input &input, dora::OutputSender &output) override
+    {
+        received_ += 1;
+        std::string status = "received " + std::to_string(received_) + " inputs, last one was `" + std::string(input.id()) + "`";
+        output.send("status", reinterpret_cast<const uint8_t *>(status.data()), status.size());
+        return dora::Status::Continue;
+    }
 
-DoraOnInputResult on_input(Operator &op, rust::Str id, rust::Slice<const uint8_t> data, OutputSender &output_sender)
-{
-    op.counter += 1;
-    std::cout << "Rust API operator received input `" << id.data() << "` with data `" << (unsigned int)data[0] << "` (internal counter: " << (unsigned int)op.counter << ")" << std::endl;
+private:
+    unsigned long received_ = 0;
+};
 
-    std::vector<unsigned char> out_vec{op.counter};
-    rust::Slice<const uint8_t> out_slice{out_vec.data(), out_vec.size()};
-    auto send_result = send_output(output_sender, rust::Str("status"), out_slice);
-    DoraOnInputResult result = {send_result.error, false};
-    return result;
-}
+DORA_REGISTER_OPERATOR(ExampleOperator)
@@ -0,0 +1,19 @@
+use dora_core::node_template::{parse_input, NodeIo};
+
+/// Inputs and outputs of a generated custom node.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct IoArgs {
+    /// Input of the node as `ID=SOURCE`, e.g. `tick=dora/timer/millis/100`
+    #[clap(long = "input", value_name = "ID=SOURCE", value_parser = parse_input)]
+    pub inputs: Vec<(String, String)>,
+    /// Output ID of the node
+    #[clap(long = "output", value_name = "ID")]
+    pub outputs: Vec<String>,
+}
+
+impl From<IoArgs> for NodeIo {
+    fn from(args: IoArgs) -> Self {
+        let IoArgs { inputs, outputs } = args;
+        NodeIo { inputs, outputs }
+    }
+}
@@ -1,9 +1,20 @@
 mod c;
 mod cxx;
+mod io;
 mod python;
 mod rust;
+mod wizard;
+
+pub use io::IoArgs;
+pub use wizard::init_interactive;
+
+use std::fmt::Write;
 
 pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()> {
+    let has_io = !args.io.inputs.is_empty() || !args.io.outputs.is_empty();
+    if args.kind != crate::Kind::CustomNode && has_io {
+        eyre::bail!("`--input` and `--output` are only supported for custom nodes");
+    }
     match args.lang {
         crate::Lang::Rust => rust::create(args, use_path_deps),
         crate::Lang::Python => python::create(args),
@@ -11,3 +22,18 @@ pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()>
         crate::Lang::Cxx => cxx::create(args, use_path_deps),
     }
 }
+
+/// Prints the descriptor entry for a generated operator.
+///
+/// The `source_kind` is the operator source field, e.g. `python` or
+/// `shared-library`. The generated operators send on a `status` output.
+fn print_operator_snippet(id: &str, source_kind: &str, source: &str, build: Option<&str>) {
+    let mut snippet = format!("  - id: {id}\n    operator:\n");
+    if let Some(build) = build {
+        writeln!(snippet, "      build: {build}").unwrap();
+    }
+    writeln!(snippet, "      {source_kind}: {source}").unwrap();
+    snippet.push_str("      inputs:\n        tick: dora/timer/millis/100\n");
+    snippet.push_str("      outputs:\n        - status\n");
+    println!("\nAdd the operator to the `nodes` of your dataflow:\n\n{snippet}");
+}
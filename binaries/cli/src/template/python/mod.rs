@@ -1,3 +1,4 @@
+use dora_core::node_template::NodeIo;
 use eyre::{bail, Context};
 use std::{
     fs,
@@ -7,6 +8,10 @@ use std::{
 const NODE_PY: &str = include_str!("node/node-template.py");
 const TALKER_PY: &str = include_str!("talker/talker-template.py");
 const LISTENER_PY: &str = include_str!("listener/listener-template.py");
+const OPERATOR_PY: &str = include_str!("operator/operator-template.py");
+const OPERATOR_TEST_PY: &str = include_str!("operator/test-template.py");
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub fn create(args: crate::CommandNew) -> eyre::Result<()> {
     let crate::CommandNew {
@@ -14,15 +19,27 @@ pub fn create(args: crate::CommandNew) -> eyre::Result<()> {
         lang: _,
         name,
         path,
+        io,
     } = args;
+    let io = NodeIo::from(io);
 
     match kind {
+        crate::Kind::CustomNode if !io.is_empty() => {
+            create_custom_node(name.clone(), path, &io.python_node())?;
+            print_snippet(&io, &name);
+            Ok(())
+        }
         crate::Kind::CustomNode => create_custom_node(name, path, NODE_PY),
+        crate::Kind::Operator => {
+            create_operator(name.clone(), path)?;
+            super::print_operator_snippet(&name, "python", &format!("{name}/{name}.py"), None);
+            Ok(())
+        }
         crate::Kind::Dataflow => create_dataflow(name, path),
     }
 }
 
-fn create_custom_node(
+pub(super) fn create_custom_node(
     name: String,
     path: Option<PathBuf>,
     template_scripts: &str,
@@ -44,8 +61,35 @@ fn create_custom_node(
     Ok(())
 }
 
+/// Creates the operator script together with a `pytest` test for it.
+fn create_operator(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrReport> {
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("operator name must be a valid Python module name");
+    }
+
+    // create directories
+    let root = path.as_deref().unwrap_or_else(|| Path::new(&name));
+    fs::create_dir(root)
+        .with_context(|| format!("failed to create directory `{}`", root.display()))?;
+
+    let operator_path = root.join(format!("{name}.py"));
+    fs::write(&operator_path, OPERATOR_PY)
+        .with_context(|| format!("failed to write `{}`", operator_path.display()))?;
+    let test_path = root.join(format!("test_{name}.py"));
+    fs::write(&test_path, OPERATOR_TEST_PY.replace("___name___", &name))
+        .with_context(|| format!("failed to write `{}`", test_path.display()))?;
+
+    println!(
+        "Created new Python operator `{name}` at {}",
+        Path::new(".").join(root).display()
+    );
+
+    Ok(())
+}
+
 fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrReport> {
     const DATAFLOW_YML: &str = include_str!("dataflow-template.yml");
+    const REQUIREMENTS_TXT: &str = include_str!("requirements-template.txt");
 
     if name.contains('/') {
         bail!("dataflow name must not contain `/` separators");
@@ -63,6 +107,12 @@ fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrR
     let dataflow_yml_path = root.join("dataflow.yml");
     fs::write(&dataflow_yml_path, dataflow_yml)
         .with_context(|| format!("failed to write `{}`", dataflow_yml_path.display()))?;
+    let requirements_path = root.join("requirements.txt");
+    fs::write(
+        &requirements_path,
+        REQUIREMENTS_TXT.replace("___version___", VERSION),
+    )
+    .with_context(|| format!("failed to write `{}`", requirements_path.display()))?;
 
     create_custom_node("talker_1".into(), Some(root.join("talker_1")), TALKER_PY)?;
     create_custom_node("talker_2".into(), Some(root.join("talker_2")), TALKER_PY)?;
@@ -71,11 +121,21 @@ fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrR
         Some(root.join("listener_1")),
         LISTENER_PY,
     )?;
+    create_operator("counter".into(), Some(root.join("counter")))?;
 
     println!(
         "Created new yaml dataflow `{name}` at {}",
         Path::new(".").join(root).display()
     );
+    println!(
+        "\nInstall the dependencies with `pip install -r requirements.txt` \
+        and run the tests with `pytest`."
+    );
 
     Ok(())
 }
+
+fn print_snippet(io: &NodeIo, name: &str) {
+    let snippet = io.descriptor_snippet(name, &format!("{name}/{name}.py"), None);
+    println!("\nAdd the node to the `nodes` of your dataflow:\n\n{snippet}");
+}
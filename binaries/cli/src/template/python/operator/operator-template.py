@@ -1,14 +1,15 @@
 from dora import DoraStatus
+import pyarrow as pa
 
 
 class Operator:
     """
-    Template docstring
+    Counts the received inputs and reports the count on the `status` output.
     """
 
     def __init__(self):
         """Called on initialisation"""
-        pass
+        self.received = 0
 
     def on_event(
         self,
@@ -31,12 +32,22 @@ class Operator:
                 CONTINUE means that the operator will
                     keep listening for further inputs.
                 STOP means that the operator stop listening for inputs.
+                STOP_ALL stops the whole dataflow.
+            Returning `None` is the same as CONTINUE. Returning a dict sends
+            its values as outputs, keyed by output ID, and then continues.
+
+        This method can also be defined as `async def`. The returned coroutine
+        is then awaited on an asyncio event loop of the operator thread.
 
+        Alternatively, inputs can be handled in a separate `on_input` method
+        with the same signature. `on_event` is then optional and only receives
+        the other events, e.g. `INPUT_CLOSED` or `STOP`, which is useful to
+        flush buffered data when upstream nodes finish.
         """
         if dora_event["type"] == "INPUT":
-            print(
-                f"Received input {dora_event['id']}, with data: {dora_event['value']}"
-            )
+            self.received += 1
+            status = f"received {self.received} inputs, last one was `{dora_event['id']}`"
+            send_output("status", pa.array([status]), dora_event["metadata"])
 
         return DoraStatus.CONTINUE
 
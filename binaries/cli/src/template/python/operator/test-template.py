@@ -0,0 +1,24 @@
+import pyarrow as pa
+from dora import DoraStatus
+
+from ___name___ import Operator
+
+
+def test_counts_inputs():
+    sent = []
+    operator = Operator()
+
+    for _ in range(2):
+        event = {
+            "type": "INPUT",
+            "id": "tick",
+            "value": pa.array([]),
+            "metadata": {},
+        }
+        status = operator.on_event(
+            event, lambda id, data, metadata: sent.append((id, data))
+        )
+        assert status == DoraStatus.CONTINUE
+
+    assert [id for id, _ in sent] == ["status", "status"]
+    assert sent[-1][1][0].as_py() == "received 2 inputs, last one was `tick`"
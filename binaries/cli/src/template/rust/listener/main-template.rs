@@ -11,7 +11,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 metadata,
                 data,
             } => match id.as_str() {
-                "speech" => {
+                "speech-1" | "speech-2" | "status" => {
                     let message: &str = (&data).try_into()?;
                     println!("I heard: {message} from {id}");
                 }
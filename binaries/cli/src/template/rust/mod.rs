@@ -1,3 +1,4 @@
+use dora_core::node_template::NodeIo;
 use eyre::{bail, Context};
 use std::{
     fs,
@@ -7,6 +8,7 @@ use std::{
 const MAIN_RS: &str = include_str!("node/main-template.rs");
 const TALKER_RS: &str = include_str!("talker/main-template.rs");
 const LISTENER_RS: &str = include_str!("listener/main-template.rs");
+const OPERATOR_RS: &str = include_str!("operator/lib-template.rs");
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()> {
@@ -15,10 +17,24 @@ pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()>
         lang: _,
         name,
         path,
+        io,
     } = args;
+    let io = NodeIo::from(io);
 
     match kind {
+        crate::Kind::CustomNode if !io.is_empty() => {
+            create_custom_node(name.clone(), path, use_path_deps, &io.rust_node())?;
+            print_snippet(&io, &name);
+            Ok(())
+        }
         crate::Kind::CustomNode => create_custom_node(name, path, use_path_deps, MAIN_RS),
+        crate::Kind::Operator => {
+            create_operator(name.clone(), path, use_path_deps)?;
+            let build = format!("cargo build --manifest-path {name}/Cargo.toml");
+            let source = format!("{name}/target/debug/{name}");
+            super::print_operator_snippet(&name, "shared-library", &source, Some(&build));
+            Ok(())
+        }
         crate::Kind::Dataflow => create_dataflow(name, path, use_path_deps),
     }
 }
@@ -70,16 +86,18 @@ fn create_dataflow(
         use_path_deps,
         LISTENER_RS,
     )?;
+    create_operator("counter".into(), Some(root.join("counter")), use_path_deps)?;
 
     println!(
         "Created new Rust dataflow at `{name}` at {}",
         Path::new(".").join(root).display()
     );
+    println!("\nBuild it with `dora build dataflow.yml` and run the tests with `cargo test`.");
 
     Ok(())
 }
 
-fn create_custom_node(
+pub(super) fn create_custom_node(
     name: String,
     path: Option<PathBuf>,
     use_path_deps: bool,
@@ -125,3 +143,55 @@ fn create_custom_node(
 
     Ok(())
 }
+
+fn create_operator(
+    name: String,
+    path: Option<PathBuf>,
+    use_path_deps: bool,
+) -> Result<(), eyre::ErrReport> {
+    const CARGO_TOML: &str = include_str!("operator/Cargo-template.toml");
+
+    if name.contains('/') {
+        bail!("operator name must not contain `/` separators");
+    }
+    if !name.is_ascii() {
+        bail!("operator name must be ASCII");
+    }
+
+    // create directories
+    let root = path.as_deref().unwrap_or_else(|| Path::new(&name));
+    fs::create_dir(root)
+        .with_context(|| format!("failed to create directory `{}`", root.display()))?;
+    let src = root.join("src");
+    fs::create_dir(&src)
+        .with_context(|| format!("failed to create directory `{}`", src.display()))?;
+
+    let dep = if use_path_deps {
+        r#"dora-operator-api = { path = "../../apis/rust/operator" }"#.to_string()
+    } else {
+        format!(r#"dora-operator-api = "{VERSION}""#)
+    };
+    let cargo_toml = CARGO_TOML
+        .replace("___name___", &name)
+        .replace("dora-operator-api = {}", &dep);
+    let cargo_toml_path = root.join("Cargo.toml");
+    fs::write(&cargo_toml_path, cargo_toml)
+        .with_context(|| format!("failed to write `{}`", cargo_toml_path.display()))?;
+
+    let lib_rs_path = src.join("lib.rs");
+    fs::write(&lib_rs_path, OPERATOR_RS)
+        .with_context(|| format!("failed to write `{}`", lib_rs_path.display()))?;
+
+    println!(
+        "Created new Rust operator `{name}` at {}",
+        Path::new(".").join(root).display()
+    );
+
+    Ok(())
+}
+
+fn print_snippet(io: &NodeIo, name: &str) {
+    let build = format!("cargo build --manifest-path {name}/Cargo.toml");
+    let snippet = io.descriptor_snippet(name, &format!("{name}/target/debug/{name}"), Some(&build));
+    println!("\nAdd the node to the `nodes` of your dataflow:\n\n{snippet}");
+}
@@ -1,10 +1,20 @@
-use dora_operator_api::{register_operator, DoraOperator, DoraOutputSender, DoraStatus, Event};
+use dora_operator_api::{
+    register_operator, DoraOperator, DoraOutputSender, DoraStatus, Event, IntoArrow,
+};
 
 register_operator!(ExampleOperator);
 
+/// Counts the received inputs and reports the count on the `status` output.
 #[derive(Debug, Default)]
 struct ExampleOperator {
-    example_field: u32,
+    received: u64,
+}
+
+impl ExampleOperator {
+    fn handle_input(&mut self, id: &str) -> String {
+        self.received += 1;
+        format!("received {} inputs, last one was `{id}`", self.received)
+    }
 }
 
 impl DoraOperator for ExampleOperator {
@@ -14,12 +24,29 @@ impl DoraOperator for ExampleOperator {
         output_sender: &mut DoraOutputSender,
     ) -> Result<DoraStatus, String> {
         match event {
-            Event::Input { id, data } => match id {
-                other => eprintln!("Received input {other}"),
-            },
+            Event::Input { id, data: _ } => {
+                let status = self.handle_input(id);
+                output_sender.send("status".into(), status.into_arrow())?;
+            }
+            Event::InputClosed { id } => eprintln!("input `{id}` was closed"),
             _ => {}
         }
 
         Ok(DoraStatus::Continue)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ExampleOperator;
+
+    #[test]
+    fn counts_inputs() {
+        let mut operator = ExampleOperator::default();
+        operator.handle_input("tick");
+        assert_eq!(
+            operator.handle_input("tick"),
+            "received 2 inputs, last one was `tick`"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Interactive creation of a dataflow, see `dora init --interactive`.
+//!
+//! The user is asked for the nodes of the dataflow, their languages and
+//! outputs, and then for the inputs of each node. From the answers, the
+//! descriptor and a scaffold of each node are generated through the same
+//! templates as `dora new --kind custom-node --input ... --output ...`.
+
+use dora_core::{descriptor::Descriptor, node_template::NodeIo};
+use eyre::{bail, Context};
+use inquire::{validator::Validation, Confirm, MultiSelect, Select, Text};
+use std::{fs, path::Path};
+
+const TIMER: &str = "timer";
+const LANGUAGES: [&str; 4] = ["Python", "Rust", "C", "C++"];
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+struct WizardNode {
+    id: String,
+    lang: crate::Lang,
+    io: NodeIo,
+}
+
+pub fn init_interactive(name: Option<String>, use_path_deps: bool) -> eyre::Result<()> {
+    let name = Text::new("Name of the dataflow:")
+        .with_default(name.as_deref().unwrap_or("dataflow"))
+        .with_validator(|name: &str| {
+            Ok(
+                if name.is_empty() || name.contains('/') || !name.is_ascii() {
+                    Validation::Invalid("must be a non-empty ASCII name without `/`".into())
+                } else {
+                    Validation::Valid
+                },
+            )
+        })
+        .prompt()?;
+    let root = Path::new(&name);
+    if root.exists() {
+        bail!("`{}` exists already", root.display());
+    }
+
+    let mut nodes = Vec::new();
+    loop {
+        let used: Vec<String> = nodes.iter().map(|n: &WizardNode| n.id.clone()).collect();
+        let id = Text::new("ID of the node:")
+            .with_validator(move |id: &str| {
+                Ok(if !is_valid_id(id) {
+                    Validation::Invalid(
+                        "must consist of ASCII letters, digits, `-`, and `_`".into(),
+                    )
+                } else if used.iter().any(|u| u == id) {
+                    Validation::Invalid("a node with this ID exists already".into())
+                } else {
+                    Validation::Valid
+                })
+            })
+            .prompt()?;
+        let lang = match Select::new("Language:", LANGUAGES.to_vec()).prompt()? {
+            "Rust" => crate::Lang::Rust,
+            "C" => crate::Lang::C,
+            "C++" => crate::Lang::Cxx,
+            _ => crate::Lang::Python,
+        };
+        let outputs = Text::new("Outputs (comma-separated, may be empty):")
+            .with_validator(|outputs: &str| {
+                Ok(match split_list(outputs).all(is_valid_id) {
+                    true => Validation::Valid,
+                    false => Validation::Invalid(
+                        "output IDs must consist of ASCII letters, digits, `-`, and `_`".into(),
+                    ),
+                })
+            })
+            .prompt()?;
+        let mut unique_outputs: Vec<String> = Vec::new();
+        for output in split_list(&outputs) {
+            if !unique_outputs.iter().any(|o| o == output) {
+                unique_outputs.push(output.to_owned());
+            }
+        }
+        nodes.push(WizardNode {
+            id,
+            lang,
+            io: NodeIo {
+                inputs: Vec::new(),
+                outputs: unique_outputs,
+            },
+        });
+        if !Confirm::new("Add another node?")
+            .with_default(nodes.len() < 2)
+            .prompt()?
+        {
+            break;
+        }
+    }
+
+    for index in 0..nodes.len() {
+        let id = nodes[index].id.clone();
+        let mut sources = vec![TIMER.to_owned()];
+        for node in nodes.iter().filter(|n| n.id != id) {
+            sources.extend(node.io.outputs.iter().map(|o| format!("{}/{o}", node.id)));
+        }
+        let selected = MultiSelect::new(&format!("Inputs of `{id}`:"), sources).prompt()?;
+        let mut inputs: Vec<(String, String)> = Vec::new();
+        for source in selected {
+            let (input_id, source) = if source == TIMER {
+                let millis =
+                    Text::new(&format!("Interval of the timer of `{id}` in milliseconds:"))
+                        .with_default("100")
+                        .with_validator(|millis: &str| {
+                            Ok(match millis.parse::<u64>() {
+                                Ok(millis) if millis > 0 => Validation::Valid,
+                                _ => Validation::Invalid("expected a positive number".into()),
+                            })
+                        })
+                        .prompt()?;
+                ("tick".to_owned(), format!("dora/timer/millis/{millis}"))
+            } else {
+                let (node, output) = source.split_once('/').unwrap_or_default();
+                let input_id = if inputs.iter().any(|(i, _)| i == output) {
+                    format!("{node}_{output}")
+                } else {
+                    output.to_owned()
+                };
+                (input_id, source)
+            };
+            inputs.push((input_id, source));
+        }
+        nodes[index].io.inputs = inputs;
+    }
+
+    let mut dataflow_yml = String::from("nodes:\n");
+    for node in &nodes {
+        let id = &node.id;
+        let snippet = match node.lang {
+            crate::Lang::Python => node
+                .io
+                .descriptor_snippet(id, &format!("{id}/{id}.py"), None),
+            crate::Lang::Rust => node.io.descriptor_snippet(
+                id,
+                &format!("{id}/target/debug/{id}"),
+                Some(&format!("cargo build --manifest-path {id}/Cargo.toml")),
+            ),
+            _ => node.io.descriptor_snippet(id, &format!("bin/{id}"), None),
+        };
+        dataflow_yml.push_str(&snippet);
+    }
+    // catch mistakes of the generated YAML before creating any files
+    let descriptor = Descriptor::parse(dataflow_yml.clone().into_bytes())?;
+    descriptor.resolve_aliases_and_set_defaults()?;
+
+    fs::create_dir(root)
+        .with_context(|| format!("failed to create directory `{}`", root.display()))?;
+    let dataflow_yml_path = root.join("dataflow.yml");
+    fs::write(&dataflow_yml_path, dataflow_yml)
+        .with_context(|| format!("failed to write `{}`", dataflow_yml_path.display()))?;
+    for node in &nodes {
+        let path = Some(root.join(&node.id));
+        match node.lang {
+            crate::Lang::Python => {
+                super::python::create_custom_node(node.id.clone(), path, &node.io.python_node())?
+            }
+            crate::Lang::Rust => super::rust::create_custom_node(
+                node.id.clone(),
+                path,
+                use_path_deps,
+                &node.io.rust_node(),
+            )?,
+            crate::Lang::C => {
+                super::c::create_custom_node(node.id.clone(), path, &node.io.c_node())?
+            }
+            crate::Lang::Cxx => {
+                super::cxx::create_custom_node(node.id.clone(), path, &node.io.cxx_node())?
+            }
+        }
+    }
+    let uses_python = nodes.iter().any(|n| n.lang == crate::Lang::Python);
+    if uses_python {
+        let requirements_path = root.join("requirements.txt");
+        let requirements = include_str!("python/requirements-template.txt");
+        fs::write(
+            &requirements_path,
+            requirements.replace("___version___", VERSION),
+        )
+        .with_context(|| format!("failed to write `{}`", requirements_path.display()))?;
+    }
+
+    println!(
+        "Created new dataflow `{name}` at {}",
+        Path::new(".").join(root).display()
+    );
+    if nodes.iter().any(|n| n.lang == crate::Lang::C) {
+        println!(
+            "\nCompile the C nodes to `bin/<NODE_ID>`, linking `dora_node_api_c`, \
+            before starting the dataflow."
+        );
+    }
+    if nodes.iter().any(|n| n.lang == crate::Lang::Cxx) {
+        println!(
+            "\nCompile the C++ nodes to `bin/<NODE_ID>`, linking `dora_node_api_cxx`, \
+            before starting the dataflow."
+        );
+    }
+    if nodes
+        .iter()
+        .any(|n| matches!(n.lang, crate::Lang::C | crate::Lang::Cxx))
+    {
+        // the compiled nodes don't exist yet, so they can't be checked
+    } else if nodes.iter().any(|n| n.lang == crate::Lang::Rust) {
+        println!("\nBuild the Rust nodes with `dora build dataflow.yml`.");
+    } else {
+        // all sources exist already, so the dataflow can be checked completely
+        descriptor.check(root)?;
+    }
+    if uses_python {
+        println!("\nInstall the Python dependencies with `pip install -r requirements.txt`.");
+    }
+    println!("Start the dataflow with `dora start dataflow.yml`.");
+    Ok(())
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
@@ -0,0 +1,367 @@
+//! Interactive view of the nodes of the running dataflows, similar to `htop`.
+//!
+//! The CPU and memory usage is sampled by the daemons whenever the statistics
+//! are requested. Message rates are computed from the difference between two
+//! consecutive refreshes.
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    queue,
+    style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use dora_core::{
+    config::NodeId,
+    daemon_messages::{EdgeStats, NodeStats},
+    topics::{ControlRequest, ControlRequestReply, DataflowId},
+};
+use eyre::{bail, Context};
+use uuid::Uuid;
+
+use crate::{formatting::format_bytes, query_running_dataflows};
+
+pub fn run(
+    session: &mut TcpRequestReplyConnection,
+    dataflow: Option<DataflowId>,
+    interval: Duration,
+) -> eyre::Result<()> {
+    let _terminal = RawTerminal::enter()?;
+    let mut sort = SortColumn::Cpu;
+    let mut previous: Option<Snapshot> = None;
+
+    loop {
+        let current = Snapshot::query(session, dataflow.as_ref())?;
+        let mut rows = current.rows(previous.as_ref());
+        sort.sort(&mut rows);
+        render(&rows, &current, sort)?;
+        previous = Some(current);
+
+        // handle key presses until the next refresh is due
+        let next_refresh = Instant::now() + interval;
+        loop {
+            let timeout = next_refresh.saturating_duration_since(Instant::now());
+            if !event::poll(timeout)? {
+                break;
+            }
+            if let Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) = event::read()?
+            {
+                match code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Char(c) => {
+                        if let Some(column) = SortColumn::from_key(c) {
+                            sort = column;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Switches the terminal to raw mode on an alternate screen and restores it
+/// on drop.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> eyre::Result<Self> {
+        terminal::enable_raw_mode().context("failed to enable raw terminal mode")?;
+        queue!(
+            std::io::stdout(),
+            terminal::EnterAlternateScreen,
+            cursor::Hide
+        )?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut stdout = std::io::stdout();
+        let _ = queue!(stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Snapshot {
+    dataflows: Vec<(DataflowId, BTreeMap<NodeId, NodeStats>)>,
+    time: Instant,
+}
+
+impl Snapshot {
+    fn query(
+        session: &mut TcpRequestReplyConnection,
+        filter: Option<&DataflowId>,
+    ) -> eyre::Result<Self> {
+        let active = query_running_dataflows(session)?.get_active();
+        let mut dataflows = Vec::new();
+        for id in active {
+            if filter.is_some_and(|filter| filter.uuid != id.uuid) {
+                continue;
+            }
+            let reply_raw = session
+                .request(
+                    &serde_json::to_vec(&ControlRequest::NodeStats {
+                        dataflow_uuid: id.uuid,
+                    })
+                    .unwrap(),
+                )
+                .wrap_err("failed to send node stats request")?;
+            let reply: ControlRequestReply =
+                serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+            match reply {
+                ControlRequestReply::NodeStats(stats) => dataflows.push((id, stats)),
+                // the dataflow might have finished in the meantime
+                ControlRequestReply::Error(_) => {}
+                other => bail!("unexpected node stats reply: {other:?}"),
+            }
+        }
+        Ok(Self {
+            dataflows,
+            time: Instant::now(),
+        })
+    }
+
+    fn get(&self, dataflow: Uuid, node: &NodeId) -> Option<&NodeStats> {
+        self.dataflows
+            .iter()
+            .find(|(id, _)| id.uuid == dataflow)
+            .and_then(|(_, stats)| stats.get(node))
+    }
+
+    fn rows(&self, previous: Option<&Snapshot>) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (dataflow, nodes) in &self.dataflows {
+            for (node, stats) in nodes {
+                let before = previous.and_then(|p| p.get(dataflow.uuid, node));
+                let rate = |current: u64, before: Option<u64>| {
+                    let elapsed = (self.time - previous?.time).as_secs_f64();
+                    Some(current.saturating_sub(before?) as f64 / elapsed)
+                };
+                let sum = |edges: &BTreeMap<_, EdgeStats>| {
+                    edges.values().fold((0, 0), |(messages, bytes), edge| {
+                        (messages + edge.messages, bytes + edge.bytes)
+                    })
+                };
+                let (inputs, _) = sum(&stats.inputs);
+                let (outputs, output_bytes) = sum(&stats.outputs);
+                let before_inputs = before.map(|b| sum(&b.inputs).0);
+                let before_outputs = before.map(|b| sum(&b.outputs));
+                rows.push(Row {
+                    dataflow: dataflow
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| dataflow.uuid.to_string()),
+                    node: node.to_string(),
+                    cpu: stats.cpu_usage,
+                    memory: stats.memory,
+                    input_rate: rate(inputs, before_inputs),
+                    output_rate: rate(outputs, before_outputs.map(|(m, _)| m)),
+                    output_bandwidth: rate(output_bytes, before_outputs.map(|(_, b)| b)),
+                    queue_depth: stats.queue_depth,
+                    dropped: stats.dropped_inputs,
+                });
+            }
+        }
+        rows
+    }
+}
+
+struct Row {
+    dataflow: String,
+    node: String,
+    cpu: Option<f32>,
+    memory: Option<u64>,
+    input_rate: Option<f64>,
+    output_rate: Option<f64>,
+    output_bandwidth: Option<f64>,
+    queue_depth: usize,
+    dropped: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortColumn {
+    Node,
+    Cpu,
+    Memory,
+    Rate,
+    Dropped,
+}
+
+impl SortColumn {
+    fn from_key(key: char) -> Option<Self> {
+        match key {
+            'n' => Some(Self::Node),
+            'c' => Some(Self::Cpu),
+            'm' => Some(Self::Memory),
+            'r' => Some(Self::Rate),
+            'd' => Some(Self::Dropped),
+            _ => None,
+        }
+    }
+
+    fn sort(self, rows: &mut [Row]) {
+        match self {
+            Self::Node => rows.sort_by(|a, b| (&a.dataflow, &a.node).cmp(&(&b.dataflow, &b.node))),
+            Self::Cpu => rows.sort_by(|a, b| b.cpu.unwrap_or(0.0).total_cmp(&a.cpu.unwrap_or(0.0))),
+            Self::Memory => rows.sort_by_key(|r| std::cmp::Reverse(r.memory)),
+            Self::Rate => rows.sort_by(|a, b| {
+                let total = |r: &Row| r.input_rate.unwrap_or(0.0) + r.output_rate.unwrap_or(0.0);
+                total(b).total_cmp(&total(a))
+            }),
+            Self::Dropped => rows.sort_by_key(|r| std::cmp::Reverse(r.dropped)),
+        }
+    }
+}
+
+const COLUMNS: [(&str, usize, Option<SortColumn>); 9] = [
+    ("DATAFLOW", 16, None),
+    ("NODE", 20, Some(SortColumn::Node)),
+    ("CPU%", 7, Some(SortColumn::Cpu)),
+    ("MEM", 10, Some(SortColumn::Memory)),
+    ("IN/s", 9, Some(SortColumn::Rate)),
+    ("OUT/s", 9, None),
+    ("OUT B/s", 10, None),
+    ("QUEUE", 6, None),
+    ("DROPPED", 8, Some(SortColumn::Dropped)),
+];
+
+fn render(rows: &[Row], snapshot: &Snapshot, sort: SortColumn) -> eyre::Result<()> {
+    let mut stdout = std::io::stdout();
+    let (width, height) = terminal::size()?;
+    let width = usize::from(width);
+    queue!(
+        stdout,
+        cursor::MoveTo(0, 0),
+        terminal::Clear(ClearType::All)
+    )?;
+
+    // summary header
+    let total_cpu: f32 = rows.iter().filter_map(|r| r.cpu).sum();
+    let total_memory: u64 = rows.iter().filter_map(|r| r.memory).sum();
+    let total_dropped: u64 = rows.iter().map(|r| r.dropped).sum();
+    let summary = [
+        ("Dataflows", snapshot.dataflows.len().to_string()),
+        ("Nodes", rows.len().to_string()),
+        ("CPU", format!("{total_cpu:.1}%")),
+        ("Mem", format_bytes(total_memory as f64)),
+        ("Dropped", total_dropped.to_string()),
+    ];
+    for (label, value) in summary {
+        queue!(
+            stdout,
+            SetForegroundColor(Color::Cyan),
+            Print(format!("{label}: ")),
+            SetAttribute(Attribute::Bold),
+            Print(format!("{value}  ")),
+            SetAttribute(Attribute::Reset),
+        )?;
+    }
+
+    // column header
+    let mut header = String::new();
+    for (name, column_width, column) in COLUMNS {
+        let marker = if column == Some(sort) { "▼" } else { "" };
+        header.push_str(&format!("{:<column_width$} ", format!("{name}{marker}")));
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(0, 2),
+        SetBackgroundColor(Color::Green),
+        SetForegroundColor(Color::Black),
+        Print(format!("{header:<width$}")),
+        SetAttribute(Attribute::Reset),
+    )?;
+
+    let visible_rows = usize::from(height).saturating_sub(4);
+    for (i, row) in rows.iter().take(visible_rows).enumerate() {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "-".into());
+        let cells = [
+            truncate(&row.dataflow, COLUMNS[0].1),
+            truncate(&row.node, COLUMNS[1].1),
+            optional(row.cpu.map(|c| format!("{c:.1}"))),
+            optional(row.memory.map(|m| format_bytes(m as f64))),
+            optional(row.input_rate.map(|r| format!("{r:.1}"))),
+            optional(row.output_rate.map(|r| format!("{r:.1}"))),
+            optional(row.output_bandwidth.map(format_bytes)),
+            row.queue_depth.to_string(),
+            row.dropped.to_string(),
+        ];
+        queue!(stdout, cursor::MoveTo(0, (i + 3) as u16))?;
+        for (index, (cell, (_, column_width, _))) in cells.iter().zip(COLUMNS).enumerate() {
+            let color = match index {
+                2 => match row.cpu.unwrap_or(0.0) {
+                    c if c >= 90.0 => Color::Red,
+                    c if c >= 50.0 => Color::Yellow,
+                    _ => Color::Green,
+                },
+                8 if row.dropped > 0 => Color::Red,
+                _ => Color::Reset,
+            };
+            queue!(
+                stdout,
+                SetForegroundColor(color),
+                Print(format!("{cell:<column_width$} ")),
+            )?;
+        }
+        queue!(stdout, SetAttribute(Attribute::Reset))?;
+    }
+    if rows.is_empty() {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, 3),
+            Print("No dataflows are running")
+        )?;
+    }
+
+    // key bindings footer
+    queue!(stdout, cursor::MoveTo(0, height.saturating_sub(1)))?;
+    for (key, action) in [
+        ("q", "Quit"),
+        ("c", "CPU"),
+        ("m", "Memory"),
+        ("r", "Rate"),
+        ("d", "Dropped"),
+        ("n", "Name"),
+    ] {
+        queue!(
+            stdout,
+            SetAttribute(Attribute::Bold),
+            Print(key),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(Color::Cyan),
+            SetForegroundColor(Color::Black),
+            Print(format!("{action:<8}")),
+            SetAttribute(Attribute::Reset),
+            Print(" "),
+        )?;
+    }
+
+    stdout.flush()?;
+    Ok(())
+}
+
+fn truncate(value: &str, width: usize) -> String {
+    if value.chars().count() <= width {
+        value.to_owned()
+    } else {
+        let mut truncated: String = value.chars().take(width - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
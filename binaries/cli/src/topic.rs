@@ -0,0 +1,379 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    config::{DataId, NodeId},
+    topics::{AttachedNode, ControlRequest, ControlRequestReply},
+};
+use dora_node_api::{
+    arrow::{
+        array::{Array, ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, UInt8Array},
+        datatypes::DataType,
+        util::display::{ArrayFormatter, FormatOptions},
+    },
+    DoraNode, Event, MetadataParameters,
+};
+use eyre::{bail, eyre, Context, Result};
+use uuid::Uuid;
+
+use crate::select_running_dataflow;
+
+#[derive(Debug, clap::Subcommand)]
+pub enum TopicCommand {
+    /// Print the messages of an output of a running dataflow.
+    ///
+    /// Attaches a temporary node to the dataflow that subscribes to the
+    /// output. The node connects to the daemon on this machine.
+    Echo {
+        /// Output to print, in `node/output` form
+        #[clap(value_name = "NODE/OUTPUT")]
+        output: String,
+        /// Identifier of the dataflow
+        #[clap(long, value_name = "UUID_OR_NAME")]
+        dataflow: Option<String>,
+        /// How to decode the messages
+        #[clap(long, value_name = "FORMAT", default_value = "auto")]
+        format: TopicFormat,
+        /// Exit after printing this number of messages
+        #[clap(long, short = 'n')]
+        count: Option<usize>,
+        /// Machine of the daemon on this machine, if it's not the default one
+        #[clap(long, value_name = "MACHINE_ID", default_value = "")]
+        machine: String,
+    },
+    /// Send messages to an input of a running dataflow.
+    ///
+    /// Attaches a temporary node to the dataflow that sends to the input, in
+    /// addition to the input's existing sources. The node connects to the
+    /// daemon on this machine.
+    Pub {
+        /// Input to send to, in `node/input` form
+        #[clap(value_name = "NODE/INPUT")]
+        input: String,
+        /// Data of the message, interpreted according to `--format`
+        #[clap(value_name = "DATA")]
+        data: String,
+        /// Identifier of the dataflow
+        #[clap(long, value_name = "UUID_OR_NAME")]
+        dataflow: Option<String>,
+        /// How to encode the data
+        #[clap(long, value_name = "FORMAT", default_value = "auto")]
+        format: TopicFormat,
+        /// Send the message repeatedly at this rate, in Hz, until ctrl-c is pressed
+        #[clap(long, short = 'r')]
+        rate: Option<f64>,
+        /// Number of messages to send (default: 1, or unlimited if `--rate` is set)
+        #[clap(long, short = 'n')]
+        count: Option<usize>,
+        /// Machine of the daemon on this machine, if it's not the default one
+        #[clap(long, value_name = "MACHINE_ID", default_value = "")]
+        machine: String,
+    },
+}
+
+/// Encoding of the messages of `dora topic`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TopicFormat {
+    /// Arrow values as they are; byte arrays as JSON or text if possible.
+    ///
+    /// For `pub`, JSON numbers, booleans, strings, and arrays of them are sent
+    /// as Arrow arrays, other data as a string.
+    Auto,
+    /// JSON-encoded byte array, as used by `typed::Encoding::Json`.
+    Json,
+    /// UTF-8 string.
+    Text,
+    /// Raw bytes in hexadecimal.
+    Hex,
+}
+
+pub fn run(command: TopicCommand, session: &mut TcpRequestReplyConnection) -> Result<()> {
+    match command {
+        TopicCommand::Echo {
+            output,
+            dataflow,
+            format,
+            count,
+            machine,
+        } => {
+            let (source, output) = parse_topic(&output, "node/output")?;
+            let dataflow = select_running_dataflow(session, dataflow, "echo")?.uuid;
+            let node = AttachedNode {
+                id: attached_node_id("echo"),
+                machine,
+                inputs: [(DataId::from("data".to_owned()), (source, output))].into(),
+                outputs: BTreeMap::new(),
+            };
+            with_attached_node(session, dataflow, node, |node_id, stop| {
+                echo(node_id, format, count, stop)
+            })
+        }
+        TopicCommand::Pub {
+            input,
+            data,
+            dataflow,
+            format,
+            rate,
+            count,
+            machine,
+        } => {
+            let target = parse_topic(&input, "node/input")?;
+            let data = encode(&data, format)?;
+            let interval = match rate {
+                Some(rate) if rate > 0.0 && rate.is_finite() => {
+                    Some(Duration::from_secs_f64(1.0 / rate))
+                }
+                Some(rate) => bail!("invalid rate `{rate}`: expected a positive number"),
+                None => None,
+            };
+            let count = match (count, interval) {
+                (Some(count), _) => Some(count),
+                (None, Some(_)) => None,
+                (None, None) => Some(1),
+            };
+            let dataflow = select_running_dataflow(session, dataflow, "publish to")?.uuid;
+            let output = DataId::from("data".to_owned());
+            let node = AttachedNode {
+                id: attached_node_id("pub"),
+                machine,
+                inputs: BTreeMap::new(),
+                outputs: [(output.clone(), BTreeSet::from([target]))].into(),
+            };
+            with_attached_node(session, dataflow, node, |node_id, stop| {
+                publish(node_id, output, data, interval, count, stop)
+            })
+        }
+    }
+}
+
+/// Attaches the node, runs `f`, and detaches the node again, also if `f`
+/// fails or ctrl-c is pressed.
+fn with_attached_node(
+    session: &mut TcpRequestReplyConnection,
+    dataflow: Uuid,
+    node: AttachedNode,
+    f: impl FnOnce(NodeId, &AtomicBool) -> Result<()>,
+) -> Result<()> {
+    let node_id = node.id.clone();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_handler = stop.clone();
+    ctrlc::set_handler(move || stop_handler.store(true, Ordering::Relaxed))
+        .wrap_err("failed to set ctrl-c handler")?;
+
+    control_request(
+        session,
+        ControlRequest::AttachNode {
+            dataflow_uuid: dataflow,
+            node,
+        },
+    )
+    .wrap_err("failed to attach node to dataflow")?;
+    let result = f(node_id.clone(), &stop);
+    let detached = control_request(
+        session,
+        ControlRequest::DetachNode {
+            dataflow_uuid: dataflow,
+            node_id,
+        },
+    )
+    .wrap_err("failed to detach node from dataflow");
+    result.and(detached)
+}
+
+fn control_request(session: &mut TcpRequestReplyConnection, request: ControlRequest) -> Result<()> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&request).unwrap())
+        .wrap_err("failed to send topic request")?;
+    let reply: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::NodeAttached { .. } | ControlRequestReply::NodeDetached { .. } => {
+            Ok(())
+        }
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected topic reply: {other:?}"),
+    }
+}
+
+fn echo(
+    node_id: NodeId,
+    format: TopicFormat,
+    count: Option<usize>,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let (_node, mut events) =
+        DoraNode::init_from_node_id(node_id).wrap_err("failed to connect to local daemon")?;
+    let mut received = 0;
+    while !stop.load(Ordering::Relaxed) && count.map_or(true, |count| received < count) {
+        // use a timeout to check for ctrl-c regularly
+        match events.recv_timeout(Duration::from_millis(200)) {
+            Some(Event::Input { data, .. }) => {
+                println!("{}", decode(&data, format)?);
+                println!("---");
+                received += 1;
+            }
+            Some(Event::InputClosed { .. } | Event::AllInputsClosed | Event::Stop) | None => break,
+            Some(Event::Error(err)) => tracing::trace!("{err}"),
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn publish(
+    node_id: NodeId,
+    output: DataId,
+    data: ArrayRef,
+    interval: Option<Duration>,
+    count: Option<usize>,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let (mut node, _events) =
+        DoraNode::init_from_node_id(node_id).wrap_err("failed to connect to local daemon")?;
+    let mut sent = 0;
+    let mut next = Instant::now();
+    while !stop.load(Ordering::Relaxed) && count.map_or(true, |count| sent < count) {
+        let now = Instant::now();
+        if now < next {
+            std::thread::sleep((next - now).min(Duration::from_millis(200)));
+            continue;
+        }
+        node.send_output(output.clone(), MetadataParameters::default(), data.clone())
+            .wrap_err("failed to send message")?;
+        sent += 1;
+        match interval {
+            Some(interval) => next += interval,
+            None => next = now,
+        }
+    }
+    println!("sent {sent} message(s)");
+    Ok(())
+}
+
+fn parse_topic(topic: &str, expected: &str) -> Result<(NodeId, DataId)> {
+    let (node, id) = topic
+        .split_once('/')
+        .ok_or_else(|| eyre!("invalid topic `{topic}`: expected `{expected}`"))?;
+    Ok((NodeId::from(node.to_owned()), DataId::from(id.to_owned())))
+}
+
+fn attached_node_id(kind: &str) -> NodeId {
+    NodeId::from(format!("dora-topic-{kind}-{}", Uuid::now_v7().simple()))
+}
+
+fn decode(data: &ArrayRef, format: TopicFormat) -> Result<String> {
+    let bytes = || {
+        data.as_any()
+            .downcast_ref::<UInt8Array>()
+            .map(|array| array.values().as_ref())
+            .ok_or_else(|| eyre!("expected a byte array, got {}", data.data_type()))
+    };
+    match format {
+        TopicFormat::Auto if data.data_type() == &DataType::UInt8 => {
+            let bytes = bytes()?;
+            if let Ok(value) = serde_json::from_slice::<serde_json::Value>(bytes) {
+                Ok(serde_json::to_string_pretty(&value)?)
+            } else if let Ok(text) = std::str::from_utf8(bytes) {
+                Ok(text.to_owned())
+            } else {
+                Ok(hex(bytes))
+            }
+        }
+        TopicFormat::Auto => {
+            let formatter = ArrayFormatter::try_new(data.as_ref(), &FormatOptions::default())?;
+            let values: Vec<_> = (0..data.len())
+                .map(|i| formatter.value(i).to_string())
+                .collect();
+            match values.as_slice() {
+                [value] => Ok(value.clone()),
+                values => Ok(format!("[{}]", values.join(", "))),
+            }
+        }
+        TopicFormat::Json => {
+            let value: serde_json::Value =
+                serde_json::from_slice(bytes()?).wrap_err("failed to decode JSON")?;
+            Ok(serde_json::to_string_pretty(&value)?)
+        }
+        TopicFormat::Text => match data.as_any().downcast_ref::<StringArray>() {
+            Some(array) => Ok(array.iter().flatten().collect::<Vec<_>>().join("\n")),
+            None => Ok(String::from_utf8_lossy(bytes()?).into_owned()),
+        },
+        TopicFormat::Hex => Ok(hex(bytes()?)),
+    }
+}
+
+fn encode(data: &str, format: TopicFormat) -> Result<ArrayRef> {
+    let array: ArrayRef = match format {
+        TopicFormat::Auto => match serde_json::from_str(data) {
+            Ok(value) => json_to_arrow(&value)
+                .unwrap_or_else(|| Arc::new(StringArray::from(vec![data.to_owned()]))),
+            Err(_) => Arc::new(StringArray::from(vec![data.to_owned()])),
+        },
+        TopicFormat::Json => {
+            let value: serde_json::Value =
+                serde_json::from_str(data).wrap_err("data is not valid JSON")?;
+            Arc::new(UInt8Array::from(serde_json::to_vec(&value)?))
+        }
+        TopicFormat::Text => Arc::new(StringArray::from(vec![data.to_owned()])),
+        TopicFormat::Hex => {
+            let digits: String = data.split_whitespace().collect();
+            if digits.len() % 2 != 0 {
+                bail!("hex data must have an even number of digits");
+            }
+            let bytes = (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .wrap_err("invalid hex data")?;
+            Arc::new(UInt8Array::from(bytes))
+        }
+    };
+    Ok(array)
+}
+
+/// Converts JSON primitives and arrays of them to an Arrow array.
+///
+/// Returns `None` for objects and mixed arrays.
+fn json_to_arrow(value: &serde_json::Value) -> Option<ArrayRef> {
+    use serde_json::Value;
+
+    let values = match value {
+        Value::Array(values) => values.as_slice(),
+        value => std::slice::from_ref(value),
+    };
+    let array: ArrayRef = if values.iter().all(|v| v.is_i64()) {
+        Arc::new(Int64Array::from_iter_values(
+            values.iter().filter_map(Value::as_i64),
+        ))
+    } else if values.iter().all(|v| v.is_number()) {
+        Arc::new(Float64Array::from_iter_values(
+            values.iter().filter_map(Value::as_f64),
+        ))
+    } else if values.iter().all(|v| v.is_boolean()) {
+        Arc::new(BooleanArray::from(
+            values.iter().filter_map(Value::as_bool).collect::<Vec<_>>(),
+        ))
+    } else if values.iter().all(|v| v.is_string()) {
+        Arc::new(StringArray::from_iter_values(
+            values.iter().filter_map(Value::as_str),
+        ))
+    } else {
+        return None;
+    };
+    Some(array)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .concat()
+}
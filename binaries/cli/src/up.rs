@@ -86,6 +86,7 @@ fn start_coordinator() -> eyre::Result<()> {
         Command::new(std::env::current_exe().wrap_err("failed to get current executable path")?);
     cmd.arg("coordinator");
     cmd.arg("--quiet");
+    detach_from_terminal(&mut cmd);
     cmd.spawn().wrap_err("failed to run `dora coordinator`")?;
 
     println!("started dora coordinator");
@@ -98,9 +99,19 @@ fn start_daemon() -> eyre::Result<()> {
         Command::new(std::env::current_exe().wrap_err("failed to get current executable path")?);
     cmd.arg("daemon");
     cmd.arg("--quiet");
+    detach_from_terminal(&mut cmd);
     cmd.spawn().wrap_err("failed to run `dora daemon`")?;
 
     println!("started dora daemon");
 
     Ok(())
 }
+
+/// Moves the spawned process into its own process group, so that it keeps
+/// running when ctrl-c is pressed while `dora up --watch` is in the foreground.
+fn detach_from_terminal(cmd: &mut Command) {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(cmd, 0);
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
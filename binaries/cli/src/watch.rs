@@ -0,0 +1,268 @@
+//! Edit-run loop of `dora up --watch`.
+//!
+//! The dataflow is started once and then updated whenever files in its
+//! directory change: the build commands run again and the nodes whose source
+//! or build artifact changed are restarted one after another, while the rest
+//! of the dataflow keeps running. Changes to the descriptor restart the whole
+//! dataflow.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    adjust_shared_library_path,
+    config::NodeId,
+    descriptor::{
+        lua_source_path, source_is_url, CoreNodeKind, Descriptor, OperatorSource, ResolvedNode,
+        DYNAMIC_SOURCE, SHELL_SOURCE,
+    },
+};
+use eyre::Context;
+use notify::{Config, Event as NotifyEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use uuid::Uuid;
+
+use crate::{
+    build::{self, BuildTargets, IGNORED_DIRS},
+    query_running_dataflows, restart_node, start_dataflow, stop_dataflow,
+};
+
+/// Changes within this duration after the first one are handled together,
+/// e.g. when an editor writes multiple files at once.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+enum WatchEvent {
+    Changed(Vec<PathBuf>),
+    Stop,
+}
+
+/// Starts the dataflow and updates it on changes until ctrl-c is pressed.
+pub fn watch(
+    dataflow_path: &Path,
+    name: Option<String>,
+    session: &mut TcpRequestReplyConnection,
+) -> eyre::Result<()> {
+    let dataflow_path = dataflow_path
+        .canonicalize()
+        .wrap_err("failed to canonicalize dataflow path")?;
+    let working_dir = dataflow_path
+        .parent()
+        .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
+        .to_owned();
+
+    let (tx, rx) = mpsc::channel();
+    let ctrlc_tx = tx.clone();
+    ctrlc::set_handler(move || {
+        let _ = ctrlc_tx.send(WatchEvent::Stop);
+    })
+    .wrap_err("failed to set ctrl-c handler")?;
+    let (mut uuid, mut node_files) = start(&dataflow_path, &working_dir, name.clone(), session)?;
+    let mut watcher = RecommendedWatcher::new(
+        move |event: notify::Result<NotifyEvent>| {
+            if let Ok(event) = event {
+                if !event.kind.is_access() {
+                    let _ = tx.send(WatchEvent::Changed(event.paths));
+                }
+            }
+        },
+        Config::default(),
+    )?;
+    watcher
+        .watch(&working_dir, RecursiveMode::Recursive)
+        .wrap_err_with(|| format!("failed to watch `{}`", working_dir.display()))?;
+    let mut watched: BTreeSet<PathBuf> = BTreeSet::new();
+    watch_outside(&mut watcher, &working_dir, &node_files, &mut watched);
+    println!("watching `{}` for changes", working_dir.display());
+
+    while let Ok(WatchEvent::Changed(mut changed)) = rx.recv() {
+        std::thread::sleep(DEBOUNCE);
+        for event in rx.try_iter() {
+            match event {
+                WatchEvent::Changed(paths) => changed.extend(paths),
+                WatchEvent::Stop => return stop_dataflow(uuid, None, session),
+            }
+        }
+        let changed: BTreeSet<_> = changed
+            .into_iter()
+            .filter(|path| {
+                node_files.values().flatten().any(|file| file == path)
+                    || !is_ignored(path, &working_dir)
+            })
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+
+        let running = query_running_dataflows(session)?
+            .get_active()
+            .iter()
+            .any(|id| id.uuid == uuid);
+        let result = if changed.contains(&dataflow_path) || !running {
+            println!("restarting dataflow");
+            if running {
+                if let Err(err) = stop_dataflow(uuid, None, session) {
+                    eprintln!("{err:?}");
+                }
+            }
+            start(&dataflow_path, &working_dir, name.clone(), session).map(|(new, files)| {
+                uuid = new;
+                node_files = files;
+            })
+        } else {
+            update(uuid, &dataflow_path, &node_files, &changed, session)
+        };
+        if let Err(err) = result {
+            eprintln!("{err:?}");
+        }
+        watch_outside(&mut watcher, &working_dir, &node_files, &mut watched);
+
+        // ignore the changes caused by builds, they were handled already
+        if rx.try_iter().any(|event| matches!(event, WatchEvent::Stop)) {
+            break;
+        }
+    }
+    stop_dataflow(uuid, None, session)
+}
+
+/// Starts the dataflow and returns its UUID and the files of its nodes.
+fn start(
+    dataflow_path: &Path,
+    working_dir: &Path,
+    name: Option<String>,
+    session: &mut TcpRequestReplyConnection,
+) -> eyre::Result<(Uuid, BTreeMap<NodeId, Vec<PathBuf>>)> {
+    let descriptor = Descriptor::blocking_read(dataflow_path)?;
+    descriptor
+        .check(working_dir)
+        .wrap_err("Could not validate yaml")?;
+    build::build(dataflow_path, None, false, &BuildTargets::default())?;
+    let node_files = descriptor
+        .resolve_aliases_and_set_defaults()?
+        .iter()
+        .map(|node| (node.id.clone(), node_files(node, working_dir)))
+        .collect();
+    let uuid = start_dataflow(descriptor, name, working_dir.to_owned(), session)?;
+    Ok((uuid, node_files))
+}
+
+/// Runs the build commands and restarts the nodes whose files changed.
+fn update(
+    uuid: Uuid,
+    dataflow_path: &Path,
+    node_files: &BTreeMap<NodeId, Vec<PathBuf>>,
+    changed: &BTreeSet<PathBuf>,
+    session: &mut TcpRequestReplyConnection,
+) -> eyre::Result<()> {
+    let before = modification_times(node_files);
+    build::build(dataflow_path, None, false, &BuildTargets::default())?;
+    let after = modification_times(node_files);
+
+    let affected = node_files.iter().filter(|(_, files)| {
+        files
+            .iter()
+            .any(|file| changed.contains(file) || before.get(file) != after.get(file))
+    });
+    for (node_id, _) in affected {
+        if let Err(err) = restart_node(uuid, node_id.clone(), None, session) {
+            eprintln!("failed to restart `{node_id}`: {err:?}");
+        }
+    }
+    Ok(())
+}
+
+/// Files that the node or its operators are loaded from.
+fn node_files(node: &ResolvedNode, working_dir: &Path) -> Vec<PathBuf> {
+    let mut sources = Vec::new();
+    match &node.kind {
+        CoreNodeKind::Custom(custom) => {
+            let source = custom.source.as_str();
+            if source != SHELL_SOURCE && source != DYNAMIC_SOURCE && !source_is_url(source) {
+                let path = Path::new(source);
+                let path = if path.extension().is_none() {
+                    path.with_extension(std::env::consts::EXE_EXTENSION)
+                } else {
+                    path.to_owned()
+                };
+                sources.push(path);
+            }
+        }
+        CoreNodeKind::Runtime(runtime) => {
+            for operator in &runtime.operators {
+                match &operator.config.source {
+                    OperatorSource::SharedLibrary(path) if !source_is_url(path) => {
+                        sources.extend(adjust_shared_library_path(Path::new(path)).ok());
+                    }
+                    OperatorSource::Python(python) if !source_is_url(&python.source) => {
+                        sources.push(PathBuf::from(&python.source));
+                    }
+                    OperatorSource::Wasm(path)
+                    | OperatorSource::Julia(path)
+                    | OperatorSource::Matlab(path)
+                        if !source_is_url(path) =>
+                    {
+                        sources.push(PathBuf::from(path));
+                    }
+                    OperatorSource::Lua(source) => {
+                        sources.extend(lua_source_path(source).map(PathBuf::from));
+                    }
+                    OperatorSource::Onnx(onnx) => sources.push(PathBuf::from(&onnx.model)),
+                    _ => {}
+                }
+            }
+        }
+    }
+    // notify reports canonical paths
+    sources
+        .into_iter()
+        .map(|path| {
+            let path = working_dir.join(path);
+            path.canonicalize().unwrap_or(path)
+        })
+        .collect()
+}
+
+/// Watches the directories of node files that are outside of the working
+/// directory, e.g. build artifacts in a workspace `target` directory.
+fn watch_outside(
+    watcher: &mut RecommendedWatcher,
+    working_dir: &Path,
+    node_files: &BTreeMap<NodeId, Vec<PathBuf>>,
+    watched: &mut BTreeSet<PathBuf>,
+) {
+    let dirs = node_files
+        .values()
+        .flatten()
+        .filter(|file| !file.starts_with(working_dir))
+        .filter_map(|file| file.parent());
+    for dir in dirs {
+        if !watched.contains(dir) && watcher.watch(dir, RecursiveMode::NonRecursive).is_ok() {
+            watched.insert(dir.to_owned());
+        }
+    }
+}
+
+/// Changes in hidden directories and build outputs are ignored, unless they
+/// affect a file that a node is loaded from.
+fn is_ignored(path: &Path, working_dir: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(working_dir) else {
+        return true;
+    };
+    relative.components().any(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        name.starts_with('.') || IGNORED_DIRS.contains(&name.as_ref())
+    })
+}
+
+fn modification_times(
+    node_files: &BTreeMap<NodeId, Vec<PathBuf>>,
+) -> BTreeMap<&PathBuf, SystemTime> {
+    node_files
+        .values()
+        .flatten()
+        .filter_map(|file| Some((file, file.metadata().ok()?.modified().ok()?)))
+        .collect()
+}
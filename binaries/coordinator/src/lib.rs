@@ -6,13 +6,22 @@ pub use control::ControlEvent;
 use dora_core::{
     config::{NodeId, OperatorId},
     coordinator_messages::{LogMessage, RegisterResult},
-    daemon_messages::{DaemonCoordinatorEvent, DaemonCoordinatorReply, Timestamped},
-    descriptor::{Descriptor, ResolvedNode},
+    daemon_messages::{
+        DaemonCoordinatorEvent, DaemonCoordinatorReply, LogChunk, NodeStats, OperatorError,
+        Timestamped,
+    },
+    descriptor::{
+        CoreNodeKind, Descriptor, OperatorDefinition, OperatorErrorPolicy, OperatorSource,
+        ResolvedNode, RuntimeNode,
+    },
     message::uhlc::{self, HLC},
+    record::RecordingInfo,
     topics::{
-        ControlRequest, ControlRequestReply, DataflowDaemonResult, DataflowId, DataflowListEntry,
-        DataflowResult,
+        AttachedNode, ControlRequest, ControlRequestReply, DataflowDaemonResult, DataflowId,
+        DataflowInspection, DataflowListEntry, DataflowResult, DataflowStatus, ErrorRecord,
+        NodeInspection, NodeState,
     },
+    version,
 };
 use eyre::{bail, eyre, ContextCompat, WrapErr};
 use futures::{stream::FuturesUnordered, Future, Stream, StreamExt};
@@ -20,11 +29,11 @@ use futures_concurrency::stream::Merge;
 use log_subscriber::LogSubscriber;
 use run::SpawnedDataflow;
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
     net::SocketAddr,
     path::PathBuf,
     sync::Arc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{net::TcpStream, sync::mpsc, task::JoinHandle};
 use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
@@ -175,15 +184,10 @@ async fn start_inner(
                     dora_version: daemon_version,
                     listen_port,
                 } => {
-                    let coordinator_version: &&str = &env!("CARGO_PKG_VERSION");
-                    let version_check = if &daemon_version == coordinator_version {
-                        Ok(())
-                    } else {
-                        Err(format!(
-                            "version mismatch: daemon v{daemon_version} is \
-                        not compatible with coordinator v{coordinator_version}"
-                        ))
-                    };
+                    let version_check = version::check_compatible(
+                        ("daemon", &daemon_version),
+                        ("coordinator", &version::current()),
+                    );
                     let peer_ip = connection
                         .peer_addr()
                         .map(|addr| addr.ip())
@@ -287,6 +291,9 @@ async fn start_inner(
                                 .insert(machine_id, result);
                             if entry.get_mut().machines.is_empty() {
                                 let finished_dataflow = entry.remove();
+                                // include the errors that were reported after archiving
+                                archived_dataflows
+                                    .insert(uuid, ArchivedDataflow::from(&finished_dataflow));
                                 let reply = ControlRequestReply::DataflowStopped {
                                     uuid,
                                     result: dataflow_results
@@ -393,6 +400,62 @@ async fn start_inner(
                                     });
                             let _ = reply_sender.send(reply);
                         }
+                        ControlRequest::RestartNode {
+                            dataflow_uuid,
+                            node_id,
+                            grace_duration,
+                        } => {
+                            let reply = restart_node(
+                                &mut running_dataflows,
+                                dataflow_uuid,
+                                node_id.clone(),
+                                grace_duration,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| {
+                                ControlRequestReply::NodeRestarting {
+                                    uuid: dataflow_uuid,
+                                    node_id,
+                                }
+                            });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::LoadOperator {
+                            dataflow_id,
+                            node_id,
+                            operator,
+                        } => {
+                            let reply = load_operator(
+                                &mut running_dataflows,
+                                dataflow_id,
+                                node_id,
+                                operator,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| ControlRequestReply::OperatorLoaded { uuid: dataflow_id });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::UnloadOperator {
+                            dataflow_id,
+                            node_id,
+                            operator_id,
+                        } => {
+                            let reply = unload_operator(
+                                &mut running_dataflows,
+                                dataflow_id,
+                                node_id,
+                                operator_id,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| ControlRequestReply::OperatorUnloaded { uuid: dataflow_id });
+                            let _ = reply_sender.send(reply);
+                        }
                         ControlRequest::Stop {
                             dataflow_uuid,
                             grace_duration,
@@ -430,7 +493,13 @@ async fn start_inner(
                                 let _ = reply_sender.send(Err(err));
                             }
                         },
-                        ControlRequest::Logs { uuid, name, node } => {
+                        ControlRequest::Logs {
+                            uuid,
+                            name,
+                            node,
+                            since,
+                            offsets,
+                        } => {
                             let dataflow_uuid = if let Some(uuid) = uuid {
                                 uuid
                             } else if let Some(name) = name {
@@ -443,9 +512,11 @@ async fn start_inner(
                                 &running_dataflows,
                                 &archived_dataflows,
                                 dataflow_uuid,
-                                node.into(),
+                                node.map(NodeId::from),
+                                since,
+                                offsets,
                                 &mut daemon_connections,
-                                clock.new_timestamp(),
+                                &clock,
                             )
                             .await
                             .map(ControlRequestReply::Logs);
@@ -507,6 +578,166 @@ async fn start_inner(
                             ));
                             let _ = reply_sender.send(reply);
                         }
+                        ControlRequest::NodeStats { dataflow_uuid } => {
+                            let reply = retrieve_node_stats(
+                                &running_dataflows,
+                                dataflow_uuid,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(ControlRequestReply::NodeStats);
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::Topology { dataflow_uuid } => {
+                            let reply = retrieve_node_stats(
+                                &running_dataflows,
+                                dataflow_uuid,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|stats| {
+                                ControlRequestReply::Topology {
+                                    nodes: running_dataflows
+                                        .get(&dataflow_uuid)
+                                        .map(|dataflow| dataflow.nodes.clone())
+                                        .unwrap_or_default(),
+                                    stats,
+                                }
+                            });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::StartRecording {
+                            dataflow_uuid,
+                            outputs,
+                        } => {
+                            let reply = set_recording(
+                                &running_dataflows,
+                                dataflow_uuid,
+                                DaemonCoordinatorEvent::StartRecording {
+                                    dataflow_id: dataflow_uuid,
+                                    outputs,
+                                },
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| {
+                                ControlRequestReply::RecordingStarted {
+                                    uuid: dataflow_uuid,
+                                }
+                            });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::StopRecording { dataflow_uuid } => {
+                            let reply = set_recording(
+                                &running_dataflows,
+                                dataflow_uuid,
+                                DaemonCoordinatorEvent::StopRecording {
+                                    dataflow_id: dataflow_uuid,
+                                },
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| {
+                                ControlRequestReply::RecordingStopped {
+                                    uuid: dataflow_uuid,
+                                }
+                            });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::Inspect { dataflow_uuid } => {
+                            let reply = inspect_dataflow(
+                                dataflow_uuid,
+                                &running_dataflows,
+                                &archived_dataflows,
+                                &dataflow_results,
+                                &mut daemon_connections,
+                                &clock,
+                            )
+                            .await
+                            .map(|inspection| ControlRequestReply::Inspect(Box::new(inspection)));
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::ListRecordings => {
+                            let reply =
+                                list_recordings(&mut daemon_connections, clock.new_timestamp())
+                                    .await
+                                    .map(ControlRequestReply::Recordings);
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::UpgradeDaemons {
+                            binary,
+                            machine_ids,
+                        } => {
+                            let reply = upgrade_daemons(
+                                binary,
+                                machine_ids,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(ControlRequestReply::DaemonsUpgrading);
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::Deploy {
+                            name,
+                            bundle,
+                            target_dir,
+                            machine_ids,
+                        } => {
+                            let reply = deploy(
+                                name,
+                                bundle,
+                                target_dir,
+                                machine_ids,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(ControlRequestReply::Deployed);
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::AttachNode {
+                            dataflow_uuid,
+                            node,
+                        } => {
+                            let reply = attach_node(
+                                &mut running_dataflows,
+                                dataflow_uuid,
+                                node,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| {
+                                ControlRequestReply::NodeAttached {
+                                    uuid: dataflow_uuid,
+                                }
+                            });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::DetachNode {
+                            dataflow_uuid,
+                            node_id,
+                        } => {
+                            let reply = detach_node(
+                                &mut running_dataflows,
+                                dataflow_uuid,
+                                node_id,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| {
+                                ControlRequestReply::NodeDetached {
+                                    uuid: dataflow_uuid,
+                                }
+                            });
+                            let _ = reply_sender.send(reply);
+                        }
                         ControlRequest::LogSubscribe { .. } => {
                             let _ = reply_sender.send(Err(eyre::eyre!(
                                 "LogSubscribe request should be handled separately"
@@ -579,19 +810,33 @@ async fn start_inner(
                 }
             }
             Event::Log(message) => {
-                if let Some(dataflow) = running_dataflows.get_mut(&message.dataflow_id) {
-                    for subscriber in &mut dataflow.log_subscribers {
-                        let send_result = tokio::time::timeout(
-                            Duration::from_millis(100),
-                            subscriber.send_message(&message),
-                        );
-
-                        if send_result.await.is_err() {
-                            subscriber.close();
-                        }
+                forward_log_message(&mut running_dataflows, &message).await;
+            }
+            Event::OperatorError {
+                dataflow_id,
+                node_id,
+                error,
+            } => {
+                tracing::error!(
+                    "operator `{node_id}/{}` of dataflow `{dataflow_id}` failed: {error}",
+                    error.operator_id
+                );
+                if let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) {
+                    if operator_restarts(&dataflow.nodes, &node_id, &error.operator_id) {
+                        *dataflow.restarts.entry(node_id.clone()).or_default() += 1;
                     }
-                    dataflow.log_subscribers.retain(|s| !s.is_closed());
                 }
+                let message = LogMessage {
+                    dataflow_id,
+                    target: Some(format!("{node_id}/{}", error.operator_id)),
+                    node_id: Some(node_id),
+                    level: log::Level::Error,
+                    module_path: None,
+                    file: None,
+                    line: None,
+                    message: error.to_string(),
+                };
+                forward_log_message(&mut running_dataflows, &message).await;
             }
         }
     }
@@ -601,6 +846,142 @@ async fn start_inner(
     Ok(())
 }
 
+/// Number of error messages per dataflow that are kept for `dora inspect`.
+const MAX_RECENT_ERRORS: usize = 20;
+
+/// Whether the operator is restarted after an error.
+fn operator_restarts(nodes: &[ResolvedNode], node_id: &NodeId, operator_id: &OperatorId) -> bool {
+    nodes
+        .iter()
+        .filter(|node| &node.id == node_id)
+        .filter_map(|node| match &node.kind {
+            CoreNodeKind::Runtime(runtime) => Some(runtime),
+            CoreNodeKind::Custom(_) => None,
+        })
+        .flat_map(|runtime| &runtime.operators)
+        .any(|op| &op.id == operator_id && op.config.on_error == OperatorErrorPolicy::Restart)
+}
+
+async fn inspect_dataflow(
+    dataflow_id: Uuid,
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
+    dataflow_results: &HashMap<Uuid, BTreeMap<String, DataflowDaemonResult>>,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    clock: &HLC,
+) -> eyre::Result<DataflowInspection> {
+    let (name, nodes, descriptor, restarts, recent_errors, status, mut stats) =
+        match running_dataflows.get(&dataflow_id) {
+            Some(dataflow) => {
+                let stats = retrieve_node_stats(
+                    running_dataflows,
+                    dataflow_id,
+                    daemon_connections,
+                    clock.new_timestamp(),
+                )
+                .await
+                .unwrap_or_else(|err| {
+                    tracing::warn!("failed to retrieve node stats for inspection: {err:?}");
+                    BTreeMap::new()
+                });
+                (
+                    &dataflow.name,
+                    &dataflow.nodes,
+                    &dataflow.descriptor,
+                    &dataflow.restarts,
+                    &dataflow.recent_errors,
+                    DataflowStatus::Running,
+                    stats,
+                )
+            }
+            None => {
+                let dataflow = archived_dataflows
+                    .get(&dataflow_id)
+                    .with_context(|| format!("no dataflow with UUID `{dataflow_id}`"))?;
+                let status = match dataflow_results.get(&dataflow_id) {
+                    Some(results) if !results.values().all(|r| r.is_ok()) => DataflowStatus::Failed,
+                    _ => DataflowStatus::Finished,
+                };
+                (
+                    &dataflow.name,
+                    &dataflow.nodes,
+                    &dataflow.descriptor,
+                    &dataflow.restarts,
+                    &dataflow.recent_errors,
+                    status,
+                    BTreeMap::new(),
+                )
+            }
+        };
+
+    // results of machines on which the dataflow already finished
+    let node_results: BTreeMap<_, _> = dataflow_results
+        .get(&dataflow_id)
+        .into_iter()
+        .flat_map(|results| results.values())
+        .flat_map(|result| &result.node_results)
+        .collect();
+
+    let nodes = nodes
+        .iter()
+        .map(|node| {
+            let state = match node_results.get(&node.id) {
+                Some(Ok(())) => NodeState::Finished,
+                Some(Err(err)) => NodeState::Failed(err.to_string()),
+                None => match stats.remove(&node.id).map(|stats| stats.exit) {
+                    Some(Some(Ok(()))) => NodeState::Finished,
+                    Some(Some(Err(err))) => NodeState::Failed(err),
+                    Some(None) => NodeState::Running,
+                    None => NodeState::Unknown,
+                },
+            };
+            NodeInspection {
+                node: node.clone(),
+                state,
+                restarts: restarts.get(&node.id).copied().unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Ok(DataflowInspection {
+        id: DataflowId {
+            uuid: dataflow_id,
+            name: name.clone(),
+        },
+        status,
+        descriptor: descriptor.clone(),
+        nodes,
+        recent_errors: recent_errors.iter().cloned().collect(),
+    })
+}
+
+async fn forward_log_message(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    message: &LogMessage,
+) {
+    if let Some(dataflow) = running_dataflows.get_mut(&message.dataflow_id) {
+        if message.level == log::Level::Error {
+            if dataflow.recent_errors.len() >= MAX_RECENT_ERRORS {
+                dataflow.recent_errors.pop_front();
+            }
+            dataflow.recent_errors.push_back(ErrorRecord {
+                time: SystemTime::now(),
+                node_id: message.node_id.clone(),
+                message: message.message.clone(),
+            });
+        }
+        for subscriber in &mut dataflow.log_subscribers {
+            let send_result =
+                tokio::time::timeout(Duration::from_millis(100), subscriber.send_message(message));
+
+            if send_result.await.is_err() {
+                subscriber.close();
+            }
+        }
+        dataflow.log_subscribers.retain(|s| !s.is_closed());
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn stop_dataflow_by_uuid(
     running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
@@ -651,7 +1032,7 @@ fn dataflow_result(
     clock: &uhlc::HLC,
 ) -> DataflowResult {
     let mut node_results = BTreeMap::new();
-    for (_machine, result) in results {
+    for result in results.values() {
         node_results.extend(result.node_results.clone());
         if let Err(err) = clock.update_with_timestamp(&result.timestamp) {
             tracing::warn!("failed to update HLC: {err}");
@@ -718,6 +1099,13 @@ struct RunningDataflow {
     pending_machines: BTreeSet<String>,
     exited_before_subscribe: Vec<NodeId>,
     nodes: Vec<ResolvedNode>,
+    descriptor: Descriptor,
+    /// Dynamic nodes that were added through `ControlRequest::AttachNode`.
+    attached_nodes: BTreeMap<NodeId, AttachedNode>,
+    /// Number of restarts per node, through `OperatorErrorPolicy::Restart` or
+    /// `ControlRequest::RestartNode`.
+    restarts: BTreeMap<NodeId, u32>,
+    recent_errors: VecDeque<ErrorRecord>,
 
     reply_senders: Vec<tokio::sync::oneshot::Sender<eyre::Result<ControlRequestReply>>>,
 
@@ -727,6 +1115,9 @@ struct RunningDataflow {
 struct ArchivedDataflow {
     name: Option<String>,
     nodes: Vec<ResolvedNode>,
+    descriptor: Descriptor,
+    restarts: BTreeMap<NodeId, u32>,
+    recent_errors: VecDeque<ErrorRecord>,
 }
 
 impl From<&RunningDataflow> for ArchivedDataflow {
@@ -734,6 +1125,9 @@ impl From<&RunningDataflow> for ArchivedDataflow {
         ArchivedDataflow {
             name: dataflow.name.clone(),
             nodes: dataflow.nodes.clone(),
+            descriptor: dataflow.descriptor.clone(),
+            restarts: dataflow.restarts.clone(),
+            recent_errors: dataflow.recent_errors.clone(),
         }
     }
 }
@@ -833,14 +1227,285 @@ async fn reload_dataflow(
     Ok(())
 }
 
+async fn restart_node(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    node_id: NodeId,
+    grace_duration: Option<Duration>,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    let machine_id = dataflow
+        .nodes
+        .iter()
+        .find(|n| n.id == node_id)
+        .map(|n| n.deploy.machine.clone())
+        .wrap_err_with(|| format!("no node with ID `{node_id}` in dataflow"))?;
+    let message = serde_json::to_vec(&Timestamped {
+        inner: DaemonCoordinatorEvent::RestartNode {
+            dataflow_id,
+            node_id: node_id.clone(),
+            grace_duration,
+        },
+        timestamp,
+    })?;
+
+    let daemon_connection = daemon_connections
+        .get_mut(&machine_id)
+        .wrap_err_with(|| format!("no daemon connection for machine `{machine_id}`"))?;
+    tcp_send(&mut daemon_connection.stream, &message)
+        .await
+        .wrap_err("failed to send restart node message to daemon")?;
+
+    // wait for reply
+    let reply_raw = tcp_receive(&mut daemon_connection.stream)
+        .await
+        .wrap_err("failed to receive restart node reply from daemon")?;
+    match serde_json::from_slice(&reply_raw)
+        .wrap_err("failed to deserialize restart node reply from daemon")?
+    {
+        DaemonCoordinatorReply::RestartNodeResult(result) => result
+            .map_err(|e| eyre!(e))
+            .wrap_err_with(|| format!("failed to restart node `{node_id}`"))?,
+        other => bail!("unexpected reply after sending restart node: {other:?}"),
+    }
+
+    *dataflow.restarts.entry(node_id.clone()).or_default() += 1;
+    tracing::info!("restarting node `{node_id}` of dataflow `{dataflow_id}`");
+
+    Ok(())
+}
+
+async fn load_operator(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    node_id: NodeId,
+    operator: OperatorDefinition,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    if let OperatorSource::Python(_) = operator.config.source {
+        bail!("Python operators can't be loaded into running nodes");
+    }
+    let (runtime, machine) = runtime_node(&mut dataflow.nodes, &node_id)?;
+    if runtime.operators.iter().any(|o| o.id == operator.id) {
+        bail!(
+            "node `{node_id}` already has an operator with ID `{}`",
+            operator.id
+        );
+    }
+    let operator_id = operator.id.clone();
+    let event = DaemonCoordinatorEvent::LoadOperator {
+        dataflow_id,
+        node_id: node_id.clone(),
+        machine,
+        operator: operator.clone(),
+    };
+    send_operator_event(&dataflow.machines, event, daemon_connections, timestamp)
+        .await
+        .wrap_err_with(|| format!("failed to load operator `{node_id}/{operator_id}`"))?;
+
+    runtime.operators.push(operator);
+    tracing::info!("loaded operator `{node_id}/{operator_id}` into dataflow `{dataflow_id}`");
+
+    Ok(())
+}
+
+async fn unload_operator(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    node_id: NodeId,
+    operator_id: OperatorId,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    let (runtime, machine) = runtime_node(&mut dataflow.nodes, &node_id)?;
+    if !runtime.operators.iter().any(|o| o.id == operator_id) {
+        bail!("node `{node_id}` has no operator with ID `{operator_id}`");
+    }
+    let event = DaemonCoordinatorEvent::UnloadOperator {
+        dataflow_id,
+        node_id: node_id.clone(),
+        machine,
+        operator_id: operator_id.clone(),
+    };
+    send_operator_event(&dataflow.machines, event, daemon_connections, timestamp)
+        .await
+        .wrap_err_with(|| format!("failed to unload operator `{node_id}/{operator_id}`"))?;
+
+    runtime.operators.retain(|o| o.id != operator_id);
+    tracing::info!("unloaded operator `{node_id}/{operator_id}` from dataflow `{dataflow_id}`");
+
+    Ok(())
+}
+
+/// Returns the runtime node with the given ID and the machine that it runs on.
+fn runtime_node<'a>(
+    nodes: &'a mut [ResolvedNode],
+    node_id: &NodeId,
+) -> eyre::Result<(&'a mut RuntimeNode, String)> {
+    let node = nodes
+        .iter_mut()
+        .find(|n| &n.id == node_id)
+        .wrap_err_with(|| format!("no node with ID `{node_id}` in dataflow"))?;
+    match &mut node.kind {
+        CoreNodeKind::Runtime(runtime) => Ok((runtime, node.deploy.machine.clone())),
+        CoreNodeKind::Custom(_) => bail!("node `{node_id}` is not a runtime node"),
+    }
+}
+
+async fn attach_node(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    node: AttachedNode,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    let node_id = node.id.clone();
+    if dataflow.nodes.iter().any(|n| n.id == node_id)
+        || dataflow.attached_nodes.contains_key(&node_id)
+    {
+        bail!("dataflow already has a node with ID `{node_id}`");
+    }
+    if !dataflow.machines.contains(&node.machine) {
+        bail!(
+            "dataflow `{dataflow_id}` has no nodes on machine `{}`",
+            node.machine
+        );
+    }
+
+    let find_node = |id: &NodeId| {
+        dataflow
+            .nodes
+            .iter()
+            .find(|n| &n.id == id)
+            .wrap_err_with(|| format!("no node with ID `{id}` in dataflow"))
+    };
+    for (source, output) in node.inputs.values() {
+        if !find_node(source)?
+            .kind
+            .run_config()
+            .outputs
+            .contains(output)
+        {
+            bail!("node `{source}` has no output `{output}`");
+        }
+    }
+    let mut target_machines = BTreeMap::new();
+    for (target, input) in node.outputs.values().flatten() {
+        let target_node = find_node(target)?;
+        if !target_node.kind.run_config().inputs.contains_key(input) {
+            bail!("node `{target}` has no input `{input}`");
+        }
+        target_machines.insert(target.clone(), target_node.deploy.machine.clone());
+    }
+
+    let event = DaemonCoordinatorEvent::AttachNode {
+        dataflow_id,
+        node: node.clone(),
+        descriptor: dataflow.descriptor.clone(),
+        target_machines,
+    };
+    send_operator_event(&dataflow.machines, event, daemon_connections, timestamp)
+        .await
+        .wrap_err_with(|| format!("failed to attach node `{node_id}`"))?;
+
+    dataflow.attached_nodes.insert(node_id.clone(), node);
+    tracing::info!("attached node `{node_id}` to dataflow `{dataflow_id}`");
+
+    Ok(())
+}
+
+async fn detach_node(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    node_id: NodeId,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    if !dataflow.attached_nodes.contains_key(&node_id) {
+        bail!("no attached node with ID `{node_id}` in dataflow `{dataflow_id}`");
+    }
+    let event = DaemonCoordinatorEvent::DetachNode {
+        dataflow_id,
+        node_id: node_id.clone(),
+    };
+    send_operator_event(&dataflow.machines, event, daemon_connections, timestamp)
+        .await
+        .wrap_err_with(|| format!("failed to detach node `{node_id}`"))?;
+
+    dataflow.attached_nodes.remove(&node_id);
+    tracing::info!("detached node `{node_id}` from dataflow `{dataflow_id}`");
+
+    Ok(())
+}
+
+/// Sends a load, unload, attach, or detach event to all daemons of the dataflow.
+///
+/// All daemons need to update their mappings, not only the daemon that runs the
+/// affected node, since it might be connected to nodes on other machines.
+async fn send_operator_event(
+    machines: &BTreeSet<String>,
+    event: DaemonCoordinatorEvent,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let message = serde_json::to_vec(&Timestamped {
+        inner: event,
+        timestamp,
+    })?;
+
+    for machine_id in machines {
+        let daemon_connection = daemon_connections
+            .get_mut(machine_id)
+            .wrap_err("no daemon connection")?;
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send operator message to daemon")?;
+
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to receive operator reply from daemon")?;
+        match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize operator reply from daemon")?
+        {
+            DaemonCoordinatorReply::OperatorResult(result)
+            | DaemonCoordinatorReply::AttachResult(result) => result
+                .map_err(|e| eyre!(e))
+                .wrap_err_with(|| format!("daemon on machine `{machine_id}` failed"))?,
+            other => bail!("unexpected reply after sending operator message: {other:?}"),
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn retrieve_logs(
     running_dataflows: &HashMap<Uuid, RunningDataflow>,
     archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
     dataflow_id: Uuid,
-    node_id: NodeId,
+    node_id: Option<NodeId>,
+    since: Option<SystemTime>,
+    offsets: BTreeMap<NodeId, u64>,
     daemon_connections: &mut HashMap<String, DaemonConnection>,
-    timestamp: uhlc::Timestamp,
-) -> eyre::Result<Vec<u8>> {
+    clock: &HLC,
+) -> eyre::Result<BTreeMap<NodeId, LogChunk>> {
     let nodes = if let Some(dataflow) = archived_dataflows.get(&dataflow_id) {
         dataflow.nodes.clone()
     } else if let Some(dataflow) = running_dataflows.get(&dataflow_id) {
@@ -849,52 +1514,280 @@ async fn retrieve_logs(
         bail!("No dataflow found with UUID `{dataflow_id}`")
     };
 
+    let nodes: Vec<_> = match &node_id {
+        Some(node_id) => {
+            let matching: Vec<_> = nodes.iter().filter(|node| &node.id == node_id).collect();
+            match &matching[..] {
+                [_] => matching,
+                [] => bail!("No machine contains {}/{}", dataflow_id, node_id),
+                _ => bail!(
+                    "More than one machine contains {}/{}. However, it should only be present on one.",
+                    dataflow_id,
+                    node_id
+                ),
+            }
+        }
+        None => nodes.iter().collect(),
+    };
+
+    let mut logs = BTreeMap::new();
+    for node in nodes {
+        let message = serde_json::to_vec(&Timestamped {
+            inner: DaemonCoordinatorEvent::Logs {
+                dataflow_id,
+                node_id: node.id.clone(),
+                since,
+                offset: offsets.get(&node.id).copied(),
+            },
+            timestamp: clock.new_timestamp(),
+        })?;
+
+        let daemon_connection = daemon_connections
+            .get_mut(node.deploy.machine.as_str())
+            .wrap_err("no daemon connection")?;
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send logs message to daemon")?;
+
+        // wait for reply
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to retrieve logs reply from daemon")?;
+        let reply_logs = match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize logs reply from daemon")?
+        {
+            DaemonCoordinatorReply::Logs(logs) => logs,
+            other => bail!("unexpected reply after sending logs: {other:?}"),
+        };
+        match reply_logs {
+            Ok(chunk) => {
+                logs.insert(node.id.clone(), chunk);
+            }
+            // nodes that failed to spawn have no log file
+            Err(err) if node_id.is_none() => {
+                tracing::debug!("no logs for `{dataflow_id}/{}`: {err}", node.id)
+            }
+            Err(err) => bail!(err),
+        }
+    }
+    tracing::debug!("successfully retrieved logs for `{dataflow_id}`");
+
+    Ok(logs)
+}
+
+async fn retrieve_node_stats(
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<BTreeMap<NodeId, NodeStats>> {
+    let Some(dataflow) = running_dataflows.get(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
     let message = serde_json::to_vec(&Timestamped {
-        inner: DaemonCoordinatorEvent::Logs {
-            dataflow_id,
-            node_id: node_id.clone(),
-        },
+        inner: DaemonCoordinatorEvent::NodeStats { dataflow_id },
         timestamp,
     })?;
 
-    let machine_ids: Vec<String> = nodes
-        .iter()
-        .filter(|node| node.id == node_id)
-        .map(|node| node.deploy.machine.clone())
-        .collect();
+    let mut stats = BTreeMap::new();
+    for machine_id in &dataflow.machines {
+        let daemon_connection = daemon_connections
+            .get_mut(machine_id.as_str())
+            .wrap_err("no daemon connection")?;
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send node stats message to daemon")?;
+
+        // wait for reply
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to retrieve node stats reply from daemon")?;
+        match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize node stats reply from daemon")?
+        {
+            DaemonCoordinatorReply::NodeStats(result) => {
+                stats.extend(result.map_err(|err| eyre!(err))?);
+            }
+            other => bail!("unexpected reply after sending node stats: {other:?}"),
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Sends a `StartRecording` or `StopRecording` event to all machines of the
+/// dataflow.
+async fn set_recording(
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    event: DaemonCoordinatorEvent,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    let message = serde_json::to_vec(&Timestamped {
+        inner: event,
+        timestamp,
+    })?;
+
+    for machine_id in &dataflow.machines {
+        let daemon_connection = daemon_connections
+            .get_mut(machine_id.as_str())
+            .wrap_err("no daemon connection")?;
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send recording message to daemon")?;
+
+        // wait for reply
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to receive recording reply from daemon")?;
+        match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize recording reply from daemon")?
+        {
+            DaemonCoordinatorReply::RecordingResult(result) => result
+                .map_err(|e| eyre!(e))
+                .wrap_err_with(|| format!("recording failed on machine `{machine_id}`"))?,
+            other => bail!("unexpected reply after sending recording message: {other:?}"),
+        }
+    }
+
+    Ok(())
+}
+
+async fn list_recordings(
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<Vec<RecordingInfo>> {
+    let message = serde_json::to_vec(&Timestamped {
+        inner: DaemonCoordinatorEvent::ListRecordings,
+        timestamp,
+    })?;
+
+    let mut recordings = Vec::new();
+    for (machine_id, daemon_connection) in daemon_connections {
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send list recordings message to daemon")?;
+
+        // wait for reply
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to receive list recordings reply from daemon")?;
+        match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize list recordings reply from daemon")?
+        {
+            DaemonCoordinatorReply::Recordings(result) => recordings.extend(
+                result
+                    .map_err(|e| eyre!(e))
+                    .wrap_err_with(|| format!("failed to list recordings of `{machine_id}`"))?,
+            ),
+            other => bail!("unexpected reply after sending list recordings: {other:?}"),
+        }
+    }
+
+    Ok(recordings)
+}
 
-    let machine_id = if let [machine_id] = &machine_ids[..] {
-        machine_id
-    } else if machine_ids.is_empty() {
-        bail!("No machine contains {}/{}", dataflow_id, node_id)
+async fn upgrade_daemons(
+    binary: Vec<u8>,
+    machine_ids: BTreeSet<String>,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<BTreeMap<String, version::Version>> {
+    let machine_ids = if machine_ids.is_empty() {
+        daemon_connections.keys().cloned().collect()
     } else {
-        bail!(
-            "More than one machine contains {}/{}. However, it should only be present on one.",
-            dataflow_id,
-            node_id
-        )
+        machine_ids
     };
+    let message = serde_json::to_vec(&Timestamped {
+        inner: DaemonCoordinatorEvent::Upgrade {
+            binary,
+            coordinator_version: version::current(),
+        },
+        timestamp,
+    })?;
 
-    let daemon_connection = daemon_connections
-        .get_mut(machine_id.as_str())
-        .wrap_err("no daemon connection")?;
-    tcp_send(&mut daemon_connection.stream, &message)
-        .await
-        .wrap_err("failed to send logs message to daemon")?;
+    let mut upgrading = BTreeMap::new();
+    for machine_id in machine_ids {
+        let daemon_connection = daemon_connections
+            .get_mut(machine_id.as_str())
+            .wrap_err_with(|| format!("no daemon connection for machine `{machine_id}`"))?;
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send upgrade message to daemon")?;
 
-    // wait for reply
-    let reply_raw = tcp_receive(&mut daemon_connection.stream)
-        .await
-        .wrap_err("failed to retrieve logs reply from daemon")?;
-    let reply_logs = match serde_json::from_slice(&reply_raw)
-        .wrap_err("failed to deserialize logs reply from daemon")?
-    {
-        DaemonCoordinatorReply::Logs(logs) => logs,
-        other => bail!("unexpected reply after sending logs: {other:?}"),
+        // wait for reply
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to receive upgrade reply from daemon")?;
+        let new_version = match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize upgrade reply from daemon")?
+        {
+            DaemonCoordinatorReply::UpgradeResult(result) => result
+                .map_err(|e| eyre!(e))
+                .wrap_err_with(|| format!("failed to upgrade daemon `{machine_id}`"))?,
+            other => bail!("unexpected reply after sending upgrade: {other:?}"),
+        };
+        tracing::info!(
+            "daemon `{machine_id}` will upgrade to v{new_version} after its dataflows finished"
+        );
+        upgrading.insert(machine_id, new_version);
+    }
+
+    Ok(upgrading)
+}
+
+async fn deploy(
+    name: String,
+    bundle: Vec<u8>,
+    target_dir: Option<PathBuf>,
+    machine_ids: BTreeSet<String>,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<BTreeMap<String, PathBuf>> {
+    let machine_ids = if machine_ids.is_empty() {
+        daemon_connections.keys().cloned().collect()
+    } else {
+        machine_ids
     };
-    tracing::info!("successfully retrieved logs for `{dataflow_id}/{node_id}`");
+    let message = serde_json::to_vec(&Timestamped {
+        inner: DaemonCoordinatorEvent::Deploy {
+            name,
+            bundle,
+            target_dir,
+        },
+        timestamp,
+    })?;
 
-    reply_logs.map_err(|err| eyre!(err))
+    let mut deployed = BTreeMap::new();
+    for machine_id in machine_ids {
+        let daemon_connection = daemon_connections
+            .get_mut(machine_id.as_str())
+            .wrap_err_with(|| format!("no daemon connection for machine `{machine_id}`"))?;
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send deploy message to daemon")?;
+
+        // wait for reply
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to receive deploy reply from daemon")?;
+        let dir = match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize deploy reply from daemon")?
+        {
+            DaemonCoordinatorReply::DeployResult(result) => result
+                .map_err(|e| eyre!(e))
+                .wrap_err_with(|| format!("failed to deploy bundle on machine `{machine_id}`"))?,
+            other => bail!("unexpected reply after sending deploy: {other:?}"),
+        };
+        tracing::info!("deployed bundle to `{}` on `{machine_id}`", dir.display());
+        deployed.insert(machine_id, dir);
+    }
+
+    Ok(deployed)
 }
 
 async fn start_dataflow(
@@ -904,6 +1797,7 @@ async fn start_dataflow(
     daemon_connections: &mut HashMap<String, DaemonConnection>,
     clock: &HLC,
 ) -> eyre::Result<RunningDataflow> {
+    let descriptor = dataflow.clone();
     let SpawnedDataflow {
         uuid,
         machines,
@@ -920,6 +1814,10 @@ async fn start_dataflow(
         exited_before_subscribe: Default::default(),
         machines,
         nodes,
+        descriptor,
+        attached_nodes: BTreeMap::new(),
+        restarts: BTreeMap::new(),
+        recent_errors: VecDeque::new(),
         reply_senders: Vec::new(),
         log_subscribers: Vec::new(),
     })
@@ -962,13 +1860,23 @@ async fn destroy_daemons(
 pub enum Event {
     NewDaemonConnection(TcpStream),
     DaemonConnectError(eyre::Report),
-    DaemonHeartbeat { machine_id: String },
-    Dataflow { uuid: Uuid, event: DataflowEvent },
+    DaemonHeartbeat {
+        machine_id: String,
+    },
+    Dataflow {
+        uuid: Uuid,
+        event: DataflowEvent,
+    },
     Control(ControlEvent),
     Daemon(DaemonEvent),
     DaemonHeartbeatInterval,
     CtrlC,
     Log(LogMessage),
+    OperatorError {
+        dataflow_id: Uuid,
+        node_id: NodeId,
+        error: OperatorError,
+    },
 }
 
 impl Event {
@@ -997,7 +1905,7 @@ pub enum DataflowEvent {
 #[derive(Debug)]
 pub enum DaemonEvent {
     Register {
-        dora_version: String,
+        dora_version: version::Version,
         machine_id: String,
         connection: TcpStream,
         listen_port: u16,
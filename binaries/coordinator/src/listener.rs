@@ -103,6 +103,20 @@ pub async fn handle_connection(
                         break;
                     }
                 }
+                coordinator_messages::DaemonEvent::OperatorError {
+                    dataflow_id,
+                    node_id,
+                    error,
+                } => {
+                    let event = Event::OperatorError {
+                        dataflow_id,
+                        node_id,
+                        error,
+                    };
+                    if events_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
             },
         };
     }
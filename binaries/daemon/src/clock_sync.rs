@@ -0,0 +1,281 @@
+//! NTP-like estimation of the clock offsets between daemons.
+//!
+//! Each daemon periodically sends a `ClockSyncRequest` to all known remote
+//! daemons, which answer with the time they received the request and the time
+//! they sent the reply. From the four timestamps of such a round trip, the
+//! offset between the two clocks can be estimated. Like NTP, we keep a few
+//! recent samples per machine and use the one with the lowest round-trip delay.
+//!
+//! Dataflows that use encryption only trust samples of round trips that were
+//! encrypted with their own key, so they are kept separately.
+
+use dora_core::{daemon_messages::DataflowId, message::uhlc};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SAMPLE_WINDOW: usize = 8;
+
+#[derive(Default)]
+pub struct ClockSync {
+    /// Samples per remote machine and the dataflow whose key authenticated
+    /// them, if any.
+    samples: BTreeMap<(String, Option<DataflowId>), VecDeque<Sample>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// Offset of the remote clock relative to the local clock.
+    offset: i64,
+    delay: u64,
+}
+
+impl ClockSync {
+    /// Records a completed round trip to the given machine.
+    ///
+    /// All timestamps are nanoseconds since the UNIX epoch. `request_sent` and
+    /// `reply_received` are measured with the local clock, the others with the
+    /// remote clock. `dataflow` is the dataflow whose key authenticated the
+    /// round trip, or `None` for unencrypted clock sync.
+    pub fn add_sample(
+        &mut self,
+        machine_id: String,
+        dataflow: Option<DataflowId>,
+        request_sent: u64,
+        request_received: u64,
+        reply_sent: u64,
+        reply_received: u64,
+    ) {
+        let [t0, t1, t2, t3] =
+            [request_sent, request_received, reply_sent, reply_received].map(i128::from);
+        let offset = ((t1 - t0) + (t2 - t3)) / 2;
+        let delay = (t3 - t0) - (t2 - t1);
+        if delay < 0 {
+            tracing::debug!("ignoring clock sync sample with negative delay from `{machine_id}`");
+            return;
+        }
+
+        let samples = self.samples.entry((machine_id, dataflow)).or_default();
+        if samples.len() >= SAMPLE_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            offset: offset as i64,
+            delay: delay as u64,
+        });
+    }
+
+    /// Estimated offset of the given machine's clock relative to the local clock.
+    pub fn offset(&self, machine_id: &str, dataflow: Option<DataflowId>) -> Option<i64> {
+        self.samples
+            .get(&(machine_id.to_owned(), dataflow))?
+            .iter()
+            .min_by_key(|s| s.delay)
+            .map(|s| s.offset)
+    }
+
+    /// Converts a local timestamp to the clock of the given reference machine.
+    ///
+    /// Only samples authenticated for `dataflow` are used, see
+    /// [`add_sample`](Self::add_sample). Returns `None` if no offset to the
+    /// reference machine is known yet.
+    pub fn normalize(
+        &self,
+        local_machine: &str,
+        reference_machine: &str,
+        dataflow: Option<DataflowId>,
+        timestamp: uhlc::Timestamp,
+    ) -> Option<u64> {
+        let local = timestamp.get_time().to_duration().as_nanos() as u64;
+        if local_machine == reference_machine {
+            Some(local)
+        } else {
+            self.offset(reference_machine, dataflow)
+                .map(|offset| local.saturating_add_signed(offset))
+        }
+    }
+}
+
+/// Current system time in nanoseconds since the UNIX epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn symmetric_delay() {
+        let mut sync = ClockSync::default();
+        // remote clock is 5ms ahead, 1ms delay in each direction, 2ms processing
+        sync.add_sample(
+            "b".into(),
+            None,
+            SECOND,
+            SECOND + 6_000_000,
+            SECOND + 8_000_000,
+            SECOND + 4_000_000,
+        );
+
+        assert_eq!(sync.offset("b", None), Some(5_000_000));
+        assert_eq!(sync.offset("c", None), None);
+    }
+
+    #[test]
+    fn negative_offset() {
+        let mut sync = ClockSync::default();
+        // remote clock is 3ms behind, 1ms delay in each direction
+        sync.add_sample(
+            "b".into(),
+            None,
+            SECOND,
+            SECOND - 2_000_000,
+            SECOND - 2_000_000,
+            SECOND + 2_000_000,
+        );
+
+        assert_eq!(sync.offset("b", None), Some(-3_000_000));
+    }
+
+    #[test]
+    fn asymmetric_delay() {
+        let mut sync = ClockSync::default();
+        // clocks are in sync, but the request takes 3ms and the reply 1ms, so
+        // the estimate is off by half the asymmetry
+        sync.add_sample(
+            "b".into(),
+            None,
+            SECOND,
+            SECOND + 3_000_000,
+            SECOND + 3_000_000,
+            SECOND + 4_000_000,
+        );
+
+        assert_eq!(sync.offset("b", None), Some(1_000_000));
+    }
+
+    #[test]
+    fn uses_sample_with_lowest_delay() {
+        let mut sync = ClockSync::default();
+        // 10ms round trip, offset estimate 4ms
+        sync.add_sample(
+            "b".into(),
+            None,
+            SECOND,
+            SECOND + 9_000_000,
+            SECOND + 9_000_000,
+            SECOND + 10_000_000,
+        );
+        // 2ms round trip, offset estimate 1ms
+        sync.add_sample(
+            "b".into(),
+            None,
+            2 * SECOND,
+            2 * SECOND + 2_000_000,
+            2 * SECOND + 2_000_000,
+            2 * SECOND + 2_000_000,
+        );
+        // 6ms round trip, offset estimate -1ms
+        sync.add_sample(
+            "b".into(),
+            None,
+            3 * SECOND,
+            3 * SECOND + 2_000_000,
+            3 * SECOND + 2_000_000,
+            3 * SECOND + 6_000_000,
+        );
+
+        assert_eq!(sync.offset("b", None), Some(1_000_000));
+    }
+
+    #[test]
+    fn ignores_negative_delay() {
+        let mut sync = ClockSync::default();
+        // the remote claims to have spent more time than the whole round trip
+        sync.add_sample(
+            "b".into(),
+            None,
+            SECOND,
+            SECOND,
+            SECOND + 5_000_000,
+            SECOND + 1_000_000,
+        );
+
+        assert_eq!(sync.offset("b", None), None);
+    }
+
+    #[test]
+    fn old_samples_are_dropped() {
+        let mut sync = ClockSync::default();
+        // zero-delay sample, which would be preferred as long as it's kept
+        sync.add_sample(
+            "b".into(),
+            None,
+            SECOND,
+            SECOND + 7_000_000,
+            SECOND + 7_000_000,
+            SECOND,
+        );
+        for i in 0..SAMPLE_WINDOW as u64 {
+            let t = (i + 2) * SECOND;
+            sync.add_sample(
+                "b".into(),
+                None,
+                t,
+                t + 1_000_000,
+                t + 1_000_000,
+                t + 2_000_000,
+            );
+        }
+
+        assert_eq!(sync.offset("b", None), Some(0));
+    }
+
+    #[test]
+    fn samples_are_kept_per_dataflow() {
+        let mut sync = ClockSync::default();
+        let dataflow = DataflowId::now_v7();
+        sync.add_sample(
+            "b".into(),
+            Some(dataflow),
+            SECOND,
+            SECOND + 2_000_000,
+            SECOND + 2_000_000,
+            SECOND + 2_000_000,
+        );
+
+        assert_eq!(sync.offset("b", Some(dataflow)), Some(1_000_000));
+        assert_eq!(sync.offset("b", None), None);
+        assert_eq!(sync.offset("b", Some(DataflowId::now_v7())), None);
+    }
+
+    #[test]
+    fn normalize_applies_offset() {
+        let mut sync = ClockSync::default();
+        sync.add_sample(
+            "b".into(),
+            None,
+            SECOND,
+            SECOND + 6_000_000,
+            SECOND + 8_000_000,
+            SECOND + 4_000_000,
+        );
+
+        let clock = uhlc::HLC::default();
+        let timestamp = clock.new_timestamp();
+        let local = timestamp.get_time().to_duration().as_nanos() as u64;
+        assert_eq!(sync.normalize("a", "a", None, timestamp), Some(local));
+        assert_eq!(
+            sync.normalize("a", "b", None, timestamp),
+            Some(local + 5_000_000)
+        );
+        assert_eq!(sync.normalize("a", "c", None, timestamp), None);
+    }
+}
@@ -0,0 +1,39 @@
+//! Compression of output data that is sent to other machines.
+
+use aligned_vec::{AVec, ConstAlign};
+use dora_core::{config::InputCompression, message::Compression};
+use eyre::Context;
+
+pub fn compress(
+    data: &[u8],
+    settings: &InputCompression,
+) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+    let compressed = match settings.algorithm {
+        Compression::Lz4 => {
+            let mode = match settings.level {
+                Some(level) if level > 0 => lz4::block::CompressionMode::HIGHCOMPRESSION(level),
+                _ => lz4::block::CompressionMode::DEFAULT,
+            };
+            lz4::block::compress(data, Some(mode), true).wrap_err("lz4 compression failed")?
+        }
+        Compression::Zstd => {
+            // level 0 selects zstd's default level
+            zstd::bulk::compress(data, settings.level.unwrap_or(0))
+                .wrap_err("zstd compression failed")?
+        }
+    };
+    Ok(AVec::from_slice(128, &compressed))
+}
+
+pub fn decompress(data: &[u8], algorithm: Compression) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+    let decompressed = match algorithm {
+        // the uncompressed size is prepended by `compress`
+        Compression::Lz4 => {
+            lz4::block::decompress(data, None).wrap_err("lz4 decompression failed")?
+        }
+        Compression::Zstd => {
+            zstd::stream::decode_all(data).wrap_err("zstd decompression failed")?
+        }
+    };
+    Ok(AVec::from_slice(128, &decompressed))
+}
@@ -35,7 +35,7 @@ pub async fn register(
         .wrap_err("failed to set TCP_NODELAY")?;
     let register = serde_json::to_vec(&Timestamped {
         inner: CoordinatorRequest::Register {
-            dora_version: env!("CARGO_PKG_VERSION").to_owned(),
+            dora_version: dora_core::version::current(),
             machine_id,
             listen_port,
         },
@@ -49,7 +49,10 @@ pub async fn register(
         .wrap_err("failed to register reply from dora-coordinator")?;
     let result: Timestamped<RegisterResult> = serde_json::from_slice(&reply_raw)
         .wrap_err("failed to deserialize dora-coordinator reply")?;
-    result.inner.to_result()?;
+    result
+        .inner
+        .to_result()
+        .wrap_err("dora-coordinator rejected the daemon registration")?;
     if let Err(err) = clock.update_with_timestamp(&result.timestamp) {
         tracing::warn!("failed to update timestamp after register: {err}");
     }
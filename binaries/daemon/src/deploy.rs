@@ -0,0 +1,190 @@
+//! Extraction of dataflow bundles that are created by `dora bundle`.
+
+use eyre::{bail, eyre, Context};
+use flate2::read::GzDecoder;
+use std::path::{Path, PathBuf};
+
+/// Manifest at the root of each bundle, see `dora bundle`.
+const MANIFEST_FILE: &str = "dora-bundle.json";
+
+/// Returns the directory that the bundle of the given name is extracted to.
+pub fn target_dir(name: &str, target_dir: Option<PathBuf>) -> eyre::Result<PathBuf> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        bail!("invalid bundle name `{name}`");
+    }
+    match target_dir {
+        Some(dir) if dir.is_absolute() => Ok(dir),
+        Some(dir) => bail!("target directory `{}` is not absolute", dir.display()),
+        None => dirs::home_dir()
+            .map(|home| home.join(".dora").join("bundles").join(name))
+            .ok_or_else(|| eyre!("failed to determine home directory")),
+    }
+}
+
+/// Extracts the `.tar.gz` bundle to `dir`, replacing its previous content.
+///
+/// Only empty directories and previous deployments of a bundle are replaced,
+/// so that a wrong target directory doesn't wipe unrelated files. The bundle
+/// is unpacked next to `dir` first, so that a corrupt bundle leaves the
+/// previous deployment intact.
+pub fn extract(bundle: &[u8], dir: &Path) -> eyre::Result<()> {
+    let file_name = dir
+        .file_name()
+        .ok_or_else(|| eyre!("invalid target directory `{}`", dir.display()))?;
+    check_replaceable(dir)?;
+    let staging = dir.with_file_name(format!(".{}.partial", file_name.to_string_lossy()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)
+            .wrap_err_with(|| format!("failed to remove `{}`", staging.display()))?;
+    }
+    std::fs::create_dir_all(&staging)
+        .wrap_err_with(|| format!("failed to create `{}`", staging.display()))?;
+
+    // entries that point outside of the staging directory are skipped by `unpack`
+    let mut archive = tar::Archive::new(GzDecoder::new(bundle));
+    archive.set_preserve_permissions(true);
+    if let Err(err) = archive.unpack(&staging) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(err).wrap_err("failed to unpack bundle");
+    }
+    if !staging.join(MANIFEST_FILE).is_file() {
+        let _ = std::fs::remove_dir_all(&staging);
+        bail!("bundle has no `{MANIFEST_FILE}` manifest");
+    }
+
+    if dir.exists() {
+        std::fs::remove_dir_all(dir).wrap_err_with(|| {
+            format!("failed to remove previous deployment `{}`", dir.display())
+        })?;
+    }
+    std::fs::rename(&staging, dir)
+        .wrap_err_with(|| format!("failed to move bundle to `{}`", dir.display()))
+}
+
+/// Fails if `dir` exists and is neither empty nor a previous deployment.
+fn check_replaceable(dir: &Path) -> eyre::Result<()> {
+    let metadata = match std::fs::symlink_metadata(dir) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| format!("failed to check `{}`", dir.display()))
+        }
+    };
+    if !metadata.is_dir() {
+        bail!("target `{}` exists and is not a directory", dir.display());
+    }
+    if dir.join(MANIFEST_FILE).is_file() {
+        return Ok(());
+    }
+    let mut entries =
+        std::fs::read_dir(dir).wrap_err_with(|| format!("failed to read `{}`", dir.display()))?;
+    if entries.next().is_some() {
+        bail!(
+            "target directory `{}` is not empty and contains no previous bundle \
+            (no `{MANIFEST_FILE}`), refusing to replace it",
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+
+    fn bundle(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn valid_bundle(content: &str) -> Vec<u8> {
+        bundle(&[(MANIFEST_FILE, "{}"), ("dataflow.yml", content)])
+    }
+
+    #[test]
+    fn extracts_to_new_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("bundle");
+        extract(&valid_bundle("a"), &dir).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("dataflow.yml")).unwrap(),
+            "a"
+        );
+    }
+
+    #[test]
+    fn extracts_to_empty_dir() {
+        let root = tempfile::tempdir().unwrap();
+        extract(&valid_bundle("a"), root.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("dataflow.yml")).unwrap(),
+            "a"
+        );
+    }
+
+    #[test]
+    fn replaces_previous_bundle() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("bundle");
+        extract(&bundle(&[(MANIFEST_FILE, "{}"), ("old.txt", "")]), &dir).unwrap();
+        extract(&valid_bundle("b"), &dir).unwrap();
+        assert!(!dir.join("old.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("dataflow.yml")).unwrap(),
+            "b"
+        );
+    }
+
+    #[test]
+    fn keeps_unrelated_dir() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("important.txt"), "data").unwrap();
+
+        let err = extract(&valid_bundle("a"), root.path()).unwrap_err();
+        assert!(err.to_string().contains("refusing to replace"), "{err:?}");
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("important.txt")).unwrap(),
+            "data"
+        );
+    }
+
+    #[test]
+    fn keeps_file() {
+        let root = tempfile::tempdir().unwrap();
+        let file = root.path().join("bundle");
+        std::fs::write(&file, "data").unwrap();
+
+        let err = extract(&valid_bundle("a"), &file).unwrap_err();
+        assert!(err.to_string().contains("not a directory"), "{err:?}");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "data");
+    }
+
+    #[test]
+    fn rejects_bundle_without_manifest() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("bundle");
+        extract(&valid_bundle("a"), &dir).unwrap();
+
+        let err = extract(&bundle(&[("dataflow.yml", "b")]), &dir).unwrap_err();
+        assert!(err.to_string().contains("no `dora-bundle.json`"), "{err:?}");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("dataflow.yml")).unwrap(),
+            "a"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Enforces the `downsample` settings of inputs.
+
+use dora_core::config::Downsample;
+use std::time::{Duration, Instant};
+
+pub struct Downsampler {
+    config: Downsample,
+    received: u64,
+    /// Earliest time at which the next message is delivered, if limited.
+    next_due: Option<Instant>,
+}
+
+impl Downsampler {
+    pub fn new(config: Downsample) -> Self {
+        Self {
+            config,
+            received: 0,
+            next_due: None,
+        }
+    }
+
+    pub fn config(&self) -> Downsample {
+        self.config
+    }
+
+    /// Returns whether the next message, received at `now`, should be delivered.
+    pub fn accept(&mut self, now: Instant) -> bool {
+        let index = self.received;
+        self.received += 1;
+        if let Some(every) = self.config.every {
+            if index % u64::from(every.get()) != 0 {
+                return false;
+            }
+        }
+        if let Some(frequency) = self.config.max_frequency {
+            if self.next_due.is_some_and(|due| now < due) {
+                return false;
+            }
+            let interval = Duration::from_secs(1) / frequency.get();
+            // keep the cadence instead of drifting by the message jitter, but
+            // don't catch up after gaps in the stream
+            self.next_due = Some(match self.next_due {
+                Some(due) if now < due + interval => due + interval,
+                _ => now + interval,
+            });
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU32;
+
+    fn downsampler(every: Option<u32>, max_frequency: Option<u32>) -> Downsampler {
+        Downsampler::new(Downsample {
+            every: every.and_then(NonZeroU32::new),
+            max_frequency: max_frequency.and_then(NonZeroU32::new),
+        })
+    }
+
+    /// Feeds messages at the given millisecond offsets and returns the
+    /// offsets of the delivered ones.
+    fn delivered(downsampler: &mut Downsampler, start: Instant, times: &[u64]) -> Vec<u64> {
+        times
+            .iter()
+            .copied()
+            .filter(|&t| downsampler.accept(start + Duration::from_millis(t)))
+            .collect()
+    }
+
+    #[test]
+    fn every_nth_message() {
+        let start = Instant::now();
+        let mut downsampler = downsampler(Some(3), None);
+        let times: Vec<_> = (0..7).collect();
+        assert_eq!(delivered(&mut downsampler, start, &times), [0, 3, 6]);
+    }
+
+    #[test]
+    fn every_combined_with_max_frequency() {
+        let start = Instant::now();
+        let mut downsampler = downsampler(Some(2), Some(10));
+        // 50 Hz input: `every` halves it to 25 Hz, then `max_frequency`
+        // limits it to 10 Hz
+        let times: Vec<_> = (0..16).map(|i| i * 20).collect();
+        assert_eq!(delivered(&mut downsampler, start, &times), [0, 120, 200]);
+    }
+
+    #[test]
+    fn max_frequency_keeps_cadence() {
+        let start = Instant::now();
+        let mut downsampler = downsampler(None, Some(10));
+        // late messages don't shift the following slots
+        let times = [0, 130, 180, 210, 290, 300];
+        assert_eq!(
+            delivered(&mut downsampler, start, &times),
+            [0, 130, 210, 300]
+        );
+    }
+
+    #[test]
+    fn no_catch_up_after_gap() {
+        let start = Instant::now();
+        let mut downsampler = downsampler(None, Some(10));
+        // after a gap in the stream, the next slot is one interval after the
+        // first message instead of all the missed slots being delivered
+        let times = [0, 100, 1000, 1020, 1050, 1090, 1100];
+        assert_eq!(
+            delivered(&mut downsampler, start, &times),
+            [0, 100, 1000, 1100]
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//! Encryption of inter-daemon events with a pre-shared key per dataflow.
+//!
+//! Events are encrypted with XChaCha20-Poly1305. The nonce consists of a
+//! random prefix, chosen when the key is loaded, and a counter that increases
+//! with each event. The dataflow ID and the sending machine are passed as
+//! associated data, so an event can't be replayed into another dataflow that
+//! happens to use the same key.
+//!
+//! Receivers keep a window of the most recent counters per sending machine
+//! and drop events whose counter was seen already or is older than the
+//! window, so captured events can't be replayed into the same dataflow either.
+//! The counter starts at the current time in nanoseconds, so that it keeps
+//! increasing when a daemon is restarted.
+
+use crate::clock_sync;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use dora_core::daemon_messages::{DataflowId, InterDaemonEvent};
+use eyre::{bail, eyre, Context};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const PREFIX_LEN: usize = 16;
+/// Number of counters below the highest seen counter that are still accepted,
+/// to allow for events that are reordered in transit.
+const REPLAY_WINDOW: u64 = 64;
+
+pub struct DataflowCipher {
+    cipher: XChaCha20Poly1305,
+    machine_id: String,
+    nonce_prefix: [u8; PREFIX_LEN],
+    counter: AtomicU64,
+    /// Received counters per sending machine.
+    received: HashMap<String, ReplayWindow>,
+}
+
+impl DataflowCipher {
+    /// Reads the hex-encoded key from the given file.
+    pub async fn load(key_file: &Path, machine_id: String) -> eyre::Result<Self> {
+        let content = tokio::fs::read_to_string(key_file)
+            .await
+            .wrap_err_with(|| format!("failed to read key file `{}`", key_file.display()))?;
+        let key = parse_hex_key(content.trim())
+            .wrap_err_with(|| format!("invalid key in `{}`", key_file.display()))?;
+        Ok(Self::new(&key, machine_id))
+    }
+
+    fn new(key: &[u8; 32], machine_id: String) -> Self {
+        let mut nonce_prefix = [0; PREFIX_LEN];
+        OsRng.fill_bytes(&mut nonce_prefix);
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+            machine_id,
+            nonce_prefix,
+            counter: AtomicU64::new(clock_sync::now()),
+            received: HashMap::new(),
+        }
+    }
+
+    pub fn encrypt(
+        &self,
+        dataflow_id: DataflowId,
+        event: &InterDaemonEvent,
+    ) -> eyre::Result<InterDaemonEvent> {
+        let plaintext = bincode::serialize(event).wrap_err("failed to serialize event")?;
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        let mut nonce = [0; 24];
+        nonce[..PREFIX_LEN].copy_from_slice(&self.nonce_prefix);
+        nonce[PREFIX_LEN..].copy_from_slice(&counter.to_be_bytes());
+        let payload = Payload {
+            msg: &plaintext,
+            aad: &associated_data(dataflow_id, &self.machine_id),
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| eyre!("failed to encrypt event"))?;
+        Ok(InterDaemonEvent::Encrypted {
+            dataflow_id,
+            sender: self.machine_id.clone(),
+            droppable: !matches!(event, InterDaemonEvent::InputsClosed { .. }),
+            nonce,
+            ciphertext,
+        })
+    }
+
+    pub fn decrypt(
+        &mut self,
+        dataflow_id: DataflowId,
+        sender: &str,
+        nonce: &[u8; 24],
+        ciphertext: &[u8],
+    ) -> eyre::Result<InterDaemonEvent> {
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &associated_data(dataflow_id, sender),
+        };
+        let plaintext = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| eyre!("failed to decrypt event: authentication failed"))?;
+        let event = bincode::deserialize(&plaintext).wrap_err("failed to deserialize event")?;
+        let expected = match &event {
+            InterDaemonEvent::Output {
+                dataflow_id: inner_id,
+                ..
+            }
+            | InterDaemonEvent::InputsClosed {
+                dataflow_id: inner_id,
+                ..
+            } => *inner_id == dataflow_id,
+            InterDaemonEvent::ClockSyncRequest { machine_id, .. }
+            | InterDaemonEvent::ClockSyncReply { machine_id, .. } => machine_id == sender,
+            InterDaemonEvent::Encrypted { .. } => false,
+        };
+        if !expected {
+            bail!("unexpected encrypted event");
+        }
+
+        // only authenticated counters may advance the window
+        let mut counter = [0; 8];
+        counter.copy_from_slice(&nonce[PREFIX_LEN..]);
+        let counter = u64::from_be_bytes(counter);
+        if !self
+            .received
+            .entry(sender.to_owned())
+            .or_default()
+            .accept(counter)
+        {
+            bail!("dropping replayed or outdated event from `{sender}`");
+        }
+        Ok(event)
+    }
+}
+
+fn associated_data(dataflow_id: DataflowId, sender: &str) -> Vec<u8> {
+    let mut data = dataflow_id.as_bytes().to_vec();
+    data.extend_from_slice(sender.as_bytes());
+    data
+}
+
+/// Tracks which of the most recent counters of a sender were received, like
+/// the anti-replay window of IPsec.
+#[derive(Debug, Default)]
+struct ReplayWindow {
+    highest: u64,
+    /// Bit `i` is set if `highest - i` was received.
+    seen: u64,
+}
+
+impl ReplayWindow {
+    /// Returns `false` if the counter was received before or is too old.
+    fn accept(&mut self, counter: u64) -> bool {
+        if counter > self.highest {
+            let shift = counter - self.highest;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.highest = counter;
+            true
+        } else {
+            let age = self.highest - counter;
+            if age >= REPLAY_WINDOW || self.seen & (1 << age) != 0 {
+                return false;
+            }
+            self.seen |= 1 << age;
+            true
+        }
+    }
+}
+
+fn parse_hex_key(hex: &str) -> eyre::Result<[u8; 32]> {
+    if hex.len() != 64 {
+        bail!("expected 64 hex digits, found {} characters", hex.len());
+    }
+    let mut key = [0; 32];
+    for (byte, digits) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let digits = std::str::from_utf8(digits).wrap_err("key is not valid UTF-8")?;
+        *byte = u8::from_str_radix(digits, 16)
+            .wrap_err_with(|| format!("invalid hex digits `{digits}`"))?;
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    fn inputs_closed(dataflow_id: DataflowId) -> InterDaemonEvent {
+        InterDaemonEvent::InputsClosed {
+            dataflow_id,
+            inputs: BTreeSet::from([("node".to_owned().into(), "input".to_owned().into())]),
+            sources: BTreeSet::new(),
+        }
+    }
+
+    fn ciphers() -> (DataflowCipher, DataflowCipher) {
+        let key = parse_hex_key(KEY).unwrap();
+        (
+            DataflowCipher::new(&key, "a".into()),
+            DataflowCipher::new(&key, "b".into()),
+        )
+    }
+
+    fn decrypt(
+        receiver: &mut DataflowCipher,
+        dataflow_id: DataflowId,
+        event: &InterDaemonEvent,
+    ) -> eyre::Result<InterDaemonEvent> {
+        let InterDaemonEvent::Encrypted {
+            sender,
+            nonce,
+            ciphertext,
+            ..
+        } = event
+        else {
+            panic!("expected encrypted event, got {event:?}");
+        };
+        receiver.decrypt(dataflow_id, sender, nonce, ciphertext)
+    }
+
+    #[test]
+    fn parse_key() {
+        let key = parse_hex_key(KEY).unwrap();
+        assert_eq!(key[0], 0x00);
+        assert_eq!(key[10], 0x0a);
+        assert_eq!(key[31], 0x1f);
+        assert_eq!(parse_hex_key(&KEY.to_uppercase()).unwrap(), key);
+
+        let err = parse_hex_key(&KEY[2..]).unwrap_err();
+        assert!(err.to_string().contains("expected 64 hex digits"), "{err}");
+        let err = parse_hex_key(&format!("zz{}", &KEY[2..])).unwrap_err();
+        assert!(err.to_string().contains("invalid hex digits `zz`"), "{err}");
+        // 64 bytes, but the first pair splits a multi-byte character
+        let err = parse_hex_key(&format!("0ä{}", &KEY[3..])).unwrap_err();
+        assert!(err.to_string().contains("not valid UTF-8"), "{err}");
+    }
+
+    #[test]
+    fn round_trip() {
+        let (sender, mut receiver) = ciphers();
+        let dataflow_id = DataflowId::from_u128(1);
+        let event = inputs_closed(dataflow_id);
+        let encrypted = sender.encrypt(dataflow_id, &event).unwrap();
+        assert!(matches!(
+            encrypted,
+            InterDaemonEvent::Encrypted {
+                droppable: false,
+                ..
+            }
+        ));
+
+        let decrypted = decrypt(&mut receiver, dataflow_id, &encrypted).unwrap();
+        assert_eq!(
+            bincode::serialize(&decrypted).unwrap(),
+            bincode::serialize(&event).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_replay() {
+        let (sender, mut receiver) = ciphers();
+        let dataflow_id = DataflowId::from_u128(1);
+        let first = sender
+            .encrypt(dataflow_id, &inputs_closed(dataflow_id))
+            .unwrap();
+        let second = sender
+            .encrypt(dataflow_id, &inputs_closed(dataflow_id))
+            .unwrap();
+
+        // reordered events are accepted once
+        decrypt(&mut receiver, dataflow_id, &second).unwrap();
+        decrypt(&mut receiver, dataflow_id, &first).unwrap();
+        assert!(decrypt(&mut receiver, dataflow_id, &first).is_err());
+        assert!(decrypt(&mut receiver, dataflow_id, &second).is_err());
+    }
+
+    #[test]
+    fn rejects_other_dataflow_and_tampering() {
+        let (sender, mut receiver) = ciphers();
+        let dataflow_id = DataflowId::from_u128(1);
+        let encrypted = sender
+            .encrypt(dataflow_id, &inputs_closed(dataflow_id))
+            .unwrap();
+        assert!(decrypt(&mut receiver, DataflowId::from_u128(2), &encrypted).is_err());
+
+        let InterDaemonEvent::Encrypted {
+            sender: sender_id,
+            nonce,
+            mut ciphertext,
+            ..
+        } = encrypted
+        else {
+            unreachable!()
+        };
+        assert!(receiver
+            .decrypt(dataflow_id, "c", &nonce, &ciphertext)
+            .is_err());
+        ciphertext[0] ^= 1;
+        assert!(receiver
+            .decrypt(dataflow_id, &sender_id, &nonce, &ciphertext)
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_other_key() {
+        let (sender, _) = ciphers();
+        let mut receiver = DataflowCipher::new(&[7; 32], "b".into());
+        let dataflow_id = DataflowId::from_u128(1);
+        let encrypted = sender
+            .encrypt(dataflow_id, &inputs_closed(dataflow_id))
+            .unwrap();
+        assert!(decrypt(&mut receiver, dataflow_id, &encrypted).is_err());
+    }
+
+    #[test]
+    fn clock_sync_sender_must_match() {
+        let (sender, mut receiver) = ciphers();
+        let dataflow_id = DataflowId::from_u128(1);
+        let spoofed = InterDaemonEvent::ClockSyncRequest {
+            machine_id: "c".into(),
+            request_sent: 1,
+        };
+        let encrypted = sender.encrypt(dataflow_id, &spoofed).unwrap();
+        assert!(decrypt(&mut receiver, dataflow_id, &encrypted).is_err());
+
+        let request = InterDaemonEvent::ClockSyncRequest {
+            machine_id: "a".into(),
+            request_sent: 1,
+        };
+        let encrypted = sender.encrypt(dataflow_id, &request).unwrap();
+        decrypt(&mut receiver, dataflow_id, &encrypted).unwrap();
+    }
+
+    #[test]
+    fn replay_window() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(100));
+        assert!(!window.accept(100));
+        assert!(window.accept(102));
+        assert!(window.accept(101));
+        assert!(!window.accept(101));
+        // older than the window
+        assert!(window.accept(102 + REPLAY_WINDOW));
+        assert!(!window.accept(102));
+        assert!(window.accept(103));
+        // jumps beyond the window reset it
+        assert!(window.accept(1000));
+        assert!(!window.accept(103 + REPLAY_WINDOW));
+        assert!(window.accept(1000 - REPLAY_WINDOW + 1));
+    }
+}
@@ -1,36 +1,197 @@
-use crate::tcp_utils::{tcp_receive, tcp_send};
-use dora_core::daemon_messages::{InterDaemonEvent, Timestamped};
+use crate::{
+    tcp_utils::{tcp_receive, tcp_send},
+    OutputId,
+};
+use dora_core::daemon_messages::{DataflowId, InterDaemonEvent, Timestamped};
 use eyre::{Context, ContextCompat};
-use std::{collections::BTreeMap, io::ErrorKind, net::SocketAddr};
-use tokio::net::{TcpListener, TcpStream};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::ErrorKind,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+};
+
+/// Number of queued events per target machine from which on best-effort
+/// events are dropped.
+///
+/// When the queue is full, further best-effort outputs are dropped until the
+/// remote daemon catches up.
+const SEND_QUEUE_SIZE: usize = 1024;
+/// Maximum number of events that are queued per target machine, including
+/// control events such as `InputsClosed` and reliable outputs.
+///
+/// Guaranteed events are queued without waiting for the remote daemon, as
+/// waiting would block the event loop of the daemon. They are only dropped
+/// when the remote daemon stays unreachable for so long that the queue grows
+/// beyond this size.
+const MAX_QUEUED_EVENTS: usize = 16 * SEND_QUEUE_SIZE;
+/// Maximum number of best-effort outputs that are queued per edge, i.e. per
+/// output and target machine.
+///
+/// Keeps a single high-rate output from filling the whole send queue and
+/// starving the other outputs that are sent to the same machine.
+const EDGE_QUEUE_SIZE: usize = 64;
+/// Number of send attempts for a best-effort event before it is dropped.
+const MAX_SEND_ATTEMPTS: u32 = 5;
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const KEEPALIVE_TIME: Duration = Duration::from_secs(10);
+
+type Edge = (DataflowId, OutputId);
 
 pub struct InterDaemonConnection {
     socket: SocketAddr,
-    connection: Option<TcpStream>,
+    queue: mpsc::UnboundedSender<QueuedEvent>,
+    /// Number of events in the queue, including the one that is being sent.
+    queued: Arc<AtomicUsize>,
+    edges: HashMap<Edge, EdgeQueue>,
+    /// Number of guaranteed events that were dropped because the queue was
+    /// full, since the queue last had space.
+    dropped_guaranteed: u64,
+    /// Stops the retries of the send loop when the connection is dropped.
+    _closed: oneshot::Sender<()>,
+}
+
+/// How hard the send loop tries to deliver an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delivery {
+    /// Dropped when the queue is full or after [`MAX_SEND_ATTEMPTS`].
+    BestEffort,
+    /// Retried until the connection is dropped, only dropped when more than
+    /// [`MAX_QUEUED_EVENTS`] events are queued.
+    ///
+    /// Used for control events, since losing e.g. an `InputsClosed` event
+    /// would keep the receiving nodes waiting forever.
+    Guaranteed,
+}
+
+struct QueuedEvent {
+    message: Arc<Vec<u8>>,
+    delivery: Delivery,
+    /// Free the slots in the send queue and the edge queue once the event is
+    /// sent or dropped.
+    _slot: QueueSlot,
+    _edge_slot: Option<QueueSlot>,
+}
+
+#[derive(Default)]
+struct EdgeQueue {
+    queued: Arc<AtomicUsize>,
+    dropped: u64,
+}
+
+/// Counts an event as queued until it is dropped.
+struct QueueSlot(Arc<AtomicUsize>);
+
+impl QueueSlot {
+    fn take(queued: &Arc<AtomicUsize>) -> Self {
+        queued.fetch_add(1, Ordering::AcqRel);
+        Self(queued.clone())
+    }
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
 }
 
 impl InterDaemonConnection {
     pub fn new(socket: SocketAddr) -> Self {
+        let (queue, queue_rx) = mpsc::unbounded_channel();
+        let (closed_tx, closed) = oneshot::channel();
+        tokio::spawn(send_loop(socket, queue_rx, closed));
         Self {
             socket,
-            connection: None,
+            queue,
+            queued: Default::default(),
+            edges: HashMap::new(),
+            dropped_guaranteed: 0,
+            _closed: closed_tx,
         }
     }
 
-    #[tracing::instrument(skip(self), fields(%self.socket))]
-    async fn connect(&mut self) -> eyre::Result<&mut TcpStream> {
-        match &mut self.connection {
-            Some(c) => Ok(c),
-            entry @ None => {
-                let connection = TcpStream::connect(self.socket)
-                    .await
-                    .wrap_err("failed to connect")?;
-                connection
-                    .set_nodelay(true)
-                    .wrap_err("failed to set nodelay")?;
-                Ok(entry.insert(connection))
+    /// Queues the given event for sending, without waiting for the remote
+    /// daemon.
+    fn send(
+        &mut self,
+        message: Arc<Vec<u8>>,
+        delivery: Delivery,
+        edge: Option<&Edge>,
+    ) -> eyre::Result<()> {
+        let queued = self.queued.load(Ordering::Acquire);
+        if delivery == Delivery::Guaranteed {
+            if queued >= MAX_QUEUED_EVENTS {
+                if self.dropped_guaranteed == 0 {
+                    tracing::error!(
+                        "dropping control events and reliable outputs for {} because \
+                        {queued} events are queued for it, the remote daemon seems \
+                        to be unreachable",
+                        self.socket
+                    );
+                }
+                self.dropped_guaranteed += 1;
+                return Ok(());
             }
+            if self.dropped_guaranteed > 0 {
+                tracing::warn!(
+                    "dropped {} control events and reliable outputs for {}",
+                    self.dropped_guaranteed,
+                    self.socket
+                );
+                self.dropped_guaranteed = 0;
+            }
+            let event = QueuedEvent {
+                message,
+                delivery,
+                _slot: QueueSlot::take(&self.queued),
+                _edge_slot: None,
+            };
+            return self
+                .queue
+                .send(event)
+                .map_err(|_| eyre::eyre!("send loop exited"));
         }
+
+        let mut edge_queue = edge.map(|edge| (edge, self.edges.entry(edge.clone()).or_default()));
+        if let Some((_, edge_queue)) = &mut edge_queue {
+            if queued >= SEND_QUEUE_SIZE
+                || edge_queue.queued.load(Ordering::Acquire) >= EDGE_QUEUE_SIZE
+            {
+                edge_queue.dropped += 1;
+                return Ok(());
+            }
+        } else if queued >= SEND_QUEUE_SIZE {
+            return Ok(());
+        }
+        let event = QueuedEvent {
+            message,
+            delivery,
+            _slot: QueueSlot::take(&self.queued),
+            _edge_slot: edge_queue.as_ref().map(|(_, q)| QueueSlot::take(&q.queued)),
+        };
+        self.queue
+            .send(event)
+            .map_err(|_| eyre::eyre!("send loop exited"))?;
+        if let Some(((dataflow_id, OutputId(node_id, output_id)), edge_queue)) = edge_queue {
+            if edge_queue.dropped > 0 {
+                tracing::warn!(
+                    "dropped {} outputs of `{node_id}/{output_id}` (dataflow \
+                    `{dataflow_id}`) for {} because the send queue was full",
+                    edge_queue.dropped,
+                    self.socket
+                );
+                edge_queue.dropped = 0;
+            }
+        }
+        Ok(())
     }
 
     pub fn socket(&self) -> SocketAddr {
@@ -38,22 +199,153 @@ impl InterDaemonConnection {
     }
 }
 
+/// Sends queued events to the given socket, reconnecting on errors.
+#[tracing::instrument(skip(queue, closed))]
+async fn send_loop(
+    socket: SocketAddr,
+    mut queue: mpsc::UnboundedReceiver<QueuedEvent>,
+    mut closed: oneshot::Receiver<()>,
+) {
+    let mut connection = None;
+    while let Some(event) = queue.recv().await {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let result = match &mut connection {
+                Some(c) => tcp_send(c, &event.message)
+                    .await
+                    .wrap_err("failed to send event"),
+                None => match connect(socket).await {
+                    Ok(c) => {
+                        let c = connection.insert(c);
+                        tcp_send(c, &event.message)
+                            .await
+                            .wrap_err("failed to send event")
+                    }
+                    Err(err) => Err(err),
+                },
+            };
+            let Err(err) = result else {
+                break;
+            };
+            connection = None;
+            if attempt >= MAX_SEND_ATTEMPTS {
+                match event.delivery {
+                    Delivery::BestEffort => {
+                        tracing::warn!(
+                            "dropping inter-daemon event after {attempt} attempts: {err:?}"
+                        );
+                        break;
+                    }
+                    Delivery::Guaranteed if attempt == MAX_SEND_ATTEMPTS => {
+                        tracing::error!(
+                            "failed to send inter-daemon event after {attempt} attempts, \
+                            retrying until the connection is closed: {err:?}"
+                        );
+                    }
+                    Delivery::Guaranteed => {}
+                }
+            }
+            let delay = (Duration::from_millis(100) * 2u32.pow(attempt.min(MAX_SEND_ATTEMPTS)))
+                .min(MAX_RECONNECT_DELAY);
+            tracing::debug!("retrying in {delay:?}: {err:?}");
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = &mut closed => {
+                    tracing::warn!("connection was closed with undelivered events");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+async fn connect(socket: SocketAddr) -> eyre::Result<TcpStream> {
+    let connection = TcpStream::connect(socket)
+        .await
+        .wrap_err("failed to connect")?;
+    configure_connection(&connection)?;
+    Ok(connection)
+}
+
+fn configure_connection(connection: &TcpStream) -> eyre::Result<()> {
+    connection
+        .set_nodelay(true)
+        .wrap_err("failed to set nodelay")?;
+    socket2::SockRef::from(connection)
+        .set_tcp_keepalive(&socket2::TcpKeepalive::new().with_time(KEEPALIVE_TIME))
+        .wrap_err("failed to enable keepalive")?;
+    Ok(())
+}
+
+/// Sends a control event, e.g. `InputsClosed`, or a clock sync event.
 #[tracing::instrument(skip(inter_daemon_connections))]
-pub async fn send_inter_daemon_event(
+pub fn send_inter_daemon_event(
+    target_machines: &[String],
+    inter_daemon_connections: &mut BTreeMap<String, InterDaemonConnection>,
+    event: &Timestamped<InterDaemonEvent>,
+) -> eyre::Result<()> {
+    // clock sync samples are useless once they are delayed by retries
+    let delivery = match event.inner {
+        InterDaemonEvent::Output { .. }
+        | InterDaemonEvent::Encrypted {
+            droppable: true, ..
+        }
+        | InterDaemonEvent::ClockSyncRequest { .. }
+        | InterDaemonEvent::ClockSyncReply { .. } => Delivery::BestEffort,
+        InterDaemonEvent::InputsClosed { .. }
+        | InterDaemonEvent::Encrypted {
+            droppable: false, ..
+        } => Delivery::Guaranteed,
+    };
+    send_event(
+        target_machines,
+        inter_daemon_connections,
+        event,
+        delivery,
+        None,
+    )
+}
+
+/// Sends an output of the given dataflow, subject to the flow control of its
+/// edge.
+///
+/// Outputs that have receivers with a reliable QoS preset are never dropped.
+pub fn send_output_event(
+    target_machines: &[String],
+    inter_daemon_connections: &mut BTreeMap<String, InterDaemonConnection>,
+    event: &Timestamped<InterDaemonEvent>,
+    edge: (DataflowId, &OutputId),
+    reliable: bool,
+) -> eyre::Result<()> {
+    let (delivery, edge) = if reliable {
+        (Delivery::Guaranteed, None)
+    } else {
+        (Delivery::BestEffort, Some((edge.0, edge.1.clone())))
+    };
+    send_event(
+        target_machines,
+        inter_daemon_connections,
+        event,
+        delivery,
+        edge.as_ref(),
+    )
+}
+
+fn send_event(
     target_machines: &[String],
     inter_daemon_connections: &mut BTreeMap<String, InterDaemonConnection>,
     event: &Timestamped<InterDaemonEvent>,
+    delivery: Delivery,
+    edge: Option<&Edge>,
 ) -> eyre::Result<()> {
-    let message = bincode::serialize(event).wrap_err("failed to serialize InterDaemonEvent")?;
+    let message =
+        Arc::new(bincode::serialize(event).wrap_err("failed to serialize InterDaemonEvent")?);
     for target_machine in target_machines {
-        let connection = inter_daemon_connections
+        inter_daemon_connections
             .get_mut(target_machine)
             .wrap_err_with(|| format!("unknown target machine `{target_machine}`"))?
-            .connect()
-            .await
-            .wrap_err_with(|| format!("failed to connect to machine `{target_machine}`"))?;
-        tcp_send(connection, &message)
-            .await
+            .send(message.clone(), delivery, edge)
             .wrap_err_with(|| format!("failed to send event to machine `{target_machine}`"))?;
     }
 
@@ -108,8 +400,8 @@ async fn handle_connection_loop(
     mut connection: TcpStream,
     events_tx: flume::Sender<Timestamped<InterDaemonEvent>>,
 ) {
-    if let Err(err) = connection.set_nodelay(true) {
-        tracing::warn!("failed to set nodelay for connection: {err}");
+    if let Err(err) = configure_connection(&connection) {
+        tracing::warn!("failed to configure connection: {err:?}");
     }
 
     loop {
@@ -147,3 +439,63 @@ async fn receive_message(
         .wrap_err("failed to deserialize DaemonRequest")
         .map(Some)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreachable_addr() -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    fn edge() -> Edge {
+        (
+            DataflowId::now_v7(),
+            OutputId("node".to_owned().into(), "out".to_owned().into()),
+        )
+    }
+
+    // the send loop doesn't run in between, since the tests don't await
+
+    #[tokio::test]
+    async fn limits_best_effort_events_per_edge() {
+        let mut connection = InterDaemonConnection::new(unreachable_addr());
+        let message = Arc::new(Vec::new());
+        let edge = edge();
+        for _ in 0..EDGE_QUEUE_SIZE + 10 {
+            connection
+                .send(message.clone(), Delivery::BestEffort, Some(&edge))
+                .unwrap();
+        }
+        assert_eq!(connection.queued.load(Ordering::Acquire), EDGE_QUEUE_SIZE);
+        assert_eq!(connection.edges[&edge].dropped, 10);
+    }
+
+    #[tokio::test]
+    async fn queues_guaranteed_events_without_waiting() {
+        let mut connection = InterDaemonConnection::new(unreachable_addr());
+        let message = Arc::new(Vec::new());
+        for _ in 0..SEND_QUEUE_SIZE + 10 {
+            connection
+                .send(message.clone(), Delivery::BestEffort, None)
+                .unwrap();
+        }
+        assert_eq!(connection.queued.load(Ordering::Acquire), SEND_QUEUE_SIZE);
+
+        // guaranteed events don't wait for space in the full queue
+        for _ in SEND_QUEUE_SIZE..MAX_QUEUED_EVENTS {
+            connection
+                .send(message.clone(), Delivery::Guaranteed, None)
+                .unwrap();
+        }
+        assert_eq!(connection.queued.load(Ordering::Acquire), MAX_QUEUED_EVENTS);
+        assert_eq!(connection.dropped_guaranteed, 0);
+
+        connection
+            .send(message.clone(), Delivery::Guaranteed, None)
+            .unwrap();
+        assert_eq!(connection.queued.load(Ordering::Acquire), MAX_QUEUED_EVENTS);
+        assert_eq!(connection.dropped_guaranteed, 1);
+    }
+}
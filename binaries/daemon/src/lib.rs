@@ -1,15 +1,21 @@
 use aligned_vec::{AVec, ConstAlign};
 use coordinator::CoordinatorEvent;
 use crossbeam::queue::ArrayQueue;
-use dora_core::config::{Input, OperatorId};
+use dora_arrow_convert::gpu::is_cuda_ipc_tensor;
+use dora_core::config::{
+    Downsample, Input, InputCompression, NodeRunConfig, OperatorId, UserInputMapping,
+};
 use dora_core::coordinator_messages::{CoordinatorRequest, Level, LogMessage};
 use dora_core::daemon_messages::{
-    DataMessage, DynamicNodeEvent, InterDaemonEvent, NodeConfig, Timestamped,
+    DataMessage, DynamicNodeEvent, InterDaemonEvent, LogChunk, NodeConfig, Timestamped,
 };
-use dora_core::descriptor::runtime_node_inputs;
+use dora_core::descriptor::{runtime_node_inputs, RecordConfig};
 use dora_core::message::uhlc::{self, HLC};
-use dora_core::message::{ArrowTypeInfo, Metadata, MetadataParameters};
-use dora_core::topics::LOCALHOST;
+use dora_core::message::{
+    ArrowTypeInfo, MessagePriority, Metadata, MetadataParameters, SendTime, SOURCE_PARAMETER,
+};
+use dora_core::record::RecordingInfo;
+use dora_core::topics::{AttachedNode, LOCALHOST};
 use dora_core::topics::{
     DataflowDaemonResult, DataflowResult, NodeError, NodeErrorCause, NodeExitStatus,
 };
@@ -20,20 +26,26 @@ use dora_core::{
         self, DaemonCoordinatorEvent, DaemonCoordinatorReply, DaemonReply, DataflowId, DropToken,
         SpawnDataflowNodes,
     },
-    descriptor::{CoreNodeKind, Descriptor, ResolvedNode},
+    descriptor::{
+        CoreNodeKind, Descriptor, NodeHeartbeat, OperatorDefinition, ResolvedNode,
+        ResourceLimitAction, ResourceLimits, RuntimeNode, WatchdogAction,
+    },
 };
 
+use communication_layer_pub_sub::Reliability;
 use eyre::{bail, eyre, Context, ContextCompat, Result};
 use futures::{future, stream, FutureExt, TryFutureExt};
 use futures_concurrency::stream::Merge;
 use inter_daemon::InterDaemonConnection;
 use local_listener::DynamicNodeEventWrapper;
+pub use local_listener::LocalListenConfig;
 use pending::PendingNodes;
 use shared_memory_server::ShmemConf;
 use std::sync::Arc;
 use std::time::Instant;
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{btree_map, hash_map, BTreeMap, BTreeSet, HashMap},
+    io::SeekFrom,
     net::SocketAddr,
     path::{Path, PathBuf},
     time::Duration,
@@ -41,7 +53,7 @@ use std::{
 use sysinfo::Pid;
 use tcp_utils::tcp_send;
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::Sender;
@@ -50,14 +62,26 @@ use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
 use tracing::{error, warn};
 use uuid::{NoContext, Timestamp, Uuid};
 
+mod clock_sync;
+mod compression;
 mod coordinator;
+mod deploy;
+mod downsample;
+mod encryption;
 mod inter_daemon;
 mod local_listener;
 mod log;
 mod node_communication;
 mod pending;
+mod pub_sub_transport;
+mod rate_limit;
+mod record;
+mod replay;
+mod resources;
 mod spawn;
+mod stats;
 mod tcp_utils;
+mod upgrade;
 
 #[cfg(feature = "telemetry")]
 use dora_tracing::telemetry::serialize_context;
@@ -67,10 +91,14 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 use crate::pending::DataflowStatus;
 
 const STDERR_LOG_LINES: usize = 10;
+/// Time between sending `SIGTERM` to a node and killing it.
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(2);
 
 pub struct Daemon {
     running: HashMap<DataflowId, RunningDataflow>,
     working_dir: HashMap<DataflowId, PathBuf>,
+    /// Kept after dataflows finish, like the log files themselves.
+    log_indexes: HashMap<(DataflowId, NodeId), log::LogIndex>,
 
     events_tx: mpsc::Sender<Timestamped<Event>>,
 
@@ -78,6 +106,14 @@ pub struct Daemon {
     last_coordinator_heartbeat: Instant,
     inter_daemon_connections: BTreeMap<String, InterDaemonConnection>,
     machine_id: String,
+    clock_sync: clock_sync::ClockSync,
+    resource_monitor: resources::ResourceMonitor,
+    process_monitor: resources::ProcessMonitor,
+    ctrlc_received: bool,
+    /// Set when the coordinator pushed a new daemon executable. The daemon stops
+    /// accepting new dataflows and restarts with the new executable once all
+    /// running dataflows are finished.
+    pending_upgrade: Option<upgrade::StagedUpgrade>,
 
     /// used for testing and examples
     exit_when_done: Option<BTreeSet<(Uuid, NodeId)>>,
@@ -94,7 +130,7 @@ impl Daemon {
         coordinator_addr: SocketAddr,
         machine_id: String,
         inter_daemon_addr: SocketAddr,
-        local_listen_port: u16,
+        local_listen: LocalListenConfig,
     ) -> eyre::Result<()> {
         let clock = Arc::new(HLC::default());
 
@@ -127,12 +163,28 @@ impl Daemon {
 
         // Spawn local listener loop
         let (events_tx, events_rx) = flume::bounded(10);
-        let _listen_port = local_listener::spawn_listener_loop(
-            (LOCALHOST, local_listen_port).into(),
-            machine_id.clone(),
-            events_tx,
-        )
-        .await?;
+        // removes the socket file when the daemon exits
+        #[cfg(unix)]
+        let _socket_file;
+        match local_listen {
+            LocalListenConfig::Tcp(port) => {
+                local_listener::spawn_listener_loop(
+                    (LOCALHOST, port).into(),
+                    machine_id.clone(),
+                    events_tx,
+                )
+                .await?;
+            }
+            #[cfg(unix)]
+            LocalListenConfig::Unix { addr, permissions } => {
+                _socket_file = Some(local_listener::spawn_unix_listener_loop(
+                    &addr,
+                    permissions,
+                    machine_id.clone(),
+                    events_tx,
+                )?);
+            }
+        }
         let dynamic_node_events = events_rx.into_stream().map(|e| Timestamped {
             inner: Event::DynamicNode(e.inner),
             timestamp: e.timestamp,
@@ -247,11 +299,17 @@ impl Daemon {
         let daemon = Self {
             running: HashMap::new(),
             working_dir: HashMap::new(),
+            log_indexes: HashMap::new(),
             events_tx: dora_events_tx,
             coordinator_connection,
             last_coordinator_heartbeat: Instant::now(),
             inter_daemon_connections: BTreeMap::new(),
             machine_id,
+            clock_sync: Default::default(),
+            resource_monitor: resources::ResourceMonitor::new(),
+            process_monitor: resources::ProcessMonitor::new(),
+            ctrlc_received: false,
+            pending_upgrade: None,
             exit_when_done,
             dataflow_node_results: BTreeMap::new(),
             clock,
@@ -306,6 +364,21 @@ impl Daemon {
                 },
                 Event::DynamicNode(event) => self.handle_dynamic_node_event(event).await?,
                 Event::HeartbeatInterval => {
+                    // checked on the heartbeat interval to give the upgrade reply
+                    // enough time to reach the coordinator
+                    if self.running.is_empty() {
+                        if let Some(upgrade) = self.pending_upgrade.take() {
+                            tracing::info!(
+                                "no running dataflows left -> restarting daemon as v{}",
+                                upgrade.version
+                            );
+                            let err = upgrade.install_and_restart();
+                            tracing::error!("{:?}", err.wrap_err("failed to upgrade daemon"));
+                        }
+                    }
+                    self.send_clock_sync_requests().await;
+                    self.check_resource_limits().await?;
+                    self.check_node_liveness().await?;
                     if let Some(connection) = &mut self.coordinator_connection {
                         let msg = serde_json::to_vec(&Timestamped {
                             inner: CoordinatorRequest::Event {
@@ -324,6 +397,18 @@ impl Daemon {
                     }
                 }
                 Event::CtrlC => {
+                    // a pending upgrade is cancelled by a stop signal
+                    if let Some(upgrade) = self.pending_upgrade.take() {
+                        upgrade.discard();
+                    }
+                    if self.ctrlc_received {
+                        tracing::warn!("received second stop signal -> killing all nodes");
+                        for dataflow in self.running.values_mut() {
+                            dataflow.kill_all();
+                        }
+                        break;
+                    }
+                    self.ctrlc_received = true;
                     for dataflow in self.running.values_mut() {
                         dataflow.stop_all(&self.clock, None).await;
                     }
@@ -335,17 +420,21 @@ impl Daemon {
     }
 
     async fn send_log_message(&mut self, message: LogMessage) -> eyre::Result<()> {
+        self.send_coordinator_event(DaemonEvent::Log(message)).await
+    }
+
+    async fn send_coordinator_event(&mut self, event: DaemonEvent) -> eyre::Result<()> {
         if let Some(connection) = &mut self.coordinator_connection {
             let msg = serde_json::to_vec(&Timestamped {
                 inner: CoordinatorRequest::Event {
                     machine_id: self.machine_id.clone(),
-                    event: DaemonEvent::Log(message),
+                    event,
                 },
                 timestamp: self.clock.new_timestamp(),
             })?;
             tcp_send(connection, &msg)
                 .await
-                .wrap_err("failed to send watchdog message to dora-coordinator")?;
+                .wrap_err("failed to send message to dora-coordinator")?;
 
             if self.last_coordinator_heartbeat.elapsed() > Duration::from_secs(20) {
                 bail!("lost connection to coordinator")
@@ -354,6 +443,121 @@ impl Daemon {
         Ok(())
     }
 
+    async fn check_resource_limits(&mut self) -> eyre::Result<()> {
+        if !self.running.values().any(|d| d.resource_limits.is_set()) {
+            return Ok(());
+        }
+        self.resource_monitor.refresh();
+
+        let mut log_messages = Vec::new();
+        for (dataflow_id, dataflow) in &mut self.running {
+            let Some(working_dir) = self.working_dir.get(dataflow_id) else {
+                continue;
+            };
+            if !dataflow.resource_limits.is_set() {
+                continue;
+            }
+            let exceeded = self
+                .resource_monitor
+                .check(&dataflow.resource_limits, working_dir);
+            if exceeded.is_some() == dataflow.resource_limit_exceeded {
+                continue;
+            }
+            dataflow.resource_limit_exceeded = exceeded.is_some();
+
+            let (level, message) = match exceeded {
+                Some(reason) => (Level::Error, format!("resource limit exceeded: {reason}")),
+                None => (
+                    Level::Info,
+                    "resource usage is back within limits".to_owned(),
+                ),
+            };
+            tracing::warn!("dataflow `{dataflow_id}`: {message}");
+            if dataflow
+                .resource_limits
+                .actions
+                .contains(&ResourceLimitAction::Alert)
+            {
+                log_messages.push(LogMessage {
+                    dataflow_id: *dataflow_id,
+                    node_id: None,
+                    level,
+                    target: None,
+                    module_path: None,
+                    file: None,
+                    line: None,
+                    message,
+                });
+            }
+        }
+        for log_message in log_messages {
+            self.send_log_message(log_message).await?;
+        }
+        Ok(())
+    }
+
+    async fn check_node_liveness(&mut self) -> eyre::Result<()> {
+        let mut log_messages = Vec::new();
+        let mut system = None;
+        for (dataflow_id, dataflow) in &mut self.running {
+            for (node_id, heartbeat) in &dataflow.heartbeats {
+                let Some(running_node) = dataflow.running_nodes.get(node_id) else {
+                    continue;
+                };
+                let reason = dataflow
+                    .node_stats
+                    .get(node_id)
+                    .and_then(|stats| stats.unresponsive(heartbeat.timeout()));
+                let reason = match reason {
+                    Some(reason) => reason,
+                    None => {
+                        if dataflow.unresponsive_nodes.remove(node_id) {
+                            tracing::info!("node `{dataflow_id}/{node_id}` is responsive again");
+                        }
+                        continue;
+                    }
+                };
+                if !dataflow.unresponsive_nodes.insert(node_id.clone()) {
+                    continue;
+                }
+
+                let mut message = format!("node `{node_id}` is unresponsive: {reason}");
+                if heartbeat.action == WatchdogAction::Kill {
+                    message.push_str(" -> killing it");
+                    let system = system.get_or_insert_with(|| {
+                        let mut system = sysinfo::System::new();
+                        system.refresh_processes();
+                        system
+                    });
+                    if let Some(process) = running_node
+                        .pid
+                        .and_then(|pid| system.process(Pid::from(pid as usize)))
+                    {
+                        if let Some(container) = &running_node.container {
+                            container.remove();
+                        }
+                        process.kill();
+                    }
+                }
+                tracing::warn!("dataflow `{dataflow_id}`: {message}");
+                log_messages.push(LogMessage {
+                    dataflow_id: *dataflow_id,
+                    node_id: Some(node_id.clone()),
+                    level: Level::Error,
+                    target: None,
+                    module_path: None,
+                    file: None,
+                    line: None,
+                    message,
+                });
+            }
+        }
+        for log_message in log_messages {
+            self.send_log_message(log_message).await?;
+        }
+        Ok(())
+    }
+
     async fn handle_coordinator_event(
         &mut self,
         event: DaemonCoordinatorEvent,
@@ -367,9 +571,22 @@ impl Daemon {
                 machine_listen_ports,
                 dataflow_descriptor,
             }) => {
-                match dataflow_descriptor.communication.remote {
-                    dora_core::config::RemoteCommunicationConfig::Tcp => {}
+                if self.pending_upgrade.is_some() {
+                    let reply = DaemonCoordinatorReply::SpawnResult(Err(format!(
+                        "daemon on machine `{}` is waiting for an upgrade",
+                        self.machine_id
+                    )));
+                    let _ = reply_tx.send(Some(reply)).map_err(|_| {
+                        error!("could not send `SpawnResult` reply from daemon to coordinator")
+                    });
+                    return Ok(RunStatus::Continue);
                 }
+                // all daemons of the dataflow use the same machine as clock reference
+                let clock_reference = machine_listen_ports
+                    .keys()
+                    .next()
+                    .cloned()
+                    .unwrap_or_else(|| self.machine_id.clone());
                 for (machine_id, socket) in machine_listen_ports {
                     match self.inter_daemon_connections.entry(machine_id) {
                         std::collections::btree_map::Entry::Vacant(entry) => {
@@ -384,7 +601,13 @@ impl Daemon {
                 }
 
                 let result = self
-                    .spawn_dataflow(dataflow_id, working_dir, nodes, dataflow_descriptor)
+                    .spawn_dataflow(
+                        dataflow_id,
+                        working_dir,
+                        nodes,
+                        clock_reference,
+                        dataflow_descriptor,
+                    )
                     .await;
                 if let Err(err) = &result {
                     tracing::error!("{err:?}");
@@ -426,13 +649,87 @@ impl Daemon {
                 });
                 RunStatus::Continue
             }
+            DaemonCoordinatorEvent::NodeStats { dataflow_id } => {
+                let result = match self.running.get(&dataflow_id) {
+                    Some(dataflow) => Ok(dataflow
+                        .node_stats
+                        .iter()
+                        .map(|(id, stats)| {
+                            let mut stats = stats.snapshot();
+                            let pid = dataflow.running_nodes.get(id).and_then(|n| n.pid);
+                            if let Some(usage) =
+                                pid.and_then(|pid| self.process_monitor.sample(pid))
+                            {
+                                stats.cpu_usage = Some(usage.cpu);
+                                stats.memory = Some(usage.memory);
+                            }
+                            stats.exit = self
+                                .dataflow_node_results
+                                .get(&dataflow_id)
+                                .and_then(|results| results.get(id))
+                                .map(|result| result.as_ref().copied().map_err(|e| e.to_string()));
+                            (id.clone(), stats)
+                        })
+                        .collect()),
+                    None => Err(format!("no running dataflow with ID `{dataflow_id}`")),
+                };
+                let _ = reply_tx.send(Some(DaemonCoordinatorReply::NodeStats(result)));
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::StartRecording {
+                dataflow_id,
+                outputs,
+            } => {
+                let result = self.start_recording(dataflow_id, outputs);
+                let _ = reply_tx.send(Some(DaemonCoordinatorReply::RecordingResult(
+                    result.map_err(|err| format!("{err:?}")),
+                )));
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::StopRecording { dataflow_id } => {
+                let result = match self.running.get_mut(&dataflow_id) {
+                    Some(dataflow) => match dataflow.recorder.take() {
+                        Some(recorder) => {
+                            tracing::info!(
+                                "stopped recording dataflow `{dataflow_id}` to `{}`",
+                                recorder.dir().display()
+                            );
+                            // dropping the recorder waits until all messages are written
+                            tokio::task::spawn_blocking(move || drop(recorder));
+                            Ok(())
+                        }
+                        None => Err(format!("dataflow `{dataflow_id}` is not being recorded")),
+                    },
+                    None => Err(format!("no running dataflow with ID `{dataflow_id}`")),
+                };
+                let _ = reply_tx.send(Some(DaemonCoordinatorReply::RecordingResult(result)));
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::ListRecordings => {
+                let result = self.list_recordings();
+                let _ = reply_tx.send(Some(DaemonCoordinatorReply::Recordings(
+                    result.map_err(|err| format!("{err:?}")),
+                )));
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::Logs {
                 dataflow_id,
                 node_id,
+                since,
+                offset,
             } => {
                 match self.working_dir.get(&dataflow_id) {
                     Some(working_dir) => {
                         let working_dir = working_dir.clone();
+                        let offset = match (offset, since) {
+                            (Some(offset), _) => offset,
+                            (None, Some(since)) => self
+                                .log_indexes
+                                .get(&(dataflow_id, node_id.clone()))
+                                .map_or(Some(0), |index| index.offset_since(since))
+                                .unwrap_or(u64::MAX),
+                            (None, None) => 0,
+                        };
                         tokio::spawn(async move {
                             let logs = async {
                                 let mut file =
@@ -443,11 +740,20 @@ impl Daemon {
                                             log::log_path(&working_dir, &dataflow_id, &node_id)
                                         ))?;
 
-                                let mut contents = vec![];
-                                file.read_to_end(&mut contents)
+                                let len = file
+                                    .metadata()
+                                    .await
+                                    .wrap_err("Could not read metadata of log file")?
+                                    .len();
+                                let offset = offset.min(len);
+                                file.seek(SeekFrom::Start(offset))
+                                    .await
+                                    .wrap_err("Could not seek in log file")?;
+                                let mut data = vec![];
+                                file.read_to_end(&mut data)
                                     .await
                                     .wrap_err("Could not read content of log file")?;
-                                Result::<Vec<u8>, eyre::Report>::Ok(contents)
+                                Result::<LogChunk, eyre::Report>::Ok(LogChunk { offset, data })
                             }
                             .await
                             .map_err(|err| format!("{err:?}"));
@@ -482,6 +788,68 @@ impl Daemon {
                     .map_err(|_| error!("could not send reload reply from daemon to coordinator"));
                 RunStatus::Continue
             }
+            DaemonCoordinatorEvent::LoadOperator {
+                dataflow_id,
+                node_id,
+                machine,
+                operator,
+            } => {
+                let result = self
+                    .load_operator(dataflow_id, node_id, machine, operator)
+                    .await;
+                let reply = DaemonCoordinatorReply::OperatorResult(
+                    result.map_err(|err| format!("{err:?}")),
+                );
+                let _ = reply_tx.send(Some(reply)).map_err(|_| {
+                    error!("could not send operator reply from daemon to coordinator")
+                });
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::UnloadOperator {
+                dataflow_id,
+                node_id,
+                machine,
+                operator_id,
+            } => {
+                let result = self
+                    .unload_operator(dataflow_id, node_id, machine, operator_id)
+                    .await;
+                let reply = DaemonCoordinatorReply::OperatorResult(
+                    result.map_err(|err| format!("{err:?}")),
+                );
+                let _ = reply_tx.send(Some(reply)).map_err(|_| {
+                    error!("could not send operator reply from daemon to coordinator")
+                });
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::AttachNode {
+                dataflow_id,
+                node,
+                descriptor,
+                target_machines,
+            } => {
+                let result = self
+                    .attach_node(dataflow_id, node, descriptor, target_machines)
+                    .await;
+                let reply =
+                    DaemonCoordinatorReply::AttachResult(result.map_err(|err| format!("{err:?}")));
+                let _ = reply_tx
+                    .send(Some(reply))
+                    .map_err(|_| error!("could not send attach reply from daemon to coordinator"));
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::DetachNode {
+                dataflow_id,
+                node_id,
+            } => {
+                let result = self.detach_node(dataflow_id, &node_id);
+                let reply =
+                    DaemonCoordinatorReply::AttachResult(result.map_err(|err| format!("{err:?}")));
+                let _ = reply_tx
+                    .send(Some(reply))
+                    .map_err(|_| error!("could not send detach reply from daemon to coordinator"));
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::StopDataflow {
                 dataflow_id,
                 grace_duration,
@@ -499,6 +867,59 @@ impl Daemon {
                 dataflow.stop_all(&self.clock, grace_duration).await;
                 RunStatus::Continue
             }
+            DaemonCoordinatorEvent::RestartNode {
+                dataflow_id,
+                node_id,
+                grace_duration,
+            } => {
+                let result = self.restart_node(dataflow_id, &node_id, grace_duration);
+                let reply = DaemonCoordinatorReply::RestartNodeResult(
+                    result.map_err(|err| format!("{err:?}")),
+                );
+                let _ = reply_tx.send(Some(reply)).map_err(|_| {
+                    error!("could not send restart node reply from daemon to coordinator")
+                });
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::Upgrade {
+                binary,
+                coordinator_version,
+            } => {
+                let result = match std::env::current_exe() {
+                    Ok(exe) => upgrade::stage(&binary, &exe, &coordinator_version).await,
+                    Err(err) => Err(eyre!(err).wrap_err("failed to get current executable")),
+                };
+                let result = result.map(|staged| {
+                    tracing::info!(
+                        "received upgrade to v{} -> restarting after {} running dataflow(s) finished",
+                        staged.version,
+                        self.running.len()
+                    );
+                    let version = staged.version.clone();
+                    // a previously staged upgrade was overwritten by `stage`
+                    self.pending_upgrade = Some(staged);
+                    version
+                });
+                let reply =
+                    DaemonCoordinatorReply::UpgradeResult(result.map_err(|err| format!("{err:?}")));
+                let _ = reply_tx
+                    .send(Some(reply))
+                    .map_err(|_| error!("could not send upgrade reply from daemon to coordinator"));
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::Deploy {
+                name,
+                bundle,
+                target_dir,
+            } => {
+                let result = self.deploy(&name, bundle, target_dir).await;
+                let reply =
+                    DaemonCoordinatorReply::DeployResult(result.map_err(|err| format!("{err:?}")));
+                let _ = reply_tx
+                    .send(Some(reply))
+                    .map_err(|_| error!("could not send deploy reply from daemon to coordinator"));
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::Destroy => {
                 tracing::info!("received destroy command -> exiting");
                 let (notify_tx, notify_rx) = oneshot::channel();
@@ -525,15 +946,73 @@ impl Daemon {
     }
 
     async fn handle_inter_daemon_event(&mut self, event: InterDaemonEvent) -> eyre::Result<()> {
+        // the dataflow whose key authenticated the event, if it was encrypted
+        let (event, authenticated) = match event {
+            InterDaemonEvent::Encrypted {
+                dataflow_id,
+                sender,
+                nonce,
+                ciphertext,
+                ..
+            } => {
+                let decrypted = self
+                    .running
+                    .get_mut(&dataflow_id)
+                    .and_then(|dataflow| dataflow.cipher.as_mut())
+                    .ok_or_else(|| eyre!("dataflow `{dataflow_id}` has no encryption key"))
+                    .and_then(|cipher| cipher.decrypt(dataflow_id, &sender, &nonce, &ciphertext));
+                match decrypted {
+                    Ok(event) => (event, Some(dataflow_id)),
+                    Err(err) => {
+                        tracing::warn!("dropping encrypted inter-daemon event: {err:?}");
+                        return Ok(());
+                    }
+                }
+            }
+            InterDaemonEvent::Output { dataflow_id, .. }
+            | InterDaemonEvent::InputsClosed { dataflow_id, .. }
+                if self
+                    .running
+                    .get(&dataflow_id)
+                    .is_some_and(|dataflow| dataflow.cipher.is_some()) =>
+            {
+                tracing::warn!(
+                    "dropping unencrypted inter-daemon event for dataflow `{dataflow_id}`, \
+                    which requires encryption"
+                );
+                return Ok(());
+            }
+            other => (other, None),
+        };
         match event {
             InterDaemonEvent::Output {
                 dataflow_id,
                 node_id,
                 output_id,
-                metadata,
-                data,
+                mut metadata,
+                mut data,
             } => {
+                if metadata.parameters.is_expired() {
+                    tracing::trace!(
+                        "dropping remote output `{node_id}/{output_id}` because its deadline passed"
+                    );
+                    return Ok(());
+                }
                 let inner = async {
+                    if let Some(algorithm) = metadata.compression() {
+                        if let Some(compressed) = &data {
+                            data = Some(
+                                compression::decompress(compressed, algorithm).wrap_err_with(
+                                    || {
+                                        format!(
+                                            "failed to decompress output `{node_id}/{output_id}`"
+                                        )
+                                    },
+                                )?,
+                            );
+                        }
+                        metadata.set_compression(None);
+                    }
                     let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
                         format!("send out failed: no running dataflow with ID `{dataflow_id}`")
                     })?;
@@ -543,6 +1022,7 @@ impl Daemon {
                         dataflow,
                         &metadata,
                         data.map(DataMessage::Vec),
+                        true,
                         &self.clock,
                     )
                     .await?;
@@ -559,14 +1039,19 @@ impl Daemon {
             InterDaemonEvent::InputsClosed {
                 dataflow_id,
                 inputs,
+                sources,
             } => {
                 tracing::debug!(?dataflow_id, ?inputs, "received InputsClosed event");
                 let inner = async {
                     let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
                         format!("send out failed: no running dataflow with ID `{dataflow_id}`")
                     })?;
+                    let sources: BTreeSet<_> = sources
+                        .into_iter()
+                        .map(|(node_id, output_id)| OutputId(node_id, output_id))
+                        .collect();
                     for (receiver_id, input_id) in &inputs {
-                        close_input(dataflow, receiver_id, input_id, &self.clock);
+                        close_input(dataflow, receiver_id, input_id, &sources, &self.clock);
                     }
                     Result::<(), eyre::Report>::Ok(())
                 };
@@ -578,17 +1063,407 @@ impl Daemon {
                 }
                 Ok(())
             }
+            InterDaemonEvent::Encrypted { dataflow_id, .. } => {
+                tracing::warn!("dropping nested encrypted event for dataflow `{dataflow_id}`");
+                Ok(())
+            }
+            InterDaemonEvent::ClockSyncRequest {
+                machine_id,
+                request_sent,
+            } => {
+                let request_received = clock_sync::now();
+                let reply = InterDaemonEvent::ClockSyncReply {
+                    machine_id: self.machine_id.clone(),
+                    request_sent,
+                    request_received,
+                    reply_sent: clock_sync::now(),
+                };
+                // answer encrypted requests with the key of the same dataflow
+                let inner = match authenticated {
+                    Some(dataflow_id) => {
+                        let encrypted = self
+                            .running
+                            .get(&dataflow_id)
+                            .and_then(|dataflow| dataflow.cipher.as_ref())
+                            .ok_or_else(|| eyre!("dataflow `{dataflow_id}` has no encryption key"))
+                            .and_then(|cipher| cipher.encrypt(dataflow_id, &reply));
+                        match encrypted {
+                            Ok(inner) => inner,
+                            Err(err) => {
+                                tracing::warn!("failed to encrypt clock sync reply: {err:?}");
+                                return Ok(());
+                            }
+                        }
+                    }
+                    None => reply,
+                };
+                let event = Timestamped {
+                    inner,
+                    timestamp: self.clock.new_timestamp(),
+                };
+                if let Err(err) = inter_daemon::send_inter_daemon_event(
+                    &[machine_id],
+                    &mut self.inter_daemon_connections,
+                    &event,
+                ) {
+                    tracing::warn!("failed to reply to clock sync request: {err:?}");
+                }
+                Ok(())
+            }
+            InterDaemonEvent::ClockSyncReply {
+                machine_id,
+                request_sent,
+                request_received,
+                reply_sent,
+            } => {
+                self.clock_sync.add_sample(
+                    machine_id,
+                    authenticated,
+                    request_sent,
+                    request_received,
+                    reply_sent,
+                    clock_sync::now(),
+                );
+                Ok(())
+            }
         }
     }
 
+    async fn send_clock_sync_requests(&mut self) {
+        let remote_machines: Vec<_> = self
+            .inter_daemon_connections
+            .keys()
+            .filter(|m| **m != self.machine_id)
+            .cloned()
+            .collect();
+        for machine_id in remote_machines {
+            let event = Timestamped {
+                inner: InterDaemonEvent::ClockSyncRequest {
+                    machine_id: self.machine_id.clone(),
+                    request_sent: clock_sync::now(),
+                },
+                timestamp: self.clock.new_timestamp(),
+            };
+            if let Err(err) = inter_daemon::send_inter_daemon_event(
+                &[machine_id],
+                &mut self.inter_daemon_connections,
+                &event,
+            ) {
+                tracing::warn!("failed to send clock sync request: {err:?}");
+            }
+        }
+
+        // dataflows that use encryption only trust clock sync round trips to
+        // their clock reference that are encrypted with their own key
+        let mut encrypted_requests = Vec::new();
+        for (dataflow_id, dataflow) in &self.running {
+            let Some(cipher) = &dataflow.cipher else {
+                continue;
+            };
+            if dataflow.clock_reference.is_empty() || dataflow.clock_reference == self.machine_id {
+                continue;
+            }
+            let request = InterDaemonEvent::ClockSyncRequest {
+                machine_id: self.machine_id.clone(),
+                request_sent: clock_sync::now(),
+            };
+            match cipher.encrypt(*dataflow_id, &request) {
+                Ok(inner) => encrypted_requests.push((dataflow.clock_reference.clone(), inner)),
+                Err(err) => tracing::warn!("failed to encrypt clock sync request: {err:?}"),
+            }
+        }
+        for (machine_id, inner) in encrypted_requests {
+            let event = Timestamped {
+                inner,
+                timestamp: self.clock.new_timestamp(),
+            };
+            if let Err(err) = inter_daemon::send_inter_daemon_event(
+                &[machine_id],
+                &mut self.inter_daemon_connections,
+                &event,
+            ) {
+                tracing::warn!("failed to send clock sync request: {err:?}");
+            }
+        }
+    }
+
+    fn start_recording(&mut self, dataflow_id: Uuid, outputs: BTreeSet<String>) -> Result<()> {
+        let working_dir = self
+            .working_dir
+            .get(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        if let Some(recorder) = &dataflow.recorder {
+            bail!(
+                "dataflow `{dataflow_id}` is already being recorded to `{}`",
+                recorder.dir().display()
+            );
+        }
+        let config = RecordConfig {
+            enabled: true,
+            outputs,
+            segment_size: None,
+        };
+        dataflow.recorder = Some(record::DataflowRecorder::new(
+            config,
+            working_dir,
+            dataflow_id,
+        )?);
+        Ok(())
+    }
+
+    /// Asks the given node to stop and spawns it again once it exited, see
+    /// `respawn_node`.
+    fn restart_node(
+        &mut self,
+        dataflow_id: Uuid,
+        node_id: &NodeId,
+        grace_duration: Option<Duration>,
+    ) -> Result<()> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        if dataflow.stop_sent {
+            bail!("dataflow `{dataflow_id}` is stopping");
+        }
+        let node = dataflow.running_nodes.get(node_id).wrap_err_with(|| {
+            format!(
+                "node `{node_id}` is not running on machine `{}`",
+                self.machine_id
+            )
+        })?;
+        if node.node_config.dynamic {
+            bail!("node `{node_id}` is dynamic and cannot be restarted");
+        }
+        if !dataflow.pending_nodes.started() {
+            bail!("dataflow `{dataflow_id}` did not start yet");
+        }
+        if !dataflow.restarting_nodes.insert(node_id.clone()) {
+            bail!("node `{node_id}` is already restarting");
+        }
+
+        tracing::info!("restarting node `{dataflow_id}/{node_id}`");
+        if let Some(channel) = dataflow.subscribe_channels.remove(node_id) {
+            let _ = send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, &self.clock);
+        }
+        terminate_after_grace_duration(
+            BTreeMap::from([(node_id.clone(), node.clone())]),
+            grace_duration,
+            dataflow.grace_duration_kills.clone(),
+        );
+        Ok(())
+    }
+
+    /// Spawns a node again after it exited because of `restart_node`.
+    ///
+    /// The new instance subscribes to the running dataflow like a dynamic
+    /// node, so it doesn't wait for the other nodes.
+    async fn respawn_node(&mut self, dataflow_id: Uuid, node_id: NodeId) -> Result<()> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        let working_dir = self
+            .working_dir
+            .get(&dataflow_id)
+            .wrap_err("no working dir for dataflow")?;
+        let node = dataflow
+            .nodes
+            .get(&node_id)
+            .cloned()
+            .wrap_err_with(|| format!("no local node `{node_id}`"))?;
+        let descriptor = dataflow
+            .descriptor
+            .clone()
+            .wrap_err("no descriptor for dataflow")?;
+
+        // the exited instance won't report its remaining drop tokens
+        let tokens: Vec<_> = dataflow
+            .pending_drop_tokens
+            .iter_mut()
+            .filter_map(|(token, info)| info.pending_nodes.remove(&node_id).then_some(*token))
+            .collect();
+        for token in tokens {
+            dataflow.check_drop_token(token, &self.clock).await?;
+        }
+        dataflow.drop_channels.remove(&node_id);
+        dataflow.grace_duration_kills.remove(&node_id);
+        dataflow.unresponsive_nodes.remove(&node_id);
+
+        let node_stderr_most_recent = dataflow
+            .node_stderr_most_recent
+            .entry(node_id.clone())
+            .or_insert_with(|| Arc::new(ArrayQueue::new(STDERR_LOG_LINES)))
+            .clone();
+        let stats = dataflow
+            .node_stats
+            .entry(node_id.clone())
+            .or_default()
+            .clone();
+        let log_index = self
+            .log_indexes
+            .entry((dataflow_id, node_id.clone()))
+            .or_default()
+            .clone();
+        let result = spawn::spawn_node(
+            dataflow_id,
+            working_dir,
+            node,
+            self.events_tx.clone(),
+            descriptor,
+            self.clock.clone(),
+            node_stderr_most_recent,
+            stats,
+            log_index,
+        )
+        .await
+        .wrap_err_with(|| format!("failed to restart node `{node_id}`"));
+        let (level, message) = match result {
+            Ok(running_node) => {
+                dataflow.running_nodes.insert(node_id.clone(), running_node);
+                (Level::Info, "node restarted".to_owned())
+            }
+            Err(err) => {
+                self.handle_node_stop(dataflow_id, &node_id).await?;
+                (Level::Error, format!("{err:?}"))
+            }
+        };
+        self.send_log_message(LogMessage {
+            dataflow_id,
+            node_id: Some(node_id),
+            level,
+            target: None,
+            module_path: None,
+            file: None,
+            line: None,
+            message,
+        })
+        .await
+    }
+
+    /// Extracts a dataflow bundle and returns the directory that it was
+    /// extracted to.
+    async fn deploy(
+        &self,
+        name: &str,
+        bundle: Vec<u8>,
+        target_dir: Option<PathBuf>,
+    ) -> Result<PathBuf> {
+        let dir = deploy::target_dir(name, target_dir)?;
+        let in_use = self.running.keys().find(|dataflow_id| {
+            self.working_dir
+                .get(dataflow_id)
+                .is_some_and(|working_dir| working_dir.starts_with(&dir))
+        });
+        if let Some(dataflow_id) = in_use {
+            bail!(
+                "dataflow `{dataflow_id}` is running from `{}`, stop it before deploying",
+                dir.display()
+            );
+        }
+
+        let extract_dir = dir.clone();
+        tokio::task::spawn_blocking(move || deploy::extract(&bundle, &extract_dir))
+            .await
+            .context("bundle extraction panicked")??;
+        tracing::info!("deployed bundle `{name}` to `{}`", dir.display());
+        Ok(dir)
+    }
+
+    /// Lists the recordings of all dataflows that were started by this daemon.
+    fn list_recordings(&self) -> Result<Vec<RecordingInfo>> {
+        let mut recordings = Vec::new();
+        for (dataflow_id, working_dir) in &self.working_dir {
+            let active_dir = self
+                .running
+                .get(dataflow_id)
+                .and_then(|dataflow| dataflow.recorder.as_ref())
+                .map(|recorder| recorder.dir());
+            for dir in dora_core::record::recording_dirs(working_dir, dataflow_id)? {
+                let active = active_dir == Some(dir.as_path());
+                recordings.push(RecordingInfo::read(
+                    self.machine_id.clone(),
+                    *dataflow_id,
+                    dir,
+                    active,
+                )?);
+            }
+        }
+        Ok(recordings)
+    }
+
     async fn spawn_dataflow(
         &mut self,
         dataflow_id: uuid::Uuid,
         working_dir: PathBuf,
         nodes: Vec<ResolvedNode>,
+        clock_reference: String,
         dataflow_descriptor: Descriptor,
     ) -> eyre::Result<()> {
-        let dataflow = RunningDataflow::new(dataflow_id, self.machine_id.clone());
+        let mut dataflow = RunningDataflow::new(dataflow_id, self.machine_id.clone());
+        dataflow.clock_reference = clock_reference;
+        dataflow.descriptor = Some(dataflow_descriptor.clone());
+        dataflow.resource_limits = dataflow_descriptor.resource_limits.clone();
+        for output in &dataflow_descriptor.latched_outputs {
+            let (node, output_id) = output.split_once('/').ok_or_else(|| {
+                eyre!("invalid latched output `{output}`: expected `node/output`")
+            })?;
+            dataflow.latched_outputs.insert(OutputId(
+                node.to_owned().into(),
+                output_id.to_owned().into(),
+            ));
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(endpoint) = dataflow_descriptor.logging.metrics_endpoint() {
+            dataflow.metrics = Some(dora_tracing::metrics::DataflowMetrics::new(
+                dataflow_id.to_string(),
+                endpoint,
+                dataflow_descriptor.logging.metrics_interval(),
+            )?);
+        }
+        if dataflow_descriptor.record.enabled {
+            dataflow.recorder = Some(record::DataflowRecorder::new(
+                dataflow_descriptor.record.clone(),
+                &working_dir,
+                dataflow_id,
+            )?);
+        }
+        dataflow.pub_sub = pub_sub_transport::PubSubTransport::start(
+            &dataflow_descriptor.communication.remote,
+            dataflow_id,
+            &self.machine_id,
+            self.events_tx.clone(),
+        )
+        .await
+        .wrap_err("failed to set up inter-daemon transport")?;
+        if let Some(encryption) = &dataflow_descriptor.communication.encryption {
+            let cipher = encryption::DataflowCipher::load(
+                &working_dir.join(&encryption.key_file),
+                self.machine_id.clone(),
+            )
+            .await
+            .wrap_err("failed to load encryption key of dataflow")?;
+            dataflow.cipher = Some(cipher);
+        }
+        let replayed_nodes: BTreeSet<_> = nodes
+            .iter()
+            .filter(|n| n.deploy.machine == self.machine_id)
+            .filter(|n| dataflow_descriptor.replay.replays(&n.id))
+            .map(|n| n.id.clone())
+            .collect();
+        if let Some(path) = &dataflow_descriptor.replay.path {
+            if !replayed_nodes.is_empty() {
+                dataflow.replay = Some(replay::ReplaySource {
+                    path: working_dir.join(path),
+                    nodes: replayed_nodes.clone(),
+                    speed: replay::replay_speed(dataflow_descriptor.replay.speed)?,
+                });
+            }
+        }
         let dataflow = match self.running.entry(dataflow_id) {
             std::collections::hash_map::Entry::Vacant(entry) => {
                 self.working_dir.insert(dataflow_id, working_dir.clone());
@@ -602,43 +1477,27 @@ impl Daemon {
         let mut log_messages = Vec::new();
         for node in nodes {
             let local = node.deploy.machine == self.machine_id;
+            if replayed_nodes.contains(&node.id) {
+                // outputs of this node are injected from the recording instead
+                continue;
+            }
 
             let inputs = node_inputs(&node);
             for (input_id, input) in inputs {
-                if local {
-                    dataflow
-                        .open_inputs
-                        .entry(node.id.clone())
-                        .or_default()
-                        .insert(input_id.clone());
-                    match input.mapping {
-                        InputMapping::User(mapping) => {
-                            dataflow
-                                .mappings
-                                .entry(OutputId(mapping.source, mapping.output))
-                                .or_default()
-                                .insert((node.id.clone(), input_id));
-                        }
-                        InputMapping::Timer { interval } => {
-                            dataflow
-                                .timers
-                                .entry(interval)
-                                .or_default()
-                                .insert((node.id.clone(), input_id));
-                        }
-                    }
-                } else if let InputMapping::User(mapping) = input.mapping {
-                    dataflow
-                        .open_external_mappings
-                        .entry(OutputId(mapping.source, mapping.output))
-                        .or_default()
-                        .entry(node.deploy.machine.clone())
-                        .or_default()
-                        .insert((node.id.clone(), input_id));
+                if is_runtime_internal(&node, &input.mapping) {
+                    // delivered by the runtime without going through the daemon
+                    continue;
                 }
+                dataflow.add_input(&node.id, &node.deploy.machine, local, input_id, input);
             }
             if local {
                 dataflow.pending_nodes.insert(node.id.clone());
+                dataflow.nodes.insert(node.id.clone(), node.clone());
+                if let Some(heartbeat) = &node.heartbeat {
+                    dataflow
+                        .heartbeats
+                        .insert(node.id.clone(), heartbeat.clone());
+                }
 
                 let node_id = node.id.clone();
                 let node_stderr_most_recent = dataflow
@@ -646,6 +1505,12 @@ impl Daemon {
                     .entry(node.id.clone())
                     .or_insert_with(|| Arc::new(ArrayQueue::new(STDERR_LOG_LINES)))
                     .clone();
+                let stats = dataflow.node_stats(&node.id);
+                let log_index = self
+                    .log_indexes
+                    .entry((dataflow_id, node.id.clone()))
+                    .or_default()
+                    .clone();
                 match spawn::spawn_node(
                     dataflow_id,
                     &working_dir,
@@ -654,6 +1519,8 @@ impl Daemon {
                     dataflow_descriptor.clone(),
                     self.clock.clone(),
                     node_stderr_most_recent,
+                    stats,
+                    log_index,
                 )
                 .await
                 .wrap_err_with(|| format!("failed to spawn node `{node_id}`"))
@@ -800,7 +1667,7 @@ impl Daemon {
                                 );
                                 dataflow.start(&self.events_tx, &self.clock).await?;
                             }
-                            DataflowStatus::Pending => {}
+                            DataflowStatus::Pending | DataflowStatus::Running => {}
                         }
                     }
                 }
@@ -825,6 +1692,9 @@ impl Daemon {
                         .running
                         .get_mut(&dataflow_id)
                         .wrap_err_with(|| format!("failed to get downstream nodes: no running dataflow with ID `{dataflow_id}`"))?;
+                    if dataflow.restarting_nodes.contains(&node_id) {
+                        return Ok(());
+                    }
                     send_input_closed_events(
                         dataflow,
                         &mut self.inter_daemon_connections,
@@ -896,6 +1766,43 @@ impl Daemon {
                 let reply = inner.await.map_err(|err| format!("{err:?}"));
                 let _ = reply_sender.send(DaemonReply::Result(reply));
             }
+            DaemonNodeEvent::OperatorError { error } => {
+                tracing::error!(
+                    "operator `{node_id}/{}` of dataflow `{dataflow_id}` failed: {error}",
+                    error.operator_id
+                );
+                self.send_coordinator_event(DaemonEvent::OperatorError {
+                    dataflow_id,
+                    node_id,
+                    error,
+                })
+                .await?;
+            }
+            DaemonNodeEvent::Log { record } => {
+                // write the record to the log file too, so that it shows up
+                // in `dora logs` next to the stdout of the node
+                let log_sender = self
+                    .running
+                    .get(&dataflow_id)
+                    .and_then(|dataflow| dataflow.running_nodes.get(&node_id))
+                    .and_then(|node| node.log_sender.as_ref()?.upgrade());
+                if let Some(log_sender) = log_sender {
+                    let _ = log_sender
+                        .send(record.log_line(&dataflow_id, &node_id))
+                        .await;
+                }
+                self.send_log_message(LogMessage {
+                    dataflow_id,
+                    target: Some(record.target(&node_id)),
+                    node_id: Some(node_id),
+                    level: record.level,
+                    module_path: None,
+                    file: None,
+                    line: None,
+                    message: record.message_with_fields(),
+                })
+                .await?;
+            }
         }
         Ok(())
     }
@@ -924,51 +1831,460 @@ impl Daemon {
         Ok(())
     }
 
+    /// Adds the inputs of a newly loaded operator to the mappings of the dataflow
+    /// and forwards the operator to its runtime node, if it runs on this machine.
+    async fn load_operator(
+        &mut self,
+        dataflow_id: Uuid,
+        node_id: NodeId,
+        machine: String,
+        operator: OperatorDefinition,
+    ) -> eyre::Result<()> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        let local = machine == self.machine_id;
+        if local && !dataflow.subscribe_channels.contains_key(&node_id) {
+            bail!("node `{node_id}` is not running");
+        }
+
+        let inputs = runtime_node_inputs(&RuntimeNode {
+            operators: vec![operator.clone()],
+        });
+        for input in inputs.values() {
+            if let InputMapping::Timer { interval } = &input.mapping {
+                if local && !dataflow.timers.contains_key(interval) {
+                    bail!(
+                        "operator `{}` uses a timer interval that no other node of the \
+                        dataflow uses, which is not supported for loaded operators",
+                        operator.id
+                    );
+                }
+            }
+        }
+        for (input_id, input) in inputs {
+            if matches!(&input.mapping, InputMapping::User(m) if m.source == node_id) {
+                // delivered by the runtime without going through the daemon
+                continue;
+            }
+            dataflow.add_input(&node_id, &machine, local, input_id, input);
+        }
+
+        if local {
+            let operator =
+                serde_json::to_string(&operator).wrap_err("failed to serialize operator")?;
+            let channel = &dataflow.subscribe_channels[&node_id];
+            if send_with_timestamp(
+                channel,
+                daemon_messages::NodeEvent::LoadOperator { operator },
+                &self.clock,
+            )
+            .is_err()
+            {
+                dataflow.subscribe_channels.remove(&node_id);
+                bail!("node `{node_id}` is not running");
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the inputs of the given operator from the mappings of the dataflow
+    /// and tells its runtime node to stop it, if it runs on this machine.
+    ///
+    /// The outputs of the operator are closed by the runtime node once the
+    /// operator has stopped.
+    async fn unload_operator(
+        &mut self,
+        dataflow_id: Uuid,
+        node_id: NodeId,
+        machine: String,
+        operator_id: OperatorId,
+    ) -> eyre::Result<()> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        let prefix = format!("{operator_id}/");
+        let is_operator_input =
+            |(receiver, input_id): &InputId| receiver == &node_id && input_id.starts_with(&prefix);
+
+        if machine == self.machine_id {
+            for receivers in dataflow.mappings.values_mut() {
+                receivers.retain(|input| !is_operator_input(input));
+            }
+            for receivers in dataflow.timers.values_mut() {
+                receivers.retain(|input| !is_operator_input(input));
+            }
+            if let Some(open_inputs) = dataflow.open_inputs.get_mut(&node_id) {
+                open_inputs.retain(|input_id| !input_id.starts_with(&prefix));
+            }
+            if let Some(channel) = dataflow.subscribe_channels.get(&node_id) {
+                if send_with_timestamp(
+                    channel,
+                    daemon_messages::NodeEvent::UnloadOperator { operator_id },
+                    &self.clock,
+                )
+                .is_err()
+                {
+                    dataflow.subscribe_channels.remove(&node_id);
+                }
+            }
+        } else {
+            for receivers in dataflow.open_external_mappings.values_mut() {
+                if let Some(inputs) = receivers.get_mut(&machine) {
+                    inputs.retain(|input| !is_operator_input(input));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers a dynamic node that is added to the running dataflow.
+    ///
+    /// The daemon of the node's machine starts a listener for it, so that the
+    /// node can connect through `DoraNode::init_from_node_id`. All daemons
+    /// update their mappings for the node's inputs and outputs.
+    async fn attach_node(
+        &mut self,
+        dataflow_id: Uuid,
+        node: AttachedNode,
+        descriptor: Descriptor,
+        target_machines: BTreeMap<NodeId, String>,
+    ) -> eyre::Result<()> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        let local = node.machine == self.machine_id;
+
+        let mut inputs = BTreeMap::new();
+        for (input_id, (source, output)) in &node.inputs {
+            let input = Input {
+                mapping: InputMapping::User(UserInputMapping {
+                    source: source.clone(),
+                    output: output.clone(),
+                }),
+                additional_sources: Vec::new(),
+                queue_size: None,
+                priority: None,
+                compression: None,
+                rate_limit: None,
+                downsample: None,
+                qos: None,
+            };
+            dataflow.add_input(
+                &node.id,
+                &node.machine,
+                local,
+                input_id.clone(),
+                input.clone(),
+            );
+            inputs.insert(input_id.clone(), input);
+        }
+
+        for (output_id, targets) in &node.outputs {
+            let output = OutputId(node.id.clone(), output_id.clone());
+            for (target, input_id) in targets {
+                let machine = target_machines
+                    .get(target)
+                    .wrap_err_with(|| format!("no machine for node `{target}`"))?;
+                let input = (target.clone(), input_id.clone());
+                if machine == &self.machine_id {
+                    if !dataflow.open_inputs(target).contains(input_id) {
+                        bail!("input `{target}/{input_id}` is already closed");
+                    }
+                    // keep the input open when the attached node is detached
+                    if !dataflow.open_sources.contains_key(&input) {
+                        let sources = dataflow
+                            .mappings
+                            .iter()
+                            .filter(|(_, receivers)| receivers.contains(&input))
+                            .map(|(source, _)| source.clone())
+                            .collect();
+                        dataflow.open_sources.insert(input.clone(), sources);
+                    }
+                    if let Some(sources) = dataflow.open_sources.get_mut(&input) {
+                        sources.insert(output.clone());
+                    }
+                    dataflow
+                        .mappings
+                        .entry(output.clone())
+                        .or_default()
+                        .insert(input);
+                } else if local {
+                    dataflow
+                        .open_external_mappings
+                        .entry(output.clone())
+                        .or_default()
+                        .entry(machine.clone())
+                        .or_default()
+                        .insert(input);
+                }
+            }
+        }
+
+        if local {
+            let queue_sizes = inputs.keys().map(|id| (id.clone(), 10)).collect();
+            let stats = dataflow.node_stats(&node.id);
+            let daemon_communication = node_communication::spawn_listener_loop(
+                &dataflow_id,
+                &node.id,
+                &self.events_tx,
+                descriptor.communication.local,
+                queue_sizes,
+                BTreeMap::new(),
+                self.clock.clone(),
+                stats,
+            )
+            .await
+            .wrap_err("failed to start listener for attached node")?;
+            let node_config = NodeConfig {
+                dataflow_id,
+                node_id: node.id.clone(),
+                run_config: NodeRunConfig {
+                    inputs,
+                    outputs: node.outputs.keys().cloned().collect(),
+                },
+                daemon_communication,
+                dataflow_descriptor: descriptor,
+                dynamic: true,
+            };
+            dataflow.running_nodes.insert(
+                node.id,
+                RunningNode {
+                    pid: None,
+                    node_config,
+                    log_sender: None,
+                    container: None,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Removes an attached node from the mappings of the dataflow.
+    ///
+    /// Inputs that the node sent to are closed if they have no other open
+    /// sources.
+    fn detach_node(&mut self, dataflow_id: Uuid, node_id: &NodeId) -> eyre::Result<()> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+
+        let closed_outputs: BTreeSet<_> = dataflow
+            .mappings
+            .keys()
+            .filter(|OutputId(source, _)| source == node_id)
+            .cloned()
+            .collect();
+        let local_inputs: BTreeSet<_> = closed_outputs
+            .iter()
+            .flat_map(|output_id| &dataflow.mappings[output_id])
+            .cloned()
+            .collect();
+        for (receiver_id, input_id) in &local_inputs {
+            close_input(
+                dataflow,
+                receiver_id,
+                input_id,
+                &closed_outputs,
+                &self.clock,
+            );
+        }
+
+        dataflow
+            .mappings
+            .retain(|OutputId(source, _), _| source != node_id);
+        for receivers in dataflow.mappings.values_mut() {
+            receivers.retain(|(receiver, _)| receiver != node_id);
+        }
+        dataflow
+            .open_external_mappings
+            .retain(|OutputId(source, _), _| source != node_id);
+        for receivers in dataflow.open_external_mappings.values_mut() {
+            for inputs in receivers.values_mut() {
+                inputs.retain(|(receiver, _)| receiver != node_id);
+            }
+        }
+        for sources in dataflow.open_sources.values_mut() {
+            sources.retain(|OutputId(source, _)| source != node_id);
+        }
+        dataflow.open_inputs.remove(node_id);
+        dataflow.running_nodes.remove(node_id);
+        dataflow.subscribe_channels.remove(node_id);
+        dataflow.drop_channels.remove(node_id);
+        dataflow.node_stats.remove(node_id);
+        Ok(())
+    }
+
     async fn send_out(
         &mut self,
         dataflow_id: Uuid,
         node_id: NodeId,
         output_id: DataId,
-        metadata: dora_core::message::Metadata,
+        mut metadata: dora_core::message::Metadata,
         data: Option<DataMessage>,
     ) -> Result<(), eyre::ErrReport> {
         let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
             format!("send out failed: no running dataflow with ID `{dataflow_id}`")
         })?;
+        metadata.set_dataflow_timestamp(self.clock_sync.normalize(
+            &self.machine_id,
+            &dataflow.clock_reference,
+            dataflow.cipher.is_some().then_some(dataflow_id),
+            metadata.timestamp(),
+        ));
+        if dataflow.resource_limit_exceeded
+            && dataflow
+                .resource_limits
+                .is_best_effort(&node_id, &output_id)
+        {
+            // drop the message, but make sure that its drop token is still reported
+            if let Some(token) = data.as_ref().and_then(|d| d.drop_token()) {
+                dataflow
+                    .pending_drop_tokens
+                    .entry(token)
+                    .or_insert_with(|| DropTokenInformation {
+                        owner: node_id.clone(),
+                        pending_nodes: Default::default(),
+                    });
+                dataflow.check_drop_token(token, &self.clock).await?;
+            }
+            return Ok(());
+        }
+        if let Some(stats) = dataflow.node_stats.get(&node_id) {
+            stats.output_sent(&output_id, data.as_ref().map(|d| d.len()).unwrap_or(0));
+        }
         let data_bytes = send_output_to_local_receivers(
             node_id.clone(),
             output_id.clone(),
             dataflow,
             &metadata,
             data,
+            false,
             &self.clock,
         )
         .await?;
+        let recording_paused = dataflow.resource_limit_exceeded
+            && dataflow
+                .resource_limits
+                .actions
+                .contains(&ResourceLimitAction::PauseRecording);
+        if let Some(recorder) = dataflow.recorder.as_ref().filter(|_| !recording_paused) {
+            recorder.record(&node_id, &output_id, &metadata, data_bytes.as_ref());
+        }
 
         let output_id = OutputId(node_id, output_id);
-        let remote_receivers: Vec<_> = dataflow
+        let mut remote_receivers: Vec<_> = dataflow
             .open_external_mappings
             .get(&output_id)
             .map(|m| m.keys().cloned().collect())
             .unwrap_or_default();
-        if !remote_receivers.is_empty() {
-            let event = Timestamped {
-                inner: InterDaemonEvent::Output {
+        if metadata.parameters.is_expired() {
+            // late messages would be dropped by the receiving daemon anyway
+            remote_receivers.clear();
+        }
+        if let Some(downsamplers) = dataflow.remote_downsamplers.get_mut(&output_id) {
+            let now = Instant::now();
+            remote_receivers.retain(|machine| match downsamplers.get_mut(machine) {
+                Some(Some(downsampler)) => downsampler.accept(now),
+                _ => true,
+            });
+        }
+        if let Some(limiters) = dataflow.remote_rate_limiters.get_mut(&output_id) {
+            // drop messages that exceed the rate limit before they use network bandwidth
+            let len = data_bytes.as_ref().map(|d| d.len()).unwrap_or(0);
+            let now = Instant::now();
+            remote_receivers.retain(|machine| match limiters.get_mut(machine) {
+                Some(Some(bucket)) => bucket.try_take(len, now),
+                _ => true,
+            });
+        }
+        if !remote_receivers.is_empty() && is_cuda_ipc_tensor(&metadata.type_info.data_type) {
+            tracing::warn!(
+                "not forwarding output `{}/{}` to remote machines because GPU tensors \
+                can only be shared on the same machine",
+                output_id.0,
+                output_id.1
+            );
+        } else if !remote_receivers.is_empty() {
+            // monotonic clocks of different machines are not comparable
+            if let Some(send_time) = metadata.send_time() {
+                metadata.set_send_time(Some(SendTime {
+                    monotonic: None,
+                    ..send_time
+                }));
+            }
+            let compression = dataflow.remote_compression.get(&output_id);
+            let (compressed, uncompressed): (Vec<_>, Vec<_>) = remote_receivers
+                .into_iter()
+                .partition(|machine| compression.is_some_and(|c| c.contains_key(machine)));
+            let mut targets = Vec::new();
+            for machine in compressed {
+                let settings = compression.and_then(|c| c.get(&machine)).copied();
+                let mut metadata = metadata.clone();
+                let data = match (settings, &data_bytes) {
+                    (Some(settings), Some(data)) => {
+                        metadata.set_compression(Some(settings.algorithm));
+                        Some(compression::compress(data, &settings).wrap_err_with(|| {
+                            format!(
+                                "failed to compress output `{}/{}`",
+                                output_id.0, output_id.1
+                            )
+                        })?)
+                    }
+                    _ => data_bytes.clone(),
+                };
+                targets.push((vec![machine], metadata, data));
+            }
+            let priority = metadata.parameters.priority;
+            if !uncompressed.is_empty() {
+                targets.push((uncompressed, metadata, data_bytes));
+            }
+
+            let reliability = dataflow.remote_reliability.get(&output_id);
+            for (machines, metadata, data) in targets {
+                let mut inner = InterDaemonEvent::Output {
                     dataflow_id,
-                    node_id: output_id.0,
-                    output_id: output_id.1,
+                    node_id: output_id.0.clone(),
+                    output_id: output_id.1.clone(),
                     metadata,
-                    data: data_bytes,
-                },
-                timestamp: self.clock.new_timestamp(),
-            };
-            inter_daemon::send_inter_daemon_event(
-                &remote_receivers,
-                &mut self.inter_daemon_connections,
-                &event,
-            )
-            .await
-            .wrap_err("failed to forward output to remote receivers")?;
+                    data,
+                };
+                if let Some(cipher) = &dataflow.cipher {
+                    inner = cipher.encrypt(dataflow_id, &inner)?;
+                }
+                let event = Timestamped {
+                    inner,
+                    timestamp: self.clock.new_timestamp(),
+                };
+                // machines without a QoS preset keep the default of the transport
+                let mut by_reliability: BTreeMap<Option<Reliability>, Vec<String>> =
+                    BTreeMap::new();
+                for machine in machines {
+                    let qos = reliability.and_then(|r| r.get(&machine)).copied().flatten();
+                    by_reliability.entry(qos).or_default().push(machine);
+                }
+                for (qos, machines) in by_reliability {
+                    match &mut dataflow.pub_sub {
+                        Some(transport) => {
+                            transport
+                                .send(&machines, &event, priority, qos.unwrap_or_default())
+                                .await
+                        }
+                        None => inter_daemon::send_output_event(
+                            &machines,
+                            &mut self.inter_daemon_connections,
+                            &event,
+                            (dataflow_id, &output_id),
+                            qos == Some(Reliability::Reliable),
+                        ),
+                    }
+                    .wrap_err("failed to forward output to remote receivers")?;
+                }
+            }
         }
 
         Ok(())
@@ -980,6 +2296,29 @@ impl Daemon {
         event_sender: UnboundedSender<Timestamped<daemon_messages::NodeEvent>>,
         clock: &HLC,
     ) {
+        // deliver the retained values of latched outputs first, even if the
+        // sending node has finished in the meantime
+        for (output_id, (metadata, data)) in &dataflow.latched_values {
+            let Some(receivers) = dataflow.mappings.get(output_id) else {
+                continue;
+            };
+            for (_, input_id) in receivers.iter().filter(|(node, _)| node == &node_id) {
+                let mut metadata = metadata.clone();
+                if dataflow.has_multiple_sources(&node_id, input_id) {
+                    set_source_parameter(&mut metadata, output_id);
+                }
+                let _ = send_with_timestamp(
+                    &event_sender,
+                    daemon_messages::NodeEvent::Input {
+                        id: input_id.clone(),
+                        metadata,
+                        data: data.clone().map(DataMessage::Vec),
+                    },
+                    clock,
+                );
+            }
+        }
+
         // some inputs might have been closed already -> report those events
         let closed_inputs = dataflow
             .mappings
@@ -1027,13 +2366,16 @@ impl Daemon {
         node_id: &NodeId,
         clock: &HLC,
     ) -> eyre::Result<()> {
-        send_input_closed_events(
-            dataflow,
-            inter_daemon_connections,
-            |OutputId(source_id, _)| source_id == node_id,
-            clock,
-        )
-        .await?;
+        // the restarted instance of the node continues to send to the inputs
+        if !dataflow.restarting_nodes.contains(node_id) {
+            send_input_closed_events(
+                dataflow,
+                inter_daemon_connections,
+                |OutputId(source_id, _)| source_id == node_id,
+                clock,
+            )
+            .await?;
+        }
         dataflow.drop_channels.remove(node_id);
         Ok(())
     }
@@ -1194,11 +2536,50 @@ impl Daemon {
                     dataflow.subscribe_channels.remove(id);
                 }
             }
+            DoraEvent::ReplayOutput {
+                dataflow_id,
+                node_id,
+                output_id,
+                metadata,
+                data,
+            } => {
+                self.send_out(
+                    dataflow_id,
+                    node_id,
+                    output_id,
+                    metadata,
+                    data.map(DataMessage::Vec),
+                )
+                .await?;
+            }
+            DoraEvent::ReplayFinished { dataflow_id, nodes } => {
+                let Some(dataflow) = self.running.get_mut(&dataflow_id) else {
+                    tracing::warn!("Replay event for unknown dataflow `{dataflow_id}`");
+                    return Ok(RunStatus::Continue);
+                };
+                tracing::info!("finished replaying recorded outputs of dataflow `{dataflow_id}`");
+                for node_id in &nodes {
+                    Self::handle_outputs_done(
+                        dataflow,
+                        &mut self.inter_daemon_connections,
+                        node_id,
+                        &self.clock,
+                    )
+                    .await?;
+                }
+            }
             DoraEvent::SpawnedNodeResult {
                 dataflow_id,
                 node_id,
                 exit_status,
             } => {
+                if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                    // nodes are not restarted anymore once the dataflow is stopped
+                    if dataflow.restarting_nodes.remove(&node_id) && !dataflow.stop_sent {
+                        self.respawn_node(dataflow_id, node_id).await?;
+                        return Ok(RunStatus::Continue);
+                    }
+                }
                 let node_result = match exit_status {
                     NodeExitStatus::Success => {
                         tracing::info!("node {dataflow_id}/{node_id} finished successfully");
@@ -1293,19 +2674,64 @@ async fn send_output_to_local_receivers(
     dataflow: &mut RunningDataflow,
     metadata: &dora_core::message::Metadata,
     data: Option<DataMessage>,
+    from_remote: bool,
     clock: &HLC,
 ) -> Result<Option<AVec<u8, ConstAlign<128>>>, eyre::ErrReport> {
     let timestamp = metadata.timestamp();
     let empty_set = BTreeSet::new();
     let output_id = OutputId(node_id, output_id);
     let local_receivers = dataflow.mappings.get(&output_id).unwrap_or(&empty_set);
-    let OutputId(node_id, _) = output_id;
+    let node_id = &output_id.0;
     let mut closed = Vec::new();
     for (receiver_id, input_id) in local_receivers {
         if let Some(channel) = dataflow.subscribe_channels.get(receiver_id) {
+            let len = data.as_ref().map(|d| d.len()).unwrap_or(0);
+            let downsampled_by_sender = from_remote
+                && dataflow
+                    .shared_downsample
+                    .get(&output_id)
+                    .is_some_and(|d| d.is_some());
+            if !dataflow.downsamplers.is_empty() && !downsampled_by_sender {
+                let input = (receiver_id.clone(), input_id.clone());
+                if let Some(downsampler) = dataflow.downsamplers.get_mut(&input) {
+                    if !downsampler.accept(Instant::now()) {
+                        tracing::trace!(
+                            "downsampling drops message for `{receiver_id}/{input_id}`"
+                        );
+                        continue;
+                    }
+                }
+            }
+            if !dataflow.rate_limiters.is_empty() {
+                let input = (receiver_id.clone(), input_id.clone());
+                if let Some(bucket) = dataflow.rate_limiters.get_mut(&input) {
+                    if !bucket.try_take(len, Instant::now()) {
+                        tracing::trace!(
+                            "dropping message for `{receiver_id}/{input_id}` because of its rate limit"
+                        );
+                        continue;
+                    }
+                }
+            }
+            if let Some(stats) = dataflow.node_stats.get(receiver_id) {
+                stats.input_delivered(input_id, len);
+            }
+            let mut metadata = metadata.clone();
+            if dataflow.has_multiple_sources(receiver_id, input_id) {
+                // allow the receiver to tell the sources of the input apart
+                set_source_parameter(&mut metadata, &output_id);
+            }
+            #[cfg(feature = "telemetry")]
+            record_transfer_span(
+                &mut metadata,
+                dataflow.id,
+                &output_id,
+                (receiver_id, input_id),
+                from_remote,
+            );
             let item = daemon_messages::NodeEvent::Input {
                 id: input_id.clone(),
-                metadata: metadata.clone(),
+                metadata,
                 data: data.clone(),
             };
             match channel.send(Timestamped {
@@ -1362,9 +2788,20 @@ async fn send_output_to_local_receivers(
         // check if all local subscribers are finished with the token
         dataflow.check_drop_token(token, clock).await?;
     }
+    if dataflow.latched_outputs.contains(&output_id) {
+        dataflow
+            .latched_values
+            .insert(output_id, (metadata.clone(), data_bytes.clone()));
+    }
     Ok(data_bytes)
 }
 
+/// Checks whether the input connects two operators of the same runtime node.
+fn is_runtime_internal(node: &ResolvedNode, mapping: &InputMapping) -> bool {
+    matches!(node.kind, CoreNodeKind::Runtime(_))
+        && matches!(mapping, InputMapping::User(mapping) if mapping.source == node.id)
+}
+
 fn node_inputs(node: &ResolvedNode) -> BTreeMap<DataId, Input> {
     match &node.kind {
         CoreNodeKind::Custom(n) => n.run_config.inputs.clone(),
@@ -1381,50 +2818,125 @@ async fn send_input_closed_events<F>(
 where
     F: FnMut(&OutputId) -> bool,
 {
-    let local_node_inputs: BTreeSet<_> = dataflow
+    let closed_outputs: BTreeSet<_> = dataflow
         .mappings
+        .keys()
+        .filter(|k| filter(k))
+        .cloned()
+        .collect();
+    let local_node_inputs: BTreeSet<_> = closed_outputs
         .iter()
-        .filter(|(k, _)| filter(k))
-        .flat_map(|(_, v)| v)
+        .flat_map(|output_id| &dataflow.mappings[output_id])
         .cloned()
         .collect();
     for (receiver_id, input_id) in &local_node_inputs {
-        close_input(dataflow, receiver_id, input_id, clock);
+        close_input(dataflow, receiver_id, input_id, &closed_outputs, clock);
     }
 
     let mut external_node_inputs = BTreeMap::new();
+    let mut external_sources = BTreeSet::new();
     for (output_id, mapping) in &mut dataflow.open_external_mappings {
-        if filter(output_id) {
+        if filter(output_id) && !mapping.is_empty() {
             external_node_inputs.append(mapping);
+            external_sources.insert((output_id.0.clone(), output_id.1.clone()));
         }
     }
     if !external_node_inputs.is_empty() {
         for (target_machine, inputs) in external_node_inputs {
+            let mut inner = InterDaemonEvent::InputsClosed {
+                dataflow_id: dataflow.id,
+                inputs,
+                sources: external_sources.clone(),
+            };
+            if let Some(cipher) = &dataflow.cipher {
+                inner = cipher.encrypt(dataflow.id, &inner)?;
+            }
             let event = Timestamped {
-                inner: InterDaemonEvent::InputsClosed {
-                    dataflow_id: dataflow.id,
-                    inputs,
-                },
+                inner,
                 timestamp: clock.new_timestamp(),
             };
-            inter_daemon::send_inter_daemon_event(
-                &[target_machine],
-                inter_daemon_connections,
-                &event,
-            )
-            .await
+            match &mut dataflow.pub_sub {
+                // use the lowest priority so that the event doesn't overtake
+                // the last outputs of the closed sources
+                Some(transport) => {
+                    transport
+                        .send(
+                            &[target_machine],
+                            &event,
+                            MessagePriority::Low,
+                            Reliability::Reliable,
+                        )
+                        .await
+                }
+                None => inter_daemon::send_inter_daemon_event(
+                    &[target_machine],
+                    inter_daemon_connections,
+                    &event,
+                ),
+            }
             .wrap_err("failed to sent InputClosed event to remote receiver")?;
         }
     }
     Ok(())
 }
 
+fn set_source_parameter(metadata: &mut Metadata, OutputId(node_id, output_id): &OutputId) {
+    metadata
+        .parameters
+        .insert(SOURCE_PARAMETER, format!("{node_id}/{output_id}"));
+}
+
+/// Records the transfer of a message from the sending node to the input queue
+/// of a receiver as a span, e.g. to see the latency of an edge in Jaeger.
+///
+/// The span starts when the message was sent and continues the trace of the
+/// message. The receiver continues the trace from the span. For messages from
+/// other machines, the span duration depends on the clock synchronization
+/// between the machines.
+#[cfg(feature = "telemetry")]
+fn record_transfer_span(
+    metadata: &mut Metadata,
+    dataflow_id: Uuid,
+    OutputId(node_id, output_id): &OutputId,
+    (receiver_id, input_id): (&NodeId, &DataId),
+    from_remote: bool,
+) {
+    let start = match metadata.send_time() {
+        Some(send_time) => std::time::UNIX_EPOCH + Duration::from_nanos(send_time.wall_clock),
+        None => metadata.timestamp().get_time().to_system_time(),
+    };
+    metadata.parameters.open_telemetry_context = dora_tracing::telemetry::record_span(
+        "transfer",
+        &metadata.parameters.open_telemetry_context,
+        start,
+        &[
+            ("dataflow", dataflow_id.to_string()),
+            ("source", format!("{node_id}/{output_id}")),
+            ("target", format!("{receiver_id}/{input_id}")),
+            ("remote", from_remote.to_string()),
+        ],
+    );
+}
+
+/// Closes the given input because the `closed_sources` were closed.
+///
+/// Inputs with multiple sources stay open until all their sources are closed.
 fn close_input(
     dataflow: &mut RunningDataflow,
     receiver_id: &NodeId,
     input_id: &DataId,
+    closed_sources: &BTreeSet<OutputId>,
     clock: &HLC,
 ) {
+    if let Some(open_sources) = dataflow
+        .open_sources
+        .get_mut(&(receiver_id.clone(), input_id.clone()))
+    {
+        open_sources.retain(|source| !closed_sources.contains(source));
+        if !open_sources.is_empty() {
+            return;
+        }
+    }
     if let Some(open_inputs) = dataflow.open_inputs.get_mut(receiver_id) {
         if !open_inputs.remove(input_id) {
             return;
@@ -1450,6 +2962,12 @@ fn close_input(
 struct RunningNode {
     pid: Option<u32>,
     node_config: NodeConfig,
+    /// Appends lines to the log file of the node, if the daemon writes one.
+    ///
+    /// Weak, because the log file is closed once the node exits.
+    log_sender: Option<mpsc::WeakSender<String>>,
+    /// The container that the node runs in, if any.
+    container: Option<spawn::NodeContainer>,
 }
 
 pub struct RunningDataflow {
@@ -1465,6 +2983,8 @@ pub struct RunningDataflow {
     running_nodes: BTreeMap<NodeId, RunningNode>,
 
     open_external_mappings: HashMap<OutputId, BTreeMap<String, BTreeSet<InputId>>>,
+    /// Compression settings for outputs that are sent to other machines.
+    remote_compression: HashMap<OutputId, BTreeMap<String, InputCompression>>,
 
     pending_drop_tokens: HashMap<DropToken, DropTokenInformation>,
 
@@ -1482,6 +3002,79 @@ pub struct RunningDataflow {
     grace_duration_kills: Arc<crossbeam_skiplist::SkipSet<NodeId>>,
 
     node_stderr_most_recent: BTreeMap<NodeId, Arc<ArrayQueue<String>>>,
+
+    node_stats: BTreeMap<NodeId, stats::SharedNodeStats>,
+
+    /// Records the outputs of this dataflow to disk, if enabled in the descriptor.
+    recorder: Option<record::DataflowRecorder>,
+    resource_limits: ResourceLimits,
+    /// Whether one of the `resource_limits` was exceeded at the last check.
+    resource_limit_exceeded: bool,
+    /// Machine whose clock is used for the normalized dataflow timestamps.
+    clock_reference: String,
+    /// Recorded outputs to replay once the dataflow is started.
+    replay: Option<replay::ReplaySource>,
+    _replay_handle: Option<futures::future::RemoteHandle<()>>,
+    /// Set if the dataflow exchanges data with other machines over a pub-sub
+    /// backend such as zenoh.
+    pub_sub: Option<pub_sub_transport::PubSubTransport>,
+    /// Encrypts the events that are exchanged with other machines, if enabled.
+    cipher: Option<encryption::DataflowCipher>,
+    /// Outputs whose last value is delivered to nodes that subscribe later.
+    latched_outputs: BTreeSet<OutputId>,
+    /// The last value sent on each of the `latched_outputs`.
+    latched_values: HashMap<OutputId, (Metadata, Option<AVec<u8, ConstAlign<128>>>)>,
+    /// Sources that are still open, for local inputs that have multiple sources.
+    open_sources: HashMap<InputId, BTreeSet<OutputId>>,
+    /// Enforces the rate limits of local inputs.
+    rate_limiters: HashMap<InputId, rate_limit::TokenBucket>,
+    /// Rate limits for outputs that are sent to other machines.
+    ///
+    /// Set to `None` if one of the receiving inputs on the machine has no limit.
+    remote_rate_limiters: HashMap<OutputId, BTreeMap<String, Option<rate_limit::TokenBucket>>>,
+    /// Enforces the `downsample` settings of local inputs.
+    downsamplers: HashMap<InputId, downsample::Downsampler>,
+    /// The `downsample` setting of the local inputs that read an output, if
+    /// all of them use the same one.
+    ///
+    /// In this case, the sending daemon downsamples messages of remote outputs
+    /// already, so they must not be downsampled again.
+    shared_downsample: HashMap<OutputId, Option<Downsample>>,
+    /// Downsamples outputs that are sent to other machines, if all receiving
+    /// inputs on the machine use the same settings.
+    remote_downsamplers: HashMap<OutputId, BTreeMap<String, Option<downsample::Downsampler>>>,
+    /// Reliability of outputs that are sent to other machines, as requested by
+    /// the QoS presets of the receiving inputs.
+    ///
+    /// `None` if no receiving input on the machine sets a preset.
+    remote_reliability: HashMap<OutputId, BTreeMap<String, Option<Reliability>>>,
+    /// Heartbeat configuration of the local nodes that are monitored.
+    heartbeats: BTreeMap<NodeId, NodeHeartbeat>,
+    /// Nodes that were reported as unresponsive and didn't recover yet.
+    unresponsive_nodes: BTreeSet<NodeId>,
+    /// The local nodes and the descriptor they were spawned from, to spawn
+    /// them again on restart.
+    nodes: BTreeMap<NodeId, ResolvedNode>,
+    descriptor: Option<Descriptor>,
+    /// Nodes that are spawned again once they exited.
+    ///
+    /// The inputs that these nodes send to are not closed when they stop.
+    restarting_nodes: BTreeSet<NodeId>,
+    /// Exports the node statistics over OTLP, if configured in the descriptor.
+    #[cfg(feature = "metrics")]
+    metrics: Option<dora_tracing::metrics::DataflowMetrics>,
+}
+
+impl Drop for RunningDataflow {
+    fn drop(&mut self) {
+        // `kill_on_drop` only kills the container engine command, so the
+        // containers would keep running after the daemon exits
+        for node in self.running_nodes.values() {
+            if let Some(container) = &node.container {
+                container.remove();
+            }
+        }
+    }
 }
 
 impl RunningDataflow {
@@ -1496,6 +3089,7 @@ impl RunningDataflow {
             open_inputs: BTreeMap::new(),
             running_nodes: BTreeMap::new(),
             open_external_mappings: HashMap::new(),
+            remote_compression: HashMap::new(),
             pending_drop_tokens: HashMap::new(),
             _timer_handles: Vec::new(),
             stop_sent: false,
@@ -1503,6 +3097,209 @@ impl RunningDataflow {
             cascading_error_causes: Default::default(),
             grace_duration_kills: Default::default(),
             node_stderr_most_recent: BTreeMap::new(),
+            node_stats: BTreeMap::new(),
+            recorder: None,
+            resource_limits: Default::default(),
+            resource_limit_exceeded: false,
+            clock_reference: String::new(),
+            replay: None,
+            _replay_handle: None,
+            pub_sub: None,
+            cipher: None,
+            latched_outputs: BTreeSet::new(),
+            latched_values: HashMap::new(),
+            open_sources: HashMap::new(),
+            rate_limiters: HashMap::new(),
+            remote_rate_limiters: HashMap::new(),
+            downsamplers: HashMap::new(),
+            shared_downsample: HashMap::new(),
+            remote_downsamplers: HashMap::new(),
+            remote_reliability: HashMap::new(),
+            heartbeats: BTreeMap::new(),
+            unresponsive_nodes: BTreeSet::new(),
+            nodes: BTreeMap::new(),
+            descriptor: None,
+            restarting_nodes: BTreeSet::new(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Returns the statistics of the given node, creating them if needed.
+    fn node_stats(&mut self, node_id: &NodeId) -> stats::SharedNodeStats {
+        #[cfg(feature = "metrics")]
+        if !self.node_stats.contains_key(node_id) {
+            let metrics = self.metrics.as_ref().map(|m| m.node(node_id.as_ref()));
+            self.node_stats.insert(
+                node_id.clone(),
+                stats::SharedNodeStats::with_metrics(metrics),
+            );
+        }
+        self.node_stats.entry(node_id.clone()).or_default().clone()
+    }
+
+    fn has_multiple_sources(&self, node_id: &NodeId, input_id: &DataId) -> bool {
+        !self.open_sources.is_empty()
+            && self
+                .open_sources
+                .contains_key(&(node_id.clone(), input_id.clone()))
+    }
+
+    /// Registers an input of a node that runs on the given machine.
+    fn add_input(
+        &mut self,
+        node_id: &NodeId,
+        machine: &str,
+        local: bool,
+        input_id: DataId,
+        input: Input,
+    ) {
+        let input_id = (node_id.clone(), input_id);
+        if local {
+            self.open_inputs
+                .entry(node_id.clone())
+                .or_default()
+                .insert(input_id.1.clone());
+            if !input.additional_sources.is_empty() {
+                let sources = input.sources().filter_map(|mapping| match mapping {
+                    InputMapping::User(m) => Some(OutputId(m.source.clone(), m.output.clone())),
+                    InputMapping::Timer { .. } => None,
+                });
+                self.open_sources
+                    .insert(input_id.clone(), sources.collect());
+            }
+            if let Some(limit) = input.rate_limit {
+                self.rate_limiters.insert(
+                    input_id.clone(),
+                    rate_limit::TokenBucket::new(limit, Instant::now()),
+                );
+            }
+            if let Some(config) = input.downsample {
+                self.downsamplers
+                    .insert(input_id.clone(), downsample::Downsampler::new(config));
+            }
+        }
+        if input.qos.is_some_and(|qos| qos.is_latched()) {
+            for mapping in input.sources() {
+                if let InputMapping::User(mapping) = mapping {
+                    self.latched_outputs
+                        .insert(OutputId(mapping.source.clone(), mapping.output.clone()));
+                }
+            }
+        }
+        for mapping in input.sources() {
+            match mapping {
+                InputMapping::User(mapping) if local => {
+                    let output_id = OutputId(mapping.source.clone(), mapping.output.clone());
+                    match self.shared_downsample.entry(output_id.clone()) {
+                        hash_map::Entry::Vacant(entry) => {
+                            entry.insert(input.downsample);
+                        }
+                        hash_map::Entry::Occupied(mut entry) => {
+                            if *entry.get() != input.downsample {
+                                entry.insert(None);
+                            }
+                        }
+                    }
+                    self.mappings
+                        .entry(output_id)
+                        .or_default()
+                        .insert(input_id.clone());
+                }
+                InputMapping::User(mapping) => {
+                    let output_id = OutputId(mapping.source.clone(), mapping.output.clone());
+                    if let Some(compression) = input.compression {
+                        self.remote_compression
+                            .entry(output_id.clone())
+                            .or_default()
+                            .entry(machine.to_owned())
+                            .or_insert(compression);
+                    }
+                    let limiter = self
+                        .remote_rate_limiters
+                        .entry(output_id.clone())
+                        .or_default()
+                        .entry(machine.to_owned());
+                    match limiter {
+                        btree_map::Entry::Vacant(entry) => {
+                            entry.insert(
+                                input.rate_limit.map(|limit| {
+                                    rate_limit::TokenBucket::new(limit, Instant::now())
+                                }),
+                            );
+                        }
+                        btree_map::Entry::Occupied(mut entry) => {
+                            let merged = entry.get().as_ref().zip(input.rate_limit).map(
+                                |(bucket, limit)| {
+                                    rate_limit::most_permissive(bucket.limit(), limit)
+                                },
+                            );
+                            entry.insert(
+                                merged.map(|limit| {
+                                    rate_limit::TokenBucket::new(limit, Instant::now())
+                                }),
+                            );
+                        }
+                    }
+                    let downsampler = self
+                        .remote_downsamplers
+                        .entry(output_id.clone())
+                        .or_default()
+                        .entry(machine.to_owned());
+                    match downsampler {
+                        btree_map::Entry::Vacant(entry) => {
+                            entry.insert(input.downsample.map(downsample::Downsampler::new));
+                        }
+                        btree_map::Entry::Occupied(mut entry) => {
+                            if entry.get().as_ref().map(|d| d.config()) != input.downsample {
+                                entry.insert(None);
+                            }
+                        }
+                    }
+                    let reliability = input.qos.map(|qos| {
+                        if qos.is_reliable() {
+                            Reliability::Reliable
+                        } else {
+                            Reliability::BestEffort
+                        }
+                    });
+                    let merged = self
+                        .remote_reliability
+                        .entry(output_id.clone())
+                        .or_default()
+                        .entry(machine.to_owned());
+                    match merged {
+                        btree_map::Entry::Vacant(entry) => {
+                            entry.insert(reliability);
+                        }
+                        btree_map::Entry::Occupied(mut entry) => {
+                            // send reliably if any of the inputs requires it
+                            let merged = match (*entry.get(), reliability) {
+                                (Some(Reliability::Reliable), _)
+                                | (_, Some(Reliability::Reliable)) => Some(Reliability::Reliable),
+                                (Some(Reliability::BestEffort), Some(Reliability::BestEffort)) => {
+                                    Some(Reliability::BestEffort)
+                                }
+                                _ => None,
+                            };
+                            entry.insert(merged);
+                        }
+                    }
+                    self.open_external_mappings
+                        .entry(output_id)
+                        .or_default()
+                        .entry(machine.to_owned())
+                        .or_default()
+                        .insert(input_id.clone());
+                }
+                InputMapping::Timer { interval } if local => {
+                    self.timers
+                        .entry(*interval)
+                        .or_default()
+                        .insert(input_id.clone());
+                }
+                InputMapping::Timer { .. } => {}
+            }
         }
     }
 
@@ -1534,6 +3331,8 @@ impl RunningDataflow {
                             open_telemetry_context: serialize_context(&span.context()),
                             #[cfg(not(feature = "telemetry"))]
                             open_telemetry_context: "".into(),
+                            entries: Default::default(),
+                            priority: Default::default(),
                         },
                     );
 
@@ -1556,6 +3355,10 @@ impl RunningDataflow {
             self._timer_handles.push(handle);
         }
 
+        if let Some(replay) = self.replay.take() {
+            self._replay_handle = Some(replay.start(self.id, events_tx.clone(), clock.clone()));
+        }
+
         Ok(())
     }
 
@@ -1564,28 +3367,35 @@ impl RunningDataflow {
             let _ = send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, clock);
         }
 
-        let running_nodes = self.running_nodes.clone();
-        let grace_duration_kills = self.grace_duration_kills.clone();
-        tokio::spawn(async move {
-            let duration = grace_duration.unwrap_or(Duration::from_millis(2000));
-            tokio::time::sleep(duration).await;
-            let mut system = sysinfo::System::new();
-            system.refresh_processes();
+        terminate_after_grace_duration(
+            self.running_nodes.clone(),
+            grace_duration,
+            self.grace_duration_kills.clone(),
+        );
+        self.stop_sent = true;
+    }
 
-            for (node, node_details) in running_nodes.iter() {
-                if let Some(pid) = node_details.pid {
-                    if let Some(process) = system.process(Pid::from(pid as usize)) {
-                        grace_duration_kills.insert(node.clone());
-                        process.kill();
-                        warn!(
-                            "{node} was killed due to not stopping within the {:#?} grace period",
-                            duration
-                        )
+    /// Kills all running nodes immediately.
+    ///
+    /// Also removes the shared memory regions of the nodes, as the daemon exits
+    /// without waiting for their listener loops to finish.
+    fn kill_all(&mut self) {
+        let mut system = sysinfo::System::new();
+        system.refresh_processes();
+        for (node, node_details) in &self.running_nodes {
+            if let Some(pid) = node_details.pid {
+                if let Some(process) = system.process(Pid::from(pid as usize)) {
+                    self.grace_duration_kills.insert(node.clone());
+                    if let Some(container) = &node_details.container {
+                        container.remove();
                     }
+                    process.kill();
                 }
             }
-        });
-        self.stop_sent = true;
+            node_communication::unlink_shared_memory(
+                &node_details.node_config.daemon_communication,
+            );
+        }
     }
 
     fn open_inputs(&self, node_id: &NodeId) -> &BTreeSet<DataId> {
@@ -1625,7 +3435,7 @@ impl RunningDataflow {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct OutputId(NodeId, DataId);
 type InputId = (NodeId, DataId);
 
@@ -1686,6 +3496,12 @@ pub enum DaemonNodeEvent {
     EventStreamDropped {
         reply_sender: oneshot::Sender<DaemonReply>,
     },
+    OperatorError {
+        error: daemon_messages::OperatorError,
+    },
+    Log {
+        record: daemon_messages::NodeLogRecord,
+    },
 }
 
 #[derive(Debug)]
@@ -1706,6 +3522,17 @@ pub enum DoraEvent {
         node_id: NodeId,
         exit_status: NodeExitStatus,
     },
+    ReplayOutput {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        output_id: DataId,
+        metadata: Metadata,
+        data: Option<AVec<u8, ConstAlign<128>>>,
+    },
+    ReplayFinished {
+        dataflow_id: DataflowId,
+        nodes: BTreeSet<NodeId>,
+    },
 }
 
 #[must_use]
@@ -1714,6 +3541,66 @@ enum RunStatus {
     Exit,
 }
 
+/// Terminates the given nodes if they are still running after the grace
+/// duration, escalating to a kill if they ignore the termination signal.
+fn terminate_after_grace_duration(
+    running_nodes: BTreeMap<NodeId, RunningNode>,
+    grace_duration: Option<Duration>,
+    grace_duration_kills: Arc<crossbeam_skiplist::SkipSet<NodeId>>,
+) {
+    tokio::spawn(async move {
+        let duration = grace_duration.unwrap_or(Duration::from_millis(2000));
+        tokio::time::sleep(duration).await;
+        let mut system = sysinfo::System::new();
+        system.refresh_processes();
+
+        // ask the remaining nodes to terminate first
+        let mut terminated = false;
+        for (node, node_details) in running_nodes.iter() {
+            if let Some(pid) = node_details.pid {
+                if let Some(process) = system.process(Pid::from(pid as usize)) {
+                    grace_duration_kills.insert(node.clone());
+                    warn!(
+                        "{node} did not stop within the {:#?} grace period -> terminating it",
+                        duration
+                    );
+                    if let Some(container) = &node_details.container {
+                        // the engine kills the container after the timeout
+                        container.stop(TERMINATE_TIMEOUT);
+                        terminated = true;
+                        continue;
+                    }
+                    match process.kill_with(sysinfo::Signal::Term) {
+                        Some(_) => terminated = true,
+                        // platform does not support SIGTERM
+                        None => {
+                            process.kill();
+                        }
+                    }
+                }
+            }
+        }
+        if !terminated {
+            return;
+        }
+
+        // escalate to SIGKILL for nodes that ignore SIGTERM
+        tokio::time::sleep(TERMINATE_TIMEOUT).await;
+        system.refresh_processes();
+        for (node, node_details) in running_nodes.iter() {
+            if let Some(pid) = node_details.pid {
+                if let Some(process) = system.process(Pid::from(pid as usize)) {
+                    if let Some(container) = &node_details.container {
+                        container.remove();
+                    }
+                    process.kill();
+                    warn!("{node} was killed because it did not react to SIGTERM");
+                }
+            }
+        }
+    });
+}
+
 fn send_with_timestamp<T>(
     sender: &UnboundedSender<Timestamped<T>>,
     event: T,
@@ -1728,17 +3615,19 @@ fn send_with_timestamp<T>(
 fn set_up_ctrlc_handler(
     clock: Arc<HLC>,
 ) -> Result<impl Stream<Item = Timestamped<Event>>, eyre::ErrReport> {
-    let (ctrlc_tx, ctrlc_rx) = mpsc::channel(1);
+    let (ctrlc_tx, ctrlc_rx) = mpsc::channel(2);
 
-    let mut ctrlc_sent = false;
+    let mut ctrlc_count = 0;
     ctrlc::set_handler(move || {
-        if ctrlc_sent {
-            tracing::warn!("received second ctrlc signal -> aborting immediately");
+        ctrlc_count += 1;
+        if ctrlc_count > 2 {
+            tracing::warn!("received third stop signal -> aborting immediately");
             std::process::abort();
         } else {
-            tracing::info!("received ctrlc signal");
+            tracing::info!("received stop signal");
+            // the second signal makes the daemon kill all nodes before exiting
             if ctrlc_tx
-                .blocking_send(Timestamped {
+                .try_send(Timestamped {
                     inner: Event::CtrlC,
                     timestamp: clock.new_timestamp(),
                 })
@@ -1746,8 +3635,6 @@ fn set_up_ctrlc_handler(
             {
                 tracing::error!("failed to report ctrl-c event to dora-coordinator");
             }
-
-            ctrlc_sent = true;
         }
     })
     .wrap_err("failed to set ctrl-c handler")?;
@@ -1774,3 +3661,77 @@ impl CascadingErrorCauses {
         self.caused_by.entry(affected_node).or_insert(causing_node);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU32;
+
+    fn input(downsample: Option<u32>) -> Input {
+        Input {
+            mapping: InputMapping::User(UserInputMapping {
+                source: "source".to_owned().into(),
+                output: "out".to_owned().into(),
+            }),
+            additional_sources: Vec::new(),
+            queue_size: None,
+            priority: None,
+            compression: None,
+            rate_limit: None,
+            downsample: downsample.map(|every| Downsample {
+                every: NonZeroU32::new(every),
+                max_frequency: None,
+            }),
+            qos: None,
+        }
+    }
+
+    /// Registers the inputs of a node on machine `b` with the daemons of the
+    /// sending machine `a` and the receiving machine `b`.
+    fn dataflows(inputs: &[Option<u32>]) -> (RunningDataflow, RunningDataflow) {
+        let id = Uuid::now_v7();
+        let mut sender = RunningDataflow::new(id, "a".into());
+        let mut receiver = RunningDataflow::new(id, "b".into());
+        let node_id: NodeId = "receiver".to_owned().into();
+        for (i, &downsample) in inputs.iter().enumerate() {
+            let input_id: DataId = format!("in_{i}").into();
+            sender.add_input(&node_id, "b", false, input_id.clone(), input(downsample));
+            receiver.add_input(&node_id, "b", true, input_id, input(downsample));
+        }
+        (sender, receiver)
+    }
+
+    fn output_id() -> OutputId {
+        OutputId("source".to_owned().into(), "out".to_owned().into())
+    }
+
+    fn downsampled_by_sender(sender: &RunningDataflow) -> bool {
+        sender.remote_downsamplers[&output_id()]["b"].is_some()
+    }
+
+    fn skipped_by_receiver(receiver: &RunningDataflow) -> bool {
+        receiver.shared_downsample[&output_id()].is_some()
+    }
+
+    #[test]
+    fn shared_downsample_is_applied_once() {
+        let (sender, receiver) = dataflows(&[Some(2), Some(2)]);
+        assert!(downsampled_by_sender(&sender));
+        assert!(skipped_by_receiver(&receiver));
+    }
+
+    #[test]
+    fn differing_downsample_is_applied_by_receiver() {
+        for inputs in [
+            &[Some(2), Some(3)][..],
+            &[Some(2), None],
+            &[None, Some(2)],
+            &[Some(2), Some(3), Some(2)],
+            &[None, None],
+        ] {
+            let (sender, receiver) = dataflows(inputs);
+            assert!(!downsampled_by_sender(&sender), "{inputs:?}");
+            assert!(!skipped_by_receiver(&receiver), "{inputs:?}");
+        }
+    }
+}
@@ -1,12 +1,28 @@
 use crate::tcp_utils::{tcp_receive, tcp_send};
 use dora_core::daemon_messages::{DaemonReply, DaemonRequest, DynamicNodeEvent, Timestamped};
+#[cfg(unix)]
+use dora_core::topics::LocalSocketAddr;
 use eyre::Context;
 use std::{io::ErrorKind, net::SocketAddr};
 use tokio::{
-    net::{TcpListener, TcpStream},
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
     sync::oneshot,
 };
 
+/// Where the daemon listens for connections of dynamic nodes.
+#[derive(Debug, Clone)]
+pub enum LocalListenConfig {
+    Tcp(u16),
+    #[cfg(unix)]
+    Unix {
+        addr: LocalSocketAddr,
+        /// File permissions of the socket, e.g. `0o600` to restrict access to the
+        /// current user. Ignored for abstract sockets.
+        permissions: u32,
+    },
+}
+
 #[derive(Debug)]
 pub struct DynamicNodeEventWrapper {
     pub event: DynamicNodeEvent,
@@ -37,6 +53,192 @@ pub async fn spawn_listener_loop(
     Ok(listen_port)
 }
 
+#[cfg(unix)]
+pub fn spawn_unix_listener_loop(
+    addr: &LocalSocketAddr,
+    permissions: u32,
+    machine_id: String,
+    events_tx: flume::Sender<Timestamped<DynamicNodeEventWrapper>>,
+) -> eyre::Result<SocketFile> {
+    let (listener, socket_file) = bind_unix_socket(addr, permissions)
+        .wrap_err_with(|| format!("failed to create local unix socket listener at `{addr}`"))?;
+    tracing::info!("listening for dynamic nodes of machine `{machine_id}` on unix socket `{addr}`");
+
+    tokio::spawn(async move {
+        loop {
+            match listener
+                .accept()
+                .await
+                .wrap_err("failed to accept new connection")
+            {
+                Err(err) => {
+                    tracing::info!("{err}");
+                }
+                Ok((connection, _)) => {
+                    tokio::spawn(handle_connection_loop(connection, events_tx.clone()));
+                }
+            }
+        }
+    });
+
+    Ok(socket_file)
+}
+
+/// Removes the file of a unix socket when dropped.
+///
+/// Abstract sockets have no file, they are removed together with the listener.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct SocketFile(Option<SocketFileId>);
+
+/// Path of a socket file and the device and inode numbers it had when it was
+/// created, to detect if it was replaced in the meantime.
+#[cfg(unix)]
+#[derive(Debug)]
+struct SocketFileId {
+    path: std::path::PathBuf,
+    dev: u64,
+    ino: u64,
+}
+
+#[cfg(unix)]
+impl SocketFileId {
+    fn is_current(&self) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        std::fs::symlink_metadata(&self.path)
+            .is_ok_and(|meta| meta.dev() == self.dev && meta.ino() == self.ino)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Some(file) = &self.0 {
+            // don't remove the socket of another daemon that replaced ours
+            if !file.is_current() {
+                tracing::warn!(
+                    "not removing socket `{}` because it was replaced",
+                    file.path.display()
+                );
+                return;
+            }
+            if let Err(err) = std::fs::remove_file(&file.path) {
+                tracing::warn!("failed to remove socket `{}`: {err}", file.path.display());
+            }
+        }
+    }
+}
+
+/// Removes the socket file of a previous daemon run, if there is one.
+///
+/// Fails if the path exists but is not a socket, or if another process is
+/// still listening on the socket.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> eyre::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| format!("failed to check `{}`", path.display()))
+        }
+    };
+    if !metadata.file_type().is_socket() {
+        eyre::bail!("`{}` already exists and is not a socket", path.display());
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        eyre::bail!(
+            "address in use: another process is listening on `{}`",
+            path.display()
+        );
+    }
+    std::fs::remove_file(path)
+        .wrap_err_with(|| format!("failed to remove stale socket `{}`", path.display()))
+}
+
+#[cfg(unix)]
+fn bind_unix_socket(
+    addr: &LocalSocketAddr,
+    permissions: u32,
+) -> eyre::Result<(tokio::net::UnixListener, SocketFile)> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    match addr {
+        LocalSocketAddr::Path(path) => {
+            remove_stale_socket(path)?;
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent).wrap_err_with(|| {
+                    format!("failed to create socket directory `{}`", parent.display())
+                })?;
+            }
+            // Bind in a private directory first and only move the socket to its
+            // final path once its permissions are set, so that other users can't
+            // connect in between. Changing the umask instead would affect all
+            // threads of the daemon.
+            let file_name = path
+                .file_name()
+                .ok_or_else(|| eyre::eyre!("socket path `{}` has no file name", path.display()))?;
+            let private_dir = path.with_file_name(format!(
+                ".{}.{}",
+                file_name.to_string_lossy(),
+                std::process::id()
+            ));
+            std::fs::DirBuilder::new()
+                .mode(0o700)
+                .create(&private_dir)
+                .wrap_err_with(|| {
+                    format!("failed to create directory `{}`", private_dir.display())
+                })?;
+            let result = (|| -> eyre::Result<_> {
+                let tmp_path = private_dir.join(file_name);
+                let listener = tokio::net::UnixListener::bind(&tmp_path)?;
+                std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(permissions))
+                    .wrap_err("failed to set socket permissions")?;
+                // unlike a rename, a hard link fails instead of replacing a
+                // socket that another daemon created in the meantime
+                std::fs::hard_link(&tmp_path, path).map_err(|err| {
+                    if err.kind() == ErrorKind::AlreadyExists {
+                        eyre::eyre!(
+                            "address in use: `{}` was created concurrently",
+                            path.display()
+                        )
+                    } else {
+                        eyre::Report::new(err).wrap_err("failed to move socket to its final path")
+                    }
+                })?;
+                let metadata = std::fs::symlink_metadata(path)
+                    .wrap_err("failed to read metadata of socket")?;
+                let file = SocketFileId {
+                    path: path.clone(),
+                    dev: metadata.dev(),
+                    ino: metadata.ino(),
+                };
+                Ok((listener, SocketFile(Some(file))))
+            })();
+            let _ = std::fs::remove_dir_all(&private_dir);
+            result
+        }
+        #[cfg(target_os = "linux")]
+        LocalSocketAddr::Abstract(name) => {
+            use std::os::linux::net::SocketAddrExt;
+
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+            let listener = std::os::unix::net::UnixListener::bind_addr(&addr)?;
+            listener.set_nonblocking(true)?;
+            Ok((
+                tokio::net::UnixListener::from_std(listener)?,
+                SocketFile(None),
+            ))
+        }
+        #[cfg(not(target_os = "linux"))]
+        LocalSocketAddr::Abstract(_) => {
+            eyre::bail!("abstract unix sockets are only supported on Linux")
+        }
+    }
+}
+
 async fn listener_loop(
     listener: TcpListener,
     events_tx: flume::Sender<Timestamped<DynamicNodeEventWrapper>>,
@@ -51,6 +253,9 @@ async fn listener_loop(
                 tracing::info!("{err}");
             }
             Ok((connection, _)) => {
+                if let Err(err) = connection.set_nodelay(true) {
+                    tracing::warn!("failed to set nodelay for connection: {err}");
+                }
                 tokio::spawn(handle_connection_loop(connection, events_tx.clone()));
             }
         }
@@ -58,13 +263,9 @@ async fn listener_loop(
 }
 
 async fn handle_connection_loop(
-    mut connection: TcpStream,
+    mut connection: impl AsyncRead + AsyncWrite + Unpin,
     events_tx: flume::Sender<Timestamped<DynamicNodeEventWrapper>>,
 ) {
-    if let Err(err) = connection.set_nodelay(true) {
-        tracing::warn!("failed to set nodelay for connection: {err}");
-    }
-
     loop {
         match receive_message(&mut connection).await {
             Ok(Some(Timestamped {
@@ -118,7 +319,7 @@ async fn handle_connection_loop(
 }
 
 async fn receive_message(
-    connection: &mut TcpStream,
+    connection: &mut (impl AsyncRead + Unpin),
 ) -> eyre::Result<Option<Timestamped<DaemonRequest>>> {
     let raw = match tcp_receive(connection).await {
         Ok(raw) => raw,
@@ -136,3 +337,72 @@ async fn receive_message(
         .wrap_err("failed to deserialize DaemonRequest")
         .map(Some)
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn socket_addr(dir: &tempfile::TempDir) -> (PathBuf, LocalSocketAddr) {
+        let path = dir.path().join("daemon.sock");
+        (path.clone(), LocalSocketAddr::Path(path))
+    }
+
+    #[tokio::test]
+    async fn removes_socket_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, addr) = socket_addr(&dir);
+        let (listener, file) = bind_unix_socket(&addr, 0o600).unwrap();
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+
+        drop(file);
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn refuses_live_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, addr) = socket_addr(&dir);
+        let _first = bind_unix_socket(&addr, 0o600).unwrap();
+
+        let err = bind_unix_socket(&addr, 0o600).unwrap_err();
+        assert!(err.to_string().contains("address in use"), "{err:?}");
+        assert!(std::os::unix::net::UnixStream::connect(path).is_ok());
+    }
+
+    #[tokio::test]
+    async fn replaces_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, addr) = socket_addr(&dir);
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let _listener = bind_unix_socket(&addr, 0o600).unwrap();
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+    }
+
+    #[tokio::test]
+    async fn refuses_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, addr) = socket_addr(&dir);
+        std::fs::write(&path, "data").unwrap();
+
+        let err = bind_unix_socket(&addr, 0o600).unwrap_err();
+        assert!(err.to_string().contains("is not a socket"), "{err:?}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+    }
+
+    #[tokio::test]
+    async fn keeps_replaced_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, addr) = socket_addr(&dir);
+        let (_first_listener, first_file) = bind_unix_socket(&addr, 0o600).unwrap();
+        // e.g. removed by the user while the first daemon is still running
+        std::fs::remove_file(&path).unwrap();
+        let _second = bind_unix_socket(&addr, 0o600).unwrap();
+
+        drop(first_file);
+        assert!(std::os::unix::net::UnixStream::connect(path).is_ok());
+    }
+}
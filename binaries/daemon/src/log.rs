@@ -1,4 +1,8 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
 
 use dora_core::config::NodeId;
 use uuid::Uuid;
@@ -7,3 +11,49 @@ pub fn log_path(working_dir: &Path, dataflow_id: &Uuid, node_id: &NodeId) -> Pat
     let dataflow_dir = working_dir.join("out").join(dataflow_id.to_string());
     dataflow_dir.join(format!("log_{node_id}.txt"))
 }
+
+/// Resolution of the `LogIndex`.
+const LOG_INDEX_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Remembers at which byte offset of a log file the messages of each second
+/// start, so that logs can be requested from a given time on.
+///
+/// At most one entry is stored per second, which keeps the index small for
+/// nodes that log a lot.
+#[derive(Debug, Clone, Default)]
+pub struct LogIndex(Arc<Mutex<Vec<(SystemTime, u64)>>>);
+
+impl LogIndex {
+    /// Records that a log message was written at the given offset.
+    pub fn record(&self, time: SystemTime, offset: u64) {
+        let Ok(mut entries) = self.0.lock() else {
+            return;
+        };
+        let new_interval = match entries.last() {
+            Some((last, _)) => time
+                .duration_since(*last)
+                .is_ok_and(|elapsed| elapsed >= LOG_INDEX_INTERVAL),
+            None => true,
+        };
+        if new_interval {
+            entries.push((time, offset));
+        }
+    }
+
+    /// Returns the offset of the first message that was written after `since`.
+    ///
+    /// The returned offset might include up to one second of earlier messages.
+    /// Returns `None` if no message was written since then.
+    pub fn offset_since(&self, since: SystemTime) -> Option<u64> {
+        let entries = self.0.lock().ok()?;
+        let index = entries.partition_point(|(time, _)| *time <= since);
+        match index.checked_sub(1).map(|i| entries[i]) {
+            // `since` is after the start of the last interval
+            Some((start, _)) if index == entries.len() && since >= start + LOG_INDEX_INTERVAL => {
+                None
+            }
+            Some((_, offset)) => Some(offset),
+            None => Some(0),
+        }
+    }
+}
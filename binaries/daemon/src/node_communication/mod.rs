@@ -1,18 +1,19 @@
-use crate::{DaemonNodeEvent, Event};
+use crate::{stats::SharedNodeStats, DaemonNodeEvent, Event};
 use dora_core::{
-    config::{DataId, LocalCommunicationConfig, NodeId},
+    config::{DataId, InputPriority, LocalCommunicationConfig, NodeId},
     daemon_messages::{
         DaemonCommunication, DaemonReply, DaemonRequest, DataflowId, NodeDropEvent, NodeEvent,
         Timestamped,
     },
-    message::uhlc,
+    message::{uhlc, MessagePriority, SOURCE_PARAMETER},
     topics::LOCALHOST,
+    version,
 };
 use eyre::{eyre, Context};
 use futures::{future, task, Future};
 use shared_memory_server::{ShmemConf, ShmemServer};
 use std::{
-    collections::{BTreeMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
     mem,
     sync::Arc,
     task::Poll,
@@ -29,13 +30,16 @@ use tokio::{
 pub mod shmem;
 pub mod tcp;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn spawn_listener_loop(
     dataflow_id: &DataflowId,
     node_id: &NodeId,
     daemon_tx: &mpsc::Sender<Timestamped<Event>>,
     config: LocalCommunicationConfig,
     queue_sizes: BTreeMap<DataId, usize>,
+    priorities: BTreeMap<DataId, InputPriority>,
     clock: Arc<uhlc::HLC>,
+    stats: SharedNodeStats,
 ) -> eyre::Result<DaemonCommunication> {
     match config {
         LocalCommunicationConfig::Tcp => {
@@ -54,7 +58,7 @@ pub async fn spawn_listener_loop(
             let event_loop_node_id = format!("{dataflow_id}/{node_id}");
             let daemon_tx = daemon_tx.clone();
             tokio::spawn(async move {
-                tcp::listener_loop(socket, daemon_tx, queue_sizes, clock).await;
+                tcp::listener_loop(socket, daemon_tx, queue_sizes, priorities, clock, stats).await;
                 tracing::debug!("event listener loop finished for `{event_loop_node_id}`");
             });
 
@@ -87,8 +91,17 @@ pub async fn spawn_listener_loop(
                     .wrap_err("failed to create control server")?;
                 let daemon_tx = daemon_tx.clone();
                 let queue_sizes = queue_sizes.clone();
+                let priorities = priorities.clone();
                 let clock = clock.clone();
-                tokio::spawn(shmem::listener_loop(server, daemon_tx, queue_sizes, clock));
+                let stats = stats.clone();
+                tokio::spawn(shmem::listener_loop(
+                    server,
+                    daemon_tx,
+                    queue_sizes,
+                    priorities,
+                    clock,
+                    stats,
+                ));
             }
 
             {
@@ -97,9 +110,12 @@ pub async fn spawn_listener_loop(
                 let event_loop_node_id = format!("{dataflow_id}/{node_id}");
                 let daemon_tx = daemon_tx.clone();
                 let queue_sizes = queue_sizes.clone();
+                let priorities = priorities.clone();
                 let clock = clock.clone();
+                let stats = stats.clone();
                 tokio::task::spawn(async move {
-                    shmem::listener_loop(server, daemon_tx, queue_sizes, clock).await;
+                    shmem::listener_loop(server, daemon_tx, queue_sizes, priorities, clock, stats)
+                        .await;
                     tracing::debug!("event listener loop finished for `{event_loop_node_id}`");
                 });
             }
@@ -110,9 +126,12 @@ pub async fn spawn_listener_loop(
                 let drop_loop_node_id = format!("{dataflow_id}/{node_id}");
                 let daemon_tx = daemon_tx.clone();
                 let queue_sizes = queue_sizes.clone();
+                let priorities = priorities.clone();
                 let clock = clock.clone();
+                let stats = stats.clone();
                 tokio::task::spawn(async move {
-                    shmem::listener_loop(server, daemon_tx, queue_sizes, clock).await;
+                    shmem::listener_loop(server, daemon_tx, queue_sizes, priorities, clock, stats)
+                        .await;
                     tracing::debug!("drop listener loop finished for `{drop_loop_node_id}`");
                 });
             }
@@ -124,7 +143,8 @@ pub async fn spawn_listener_loop(
                 let daemon_tx = daemon_tx.clone();
                 let clock = clock.clone();
                 tokio::task::spawn(async move {
-                    shmem::listener_loop(server, daemon_tx, queue_sizes, clock).await;
+                    shmem::listener_loop(server, daemon_tx, queue_sizes, priorities, clock, stats)
+                        .await;
                     tracing::debug!(
                         "events close listener loop finished for `{drop_loop_node_id}`"
                     );
@@ -141,6 +161,40 @@ pub async fn spawn_listener_loop(
     }
 }
 
+/// Removes the shared memory regions that were created for communicating with
+/// a node.
+///
+/// The regions are normally removed when the listener loops of the node exit.
+/// This is for nodes that were killed, e.g. because the daemon is stopped, so
+/// that the regions don't outlive the daemon.
+pub fn unlink_shared_memory(communication: &DaemonCommunication) {
+    let DaemonCommunication::Shmem {
+        daemon_control_region_id,
+        daemon_drop_region_id,
+        daemon_events_region_id,
+        daemon_events_close_region_id,
+    } = communication
+    else {
+        return;
+    };
+    for region_id in [
+        daemon_control_region_id,
+        daemon_drop_region_id,
+        daemon_events_region_id,
+        daemon_events_close_region_id,
+    ] {
+        match ShmemConf::new().os_id(region_id).open() {
+            Ok(mut region) => {
+                // the region is unlinked when its owner is dropped
+                region.set_owner(true);
+            }
+            Err(err) => {
+                tracing::debug!("failed to open shared memory region `{region_id}`: {err}")
+            }
+        }
+    }
+}
+
 struct Listener {
     dataflow_id: DataflowId,
     node_id: NodeId,
@@ -149,7 +203,9 @@ struct Listener {
     subscribed_drop_events: Option<UnboundedReceiver<Timestamped<NodeDropEvent>>>,
     queue: VecDeque<Box<Option<Timestamped<NodeEvent>>>>,
     queue_sizes: BTreeMap<DataId, usize>,
+    priorities: BTreeMap<DataId, InputPriority>,
     clock: Arc<uhlc::HLC>,
+    stats: SharedNodeStats,
 }
 
 impl Listener {
@@ -157,7 +213,9 @@ impl Listener {
         mut connection: C,
         daemon_tx: mpsc::Sender<Timestamped<Event>>,
         queue_sizes: BTreeMap<DataId, usize>,
+        priorities: BTreeMap<DataId, InputPriority>,
         hlc: Arc<uhlc::HLC>,
+        stats: SharedNodeStats,
     ) {
         // receive the first message
         let message = match connection
@@ -186,15 +244,10 @@ impl Listener {
                 node_id,
                 dora_version: node_api_version,
             } => {
-                let daemon_version = env!("CARGO_PKG_VERSION");
-                let result = if node_api_version == daemon_version {
-                    Ok(())
-                } else {
-                    Err(format!(
-                        "version mismatch: node API v{node_api_version} is not compatible \
-                        with daemon v{daemon_version}"
-                    ))
-                };
+                let result = version::check_compatible(
+                    ("node API", &node_api_version),
+                    ("daemon", &version::current()),
+                );
                 let send_result = connection
                     .send_reply(DaemonReply::Result(result.clone()))
                     .await
@@ -208,8 +261,10 @@ impl Listener {
                             subscribed_events: None,
                             subscribed_drop_events: None,
                             queue_sizes,
+                            priorities,
                             queue: VecDeque::new(),
                             clock: hlc.clone(),
+                            stats,
                         };
                         match listener
                             .run_inner(connection)
@@ -286,6 +341,8 @@ impl Listener {
 
             // drop oldest input events to maintain max queue length queue
             self.drop_oldest_inputs().await?;
+            self.stats
+                .set_queue_depth(self.queue.iter().filter(|e| e.is_some()).count());
         }
         Ok(())
     }
@@ -293,19 +350,35 @@ impl Listener {
     #[tracing::instrument(skip(self), fields(%self.node_id), level = "trace")]
     async fn drop_oldest_inputs(&mut self) -> Result<(), eyre::ErrReport> {
         let mut queue_size_remaining = self.queue_sizes.clone();
+        // inputs with multiple sources have a separate queue size per source
+        let mut source_queue_size_remaining = HashMap::new();
         let mut dropped = 0;
         let mut drop_tokens = Vec::new();
 
         // iterate over queued events, newest first
         for event in self.queue.iter_mut().rev() {
             let Some(Timestamped {
-                inner: NodeEvent::Input { id, data, .. },
+                inner: NodeEvent::Input { id, data, metadata },
                 ..
             }) = event.as_mut()
             else {
                 continue;
             };
-            match queue_size_remaining.get_mut(id) {
+            let source = metadata
+                .parameters
+                .get(SOURCE_PARAMETER)
+                .and_then(|s| s.as_str());
+            let remaining = match source {
+                Some(source) => {
+                    let queue_size = self.queue_sizes.get(id).copied();
+                    source_queue_size_remaining
+                        .entry((id.clone(), source.to_owned()))
+                        .or_insert(queue_size)
+                        .as_mut()
+                }
+                None => queue_size_remaining.get_mut(id),
+            };
+            match remaining {
                 Some(0) => {
                     dropped += 1;
                     if let Some(drop_token) = data.as_ref().and_then(|d| d.drop_token()) {
@@ -324,6 +397,7 @@ impl Listener {
         self.report_drop_tokens(drop_tokens).await?;
 
         if dropped > 0 {
+            self.stats.inputs_dropped(dropped);
             tracing::debug!(
                 "dropped {dropped} inputs of node `{}` because event queue was too full",
                 self.node_id
@@ -355,6 +429,35 @@ impl Listener {
                     .await
                     .wrap_err("failed to send register reply")?;
             }
+            DaemonRequest::Heartbeat { health } => {
+                let previous = self.stats.heartbeat(health.clone());
+                if previous.unwrap_or_default().status != health.status {
+                    let details = match &health.message {
+                        Some(message) => format!(": {message}"),
+                        None => String::new(),
+                    };
+                    tracing::info!(
+                        "node `{}` reports health status {:?}{details}",
+                        self.node_id,
+                        health.status
+                    );
+                }
+                self.send_reply(DaemonReply::Empty, connection)
+                    .await
+                    .wrap_err("failed to send heartbeat reply")?;
+            }
+            DaemonRequest::ReportOperatorError(error) => {
+                self.process_daemon_event(
+                    DaemonNodeEvent::OperatorError { error },
+                    None,
+                    connection,
+                )
+                .await?;
+            }
+            DaemonRequest::Log(record) => {
+                self.process_daemon_event(DaemonNodeEvent::Log { record }, None, connection)
+                    .await?;
+            }
             DaemonRequest::OutputsDone => {
                 let (reply_sender, reply) = oneshot::channel();
                 self.process_daemon_event(
@@ -420,26 +523,33 @@ impl Listener {
                 self.report_drop_tokens(drop_tokens).await?;
 
                 // try to take the queued events first
-                let queued_events: Vec<_> = mem::take(&mut self.queue)
+                let (expired, queued_events): (Vec<_>, Vec<_>) = mem::take(&mut self.queue)
                     .into_iter()
                     .filter_map(|e| *e)
-                    .collect();
-                let reply = if queued_events.is_empty() {
-                    match self.subscribed_events.as_mut() {
-                        // wait for next event
-                        Some(events) => match events.recv().await {
-                            Some(event) => DaemonReply::NextEvents(vec![event]),
-                            None => DaemonReply::NextEvents(vec![]),
-                        },
-                        None => {
-                            DaemonReply::Result(Err("Ignoring event request because no subscribe \
-                                message was sent yet"
-                                .into()))
+                    .partition(|e| is_expired(&e.inner));
+                self.drop_expired(expired).await?;
+                let queued_events = prioritize(queued_events, |id| {
+                    self.priorities.get(id).copied().unwrap_or_default()
+                });
+                let reply = loop {
+                    if !queued_events.is_empty() {
+                        break DaemonReply::NextEvents(queued_events);
+                    }
+                    let Some(events) = self.subscribed_events.as_mut() else {
+                        break DaemonReply::Result(Err("Ignoring event request because no \
+                            subscribe message was sent yet"
+                            .into()));
+                    };
+                    // wait for next event
+                    match events.recv().await {
+                        Some(event) if is_expired(&event.inner) => {
+                            self.drop_expired(vec![event]).await?
                         }
+                        Some(event) => break DaemonReply::NextEvents(vec![event]),
+                        None => break DaemonReply::NextEvents(vec![]),
                     }
-                } else {
-                    DaemonReply::NextEvents(queued_events)
                 };
+                self.record_delivery(&reply);
 
                 self.send_reply(reply.clone(), connection)
                     .await
@@ -483,6 +593,41 @@ impl Listener {
         Ok(())
     }
 
+    async fn drop_expired(&mut self, events: Vec<Timestamped<NodeEvent>>) -> eyre::Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let dropped = events.len() as u64;
+        let drop_tokens = events
+            .into_iter()
+            .filter_map(|event| match event.inner {
+                NodeEvent::Input { data, .. } => data.and_then(|d| d.drop_token()),
+                _ => None,
+            })
+            .collect();
+        self.report_drop_tokens(drop_tokens).await?;
+        self.stats.inputs_dropped(dropped);
+        tracing::debug!(
+            "dropped {dropped} inputs of node `{}` because their deadline passed",
+            self.node_id
+        );
+        Ok(())
+    }
+
+    fn record_delivery(&self, reply: &DaemonReply) {
+        let DaemonReply::NextEvents(events) = reply else {
+            return;
+        };
+        let now = self.clock.new_timestamp().get_time().to_duration();
+        for event in events {
+            if let NodeEvent::Input { metadata, .. } = &event.inner {
+                let sent = metadata.timestamp().get_time().to_duration();
+                self.stats.add_latency(now.saturating_sub(sent));
+            }
+        }
+        self.stats.set_queue_depth(0);
+    }
+
     async fn report_drop_tokens(
         &mut self,
         drop_tokens: Vec<dora_core::daemon_messages::DropToken>,
@@ -574,3 +719,244 @@ trait Connection {
     async fn receive_message(&mut self) -> eyre::Result<Option<Timestamped<DaemonRequest>>>;
     async fn send_reply(&mut self, message: DaemonReply) -> eyre::Result<()>;
 }
+
+/// Moves queued inputs of higher priority to the front.
+///
+/// Only consecutive inputs are reordered. All other events, e.g. `Stop` or
+/// `InputClosed`, act as barriers that no input is moved across, so inputs
+/// are never delivered after an event that ends their input stream.
+fn prioritize(
+    events: Vec<Timestamped<NodeEvent>>,
+    input_priority: impl Fn(&DataId) -> InputPriority,
+) -> Vec<Timestamped<NodeEvent>> {
+    let mut prioritized = Vec::with_capacity(events.len());
+    let mut run = Vec::new();
+    for event in events {
+        if matches!(event.inner, NodeEvent::Input { .. }) {
+            run.push(event);
+        } else {
+            prioritized.extend(sort_inputs(mem::take(&mut run), &input_priority));
+            prioritized.push(event);
+        }
+    }
+    prioritized.extend(sort_inputs(run, &input_priority));
+    prioritized
+}
+
+/// Sorts inputs by the priority of their input, then by message priority.
+///
+/// Messages never overtake earlier messages of the same input. Instead, the
+/// earlier messages inherit the highest message priority queued after them.
+fn sort_inputs(
+    inputs: Vec<Timestamped<NodeEvent>>,
+    input_priority: &impl Fn(&DataId) -> InputPriority,
+) -> Vec<Timestamped<NodeEvent>> {
+    let mut highest: BTreeMap<DataId, MessagePriority> = BTreeMap::new();
+    let mut keyed: Vec<_> = inputs
+        .into_iter()
+        .rev()
+        .map(|event| {
+            let key = match &event.inner {
+                NodeEvent::Input { id, metadata, .. } => {
+                    let priority = metadata.parameters.priority;
+                    let inherited = highest.entry(id.clone()).or_insert(priority);
+                    *inherited = (*inherited).max(priority);
+                    (input_priority(id), *inherited)
+                }
+                _ => Default::default(),
+            };
+            (key, event)
+        })
+        .collect();
+    keyed.reverse();
+    // the keys of each input are non-increasing and the sort is stable, so
+    // the messages of each input stay in order
+    keyed.sort_by_key(|(key, _)| std::cmp::Reverse(*key));
+    keyed.into_iter().map(|(_, event)| event).collect()
+}
+
+fn is_expired(event: &NodeEvent) -> bool {
+    match event {
+        NodeEvent::Input { metadata, .. } => metadata.parameters.is_expired(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::message::{ArrowTypeInfo, Metadata};
+
+    /// Builds queued events from `(input, message priority)` pairs and
+    /// `NodeEvent`s, and names the inputs by their position in the queue of
+    /// their input, e.g. `a1`, `a2`.
+    struct Queue {
+        clock: uhlc::HLC,
+        events: Vec<Timestamped<NodeEvent>>,
+        names: BTreeMap<uhlc::Timestamp, String>,
+        counts: BTreeMap<String, usize>,
+    }
+
+    impl Queue {
+        fn new() -> Self {
+            Self {
+                clock: uhlc::HLC::default(),
+                events: Vec::new(),
+                names: BTreeMap::new(),
+                counts: BTreeMap::new(),
+            }
+        }
+
+        fn input(mut self, id: &str, priority: MessagePriority) -> Self {
+            let mut metadata = Metadata::new(self.clock.new_timestamp(), ArrowTypeInfo::empty());
+            metadata.parameters.priority = priority;
+            let count = self.counts.entry(id.to_owned()).or_default();
+            *count += 1;
+            let name = format!("{id}{count}");
+            self.push(
+                NodeEvent::Input {
+                    id: id.to_owned().into(),
+                    metadata,
+                    data: None,
+                },
+                name,
+            )
+        }
+
+        fn event(self, event: NodeEvent) -> Self {
+            let name = match &event {
+                NodeEvent::InputClosed { id } => format!("closed({id})"),
+                other => format!("{other:?}"),
+            };
+            self.push(event, name)
+        }
+
+        fn push(mut self, inner: NodeEvent, name: String) -> Self {
+            let timestamp = self.clock.new_timestamp();
+            self.names.insert(timestamp, name);
+            self.events.push(Timestamped { inner, timestamp });
+            self
+        }
+
+        /// Prioritizes the queue, with the input priorities given as
+        /// `(input, priority)` pairs. Other inputs have normal priority.
+        fn prioritize(self, priorities: &[(&str, InputPriority)]) -> Vec<String> {
+            let priorities: BTreeMap<DataId, InputPriority> = priorities
+                .iter()
+                .map(|(id, priority)| ((*id).to_owned().into(), *priority))
+                .collect();
+            prioritize(self.events, |id| {
+                priorities.get(id).copied().unwrap_or_default()
+            })
+            .into_iter()
+            .map(|event| self.names[&event.timestamp].clone())
+            .collect()
+        }
+    }
+
+    use InputPriority as I;
+    use MessagePriority as M;
+
+    #[test]
+    fn keeps_order_without_priorities() {
+        let order = Queue::new()
+            .input("a", M::Normal)
+            .input("b", M::Normal)
+            .input("a", M::Normal)
+            .prioritize(&[]);
+        assert_eq!(order, ["a1", "b1", "a2"]);
+    }
+
+    #[test]
+    fn higher_input_priority_first() {
+        let order = Queue::new()
+            .input("a", M::Normal)
+            .input("b", M::Normal)
+            .input("c", M::Normal)
+            .input("a", M::Normal)
+            .input("b", M::Normal)
+            .prioritize(&[("a", I::Low), ("b", I::High)]);
+        assert_eq!(order, ["b1", "b2", "c1", "a1", "a2"]);
+    }
+
+    #[test]
+    fn input_priority_before_message_priority() {
+        let order = Queue::new()
+            .input("a", M::RealTime)
+            .input("b", M::Low)
+            .prioritize(&[("a", I::Low)]);
+        assert_eq!(order, ["b1", "a1"]);
+    }
+
+    #[test]
+    fn higher_message_priority_first() {
+        let order = Queue::new()
+            .input("a", M::Low)
+            .input("b", M::Normal)
+            .input("c", M::High)
+            .prioritize(&[]);
+        assert_eq!(order, ["c1", "b1", "a1"]);
+    }
+
+    #[test]
+    fn earlier_messages_inherit_message_priority() {
+        // `a2` must not overtake `a1`, so `a1` is moved to the front too
+        let order = Queue::new()
+            .input("a", M::Normal)
+            .input("b", M::Normal)
+            .input("a", M::High)
+            .prioritize(&[]);
+        assert_eq!(order, ["a1", "a2", "b1"]);
+    }
+
+    #[test]
+    fn later_messages_dont_inherit_message_priority() {
+        let order = Queue::new()
+            .input("a", M::High)
+            .input("b", M::Normal)
+            .input("a", M::Low)
+            .prioritize(&[]);
+        // `a2` keeps its low priority instead of inheriting the high
+        // priority of `a1`
+        assert_eq!(order, ["a1", "b1", "a2"]);
+    }
+
+    #[test]
+    fn fifo_per_input() {
+        let order = Queue::new()
+            .input("a", M::Low)
+            .input("b", M::High)
+            .input("a", M::RealTime)
+            .input("b", M::Low)
+            .input("a", M::Low)
+            .input("b", M::Normal)
+            .prioritize(&[]);
+        assert_eq!(order, ["a1", "a2", "b1", "b2", "b3", "a3"]);
+    }
+
+    #[test]
+    fn no_reordering_across_barriers() {
+        let order = Queue::new()
+            .input("a", M::Normal)
+            .event(NodeEvent::InputClosed {
+                id: "c".to_owned().into(),
+            })
+            .input("b", M::Normal)
+            .input("a", M::Normal)
+            .event(NodeEvent::Stop)
+            .input("b", M::Normal)
+            .prioritize(&[("b", I::High)]);
+        assert_eq!(order, ["a1", "closed(c)", "b1", "a2", "Stop", "b2"]);
+    }
+
+    #[test]
+    fn message_priority_is_not_inherited_across_barriers() {
+        let order = Queue::new()
+            .input("a", M::Normal)
+            .input("b", M::Normal)
+            .event(NodeEvent::AllInputsClosed)
+            .input("a", M::High)
+            .prioritize(&[]);
+        assert_eq!(order, ["a1", "b1", "AllInputsClosed", "a2"]);
+    }
+}
@@ -1,9 +1,9 @@
 use std::{collections::BTreeMap, sync::Arc};
 
 use super::{Connection, Listener};
-use crate::Event;
+use crate::{stats::SharedNodeStats, Event};
 use dora_core::{
-    config::DataId,
+    config::{DataId, InputPriority},
     daemon_messages::{DaemonReply, DaemonRequest, Timestamped},
     message::uhlc::HLC,
 };
@@ -11,12 +11,14 @@ use eyre::eyre;
 use shared_memory_server::ShmemServer;
 use tokio::sync::{mpsc, oneshot};
 
-#[tracing::instrument(skip(server, daemon_tx, clock), level = "trace")]
+#[tracing::instrument(skip(server, daemon_tx, clock, stats), level = "trace")]
 pub async fn listener_loop(
     mut server: ShmemServer<Timestamped<DaemonRequest>, DaemonReply>,
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
     queue_sizes: BTreeMap<DataId, usize>,
+    priorities: BTreeMap<DataId, InputPriority>,
     clock: Arc<HLC>,
+    stats: SharedNodeStats,
 ) {
     let (tx, rx) = flume::bounded(0);
     tokio::task::spawn_blocking(move || {
@@ -40,7 +42,7 @@ pub async fn listener_loop(
         }
     });
     let connection = ShmemConnection(tx);
-    Listener::run(connection, daemon_tx, queue_sizes, clock).await
+    Listener::run(connection, daemon_tx, queue_sizes, priorities, clock, stats).await
 }
 
 enum Operation {
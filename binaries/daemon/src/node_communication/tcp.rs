@@ -2,11 +2,12 @@ use std::{collections::BTreeMap, io::ErrorKind, sync::Arc};
 
 use super::{Connection, Listener};
 use crate::{
+    stats::SharedNodeStats,
     tcp_utils::{tcp_receive, tcp_send},
     Event,
 };
 use dora_core::{
-    config::DataId,
+    config::{DataId, InputPriority},
     daemon_messages::{DaemonReply, DaemonRequest, Timestamped},
     message::uhlc::HLC,
 };
@@ -16,12 +17,14 @@ use tokio::{
     sync::mpsc,
 };
 
-#[tracing::instrument(skip(listener, daemon_tx, clock), level = "trace")]
+#[tracing::instrument(skip(listener, daemon_tx, clock, stats), level = "trace")]
 pub async fn listener_loop(
     listener: TcpListener,
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
     queue_sizes: BTreeMap<DataId, usize>,
+    priorities: BTreeMap<DataId, InputPriority>,
     clock: Arc<HLC>,
+    stats: SharedNodeStats,
 ) {
     loop {
         match listener
@@ -37,25 +40,37 @@ pub async fn listener_loop(
                     connection,
                     daemon_tx.clone(),
                     queue_sizes.clone(),
+                    priorities.clone(),
                     clock.clone(),
+                    stats.clone(),
                 ));
             }
         }
     }
 }
 
-#[tracing::instrument(skip(connection, daemon_tx, clock), level = "trace")]
+#[tracing::instrument(skip(connection, daemon_tx, clock, stats), level = "trace")]
 async fn handle_connection_loop(
     connection: TcpStream,
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
     queue_sizes: BTreeMap<DataId, usize>,
+    priorities: BTreeMap<DataId, InputPriority>,
     clock: Arc<HLC>,
+    stats: SharedNodeStats,
 ) {
     if let Err(err) = connection.set_nodelay(true) {
         tracing::warn!("failed to set nodelay for connection: {err}");
     }
 
-    Listener::run(TcpConnection(connection), daemon_tx, queue_sizes, clock).await
+    Listener::run(
+        TcpConnection(connection),
+        daemon_tx,
+        queue_sizes,
+        priorities,
+        clock,
+        stats,
+    )
+    .await
 }
 
 struct TcpConnection(TcpStream);
@@ -32,6 +32,11 @@ pub struct PendingNodes {
 
     /// Whether the local init result was already reported to the coordinator.
     reported_init_to_coordinator: bool,
+    /// Set once the subscribe requests were answered.
+    ///
+    /// Nodes that subscribe afterwards, e.g. nodes that are attached to the
+    /// running dataflow, don't need to wait for other nodes.
+    answered: bool,
 }
 
 impl PendingNodes {
@@ -44,6 +49,7 @@ impl PendingNodes {
             waiting_subscribers: HashMap::new(),
             exited_before_subscribe: Default::default(),
             reported_init_to_coordinator: false,
+            answered: false,
         }
     }
 
@@ -55,6 +61,11 @@ impl PendingNodes {
         self.external_nodes = value;
     }
 
+    /// Whether the subscribe requests were answered, i.e. the dataflow started.
+    pub fn started(&self) -> bool {
+        self.answered
+    }
+
     pub async fn handle_node_subscription(
         &mut self,
         node_id: NodeId,
@@ -63,6 +74,10 @@ impl PendingNodes {
         clock: &HLC,
         cascading_errors: &mut CascadingErrorCauses,
     ) -> eyre::Result<DataflowStatus> {
+        if self.answered {
+            let _ = reply_sender.send(DaemonReply::Result(Ok(())));
+            return Ok(DataflowStatus::Running);
+        }
         self.waiting_subscribers
             .insert(node_id.clone(), reply_sender);
         self.local_nodes.remove(&node_id);
@@ -159,6 +174,7 @@ impl PendingNodes {
         };
 
         // answer all subscribe requests
+        self.answered = true;
         let subscribe_replies = std::mem::take(&mut self.waiting_subscribers);
         for (node_id, reply_sender) in subscribe_replies.into_iter() {
             if let Some(causing_node) = node_exited_before_subscribe {
@@ -202,4 +218,6 @@ impl PendingNodes {
 pub enum DataflowStatus {
     AllNodesReady,
     Pending,
+    /// The dataflow was already started before the node subscribed.
+    Running,
 }
@@ -0,0 +1,242 @@
+//! Forwards inter-daemon events of a dataflow over a pub-sub backend instead
+//! of TCP, e.g. over zenoh.
+//!
+//! Each daemon subscribes to the topic `<dataflow>/<machine>` and publishes
+//! events for other machines to their respective topics. The messages use the
+//! same bincode format as the TCP connections, so received events are handled
+//! like events from the inter-daemon listener.
+//!
+//! Outputs are published with the transport priority that corresponds to the
+//! [`MessagePriority`] of their metadata, if the backend supports priorities.
+//! The [`Reliability`] of a message follows the QoS presets of its receivers.
+
+use crate::Event;
+#[cfg(feature = "zenoh")]
+use communication_layer_pub_sub::zenoh::ZenohCommunicationLayer;
+use communication_layer_pub_sub::{
+    fragment::FragmentingLayer, registry, BoxError, CommunicationLayer, Priority, Publisher,
+    Reliability,
+};
+use dora_core::{
+    config::RemoteCommunicationConfig,
+    daemon_messages::{DataflowId, InterDaemonEvent, Timestamped},
+    message::MessagePriority,
+};
+use eyre::{eyre, Context};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::mpsc;
+
+type LayerInit = dyn FnOnce() -> Result<Box<dyn CommunicationLayer>, BoxError> + Send;
+
+pub struct PubSubTransport {
+    /// Only `None` while dropping.
+    layer: Option<Box<dyn CommunicationLayer>>,
+    dataflow_id: DataflowId,
+    /// Describes the backend in error messages.
+    description: String,
+    /// Publishers by target machine, priority, and reliability.
+    publishers: BTreeMap<(String, Priority, Reliability), Box<dyn Publisher>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl PubSubTransport {
+    /// Sets up the backend of the given config and starts receiving the events
+    /// that other daemons send to this machine.
+    ///
+    /// Returns `None` if the config doesn't use a pub-sub backend.
+    pub async fn start(
+        config: &RemoteCommunicationConfig,
+        dataflow_id: DataflowId,
+        machine_id: &str,
+        events_tx: mpsc::Sender<Timestamped<Event>>,
+    ) -> eyre::Result<Option<Self>> {
+        let (description, init): (String, Box<LayerInit>) = match config {
+            RemoteCommunicationConfig::Tcp => return Ok(None),
+            #[cfg(feature = "zenoh")]
+            RemoteCommunicationConfig::Zenoh { config, prefix } => {
+                let config = match config {
+                    Some(config) => zenoh::config::Config::from_deserializer(config.clone())
+                        .map_err(|err| match err {
+                            Ok(_) => eyre!("invalid zenoh configuration"),
+                            Err(err) => eyre!("failed to parse zenoh configuration: {err}"),
+                        })?,
+                    None => zenoh::config::Config::default(),
+                };
+                let prefix = prefix.clone();
+                (
+                    format!("zenoh prefix `{prefix}`"),
+                    Box::new(move || {
+                        ZenohCommunicationLayer::init(config, prefix)
+                            .map(|layer| Box::new(layer) as Box<dyn CommunicationLayer>)
+                    }),
+                )
+            }
+            #[cfg(not(feature = "zenoh"))]
+            RemoteCommunicationConfig::Zenoh { .. } => {
+                eyre::bail!(
+                    "dora-daemon was built without zenoh support, rebuild it with the \
+                    `zenoh` feature enabled"
+                )
+            }
+            RemoteCommunicationConfig::Custom { backend, options } => {
+                let backend = backend.clone();
+                let options = options.clone();
+                (
+                    format!("backend `{backend}`"),
+                    Box::new(move || registry::create_backend(&backend, &options)),
+                )
+            }
+        };
+        let layer = tokio::task::spawn_blocking(move || -> Result<_, BoxError> {
+            let layer = init()?;
+            let capabilities = layer.capabilities();
+            match capabilities.max_message_size {
+                // events can be larger than the message size limit
+                Some(max) => {
+                    Ok(Box::new(FragmentingLayer::new(layer, max)?) as Box<dyn CommunicationLayer>)
+                }
+                None => Ok(layer),
+            }
+        })
+        .await
+        .wrap_err("failed to join transport init task")?
+        .map_err(|err| eyre!(err))
+        .wrap_err_with(|| format!("failed to set up {description}"))?;
+
+        Self::with_layer(layer, description, dataflow_id, machine_id, events_tx).map(Some)
+    }
+
+    fn with_layer(
+        mut layer: Box<dyn CommunicationLayer>,
+        description: String,
+        dataflow_id: DataflowId,
+        machine_id: &str,
+        events_tx: mpsc::Sender<Timestamped<Event>>,
+    ) -> eyre::Result<Self> {
+        if !layer.capabilities().inter_host {
+            tracing::warn!("{description} might not reach daemons on other machines");
+        }
+
+        let mut subscriber = layer
+            .subscribe(&topic(dataflow_id, machine_id))
+            .map_err(|err| eyre!(err))
+            .wrap_err("failed to subscribe to inter-daemon events")?;
+        let closed = Arc::new(AtomicBool::new(false));
+        let receiver_closed = closed.clone();
+        // the subscriber keeps the session alive, so the thread only notices
+        // that the transport was dropped once the next sample arrives
+        std::thread::spawn(move || loop {
+            let sample = match subscriber.recv() {
+                Ok(Some(sample)) => sample,
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::warn!("failed to receive inter-daemon event: {err}");
+                    break;
+                }
+            };
+            if receiver_closed.load(Ordering::Relaxed) {
+                break;
+            }
+            let event: Timestamped<InterDaemonEvent> = match bincode::deserialize(&sample.get()) {
+                Ok(event) => event,
+                Err(err) => {
+                    tracing::warn!("failed to deserialize inter-daemon event: {err}");
+                    continue;
+                }
+            };
+            let event = Timestamped {
+                inner: Event::Daemon(event.inner),
+                timestamp: event.timestamp,
+            };
+            if events_tx.blocking_send(event).is_err() {
+                break;
+            }
+        });
+
+        Ok(Self {
+            layer: Some(layer),
+            dataflow_id,
+            description,
+            publishers: BTreeMap::new(),
+            closed,
+        })
+    }
+
+    pub async fn send(
+        &mut self,
+        target_machines: &[String],
+        event: &Timestamped<InterDaemonEvent>,
+        priority: MessagePriority,
+        reliability: Reliability,
+    ) -> eyre::Result<()> {
+        let message =
+            Arc::new(bincode::serialize(event).wrap_err("failed to serialize InterDaemonEvent")?);
+        let priority = match priority {
+            MessagePriority::Low => Priority::Low,
+            MessagePriority::Normal => Priority::Normal,
+            MessagePriority::High => Priority::High,
+            MessagePriority::RealTime => Priority::RealTime,
+        };
+        for target_machine in target_machines {
+            let key = (target_machine.clone(), priority, reliability);
+            let publisher = match self.publishers.entry(key) {
+                std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    let layer = self.layer.as_mut().expect("layer is set until drop");
+                    let publisher = layer
+                        .publisher_with_qos(
+                            &topic(self.dataflow_id, target_machine),
+                            priority,
+                            reliability,
+                        )
+                        .map_err(|err| eyre!(err))
+                        .wrap_err_with(|| {
+                            format!("failed to create publisher for `{target_machine}`")
+                        })?;
+                    entry.insert(publisher)
+                }
+            };
+            // publishing blocks when the network is congested
+            let publisher = publisher.dyn_clone();
+            let message = message.clone();
+            tokio::task::spawn_blocking(move || publisher.publish(&message))
+                .await
+                .wrap_err("failed to join publish task")?
+                .map_err(|err| eyre!(err))
+                .wrap_err_with(|| {
+                    format!(
+                        "failed to publish event to machine `{target_machine}` ({})",
+                        self.description
+                    )
+                })?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PubSubTransport {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        // closing a zenoh session waits for pending messages, which would block
+        // the daemon's event loop
+        if let Some(layer) = self.layer.take() {
+            std::thread::spawn(move || drop(layer));
+        }
+    }
+}
+
+fn topic(dataflow_id: DataflowId, machine_id: &str) -> String {
+    // the daemon of `dora up` has an empty machine ID
+    let machine_id = if machine_id.is_empty() {
+        "_"
+    } else {
+        machine_id
+    };
+    format!("{dataflow_id}/{machine_id}")
+}
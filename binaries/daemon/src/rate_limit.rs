@@ -0,0 +1,179 @@
+//! Token buckets that enforce the `rate_limit` of inputs.
+
+use dora_core::config::RateLimit;
+use std::time::Instant;
+
+pub struct TokenBucket {
+    limit: RateLimit,
+    bytes: f64,
+    messages: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket, which holds one second worth of tokens.
+    pub fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            bytes: limit.bytes_per_second.unwrap_or(0) as f64,
+            messages: limit.messages_per_second.unwrap_or(0) as f64,
+            last_refill: now,
+        }
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Takes the tokens for a message of the given size, if enough are left.
+    ///
+    /// Returns `false` if the message exceeds the limit and should be dropped.
+    pub fn try_take(&mut self, len: usize, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        let bytes_per_second = self.limit.bytes_per_second.map(|b| b as f64);
+        let messages_per_second = self.limit.messages_per_second.map(f64::from);
+        if let Some(rate) = bytes_per_second {
+            self.bytes = (self.bytes + elapsed * rate).min(rate);
+        }
+        if let Some(rate) = messages_per_second {
+            self.messages = (self.messages + elapsed * rate).min(rate);
+        }
+
+        // messages larger than the bucket pass once the bucket is full, so
+        // that they are not dropped forever
+        let bytes_ok = bytes_per_second.map_or(true, |rate| {
+            self.bytes >= (len as f64).min(rate) && self.bytes > 0.0
+        });
+        let messages_ok = messages_per_second.map_or(true, |_| self.messages >= 1.0);
+        if bytes_ok && messages_ok {
+            if bytes_per_second.is_some() {
+                self.bytes -= len as f64;
+            }
+            if messages_per_second.is_some() {
+                self.messages -= 1.0;
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Combines the limits of two inputs that share a network transfer.
+///
+/// The transfer must not drop messages that one of the inputs would accept,
+/// so the combined limit is the more permissive one for each value.
+pub fn most_permissive(a: RateLimit, b: RateLimit) -> RateLimit {
+    RateLimit {
+        bytes_per_second: a
+            .bytes_per_second
+            .zip(b.bytes_per_second)
+            .map(|(a, b)| a.max(b)),
+        messages_per_second: a
+            .messages_per_second
+            .zip(b.messages_per_second)
+            .map(|(a, b)| a.max(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limit(bytes_per_second: Option<u64>, messages_per_second: Option<u32>) -> RateLimit {
+        RateLimit {
+            bytes_per_second,
+            messages_per_second,
+        }
+    }
+
+    #[test]
+    fn burst_up_to_capacity() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit(None, Some(5)), start);
+        for _ in 0..5 {
+            assert!(bucket.try_take(100, start));
+        }
+        assert!(!bucket.try_take(100, start));
+    }
+
+    #[test]
+    fn refills_with_elapsed_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit(Some(1000), None), start);
+        assert!(bucket.try_take(400, start));
+        assert!(bucket.try_take(400, start));
+        assert!(!bucket.try_take(400, start));
+
+        // 200 bytes are refilled after 200ms
+        let later = start + Duration::from_millis(200);
+        assert!(bucket.try_take(400, later));
+        assert!(!bucket.try_take(1, later));
+
+        // the bucket never holds more than one second worth of tokens
+        let much_later = later + Duration::from_secs(10);
+        assert!(bucket.try_take(1000, much_later));
+        assert!(!bucket.try_take(1, much_later));
+    }
+
+    #[test]
+    fn message_limit_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit(None, Some(10)), start);
+        for _ in 0..10 {
+            assert!(bucket.try_take(0, start));
+        }
+        assert!(!bucket.try_take(0, start + Duration::from_millis(50)));
+        assert!(bucket.try_take(0, start + Duration::from_millis(100)));
+        assert!(!bucket.try_take(0, start + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn both_limits_must_allow_message() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit(Some(1000), Some(2)), start);
+        assert!(bucket.try_take(100, start));
+        assert!(bucket.try_take(100, start));
+        assert!(!bucket.try_take(100, start));
+
+        let mut bucket = TokenBucket::new(limit(Some(100), Some(10)), start);
+        assert!(bucket.try_take(100, start));
+        assert!(!bucket.try_take(1, start));
+    }
+
+    #[test]
+    fn oversized_message_passes_when_full() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit(Some(1000), None), start);
+        assert!(bucket.try_take(5000, start));
+
+        // the oversized message is paid off before the next one passes
+        assert!(!bucket.try_take(1, start + Duration::from_secs(1)));
+        assert!(!bucket.try_take(1, start + Duration::from_secs(4)));
+        assert!(bucket.try_take(1, start + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn oversized_message_waits_for_full_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit(Some(1000), None), start);
+        assert!(bucket.try_take(500, start));
+        assert!(!bucket.try_take(5000, start));
+        assert!(bucket.try_take(5000, start + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn merge_limits() {
+        let merged = most_permissive(limit(Some(100), Some(10)), limit(Some(200), Some(5)));
+        assert_eq!(merged, limit(Some(200), Some(10)));
+
+        // an input without a limit accepts all messages
+        let merged = most_permissive(limit(Some(100), None), limit(None, Some(5)));
+        assert_eq!(merged, limit(None, None));
+        let merged = most_permissive(limit(None, None), limit(Some(100), Some(10)));
+        assert_eq!(merged, limit(None, None));
+    }
+}
@@ -0,0 +1,74 @@
+use aligned_vec::{AVec, ConstAlign};
+use dora_core::{
+    config::{DataId, NodeId},
+    descriptor::RecordConfig,
+    message::Metadata,
+    record::{new_record_dir, RecordWriter, RecordedMessage, DEFAULT_SEGMENT_SIZE},
+};
+use eyre::Context;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Writes the recorded outputs of a dataflow to disk.
+///
+/// The actual file I/O happens on a separate thread so that recording does not
+/// block the daemon event loop. Dropping the recorder closes the channel to the
+/// thread, which then writes and flushes the pending messages in the
+/// background.
+pub struct DataflowRecorder {
+    config: RecordConfig,
+    dir: PathBuf,
+    sender: flume::Sender<RecordedMessage>,
+}
+
+impl DataflowRecorder {
+    pub fn new(config: RecordConfig, working_dir: &Path, dataflow_id: Uuid) -> eyre::Result<Self> {
+        let dir = new_record_dir(working_dir, &dataflow_id);
+        let mut writer =
+            RecordWriter::create(&dir, config.segment_size.unwrap_or(DEFAULT_SEGMENT_SIZE))
+                .wrap_err("failed to create dataflow recording")?;
+        tracing::info!("recording dataflow `{dataflow_id}` to `{}`", dir.display());
+
+        let (sender, receiver) = flume::unbounded::<RecordedMessage>();
+        std::thread::spawn(move || {
+            for message in receiver {
+                if let Err(err) = writer.write(&message) {
+                    tracing::warn!("failed to record message of dataflow `{dataflow_id}`: {err:?}");
+                }
+            }
+            if let Err(err) = writer.flush() {
+                tracing::warn!("failed to flush recording of dataflow `{dataflow_id}`: {err:?}");
+            }
+        });
+
+        Ok(Self {
+            config,
+            dir,
+            sender,
+        })
+    }
+
+    /// Directory that the recording is written to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn record(
+        &self,
+        node_id: &NodeId,
+        output_id: &DataId,
+        metadata: &Metadata,
+        data: Option<&AVec<u8, ConstAlign<128>>>,
+    ) {
+        if !self.config.records(node_id, output_id) {
+            return;
+        }
+        let message = RecordedMessage {
+            node_id: node_id.clone(),
+            output_id: output_id.clone(),
+            metadata: metadata.clone(),
+            data: data.cloned(),
+        };
+        let _ = self.sender.send(message);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{DoraEvent, Event};
+use dora_core::{
+    config::NodeId,
+    daemon_messages::{DataflowId, Timestamped},
+    message::{uhlc::HLC, Metadata},
+    record::{RecordReader, RecordedMessage},
+};
+use futures::FutureExt;
+use std::{collections::BTreeSet, path::PathBuf, sync::Arc, time::Instant};
+use tokio::sync::mpsc;
+
+/// Replays the recorded outputs of some local nodes instead of running them.
+pub struct ReplaySource {
+    pub path: PathBuf,
+    pub nodes: BTreeSet<NodeId>,
+    pub speed: f64,
+}
+
+impl ReplaySource {
+    /// Starts the replay, which stops when the returned handle is dropped.
+    pub fn start(
+        self,
+        dataflow_id: DataflowId,
+        events_tx: mpsc::Sender<Timestamped<Event>>,
+        clock: Arc<HLC>,
+    ) -> futures::future::RemoteHandle<()> {
+        let Self { path, nodes, speed } = self;
+
+        // read the recording on a separate thread to avoid blocking the runtime
+        let (messages_tx, messages_rx) = flume::bounded(16);
+        let reader_nodes = nodes.clone();
+        std::thread::spawn(move || {
+            let reader = match RecordReader::open(&path) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    let _ = messages_tx.send(Err(err));
+                    return;
+                }
+            };
+            for message in reader {
+                if let Ok(m) = &message {
+                    if !reader_nodes.contains(&m.node_id) {
+                        continue;
+                    }
+                }
+                if messages_tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let task = async move {
+            let start = Instant::now();
+            let mut first_timestamp = None;
+            while let Ok(message) = messages_rx.recv_async().await {
+                let RecordedMessage {
+                    node_id,
+                    output_id,
+                    metadata,
+                    data,
+                } = match message {
+                    Ok(m) => m,
+                    Err(err) => {
+                        tracing::warn!("failed to replay recording: {err:?}");
+                        break;
+                    }
+                };
+
+                // keep the relative timing of the original messages
+                let recorded_time = metadata.timestamp().get_time().to_duration();
+                let offset = recorded_time
+                    .saturating_sub(*first_timestamp.get_or_insert(recorded_time))
+                    .div_f64(speed);
+                tokio::time::sleep_until((start + offset).into()).await;
+
+                let metadata = Metadata::from_parameters(
+                    clock.new_timestamp(),
+                    metadata.type_info,
+                    metadata.parameters,
+                );
+                let event = Timestamped {
+                    inner: DoraEvent::ReplayOutput {
+                        dataflow_id,
+                        node_id,
+                        output_id,
+                        metadata,
+                        data,
+                    }
+                    .into(),
+                    timestamp: clock.new_timestamp(),
+                };
+                if events_tx.send(event).await.is_err() {
+                    return;
+                }
+            }
+
+            let event = Timestamped {
+                inner: DoraEvent::ReplayFinished { dataflow_id, nodes }.into(),
+                timestamp: clock.new_timestamp(),
+            };
+            let _ = events_tx.send(event).await;
+        };
+        let (task, handle) = task.remote_handle();
+        tokio::spawn(task);
+        handle
+    }
+}
+
+pub fn replay_speed(speed: Option<f64>) -> eyre::Result<f64> {
+    match speed {
+        None => Ok(1.0),
+        Some(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        Some(speed) => eyre::bail!("invalid replay speed `{speed}`, must be positive"),
+    }
+}
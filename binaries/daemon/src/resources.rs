@@ -0,0 +1,101 @@
+use dora_core::descriptor::ResourceLimits;
+use std::path::Path;
+use sysinfo::{Disks, Pid, System};
+
+/// Periodically samples the free disk space and the memory usage of the daemon.
+pub struct ResourceMonitor {
+    system: System,
+    disks: Disks,
+    pid: Option<Pid>,
+}
+
+impl ResourceMonitor {
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+            disks: Disks::new(),
+            pid: sysinfo::get_current_pid().ok(),
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        self.disks.refresh_list();
+        if let Some(pid) = self.pid {
+            self.system.refresh_process(pid);
+        }
+    }
+
+    /// Returns a description of the first exceeded limit, if any.
+    pub fn check(&self, limits: &ResourceLimits, working_dir: &Path) -> Option<String> {
+        if let Some(min_free_disk) = limits.min_free_disk {
+            if let Some(available) = self.free_disk_space(working_dir) {
+                if available < min_free_disk {
+                    return Some(format!(
+                        "free disk space of `{}` is {available} bytes (minimum: {min_free_disk})",
+                        working_dir.display()
+                    ));
+                }
+            }
+        }
+        if let Some(max_daemon_memory) = limits.max_daemon_memory {
+            let memory = self
+                .pid
+                .and_then(|pid| self.system.process(pid))
+                .map(|p| p.memory());
+            if let Some(memory) = memory {
+                if memory > max_daemon_memory {
+                    return Some(format!(
+                        "daemon uses {memory} bytes of memory (maximum: {max_daemon_memory})"
+                    ));
+                }
+            }
+        }
+        None
+    }
+
+    fn free_disk_space(&self, path: &Path) -> Option<u64> {
+        let path = path.canonicalize().ok()?;
+        // use the disk with the most specific mount point
+        self.disks
+            .list()
+            .iter()
+            .filter(|d| path.starts_with(d.mount_point()))
+            .max_by_key(|d| d.mount_point().as_os_str().len())
+            .map(|d| d.available_space())
+    }
+}
+
+/// Samples the CPU and memory usage of node processes.
+pub struct ProcessMonitor {
+    system: System,
+}
+
+pub struct ProcessUsage {
+    /// CPU usage since the previous sample of the process, in percent of one core.
+    pub cpu: f32,
+    /// Resident memory in bytes.
+    pub memory: u64,
+}
+
+impl ProcessMonitor {
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+        }
+    }
+
+    /// Returns `None` if the process does not exist (anymore).
+    ///
+    /// The CPU usage is computed from the difference to the previous sample, so
+    /// it is zero on the first call for a process.
+    pub fn sample(&mut self, pid: u32) -> Option<ProcessUsage> {
+        let pid = Pid::from(pid as usize);
+        if !self.system.refresh_process(pid) {
+            return None;
+        }
+        self.system.process(pid).map(|process| ProcessUsage {
+            cpu: process.cpu_usage(),
+            memory: process.memory(),
+        })
+    }
+}
@@ -1,16 +1,17 @@
 use crate::{
-    log, node_communication::spawn_listener_loop, node_inputs, DoraEvent, Event, NodeExitStatus,
-    OutputId, RunningNode,
+    log, node_communication::spawn_listener_loop, node_inputs, stats::SharedNodeStats, DoraEvent,
+    Event, NodeExitStatus, OutputId, RunningNode,
 };
 use aligned_vec::{AVec, ConstAlign};
 use crossbeam::queue::ArrayQueue;
 use dora_arrow_convert::IntoArrow;
 use dora_core::{
-    config::DataId,
+    config::{DataId, NodeId, OperatorId},
     daemon_messages::{DataMessage, DataflowId, NodeConfig, RuntimeConfig, Timestamped},
     descriptor::{
-        resolve_path, source_is_url, Descriptor, OperatorDefinition, OperatorSource, PythonSource,
-        ResolvedNode, DYNAMIC_SOURCE, SHELL_SOURCE,
+        resolve_path, source_is_url, ContainerConfig, ContainerEngine, Descriptor,
+        OperatorDefinition, OperatorSource, PythonSource, ResolvedNode, DYNAMIC_SOURCE,
+        SHELL_SOURCE,
     },
     get_python_path,
     message::uhlc::HLC,
@@ -23,10 +24,13 @@ use dora_node_api::{
 };
 use eyre::{ContextCompat, WrapErr};
 use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
     env::consts::EXE_EXTENSION,
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
     process::Stdio,
     sync::Arc,
+    time::{Duration, SystemTime},
 };
 use tokio::{
     fs::File,
@@ -35,7 +39,299 @@ use tokio::{
 };
 use tracing::error;
 
+/// Returns the Python interpreter for the runtime of the given operator.
+///
+/// Environments for `requirements` files are created below `.dora/venvs` in
+/// the working directory, keyed by a hash of the file content, so that they
+/// are only set up again when the requirements change.
+/// Packages are installed from `.dora/wheels` without network access if that
+/// directory exists.
+async fn python_interpreter(
+    python_source: &PythonSource,
+    working_dir: &Path,
+) -> eyre::Result<PathBuf> {
+    if let Some(venv) = &python_source.venv {
+        let python = venv_python(&working_dir.join(venv));
+        if !python.exists() {
+            eyre::bail!("no Python interpreter at `{}`", python.display());
+        }
+        return Ok(python);
+    }
+    let Some(requirements) = &python_source.requirements else {
+        return get_python_path().context("Could not find python path when spawning runtime node");
+    };
+
+    let requirements = working_dir.join(requirements);
+    let content = tokio::fs::read(&requirements)
+        .await
+        .wrap_err_with(|| format!("failed to read `{}`", requirements.display()))?;
+    let hash = {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        hasher.finish()
+    };
+    let venv = working_dir
+        .join(".dora")
+        .join("venvs")
+        .join(format!("{hash:016x}"));
+    let python = venv_python(&venv);
+    if python.exists() {
+        return Ok(python);
+    }
+
+    tracing::info!(
+        "creating Python environment for `{}` at `{}`",
+        requirements.display(),
+        venv.display()
+    );
+    let base_python = get_python_path().context("Could not find python path")?;
+    // the system packages stay visible so that the `dora` module of the base
+    // environment can be imported
+    run_setup_command(
+        tokio::process::Command::new(base_python)
+            .args(["-m", "venv", "--system-site-packages"])
+            .arg(&venv),
+    )
+    .await
+    .wrap_err("failed to create virtual environment")?;
+    let mut install = tokio::process::Command::new(&python);
+    install.args(["-m", "pip", "install"]);
+    // bundles created by `dora bundle --wheels` contain all required packages
+    let wheels = working_dir.join(".dora").join("wheels");
+    if wheels.is_dir() {
+        install.arg("--no-index").arg("--find-links").arg(&wheels);
+    }
+    let install = run_setup_command(install.arg("-r").arg(&requirements)).await;
+    if let Err(err) = install {
+        // don't reuse a partially set up environment on the next start
+        let _ = tokio::fs::remove_dir_all(&venv).await;
+        return Err(err.wrap_err("failed to install requirements"));
+    }
+    Ok(python)
+}
+
+/// Returns the command that starts the Python interpreter of the given
+/// operator, e.g. `["conda", "run", "-n", "env", "python"]`.
+async fn python_command(source: &PythonSource, working_dir: &Path) -> eyre::Result<Vec<String>> {
+    if let Some(conda_env) = &source.conda_env {
+        let conda = which::which("conda").context(
+            "failed to find `conda`, yet a `conda_env` was defined. Make sure that `conda` is available.",
+        )?;
+        return Ok(vec![
+            path_to_string(conda)?,
+            "run".to_owned(),
+            "-n".to_owned(),
+            conda_env.clone(),
+            "python".to_owned(),
+        ]);
+    }
+    let python = python_interpreter(source, working_dir).await?;
+    Ok(vec![path_to_string(python)?])
+}
+
+fn path_to_string(path: PathBuf) -> eyre::Result<String> {
+    path.into_os_string()
+        .into_string()
+        .map_err(|path| eyre::eyre!("path `{}` is not valid UTF-8", Path::new(&path).display()))
+}
+
+/// Returns the Python commands for the given operators of a runtime node.
+///
+/// The runtime process uses the environment of the operator that runs inside
+/// of it, or the environment of the first operator if all operators are
+/// isolated. Isolated operators with a different environment get their own
+/// interpreter, which is returned by operator ID.
+async fn python_commands(
+    operators: &[&OperatorDefinition],
+    working_dir: &Path,
+) -> eyre::Result<(Vec<String>, BTreeMap<OperatorId, Vec<String>>)> {
+    let python_source = |operator: &OperatorDefinition| match &operator.config.source {
+        OperatorSource::Python(source) => Ok(source.clone()),
+        _ => Err(eyre::eyre!(
+            "operator `{}` is not a Python operator",
+            operator.id
+        )),
+    };
+    let main = operators
+        .iter()
+        .find(|op| !op.config.isolate)
+        .or(operators.first())
+        .context("Runtime had no operators definition.")?;
+    let main_source = python_source(main)?;
+    let main_command = python_command(&main_source, working_dir).await?;
+
+    let mut isolated = BTreeMap::new();
+    for operator in operators.iter().filter(|op| op.config.isolate) {
+        let source = python_source(operator)?;
+        let same_env = source.conda_env == main_source.conda_env
+            && source.venv == main_source.venv
+            && source.requirements == main_source.requirements;
+        if !same_env {
+            let command = python_command(&source, working_dir)
+                .await
+                .wrap_err_with(|| {
+                    format!(
+                        "failed to prepare Python environment of operator `{}`",
+                        operator.id
+                    )
+                })?;
+            isolated.insert(operator.id.clone(), command);
+        }
+    }
+    Ok((main_command, isolated))
+}
+
+fn venv_python(venv: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv.join("Scripts").join("python.exe")
+    } else {
+        venv.join("bin").join("python")
+    }
+}
+
+async fn run_setup_command(command: &mut tokio::process::Command) -> eyre::Result<()> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .wrap_err_with(|| format!("failed to run {command:?}"))?;
+    if !output.status.success() {
+        eyre::bail!(
+            "{command:?} failed with {}:\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// The named container that a node runs in.
+///
+/// Signals to the `run` command of the container engine don't reliably reach
+/// the container, so the container is stopped and removed through the engine
+/// by its name instead.
+#[derive(Debug, Clone)]
+pub struct NodeContainer {
+    engine: ContainerEngine,
+    name: String,
+}
+
+impl NodeContainer {
+    fn new(engine: ContainerEngine, dataflow_id: &DataflowId, node_id: &NodeId) -> Self {
+        // container names may only contain `[a-zA-Z0-9_.-]`
+        let name = format!("dora-{dataflow_id}-{node_id}")
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Self { engine, name }
+    }
+
+    fn engine_command(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(self.engine.command());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        command
+    }
+
+    /// Removes a leftover container of the same name, e.g. of a previous run
+    /// of the node whose removal didn't finish yet.
+    async fn remove_stale(&self) {
+        let _ = self
+            .engine_command()
+            .args(["rm", "-f", &self.name])
+            .status()
+            .await;
+    }
+
+    /// Asks the container to stop and kills it if it doesn't stop within the
+    /// given timeout.
+    ///
+    /// Runs in the background, the daemon doesn't wait for the container to
+    /// stop.
+    pub fn stop(&self, timeout: Duration) {
+        let timeout = timeout.as_secs().max(1).to_string();
+        self.spawn_engine_command(&["stop", "--time", &timeout]);
+    }
+
+    /// Kills and removes the container immediately.
+    ///
+    /// Runs in the background, so the container is removed even if the daemon
+    /// exits right after.
+    pub fn remove(&self) {
+        self.spawn_engine_command(&["rm", "-f"]);
+    }
+
+    fn spawn_engine_command(&self, args: &[&str]) {
+        let result = self.engine_command().args(args).arg(&self.name).spawn();
+        if let Err(err) = result {
+            tracing::warn!(
+                "failed to run `{} {}` for container `{}`: {err}",
+                self.engine.command(),
+                args.join(" "),
+                self.name
+            );
+        }
+    }
+}
+
+/// Wraps the given command to run it inside the given container.
+///
+/// The container shares the network and IPC namespace of the host so that the
+/// node can reach the daemon through TCP or shared memory. The working
+/// directory is mounted at the same path as on the host.
+fn container_command(
+    config: &ContainerConfig,
+    container: &NodeContainer,
+    working_dir: &Path,
+    command: &tokio::process::Command,
+) -> tokio::process::Command {
+    let inner = command.as_std();
+    let mut cmd = tokio::process::Command::new(config.engine.command());
+    cmd.args([
+        "run",
+        "--rm",
+        "--init",
+        "--name",
+        &container.name,
+        "--network",
+        "host",
+        "--ipc",
+        "host",
+    ]);
+    cmd.arg("-v")
+        .arg(format!("{0}:{0}", working_dir.display()))
+        .arg("-w")
+        .arg(working_dir);
+    for (key, value) in inner.get_envs() {
+        if let Some(value) = value {
+            // pass the variable through from the environment of the engine process
+            cmd.arg("-e").arg(key);
+            cmd.env(key, value);
+        }
+    }
+    cmd.args(&config.args);
+    cmd.arg(&config.image);
+    cmd.arg(inner.get_program());
+    cmd.args(inner.get_args());
+    cmd.current_dir(working_dir);
+    tracing::info!(
+        "running in container `{}`: {:?}",
+        config.image,
+        inner.get_program()
+    );
+    cmd
+}
+
 /// clock is required for generating timestamps when dropping messages early because queue is full
+#[allow(clippy::too_many_arguments)]
 pub async fn spawn_node(
     dataflow_id: DataflowId,
     working_dir: &Path,
@@ -44,13 +340,20 @@ pub async fn spawn_node(
     dataflow_descriptor: Descriptor,
     clock: Arc<HLC>,
     node_stderr_most_recent: Arc<ArrayQueue<String>>,
+    stats: SharedNodeStats,
+    log_index: log::LogIndex,
 ) -> eyre::Result<RunningNode> {
     let node_id = node.id.clone();
     tracing::debug!("Spawning node `{dataflow_id}/{node_id}`");
 
-    let queue_sizes = node_inputs(&node)
+    let inputs = node_inputs(&node);
+    let queue_sizes = inputs
+        .iter()
+        .map(|(k, v)| (k.clone(), v.effective_queue_size().unwrap_or(10)))
+        .collect();
+    let priorities = inputs
         .into_iter()
-        .map(|(k, v)| (k, v.queue_size.unwrap_or(10)))
+        .filter_map(|(k, v)| Some((k, v.priority?)))
         .collect();
     let daemon_communication = spawn_listener_loop(
         &dataflow_id,
@@ -58,7 +361,9 @@ pub async fn spawn_node(
         &daemon_tx,
         dataflow_descriptor.communication.local,
         queue_sizes,
+        priorities,
         clock.clone(),
+        stats,
     )
     .await?;
     let send_stdout_to = node
@@ -74,6 +379,7 @@ pub async fn spawn_node(
         dynamic: node.kind.dynamic(),
     };
 
+    let mut container = None;
     let mut child = match node.kind {
         dora_core::descriptor::CoreNodeKind::Custom(n) => {
             let mut command = match n.source.as_str() {
@@ -81,6 +387,8 @@ pub async fn spawn_node(
                     return Ok(RunningNode {
                         pid: None,
                         node_config,
+                        log_sender: None,
+                        container: None,
                     });
                 }
                 SHELL_SOURCE => {
@@ -94,6 +402,21 @@ pub async fn spawn_node(
                         cmd
                     }
                 }
+                source if node.container.is_some() => {
+                    // the working directory is mounted into the container, so
+                    // relative sources are resolved inside the container
+                    let mut cmd = if source.ends_with(".py") {
+                        let mut cmd = tokio::process::Command::new("python");
+                        cmd.arg(source);
+                        cmd
+                    } else {
+                        tokio::process::Command::new(source)
+                    };
+                    if let Some(args) = &n.args {
+                        cmd.args(args.split_ascii_whitespace());
+                    }
+                    cmd
+                }
                 source => {
                     let resolved_path = if source_is_url(source) {
                         // try to download the shared library
@@ -153,10 +476,18 @@ pub async fn spawn_node(
                     command.env(key, value.to_string());
                 }
             }
+            if let Some(config) = &node.container {
+                let handle = NodeContainer::new(config.engine, &dataflow_id, &node_id);
+                handle.remove_stale().await;
+                command = container_command(config, &handle, working_dir, &command);
+                container = Some(handle);
+            }
             command
                 .stdin(Stdio::null())
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
+                // don't leave orphaned nodes behind if the daemon exits
+                .kill_on_drop(true)
                 .spawn()
                 .wrap_err_with(move || {
                     format!(
@@ -178,50 +509,37 @@ pub async fn spawn_node(
                 .iter()
                 .any(|x| !matches!(x.config.source, OperatorSource::Python { .. }));
 
+            let mut isolated_python = BTreeMap::new();
             let mut command = if !python_operators.is_empty() && !other_operators {
                 // Use python to spawn runtime if there is a python operator
 
                 // TODO: Handle multi-operator runtime once sub-interpreter is supported
-                if python_operators.len() > 2 {
+                let shared_interpreter = python_operators
+                    .iter()
+                    .filter(|op| !op.config.isolate)
+                    .count();
+                if shared_interpreter > 1 {
                     eyre::bail!(
-                        "Runtime currently only support one Python Operator.
-                     This is because pyo4 sub-interpreter is not yet available.
-                     See: https://github.com/PyO4/pyo3/issues/576"
+                        "Runtime currently only support one non-isolated Python Operator.
+                     This is because pyo3 sub-interpreter is not yet available.
+                     See: https://github.com/PyO3/pyo3/issues/576
+                     Set `isolate: true` to run Python operators in separate processes."
                     );
                 }
 
-                let python_operator = python_operators
-                    .first()
-                    .context("Runtime had no operators definition.")?;
-
-                if let OperatorSource::Python(PythonSource {
-                    source: _,
-                    conda_env: Some(conda_env),
-                }) = &python_operator.config.source
-                {
-                    let conda = which::which("conda").context(
-                        "failed to find `conda`, yet a `conda_env` was defined. Make sure that `conda` is available.",
-                    )?;
-                    let mut command = tokio::process::Command::new(conda);
-                    command.args([
-                        "run",
-                        "-n",
-                        &conda_env,
-                        "python",
-                        "-c",
-                        format!("import dora; dora.start_runtime() # {}", node.id).as_str(),
-                    ]);
-                    command
-                } else {
-                    let python = get_python_path()
-                        .context("Could not find python path when spawning runtime node")?;
-                    let mut command = tokio::process::Command::new(python);
-                    command.args([
-                        "-c",
-                        format!("import dora; dora.start_runtime() # {}", node.id).as_str(),
-                    ]);
-                    command
-                }
+                let (python, commands) = python_commands(&python_operators, working_dir)
+                    .await
+                    .wrap_err_with(|| {
+                        format!("failed to prepare Python environment of node `{}`", node.id)
+                    })?;
+                isolated_python = commands;
+                let (program, args) = python.split_first().context("empty Python command")?;
+                let mut command = tokio::process::Command::new(program);
+                command.args(args).args([
+                    "-c",
+                    format!("import dora; dora.start_runtime() # {}", node.id).as_str(),
+                ]);
+                command
             } else if python_operators.is_empty() && other_operators {
                 let mut cmd = tokio::process::Command::new(
                     std::env::current_exe().wrap_err("failed to get current executable path")?,
@@ -236,6 +554,9 @@ pub async fn spawn_node(
             let runtime_config = RuntimeConfig {
                 node: node_config.clone(),
                 operators: n.operators,
+                workers: node.operator_workers,
+                profile: node.profile,
+                isolated_python,
             };
             command.env(
                 "DORA_RUNTIME_CONFIG",
@@ -254,6 +575,8 @@ pub async fn spawn_node(
                 .stdin(Stdio::null())
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
+                // don't leave orphaned nodes behind if the daemon exits
+                .kill_on_drop(true)
                 .spawn()
                 .wrap_err(format!(
                     "failed to run runtime {}/{}",
@@ -278,6 +601,8 @@ pub async fn spawn_node(
     let running_node = RunningNode {
         pid: Some(pid),
         node_config,
+        log_sender: Some(tx.downgrade()),
+        container,
     };
     let stdout_tx = tx.clone();
 
@@ -406,6 +731,7 @@ pub async fn spawn_node(
     let node_id = node.id.clone();
     // Log to file stream.
     tokio::spawn(async move {
+        let mut log_len = 0;
         while let Some(message) = rx.recv().await {
             // If log is an output, we're sending the logs to the dataflow
             if let Some(stdout_output_name) = &send_stdout_to {
@@ -437,10 +763,11 @@ pub async fn spawn_node(
                 let _ = daemon_tx_log.send(event).await;
             }
 
-            let _ = file
-                .write_all(message.as_bytes())
-                .await
-                .map_err(|err| error!("Could not log {message} to file due to {err}"));
+            log_index.record(SystemTime::now(), log_len);
+            match file.write_all(message.as_bytes()).await {
+                Ok(()) => log_len += message.len() as u64,
+                Err(err) => error!("Could not log {message} to file due to {err}"),
+            }
             let formatted = message.lines().fold(String::default(), |mut output, line| {
                 output.push_str("      ");
                 output.push_str(line);
@@ -460,3 +787,51 @@ pub async fn spawn_node(
     });
     Ok(running_node)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn container_name() {
+        let dataflow_id = DataflowId::nil();
+        let container = NodeContainer::new(
+            ContainerEngine::Docker,
+            &dataflow_id,
+            &"cam/left 1".to_owned().into(),
+        );
+        assert_eq!(container.name, format!("dora-{dataflow_id}-cam_left_1"));
+    }
+
+    fn python_operator(id: &str, venv: &str, isolate: bool) -> OperatorDefinition {
+        serde_yaml::from_str(&format!(
+            "id: {id}\npython:\n  source: {id}.py\n  venv: {venv}\nisolate: {isolate}"
+        ))
+        .unwrap()
+    }
+
+    fn create_venv(working_dir: &Path, venv: &str) -> String {
+        let python = venv_python(&working_dir.join(venv));
+        std::fs::create_dir_all(python.parent().unwrap()).unwrap();
+        std::fs::write(&python, "").unwrap();
+        python.to_str().unwrap().to_owned()
+    }
+
+    #[tokio::test]
+    async fn isolated_operators_use_own_venv() {
+        let dir = tempfile::tempdir().unwrap();
+        let python_a = create_venv(dir.path(), "venv-a");
+        let python_b = create_venv(dir.path(), "venv-b");
+        let a = python_operator("a", "venv-a", false);
+        let b = python_operator("b", "venv-b", true);
+        let c = python_operator("c", "venv-a", true);
+
+        let (runtime, isolated) = python_commands(&[&b, &a, &c], dir.path()).await.unwrap();
+        // the runtime uses the venv of the operator that runs inside of it
+        assert_eq!(runtime, [python_a]);
+        assert_eq!(
+            isolated,
+            BTreeMap::from([(OperatorId::from("b".to_owned()), vec![python_b])])
+        );
+    }
+}
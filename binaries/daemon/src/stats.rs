@@ -0,0 +1,187 @@
+use dora_core::{
+    config::DataId,
+    daemon_messages::{NodeHealth, NodeStats},
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Statistics of a node that are shared between the daemon event loop and the
+/// node's listener task.
+#[derive(Debug, Clone, Default)]
+pub struct SharedNodeStats(Arc<Mutex<NodeStatsCounter>>);
+
+#[derive(Debug, Default)]
+struct NodeStatsCounter {
+    stats: NodeStats,
+    /// Latencies of the inputs that were delivered since the last snapshot.
+    latency_sum: Duration,
+    latency_samples: u64,
+    last_heartbeat: Option<Instant>,
+    /// Since when the oldest queued event waits to be received by the node.
+    pending_since: Option<Instant>,
+    /// Exports the statistics over OTLP too, if configured for the dataflow.
+    #[cfg(feature = "metrics")]
+    metrics: Option<dora_tracing::metrics::NodeMetrics>,
+}
+
+impl SharedNodeStats {
+    fn update(&self, f: impl FnOnce(&mut NodeStatsCounter)) {
+        match self.0.lock() {
+            Ok(mut counter) => f(&mut counter),
+            Err(_) => tracing::warn!("node stats mutex is poisoned"),
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(metrics: Option<dora_tracing::metrics::NodeMetrics>) -> Self {
+        Self(Arc::new(Mutex::new(NodeStatsCounter {
+            metrics,
+            ..Default::default()
+        })))
+    }
+
+    pub fn output_sent(&self, output_id: &DataId, bytes: usize) {
+        self.update(|c| {
+            c.stats
+                .outputs
+                .entry(output_id.clone())
+                .or_default()
+                .add(bytes);
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &c.metrics {
+                metrics.output_sent(output_id, bytes);
+            }
+        });
+    }
+
+    pub fn input_delivered(&self, input_id: &DataId, bytes: usize) {
+        self.update(|c| {
+            c.stats
+                .inputs
+                .entry(input_id.clone())
+                .or_default()
+                .add(bytes);
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &c.metrics {
+                metrics.input_delivered(input_id, bytes);
+            }
+        });
+    }
+
+    pub fn inputs_dropped(&self, count: u64) {
+        self.update(|c| {
+            c.stats.dropped_inputs += count;
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &c.metrics {
+                metrics.inputs_dropped(count);
+            }
+        });
+    }
+
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.update(|c| {
+            c.stats.queue_depth = depth;
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &c.metrics {
+                metrics.set_queue_depth(depth);
+            }
+            if depth == 0 {
+                c.pending_since = None;
+            } else {
+                c.pending_since.get_or_insert_with(Instant::now);
+            }
+        });
+    }
+
+    /// Records a heartbeat of the node and returns the previously reported health.
+    pub fn heartbeat(&self, health: NodeHealth) -> Option<NodeHealth> {
+        let mut previous = None;
+        self.update(|c| {
+            c.last_heartbeat = Some(Instant::now());
+            previous = c.stats.health.replace(health);
+        });
+        previous
+    }
+
+    /// Returns why the node is considered unresponsive, if it is.
+    ///
+    /// Nodes are checked only after their first heartbeat, so that a slow
+    /// initialization is not mistaken for a hang.
+    pub fn unresponsive(&self, timeout: Duration) -> Option<String> {
+        let counter = self.0.lock().ok()?;
+        let since_heartbeat = counter.last_heartbeat?.elapsed();
+        if since_heartbeat > timeout {
+            return Some(format!("no heartbeat for {since_heartbeat:.1?}"));
+        }
+        let pending = counter.pending_since.map(|since| since.elapsed());
+        match pending {
+            Some(pending) if pending > timeout => Some(format!(
+                "{} queued events were not received for {pending:.1?}",
+                counter.stats.queue_depth
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn add_latency(&self, latency: Duration) {
+        self.update(|c| {
+            c.latency_sum += latency;
+            c.latency_samples += 1;
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &c.metrics {
+                metrics.input_latency(latency);
+            }
+        });
+    }
+
+    /// Returns the current statistics.
+    ///
+    /// The mean latency covers the inputs that were delivered since the
+    /// previous snapshot, so that it reflects the current latency instead of
+    /// the whole lifetime of the node.
+    pub fn snapshot(&self) -> NodeStats {
+        match self.0.lock() {
+            Ok(mut counter) => {
+                let mut stats = counter.stats.clone();
+                stats.since_last_heartbeat = counter.last_heartbeat.map(|t| t.elapsed());
+                if counter.latency_samples > 0 {
+                    let mean = counter.latency_sum.as_nanos() / u128::from(counter.latency_samples);
+                    stats.mean_latency = Some(Duration::from_nanos(mean as u64));
+                }
+                counter.latency_sum = Duration::ZERO;
+                counter.latency_samples = 0;
+                stats
+            }
+            Err(_) => NodeStats::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_latency_is_reset_per_snapshot() {
+        let stats = SharedNodeStats::default();
+        assert_eq!(stats.snapshot().mean_latency, None);
+
+        stats.add_latency(Duration::from_millis(10));
+        stats.add_latency(Duration::from_millis(30));
+        assert_eq!(
+            stats.snapshot().mean_latency,
+            Some(Duration::from_millis(20))
+        );
+
+        // no inputs since the last snapshot
+        assert_eq!(stats.snapshot().mean_latency, None);
+
+        stats.add_latency(Duration::from_micros(5));
+        assert_eq!(
+            stats.snapshot().mean_latency,
+            Some(Duration::from_micros(5))
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Upgrades of the daemon executable that are pushed through `dora upgrade-daemons`.
+
+use dora_core::version::{self, Version};
+use eyre::{bail, eyre, Context};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// A new daemon executable that was written next to the current one.
+#[derive(Debug)]
+pub struct StagedUpgrade {
+    path: PathBuf,
+    exe: PathBuf,
+    pub version: Version,
+}
+
+/// Writes the new executable next to `exe` and checks that it runs on this
+/// machine and that its version is compatible with the coordinator.
+///
+/// The executable is checked under a temporary name first, so that a broken
+/// upgrade leaves a previously staged one intact.
+pub async fn stage(
+    binary: &[u8],
+    exe: &Path,
+    coordinator_version: &Version,
+) -> eyre::Result<StagedUpgrade> {
+    let file_name = exe
+        .file_name()
+        .ok_or_else(|| eyre!("invalid executable path `{}`", exe.display()))?
+        .to_string_lossy();
+    let partial = exe.with_file_name(format!(".partial-{file_name}"));
+    let path = exe.with_file_name(format!(".upgrade-{file_name}"));
+
+    write_executable(&partial, binary)
+        .wrap_err_with(|| format!("failed to write `{}`", partial.display()))?;
+    let version = match executable_version(&partial).await {
+        Ok(version) => version,
+        Err(err) => {
+            let _ = std::fs::remove_file(&partial);
+            return Err(err.wrap_err("new daemon executable doesn't run on this machine"));
+        }
+    };
+    if let Err(err) = version::check_compatible(
+        ("new daemon", &version),
+        ("coordinator", coordinator_version),
+    ) {
+        let _ = std::fs::remove_file(&partial);
+        bail!(err);
+    }
+    std::fs::rename(&partial, &path)
+        .wrap_err_with(|| format!("failed to rename `{}`", partial.display()))?;
+
+    Ok(StagedUpgrade {
+        path,
+        exe: exe.to_owned(),
+        version,
+    })
+}
+
+impl StagedUpgrade {
+    /// Removes the staged executable, e.g. when the upgrade was cancelled.
+    pub fn discard(self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            tracing::warn!("failed to remove `{}`: {err}", self.path.display());
+        }
+    }
+
+    /// Replaces the current executable with the staged one and restarts the
+    /// daemon with the original command line arguments.
+    ///
+    /// Only returns on error.
+    pub fn install_and_restart(self) -> eyre::Report {
+        if let Err(err) = self.install() {
+            return err;
+        }
+        let mut command = std::process::Command::new(&self.exe);
+        command.args(std::env::args_os().skip(1));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            eyre::Report::new(command.exec()).wrap_err("failed to restart daemon")
+        }
+        #[cfg(not(unix))]
+        {
+            match command.spawn() {
+                Ok(_) => std::process::exit(0),
+                Err(err) => eyre::Report::new(err).wrap_err("failed to restart daemon"),
+            }
+        }
+    }
+
+    fn install(&self) -> eyre::Result<()> {
+        // running executables can't be replaced on windows, but they can be renamed
+        #[cfg(windows)]
+        {
+            let file_name = self.exe.file_name().unwrap_or_default().to_string_lossy();
+            let old = self.exe.with_file_name(format!(".old-{file_name}"));
+            // might still be locked by a previous instance
+            let _ = std::fs::remove_file(&old);
+            std::fs::rename(&self.exe, &old)
+                .wrap_err_with(|| format!("failed to rename `{}`", self.exe.display()))?;
+        }
+        std::fs::rename(&self.path, &self.exe)
+            .wrap_err_with(|| format!("failed to replace `{}`", self.exe.display()))
+    }
+}
+
+fn write_executable(path: &Path, binary: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// Runs `<exe> --version` and parses the version from its last word, e.g. from
+/// `dora-cli 0.3.5`.
+async fn executable_version(exe: &Path) -> eyre::Result<Version> {
+    let output = tokio::time::timeout(
+        Duration::from_secs(10),
+        tokio::process::Command::new(exe)
+            .arg("--version")
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| eyre!("`--version` timed out"))?
+    .wrap_err("failed to run `--version`")?;
+    if !output.status.success() {
+        bail!("`--version` failed with {}", output.status);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout
+        .split_whitespace()
+        .last()
+        .ok_or_else(|| eyre!("`--version` printed nothing"))?;
+    Version::parse(version).wrap_err_with(|| format!("invalid version `{version}`"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn script(version: &str) -> Vec<u8> {
+        format!("#!/bin/sh\necho 'dora-cli {version}'\n").into_bytes()
+    }
+
+    #[tokio::test]
+    async fn stages_compatible_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("dora");
+        std::fs::write(&exe, "current").unwrap();
+        let coordinator = Version::new(0, 3, 5);
+
+        let staged = stage(&script("0.3.9"), &exe, &coordinator).await.unwrap();
+        assert_eq!(staged.version, Version::new(0, 3, 9));
+        assert!(staged.path.is_file());
+
+        let err = stage(&script("0.4.0"), &exe, &coordinator)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("version mismatch"), "{err}");
+        let err = stage(b"not an executable", &exe, &coordinator)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("doesn't run"), "{err}");
+
+        // failed upgrades keep the staged executable
+        assert_eq!(std::fs::read(&staged.path).unwrap(), script("0.3.9"));
+        assert!(!dir.path().join(".partial-dora").exists());
+
+        staged.install().unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), script("0.3.9"));
+    }
+}
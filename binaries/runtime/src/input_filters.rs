@@ -0,0 +1,91 @@
+use arrow::{
+    array::{Array, AsArray},
+    datatypes::{DataType, Float64Type},
+};
+use dora_core::{
+    config::{DataId, OperatorId},
+    descriptor::{FilterField, FilterValue, InputFilter, OperatorConfig},
+    message::Metadata,
+};
+use std::collections::HashMap;
+
+/// Drops operator inputs that don't match their configured filter.
+///
+/// The filters are evaluated before the inputs are passed to the operator, so
+/// filtered inputs never cause a callback, e.g. a Python call that requires
+/// the GIL.
+pub struct InputFilters {
+    filters: HashMap<(OperatorId, DataId), InputFilter>,
+}
+
+impl InputFilters {
+    pub fn new(operators: &HashMap<OperatorId, OperatorConfig>) -> Self {
+        let filters = operators
+            .iter()
+            .flat_map(|(operator_id, config)| {
+                config.input_filters.iter().map(|(input_id, filter)| {
+                    ((operator_id.clone(), input_id.clone()), filter.clone())
+                })
+            })
+            .collect();
+        Self { filters }
+    }
+
+    /// Replaces the filters of the given operator, e.g. after it was loaded
+    /// into the running node.
+    pub fn add_operator(&mut self, operator_id: &OperatorId, config: &OperatorConfig) {
+        self.filters.retain(|(id, _), _| id != operator_id);
+        for (input_id, filter) in &config.input_filters {
+            self.filters
+                .insert((operator_id.clone(), input_id.clone()), filter.clone());
+        }
+    }
+
+    /// Returns `false` if the given input should be dropped.
+    pub fn check(
+        &self,
+        operator_id: &OperatorId,
+        input_id: &DataId,
+        metadata: &Metadata,
+        data: &dyn Array,
+    ) -> bool {
+        let Some(filter) = self.filters.get(&(operator_id.clone(), input_id.clone())) else {
+            return true;
+        };
+        filter.matches(|field| match field {
+            FilterField::Watermark => {
+                Some(FilterValue::Number(metadata.parameters.watermark as f64))
+            }
+            FilterField::Deadline => Some(FilterValue::Number(metadata.parameters.deadline as f64)),
+            FilterField::Len => Some(FilterValue::Number(data.len() as f64)),
+            FilterField::Data(None) => first_value(data),
+            FilterField::Data(Some(name)) => {
+                let column = data.as_struct_opt()?.column_by_name(name)?;
+                first_value(column.as_ref())
+            }
+        })
+    }
+}
+
+/// Returns the first element of the given array if it has a supported type.
+fn first_value(array: &dyn Array) -> Option<FilterValue> {
+    if array.is_empty() || array.is_null(0) {
+        return None;
+    }
+    match array.data_type() {
+        DataType::Boolean => Some(FilterValue::Bool(array.as_boolean().value(0))),
+        DataType::Utf8 => Some(FilterValue::String(
+            array.as_string::<i32>().value(0).to_owned(),
+        )),
+        DataType::LargeUtf8 => Some(FilterValue::String(
+            array.as_string::<i64>().value(0).to_owned(),
+        )),
+        data_type if data_type.is_numeric() => {
+            let value = arrow::compute::cast(&array.slice(0, 1), &DataType::Float64).ok()?;
+            Some(FilterValue::Number(
+                value.as_primitive::<Float64Type>().value(0),
+            ))
+        }
+        _ => None,
+    }
+}
@@ -1,29 +1,56 @@
 #![warn(unsafe_op_in_unsafe_fn)]
 
+use aligned_vec::{AVec, ConstAlign};
 use dora_core::{
-    config::{DataId, OperatorId},
-    daemon_messages::{NodeConfig, RuntimeConfig},
-    descriptor::OperatorConfig,
+    config::{DataId, NodeId, OperatorId},
+    daemon_messages::{DataflowId, NodeConfig, OperatorError, RuntimeConfig},
+    descriptor::{
+        Descriptor, OperatorConfig, OperatorDefinition, OperatorErrorPolicy, OperatorSource,
+        WatchdogAction,
+    },
 };
 use dora_metrics::init_meter_provider;
-use dora_node_api::{DoraNode, Event};
-use eyre::{bail, Context, Result};
+use dora_node_api::{ArrowData, DataSample, DoraNode, Event, IntoArrow, Metadata};
+use eyre::{bail, eyre, Context, OptionExt, Result};
 use futures::{Stream, StreamExt};
 use futures_concurrency::stream::Merge;
-use operator::{run_operator, OperatorEvent, StopReason};
+use operator::{
+    isolated::ISOLATED_OPERATOR_ENV,
+    run_operator, timers,
+    watchdog::{self, CallbackTimer},
+    worker_pool::WorkerPool,
+    OperatorEvent, StopReason,
+};
 
 #[cfg(feature = "tracing")]
 use dora_tracing::set_up_tracing;
+use input_filters::InputFilters;
+use local_routes::LocalRoutes;
+use output_limits::OutputLimiter;
+use profile::Profiler;
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     mem,
+    num::NonZeroUsize,
+    time::Duration,
 };
 use tokio::{
     runtime::Builder,
     sync::{mpsc, oneshot},
 };
 use tokio_stream::wrappers::ReceiverStream;
+mod input_filters;
+mod local_routes;
 mod operator;
+mod output_limits;
+mod profile;
+
+#[cfg(feature = "python")]
+pub use operator::{PythonLogger, PythonMetrics};
+
+/// Delay before restarting a failed operator, to avoid a busy loop if the
+/// operator fails again right away.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
 
 pub fn main() -> eyre::Result<()> {
     let config: RuntimeConfig = {
@@ -34,28 +61,33 @@ pub fn main() -> eyre::Result<()> {
     let RuntimeConfig {
         node: config,
         operators,
+        workers,
+        profile,
+        mut isolated_python,
     } = config;
     let node_id = config.node_id.clone();
     #[cfg(feature = "tracing")]
-    set_up_tracing(&node_id.to_string()).context("failed to set up tracing subscriber")?;
+    set_up_tracing(node_id.as_ref()).context("failed to set up tracing subscriber")?;
+
+    if let Ok(child_config) = std::env::var(ISOLATED_OPERATOR_ENV) {
+        // we're the child process of an isolated operator
+        return operator::isolated::run_child(
+            &child_config,
+            config.dataflow_id,
+            &config.dataflow_descriptor,
+        );
+    }
 
     let dataflow_descriptor = config.dataflow_descriptor.clone();
+    let dataflow_id = config.dataflow_id;
 
-    let operator_definition = if operators.is_empty() {
+    if operators.is_empty() {
         bail!("no operators");
-    } else if operators.len() > 1 {
-        bail!("multiple operators are not supported");
-    } else {
-        let mut ops = operators;
-        ops.remove(0)
-    };
-
-    let (operator_events_tx, events) = mpsc::channel(1);
-    let operator_id = operator_definition.id.clone();
-    let operator_events = ReceiverStream::new(events).map(move |event| RuntimeEvent::Operator {
-        id: operator_id.clone(),
-        event,
-    });
+    }
+    let worker_pool = WorkerPool::new(workers.unwrap_or(NonZeroUsize::MIN));
+    let profiler = profile
+        .as_ref()
+        .map(|profile| Profiler::start(profile, &dataflow_id, &node_id));
 
     let tokio_runtime = Builder::new_current_thread()
         .enable_all()
@@ -63,71 +95,315 @@ pub fn main() -> eyre::Result<()> {
         .wrap_err("Could not build a tokio runtime.")?;
 
     let mut operator_channels = HashMap::new();
-    let queue_sizes = queue_sizes(&operator_definition.config);
-    let (operator_channel, incoming_events) =
-        operator::channel::channel(tokio_runtime.handle(), queue_sizes);
-    operator_channels.insert(operator_definition.id.clone(), operator_channel);
+    let mut operator_restarts = HashMap::new();
+    let mut operator_config = HashMap::new();
+    let mut operator_event_streams = Vec::new();
+    let mut init_done = Vec::new();
+    let mut operator_tasks = Vec::new();
+    for operator_definition in operators {
+        let operator_id = operator_definition.id.clone();
+        operator_config.insert(operator_id.clone(), operator_definition.config.clone());
+
+        let python = isolated_python.remove(&operator_id);
+        let prepared = prepare_operator(
+            operator_definition,
+            python,
+            tokio_runtime.handle(),
+            profiler.as_ref(),
+        );
+        operator_event_streams.push(operator_event_stream(operator_id.clone(), prepared.events));
+        operator_channels.insert(operator_id.clone(), prepared.channel);
+        operator_restarts.insert(operator_id, prepared.restart);
+        init_done.push(prepared.init_done);
+        operator_tasks.push(prepared.task);
+    }
+    let operator_events = futures::stream::select_all(operator_event_streams);
 
+    let loader = OperatorLoader {
+        node_id: node_id.clone(),
+        dataflow_id,
+        dataflow_descriptor: dataflow_descriptor.clone(),
+        worker_pool: worker_pool.clone(),
+        profiler: profiler.clone(),
+    };
     tracing::info!("spawning main task");
-    let operator_config = [(
-        operator_definition.id.clone(),
-        operator_definition.config.clone(),
-    )]
-    .into_iter()
-    .collect();
-    let (init_done_tx, init_done) = oneshot::channel();
     let main_task = std::thread::spawn(move || -> Result<()> {
         tokio_runtime.block_on(run(
             operator_config,
             config,
             operator_events,
             operator_channels,
+            operator_restarts,
             init_done,
+            loader,
         ))
     });
 
-    let operator_id = operator_definition.id.clone();
-    run_operator(
-        &node_id,
-        operator_definition,
-        incoming_events,
-        operator_events_tx,
-        init_done_tx,
-        &dataflow_descriptor,
-    )
-    .wrap_err_with(|| format!("failed to run operator {operator_id}"))?;
+    // The first operator stays on the main thread because some libraries
+    // (e.g. GUI toolkits) only work there. All other operators get their own
+    // thread and input queue, so that they can run in parallel.
+    let mut operator_tasks = operator_tasks.into_iter();
+    let main_operator = operator_tasks.next().ok_or_eyre("no operators")?;
+    let operator_threads = operator_tasks
+        .map(|task| {
+            let node_id = node_id.clone();
+            let dataflow_descriptor = dataflow_descriptor.clone();
+            let worker_pool = worker_pool.clone();
+            std::thread::Builder::new()
+                .name(format!("operator-{}", task.definition.id))
+                .spawn(move || task.run(&node_id, dataflow_id, &dataflow_descriptor, worker_pool))
+                .wrap_err("failed to spawn operator thread")
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    main_operator.run(&node_id, dataflow_id, &dataflow_descriptor, worker_pool)?;
+
+    for thread in operator_threads {
+        match thread.join() {
+            Ok(result) => result?,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
 
     match main_task.join() {
         Ok(result) => result.wrap_err("main task failed")?,
         Err(panic) => std::panic::resume_unwind(panic),
     }
 
+    if let Some(profiler) = profiler {
+        if let Err(err) = profiler.write(&dataflow_id, &node_id) {
+            tracing::warn!("failed to write operator profile: {err:?}");
+        }
+    }
+
     Ok(())
 }
 
+struct OperatorTask {
+    definition: OperatorDefinition,
+    /// Python interpreter command for isolated operators, see
+    /// [`RuntimeConfig::isolated_python`].
+    python: Option<Vec<String>>,
+    incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
+    events_tx: mpsc::Sender<OperatorEvent>,
+    init_done: oneshot::Sender<Result<()>>,
+    callback_timer: CallbackTimer,
+    /// Signals that the operator failed and should be started again.
+    restart: flume::Receiver<()>,
+}
+
+impl OperatorTask {
+    fn run(
+        self,
+        node_id: &NodeId,
+        dataflow_id: DataflowId,
+        dataflow_descriptor: &Descriptor,
+        worker_pool: WorkerPool,
+    ) -> eyre::Result<()> {
+        let operator_id = self.definition.id.clone();
+        if let Some(scheduling) = &self.definition.config.scheduling {
+            operator::scheduling::apply(&operator_id, scheduling);
+        }
+        let mut init_done = self.init_done;
+        loop {
+            run_operator(
+                node_id,
+                dataflow_id,
+                self.definition.clone(),
+                self.python.clone(),
+                self.incoming_events.clone(),
+                self.timers.clone(),
+                self.events_tx.clone(),
+                init_done,
+                dataflow_descriptor,
+                self.callback_timer.clone(),
+                worker_pool.clone(),
+            )
+            .wrap_err_with(|| format!("failed to run operator {operator_id}"))?;
+
+            // the channel is closed when the operator should not be restarted
+            if self.restart.recv().is_err() {
+                break Ok(());
+            }
+            std::thread::sleep(RESTART_DELAY);
+            tracing::info!("restarting operator {node_id}/{operator_id}");
+            // initialization errors of restarted operators are reported as
+            // operator errors, so there is nobody waiting for the result
+            (init_done, _) = oneshot::channel();
+        }
+    }
+}
+
+/// The channels and background tasks of an operator, set up before its thread
+/// is started.
+struct PreparedOperator {
+    task: OperatorTask,
+    channel: flume::Sender<Event>,
+    restart: flume::Sender<()>,
+    events: mpsc::Receiver<OperatorEvent>,
+    init_done: oneshot::Receiver<Result<()>>,
+}
+
+fn prepare_operator(
+    definition: OperatorDefinition,
+    python: Option<Vec<String>>,
+    runtime: &tokio::runtime::Handle,
+    profiler: Option<&Profiler>,
+) -> PreparedOperator {
+    let (events_tx, events) = mpsc::channel(1);
+    let (channel, incoming_events) = operator::channel::channel(
+        runtime,
+        definition.id.clone(),
+        queue_sizes(&definition.config),
+        definition.config.drain_timeout(),
+    );
+
+    let callback_timer = match profiler {
+        Some(profiler) => CallbackTimer::with_profile(profiler.operator(&definition.id)),
+        None => CallbackTimer::default(),
+    };
+    if let Some(mut config) = definition.config.watchdog.clone() {
+        if let OperatorSource::Wasm(_) = definition.config.source {
+            // WASM callbacks are interrupted by the runtime when they exceed the
+            // timeout, which results in a regular operator error
+            config.action = WatchdogAction::Report;
+        }
+        runtime.spawn(watchdog::watch(
+            callback_timer.clone(),
+            config,
+            events_tx.clone(),
+        ));
+    }
+
+    let timers = timers::spawn(runtime, &definition.config.timers);
+    let (restart, restart_rx) = flume::bounded(1);
+    let (init_done_tx, init_done) = oneshot::channel();
+    PreparedOperator {
+        task: OperatorTask {
+            definition,
+            python,
+            incoming_events,
+            timers,
+            events_tx,
+            init_done: init_done_tx,
+            callback_timer,
+            restart: restart_rx,
+        },
+        channel,
+        restart,
+        events,
+        init_done,
+    }
+}
+
+fn operator_event_stream(
+    operator_id: OperatorId,
+    events: mpsc::Receiver<OperatorEvent>,
+) -> impl Stream<Item = RuntimeEvent> + Unpin {
+    ReceiverStream::new(events).map(move |event| RuntimeEvent::Operator {
+        id: operator_id.clone(),
+        event,
+    })
+}
+
+/// Starts operators that are loaded while the node is running.
+struct OperatorLoader {
+    node_id: NodeId,
+    dataflow_id: DataflowId,
+    dataflow_descriptor: Descriptor,
+    worker_pool: WorkerPool,
+    profiler: Option<Profiler>,
+}
+
+impl OperatorLoader {
+    /// Starts the operator on its own thread and forwards its events to the
+    /// given channel once it is initialized.
+    ///
+    /// If the operator fails to initialize, the error is logged and the
+    /// operator is reported as finished. Unlike operators that are started
+    /// with the node, this doesn't stop the node.
+    fn spawn(
+        &self,
+        operator_id: OperatorId,
+        task: OperatorTask,
+        events: mpsc::Receiver<OperatorEvent>,
+        init_done: oneshot::Receiver<Result<()>>,
+        runtime_events: mpsc::Sender<RuntimeEvent>,
+    ) {
+        let node_id = self.node_id.clone();
+        let dataflow_id = self.dataflow_id;
+        let dataflow_descriptor = self.dataflow_descriptor.clone();
+        let worker_pool = self.worker_pool.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("operator-{operator_id}"))
+            .spawn(move || {
+                if let Err(err) = task.run(&node_id, dataflow_id, &dataflow_descriptor, worker_pool)
+                {
+                    tracing::error!("{err:?}");
+                }
+            });
+        if let Err(err) = spawned {
+            tracing::error!("failed to spawn thread for operator `{operator_id}`: {err}");
+        }
+
+        tokio::spawn(async move {
+            let init_result = init_done
+                .await
+                .unwrap_or_else(|_| Err(eyre!("operator exited before it was initialized")));
+            if let Err(err) = init_result {
+                tracing::error!("failed to load operator `{operator_id}`: {err:?}");
+                let event = RuntimeEvent::Operator {
+                    id: operator_id,
+                    event: OperatorEvent::Finished {
+                        reason: StopReason::InputsClosed,
+                    },
+                };
+                let _ = runtime_events.send(event).await;
+                return;
+            }
+            tracing::info!("loaded operator `{operator_id}`");
+
+            let mut events = operator_event_stream(operator_id, events);
+            while let Some(event) = events.next().await {
+                if runtime_events.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
 fn queue_sizes(config: &OperatorConfig) -> std::collections::BTreeMap<DataId, usize> {
     let mut sizes = BTreeMap::new();
     for (input_id, input) in &config.inputs {
-        let queue_size = input.queue_size.unwrap_or(10);
+        let queue_size = input.effective_queue_size().unwrap_or(10);
         sizes.insert(input_id.clone(), queue_size);
     }
     sizes
 }
 
-#[tracing::instrument(skip(operator_events, operator_channels), level = "trace")]
+#[tracing::instrument(
+    skip(operator_events, operator_channels, operator_restarts, loader),
+    level = "trace"
+)]
 async fn run(
-    operators: HashMap<OperatorId, OperatorConfig>,
+    mut operators: HashMap<OperatorId, OperatorConfig>,
     config: NodeConfig,
     operator_events: impl Stream<Item = RuntimeEvent> + Unpin,
     mut operator_channels: HashMap<OperatorId, flume::Sender<Event>>,
-    init_done: oneshot::Receiver<Result<()>>,
+    mut operator_restarts: HashMap<OperatorId, flume::Sender<()>>,
+    init_done: Vec<oneshot::Receiver<Result<()>>>,
+    loader: OperatorLoader,
 ) -> eyre::Result<()> {
     #[cfg(feature = "metrics")]
     let _meter_provider = init_meter_provider(config.node_id.to_string());
-    init_done
-        .await
-        .wrap_err("the `init_done` channel was closed unexpectedly")?
-        .wrap_err("failed to init an operator")?;
+    for init_done in init_done {
+        init_done
+            .await
+            .wrap_err("the `init_done` channel was closed unexpectedly")?
+            .wrap_err("failed to init an operator")?;
+    }
     tracing::info!("All operators are ready, starting runtime");
 
     let (mut node, mut daemon_events) = DoraNode::init(config)?;
@@ -139,11 +415,25 @@ async fn run(
             }
         }
     });
-    let mut events = (operator_events, daemon_event_stream.into_stream()).merge();
+    let (loaded_events_tx, loaded_events) = mpsc::channel(1);
+    let mut events = (
+        operator_events,
+        daemon_event_stream.into_stream(),
+        ReceiverStream::new(loaded_events),
+    )
+        .merge();
 
+    let mut output_limiter = OutputLimiter::new(&operators)?;
+    let mut input_filters = InputFilters::new(&operators);
+    let mut local_routes = LocalRoutes::new(node.id(), &operators, node.dataflow_descriptor())?;
+    let clock = node.clock();
+    // operators keep running after their event channel is closed until they
+    // have processed all queued events
+    let mut running_operators: BTreeSet<_> = operators.keys().cloned().collect();
+    let mut stopping = false;
     let mut open_operator_inputs: HashMap<_, BTreeSet<_>> = operators
         .iter()
-        .map(|(id, config)| (id, config.inputs.keys().collect()))
+        .map(|(id, config)| (id.clone(), config.inputs.keys().cloned().collect()))
         .collect();
 
     while let Some(event) = events.next().await {
@@ -152,15 +442,115 @@ async fn run(
                 id: operator_id,
                 event,
             } => {
-                match event {
+                let failure = match event {
                     OperatorEvent::Error(err) => {
-                        bail!(err.wrap_err(format!(
+                        let report = err
+                            .chain()
+                            .find_map(|cause| cause.downcast_ref::<OperatorError>())
+                            .cloned();
+                        let err = err.wrap_err(format!(
                             "operator {}/{operator_id} raised an error",
                             node.id()
-                        )))
+                        ));
+                        Err((err, report, false))
                     }
-                    OperatorEvent::Panic(payload) => {
-                        bail!("operator {operator_id} panicked: {payload:?}");
+                    OperatorEvent::Panic(payload) => Err((
+                        eyre!("operator {operator_id} panicked: {payload:?}"),
+                        None,
+                        false,
+                    )),
+                    OperatorEvent::Timeout {
+                        elapsed,
+                        action: WatchdogAction::Kill,
+                    } => {
+                        let report = OperatorError {
+                            operator_id: operator_id.clone(),
+                            input_id: None,
+                            kind: Some("WatchdogTimeout".into()),
+                            message: format!(
+                                "event callback is still running after {elapsed:?}, \
+                                exceeding its watchdog timeout"
+                            ),
+                            traceback: None,
+                        };
+                        let err = eyre!("{report}").wrap_err(format!(
+                            "operator {}/{operator_id} is not responding",
+                            node.id()
+                        ));
+                        Err((err, Some(report), true))
+                    }
+                    other => Ok(other),
+                };
+                let event = match failure {
+                    Ok(event) => event,
+                    Err((err, report, hung)) => {
+                        if let Some(report) = report {
+                            let error_output = operators
+                                .get(&operator_id)
+                                .and_then(|config| config.error_output.clone());
+                            let result;
+                            (node, result) = tokio::task::spawn_blocking(move || {
+                                let result = report_operator_error(&mut node, report, error_output);
+                                (node, result)
+                            })
+                            .await
+                            .wrap_err("failed to wait for report_operator_error task")?;
+                            if let Err(err) = result {
+                                tracing::warn!("failed to report operator error: {err:?}");
+                            }
+                        }
+                        let policy = operators
+                            .get(&operator_id)
+                            .map(|config| config.on_error)
+                            .unwrap_or_default();
+                        // Operator threads can't be interrupted while they're stuck in a
+                        // callback, so hung operators are stopped instead of restarted.
+                        // They exit once the callback returns, because their event
+                        // channel is closed.
+                        let policy = match policy {
+                            OperatorErrorPolicy::Restart if hung => {
+                                tracing::warn!(
+                                    "cannot restart hung operator {}/{operator_id}, \
+                                    stopping it instead",
+                                    node.id()
+                                );
+                                OperatorErrorPolicy::Ignore
+                            }
+                            other => other,
+                        };
+                        match policy {
+                            OperatorErrorPolicy::FailDataflow => bail!(err),
+                            OperatorErrorPolicy::Ignore => {
+                                tracing::error!("{err:?}");
+                                tracing::warn!(
+                                    "stopping failed operator {}/{operator_id} because of its \
+                                    `on_error: ignore` policy",
+                                    node.id()
+                                );
+                                OperatorEvent::Finished {
+                                    reason: StopReason::InputsClosed,
+                                }
+                            }
+                            OperatorErrorPolicy::Restart => {
+                                tracing::error!("{err:?}");
+                                if let Some(restart) = operator_restarts.get(&operator_id) {
+                                    let _ = restart.try_send(());
+                                }
+                                continue;
+                            }
+                        }
+                    }
+                };
+                match event {
+                    OperatorEvent::Error(_) | OperatorEvent::Panic(_) => {
+                        unreachable!("operator failures are handled above")
+                    }
+                    OperatorEvent::Timeout { elapsed, .. } => {
+                        tracing::error!(
+                            "event callback of operator {}/{operator_id} is still running \
+                            after {elapsed:?}, exceeding its watchdog timeout",
+                            node.id()
+                        );
                     }
                     OperatorEvent::Finished { reason } => {
                         if let StopReason::ExplicitStopAll = reason {
@@ -197,12 +587,36 @@ async fn run(
                         .wrap_err("failed to wait for close_outputs task")?;
                         result.wrap_err("failed to close outputs of finished operator")?;
 
+                        // the daemon doesn't know about the connections inside this node
+                        for output_id in &config.outputs {
+                            for (receiver_id, input_id) in
+                                local_routes.receivers(&operator_id, output_id)
+                            {
+                                close_operator_input(
+                                    &mut operator_channels,
+                                    &mut open_operator_inputs,
+                                    receiver_id,
+                                    input_id,
+                                )
+                                .await;
+                            }
+                        }
+
                         operator_channels.remove(&operator_id);
+                        operator_restarts.remove(&operator_id);
+                        running_operators.remove(&operator_id);
 
-                        if operator_channels.is_empty() {
+                        if running_operators.is_empty() {
                             break;
                         }
                     }
+                    OperatorEvent::Log(record) => {
+                        if let Err(err) = node.log_record(record) {
+                            tracing::warn!(
+                                "failed to send log record of operator {operator_id}: {err:?}"
+                            );
+                        }
+                    }
                     OperatorEvent::AllocateOutputSample { len, sample: tx } => {
                         let sample = node.allocate_data_sample(len);
                         if tx.send(sample).is_err() {
@@ -215,6 +629,77 @@ async fn run(
                         parameters,
                         data,
                     } => {
+                        if !output_limiter.check(
+                            &operator_id,
+                            &output_id,
+                            &type_info,
+                            data.as_deref(),
+                        ) {
+                            tracing::trace!(
+                                "dropping output `{operator_id}/{output_id}` because of its output limits"
+                            );
+                            continue;
+                        }
+                        let receivers = local_routes.receivers(&operator_id, &output_id);
+                        let data = if receivers.is_empty() {
+                            data
+                        } else {
+                            let send_to_daemon =
+                                local_routes.send_to_daemon(&operator_id, &output_id);
+                            // the original sample is sent to the daemon if other nodes
+                            // receive the output too, so the local receivers get a copy
+                            let (local_data, data) = if send_to_daemon {
+                                let copy = data.as_deref().map(|bytes| {
+                                    DataSample::from(AVec::<u8, ConstAlign<128>>::from_slice(
+                                        128, bytes,
+                                    ))
+                                });
+                                (copy, data)
+                            } else {
+                                (data, None)
+                            };
+                            let array = match local_data {
+                                Some(sample) => sample
+                                    .into_arrow_array(&type_info)
+                                    .wrap_err("failed to convert operator output to Arrow")?,
+                                None => ().into_arrow().into(),
+                            };
+                            let array = arrow::array::make_array(array);
+                            let metadata = Metadata::from_parameters(
+                                clock.new_timestamp(),
+                                type_info.clone(),
+                                parameters.clone(),
+                            );
+                            for (receiver_id, input_id) in receivers {
+                                let Some(channel) = operator_channels.get(receiver_id) else {
+                                    continue;
+                                };
+                                if !input_filters.check(
+                                    receiver_id,
+                                    input_id,
+                                    &metadata,
+                                    array.as_ref(),
+                                ) {
+                                    continue;
+                                }
+                                let event = Event::Input {
+                                    id: input_id.clone(),
+                                    metadata: metadata.clone(),
+                                    data: ArrowData(array.clone()),
+                                };
+                                if channel.send_async(event).await.is_err() {
+                                    tracing::warn!(
+                                        "failed to send output `{operator_id}/{output_id}` to \
+                                        operator `{receiver_id}`"
+                                    );
+                                }
+                            }
+                            if !send_to_daemon {
+                                continue;
+                            }
+                            data
+                        };
+
                         let output_id = operator_output_id(&operator_id, &output_id);
                         let result;
                         (node, result) = tokio::task::spawn_blocking(move || {
@@ -229,6 +714,7 @@ async fn run(
                 }
             }
             RuntimeEvent::Event(Event::Stop) => {
+                stopping = true;
                 // forward stop event to all operators and close the event channels
                 for (_, channel) in operator_channels.drain() {
                     let _ = channel.send_async(Event::Stop).await;
@@ -248,6 +734,78 @@ async fn run(
             RuntimeEvent::Event(Event::Reload { operator_id: None }) => {
                 tracing::warn!("Reloading runtime nodes is not supported");
             }
+            RuntimeEvent::Event(Event::LoadOperator { operator }) => {
+                let operator_id = operator.id.clone();
+                if stopping {
+                    tracing::warn!(
+                        "not loading operator `{operator_id}` because the node is stopping"
+                    );
+                    continue;
+                }
+                if running_operators.contains(&operator_id) {
+                    tracing::warn!(
+                        "cannot load operator `{operator_id}`: an operator with the same ID \
+                        is already running"
+                    );
+                    continue;
+                }
+                if let Err(err) = output_limiter.add_operator(&operator_id, &operator.config) {
+                    tracing::warn!("cannot load operator `{operator_id}`: {err:?}");
+                    continue;
+                }
+                node.add_outputs(
+                    operator
+                        .config
+                        .outputs
+                        .iter()
+                        .map(|output_id| operator_output_id(&operator_id, output_id)),
+                );
+                input_filters.add_operator(&operator_id, &operator.config);
+                local_routes.add_operator(node.id(), &operator_id, &operator.config);
+                open_operator_inputs.insert(
+                    operator_id.clone(),
+                    operator.config.inputs.keys().cloned().collect(),
+                );
+                operators.insert(operator_id.clone(), operator.config.clone());
+
+                let PreparedOperator {
+                    task,
+                    channel,
+                    restart,
+                    events,
+                    init_done,
+                } = prepare_operator(
+                    operator,
+                    None,
+                    &tokio::runtime::Handle::current(),
+                    loader.profiler.as_ref(),
+                );
+                operator_channels.insert(operator_id.clone(), channel);
+                operator_restarts.insert(operator_id.clone(), restart);
+                running_operators.insert(operator_id.clone());
+                loader.spawn(
+                    operator_id,
+                    task,
+                    events,
+                    init_done,
+                    loaded_events_tx.clone(),
+                );
+            }
+            RuntimeEvent::Event(Event::UnloadOperator { operator_id }) => {
+                // the operator is removed when it reports that it finished, which
+                // also closes its outputs
+                operator_restarts.remove(&operator_id);
+                open_operator_inputs.remove(&operator_id);
+                match operator_channels.remove(&operator_id) {
+                    Some(channel) => {
+                        tracing::info!("unloading operator `{operator_id}`");
+                        let _ = channel.send_async(Event::Stop).await;
+                    }
+                    None => {
+                        tracing::warn!("cannot unload operator `{operator_id}`: it's not running")
+                    }
+                }
+            }
             RuntimeEvent::Event(Event::Input { id, metadata, data }) => {
                 let Some((operator_id, input_id)) = id.as_str().split_once('/') else {
                     tracing::warn!("received non-operator input {id}");
@@ -256,9 +814,16 @@ async fn run(
                 let operator_id = OperatorId::from(operator_id.to_owned());
                 let input_id = DataId::from(input_id.to_owned());
                 let Some(operator_channel) = operator_channels.get(&operator_id) else {
-                    tracing::warn!("received input {id} for unknown operator");
+                    // inputs of stopped operators are dropped silently
+                    if !operators.contains_key(&operator_id) {
+                        tracing::warn!("received input {id} for unknown operator");
+                    }
                     continue;
                 };
+                if !input_filters.check(&operator_id, &input_id, &metadata, data.as_ref()) {
+                    tracing::trace!("dropping input `{id}` because it doesn't match its filter");
+                    continue;
+                }
 
                 if let Err(err) = operator_channel
                     .send_async(Event::Input {
@@ -282,33 +847,21 @@ async fn run(
                 let operator_id = OperatorId::from(operator_id.to_owned());
                 let input_id = DataId::from(input_id.to_owned());
 
-                let Some(operator_channel) = operator_channels.get(&operator_id) else {
+                if !operator_channels.contains_key(&operator_id) {
                     tracing::warn!("received input {id} for unknown operator");
                     continue;
-                };
-                if let Err(err) = operator_channel
-                    .send_async(Event::InputClosed {
-                        id: input_id.clone(),
-                    })
-                    .await
-                    .wrap_err_with(|| {
-                        format!(
-                            "failed to send InputClosed({input_id}) to operator `{operator_id}`"
-                        )
-                    })
-                {
-                    tracing::warn!("{err}");
-                }
-
-                if let Some(open_inputs) = open_operator_inputs.get_mut(&operator_id) {
-                    open_inputs.remove(&input_id);
-                    if open_inputs.is_empty() {
-                        // all inputs of the node were closed -> close its event channel
-                        tracing::trace!("all inputs of operator {}/{operator_id} were closed -> closing event channel", node.id());
-                        open_operator_inputs.remove(&operator_id);
-                        operator_channels.remove(&operator_id);
-                    }
                 }
+                close_operator_input(
+                    &mut operator_channels,
+                    &mut open_operator_inputs,
+                    &operator_id,
+                    &input_id,
+                )
+                .await;
+            }
+            RuntimeEvent::Event(Event::AllInputsClosed) => {
+                // operators are notified through the `InputClosed` events of
+                // their own inputs
             }
             RuntimeEvent::Event(Event::Error(err)) => eyre::bail!("received error event: {err}"),
             RuntimeEvent::Event(other) => {
@@ -322,6 +875,64 @@ async fn run(
     Ok(())
 }
 
+/// Forwards an `InputClosed` event to the given operator and closes its event
+/// channel once all of its inputs are closed.
+async fn close_operator_input(
+    operator_channels: &mut HashMap<OperatorId, flume::Sender<Event>>,
+    open_operator_inputs: &mut HashMap<OperatorId, BTreeSet<DataId>>,
+    operator_id: &OperatorId,
+    input_id: &DataId,
+) {
+    let Some(operator_channel) = operator_channels.get(operator_id) else {
+        return;
+    };
+    if let Err(err) = operator_channel
+        .send_async(Event::InputClosed {
+            id: input_id.clone(),
+        })
+        .await
+        .wrap_err_with(|| {
+            format!("failed to send InputClosed({input_id}) to operator `{operator_id}`")
+        })
+    {
+        tracing::warn!("{err}");
+    }
+
+    if let Some(open_inputs) = open_operator_inputs.get_mut(operator_id) {
+        open_inputs.remove(input_id);
+        if open_inputs.is_empty() {
+            // all inputs of the operator were closed -> close its event channel
+            tracing::trace!(
+                "all inputs of operator {operator_id} were closed -> closing event channel"
+            );
+            open_operator_inputs.remove(operator_id);
+            operator_channels.remove(operator_id);
+        }
+    }
+}
+
+/// Forwards a structured operator error to the daemon and publishes it on the
+/// `_unstable_error_output` of the operator, if set.
+///
+/// The error output is sent to the daemon only, so it doesn't reach other
+/// operators of the same node.
+fn report_operator_error(
+    node: &mut DoraNode,
+    error: OperatorError,
+    error_output: Option<DataId>,
+) -> eyre::Result<()> {
+    if let Some(output_id) = error_output {
+        let json = serde_json::to_string(&error).wrap_err("failed to serialize operator error")?;
+        node.send_output(
+            operator_output_id(&error.operator_id, &output_id),
+            Default::default(),
+            arrow::array::StringArray::from(vec![json]),
+        )
+        .wrap_err("failed to publish operator error")?;
+    }
+    node.report_operator_error(error)
+}
+
 fn operator_output_id(operator_id: &OperatorId, output_id: &DataId) -> DataId {
     DataId::from(format!("{operator_id}/{output_id}"))
 }
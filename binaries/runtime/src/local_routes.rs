@@ -0,0 +1,121 @@
+use dora_core::{
+    config::{DataId, InputMapping, NodeId, OperatorId},
+    descriptor::{runtime_node_inputs, CoreNodeKind, Descriptor, OperatorConfig},
+};
+use eyre::Context;
+use std::collections::{HashMap, HashSet};
+
+/// Connections between the operators of this runtime node.
+///
+/// Outputs that are mapped to inputs of other operators in the same node are
+/// passed to them in-process, without serializing them through the daemon.
+/// The daemon skips these connections when it routes the node's outputs, so
+/// outputs are only sent to the daemon if they have receivers outside of the
+/// node.
+pub struct LocalRoutes {
+    receivers: HashMap<(OperatorId, DataId), Vec<(OperatorId, DataId)>>,
+    /// Operator outputs that are also mapped to inputs of other nodes.
+    external: HashSet<(OperatorId, DataId)>,
+}
+
+impl LocalRoutes {
+    pub fn new(
+        node_id: &NodeId,
+        operators: &HashMap<OperatorId, OperatorConfig>,
+        descriptor: &Descriptor,
+    ) -> eyre::Result<Self> {
+        let mut receivers: HashMap<_, Vec<_>> = HashMap::new();
+        for (operator_id, config) in operators {
+            for (input_id, input) in &config.inputs {
+                if let Some(source) = operator_source(node_id, &input.mapping) {
+                    receivers
+                        .entry(source)
+                        .or_default()
+                        .push((operator_id.clone(), input_id.clone()));
+                }
+            }
+        }
+
+        let nodes = descriptor
+            .resolve_aliases_and_set_defaults()
+            .wrap_err("failed to resolve dataflow nodes")?;
+        let external = nodes
+            .iter()
+            .filter(|node| &node.id != node_id)
+            .flat_map(|node| match &node.kind {
+                CoreNodeKind::Custom(custom) => custom.run_config.inputs.clone(),
+                CoreNodeKind::Runtime(runtime) => runtime_node_inputs(runtime),
+            })
+            .filter_map(|(_, input)| operator_source(node_id, &input.mapping))
+            .collect();
+
+        Ok(Self {
+            receivers,
+            external,
+        })
+    }
+
+    /// Adds the inputs of an operator that was loaded into the running node.
+    ///
+    /// Existing routes to an operator with the same ID are replaced. The outputs
+    /// of loaded operators are never mapped to inputs of other nodes, so they're
+    /// not added to the external outputs.
+    pub fn add_operator(
+        &mut self,
+        node_id: &NodeId,
+        operator_id: &OperatorId,
+        config: &OperatorConfig,
+    ) {
+        for receivers in self.receivers.values_mut() {
+            receivers.retain(|(receiver_id, _)| receiver_id != operator_id);
+        }
+        for (input_id, input) in &config.inputs {
+            if let Some(source) = operator_source(node_id, &input.mapping) {
+                self.receivers
+                    .entry(source)
+                    .or_default()
+                    .push((operator_id.clone(), input_id.clone()));
+            }
+        }
+    }
+
+    /// Returns the operator inputs of this node that the given output is
+    /// mapped to.
+    pub fn receivers(
+        &self,
+        operator_id: &OperatorId,
+        output_id: &DataId,
+    ) -> &[(OperatorId, DataId)] {
+        self.receivers
+            .get(&(operator_id.clone(), output_id.clone()))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if the given output needs to be sent to the daemon.
+    ///
+    /// This is the case if the output has receivers in other nodes, or if it
+    /// isn't connected to any operator of this node.
+    pub fn send_to_daemon(&self, operator_id: &OperatorId, output_id: &DataId) -> bool {
+        self.receivers(operator_id, output_id).is_empty()
+            || self
+                .external
+                .contains(&(operator_id.clone(), output_id.clone()))
+    }
+}
+
+/// Returns the operator and output ID if the input is mapped to an operator
+/// output of the given node.
+fn operator_source(node_id: &NodeId, mapping: &InputMapping) -> Option<(OperatorId, DataId)> {
+    let InputMapping::User(mapping) = mapping else {
+        return None;
+    };
+    if &mapping.source != node_id {
+        return None;
+    }
+    let (operator_id, output_id) = mapping.output.as_str().split_once('/')?;
+    Some((
+        OperatorId::from(operator_id.to_owned()),
+        DataId::from(output_id.to_owned()),
+    ))
+}
@@ -1,20 +1,26 @@
-use dora_core::config::DataId;
+use dora_core::config::{DataId, OperatorId};
 use dora_node_api::Event;
 use futures::{
     future::{self, FusedFuture},
     FutureExt,
 };
-use std::collections::{BTreeMap, VecDeque};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::Duration,
+};
+use tokio::time::Instant;
 
 pub fn channel(
     runtime: &tokio::runtime::Handle,
+    operator_id: OperatorId,
     queue_sizes: BTreeMap<DataId, usize>,
+    drain_timeout: Option<Duration>,
 ) -> (flume::Sender<Event>, flume::Receiver<Event>) {
     let (incoming_tx, incoming_rx) = flume::bounded(10);
     let (outgoing_tx, outgoing_rx) = flume::bounded(0);
 
-    runtime.spawn(async {
-        let mut buffer = InputBuffer::new(queue_sizes);
+    runtime.spawn(async move {
+        let mut buffer = InputBuffer::new(operator_id, queue_sizes, drain_timeout);
         buffer.run(incoming_rx, outgoing_tx).await;
     });
 
@@ -22,15 +28,32 @@ pub fn channel(
 }
 
 struct InputBuffer {
+    operator_id: OperatorId,
     queue: VecDeque<Option<Event>>,
     queue_sizes: BTreeMap<DataId, usize>,
+    drain_timeout: Option<Duration>,
+    /// Set when the stop event is received if there is a drain timeout.
+    drain_deadline: Option<Instant>,
+}
+
+enum Incoming {
+    Event(Event),
+    Closed,
+    DrainTimeout,
 }
 
 impl InputBuffer {
-    pub fn new(queue_sizes: BTreeMap<DataId, usize>) -> Self {
+    pub fn new(
+        operator_id: OperatorId,
+        queue_sizes: BTreeMap<DataId, usize>,
+        drain_timeout: Option<Duration>,
+    ) -> Self {
         Self {
+            operator_id,
             queue: VecDeque::new(),
             queue_sizes,
+            drain_timeout,
+            drain_deadline: None,
         }
     }
 
@@ -38,15 +61,26 @@ impl InputBuffer {
         let mut send_out_buf = future::Fuse::terminated();
         let mut incoming_closed = false;
         loop {
-            let next_incoming = if incoming_closed {
-                future::Fuse::terminated()
+            let next_incoming = if !incoming_closed {
+                future::Either::Left(
+                    incoming
+                        .recv_async()
+                        .map(|event| event.map_or(Incoming::Closed, Incoming::Event)),
+                )
+            } else if let Some(deadline) = self.drain_deadline {
+                future::Either::Right(future::Either::Left(Box::pin(
+                    tokio::time::sleep_until(deadline).map(|()| Incoming::DrainTimeout),
+                )))
             } else {
-                incoming.recv_async().fuse()
+                future::Either::Right(future::Either::Right(future::pending()))
             };
             match future::select(next_incoming, send_out_buf).await {
                 future::Either::Left((event, mut send_out)) => {
                     match event {
-                        Ok(event) => {
+                        Incoming::Event(event) => {
+                            if let Event::Stop = event {
+                                self.start_draining();
+                            }
                             // received a new event -> push it to the queue
                             self.add_event(event);
 
@@ -55,9 +89,13 @@ impl InputBuffer {
                                 send_out = self.send_next_queued(&outgoing);
                             }
                         }
-                        Err(flume::RecvError::Disconnected) => {
+                        Incoming::Closed => {
                             incoming_closed = true;
                         }
+                        Incoming::DrainTimeout => {
+                            self.drain_deadline = None;
+                            self.drop_queued_inputs();
+                        }
                     }
 
                     // reassign the send_out future, which might be still in progress
@@ -91,6 +129,44 @@ impl InputBuffer {
         }
     }
 
+    /// Starts the drain timeout and reports the number of inputs that the
+    /// operator still needs to process before it sees the stop event.
+    fn start_draining(&mut self) {
+        let queued = self.queued_inputs();
+        if queued > 0 {
+            tracing::info!(
+                "stopping operator `{}` after processing its {queued} queued inputs",
+                self.operator_id
+            );
+        }
+        self.drain_deadline = self.drain_timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    fn queued_inputs(&self) -> usize {
+        self.queue
+            .iter()
+            .filter(|event| matches!(event, Some(Event::Input { .. })))
+            .count()
+    }
+
+    /// Drops all queued inputs because the drain timeout expired.
+    fn drop_queued_inputs(&mut self) {
+        let mut dropped = 0;
+        for event in &mut self.queue {
+            if let Some(Event::Input { .. }) = event {
+                *event = None;
+                dropped += 1;
+            }
+        }
+        if dropped > 0 {
+            tracing::warn!(
+                "operator `{}` did not process its queued inputs within its drain timeout, \
+                dropped {dropped} remaining inputs",
+                self.operator_id
+            );
+        }
+    }
+
     fn add_event(&mut self, event: Event) {
         self.queue.push_back(Some(event));
 
@@ -0,0 +1,478 @@
+//! Runs shared library and Python operators in a child process.
+//!
+//! A crash of an isolated operator, e.g. a segfault in the shared library,
+//! only terminates its child process. The crash is reported as an operator
+//! error instead of taking down all operators of the runtime node.
+//!
+//! Isolated Python operators get an interpreter of their own. They don't
+//! share the GIL with other operators, so multiple CPU-bound Python operators
+//! of the same runtime node run in parallel.
+//!
+//! The child is a copy of the runtime process that is started with the
+//! [`ISOLATED_OPERATOR_ENV`] variable set. Python operators whose environment
+//! differs from the one of the runtime are started with the interpreter of
+//! their own environment instead. It connects back to the parent
+//! through a local TCP socket, over which events and outputs are exchanged as
+//! length-prefixed bincode messages.
+
+use super::{
+    shared_lib, timers::next_input, watchdog::CallbackTimer, worker_pool::WorkerPool,
+    OperatorEvent, OperatorInput, StopReason,
+};
+use aligned_vec::{AVec, ConstAlign};
+use dora_core::{
+    config::{DataId, NodeId, OperatorId},
+    daemon_messages::{DataflowId, NodeLogRecord, OperatorError},
+    descriptor::{Descriptor, OperatorConfig, OperatorScheduling, OperatorSource, PythonSource},
+    message::{ArrowTypeInfo, Metadata, MetadataParameters},
+};
+use dora_node_api::{
+    arrow::array::{make_array, Array},
+    arrow_utils::{copy_array_into_sample, required_data_size},
+    ArrowData, Event, RawData,
+};
+use eyre::{bail, eyre, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    process::{Child, Command},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot};
+
+/// Set for child processes that run an isolated operator.
+pub const ISOLATED_OPERATOR_ENV: &str = "DORA_ISOLATED_OPERATOR";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChildConfig {
+    node_id: NodeId,
+    operator_id: OperatorId,
+    source: IsolatedSource,
+    parameters: String,
+    /// The `config` of the operator as JSON object.
+    operator_config: String,
+    parent_addr: SocketAddr,
+    /// Applied to the thread of the child process that runs the operator.
+    scheduling: Option<OperatorScheduling>,
+}
+
+/// The operators that can be run in a child process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IsolatedSource {
+    SharedLibrary(String),
+    Python {
+        source: PythonSource,
+        batch_inputs: bool,
+    },
+}
+
+impl IsolatedSource {
+    /// Returns `None` if the operator type doesn't support isolation.
+    pub fn from_config(config: &OperatorConfig) -> Option<Self> {
+        match &config.source {
+            OperatorSource::SharedLibrary(source) => Some(Self::SharedLibrary(source.clone())),
+            OperatorSource::Python(source) => Some(Self::Python {
+                source: source.clone(),
+                batch_inputs: config.batch_inputs,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Sent from the runtime to the isolated operator.
+#[derive(Debug, Serialize, Deserialize)]
+enum ParentMessage {
+    Input {
+        id: DataId,
+        metadata: Metadata,
+        type_info: ArrowTypeInfo,
+        data: Vec<u8>,
+    },
+    InputClosed {
+        id: DataId,
+    },
+    Stop,
+    Error(String),
+    Timer {
+        id: String,
+    },
+}
+
+/// Sent from the isolated operator to the runtime.
+#[derive(Debug, Serialize, Deserialize)]
+enum ChildMessage {
+    InitDone(Result<(), String>),
+    Output {
+        output_id: DataId,
+        type_info: ArrowTypeInfo,
+        parameters: MetadataParameters,
+        data: Option<Vec<u8>>,
+    },
+    Error {
+        message: String,
+        /// Keeps structured errors, e.g. Python exceptions, intact.
+        structured: Option<OperatorError>,
+    },
+    Panic(String),
+    Finished(StopReason),
+    Log(NodeLogRecord),
+}
+
+/// Starts the given operator in a child process and forwards events and
+/// outputs until it exits.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    node_id: &NodeId,
+    operator_id: &OperatorId,
+    source: IsolatedSource,
+    python: Option<Vec<String>>,
+    events_tx: mpsc::Sender<OperatorEvent>,
+    incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
+    init_done: oneshot::Sender<Result<()>>,
+    parameters: String,
+    operator_config: String,
+    scheduling: Option<OperatorScheduling>,
+) -> eyre::Result<()> {
+    let listener =
+        TcpListener::bind(("127.0.0.1", 0)).wrap_err("failed to bind isolation socket")?;
+    let config = ChildConfig {
+        node_id: node_id.clone(),
+        operator_id: operator_id.clone(),
+        source,
+        parameters,
+        operator_config,
+        parent_addr: listener.local_addr()?,
+        scheduling,
+    };
+    let mut command = match python.as_deref() {
+        Some([program, args @ ..]) => {
+            let mut command = Command::new(program);
+            command.args(args).args([
+                "-c",
+                &format!("import dora; dora.start_runtime() # {node_id}/{operator_id}"),
+            ]);
+            command
+        }
+        _ => {
+            let mut command = Command::new(std::env::current_exe()?);
+            command.args(std::env::args_os().skip(1));
+            command
+        }
+    };
+    let mut child = command
+        .env(
+            ISOLATED_OPERATOR_ENV,
+            serde_json::to_string(&config).wrap_err("failed to serialize child config")?,
+        )
+        .spawn()
+        .wrap_err("failed to spawn isolated operator process")?;
+
+    let stream = match accept_child(&listener, &mut child) {
+        Ok(stream) => stream,
+        Err(err) => {
+            let _ = init_done.send(Err(eyre!("{err:?}")));
+            return Err(err);
+        }
+    };
+
+    let mut writer = stream.try_clone()?;
+    std::thread::spawn(move || {
+        while let Some(input) = next_input(&incoming_events, &timers) {
+            let event = match input {
+                OperatorInput::Event(event) => event,
+                OperatorInput::Timer(id) => {
+                    if let Err(err) = send_message(&mut writer, &ParentMessage::Timer { id }) {
+                        tracing::warn!("failed to forward timer to isolated operator: {err}");
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let message = match event {
+                Event::Input { id, metadata, data } => {
+                    let array = data.to_data();
+                    let mut sample: AVec<u8, ConstAlign<128>> =
+                        AVec::__from_elem(128, 0, required_data_size(&array));
+                    let type_info = copy_array_into_sample(&mut sample, &array);
+                    ParentMessage::Input {
+                        id,
+                        metadata,
+                        type_info,
+                        data: sample.to_vec(),
+                    }
+                }
+                Event::InputClosed { id } => ParentMessage::InputClosed { id },
+                Event::Stop => ParentMessage::Stop,
+                Event::Error(err) => ParentMessage::Error(err),
+                _ => continue,
+            };
+            if let Err(err) = send_message(&mut writer, &message) {
+                tracing::warn!("failed to forward event to isolated operator: {err}");
+                break;
+            }
+        }
+        // closing the socket signals the child that there are no more events
+        let _ = writer.shutdown(std::net::Shutdown::Write);
+    });
+
+    let mut init_done = Some(init_done);
+    let mut reader = stream;
+    loop {
+        let event = match receive_message(&mut reader) {
+            Ok(ChildMessage::InitDone(result)) => {
+                if let Some(init_done) = init_done.take() {
+                    let _ = init_done.send(result.map_err(|err| eyre!(err)));
+                }
+                continue;
+            }
+            Ok(ChildMessage::Output {
+                output_id,
+                type_info,
+                parameters,
+                data,
+            }) => OperatorEvent::Output {
+                output_id,
+                type_info,
+                parameters,
+                data: data.map(|d| AVec::<u8, ConstAlign<128>>::from_slice(128, &d).into()),
+            },
+            Ok(ChildMessage::Error {
+                message,
+                structured,
+            }) => OperatorEvent::Error(match structured {
+                Some(error) => eyre::Report::new(error),
+                None => eyre!(message),
+            }),
+            Ok(ChildMessage::Panic(payload)) => OperatorEvent::Panic(Box::new(payload)),
+            Ok(ChildMessage::Log(record)) => OperatorEvent::Log(record),
+            Ok(ChildMessage::Finished(reason)) => {
+                let _ = events_tx.blocking_send(OperatorEvent::Finished { reason });
+                break;
+            }
+            Err(err) => {
+                // the child exited without reporting a result, e.g. because it crashed
+                let status = child.wait().wrap_err("failed to wait for child process")?;
+                let err = eyre!(err).wrap_err(format!(
+                    "isolated operator process exited unexpectedly ({status})"
+                ));
+                match init_done.take() {
+                    Some(init_done) => {
+                        let _ = init_done.send(Err(err));
+                    }
+                    None => {
+                        let _ = events_tx.blocking_send(OperatorEvent::Error(err));
+                    }
+                }
+                return Ok(());
+            }
+        };
+        if events_tx.blocking_send(event).is_err() {
+            break;
+        }
+    }
+
+    child.wait().wrap_err("failed to wait for child process")?;
+    Ok(())
+}
+
+fn accept_child(listener: &TcpListener, child: &mut Child) -> eyre::Result<TcpStream> {
+    listener.set_nonblocking(true)?;
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                if let Some(status) = child.try_wait()? {
+                    bail!("isolated operator process exited before connecting ({status})");
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(err) => return Err(err).wrap_err("failed to accept isolated operator"),
+        }
+    }
+}
+
+/// Entry point of the child process.
+pub fn run_child(
+    config: &str,
+    dataflow_id: DataflowId,
+    dataflow_descriptor: &Descriptor,
+) -> eyre::Result<()> {
+    let ChildConfig {
+        node_id,
+        operator_id,
+        source,
+        parameters,
+        operator_config,
+        parent_addr,
+        scheduling,
+    } = serde_json::from_str(config).wrap_err("failed to deserialize child config")?;
+
+    let stream = TcpStream::connect(parent_addr).wrap_err("failed to connect to runtime")?;
+    stream.set_nodelay(true)?;
+    let mut reader = stream.try_clone()?;
+    let writer = Arc::new(Mutex::new(stream));
+
+    let (incoming_tx, incoming_events) = flume::bounded(0);
+    let (timers_tx, timers) = flume::bounded(1);
+    std::thread::spawn(move || loop {
+        let event = match receive_message(&mut reader) {
+            Ok(ParentMessage::Input {
+                id,
+                metadata,
+                type_info,
+                data,
+            }) => {
+                let data = RawData::Vec(AVec::from_slice(128, &data))
+                    .into_arrow_array(&type_info)
+                    .map(|data| ArrowData::from(make_array(data)));
+                match data {
+                    Ok(data) => Event::Input { id, metadata, data },
+                    Err(err) => Event::Error(format!("{err:?}")),
+                }
+            }
+            Ok(ParentMessage::InputClosed { id }) => Event::InputClosed { id },
+            Ok(ParentMessage::Stop) => Event::Stop,
+            Ok(ParentMessage::Error(err)) => Event::Error(err),
+            Ok(ParentMessage::Timer { id }) => {
+                // the parent already skips ticks while the operator is busy
+                let _ = timers_tx.try_send(id);
+                continue;
+            }
+            Err(_) => break,
+        };
+        if incoming_tx.send(event).is_err() {
+            break;
+        }
+    });
+
+    let (init_done_tx, init_done) = oneshot::channel::<eyre::Result<()>>();
+    let init_writer = writer.clone();
+    std::thread::spawn(move || {
+        let result = match init_done.blocking_recv() {
+            Ok(result) => result.map_err(|err| format!("{err:?}")),
+            Err(_) => Err("operator exited before initialization".to_owned()),
+        };
+        let _ = send_locked(&init_writer, &ChildMessage::InitDone(result));
+    });
+
+    let (events_tx, mut events) = mpsc::channel(1);
+    let forward_writer = writer.clone();
+    let forwarder = std::thread::spawn(move || {
+        while let Some(event) = events.blocking_recv() {
+            let message = match event {
+                OperatorEvent::Output {
+                    output_id,
+                    type_info,
+                    parameters,
+                    data,
+                } => ChildMessage::Output {
+                    output_id,
+                    type_info,
+                    parameters,
+                    data: data.map(|d| d.to_vec()),
+                },
+                OperatorEvent::AllocateOutputSample { len, sample } => {
+                    // outputs are copied to the parent anyway, so a plain
+                    // allocation is enough
+                    let _ = sample.send(Ok(AVec::__from_elem(128, 0, len).into()));
+                    continue;
+                }
+                OperatorEvent::Error(err) => ChildMessage::Error {
+                    message: format!("{err:?}"),
+                    structured: err
+                        .chain()
+                        .find_map(|cause| cause.downcast_ref::<OperatorError>())
+                        .cloned(),
+                },
+                OperatorEvent::Panic(payload) => ChildMessage::Panic(format!("{payload:?}")),
+                OperatorEvent::Finished { reason } => ChildMessage::Finished(reason),
+                OperatorEvent::Log(record) => ChildMessage::Log(record),
+                other => {
+                    tracing::warn!("isolated operator sent unsupported event {other:?}");
+                    continue;
+                }
+            };
+            if let Err(err) = send_locked(&forward_writer, &message) {
+                tracing::warn!("failed to send event to runtime: {err}");
+                break;
+            }
+        }
+    });
+
+    if let Some(scheduling) = &scheduling {
+        super::scheduling::apply(&operator_id, scheduling);
+    }
+    let callback_timer = CallbackTimer::default();
+    let worker_pool = WorkerPool::new(std::num::NonZeroUsize::MIN);
+    match source {
+        IsolatedSource::SharedLibrary(source) => shared_lib::run(
+            &node_id,
+            &operator_id,
+            &source,
+            events_tx,
+            incoming_events,
+            timers,
+            init_done_tx,
+            parameters,
+            operator_config,
+            callback_timer,
+            worker_pool,
+        )?,
+        #[cfg(feature = "python")]
+        IsolatedSource::Python {
+            source,
+            batch_inputs,
+        } => super::python::run(
+            &node_id,
+            dataflow_id,
+            &operator_id,
+            &source,
+            events_tx,
+            incoming_events,
+            timers,
+            init_done_tx,
+            dataflow_descriptor,
+            &serde_json::from_str(&parameters).wrap_err("failed to parse parameters")?,
+            &serde_json::from_str(&operator_config).wrap_err("failed to parse config")?,
+            batch_inputs,
+            callback_timer,
+            worker_pool,
+        )?,
+        #[cfg(not(feature = "python"))]
+        IsolatedSource::Python { .. } => {
+            let _ = (dataflow_id, dataflow_descriptor);
+            bail!("cannot run isolated Python operator without the `python` feature")
+        }
+    }
+    let _ = forwarder.join();
+    Ok(())
+}
+
+fn send_locked(writer: &Mutex<TcpStream>, message: &ChildMessage) -> io::Result<()> {
+    let mut writer = writer.lock().unwrap_or_else(|err| err.into_inner());
+    send_message(&mut *writer, message)
+}
+
+fn send_message(writer: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let serialized =
+        bincode::serialize(message).map_err(|err| io::Error::new(ErrorKind::Other, err))?;
+    writer.write_all(&(serialized.len() as u64).to_le_bytes())?;
+    writer.write_all(&serialized)?;
+    writer.flush()
+}
+
+fn receive_message<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<T> {
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    let mut buf = vec![0; u64::from_le_bytes(len) as usize];
+    reader.read_exact(&mut buf)?;
+    bincode::deserialize(&buf).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
@@ -0,0 +1,491 @@
+//! Runs operators that are written in Julia.
+//!
+//! The runtime embeds Julia by loading `libjulia` when the first Julia operator
+//! starts, so Julia only needs to be installed on machines that actually run
+//! Julia operators. The library path is read from the `DORA_JULIA_LIB`
+//! environment variable or, if that is not set, queried from the `julia`
+//! executable in `PATH`.
+//!
+//! ## Operator interface
+//!
+//! Each operator source file is loaded into its own module and must define an
+//! `on_input(id::String, data::Vector{UInt8}, send_output)` function. The
+//! `data` vector is only valid during the call, so operators need to `copy` it
+//! to keep it around. Calling `send_output(id, data::AbstractVector{UInt8})`
+//! sends the given bytes as a `UInt8` array.
+//!
+//! The following functions are optional:
+//!
+//! - `on_input_closed(id::String)`
+//! - `on_timer(id::String, send_output)`, called for the operator's timers
+//! - `on_stop()`, called when the dataflow is stopped
+//!
+//! All functions return `nothing` or `:continue` to continue, `:stop` to stop
+//! the operator, or `:stop_all` to stop the whole dataflow. Exceptions are
+//! reported as operator errors.
+//!
+//! Like for WASM operators, inputs are passed as raw bytes, so only `UInt8`
+//! arrays are supported as input data.
+//!
+//! Julia can only be initialized once per process and must only be called from
+//! the thread that initialized it, so a runtime node supports at most one Julia
+//! operator.
+
+use super::{
+    timers::next_input, watchdog::CallbackTimer, worker_pool::WorkerPool, OperatorEvent,
+    OperatorInput, StopReason,
+};
+use aligned_vec::{AVec, ConstAlign};
+use arrow::array::{Array, UInt8Array};
+use dora_core::{
+    config::{DataId, NodeId, OperatorId},
+    descriptor::source_is_url,
+};
+use dora_download::download_file;
+use dora_node_api::{
+    arrow_utils::{copy_array_into_sample, required_data_size},
+    Event, MetadataParameters,
+};
+use eyre::{bail, eyre, Context, Result};
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+    thread::ThreadId,
+};
+use tokio::sync::{mpsc::Sender, oneshot};
+
+const EVENT_INPUT: c_int = 0;
+const EVENT_INPUT_CLOSED: c_int = 1;
+const EVENT_STOP: c_int = 2;
+const EVENT_TIMER: c_int = 3;
+
+/// Julia code that is evaluated once after initializing Julia.
+///
+/// It provides the functions that the runtime calls through `@cfunction`
+/// pointers, so that no Julia API beyond `jl_eval_string` is needed.
+const RUNTIME_MODULE: &str = r#"
+module DoraRuntime
+
+const OPERATORS = Module[]
+const LAST_ERROR = Ref{String}("")
+const SEND_OUTPUT = Ref{Ptr{Cvoid}}(C_NULL)
+
+last_error_ptr() = pointer(LAST_ERROR[])
+
+function set_error(err, backtrace)
+    LAST_ERROR[] = sprint(showerror, err, backtrace)
+end
+
+function load(path_ptr::Cstring)::Cint
+    try
+        path = unsafe_string(path_ptr)
+        mod = Module(:DoraOperator)
+        Core.eval(mod, :(include(path) = Base.include($mod, path)))
+        Base.include(mod, path)
+        isdefined(mod, :on_input) || error("`$path` does not define an `on_input` function")
+        push!(OPERATORS, mod)
+        return Cint(length(OPERATORS))
+    catch err
+        set_error(err, catch_backtrace())
+        return Cint(-1)
+    end
+end
+
+function send_output(context::Ptr{Cvoid}, id::AbstractString, data::AbstractVector{UInt8})
+    bytes = Vector{UInt8}(data)
+    result = ccall(
+        SEND_OUTPUT[], Cint, (Ptr{Cvoid}, Cstring, Ptr{UInt8}, Csize_t),
+        context, id, bytes, length(bytes),
+    )
+    result == 0 || error("failed to send output `$id`")
+    nothing
+end
+
+function status_code(status)
+    (status === nothing || status === :continue) && return Cint(0)
+    status === :stop && return Cint(1)
+    status === :stop_all && return Cint(2)
+    error("invalid operator status `$status`, expected `:continue`, `:stop`, or `:stop_all`")
+end
+
+call_optional(mod, name, args...) =
+    isdefined(mod, name) ? Base.invokelatest(getfield(mod, name), args...) : nothing
+
+function on_event(
+    operator::Cint, kind::Cint, id_ptr::Cstring, data::Ptr{UInt8}, len::Csize_t, context::Ptr{Cvoid},
+)::Cint
+    try
+        mod = OPERATORS[operator]
+        id = unsafe_string(id_ptr)
+        send = (output, bytes) -> send_output(context, output, bytes)
+        status = if kind == 0
+            input = len == 0 ? UInt8[] : unsafe_wrap(Array, data, len)
+            Base.invokelatest(mod.on_input, id, input, send)
+        elseif kind == 1
+            call_optional(mod, :on_input_closed, id)
+        elseif kind == 2
+            call_optional(mod, :on_stop)
+        else
+            call_optional(mod, :on_timer, id, send)
+        end
+        return status_code(status)
+    catch err
+        set_error(err, catch_backtrace())
+        return Cint(-1)
+    end
+end
+
+load_ptr() = @cfunction(load, Cint, (Cstring,))
+on_event_ptr() = @cfunction(
+    on_event, Cint, (Cint, Cint, Cstring, Ptr{UInt8}, Csize_t, Ptr{Cvoid}),
+)
+
+end
+"#;
+
+type OnEventFn = unsafe extern "C" fn(
+    operator: c_int,
+    kind: c_int,
+    id: *const c_char,
+    data: *const u8,
+    len: usize,
+    context: *const c_void,
+) -> c_int;
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    node_id: &NodeId,
+    operator_id: &OperatorId,
+    source: &str,
+    events_tx: Sender<OperatorEvent>,
+    incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
+    init_done: oneshot::Sender<Result<()>>,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
+) -> eyre::Result<()> {
+    let path = if source_is_url(source) {
+        let target_path = Path::new("build")
+            .join(node_id.to_string())
+            .join(format!("{operator_id}.jl"));
+        // try to download the Julia source file
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        rt.block_on(download_file(source, &target_path))
+            .wrap_err("failed to download Julia operator")?;
+        target_path
+    } else {
+        PathBuf::from(source)
+    };
+
+    let operator = match JuliaOperator::load(&path) {
+        Ok(operator) => operator,
+        Err(err) => {
+            let _ = init_done.send(Err(eyre!("{err:?}")));
+            return Err(err);
+        }
+    };
+    let _ = init_done.send(Ok(()));
+
+    match operator.run(
+        &events_tx,
+        incoming_events,
+        timers,
+        callback_timer,
+        worker_pool,
+    ) {
+        Ok(reason) => {
+            let _ = events_tx.blocking_send(OperatorEvent::Finished { reason });
+        }
+        Err(err) => {
+            let _ = events_tx.blocking_send(OperatorEvent::Error(err));
+        }
+    }
+
+    Ok(())
+}
+
+/// The embedded Julia runtime, initialized by the first Julia operator.
+struct Julia {
+    thread: ThreadId,
+    api: JuliaApi,
+    load: unsafe extern "C" fn(*const c_char) -> c_int,
+    on_event: OnEventFn,
+}
+
+impl Julia {
+    fn get() -> eyre::Result<&'static Self> {
+        static JULIA: OnceLock<Result<Julia, String>> = OnceLock::new();
+        let julia = JULIA
+            .get_or_init(|| Self::init().map_err(|err| format!("{err:?}")))
+            .as_ref()
+            .map_err(|err| eyre!("failed to initialize Julia: {err}"))?;
+        if julia.thread != std::thread::current().id() {
+            bail!("only one Julia operator is supported per runtime node");
+        }
+        Ok(julia)
+    }
+
+    fn init() -> eyre::Result<Self> {
+        let path = match std::env::var_os("DORA_JULIA_LIB") {
+            Some(path) => PathBuf::from(path),
+            None => find_libjulia()?,
+        };
+        let library = load_library(&path)
+            .wrap_err_with(|| format!("failed to load libjulia from `{}`", path.display()))?;
+        // Julia can't be shut down and reinitialized, so keep it loaded forever
+        let library: &'static libloading::Library = Box::leak(Box::new(library));
+
+        let api = unsafe {
+            let init: libloading::Symbol<unsafe extern "C" fn()> = library.get(b"jl_init\0")?;
+            init();
+            JuliaApi {
+                eval_string: *library.get(b"jl_eval_string\0")?,
+                unbox_voidptr: *library.get(b"jl_unbox_voidptr\0")?,
+            }
+        };
+        api.eval(RUNTIME_MODULE)?;
+        api.eval(&format!(
+            "DoraRuntime.SEND_OUTPUT[] = Ptr{{Cvoid}}(UInt({}))",
+            send_output as usize
+        ))?;
+        let load = api.eval_ptr("DoraRuntime.load_ptr()")?;
+        let on_event = api.eval_ptr("DoraRuntime.on_event_ptr()")?;
+
+        Ok(Self {
+            thread: std::thread::current().id(),
+            api,
+            // SAFETY: the `@cfunction` signatures in `RUNTIME_MODULE` match
+            load: unsafe { std::mem::transmute(load) },
+            on_event: unsafe { std::mem::transmute(on_event) },
+        })
+    }
+}
+
+/// The parts of the Julia C API that the runtime uses.
+struct JuliaApi {
+    eval_string: unsafe extern "C" fn(*const c_char) -> *mut c_void,
+    unbox_voidptr: unsafe extern "C" fn(*mut c_void) -> *mut c_void,
+}
+
+impl JuliaApi {
+    /// Evaluates the given Julia code in the `Main` module.
+    fn eval(&self, code: &str) -> eyre::Result<*mut c_void> {
+        let code = CString::new(code)?;
+        let value = unsafe { (self.eval_string)(code.as_ptr()) };
+        if value.is_null() {
+            bail!("failed to evaluate Julia code: {}", self.last_error());
+        }
+        Ok(value)
+    }
+
+    fn eval_ptr(&self, code: &str) -> eyre::Result<*mut c_void> {
+        let value = self.eval(code)?;
+        Ok(unsafe { (self.unbox_voidptr)(value) })
+    }
+
+    /// Returns the last error that was caught by the `DoraRuntime` module.
+    fn last_error(&self) -> String {
+        let code = b"DoraRuntime.last_error_ptr()\0";
+        let value = unsafe { (self.eval_string)(code.as_ptr().cast()) };
+        if value.is_null() {
+            return "unknown error".into();
+        }
+        let ptr = unsafe { (self.unbox_voidptr)(value) };
+        unsafe { CStr::from_ptr(ptr.cast()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+struct JuliaOperator {
+    julia: &'static Julia,
+    index: c_int,
+}
+
+impl JuliaOperator {
+    fn load(path: &Path) -> eyre::Result<Self> {
+        let julia = Julia::get()?;
+        let path_str = CString::new(path.as_os_str().as_encoded_bytes())
+            .wrap_err("invalid Julia operator path")?;
+        let index = unsafe { (julia.load)(path_str.as_ptr()) };
+        if index < 0 {
+            bail!(
+                "failed to load Julia operator at `{}`: {}",
+                path.display(),
+                julia.api.last_error()
+            );
+        }
+        Ok(Self { julia, index })
+    }
+
+    fn run(
+        self,
+        events_tx: &Sender<OperatorEvent>,
+        incoming_events: flume::Receiver<Event>,
+        timers: flume::Receiver<String>,
+        callback_timer: CallbackTimer,
+        worker_pool: WorkerPool,
+    ) -> eyre::Result<StopReason> {
+        let reason = loop {
+            let event = match next_input(&incoming_events, &timers) {
+                Some(OperatorInput::Event(event)) => event,
+                Some(OperatorInput::Timer(timer_id)) => {
+                    let _worker = worker_pool.acquire();
+                    let _running = callback_timer.start();
+                    match self.call_on_event(events_tx, EVENT_TIMER, &timer_id, &[])? {
+                        Some(reason) => break reason,
+                        None => continue,
+                    }
+                }
+                None => break StopReason::InputsClosed,
+            };
+
+            let _worker = worker_pool.acquire();
+            let _running = callback_timer.start();
+            let reason = match event {
+                Event::Stop => self.call_on_event(events_tx, EVENT_STOP, "", &[])?,
+                Event::Input { id, data, .. } => {
+                    let bytes: &[u8] = if data.is_empty() {
+                        &[]
+                    } else {
+                        match (&data).try_into() {
+                            Ok(bytes) => bytes,
+                            Err(err) => {
+                                tracing::warn!(
+                                    "ignoring input `{id}` because Julia operators only support \
+                                    UInt8 arrays: {err}"
+                                );
+                                continue;
+                            }
+                        }
+                    };
+                    self.call_on_event(events_tx, EVENT_INPUT, id.as_str(), bytes)?
+                }
+                Event::InputClosed { id } => {
+                    self.call_on_event(events_tx, EVENT_INPUT_CLOSED, id.as_str(), &[])?
+                }
+                Event::Reload { .. } => {
+                    // reloading Julia operators is not supported
+                    continue;
+                }
+                Event::Error(err) => {
+                    tracing::warn!("received error event: {err}");
+                    continue;
+                }
+                other => {
+                    tracing::warn!("unexpected event: {other:?}");
+                    continue;
+                }
+            };
+
+            if let Some(reason) = reason {
+                break reason;
+            }
+        };
+        Ok(reason)
+    }
+
+    fn call_on_event(
+        &self,
+        events_tx: &Sender<OperatorEvent>,
+        kind: c_int,
+        id: &str,
+        data: &[u8],
+    ) -> eyre::Result<Option<StopReason>> {
+        let id = CString::new(id)?;
+        let context: *const Sender<OperatorEvent> = events_tx;
+        let status = unsafe {
+            (self.julia.on_event)(
+                self.index,
+                kind,
+                id.as_ptr(),
+                data.as_ptr(),
+                data.len(),
+                context.cast(),
+            )
+        };
+        match status {
+            0 => Ok(None),
+            1 => Ok(Some(StopReason::ExplicitStop)),
+            2 => Ok(Some(StopReason::ExplicitStopAll)),
+            _ => bail!("Julia operator failed: {}", self.julia.api.last_error()),
+        }
+    }
+}
+
+/// Asks the `julia` executable for the path of `libjulia`.
+fn find_libjulia() -> eyre::Result<PathBuf> {
+    let output = Command::new("julia")
+        .args([
+            "--startup-file=no",
+            "-e",
+            r#"import Libdl; print(Libdl.dlpath("libjulia"))"#,
+        ])
+        .output()
+        .wrap_err(
+            "failed to run `julia` to locate libjulia, \
+            set `DORA_JULIA_LIB` to the path of libjulia instead",
+        )?;
+    if !output.status.success() {
+        bail!(
+            "failed to locate libjulia: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+}
+
+fn load_library(path: &Path) -> Result<libloading::Library, libloading::Error> {
+    // libjulia loads its internal libraries itself, which requires its symbols
+    // to be globally visible
+    #[cfg(unix)]
+    let library = unsafe {
+        libloading::os::unix::Library::open(
+            Some(path),
+            libloading::os::unix::RTLD_NOW | libloading::os::unix::RTLD_GLOBAL,
+        )
+        .map(Into::into)
+    };
+    #[cfg(not(unix))]
+    let library = unsafe { libloading::Library::new(path) };
+    library
+}
+
+/// Called by the `DoraRuntime` Julia module to send an operator output.
+unsafe extern "C" fn send_output(
+    context: *const c_void,
+    id: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    let events_tx = unsafe { &*context.cast::<Sender<OperatorEvent>>() };
+    let Ok(output_id) = unsafe { CStr::from_ptr(id) }.to_str() else {
+        tracing::warn!("Julia operator passed invalid output ID");
+        return -1;
+    };
+    let data = if len == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(data, len) }
+    };
+
+    let array = UInt8Array::from(data.to_vec()).into_data();
+    let mut sample: AVec<u8, ConstAlign<128>> =
+        AVec::__from_elem(128, 0, required_data_size(&array));
+    let type_info = copy_array_into_sample(&mut sample, &array);
+
+    let event = OperatorEvent::Output {
+        output_id: DataId::from(output_id.to_owned()),
+        type_info,
+        parameters: MetadataParameters::default(),
+        data: Some(sample.into()),
+    };
+    match events_tx.blocking_send(event) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
@@ -0,0 +1,353 @@
+//! Runs operators that are written in Lua.
+//!
+//! Lua operators are meant for small glue logic such as unit conversions,
+//! renaming outputs, or thresholding, which would not justify starting a Python
+//! interpreter. The code can be given inline in the dataflow descriptor or as
+//! the path of a `.lua` file:
+//!
+//! ```yaml
+//! - id: to-celsius
+//!   lua: |
+//!     function on_input(id, value)
+//!       dora.send_output("celsius", (value - 32) * 5 / 9)
+//!     end
+//!   inputs:
+//!     fahrenheit: sensor/temperature
+//!   outputs:
+//!     - celsius
+//! ```
+//!
+//! ## Operator interface
+//!
+//! The script must define an `on_input(id, value)` function. The following
+//! functions are optional:
+//!
+//! - `on_input_closed(id)`
+//! - `on_timer(id)`, called for the operator's timers
+//! - `on_stop()`, called when the dataflow is stopped
+//!
+//! All functions return `nil` or `"continue"` to continue, `"stop"` to stop the
+//! operator, or `"stop_all"` to stop the whole dataflow. Lua errors are reported
+//! as operator errors.
+//!
+//! The runtime provides a global `dora` table with a `send_output(id, value)`
+//! function and the operator `parameters` of the descriptor.
+//!
+//! ## Values
+//!
+//! Inputs of boolean, integer, floating point, and string types are converted
+//! to the corresponding Lua values. Arrays with a single element are passed as
+//! a scalar value, all other arrays as a sequence table. Empty inputs are passed
+//! as `nil`. Outputs are converted the other way around: scalars become arrays
+//! with a single element, sequence tables become arrays of their elements, and
+//! `nil` is sent as an empty output.
+
+use super::{
+    timers::next_input, watchdog::CallbackTimer, worker_pool::WorkerPool, OperatorEvent,
+    OperatorInput, StopReason,
+};
+use aligned_vec::{AVec, ConstAlign};
+use arrow::{
+    array::{
+        Array, ArrayRef, AsArray, BooleanArray, Float64Array, Int64Array, NullArray, StringArray,
+    },
+    datatypes::{DataType, Float64Type, Int64Type},
+};
+use dora_core::{
+    config::{DataId, NodeId, OperatorId},
+    descriptor::lua_source_path,
+};
+use dora_node_api::{
+    arrow_utils::{copy_array_into_sample, required_data_size},
+    Event, MetadataParameters,
+};
+use eyre::{bail, eyre, Context, Result};
+use mlua::{Function, Lua, LuaSerdeExt, Value};
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::sync::{mpsc::Sender, oneshot};
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    node_id: &NodeId,
+    operator_id: &OperatorId,
+    source: &str,
+    events_tx: Sender<OperatorEvent>,
+    incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
+    init_done: oneshot::Sender<Result<()>>,
+    parameters: &BTreeMap<String, serde_json::Value>,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
+) -> eyre::Result<()> {
+    let operator = match LuaOperator::load(node_id, operator_id, source, parameters, &events_tx) {
+        Ok(operator) => operator,
+        Err(err) => {
+            let _ = init_done.send(Err(eyre!("{err:?}")));
+            return Err(err);
+        }
+    };
+    let _ = init_done.send(Ok(()));
+
+    match operator.run(incoming_events, timers, callback_timer, worker_pool) {
+        Ok(reason) => {
+            let _ = events_tx.blocking_send(OperatorEvent::Finished { reason });
+        }
+        Err(err) => {
+            let _ = events_tx.blocking_send(OperatorEvent::Error(err));
+        }
+    }
+
+    Ok(())
+}
+
+struct LuaOperator {
+    lua: Lua,
+}
+
+impl LuaOperator {
+    fn load(
+        node_id: &NodeId,
+        operator_id: &OperatorId,
+        source: &str,
+        parameters: &BTreeMap<String, serde_json::Value>,
+        events_tx: &Sender<OperatorEvent>,
+    ) -> eyre::Result<Self> {
+        let (code, name) = match lua_source_path(source) {
+            Some(path) => (
+                std::fs::read_to_string(path)
+                    .wrap_err_with(|| format!("failed to read Lua operator at `{path}`"))?,
+                path.to_owned(),
+            ),
+            None => (source.to_owned(), format!("{node_id}/{operator_id}")),
+        };
+
+        let lua = Lua::new();
+        let dora = lua.create_table().map_err(lua_error)?;
+        let events_tx = events_tx.clone();
+        let send_output = lua
+            .create_function(move |_, (output_id, value): (String, Value)| {
+                send_output(&events_tx, output_id, value)
+                    .map_err(|err| mlua::Error::RuntimeError(format!("{err:?}")))
+            })
+            .map_err(lua_error)?;
+        dora.set("send_output", send_output).map_err(lua_error)?;
+        dora.set("parameters", lua.to_value(parameters).map_err(lua_error)?)
+            .map_err(lua_error)?;
+        lua.globals().set("dora", dora).map_err(lua_error)?;
+
+        lua.load(code.as_str())
+            .set_name(name)
+            .exec()
+            .map_err(lua_error)
+            .wrap_err("failed to load Lua operator")?;
+        if lua.globals().get::<_, Function>("on_input").is_err() {
+            bail!("Lua operator does not define an `on_input` function");
+        }
+
+        Ok(Self { lua })
+    }
+
+    fn run(
+        self,
+        incoming_events: flume::Receiver<Event>,
+        timers: flume::Receiver<String>,
+        callback_timer: CallbackTimer,
+        worker_pool: WorkerPool,
+    ) -> eyre::Result<StopReason> {
+        let reason = loop {
+            let event = match next_input(&incoming_events, &timers) {
+                Some(OperatorInput::Event(event)) => event,
+                Some(OperatorInput::Timer(timer_id)) => {
+                    let _worker = worker_pool.acquire();
+                    let _running = callback_timer.start();
+                    match self.call("on_timer", timer_id)? {
+                        Some(reason) => break reason,
+                        None => continue,
+                    }
+                }
+                None => break StopReason::InputsClosed,
+            };
+
+            let _worker = worker_pool.acquire();
+            let _running = callback_timer.start();
+            let reason = match event {
+                Event::Stop => self.call("on_stop", ())?,
+                Event::Input { id, data, .. } => {
+                    let value = match array_to_lua(&self.lua, data.as_ref()) {
+                        Ok(value) => value,
+                        Err(err) => {
+                            tracing::warn!("ignoring input `{id}`: {err}");
+                            continue;
+                        }
+                    };
+                    self.call("on_input", (id.to_string(), value))?
+                }
+                Event::InputClosed { id } => self.call("on_input_closed", id.to_string())?,
+                Event::Reload { .. } => {
+                    // reloading Lua operators is not supported
+                    continue;
+                }
+                Event::Error(err) => {
+                    tracing::warn!("received error event: {err}");
+                    continue;
+                }
+                other => {
+                    tracing::warn!("unexpected event: {other:?}");
+                    continue;
+                }
+            };
+
+            if let Some(reason) = reason {
+                break reason;
+            }
+        };
+        Ok(reason)
+    }
+
+    /// Calls the given global function, if it's defined.
+    fn call<'lua>(
+        &'lua self,
+        function: &str,
+        args: impl mlua::IntoLuaMulti<'lua>,
+    ) -> eyre::Result<Option<StopReason>> {
+        let Ok(f) = self.lua.globals().get::<_, Function>(function) else {
+            return Ok(None);
+        };
+        let status: Value = f
+            .call(args)
+            .map_err(lua_error)
+            .wrap_err_with(|| format!("Lua operator failed in `{function}`"))?;
+        match status {
+            Value::Nil => Ok(None),
+            Value::String(s) => match s.to_str().map_err(lua_error)? {
+                "continue" => Ok(None),
+                "stop" => Ok(Some(StopReason::ExplicitStop)),
+                "stop_all" => Ok(Some(StopReason::ExplicitStopAll)),
+                other => bail!("`{function}` returned unknown status `{other}`"),
+            },
+            other => bail!("`{function}` returned unexpected {}", other.type_name()),
+        }
+    }
+}
+
+fn lua_error(err: mlua::Error) -> eyre::Report {
+    eyre!("{err}")
+}
+
+fn array_to_lua<'lua>(lua: &'lua Lua, array: &dyn Array) -> eyre::Result<Value<'lua>> {
+    let mut values = Vec::with_capacity(array.len());
+    match array.data_type() {
+        DataType::Null => return Ok(Value::Nil),
+        DataType::Boolean => {
+            values.extend(
+                array
+                    .as_boolean()
+                    .iter()
+                    .map(|v| v.map_or(Value::Nil, Value::Boolean)),
+            );
+        }
+        DataType::Utf8 => {
+            for v in array.as_string::<i32>() {
+                values.push(string_value(lua, v)?);
+            }
+        }
+        DataType::LargeUtf8 => {
+            for v in array.as_string::<i64>() {
+                values.push(string_value(lua, v)?);
+            }
+        }
+        data_type if data_type.is_integer() => {
+            let array = arrow::compute::cast(array, &DataType::Int64)?;
+            values.extend(
+                array
+                    .as_primitive::<Int64Type>()
+                    .iter()
+                    .map(|v| v.map_or(Value::Nil, Value::Integer)),
+            );
+        }
+        data_type if data_type.is_floating() => {
+            let array = arrow::compute::cast(array, &DataType::Float64)?;
+            values.extend(
+                array
+                    .as_primitive::<Float64Type>()
+                    .iter()
+                    .map(|v| v.map_or(Value::Nil, Value::Number)),
+            );
+        }
+        other => bail!("unsupported data type `{other}`"),
+    }
+
+    match values.len() {
+        0 => Ok(Value::Nil),
+        1 => Ok(values.remove(0)),
+        _ => Ok(Value::Table(
+            lua.create_sequence_from(values).map_err(lua_error)?,
+        )),
+    }
+}
+
+fn string_value<'lua>(lua: &'lua Lua, value: Option<&str>) -> eyre::Result<Value<'lua>> {
+    match value {
+        Some(s) => Ok(Value::String(lua.create_string(s).map_err(lua_error)?)),
+        None => Ok(Value::Nil),
+    }
+}
+
+fn lua_to_array(value: Value) -> eyre::Result<ArrayRef> {
+    let values = match value {
+        Value::Nil => return Ok(Arc::new(NullArray::new(0))),
+        Value::Table(table) => table
+            .sequence_values()
+            .collect::<mlua::Result<Vec<Value>>>()
+            .map_err(lua_error)?,
+        other => vec![other],
+    };
+
+    let array: ArrayRef = if let Some(values) = collect(&values, |v| match v {
+        Value::Integer(i) => Some(*i),
+        _ => None,
+    }) {
+        Arc::new(Int64Array::from(values))
+    } else if let Some(values) = collect(&values, |v| match v {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Number(n) => Some(*n),
+        _ => None,
+    }) {
+        Arc::new(Float64Array::from(values))
+    } else if let Some(values) = collect(&values, |v| match v {
+        Value::Boolean(b) => Some(*b),
+        _ => None,
+    }) {
+        Arc::new(BooleanArray::from(values))
+    } else if let Some(values) = collect(&values, |v| match v {
+        Value::String(s) => s.to_str().ok().map(str::to_owned),
+        _ => None,
+    }) {
+        Arc::new(StringArray::from(values))
+    } else {
+        bail!("outputs must be numbers, booleans, UTF-8 strings, or sequence tables of one of them")
+    };
+    Ok(array)
+}
+
+/// Converts all values, or returns `None` if one of them has a different type.
+fn collect<T>(values: &[Value], convert: impl Fn(&Value) -> Option<T>) -> Option<Vec<T>> {
+    values.iter().map(convert).collect()
+}
+
+fn send_output(events_tx: &Sender<OperatorEvent>, output_id: String, value: Value) -> Result<()> {
+    let array = lua_to_array(value)?.to_data();
+    let mut sample: AVec<u8, ConstAlign<128>> =
+        AVec::__from_elem(128, 0, required_data_size(&array));
+    let type_info = copy_array_into_sample(&mut sample, &array);
+
+    let event = OperatorEvent::Output {
+        output_id: DataId::from(output_id),
+        type_info,
+        parameters: MetadataParameters::default(),
+        data: Some(sample.into()),
+    };
+    events_tx
+        .blocking_send(event)
+        .map_err(|_| eyre!("failed to send output to runtime"))
+}
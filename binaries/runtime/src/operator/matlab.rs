@@ -0,0 +1,563 @@
+//! Runs operators that are written in MATLAB.
+//!
+//! Each MATLAB operator starts its own MATLAB session through the MATLAB
+//! Engine C API. The `libeng` and `libmx` libraries are loaded when the first
+//! MATLAB operator starts, so MATLAB only needs to be installed on machines
+//! that actually run MATLAB operators. They are loaded from the `bin/<arch>`
+//! directory of the MATLAB installation, which is read from the
+//! `DORA_MATLAB_ROOT` environment variable or, if that is not set, queried
+//! from the `matlab` executable in `PATH`.
+//!
+//! ## Operator interface
+//!
+//! The operator source is a MATLAB function file, e.g. `controller.m`, that
+//! defines a function of the same name:
+//!
+//! ```matlab
+//! function [outputs, status] = controller(event, id, data)
+//!     outputs = struct();
+//!     if strcmp(event, 'input') && strcmp(id, 'error')
+//!         outputs.command = -0.5 * data;
+//!     end
+//! end
+//! ```
+//!
+//! The `event` argument is one of `'input'`, `'input_closed'`, `'timer'`, or
+//! `'stop'`, and `id` is the ID of the input or timer. For inputs, `data` is a
+//! column vector of doubles, so only numeric inputs are supported. Other
+//! events pass an empty `data` vector.
+//!
+//! Fields of the returned `outputs` struct that match one of the declared
+//! outputs of the operator are sent as `Float64` arrays. The optional `status`
+//! is `'continue'`, `'stop'` to stop the operator, or `'stop_all'` to stop
+//! the whole dataflow. Both return values may be omitted. MATLAB errors are
+//! reported as operator errors.
+//!
+//! Variables of the session survive between calls, e.g. `persistent`
+//! variables of the operator function.
+//!
+//! ## Simulink
+//!
+//! Simulink models are run from the operator function, e.g. by copying the
+//! inputs into the workspace and calling `sim` for one step, or through a
+//! `Simulink.SimulationInput` that maps the inputs to root inport signals.
+//! Models that were compiled to C through Simulink Coder don't need a MATLAB
+//! session at all: the generated `<model>_step` function can be called from a
+//! C operator, see `apis/c/operator`.
+
+use super::{
+    timers::next_input, watchdog::CallbackTimer, worker_pool::WorkerPool, OperatorEvent,
+    OperatorInput, StopReason,
+};
+use aligned_vec::{AVec, ConstAlign};
+use arrow::{
+    array::{Array, AsArray, Float64Array},
+    datatypes::{DataType, Float64Type},
+};
+use dora_core::{
+    config::{DataId, NodeId, OperatorId},
+    descriptor::source_is_url,
+};
+use dora_download::download_file;
+use dora_node_api::{
+    arrow_utils::{copy_array_into_sample, required_data_size},
+    Event, MetadataParameters,
+};
+use eyre::{bail, eyre, Context, Result};
+use std::{
+    collections::BTreeSet,
+    ffi::{c_char, c_int, c_void, CString},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+use tokio::sync::{mpsc::Sender, oneshot};
+
+/// Size of the buffer that captures the MATLAB output of each evaluation.
+const OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    node_id: &NodeId,
+    operator_id: &OperatorId,
+    source: &str,
+    outputs: &BTreeSet<DataId>,
+    events_tx: Sender<OperatorEvent>,
+    incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
+    init_done: oneshot::Sender<Result<()>>,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
+) -> eyre::Result<()> {
+    let path = if source_is_url(source) {
+        let file_name = Path::new(source)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.ends_with(".m"))
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| format!("{operator_id}.m"));
+        let target_path = Path::new("build").join(node_id.to_string()).join(file_name);
+        // try to download the MATLAB source file
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        rt.block_on(download_file(source, &target_path))
+            .wrap_err("failed to download MATLAB operator")?;
+        target_path
+    } else {
+        PathBuf::from(source)
+    };
+
+    let operator = match MatlabOperator::load(&path, outputs) {
+        Ok(operator) => operator,
+        Err(err) => {
+            let _ = init_done.send(Err(eyre!("{err:?}")));
+            return Err(err);
+        }
+    };
+    let _ = init_done.send(Ok(()));
+
+    match operator.run(
+        &events_tx,
+        incoming_events,
+        timers,
+        callback_timer,
+        worker_pool,
+    ) {
+        Ok(reason) => {
+            let _ = events_tx.blocking_send(OperatorEvent::Finished { reason });
+        }
+        Err(err) => {
+            let _ = events_tx.blocking_send(OperatorEvent::Error(err));
+        }
+    }
+
+    Ok(())
+}
+
+/// Opaque `Engine` pointer of the MATLAB Engine API.
+type Engine = *mut c_void;
+/// Opaque `mxArray` pointer of the MATLAB Matrix API.
+type MxArray = *mut c_void;
+
+/// The parts of the MATLAB Engine and Matrix C APIs that the runtime uses.
+struct MatlabApi {
+    eng_open: unsafe extern "C" fn(*const c_char) -> Engine,
+    eng_close: unsafe extern "C" fn(Engine) -> c_int,
+    eng_eval_string: unsafe extern "C" fn(Engine, *const c_char) -> c_int,
+    eng_output_buffer: unsafe extern "C" fn(Engine, *mut c_char, c_int) -> c_int,
+    eng_put_variable: unsafe extern "C" fn(Engine, *const c_char, MxArray) -> c_int,
+    eng_get_variable: unsafe extern "C" fn(Engine, *const c_char) -> MxArray,
+    mx_create_double_matrix: unsafe extern "C" fn(usize, usize, c_int) -> MxArray,
+    mx_get_pr: unsafe extern "C" fn(MxArray) -> *mut f64,
+    mx_get_number_of_elements: unsafe extern "C" fn(MxArray) -> usize,
+    mx_destroy_array: unsafe extern "C" fn(MxArray),
+}
+
+impl MatlabApi {
+    fn get() -> eyre::Result<&'static Self> {
+        static API: OnceLock<Result<MatlabApi, String>> = OnceLock::new();
+        API.get_or_init(|| Self::load().map_err(|err| format!("{err:?}")))
+            .as_ref()
+            .map_err(|err| eyre!("failed to load the MATLAB engine libraries: {err}"))
+    }
+
+    fn load() -> eyre::Result<Self> {
+        let root = match std::env::var_os("DORA_MATLAB_ROOT") {
+            Some(root) => PathBuf::from(root),
+            None => find_matlab_root()?,
+        };
+        let arch = arch_dir().ok_or_else(|| eyre!("MATLAB is not supported on this platform"))?;
+        let lib_dir = root.join("bin").join(arch);
+        let load = |name: &str| {
+            // MATLAB uses the `lib` prefix on Windows too
+            let path = lib_dir.join(format!("{name}{}", std::env::consts::DLL_SUFFIX));
+            let library = unsafe { libloading::Library::new(&path) }
+                .wrap_err_with(|| format!("failed to load `{}`", path.display()))?;
+            // the engine API keeps internal state, so keep the libraries loaded
+            eyre::Ok(&*Box::leak(Box::new(library)))
+        };
+        let eng = load("libeng")?;
+        let mx = load("libmx")?;
+
+        unsafe {
+            Ok(Self {
+                eng_open: *eng.get(b"engOpen\0")?,
+                eng_close: *eng.get(b"engClose\0")?,
+                eng_eval_string: *eng.get(b"engEvalString\0")?,
+                eng_output_buffer: *eng.get(b"engOutputBuffer\0")?,
+                eng_put_variable: *eng.get(b"engPutVariable\0")?,
+                eng_get_variable: *eng.get(b"engGetVariable\0")?,
+                mx_create_double_matrix: *mx.get(b"mxCreateDoubleMatrix_730\0")?,
+                mx_get_pr: *mx.get(b"mxGetPr\0")?,
+                mx_get_number_of_elements: *mx.get(b"mxGetNumberOfElements\0")?,
+                mx_destroy_array: *mx.get(b"mxDestroyArray\0")?,
+            })
+        }
+    }
+}
+
+/// Name of the directory in `<matlabroot>/bin` that contains the libraries.
+fn arch_dir() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("glnxa64"),
+        ("macos", "x86_64") => Some("maci64"),
+        ("macos", "aarch64") => Some("maca64"),
+        ("windows", "x86_64") => Some("win64"),
+        _ => None,
+    }
+}
+
+/// An operator function together with the MATLAB session that runs it.
+struct MatlabOperator {
+    api: &'static MatlabApi,
+    engine: Engine,
+    function: String,
+    /// Declared outputs whose IDs are valid MATLAB field names.
+    outputs: Vec<DataId>,
+    /// Buffer that MATLAB writes the output of each evaluation into, owned
+    /// by the operator.
+    output_buffer: *mut [c_char],
+}
+
+impl MatlabOperator {
+    fn load(path: &Path, outputs: &BTreeSet<DataId>) -> eyre::Result<Self> {
+        let api = MatlabApi::get()?;
+        let function = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| is_identifier(stem))
+            .ok_or_else(|| {
+                eyre!(
+                    "MATLAB operator file name `{}` is not a valid function name",
+                    path.display()
+                )
+            })?
+            .to_owned();
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let dir = dir
+            .canonicalize()
+            .wrap_err_with(|| format!("failed to resolve `{}`", dir.display()))?;
+
+        let outputs = outputs
+            .iter()
+            .filter(|output| {
+                let valid = is_identifier(output.as_str());
+                if !valid {
+                    tracing::warn!(
+                        "MATLAB operator `{function}` cannot send output `{output}` because its \
+                        ID is not a valid MATLAB field name"
+                    );
+                }
+                valid
+            })
+            .cloned()
+            .collect();
+
+        let engine = unsafe { (api.eng_open)(std::ptr::null()) };
+        if engine.is_null() {
+            bail!("failed to start MATLAB engine session");
+        }
+        let output_buffer = Box::into_raw(vec![0 as c_char; OUTPUT_BUFFER_SIZE].into_boxed_slice());
+        unsafe {
+            (api.eng_output_buffer)(engine, output_buffer.cast(), OUTPUT_BUFFER_SIZE as c_int)
+        };
+        let operator = Self {
+            api,
+            engine,
+            function,
+            outputs,
+            output_buffer,
+        };
+
+        let dir = matlab_string(&dir.to_string_lossy());
+        let function = &operator.function;
+        operator.eval(&format!(
+            "addpath('{dir}'); dora_found = exist('{function}', 'file') == 2;"
+        ))?;
+        if operator.get_scalar("dora_found")? == 0.0 {
+            bail!("no MATLAB function `{function}` found in `{dir}`");
+        }
+        Ok(operator)
+    }
+
+    fn run(
+        self,
+        events_tx: &Sender<OperatorEvent>,
+        incoming_events: flume::Receiver<Event>,
+        timers: flume::Receiver<String>,
+        callback_timer: CallbackTimer,
+        worker_pool: WorkerPool,
+    ) -> eyre::Result<StopReason> {
+        let reason = loop {
+            let event = match next_input(&incoming_events, &timers) {
+                Some(OperatorInput::Event(event)) => event,
+                Some(OperatorInput::Timer(timer_id)) => {
+                    let _worker = worker_pool.acquire();
+                    let _running = callback_timer.start();
+                    match self.call(events_tx, "timer", &timer_id, &[])? {
+                        Some(reason) => break reason,
+                        None => continue,
+                    }
+                }
+                None => break StopReason::InputsClosed,
+            };
+
+            let _worker = worker_pool.acquire();
+            let _running = callback_timer.start();
+            let reason = match event {
+                Event::Stop => self.call(events_tx, "stop", "", &[])?,
+                Event::Input { id, data, .. } => {
+                    let values = match arrow::compute::cast(data.as_ref(), &DataType::Float64) {
+                        Ok(values) if values.null_count() == 0 => values,
+                        Ok(_) => {
+                            tracing::warn!("ignoring input `{id}` because it contains null values");
+                            continue;
+                        }
+                        Err(err) => {
+                            tracing::warn!(
+                                "ignoring input `{id}` because MATLAB operators only support \
+                                numeric arrays: {err}"
+                            );
+                            continue;
+                        }
+                    };
+                    let values = values.as_primitive::<Float64Type>().values();
+                    self.call(events_tx, "input", id.as_str(), values)?
+                }
+                Event::InputClosed { id } => {
+                    self.call(events_tx, "input_closed", id.as_str(), &[])?
+                }
+                Event::Reload { .. } => {
+                    // reloading MATLAB operators is not supported
+                    continue;
+                }
+                Event::Error(err) => {
+                    tracing::warn!("received error event: {err}");
+                    continue;
+                }
+                other => {
+                    tracing::warn!("unexpected event: {other:?}");
+                    continue;
+                }
+            };
+
+            if let Some(reason) = reason {
+                break reason;
+            }
+        };
+        Ok(reason)
+    }
+
+    /// Calls the operator function and sends the outputs that it returns.
+    fn call(
+        &self,
+        events_tx: &Sender<OperatorEvent>,
+        event: &str,
+        id: &str,
+        data: &[f64],
+    ) -> eyre::Result<Option<StopReason>> {
+        self.put_vector("dora_data", data)?;
+
+        let function = &self.function;
+        let id = matlab_string(id);
+        let args = format!("'{event}', '{id}', dora_data");
+        let present = self
+            .outputs
+            .iter()
+            .map(|output| format!("isfield(dora_outputs, '{output}')"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.eval(&format!(
+            "dora_outputs = struct(); dora_status = 'continue'; dora_failed = 0;
+            try
+                switch nargout('{function}')
+                    case 0
+                        {function}({args});
+                    case 1
+                        dora_outputs = {function}({args});
+                    otherwise
+                        [dora_outputs, dora_status] = {function}({args});
+                end
+                dora_status = find(strcmp(dora_status, {{'continue', 'stop', 'stop_all'}}), 1) - 1;
+                if isempty(dora_status)
+                    error('invalid operator status, expected ''continue'', ''stop'', or ''stop_all''');
+                end
+                if ~isstruct(dora_outputs)
+                    error('operator outputs must be a struct');
+                end
+                dora_present = [{present}];
+            catch dora_err
+                dora_failed = 1;
+                disp(getReport(dora_err, 'extended', 'hyperlinks', 'off'));
+            end"
+        ))?;
+        if self.get_scalar("dora_failed")? != 0.0 {
+            bail!(
+                "MATLAB operator `{function}` failed: {}",
+                self.output().trim()
+            );
+        }
+
+        if !self.outputs.is_empty() {
+            let present = self.get_vector("dora_present")?;
+            for (output, _) in self
+                .outputs
+                .iter()
+                .zip(present)
+                .filter(|(_, present)| *present != 0.0)
+            {
+                self.eval(&format!(
+                    "try
+                        dora_output = double(dora_outputs.('{output}')(:)); dora_failed = 0;
+                    catch dora_err
+                        dora_failed = 1; disp(dora_err.message);
+                    end"
+                ))?;
+                if self.get_scalar("dora_failed")? != 0.0 {
+                    bail!(
+                        "invalid value for output `{output}` of MATLAB operator `{function}`: {}",
+                        self.output().trim()
+                    );
+                }
+                let values = self.get_vector("dora_output")?;
+                send_output(events_tx, output, values)?;
+            }
+        }
+
+        match self.get_scalar("dora_status")? as i32 {
+            0 => Ok(None),
+            1 => Ok(Some(StopReason::ExplicitStop)),
+            _ => Ok(Some(StopReason::ExplicitStopAll)),
+        }
+    }
+
+    /// Evaluates the given MATLAB code in the base workspace of the session.
+    fn eval(&self, code: &str) -> eyre::Result<()> {
+        let code = CString::new(code)?;
+        let result = unsafe { (self.api.eng_eval_string)(self.engine, code.as_ptr()) };
+        if result != 0 {
+            bail!("MATLAB engine session is no longer running");
+        }
+        Ok(())
+    }
+
+    /// Returns the text that MATLAB printed during the last evaluation.
+    fn output(&self) -> String {
+        let buffer = unsafe { &*(self.output_buffer as *const [u8]) };
+        let len = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+        String::from_utf8_lossy(&buffer[..len]).into_owned()
+    }
+
+    fn put_vector(&self, name: &str, values: &[f64]) -> eyre::Result<()> {
+        let name = CString::new(name)?;
+        unsafe {
+            let array = (self.api.mx_create_double_matrix)(values.len(), 1, 0);
+            if array.is_null() {
+                bail!("failed to allocate MATLAB array");
+            }
+            if !values.is_empty() {
+                std::ptr::copy_nonoverlapping(
+                    values.as_ptr(),
+                    (self.api.mx_get_pr)(array),
+                    values.len(),
+                );
+            }
+            // the engine copies the array into the session
+            let result = (self.api.eng_put_variable)(self.engine, name.as_ptr(), array);
+            (self.api.mx_destroy_array)(array);
+            if result != 0 {
+                bail!("failed to pass `{}` to MATLAB", name.to_string_lossy());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a `double` variable of the session.
+    fn get_vector(&self, name: &str) -> eyre::Result<Vec<f64>> {
+        let name = CString::new(name)?;
+        unsafe {
+            let array = (self.api.eng_get_variable)(self.engine, name.as_ptr());
+            if array.is_null() {
+                bail!("failed to read `{}` from MATLAB", name.to_string_lossy());
+            }
+            let len = (self.api.mx_get_number_of_elements)(array);
+            let values = if len == 0 {
+                Vec::new()
+            } else {
+                std::slice::from_raw_parts((self.api.mx_get_pr)(array), len).to_vec()
+            };
+            (self.api.mx_destroy_array)(array);
+            Ok(values)
+        }
+    }
+
+    fn get_scalar(&self, name: &str) -> eyre::Result<f64> {
+        self.get_vector(name)?
+            .first()
+            .copied()
+            .ok_or_else(|| eyre!("MATLAB variable `{name}` is empty"))
+    }
+}
+
+impl Drop for MatlabOperator {
+    fn drop(&mut self) {
+        unsafe {
+            (self.api.eng_close)(self.engine);
+            drop(Box::from_raw(self.output_buffer));
+        }
+    }
+}
+
+fn send_output(
+    events_tx: &Sender<OperatorEvent>,
+    output_id: &DataId,
+    values: Vec<f64>,
+) -> eyre::Result<()> {
+    let array = Float64Array::from(values).into_data();
+    let mut sample: AVec<u8, ConstAlign<128>> =
+        AVec::__from_elem(128, 0, required_data_size(&array));
+    let type_info = copy_array_into_sample(&mut sample, &array);
+    let event = OperatorEvent::Output {
+        output_id: output_id.clone(),
+        type_info,
+        parameters: MetadataParameters::default(),
+        data: Some(sample.into()),
+    };
+    events_tx
+        .blocking_send(event)
+        .map_err(|_| eyre!("failed to send output to runtime"))
+}
+
+/// Asks the `matlab` executable for the root directory of the installation.
+fn find_matlab_root() -> eyre::Result<PathBuf> {
+    let output = Command::new("matlab")
+        .args(["-batch", "fprintf('%s', matlabroot)"])
+        .output()
+        .wrap_err(
+            "failed to run `matlab` to locate the MATLAB installation, \
+            set `DORA_MATLAB_ROOT` to its root directory instead",
+        )?;
+    if !output.status.success() {
+        bail!(
+            "failed to locate the MATLAB installation: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+}
+
+/// Checks whether the given string is a valid MATLAB identifier.
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Escapes a string for use in a single-quoted MATLAB character vector.
+fn matlab_string(s: &str) -> String {
+    s.replace('\'', "''")
+}
@@ -0,0 +1,142 @@
+//! Metrics that operators record through their `metrics` handle.
+//!
+//! The values are recorded through the global OpenTelemetry meter provider,
+//! which is set up by the runtime, so they are exported together with the
+//! system metrics of the node. Each value is tagged with the node and operator
+//! ID. Without the `metrics` feature, all values are dropped.
+
+use dora_core::config::{NodeId, OperatorId};
+use dora_operator_api_types::{
+    safer_ffi::closure::ArcDynFn1, DoraResult, MetricKind, MetricUpdate, RecordMetric,
+};
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct OperatorMetrics {
+    #[cfg(feature = "metrics")]
+    instruments: Arc<otel::Instruments>,
+}
+
+impl OperatorMetrics {
+    pub fn new(node_id: &NodeId, operator_id: &OperatorId) -> Self {
+        #[cfg(not(feature = "metrics"))]
+        let _ = (node_id, operator_id);
+        Self {
+            #[cfg(feature = "metrics")]
+            instruments: Arc::new(otel::Instruments::new(node_id, operator_id)),
+        }
+    }
+
+    pub fn record(&self, name: &str, kind: MetricKind, value: f64) {
+        if !value.is_finite() {
+            tracing::debug!("ignoring non-finite value of operator metric `{name}`");
+            return;
+        }
+        #[cfg(feature = "metrics")]
+        self.instruments.record(name, kind, value);
+        #[cfg(not(feature = "metrics"))]
+        let _ = kind;
+    }
+
+    /// Creates the callback that is passed to shared library operators.
+    pub fn ffi_callback(&self) -> RecordMetric {
+        let metrics = self.clone();
+        let record = move |update: MetricUpdate| {
+            metrics.record(&update.name, update.kind, update.value);
+            DoraResult::SUCCESS
+        };
+        RecordMetric {
+            record_metric: ArcDynFn1::new(Arc::new(record)),
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod otel {
+    use dora_core::config::{NodeId, OperatorId};
+    use dora_operator_api_types::MetricKind;
+    use opentelemetry::{
+        metrics::{Counter, Histogram, ObservableGauge},
+        KeyValue,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    pub struct Instruments {
+        attributes: Arc<[KeyValue]>,
+        counters: Mutex<HashMap<String, Counter<f64>>>,
+        histograms: Mutex<HashMap<String, Histogram<f64>>>,
+        gauges: Mutex<HashMap<String, Gauge>>,
+    }
+
+    /// Gauges are observed by the exporter, so we store the last value.
+    struct Gauge {
+        value: Arc<AtomicU64>,
+        _instrument: ObservableGauge<f64>,
+    }
+
+    impl Instruments {
+        pub fn new(node_id: &NodeId, operator_id: &OperatorId) -> Self {
+            Self {
+                attributes: Arc::new([
+                    KeyValue::new("node", node_id.to_string()),
+                    KeyValue::new("operator", operator_id.to_string()),
+                ]),
+                counters: Default::default(),
+                histograms: Default::default(),
+                gauges: Default::default(),
+            }
+        }
+
+        pub fn record(&self, name: &str, kind: MetricKind, value: f64) {
+            // instruments are created on first use because the meter provider
+            // is only set up once the runtime starts
+            let meter = || opentelemetry::global::meter("dora-operators");
+            match kind {
+                MetricKind::Counter => {
+                    if value < 0.0 {
+                        tracing::debug!("ignoring negative increment of counter `{name}`");
+                        return;
+                    }
+                    let mut counters = self.counters.lock().unwrap();
+                    counters
+                        .entry(name.to_owned())
+                        .or_insert_with(|| meter().f64_counter(name.to_owned()).init())
+                        .add(value, &self.attributes);
+                }
+                MetricKind::Histogram => {
+                    let mut histograms = self.histograms.lock().unwrap();
+                    histograms
+                        .entry(name.to_owned())
+                        .or_insert_with(|| meter().f64_histogram(name.to_owned()).init())
+                        .record(value, &self.attributes);
+                }
+                MetricKind::Gauge => {
+                    let mut gauges = self.gauges.lock().unwrap();
+                    let gauge = gauges.entry(name.to_owned()).or_insert_with(|| {
+                        let value = Arc::new(AtomicU64::new(0f64.to_bits()));
+                        let observed = value.clone();
+                        let attributes = self.attributes.clone();
+                        let instrument = meter()
+                            .f64_observable_gauge(name.to_owned())
+                            .with_callback(move |observer| {
+                                let value = f64::from_bits(observed.load(Ordering::Relaxed));
+                                observer.observe(value, &attributes)
+                            })
+                            .init();
+                        Gauge {
+                            value,
+                            _instrument: instrument,
+                        }
+                    });
+                    gauge.value.store(value.to_bits(), Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
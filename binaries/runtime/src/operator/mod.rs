@@ -1,36 +1,121 @@
 use dora_core::{
     config::{DataId, NodeId},
-    descriptor::{Descriptor, OperatorDefinition, OperatorSource},
+    daemon_messages::{DataflowId, NodeLogRecord},
+    descriptor::{Descriptor, OperatorDefinition, OperatorSource, WatchdogAction},
     message::{ArrowTypeInfo, MetadataParameters},
 };
 use dora_node_api::{DataSample, Event};
 use eyre::{Context, Result};
-use std::any::Any;
+use serde::{Deserialize, Serialize};
+use std::{any::Any, time::Duration};
 use tokio::sync::{mpsc::Sender, oneshot};
+use watchdog::CallbackTimer;
+use worker_pool::WorkerPool;
 
 pub mod channel;
+pub mod isolated;
+#[cfg(feature = "julia")]
+mod julia;
+#[cfg(feature = "lua")]
+mod lua;
+#[cfg(feature = "matlab")]
+mod matlab;
+mod metrics;
+#[cfg(feature = "onnx")]
+mod onnx;
 #[cfg(feature = "python")]
 mod python;
+pub mod scheduling;
 mod shared_lib;
+pub mod timers;
+#[cfg(feature = "wasm")]
+mod wasm;
+pub mod watchdog;
+pub mod worker_pool;
 
-#[allow(unused_variables)]
+#[cfg(feature = "python")]
+pub use python::{Logger as PythonLogger, Metrics as PythonMetrics};
+
+#[allow(unused_variables, clippy::too_many_arguments)]
 pub fn run_operator(
     node_id: &NodeId,
+    dataflow_id: DataflowId,
     operator_definition: OperatorDefinition,
+    python: Option<Vec<String>>,
     incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
     events_tx: Sender<OperatorEvent>,
     init_done: oneshot::Sender<Result<()>>,
     dataflow_descriptor: &Descriptor,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
 ) -> eyre::Result<()> {
+    let isolate = operator_definition.config.isolate;
     match &operator_definition.config.source {
+        OperatorSource::SharedLibrary(_)
+        | OperatorSource::Wasm(_)
+        | OperatorSource::Julia(_)
+        | OperatorSource::Matlab(_)
+        | OperatorSource::Lua(_)
+        | OperatorSource::Onnx(_)
+            if operator_definition.config.batch_inputs =>
+        {
+            eyre::bail!(
+                "cannot batch inputs of operator `{}`: batching is only supported for \
+                Python operators",
+                operator_definition.id
+            )
+        }
+        _ if isolate => {
+            let source = isolated::IsolatedSource::from_config(&operator_definition.config)
+                .ok_or_else(|| {
+                    eyre::eyre!(
+                        "cannot isolate operator `{}`: only shared library and Python \
+                        operators can be isolated",
+                        operator_definition.id
+                    )
+                })?;
+            let parameters = serde_json::to_string(&operator_definition.config.parameters)
+                .wrap_err("failed to serialize operator parameters")?;
+            let config = serde_json::to_string(&operator_definition.config.config)
+                .wrap_err("failed to serialize operator config")?;
+            isolated::run(
+                node_id,
+                &operator_definition.id,
+                source,
+                python,
+                events_tx,
+                incoming_events,
+                timers,
+                init_done,
+                parameters,
+                config,
+                operator_definition.config.scheduling.clone(),
+            )
+            .wrap_err_with(|| {
+                format!(
+                    "failed to spawn isolated operator for {}",
+                    operator_definition.id
+                )
+            })?;
+        }
         OperatorSource::SharedLibrary(source) => {
+            let parameters = serde_json::to_string(&operator_definition.config.parameters)
+                .wrap_err("failed to serialize operator parameters")?;
+            let config = serde_json::to_string(&operator_definition.config.config)
+                .wrap_err("failed to serialize operator config")?;
             shared_lib::run(
                 node_id,
                 &operator_definition.id,
                 source,
                 events_tx,
                 incoming_events,
+                timers,
                 init_done,
+                parameters,
+                config,
+                callback_timer,
+                worker_pool,
             )
             .wrap_err_with(|| {
                 format!(
@@ -44,12 +129,19 @@ pub fn run_operator(
             #[cfg(feature = "python")]
             python::run(
                 node_id,
+                dataflow_id,
                 &operator_definition.id,
                 source,
                 events_tx,
                 incoming_events,
+                timers,
                 init_done,
                 dataflow_descriptor,
+                &operator_definition.config.parameters,
+                &operator_definition.config.config,
+                operator_definition.config.batch_inputs,
+                callback_timer,
+                worker_pool,
             )
             .wrap_err_with(|| {
                 format!(
@@ -62,13 +154,153 @@ pub fn run_operator(
                 "Dora runtime tried spawning Python Operator outside of python environment."
             );
         }
-        OperatorSource::Wasm(_) => {
-            tracing::error!("WASM operators are not supported yet");
+        OperatorSource::Wasm(source) => {
+            #[cfg(feature = "wasm")]
+            wasm::run(
+                node_id,
+                &operator_definition.id,
+                source,
+                events_tx,
+                incoming_events,
+                timers,
+                init_done,
+                operator_definition.config.watchdog.as_ref(),
+                callback_timer,
+                worker_pool,
+            )
+            .wrap_err_with(|| {
+                format!(
+                    "failed to spawn WASM operator for {}",
+                    operator_definition.id
+                )
+            })?;
+            #[cfg(not(feature = "wasm"))]
+            eyre::bail!(
+                "cannot run WASM operator `{}` because dora-runtime was built without \
+                the `wasm` feature",
+                operator_definition.id
+            );
+        }
+        #[allow(unused_variables)]
+        OperatorSource::Julia(source) => {
+            #[cfg(feature = "julia")]
+            julia::run(
+                node_id,
+                &operator_definition.id,
+                source,
+                events_tx,
+                incoming_events,
+                timers,
+                init_done,
+                callback_timer,
+                worker_pool,
+            )
+            .wrap_err_with(|| {
+                format!(
+                    "failed to spawn Julia operator for {}",
+                    operator_definition.id
+                )
+            })?;
+            #[cfg(not(feature = "julia"))]
+            eyre::bail!(
+                "cannot run Julia operator `{}` because dora-runtime was built without \
+                the `julia` feature",
+                operator_definition.id
+            );
+        }
+        #[allow(unused_variables)]
+        OperatorSource::Matlab(source) => {
+            #[cfg(feature = "matlab")]
+            matlab::run(
+                node_id,
+                &operator_definition.id,
+                source,
+                &operator_definition.config.outputs,
+                events_tx,
+                incoming_events,
+                timers,
+                init_done,
+                callback_timer,
+                worker_pool,
+            )
+            .wrap_err_with(|| {
+                format!(
+                    "failed to spawn MATLAB operator for {}",
+                    operator_definition.id
+                )
+            })?;
+            #[cfg(not(feature = "matlab"))]
+            eyre::bail!(
+                "cannot run MATLAB operator `{}` because dora-runtime was built without \
+                the `matlab` feature",
+                operator_definition.id
+            );
+        }
+        #[allow(unused_variables)]
+        OperatorSource::Lua(source) => {
+            #[cfg(feature = "lua")]
+            lua::run(
+                node_id,
+                &operator_definition.id,
+                source,
+                events_tx,
+                incoming_events,
+                timers,
+                init_done,
+                &operator_definition.config.parameters,
+                callback_timer,
+                worker_pool,
+            )
+            .wrap_err_with(|| {
+                format!(
+                    "failed to spawn Lua operator for {}",
+                    operator_definition.id
+                )
+            })?;
+            #[cfg(not(feature = "lua"))]
+            eyre::bail!(
+                "cannot run Lua operator `{}` because dora-runtime was built without \
+                the `lua` feature",
+                operator_definition.id
+            );
+        }
+        #[allow(unused_variables)]
+        OperatorSource::Onnx(source) => {
+            #[cfg(feature = "onnx")]
+            onnx::run(
+                &operator_definition.id,
+                source,
+                events_tx,
+                incoming_events,
+                init_done,
+                callback_timer,
+                worker_pool,
+            )
+            .wrap_err_with(|| {
+                format!(
+                    "failed to spawn ONNX operator for {}",
+                    operator_definition.id
+                )
+            })?;
+            #[cfg(not(feature = "onnx"))]
+            eyre::bail!(
+                "cannot run ONNX operator `{}` because dora-runtime was built without \
+                the `onnx` feature",
+                operator_definition.id
+            );
         }
     }
     Ok(())
 }
 
+/// Something that the operator should react to.
+#[derive(Debug)]
+pub enum OperatorInput {
+    Event(Event),
+    /// One of the `_unstable_timers` of the operator fired.
+    Timer(String),
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum OperatorEvent {
@@ -84,12 +316,19 @@ pub enum OperatorEvent {
     },
     Error(eyre::Error),
     Panic(Box<dyn Any + Send>),
+    /// An event callback of the operator exceeded its configured timeout.
+    Timeout {
+        elapsed: Duration,
+        action: WatchdogAction,
+    },
     Finished {
         reason: StopReason,
     },
+    /// A structured log record of the operator, see `DoraNode::log`.
+    Log(NodeLogRecord),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum StopReason {
     InputsClosed,
     ExplicitStop,
@@ -0,0 +1,322 @@
+//! Built-in operator that runs an ONNX model.
+//!
+//! Common inference steps don't need a custom operator: the model and the
+//! mapping between its tensors and the operator inputs and outputs are
+//! configured in the dataflow descriptor. The model is run with [`tract`],
+//! which is pure Rust, so no native inference runtime needs to be installed.
+//!
+//! ```yaml
+//! - id: classifier
+//!   onnx:
+//!     model: mobilenet.onnx
+//!     inputs:
+//!       input:                # name of the model input tensor
+//!         input: image        # operator input that provides the values
+//!         shape: [1, 3, 224, 224]
+//!     outputs:
+//!       output: scores        # model output tensor -> operator output
+//!   inputs:
+//!     image: preprocess/image
+//!   outputs:
+//!     - scores
+//! ```
+//!
+//! The model runs each time one of the mapped inputs arrives, once every model
+//! input has received a value. Model inputs whose operator input didn't change
+//! reuse their last value. The input arrays are cast to the element type of the
+//! model input and reshaped to the configured shape. Outputs are sent as flat
+//! arrays in row-major order. Timers are not supported.
+//!
+//! [`tract`]: https://github.com/sonos/tract
+
+use super::{watchdog::CallbackTimer, worker_pool::WorkerPool, OperatorEvent, StopReason};
+use aligned_vec::{AVec, ConstAlign};
+use arrow::{
+    array::{
+        Array, ArrayRef, AsArray, Float32Array, Float64Array, Int32Array, Int64Array, UInt8Array,
+    },
+    datatypes::{DataType, Float32Type, Float64Type, Int32Type, Int64Type, UInt8Type},
+};
+use dora_core::{
+    config::{DataId, OperatorId},
+    descriptor::{OnnxInput, OnnxSource},
+};
+use dora_node_api::{
+    arrow_utils::{copy_array_into_sample, required_data_size},
+    Event, MetadataParameters,
+};
+use eyre::{bail, eyre, Context, Result};
+use std::sync::Arc;
+use tokio::sync::{mpsc::Sender, oneshot};
+use tract_onnx::prelude::*;
+
+type Model = SimplePlan<TypedFact, Box<dyn TypedOp>, Graph<TypedFact, Box<dyn TypedOp>>>;
+
+pub fn run(
+    operator_id: &OperatorId,
+    source: &OnnxSource,
+    events_tx: Sender<OperatorEvent>,
+    incoming_events: flume::Receiver<Event>,
+    init_done: oneshot::Sender<Result<()>>,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
+) -> eyre::Result<()> {
+    let operator = match OnnxOperator::load(source) {
+        Ok(operator) => operator,
+        Err(err) => {
+            let _ = init_done.send(Err(eyre!("{err:?}")));
+            return Err(err);
+        }
+    };
+    let _ = init_done.send(Ok(()));
+    tracing::debug!("loaded ONNX model `{}` for `{operator_id}`", source.model);
+
+    let result = operator.run(&events_tx, incoming_events, callback_timer, worker_pool);
+    let event = match result {
+        Ok(reason) => OperatorEvent::Finished { reason },
+        Err(err) => OperatorEvent::Error(err),
+    };
+    let _ = events_tx.blocking_send(event);
+
+    Ok(())
+}
+
+struct OnnxOperator {
+    model: Model,
+    /// The model inputs, in the order expected by the model.
+    inputs: Vec<ModelInput>,
+    /// The operator output for each model output, if it's mapped.
+    outputs: Vec<Option<DataId>>,
+}
+
+struct ModelInput {
+    name: String,
+    mapping: OnnxInput,
+    datum_type: DatumType,
+    /// The most recent value, if any.
+    value: Option<TValue>,
+}
+
+impl OnnxOperator {
+    fn load(source: &OnnxSource) -> eyre::Result<Self> {
+        let mut model = tract_onnx::onnx()
+            .model_for_path(&source.model)
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err_with(|| format!("failed to load ONNX model `{}`", source.model))?;
+
+        let mut input_names = Vec::new();
+        for (index, outlet) in model
+            .input_outlets()
+            .map_err(tract_error)?
+            .to_vec()
+            .iter()
+            .enumerate()
+        {
+            let name = model.node(outlet.node).name.clone();
+            let Some(mapping) = source.inputs.get(&name) else {
+                bail!("model input `{name}` is not mapped to an operator input");
+            };
+            if !mapping.shape.is_empty() {
+                let fact = model.input_fact(index).map_err(tract_error)?.clone();
+                let fact = fact.with_shape(mapping.shape.clone());
+                model.set_input_fact(index, fact).map_err(tract_error)?;
+            }
+            input_names.push(name);
+        }
+        for name in source.inputs.keys() {
+            if !input_names.contains(name) {
+                bail!("model has no input named `{name}`");
+            }
+        }
+
+        let output_names: Vec<String> = model
+            .output_outlets()
+            .map_err(tract_error)?
+            .iter()
+            .map(|outlet| match model.outlet_label(*outlet) {
+                Some(label) => label.to_owned(),
+                None => model.node(outlet.node).name.clone(),
+            })
+            .collect();
+        for name in source.outputs.keys() {
+            if !output_names.contains(name) {
+                bail!("model has no output named `{name}`");
+            }
+        }
+
+        let model = model
+            .into_optimized()
+            .and_then(|model| model.into_runnable())
+            .map_err(tract_error)
+            .wrap_err("failed to optimize ONNX model")?;
+
+        let mut inputs = Vec::new();
+        for (index, name) in input_names.into_iter().enumerate() {
+            let datum_type = model
+                .model()
+                .input_fact(index)
+                .map_err(tract_error)?
+                .datum_type;
+            inputs.push(ModelInput {
+                mapping: source.inputs[&name].clone(),
+                name,
+                datum_type,
+                value: None,
+            });
+        }
+        let outputs = output_names
+            .iter()
+            .map(|name| source.outputs.get(name).cloned())
+            .collect();
+
+        Ok(Self {
+            model,
+            inputs,
+            outputs,
+        })
+    }
+
+    fn run(
+        mut self,
+        events_tx: &Sender<OperatorEvent>,
+        incoming_events: flume::Receiver<Event>,
+        callback_timer: CallbackTimer,
+        worker_pool: WorkerPool,
+    ) -> eyre::Result<StopReason> {
+        while let Ok(event) = incoming_events.recv() {
+            match event {
+                Event::Input { id, metadata, data } => {
+                    let _worker = worker_pool.acquire();
+                    let _running = callback_timer.start();
+                    let mut updated = false;
+                    for input in self.inputs.iter_mut().filter(|i| i.mapping.input == id) {
+                        let tensor = to_tensor(data.as_ref(), input.datum_type, &input.mapping)
+                            .wrap_err_with(|| {
+                                format!("invalid value for model input `{}`", input.name)
+                            })?;
+                        input.value = Some(tensor.into_tvalue());
+                        updated = true;
+                    }
+                    if updated {
+                        self.infer(events_tx, metadata.parameters)?;
+                    }
+                }
+                // outputs are only sent in reaction to inputs, so there is
+                // nothing to do on stop
+                Event::Stop
+                | Event::InputClosed { .. }
+                | Event::AllInputsClosed
+                | Event::Reload { .. } => {}
+                Event::Error(err) => tracing::warn!("received error event: {err}"),
+                other => tracing::warn!("unexpected event: {other:?}"),
+            }
+        }
+        Ok(StopReason::InputsClosed)
+    }
+
+    /// Runs the model if all inputs have a value and sends its outputs.
+    fn infer(
+        &self,
+        events_tx: &Sender<OperatorEvent>,
+        parameters: MetadataParameters,
+    ) -> eyre::Result<()> {
+        let Some(inputs) = self
+            .inputs
+            .iter()
+            .map(|input| input.value.clone())
+            .collect::<Option<TVec<_>>>()
+        else {
+            return Ok(());
+        };
+        let results = self
+            .model
+            .run(inputs)
+            .map_err(tract_error)
+            .wrap_err("failed to run ONNX model")?;
+        for (output_id, value) in self.outputs.iter().zip(results) {
+            let Some(output_id) = output_id else {
+                continue;
+            };
+            let array = from_tensor(&value)
+                .wrap_err_with(|| format!("failed to convert model output for `{output_id}`"))?
+                .to_data();
+            let mut sample: AVec<u8, ConstAlign<128>> =
+                AVec::__from_elem(128, 0, required_data_size(&array));
+            let type_info = copy_array_into_sample(&mut sample, &array);
+            let event = OperatorEvent::Output {
+                output_id: output_id.clone(),
+                type_info,
+                parameters: parameters.clone(),
+                data: Some(sample.into()),
+            };
+            events_tx
+                .blocking_send(event)
+                .map_err(|_| eyre!("failed to send output to runtime"))?;
+        }
+        Ok(())
+    }
+}
+
+fn tract_error(err: TractError) -> eyre::Report {
+    eyre!("{err:?}")
+}
+
+fn to_tensor(
+    array: &dyn Array,
+    datum_type: DatumType,
+    mapping: &OnnxInput,
+) -> eyre::Result<Tensor> {
+    let shape = if mapping.shape.is_empty() {
+        vec![array.len()]
+    } else {
+        mapping.shape.clone()
+    };
+    let expected_len: usize = shape.iter().product();
+    if array.len() != expected_len {
+        bail!(
+            "expected {expected_len} elements for shape {shape:?}, got {}",
+            array.len()
+        );
+    }
+    if array.null_count() > 0 {
+        bail!("null values are not supported");
+    }
+
+    macro_rules! convert {
+        ($data_type:expr, $arrow_type:ty) => {{
+            let array = arrow::compute::cast(array, &$data_type)?;
+            Tensor::from_shape(&shape, &array.as_primitive::<$arrow_type>().values()[..])
+                .map_err(tract_error)
+        }};
+    }
+    match datum_type {
+        DatumType::F32 => convert!(DataType::Float32, Float32Type),
+        DatumType::F64 => convert!(DataType::Float64, Float64Type),
+        DatumType::I32 => convert!(DataType::Int32, Int32Type),
+        DatumType::I64 => convert!(DataType::Int64, Int64Type),
+        DatumType::U8 => convert!(DataType::UInt8, UInt8Type),
+        other => bail!("unsupported element type `{other:?}`"),
+    }
+}
+
+fn from_tensor(tensor: &Tensor) -> eyre::Result<ArrayRef> {
+    let array: ArrayRef = match tensor.datum_type() {
+        DatumType::F32 => Arc::new(Float32Array::from(
+            tensor.as_slice::<f32>().map_err(tract_error)?.to_vec(),
+        )),
+        DatumType::F64 => Arc::new(Float64Array::from(
+            tensor.as_slice::<f64>().map_err(tract_error)?.to_vec(),
+        )),
+        DatumType::I32 => Arc::new(Int32Array::from(
+            tensor.as_slice::<i32>().map_err(tract_error)?.to_vec(),
+        )),
+        DatumType::I64 => Arc::new(Int64Array::from(
+            tensor.as_slice::<i64>().map_err(tract_error)?.to_vec(),
+        )),
+        DatumType::U8 => Arc::new(UInt8Array::from(
+            tensor.as_slice::<u8>().map_err(tract_error)?.to_vec(),
+        )),
+        other => bail!("unsupported element type `{other:?}`"),
+    };
+    Ok(array)
+}
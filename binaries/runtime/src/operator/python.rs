@@ -1,26 +1,37 @@
 #![allow(clippy::borrow_deref_ref)] // clippy warns about code generated by #[pymethods]
 
-use super::{OperatorEvent, StopReason};
+use super::{
+    metrics::OperatorMetrics, watchdog::CallbackTimer, worker_pool::WorkerPool, OperatorEvent,
+    OperatorInput, StopReason,
+};
 use dora_core::{
-    config::{NodeId, OperatorId},
-    descriptor::{source_is_url, Descriptor, PythonSource},
+    config::{DataId, NodeId, OperatorId},
+    daemon_messages::{DataflowId, OperatorError},
+    descriptor::{source_is_url, DataflowGraph, Descriptor, PythonSource},
 };
 use dora_download::download_file;
 use dora_node_api::Event;
 use dora_operator_api_python::PyEvent;
 use dora_operator_api_types::DoraStatus;
 use eyre::{bail, eyre, Context, Result};
+use notify::{Event as NotifyEvent, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use pyo3::{
     pyclass,
-    types::{IntoPyDict, PyAnyMethods, PyDict, PyTracebackMethods},
-    Py, PyAny, Python,
+    sync::GILOnceCell,
+    types::{
+        PyAnyMethods, PyBytes, PyBytesMethods, PyDict, PyDictMethods, PyTracebackMethods,
+        PyTypeMethods,
+    },
+    Bound, Py, PyAny, PyResult, Python,
 };
 use std::{
+    collections::BTreeMap,
     panic::{catch_unwind, AssertUnwindSafe},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 use tokio::sync::{mpsc::Sender, oneshot};
-use tracing::{error, field, span, warn};
+use tracing::{error, field, info, span, warn};
 
 fn traceback(err: pyo3::PyErr) -> eyre::Report {
     let traceback = Python::with_gil(|py| err.traceback_bound(py).and_then(|t| t.format().ok()));
@@ -31,15 +42,44 @@ fn traceback(err: pyo3::PyErr) -> eyre::Report {
     }
 }
 
+/// Converts an exception of an event callback into a structured [`OperatorError`].
+fn exception(
+    py: Python,
+    err: pyo3::PyErr,
+    operator_id: &OperatorId,
+    input_id: Option<DataId>,
+) -> eyre::Report {
+    let kind = err
+        .get_type_bound(py)
+        .name()
+        .map(|name| name.to_string())
+        .ok();
+    eyre::Report::new(OperatorError {
+        operator_id: operator_id.clone(),
+        input_id,
+        kind,
+        message: err.value_bound(py).to_string(),
+        traceback: err.traceback_bound(py).and_then(|t| t.format().ok()),
+    })
+}
+
 #[tracing::instrument(skip(events_tx, incoming_events), level = "trace")]
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     node_id: &NodeId,
+    dataflow_id: DataflowId,
     operator_id: &OperatorId,
     python_source: &PythonSource,
     events_tx: Sender<OperatorEvent>,
     incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
     init_done: oneshot::Sender<Result<()>>,
     dataflow_descriptor: &Descriptor,
+    parameters: &BTreeMap<String, serde_json::Value>,
+    config: &BTreeMap<String, serde_json::Value>,
+    batch_inputs: bool,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
 ) -> eyre::Result<()> {
     let path = if source_is_url(&python_source.source) {
         let target_path = Path::new("build")
@@ -68,11 +108,36 @@ pub fn run(
         .to_str()
         .ok_or_else(|| eyre!("module file stem is not valid utf8"))?;
     let path_parent = path.parent();
+    let state_path = state_path(dataflow_id, node_id, operator_id);
+    let init_state_path = state_path.clone();
 
     let send_output = SendOutputCallback {
         events_tx: events_tx.clone(),
+        input_context: Default::default(),
+    };
+    let metrics = Metrics {
+        metrics: OperatorMetrics::new(node_id, operator_id),
+    };
+    let logger = Logger {
+        events_tx: events_tx.clone(),
+        operator_id: operator_id.clone(),
     };
 
+    // keep the sender alive to avoid disconnect errors when hot reload is disabled
+    let (source_changed_tx, source_changed_rx) = flume::bounded(1);
+    let _watcher = if python_source.hot_reload {
+        Some(watch_source_file(&path, source_changed_tx.clone())?)
+    } else {
+        None
+    };
+
+    // created on first use by an `async def` callback
+    let event_loop = GILOnceCell::new();
+    let event_loop = &event_loop;
+
+    // the timer sender is dropped right away if no timers are configured
+    let has_timers = !timers.is_disconnected();
+
     let init_operator = move |py: Python| {
         if let Some(parent_path) = path_parent {
             let parent_path = parent_path
@@ -97,14 +162,54 @@ pub fn run(
             .getattr("Operator")
             .wrap_err("no `Operator` class found in module")?;
 
-        let locals = [("Operator", operator_class)].into_py_dict_bound(py);
-        let operator = py
-            .eval_bound("Operator()", None, Some(&locals))
-            .map_err(traceback)?;
+        let operator = create_operator(&operator_class, config)?;
         operator.setattr(
             "dataflow_descriptor",
             pythonize::pythonize(py, dataflow_descriptor)?,
         )?;
+        // same as `Node.graph()`, but with the inputs and outputs of the operator
+        match DataflowGraph::for_operator(dataflow_descriptor, node_id, operator_id) {
+            Ok(graph) => operator.setattr("graph", pythonize::pythonize(py, &graph)?)?,
+            // e.g. for operators that were loaded at runtime
+            Err(err) => warn!("no dataflow graph for operator `{operator_id}`: {err}"),
+        }
+        operator.setattr("metrics", Py::new(py, metrics.clone())?)?;
+        operator.setattr("logger", Py::new(py, logger.clone())?)?;
+        load_state(py, &operator, &init_state_path).wrap_err_with(|| {
+            format!(
+                "failed to restore operator state from `{}`",
+                init_state_path.display()
+            )
+        })?;
+
+        if !operator.hasattr("on_event")? && !operator.hasattr("on_input")? {
+            bail!("operator has neither an `on_event` nor an `on_input` method");
+        }
+        if has_timers && !operator.hasattr("on_timer")? && !operator.hasattr("on_event")? {
+            bail!(
+                "operator has `_unstable_timers` configured, but neither an `on_timer` \
+                nor an `on_event` method"
+            );
+        }
+        if batch_inputs && !operator.hasattr("on_inputs")? {
+            bail!("operator has `_unstable_batch_inputs` enabled, but no `on_inputs` method");
+        }
+
+        // the lifecycle hooks are optional
+        if operator.hasattr("on_configure")? {
+            operator
+                .call_method1("on_configure", (pythonize::pythonize(py, parameters)?,))
+                .and_then(|r| run_if_awaitable(py, event_loop, r))
+                .map_err(traceback)
+                .wrap_err("on_configure failed")?;
+        }
+        if operator.hasattr("on_start")? {
+            operator
+                .call_method0("on_start")
+                .and_then(|r| run_if_awaitable(py, event_loop, r))
+                .map_err(traceback)
+                .wrap_err("on_start failed")?;
+        }
 
         Result::<_, eyre::Report>::Ok(Py::from(operator))
     };
@@ -123,67 +228,67 @@ pub fn run(
             };
 
         let mut reload = false;
+        // event that ended the last input batch
+        let mut pending = None;
         let reason = loop {
-            #[allow(unused_mut)]
-            let Ok(mut event) = incoming_events.recv() else {
-                break StopReason::InputsClosed;
+            let input = match pending.take() {
+                Some(event) => OperatorInput::Event(event),
+                None => {
+                    let mut selector = flume::Selector::new()
+                        .recv(&incoming_events, |event| {
+                            event.ok().map(|e| Next::Input(OperatorInput::Event(e)))
+                        })
+                        .recv(&source_changed_rx, |_| Some(Next::SourceChanged));
+                    if !timers.is_disconnected() {
+                        selector = selector.recv(&timers, |tick| {
+                            Some(match tick {
+                                Ok(timer_id) => Next::Input(OperatorInput::Timer(timer_id)),
+                                Err(_) => Next::TimersStopped,
+                            })
+                        });
+                    }
+                    match selector.wait() {
+                        Some(Next::Input(input)) => input,
+                        Some(Next::SourceChanged) => {
+                            info!("source file of operator changed -> reloading");
+                            reload = true;
+                            replace_operator(&mut operator, module_name, config);
+                            continue;
+                        }
+                        Some(Next::TimersStopped) => continue,
+                        None => break StopReason::InputsClosed,
+                    }
+                }
             };
 
-            if let Event::Reload { .. } = event {
+            if let OperatorInput::Event(Event::Reload { .. }) = input {
                 reload = true;
-                // Reloading method
-                #[allow(clippy::blocks_in_conditions)]
-                match Python::with_gil(|py| -> Result<Py<PyAny>> {
-                    // Saving current state
-                    let current_state = operator
-                        .getattr(py, "__dict__")
-                        .wrap_err("Could not retrieve current operator state")?;
-                    let current_state = current_state
-                        .extract::<&PyDict>(py)
-                        .wrap_err("could not extract operator state as a PyDict")?;
-                    // Reload module
-                    let module = py
-                        .import_bound(module_name)
-                        .map_err(traceback)
-                        .wrap_err(format!("Could not retrieve {module_name} while reloading"))?;
-                    let importlib = py
-                        .import_bound("importlib")
-                        .wrap_err("failed to import `importlib` module")?;
-                    let module = importlib
-                        .call_method("reload", (module,), None)
-                        .wrap_err(format!("Could not reload {module_name} while reloading"))?;
-                    let reloaded_operator_class = module
-                        .getattr("Operator")
-                        .wrap_err("no `Operator` class found in module")?;
-
-                    // Create a new reloaded operator
-                    let locals = [("Operator", reloaded_operator_class)].into_py_dict_bound(py);
-                    let operator: Py<pyo3::PyAny> = py
-                        .eval_bound("Operator()", None, Some(&locals))
-                        .map_err(traceback)
-                        .wrap_err("Could not initialize reloaded operator")?
-                        .into();
-
-                    // Replace initialized state with current state
-                    operator
-                        .getattr(py, "__dict__")
-                        .wrap_err("Could not retrieve new operator state")?
-                        .extract::<&PyDict>(py)
-                        .wrap_err("could not extract new operator state as a PyDict")?
-                        .update(current_state.as_mapping())
-                        .wrap_err("could not restore operator state")?;
-
-                    Ok(operator)
-                }) {
-                    Ok(reloaded_operator) => {
-                        operator = reloaded_operator;
-                    }
-                    Err(err) => {
-                        error!("Failed to reload operator.\n {err}");
+                replace_operator(&mut operator, module_name, config);
+            }
+
+            #[allow(unused_mut)]
+            let mut callback = match input {
+                OperatorInput::Event(event @ Event::Input { .. }) if batch_inputs => {
+                    let mut batch = vec![event];
+                    // take all inputs that are already queued, but stop at the
+                    // first other event to keep the event order
+                    while let Ok(event) = incoming_events.try_recv() {
+                        if let Event::Input { .. } = event {
+                            batch.push(event);
+                        } else {
+                            pending = Some(event);
+                            break;
+                        }
                     }
+                    Callback::Batch(batch)
                 }
-            }
+                OperatorInput::Event(event) => Callback::Event(event),
+                OperatorInput::Timer(timer_id) => Callback::Timer(timer_id),
+            };
 
+            let worker = worker_pool.acquire();
+            // start the timer before acquiring the GIL to detect callbacks that are blocked on it
+            let running = callback_timer.start();
             let status = Python::with_gil(|py| -> Result<i32> {
                 let span = span!(tracing::Level::TRACE, "on_event", input_id = field::Empty);
                 let _ = span.enter();
@@ -191,11 +296,11 @@ pub fn run(
                 // Add metadata context if we have a tracer and
                 // incoming input has some metadata.
                 #[cfg(feature = "telemetry")]
-                if let Event::Input {
+                if let Callback::Event(Event::Input {
                     id: input_id,
                     metadata,
                     ..
-                } = &mut event
+                }) = &mut callback
                 {
                     use dora_tracing::telemetry::{deserialize_context, serialize_context};
                     use tracing_opentelemetry::OpenTelemetrySpanExt;
@@ -207,32 +312,77 @@ pub fn run(
                     let string_cx = serialize_context(&cx);
                     metadata.parameters.open_telemetry_context = string_cx;
                 }
+                let last_input = match &callback {
+                    Callback::Event(event) => Some(event),
+                    Callback::Batch(batch) => batch.last(),
+                    Callback::Timer(_) => None,
+                };
+                if let Some(Event::Input { metadata, .. }) = last_input {
+                    send_output.set_input_context(&metadata.parameters.open_telemetry_context);
+                }
 
-                let py_event = PyEvent::from(event)
-                    .to_py_dict(py)
-                    .context("Could not convert event to pydict bound")?;
-
-                let status_enum = operator
-                    .call_method1(py, "on_event", (py_event, send_output.clone()))
-                    .map_err(traceback);
-                match status_enum {
-                    Ok(status_enum) => {
-                        let status_val = Python::with_gil(|py| status_enum.getattr(py, "value"))
-                            .wrap_err("on_event must have enum return value")?;
-                        Python::with_gil(|py| status_val.extract(py))
-                            .wrap_err("on_event has invalid return value")
+                let input_id = match &callback {
+                    Callback::Event(Event::Input { id, .. }) => Some(id.clone()),
+                    _ => None,
+                };
+                let result = match callback {
+                    Callback::Event(event) => {
+                        let is_input = matches!(event, Event::Input { .. });
+                        let Some(method) = event_method(operator.bind(py), is_input)? else {
+                            return Ok(DoraStatus::Continue as i32);
+                        };
+                        let py_event = PyEvent::from(event)
+                            .to_py_dict(py)
+                            .context("Could not convert event to pydict bound")?;
+                        operator
+                            .bind(py)
+                            .call_method1(method, (py_event, send_output.clone()))
+                    }
+                    Callback::Batch(events) => {
+                        let py_events = events
+                            .into_iter()
+                            .map(|event| PyEvent::from(event).to_py_dict(py))
+                            .collect::<Result<Vec<_>, _>>()
+                            .context("Could not convert events to pydict bound")?;
+                        operator
+                            .bind(py)
+                            .call_method1("on_inputs", (py_events, send_output.clone()))
                     }
+                    Callback::Timer(timer_id) if operator.bind(py).hasattr("on_timer")? => operator
+                        .bind(py)
+                        .call_method1("on_timer", (timer_id, send_output.clone())),
+                    Callback::Timer(timer_id) => {
+                        let py_event = PyDict::new_bound(py);
+                        py_event.set_item("type", "TIMER")?;
+                        py_event.set_item("id", timer_id)?;
+                        operator
+                            .bind(py)
+                            .call_method1("on_event", (py_event, send_output.clone()))
+                    }
+                };
+                let status_enum = result
+                    .and_then(|r| run_if_awaitable(py, event_loop, r))
+                    .map_err(|err| exception(py, err, operator_id, input_id));
+                match status_enum {
+                    Ok(value) => return_status(py, &value, &send_output),
                     Err(err) => {
                         if reload {
                             // Allow error in hot reloading environment to help development.
                             warn!("{err}");
                             Ok(DoraStatus::Continue as i32)
                         } else {
+                            // persist the state so that a restarted operator can continue
+                            if let Err(err) = save_state(py, &operator, &state_path) {
+                                warn!("failed to save operator state: {err:?}");
+                            }
                             Err(err)
                         }
                     }
                 }
-            })?;
+            });
+            drop(running);
+            drop(worker);
+            let status = status?;
             match status {
                 s if s == DoraStatus::Continue as i32 => {} // ok
                 s if s == DoraStatus::Stop as i32 => break StopReason::ExplicitStop,
@@ -243,9 +393,31 @@ pub fn run(
 
         // Dropping the operator using Python garbage collector.
         // Locking the GIL for immediate release.
-        Python::with_gil(|_py| {
+        Python::with_gil(|py| -> Result<()> {
+            let bound = operator.bind(py);
+            // save the state even if `on_stop` fails, then report its error
+            let stopped = if bound.hasattr("on_stop")? {
+                bound
+                    .call_method0("on_stop")
+                    .and_then(|r| run_if_awaitable(py, event_loop, r))
+                    .map(|_| ())
+                    .map_err(traceback)
+                    .wrap_err("on_stop failed")
+            } else {
+                Ok(())
+            };
+            if let Err(err) = save_state(py, &operator, &state_path) {
+                warn!("failed to save operator state: {err:?}");
+            }
             drop(operator);
-        });
+            if let Some(event_loop) = event_loop.get(py) {
+                event_loop
+                    .call_method0(py, "close")
+                    .map_err(traceback)
+                    .wrap_err("failed to close asyncio event loop")?;
+            }
+            stopped
+        })?;
 
         Result::<_, eyre::Report>::Ok(reason)
     };
@@ -269,10 +441,362 @@ pub fn run(
     Ok(())
 }
 
+/// Interprets the return value of an event callback.
+///
+/// Besides a `DoraStatus`, callbacks may return `None` to continue, or a dict
+/// that maps output IDs to data. The entries of the dict are sent like through
+/// `send_output` before the operator continues.
+fn return_status(
+    py: Python,
+    value: &Bound<'_, PyAny>,
+    send_output: &SendOutputCallback,
+) -> Result<i32> {
+    if value.is_none() {
+        return Ok(DoraStatus::Continue as i32);
+    }
+    if let Ok(outputs) = value.downcast::<PyDict>() {
+        let send_output = Py::new(py, send_output.clone())?;
+        for (output_id, data) in outputs.iter() {
+            send_output
+                .call1(py, (output_id, data))
+                .map_err(traceback)
+                .wrap_err("failed to send returned output")?;
+        }
+        return Ok(DoraStatus::Continue as i32);
+    }
+    value
+        .getattr("value")
+        .wrap_err("callback must return a `DoraStatus`, `None`, or a dict of outputs")?
+        .extract()
+        .wrap_err("callback returned invalid status")
+}
+
+/// Returns the name of the method that handles the given event, if any.
+///
+/// Operators can receive inputs through `on_input` instead of `on_event`. In
+/// that case, `on_event` is optional and only called for the other events,
+/// e.g. to flush buffered data when an input is closed or the dataflow stops.
+fn event_method(operator: &Bound<'_, PyAny>, is_input: bool) -> PyResult<Option<&'static str>> {
+    if is_input && operator.hasattr("on_input")? {
+        Ok(Some("on_input"))
+    } else if operator.hasattr("on_event")? {
+        Ok(Some("on_event"))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The operator method to call next.
+enum Callback {
+    Event(Event),
+    /// Passed to `on_inputs` if the operator enables `_unstable_batch_inputs`.
+    Batch(Vec<Event>),
+    Timer(String),
+}
+
+enum Next {
+    Input(OperatorInput),
+    SourceChanged,
+    /// The timer tasks were stopped, e.g. because the runtime is shutting down.
+    TimersStopped,
+}
+
+/// Runs coroutines returned by `async def` callbacks to completion.
+///
+/// All coroutines of an operator run on the same asyncio event loop, which
+/// lives on the operator thread. Thus, tasks spawned by one callback keep
+/// running while later callbacks are awaited.
+fn run_if_awaitable<'py>(
+    py: Python<'py>,
+    event_loop: &GILOnceCell<Py<PyAny>>,
+    value: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let is_awaitable = py
+        .import_bound("inspect")?
+        .call_method1("isawaitable", (&value,))?
+        .is_truthy()?;
+    if !is_awaitable {
+        return Ok(value);
+    }
+    let event_loop = event_loop.get_or_try_init(py, || -> PyResult<_> {
+        let asyncio = py.import_bound("asyncio")?;
+        let event_loop = asyncio.call_method0("new_event_loop")?;
+        asyncio.call_method1("set_event_loop", (&event_loop,))?;
+        Ok(event_loop.unbind())
+    })?;
+    event_loop
+        .bind(py)
+        .call_method1("run_until_complete", (value,))
+}
+
+/// Replaces the operator with a new instance of the reloaded module, keeping
+/// the state of the current instance.
+///
+/// The state is transferred through the `save_state`/`load_state` methods if
+/// the operator defines them. Otherwise, the attributes of the current instance
+/// are copied.
+///
+/// The previous instance is dropped while holding the GIL. If the reload fails,
+/// the current instance is kept.
+fn replace_operator(
+    operator: &mut Py<PyAny>,
+    module_name: &str,
+    config: &BTreeMap<String, serde_json::Value>,
+) {
+    let result = Python::with_gil(|py| -> Result<()> {
+        let reloaded = reload_operator(py, operator, module_name, config)?;
+        drop(std::mem::replace(operator, reloaded));
+        Ok(())
+    });
+    if let Err(err) = result {
+        error!("Failed to reload operator.\n {err}");
+    }
+}
+
+/// Creates an instance of the `Operator` class, passing the `config` entries of
+/// the operator as keyword arguments.
+fn create_operator<'py>(
+    operator_class: &Bound<'py, PyAny>,
+    config: &BTreeMap<String, serde_json::Value>,
+) -> Result<Bound<'py, PyAny>> {
+    let py = operator_class.py();
+    let kwargs = PyDict::new_bound(py);
+    for (key, value) in config {
+        kwargs.set_item(key, pythonize::pythonize(py, value)?)?;
+    }
+    operator_class.call((), Some(&kwargs)).map_err(traceback)
+}
+
+fn reload_operator(
+    py: Python,
+    operator: &Py<PyAny>,
+    module_name: &str,
+    config: &BTreeMap<String, serde_json::Value>,
+) -> Result<Py<PyAny>> {
+    // Reload module
+    let module = py
+        .import_bound(module_name)
+        .map_err(traceback)
+        .wrap_err(format!("Could not retrieve {module_name} while reloading"))?;
+    let importlib = py
+        .import_bound("importlib")
+        .wrap_err("failed to import `importlib` module")?;
+    let module = importlib
+        .call_method("reload", (module,), None)
+        .wrap_err(format!("Could not reload {module_name} while reloading"))?;
+    let reloaded_operator_class = module
+        .getattr("Operator")
+        .wrap_err("no `Operator` class found in module")?;
+
+    // Create a new reloaded operator
+    let reloaded: Py<pyo3::PyAny> = create_operator(&reloaded_operator_class, config)
+        .wrap_err("Could not initialize reloaded operator")?
+        .into();
+
+    let current = operator.bind(py);
+    let new = reloaded.bind(py);
+    if current.hasattr("save_state")? && new.hasattr("load_state")? {
+        // Transfer the state through the state hooks of the operator
+        let state = current
+            .call_method0("save_state")
+            .map_err(traceback)
+            .wrap_err("Could not save operator state")?;
+        new.call_method1("load_state", (state,))
+            .map_err(traceback)
+            .wrap_err("Could not load operator state")?;
+    } else {
+        // Replace initialized state with current state
+        let current_state = current
+            .getattr("__dict__")
+            .wrap_err("Could not retrieve current operator state")?;
+        let current_state = current_state
+            .downcast::<PyDict>()
+            .map_err(|err| eyre!("could not extract operator state as a PyDict: {err}"))?;
+        new.getattr("__dict__")
+            .wrap_err("Could not retrieve new operator state")?
+            .downcast::<PyDict>()
+            .map_err(|err| eyre!("could not extract new operator state as a PyDict: {err}"))?
+            .update(current_state.as_mapping())
+            .wrap_err("could not restore operator state")?;
+    }
+    // the metrics and logger handles are not part of the user-defined state
+    for handle in ["metrics", "logger"] {
+        if let Ok(value) = current.getattr(handle) {
+            new.setattr(handle, value)?;
+        }
+    }
+
+    Ok(reloaded)
+}
+
+/// Location where the state of an operator is persisted between restarts.
+///
+/// The path is scoped to the dataflow run, next to its logs, so that a new
+/// `dora start` doesn't restore the state of an earlier, unrelated run.
+fn state_path(dataflow_id: DataflowId, node_id: &NodeId, operator_id: &OperatorId) -> PathBuf {
+    Path::new("out")
+        .join(dataflow_id.to_string())
+        .join("state")
+        .join(node_id.to_string())
+        .join(format!("{operator_id}.pickle"))
+}
+
+/// Persists the value returned by the optional `save_state` method of the operator.
+fn save_state(py: Python, operator: &Py<PyAny>, path: &Path) -> Result<()> {
+    let operator = operator.bind(py);
+    if !operator.hasattr("save_state")? {
+        return Ok(());
+    }
+    let state = operator.call_method0("save_state").map_err(traceback)?;
+    let pickled = py
+        .import_bound("pickle")?
+        .call_method1("dumps", (state,))
+        .map_err(traceback)
+        .wrap_err("failed to pickle operator state")?;
+    let bytes = pickled
+        .downcast::<PyBytes>()
+        .map_err(|err| eyre!("unexpected pickle result: {err}"))?
+        .as_bytes();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .wrap_err_with(|| format!("failed to create `{}`", parent.display()))?;
+    }
+    // write to a temporary file first to keep the previous state on failure
+    let tmp_path = path.with_extension("pickle.tmp");
+    std::fs::write(&tmp_path, bytes)
+        .wrap_err_with(|| format!("failed to write `{}`", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path)
+        .wrap_err_with(|| format!("failed to move state to `{}`", path.display()))?;
+    Ok(())
+}
+
+/// Passes the persisted state to the optional `load_state` method of the operator.
+fn load_state(py: Python, operator: &Bound<'_, PyAny>, path: &Path) -> Result<()> {
+    if !path.exists() || !operator.hasattr("load_state")? {
+        return Ok(());
+    }
+    let bytes = std::fs::read(path).wrap_err("failed to read state file")?;
+    let state = py
+        .import_bound("pickle")?
+        .call_method1("loads", (PyBytes::new_bound(py, &bytes),))
+        .map_err(traceback)
+        .wrap_err("failed to unpickle operator state")?;
+    operator
+        .call_method1("load_state", (state,))
+        .map_err(traceback)?;
+    Ok(())
+}
+
+/// Watches the given source file and sends a notification on each change.
+fn watch_source_file(path: &Path, changed_tx: flume::Sender<()>) -> Result<RecommendedWatcher> {
+    let source_path = path.to_owned();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<NotifyEvent>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_))
+            && event.paths.contains(&source_path)
+        {
+            // the channel is full if a reload is already pending
+            let _ = changed_tx.try_send(());
+        }
+    })
+    .wrap_err("failed to create file watcher")?;
+    // watch the parent directory because editors often replace the file on save
+    let watch_path = path.parent().unwrap_or(path);
+    watcher
+        .watch(watch_path, RecursiveMode::NonRecursive)
+        .wrap_err_with(|| format!("failed to watch `{}`", watch_path.display()))?;
+    Ok(watcher)
+}
+
 #[pyclass]
 #[derive(Clone)]
 struct SendOutputCallback {
     events_tx: Sender<OperatorEvent>,
+    /// Trace context of the input that is currently handled, used for
+    /// outputs that are sent without metadata.
+    input_context: Arc<Mutex<String>>,
+}
+
+impl SendOutputCallback {
+    fn set_input_context(&self, context: &str) {
+        if let Ok(mut current) = self.input_context.lock() {
+            current.clear();
+            current.push_str(context);
+        }
+    }
+}
+
+/// Records metrics of the operator, available as `self.metrics`.
+///
+/// `e.g.:  self.metrics.record_histogram("planning_time", elapsed)`
+#[pyclass(module = "dora")]
+#[derive(Clone)]
+pub struct Metrics {
+    metrics: OperatorMetrics,
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+mod metrics_impl {
+    use super::Metrics;
+    use dora_operator_api_types::MetricKind;
+    use pyo3::pymethods;
+
+    #[pymethods]
+    impl Metrics {
+        /// Adds `value` to the counter with the given name.
+        #[pyo3(signature = (name, value=1.0))]
+        fn add_counter(&self, name: &str, value: f64) {
+            self.metrics.record(name, MetricKind::Counter, value)
+        }
+
+        /// Sets the gauge with the given name to `value`.
+        fn set_gauge(&self, name: &str, value: f64) {
+            self.metrics.record(name, MetricKind::Gauge, value)
+        }
+
+        /// Records `value` in the histogram with the given name.
+        fn record_histogram(&self, name: &str, value: f64) {
+            self.metrics.record(name, MetricKind::Histogram, value)
+        }
+    }
+}
+
+/// Emits structured log records of the operator, available as `self.logger`.
+///
+/// `e.g.:  self.logger.log("warn", "no detections", {"frame": 42})`
+#[pyclass(module = "dora")]
+#[derive(Clone)]
+pub struct Logger {
+    events_tx: Sender<OperatorEvent>,
+    operator_id: OperatorId,
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+mod logger_impl {
+    use super::{Logger, OperatorEvent};
+    use dora_operator_api_python::py_log_record;
+    use eyre::eyre;
+    use pyo3::{pymethods, types::PyDict, Bound, Python};
+
+    #[pymethods]
+    impl Logger {
+        /// Emits a log record through the daemon, see `Node.log`.
+        #[pyo3(signature = (level, message, fields=None))]
+        fn log(
+            &self,
+            py: Python,
+            level: &str,
+            message: String,
+            fields: Option<Bound<'_, PyDict>>,
+        ) -> eyre::Result<()> {
+            let record = py_log_record(level, message, fields, Some(self.operator_id.clone()))?;
+            py.allow_threads(|| self.events_tx.blocking_send(OperatorEvent::Log(record)))
+                .map_err(|_| eyre!("failed to send log record to runtime"))
+        }
+    }
 }
 
 #[allow(unsafe_op_in_unsafe_fn)]
@@ -282,13 +806,17 @@ mod callback_impl {
 
     use super::SendOutputCallback;
     use aligned_vec::{AVec, ConstAlign};
-    use arrow::{array::ArrayData, pyarrow::FromPyArrow};
+    use arrow::{
+        array::{Array, ArrayData, StructArray},
+        pyarrow::FromPyArrow,
+        record_batch::RecordBatch,
+    };
     use dora_core::message::ArrowTypeInfo;
     use dora_node_api::{
         arrow_utils::{copy_array_into_sample, required_data_size},
         ZERO_COPY_THRESHOLD,
     };
-    use dora_operator_api_python::pydict_to_metadata;
+    use dora_operator_api_python::{buffer_to_pyarrow, pydict_to_metadata};
     use dora_tracing::telemetry::deserialize_context;
     use eyre::{eyre, Context, Result};
     use pyo3::{
@@ -302,8 +830,12 @@ mod callback_impl {
 
     /// Send an output from the operator:
     /// - the first argument is the `output_id` as defined in your dataflow.
-    /// - the second argument is the data as either bytes or pyarrow.Array for zero copy.
+    /// - the second argument is the data as either bytes or pyarrow.Array for zero copy. Numpy
+    ///   arrays, `memoryview`, and `bytearray` objects are accepted too, without an extra copy.
+    ///   A pyarrow.RecordBatch is sent as a struct array and shows up as `dora_event["batch"]`
+    ///   in downstream operators.
     /// - the third argument is dora metadata if you want to link the tracing from one input into an output.
+    ///   Without it, the output continues the trace of the input that is currently handled.
     /// `e.g.:  send_output("bbox", pa.array([100], type=pa.uint8()), dora_event["metadata"])`
     #[pymethods]
     impl SendOutputCallback {
@@ -314,9 +846,14 @@ mod callback_impl {
             metadata: Option<Bound<'_, PyDict>>,
             py: Python,
         ) -> Result<()> {
-            let parameters = pydict_to_metadata(metadata)
+            let mut parameters = pydict_to_metadata(metadata)
                 .wrap_err("failed to parse metadata")?
                 .into_owned();
+            if parameters.open_telemetry_context.is_empty() {
+                if let Ok(current) = self.input_context.lock() {
+                    parameters.open_telemetry_context.clone_from(&current);
+                }
+            }
             let span = span!(
                 tracing::Level::TRACE,
                 "send_output",
@@ -347,20 +884,39 @@ mod callback_impl {
                 }
             };
 
+            // Allocating the sample might wait for the runtime and copying large
+            // payloads takes a while, so both happen with the GIL released to
+            // not block the other Python operators of this node. The source
+            // data stays alive because we keep a reference to the Python object.
+            let copy_array = |arrow_array: &ArrayData| -> Result<_> {
+                py.allow_threads(|| {
+                    let total_len = required_data_size(arrow_array);
+                    let mut sample = allocate_sample(total_len)?;
+
+                    let type_info = copy_array_into_sample(&mut sample, arrow_array);
+
+                    Ok((sample, type_info))
+                })
+            };
+
             let (sample, type_info) = if let Ok(py_bytes) = data.downcast_bound::<PyBytes>(py) {
                 let data = py_bytes.as_bytes();
-                let mut sample = allocate_sample(data.len())?;
-                sample.copy_from_slice(data);
-                (sample, ArrowTypeInfo::byte_array(data.len()))
+                py.allow_threads(|| -> Result<_> {
+                    let mut sample = allocate_sample(data.len())?;
+                    sample.copy_from_slice(data);
+                    Ok((sample, ArrowTypeInfo::byte_array(data.len())))
+                })?
             } else if let Ok(arrow_array) = ArrayData::from_pyarrow_bound(data.bind(py)) {
-                let total_len = required_data_size(&arrow_array);
-                let mut sample = allocate_sample(total_len)?;
-
-                let type_info = copy_array_into_sample(&mut sample, &arrow_array);
-
-                (sample, type_info)
+                copy_array(&arrow_array)?
+            } else if let Ok(batch) = RecordBatch::from_pyarrow_bound(data.bind(py)) {
+                copy_array(&StructArray::from(batch).to_data())?
+            } else if let Some(array) = buffer_to_pyarrow(data.bind(py))? {
+                copy_array(&ArrayData::from_pyarrow_bound(&array)?)?
             } else {
-                eyre::bail!("invalid `data` type, must by `PyBytes` or arrow array")
+                eyre::bail!(
+                    "invalid `data` type, must by `PyBytes`, arrow array, arrow record batch, \
+                    numpy array, `memoryview`, or `bytearray`"
+                )
             };
 
             py.allow_threads(|| {
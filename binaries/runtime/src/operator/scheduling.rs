@@ -0,0 +1,96 @@
+//! Applies the `_unstable_scheduling` settings of an operator to its thread.
+//!
+//! Failures are not fatal: real-time scheduling and negative nice values
+//! usually require privileges (`CAP_SYS_NICE` or a suitable `rtprio` limit), so
+//! the operator keeps running with the default settings and a warning is logged.
+
+use dora_core::{config::OperatorId, descriptor::OperatorScheduling};
+
+/// Applies the scheduling settings to the calling thread.
+pub fn apply(operator_id: &OperatorId, scheduling: &OperatorScheduling) {
+    #[cfg(target_os = "linux")]
+    {
+        if !scheduling.cpu_affinity.is_empty() {
+            if let Err(err) = linux::set_affinity(&scheduling.cpu_affinity) {
+                tracing::warn!(
+                    "failed to set CPU affinity of operator `{operator_id}` to {:?}: {err}",
+                    scheduling.cpu_affinity
+                );
+            }
+        }
+        if let Err(err) = linux::set_policy(scheduling) {
+            tracing::warn!(
+                "failed to set scheduling policy of operator `{operator_id}` \
+                (policy: {:?}, priority: {:?}): {err}{}",
+                scheduling.policy,
+                scheduling.priority,
+                if err.kind() == std::io::ErrorKind::PermissionDenied {
+                    " (requires `CAP_SYS_NICE` or a suitable `rtprio` limit)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = scheduling;
+        tracing::warn!(
+            "ignoring scheduling settings of operator `{operator_id}`: \
+            not supported on this platform"
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use dora_core::descriptor::{OperatorScheduling, SchedulingPolicy};
+    use std::io;
+
+    pub fn set_affinity(cpus: &[usize]) -> io::Result<()> {
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &cpu in cpus {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("CPU index {cpu} is out of range"),
+                ));
+            }
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        // a pid of 0 refers to the calling thread
+        let result =
+            unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn set_policy(scheduling: &OperatorScheduling) -> io::Result<()> {
+        let policy = match scheduling.policy {
+            SchedulingPolicy::Other => {
+                if let Some(nice) = scheduling.priority {
+                    // nice values are per thread on Linux
+                    let tid = unsafe { libc::syscall(libc::SYS_gettid) };
+                    let result =
+                        unsafe { libc::setpriority(libc::PRIO_PROCESS as _, tid as _, nice) };
+                    if result != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                return Ok(());
+            }
+            SchedulingPolicy::Fifo => libc::SCHED_FIFO,
+            SchedulingPolicy::RoundRobin => libc::SCHED_RR,
+        };
+        let param = libc::sched_param {
+            sched_priority: scheduling.priority.unwrap_or(1),
+        };
+        let result = unsafe { libc::pthread_setschedparam(libc::pthread_self(), policy, &param) };
+        if result != 0 {
+            return Err(io::Error::from_raw_os_error(result));
+        }
+        Ok(())
+    }
+}
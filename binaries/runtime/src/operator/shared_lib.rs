@@ -1,4 +1,7 @@
-use super::{OperatorEvent, StopReason};
+use super::{
+    metrics::OperatorMetrics, timers::next_input, watchdog::CallbackTimer, worker_pool::WorkerPool,
+    OperatorEvent, OperatorInput, StopReason,
+};
 use aligned_vec::{AVec, ConstAlign};
 use dora_core::{
     adjust_shared_library_path,
@@ -11,27 +14,35 @@ use dora_node_api::{
     Event, MetadataParameters,
 };
 use dora_operator_api_types::{
-    safer_ffi::closure::ArcDynFn1, DoraDropOperator, DoraInitOperator, DoraInitResult, DoraOnEvent,
-    DoraResult, DoraStatus, Metadata, OnEventResult, Output, SendOutput,
+    safer_ffi::{char_p, closure::ArcDynFn1},
+    DoraDropOperator, DoraInitOperator, DoraInitOperatorWithConfig, DoraInitResult,
+    DoraOnConfigure, DoraOnEvent, DoraOnMetrics, DoraOnStart, DoraOnStop, DoraOperatorAbi,
+    DoraResult, DoraStatus, Metadata, OnEventResult, Output, RecordMetric, SendOutput,
 };
 use eyre::{bail, eyre, Context, Result};
 use libloading::Symbol;
 use std::{
-    ffi::c_void,
+    ffi::{c_void, CString},
     panic::{catch_unwind, AssertUnwindSafe},
     path::Path,
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 use tokio::sync::{mpsc::Sender, oneshot};
 use tracing::{field, span};
 
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     node_id: &NodeId,
     operator_id: &OperatorId,
     source: &str,
     events_tx: Sender<OperatorEvent>,
     incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
     init_done: oneshot::Sender<Result<()>>,
+    parameters: String,
+    config: String,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
 ) -> eyre::Result<()> {
     let path = if source_is_url(source) {
         let target_path = adjust_shared_library_path(
@@ -54,17 +65,23 @@ pub fn run(
         libloading::Library::new(&path)
             .wrap_err_with(|| format!("failed to load shared library at `{}`", path.display()))?
     };
+    check_abi_version(&library)
+        .wrap_err_with(|| format!("incompatible operator at `{}`", path.display()))?;
 
     let closure = AssertUnwindSafe(|| {
         let bindings = Bindings::init(&library).context("failed to init operator")?;
 
         let operator = SharedLibraryOperator {
             incoming_events,
+            timers,
             bindings,
             events_tx: events_tx.clone(),
+            callback_timer,
+            worker_pool,
+            record_metric: OperatorMetrics::new(node_id, operator_id).ffi_callback(),
         };
 
-        operator.run(init_done)
+        operator.run(init_done, &parameters, &config)
     });
     match catch_unwind(closure) {
         Ok(Ok(reason)) => {
@@ -83,18 +100,34 @@ pub fn run(
 
 struct SharedLibraryOperator<'lib> {
     incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
     events_tx: Sender<OperatorEvent>,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
+    /// Passed to the operator by reference, so it needs to outlive the operator.
+    record_metric: RecordMetric,
 
     bindings: Bindings<'lib>,
 }
 
 impl<'lib> SharedLibraryOperator<'lib> {
-    fn run(self, init_done: oneshot::Sender<Result<()>>) -> eyre::Result<StopReason> {
+    fn run(
+        self,
+        init_done: oneshot::Sender<Result<()>>,
+        parameters: &str,
+        config: &str,
+    ) -> eyre::Result<StopReason> {
         let operator_context = {
             let DoraInitResult {
                 result,
                 operator_context,
-            } = unsafe { (self.bindings.init_operator.init_operator)() };
+            } = match self.init(config) {
+                Ok(result) => result,
+                Err(err) => {
+                    let _ = init_done.send(Err(eyre!("{err:?}")));
+                    return Err(err);
+                }
+            };
             let raw = match result.error {
                 Some(error) => {
                     let _ = init_done.send(Err(eyre!(error.to_string())));
@@ -108,8 +141,16 @@ impl<'lib> SharedLibraryOperator<'lib> {
             }
         };
 
+        if let Err(err) = self.start(&operator_context, parameters) {
+            let _ = init_done.send(Err(eyre!("{err:?}")));
+            return Err(err);
+        }
         let _ = init_done.send(Ok(()));
 
+        let events_tx = self.events_tx.clone();
+        // trace context of the input that is currently handled by the operator
+        let input_context = Arc::new(Mutex::new(String::new()));
+        let current_input_context = input_context.clone();
         let send_output_closure = Arc::new(move |output: Output| {
             let Output {
                 id: output_id,
@@ -119,8 +160,14 @@ impl<'lib> SharedLibraryOperator<'lib> {
                     open_telemetry_context,
                 },
             } = output;
+            let mut open_telemetry_context: String = open_telemetry_context.into();
+            if open_telemetry_context.is_empty() {
+                if let Ok(current) = current_input_context.lock() {
+                    open_telemetry_context.clone_from(&current);
+                }
+            }
             let parameters = MetadataParameters {
-                open_telemetry_context: open_telemetry_context.into(),
+                open_telemetry_context,
                 ..Default::default()
             };
 
@@ -141,8 +188,7 @@ impl<'lib> SharedLibraryOperator<'lib> {
                 data: Some(sample.into()),
             };
 
-            let result = self
-                .events_tx
+            let result = events_tx
                 .blocking_send(event)
                 .map_err(|_| eyre!("failed to send output to runtime"));
 
@@ -154,8 +200,26 @@ impl<'lib> SharedLibraryOperator<'lib> {
 
         let reason = loop {
             #[allow(unused_mut)]
-            let Ok(mut event) = self.incoming_events.recv() else {
-                break StopReason::InputsClosed;
+            let mut event = match next_input(&self.incoming_events, &self.timers) {
+                Some(OperatorInput::Event(event)) => event,
+                Some(OperatorInput::Timer(timer_id)) => {
+                    let mut timer_event = dora_operator_api_types::RawEvent {
+                        input: None,
+                        input_closed: None,
+                        stop: false,
+                        error: None,
+                        timer: Some(timer_id.into()),
+                    };
+                    match self.call_on_event(
+                        &mut timer_event,
+                        &send_output_closure,
+                        &operator_context,
+                    )? {
+                        Some(reason) => break reason,
+                        None => continue,
+                    }
+                }
+                None => break StopReason::InputsClosed,
             };
 
             let span = span!(tracing::Level::TRACE, "on_event", input_id = field::Empty);
@@ -186,12 +250,16 @@ impl<'lib> SharedLibraryOperator<'lib> {
                     input_closed: None,
                     stop: true,
                     error: None,
+                    timer: None,
                 },
                 Event::Input {
                     id: input_id,
                     metadata,
                     data,
                 } => {
+                    if let Ok(mut current) = input_context.lock() {
+                        current.clone_from(&metadata.parameters.open_telemetry_context);
+                    }
                     let (data_array, schema) = arrow::ffi::to_ffi(&data.to_data())?;
 
                     let operator_input = dora_operator_api_types::Input {
@@ -210,6 +278,7 @@ impl<'lib> SharedLibraryOperator<'lib> {
                         input_closed: None,
                         stop: false,
                         error: None,
+                        timer: None,
                     }
                 }
                 Event::InputClosed { id: input_id } => dora_operator_api_types::RawEvent {
@@ -217,6 +286,7 @@ impl<'lib> SharedLibraryOperator<'lib> {
                     input: None,
                     stop: false,
                     error: None,
+                    timer: None,
                 },
                 Event::Reload { .. } => {
                     // Reloading shared lib operator is not supported. See: https://github.com/dora-rs/dora/pull/239#discussion_r1154313139
@@ -227,6 +297,7 @@ impl<'lib> SharedLibraryOperator<'lib> {
                     input_closed: None,
                     input: None,
                     stop: false,
+                    timer: None,
                 },
                 other => {
                     tracing::warn!("unexpected event: {other:?}");
@@ -234,30 +305,135 @@ impl<'lib> SharedLibraryOperator<'lib> {
                 }
             };
 
-            let send_output = SendOutput {
-                send_output: ArcDynFn1::new(send_output_closure.clone()),
-            };
-            let OnEventResult {
-                result: DoraResult { error },
-                status,
-            } = unsafe {
-                (self.bindings.on_event.on_event)(
-                    &mut operator_event,
-                    &send_output,
+            if let Some(reason) =
+                self.call_on_event(&mut operator_event, &send_output_closure, &operator_context)?
+            {
+                break reason;
+            }
+        };
+
+        if let Some(on_stop) = &self.bindings.on_stop {
+            let DoraResult { error } = unsafe { (on_stop.on_stop)(operator_context.raw) };
+            if let Some(error) = error {
+                bail!("on_stop failed: {}", *error);
+            }
+        }
+        Ok(reason)
+    }
+
+    /// Passes the given event to the operator and returns the stop reason if
+    /// the operator wants to stop.
+    fn call_on_event(
+        &self,
+        event: &mut dora_operator_api_types::RawEvent,
+        send_output_closure: &Arc<impl Fn(Output) -> DoraResult + Send + Sync + 'static>,
+        operator_context: &OperatorContext,
+    ) -> eyre::Result<Option<StopReason>> {
+        let send_output = SendOutput {
+            send_output: ArcDynFn1::new(send_output_closure.clone()),
+        };
+        let OnEventResult {
+            result: DoraResult { error },
+            status,
+        } = unsafe {
+            let _worker = self.worker_pool.acquire();
+            let _running = self.callback_timer.start();
+            (self.bindings.on_event.on_event)(event, &send_output, operator_context.raw)
+        };
+        match error {
+            Some(error) => bail!("on_input failed: {}", *error),
+            None => match status {
+                DoraStatus::Continue => Ok(None),
+                DoraStatus::Stop => Ok(Some(StopReason::ExplicitStop)),
+                DoraStatus::StopAll => Ok(Some(StopReason::ExplicitStopAll)),
+            },
+        }
+    }
+
+    fn init(&self, config: &str) -> eyre::Result<DoraInitResult> {
+        match &self.bindings.init_operator_with_config {
+            Some(init) => {
+                let config =
+                    CString::new(config).wrap_err("operator config contains a nul byte")?;
+                Ok(unsafe {
+                    (init.init_operator_with_config.0)(char_p::char_p_ref::from(config.as_c_str()))
+                })
+            }
+            None => {
+                if config != "{}" {
+                    tracing::warn!(
+                        "operator has a `config`, but doesn't define \
+                        `dora_init_operator_with_config`"
+                    );
+                }
+                Ok(unsafe { (self.bindings.init_operator.init_operator)() })
+            }
+        }
+    }
+
+    fn start(&self, operator_context: &OperatorContext, parameters: &str) -> eyre::Result<()> {
+        if let Some(on_metrics) = &self.bindings.on_metrics {
+            let DoraResult { error } =
+                unsafe { (on_metrics.on_metrics.0)(&self.record_metric, operator_context.raw) };
+            if let Some(error) = error {
+                bail!("on_metrics failed: {}", *error);
+            }
+        }
+        if let Some(on_configure) = &self.bindings.on_configure {
+            let parameters =
+                CString::new(parameters).wrap_err("operator parameters contain a nul byte")?;
+            let DoraResult { error } = unsafe {
+                (on_configure.on_configure.0)(
+                    char_p::char_p_ref::from(parameters.as_c_str()),
                     operator_context.raw,
                 )
             };
-            match error {
-                Some(error) => bail!("on_input failed: {}", *error),
-                None => match status {
-                    DoraStatus::Continue => {}
-                    DoraStatus::Stop => break StopReason::ExplicitStop,
-                    DoraStatus::StopAll => break StopReason::ExplicitStopAll,
-                },
+            if let Some(error) = error {
+                bail!("on_configure failed: {}", *error);
+            }
+        }
+        if let Some(on_start) = &self.bindings.on_start {
+            let DoraResult { error } = unsafe { (on_start.on_start)(operator_context.raw) };
+            if let Some(error) = error {
+                bail!("on_start failed: {}", *error);
             }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the operator was built for the same operator ABI as the runtime.
+///
+/// Calling into an operator with a different ABI is undefined behavior, so
+/// this needs to happen before any other operator function is called.
+fn check_abi_version(library: &libloading::Library) -> eyre::Result<()> {
+    let runtime = DoraOperatorAbi::CURRENT;
+    let abi: Symbol<*const DoraOperatorAbi> = match unsafe { library.get(b"dora_operator_abi") } {
+        Ok(abi) => abi,
+        Err(_) => {
+            tracing::warn!(
+                "operator does not export `dora_operator_abi`, so its compatibility with \
+                dora {} can't be checked",
+                runtime.dora_version().unwrap_or("unknown")
+            );
+            return Ok(());
+        }
+    };
+    let operator = unsafe { &**abi };
+    if operator.abi_version != runtime.abi_version {
+        let operator_version = match operator.dora_version() {
+            Some(version) => format!("dora {version}"),
+            None => "an unknown dora version".into(),
         };
-        Ok(reason)
+        bail!(
+            "operator built against {operator_version} (operator ABI v{}), runtime is dora {} \
+            (operator ABI v{}); rebuild the operator against the dora version of the runtime",
+            operator.abi_version,
+            runtime.dora_version().unwrap_or("unknown"),
+            runtime.abi_version,
+        );
     }
+    Ok(())
 }
 
 struct OperatorContext<'lib> {
@@ -273,8 +449,13 @@ impl<'lib> Drop for OperatorContext<'lib> {
 
 struct Bindings<'lib> {
     init_operator: Symbol<'lib, DoraInitOperator>,
+    init_operator_with_config: Option<Symbol<'lib, DoraInitOperatorWithConfig>>,
     drop_operator: Symbol<'lib, DoraDropOperator>,
     on_event: Symbol<'lib, DoraOnEvent>,
+    on_configure: Option<Symbol<'lib, DoraOnConfigure>>,
+    on_start: Option<Symbol<'lib, DoraOnStart>>,
+    on_stop: Option<Symbol<'lib, DoraOnStop>>,
+    on_metrics: Option<Symbol<'lib, DoraOnMetrics>>,
 }
 
 impl<'lib> Bindings<'lib> {
@@ -284,12 +465,18 @@ impl<'lib> Bindings<'lib> {
                 init_operator: library
                     .get(b"dora_init_operator")
                     .wrap_err("failed to get `dora_init_operator`")?,
+                init_operator_with_config: library.get(b"dora_init_operator_with_config").ok(),
                 drop_operator: library
                     .get(b"dora_drop_operator")
                     .wrap_err("failed to get `dora_drop_operator`")?,
                 on_event: library
                     .get(b"dora_on_event")
                     .wrap_err("failed to get `dora_on_event`")?,
+                // the lifecycle hooks are optional
+                on_configure: library.get(b"dora_on_configure").ok(),
+                on_start: library.get(b"dora_on_start").ok(),
+                on_stop: library.get(b"dora_on_stop").ok(),
+                on_metrics: library.get(b"dora_on_metrics").ok(),
             }
         };
         Ok(bindings)
@@ -0,0 +1,62 @@
+//! Drives the `_unstable_timers` of operators.
+//!
+//! Each timer is a task on the runtime's event loop that sends the timer ID to
+//! the operator at the configured interval. The channel holds at most one
+//! pending tick, so ticks that are due while the operator is still busy are
+//! dropped instead of piling up.
+
+use super::OperatorInput;
+use dora_core::descriptor::OperatorTimer;
+use dora_node_api::Event;
+use std::collections::BTreeMap;
+use tokio::{runtime::Handle, time::MissedTickBehavior};
+
+/// Starts the given timers and returns the receiver for their ticks.
+///
+/// The receiver is disconnected if there are no timers.
+pub fn spawn(handle: &Handle, timers: &BTreeMap<String, OperatorTimer>) -> flume::Receiver<String> {
+    let (tx, rx) = flume::bounded(1);
+    for (timer_id, timer) in timers {
+        let timer_id = timer_id.clone();
+        let tx = tx.clone();
+        let mut interval = {
+            let _guard = handle.enter();
+            tokio::time::interval(timer.interval())
+        };
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        handle.spawn(async move {
+            loop {
+                interval.tick().await;
+                if let Err(flume::TrySendError::Disconnected(_)) = tx.try_send(timer_id.clone()) {
+                    // the operator has stopped
+                    break;
+                }
+            }
+        });
+    }
+    rx
+}
+
+/// Waits for the next event or timer tick of an operator.
+///
+/// Returns `None` once the event channel is closed.
+pub fn next_input(
+    events: &flume::Receiver<Event>,
+    timers: &flume::Receiver<String>,
+) -> Option<OperatorInput> {
+    loop {
+        if timers.is_disconnected() {
+            return events.recv().ok().map(OperatorInput::Event);
+        }
+        let next = flume::Selector::new()
+            .recv(events, |event| Some(event.ok().map(OperatorInput::Event)))
+            .recv(timers, |tick| {
+                tick.ok().map(|id| Some(OperatorInput::Timer(id)))
+            })
+            .wait();
+        // retry without the timers if they were stopped in the meantime
+        if let Some(next) = next {
+            return next;
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! Runs operators that are compiled to WebAssembly.
+//!
+//! WASM operators run in a [`wasmtime`] sandbox, so a faulty operator results in
+//! a trap that is reported as an operator error instead of crashing the whole
+//! runtime process.
+//!
+//! ## Operator ABI
+//!
+//! WASM operators follow the shared library operator ABI of
+//! `dora-operator-api-types` as far as possible, with pointers and lengths as
+//! `i32` values instead of FFI types. The module must export its linear memory
+//! as `memory` and the following functions:
+//!
+//! - `dora_alloc(len: i32) -> i32` and `dora_dealloc(ptr: i32, len: i32)`:
+//!   allocate and free `len` bytes in the module memory. The runtime uses them
+//!   to pass event IDs and data to `dora_on_event` and frees the buffers again
+//!   when `dora_on_event` returns, so operators need to copy data that they
+//!   want to keep.
+//! - `dora_on_event(kind: i32, id_ptr: i32, id_len: i32, data_ptr: i32, data_len: i32) -> i32`:
+//!   called for each event. The `kind` selects the field of the `RawEvent`
+//!   type of shared library operators: `0` for inputs, `1` for closed inputs,
+//!   `2` for stop events, `3` for timer ticks, which pass the timer ID as `id`,
+//!   and `4` for errors, which pass the error message as `data`. Returns a
+//!   `DoraStatus`, i.e. `0` to continue, `1` to stop the operator, and `2` to
+//!   stop the whole dataflow, or a negative value on error.
+//! - `dora_init_operator() -> i32` (optional): called once before the first
+//!   event. A non-zero return value aborts the operator.
+//! - `dora_drop_operator()` (optional): called once after the last event.
+//!
+//! Modules can additionally export the `DORA_OPERATOR_ABI_VERSION` they were
+//! built for as an `i32` global named `dora_operator_abi`, which the runtime
+//! checks like for shared library operators.
+//!
+//! The runtime provides a `dora.send_output(id_ptr: i32, id_len: i32, data_ptr: i32, data_len: i32) -> i32`
+//! import, which sends the given bytes as a `UInt8` array and returns `0` on
+//! success.
+//!
+//! Inputs are passed as raw bytes, so only `UInt8` arrays are supported as
+//! input data.
+//!
+//! If the operator has a watchdog with the `kill` action, callbacks that
+//! exceed the watchdog timeout are interrupted with a trap, which stops the
+//! operator with an error.
+
+use super::{
+    timers::next_input, watchdog::CallbackTimer, worker_pool::WorkerPool, OperatorEvent,
+    OperatorInput, StopReason,
+};
+use aligned_vec::{AVec, ConstAlign};
+use arrow::array::{Array, UInt8Array};
+use dora_core::{
+    config::{DataId, NodeId, OperatorId},
+    descriptor::{source_is_url, OperatorWatchdog, WatchdogAction},
+};
+use dora_download::download_file;
+use dora_node_api::{
+    arrow_utils::{copy_array_into_sample, required_data_size},
+    Event, MetadataParameters,
+};
+use dora_operator_api_types::{DoraStatus, DORA_OPERATOR_ABI_VERSION};
+use eyre::{bail, eyre, Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+use tokio::sync::{mpsc::Sender, oneshot};
+use wasmtime::{Caller, Config, Engine, Instance, Linker, Memory, Module, Store, TypedFunc, Val};
+
+const EVENT_INPUT: i32 = 0;
+const EVENT_INPUT_CLOSED: i32 = 1;
+const EVENT_STOP: i32 = 2;
+const EVENT_TIMER: i32 = 3;
+const EVENT_ERROR: i32 = 4;
+
+/// Interval at which the epoch of the engine is increased, which limits how
+/// precisely the watchdog timeout is enforced.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    node_id: &NodeId,
+    operator_id: &OperatorId,
+    source: &str,
+    events_tx: Sender<OperatorEvent>,
+    incoming_events: flume::Receiver<Event>,
+    timers: flume::Receiver<String>,
+    init_done: oneshot::Sender<Result<()>>,
+    watchdog: Option<&OperatorWatchdog>,
+    callback_timer: CallbackTimer,
+    worker_pool: WorkerPool,
+) -> eyre::Result<()> {
+    let path = if source_is_url(source) {
+        let target_path = Path::new("build")
+            .join(node_id.to_string())
+            .join(format!("{operator_id}.wasm"));
+        // try to download the WASM module
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        rt.block_on(download_file(source, &target_path))
+            .wrap_err("failed to download WASM operator")?;
+        target_path
+    } else {
+        PathBuf::from(source)
+    };
+
+    let timeout = watchdog
+        .filter(|w| w.action == WatchdogAction::Kill)
+        .map(|w| w.timeout());
+    let operator = match WasmOperator::load(&path, events_tx.clone(), timeout) {
+        Ok(operator) => operator,
+        Err(err) => {
+            let _ = init_done.send(Err(eyre!("{err:?}")));
+            return Err(err);
+        }
+    };
+
+    // interrupts callbacks that exceed the timeout, see `WasmOperator::deadline`
+    let (_stop_ticker, ticker) = mpsc::channel::<()>();
+    if timeout.is_some() {
+        let engine = operator.store.engine().clone();
+        std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = ticker.recv_timeout(EPOCH_TICK) {
+                engine.increment_epoch();
+            }
+        });
+    }
+
+    match operator.run(
+        incoming_events,
+        timers,
+        init_done,
+        callback_timer,
+        worker_pool,
+    ) {
+        Ok(reason) => {
+            let _ = events_tx.blocking_send(OperatorEvent::Finished { reason });
+        }
+        Err(err) => {
+            let _ = events_tx.blocking_send(OperatorEvent::Error(err));
+        }
+    }
+
+    Ok(())
+}
+
+struct WasmOperator {
+    store: Store<Sender<OperatorEvent>>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    dealloc: TypedFunc<(i32, i32), ()>,
+    on_event: TypedFunc<(i32, i32, i32, i32, i32), i32>,
+    /// Number of epoch ticks after which a callback is interrupted.
+    ///
+    /// The epoch is only increased if a timeout is configured, so the deadline
+    /// is never reached otherwise.
+    deadline: u64,
+}
+
+impl WasmOperator {
+    fn load(
+        path: &Path,
+        events_tx: Sender<OperatorEvent>,
+        timeout: Option<Duration>,
+    ) -> eyre::Result<Self> {
+        let mut config = Config::new();
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config).map_err(|err| eyre!("{err:?}"))?;
+        let module = Module::from_file(&engine, path)
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err_with(|| format!("failed to load WASM module at `{}`", path.display()))?;
+
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap("dora", "send_output", send_output)
+            .map_err(|err| eyre!("{err:?}"))?;
+
+        let mut store = Store::new(&engine, events_tx);
+        store.epoch_deadline_trap();
+        let deadline = match timeout {
+            Some(timeout) => (timeout.as_millis() / EPOCH_TICK.as_millis()).max(1) as u64,
+            None => 1,
+        };
+        store.set_epoch_deadline(deadline);
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err("failed to instantiate WASM module")?;
+        check_abi_version(&mut store, &instance)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| eyre!("WASM module does not export `memory`"))?;
+        let alloc = instance
+            .get_typed_func(&mut store, "dora_alloc")
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err("failed to get `dora_alloc`")?;
+        let dealloc = instance
+            .get_typed_func(&mut store, "dora_dealloc")
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err("failed to get `dora_dealloc`")?;
+        let on_event = instance
+            .get_typed_func(&mut store, "dora_on_event")
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err("failed to get `dora_on_event`")?;
+
+        Ok(Self {
+            store,
+            instance,
+            memory,
+            alloc,
+            dealloc,
+            on_event,
+            deadline,
+        })
+    }
+
+    fn run(
+        mut self,
+        incoming_events: flume::Receiver<Event>,
+        timers: flume::Receiver<String>,
+        init_done: oneshot::Sender<Result<()>>,
+        callback_timer: CallbackTimer,
+        worker_pool: WorkerPool,
+    ) -> eyre::Result<StopReason> {
+        if let Err(err) = self.init() {
+            let _ = init_done.send(Err(eyre!("{err:?}")));
+            return Err(err);
+        }
+        let _ = init_done.send(Ok(()));
+
+        let reason = loop {
+            let event = match next_input(&incoming_events, &timers) {
+                Some(OperatorInput::Event(event)) => event,
+                Some(OperatorInput::Timer(timer_id)) => {
+                    let _worker = worker_pool.acquire();
+                    let _running = callback_timer.start();
+                    let status = self.call_on_event(EVENT_TIMER, &timer_id, &[])?;
+                    match stop_reason(status)? {
+                        Some(reason) => break reason,
+                        None => continue,
+                    }
+                }
+                None => break StopReason::InputsClosed,
+            };
+
+            let _worker = worker_pool.acquire();
+            let _running = callback_timer.start();
+            let status = match event {
+                Event::Stop => self.call_on_event(EVENT_STOP, "", &[])?,
+                Event::Input { id, data, .. } => {
+                    let bytes: &[u8] = if data.is_empty() {
+                        &[]
+                    } else {
+                        match (&data).try_into() {
+                            Ok(bytes) => bytes,
+                            Err(err) => {
+                                tracing::warn!(
+                                    "ignoring input `{id}` because WASM operators only support \
+                                    UInt8 arrays: {err}"
+                                );
+                                continue;
+                            }
+                        }
+                    };
+                    self.call_on_event(EVENT_INPUT, id.as_str(), bytes)?
+                }
+                Event::InputClosed { id } => {
+                    self.call_on_event(EVENT_INPUT_CLOSED, id.as_str(), &[])?
+                }
+                Event::Reload { .. } => {
+                    // reloading WASM operators is not supported
+                    continue;
+                }
+                Event::Error(err) => self.call_on_event(EVENT_ERROR, "", err.as_bytes())?,
+                other => {
+                    tracing::warn!("unexpected event: {other:?}");
+                    continue;
+                }
+            };
+
+            if let Some(reason) = stop_reason(status)? {
+                break reason;
+            }
+        };
+        self.drop_operator()?;
+        Ok(reason)
+    }
+
+    fn init(&mut self) -> eyre::Result<()> {
+        let Ok(init) = self
+            .instance
+            .get_typed_func::<(), i32>(&mut self.store, "dora_init_operator")
+        else {
+            return Ok(());
+        };
+        self.store.set_epoch_deadline(self.deadline);
+        match init
+            .call(&mut self.store, ())
+            .map_err(|err| eyre!("{err:?}"))?
+        {
+            0 => Ok(()),
+            code => bail!("dora_init_operator failed with error code {code}"),
+        }
+    }
+
+    fn drop_operator(&mut self) -> eyre::Result<()> {
+        let Ok(drop_operator) = self
+            .instance
+            .get_typed_func::<(), ()>(&mut self.store, "dora_drop_operator")
+        else {
+            return Ok(());
+        };
+        self.store.set_epoch_deadline(self.deadline);
+        drop_operator
+            .call(&mut self.store, ())
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err("WASM operator trapped in `dora_drop_operator`")
+    }
+
+    fn call_on_event(&mut self, kind: i32, id: &str, data: &[u8]) -> eyre::Result<i32> {
+        let (id_ptr, id_len) = self.write_to_guest(id.as_bytes())?;
+        let (data_ptr, data_len) = match self.write_to_guest(data) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                self.free_guest(id_ptr, id_len)?;
+                return Err(err);
+            }
+        };
+        self.store.set_epoch_deadline(self.deadline);
+        let status = self
+            .on_event
+            .call(&mut self.store, (kind, id_ptr, id_len, data_ptr, data_len))
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err("WASM operator trapped in `dora_on_event`")?;
+        self.free_guest(id_ptr, id_len)?;
+        self.free_guest(data_ptr, data_len)?;
+        Ok(status)
+    }
+
+    fn write_to_guest(&mut self, bytes: &[u8]) -> eyre::Result<(i32, i32)> {
+        if bytes.is_empty() {
+            return Ok((0, 0));
+        }
+        let len = i32::try_from(bytes.len()).wrap_err("message too large for WASM operator")?;
+        self.store.set_epoch_deadline(self.deadline);
+        let ptr = self
+            .alloc
+            .call(&mut self.store, len)
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err("WASM operator trapped in `dora_alloc`")?;
+        if let Err(err) = self
+            .memory
+            .write(&mut self.store, ptr as u32 as usize, bytes)
+        {
+            self.free_guest(ptr, len)?;
+            return Err(err).wrap_err("`dora_alloc` returned an invalid pointer");
+        }
+        Ok((ptr, len))
+    }
+
+    fn free_guest(&mut self, ptr: i32, len: i32) -> eyre::Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        self.store.set_epoch_deadline(self.deadline);
+        self.dealloc
+            .call(&mut self.store, (ptr, len))
+            .map_err(|err| eyre!("{err:?}"))
+            .wrap_err("WASM operator trapped in `dora_dealloc`")
+    }
+}
+
+fn check_abi_version(
+    store: &mut Store<Sender<OperatorEvent>>,
+    instance: &Instance,
+) -> eyre::Result<()> {
+    let Some(abi) = instance.get_global(&mut *store, "dora_operator_abi") else {
+        tracing::warn!(
+            "WASM operator does not export `dora_operator_abi`, so its compatibility \
+            can't be checked"
+        );
+        return Ok(());
+    };
+    match abi.get(&mut *store) {
+        Val::I32(version) if version as u32 == DORA_OPERATOR_ABI_VERSION => Ok(()),
+        Val::I32(version) => bail!(
+            "WASM operator was built for operator ABI version {version}, but this runtime \
+            supports version {DORA_OPERATOR_ABI_VERSION}"
+        ),
+        _ => bail!("`dora_operator_abi` must be an i32 global"),
+    }
+}
+
+fn stop_reason(status: i32) -> eyre::Result<Option<StopReason>> {
+    match status {
+        s if s == DoraStatus::Continue as i32 => Ok(None),
+        s if s == DoraStatus::Stop as i32 => Ok(Some(StopReason::ExplicitStop)),
+        s if s == DoraStatus::StopAll as i32 => Ok(Some(StopReason::ExplicitStopAll)),
+        code if code < 0 => bail!("on_event failed with error code {code}"),
+        other => bail!("on_event returned unknown status {other}"),
+    }
+}
+
+fn send_output(
+    mut caller: Caller<'_, Sender<OperatorEvent>>,
+    id_ptr: i32,
+    id_len: i32,
+    data_ptr: i32,
+    data_len: i32,
+) -> i32 {
+    let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory()) else {
+        return -1;
+    };
+    let read = |ptr: i32, len: i32| {
+        let start = ptr as u32 as usize;
+        let end = start.checked_add(len as u32 as usize)?;
+        memory.data(&caller).get(start..end).map(|d| d.to_vec())
+    };
+    let (Some(id), Some(data)) = (read(id_ptr, id_len), read(data_ptr, data_len)) else {
+        tracing::warn!("WASM operator passed out-of-bounds output");
+        return -1;
+    };
+    let Ok(output_id) = String::from_utf8(id) else {
+        tracing::warn!("WASM operator passed invalid output ID");
+        return -1;
+    };
+
+    let array = UInt8Array::from(data).into_data();
+    let mut sample: AVec<u8, ConstAlign<128>> =
+        AVec::__from_elem(128, 0, required_data_size(&array));
+    let type_info = copy_array_into_sample(&mut sample, &array);
+
+    let event = OperatorEvent::Output {
+        output_id: DataId::from(output_id),
+        type_info,
+        parameters: MetadataParameters::default(),
+        data: Some(sample.into()),
+    };
+    match caller.data().blocking_send(event) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
@@ -0,0 +1,108 @@
+//! Detects operator callbacks that exceed their configured timeout.
+//!
+//! The operator backends mark the duration of each event callback through a
+//! shared [`CallbackTimer`]. A separate task on the runtime's event loop checks
+//! the timer periodically and reports overruns as [`OperatorEvent::Timeout`].
+//! This also catches callbacks that never return, e.g. because they are
+//! blocked waiting for the Python GIL or hang in native code.
+
+use super::OperatorEvent;
+use crate::profile::{Allocations, OperatorProfile};
+use dora_core::descriptor::OperatorWatchdog;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::Sender;
+
+/// Records the start time of the currently running operator callback.
+#[derive(Debug, Clone, Default)]
+pub struct CallbackTimer {
+    running_since: Arc<Mutex<Option<Instant>>>,
+    profile: Option<OperatorProfile>,
+}
+
+impl CallbackTimer {
+    /// Creates a timer that also records every finished callback in the given
+    /// profile.
+    pub fn with_profile(profile: OperatorProfile) -> Self {
+        Self {
+            running_since: Default::default(),
+            profile: Some(profile),
+        }
+    }
+
+    /// Marks the start of a callback, which ends when the returned guard is dropped.
+    pub fn start(&self) -> CallbackGuard<'_> {
+        let start = Instant::now();
+        self.set(Some(start));
+        CallbackGuard {
+            timer: self,
+            start,
+            allocations: self.profile.as_ref().map(|_| Allocations::current_thread()),
+        }
+    }
+
+    fn running_since(&self) -> Option<Instant> {
+        self.running_since.lock().ok().and_then(|start| *start)
+    }
+
+    fn set(&self, value: Option<Instant>) {
+        if let Ok(mut start) = self.running_since.lock() {
+            *start = value;
+        }
+    }
+}
+
+pub struct CallbackGuard<'a> {
+    timer: &'a CallbackTimer,
+    start: Instant,
+    /// Allocations of the operator thread before the callback, if profiling
+    /// is enabled.
+    allocations: Option<Allocations>,
+}
+
+impl Drop for CallbackGuard<'_> {
+    fn drop(&mut self) {
+        self.timer.set(None);
+        if let (Some(profile), Some(before)) = (&self.timer.profile, self.allocations) {
+            let allocations = Allocations::current_thread().since(before);
+            profile.record(self.start.elapsed(), allocations);
+        }
+    }
+}
+
+/// Reports callbacks that run longer than the configured timeout.
+///
+/// Each overrun is reported once. The task exits when the runtime stops
+/// receiving operator events.
+pub async fn watch(
+    timer: CallbackTimer,
+    config: OperatorWatchdog,
+    events_tx: Sender<OperatorEvent>,
+) {
+    let timeout = config.timeout();
+    let check_interval = (timeout / 4).clamp(Duration::from_millis(10), Duration::from_secs(1));
+    let mut interval = tokio::time::interval(check_interval);
+    let mut reported = None;
+    loop {
+        interval.tick().await;
+        if events_tx.is_closed() {
+            break;
+        }
+        let Some(start) = timer.running_since() else {
+            continue;
+        };
+        let elapsed = start.elapsed();
+        if elapsed > timeout && reported != Some(start) {
+            reported = Some(start);
+            let event = OperatorEvent::Timeout {
+                elapsed,
+                action: config.action,
+            };
+            if events_tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    }
+}
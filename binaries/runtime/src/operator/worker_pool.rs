@@ -0,0 +1,59 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// Limits the number of operators that process events at the same time.
+///
+/// Each operator runs on its own thread with its own input queue. Before
+/// invoking an event callback, the operator acquires a permit from the pool,
+/// so at most `workers` callbacks run in parallel.
+#[derive(Debug, Clone)]
+pub struct WorkerPool(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl WorkerPool {
+    pub fn new(workers: NonZeroUsize) -> Self {
+        Self(Arc::new(Inner {
+            available: Mutex::new(workers.get()),
+            released: Condvar::new(),
+        }))
+    }
+
+    /// Blocks until a worker is available.
+    pub fn acquire(&self) -> WorkerPermit<'_> {
+        let mut available = self
+            .0
+            .available
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        while *available == 0 {
+            available = self
+                .0
+                .released
+                .wait(available)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        *available -= 1;
+        WorkerPermit(&self.0)
+    }
+}
+
+pub struct WorkerPermit<'a>(&'a Inner);
+
+impl Drop for WorkerPermit<'_> {
+    fn drop(&mut self) {
+        let mut available = self
+            .0
+            .available
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        *available += 1;
+        self.0.released.notify_one();
+    }
+}
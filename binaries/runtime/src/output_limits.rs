@@ -0,0 +1,101 @@
+use dora_core::{
+    config::{DataId, OperatorId},
+    descriptor::{OperatorConfig, OutputLimits},
+    message::ArrowTypeInfo,
+};
+use eyre::Context;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+/// Enforces the configured output limits of the operators.
+///
+/// This is applied to the outputs of all operator kinds, right before they
+/// are sent to the daemon.
+pub struct OutputLimiter {
+    outputs: HashMap<(OperatorId, DataId), OutputState>,
+}
+
+struct OutputState {
+    limits: OutputLimits,
+    min_interval: Option<Duration>,
+    last_sent: Option<Instant>,
+    last_data: Option<(ArrowTypeInfo, u64)>,
+}
+
+impl OutputLimiter {
+    pub fn new(operators: &HashMap<OperatorId, OperatorConfig>) -> eyre::Result<Self> {
+        let mut limiter = Self {
+            outputs: HashMap::new(),
+        };
+        for (operator_id, config) in operators {
+            limiter.add_operator(operator_id, config)?;
+        }
+        Ok(limiter)
+    }
+
+    /// Replaces the output limits of the given operator, e.g. after it was
+    /// loaded into the running node.
+    ///
+    /// Returns an error and keeps the previous limits if the limits are
+    /// invalid.
+    pub fn add_operator(
+        &mut self,
+        operator_id: &OperatorId,
+        config: &OperatorConfig,
+    ) -> eyre::Result<()> {
+        let mut outputs = Vec::new();
+        for (output_id, limits) in &config.output_limits {
+            let state = OutputState {
+                limits: limits.clone(),
+                min_interval: limits.min_interval().wrap_err_with(|| {
+                    format!("invalid limits for output `{operator_id}/{output_id}`")
+                })?,
+                last_sent: None,
+                last_data: None,
+            };
+            outputs.push(((operator_id.clone(), output_id.clone()), state));
+        }
+        self.outputs.retain(|(id, _), _| id != operator_id);
+        self.outputs.extend(outputs);
+        Ok(())
+    }
+
+    /// Returns `false` if the given output should be dropped.
+    pub fn check(
+        &mut self,
+        operator_id: &OperatorId,
+        output_id: &DataId,
+        type_info: &ArrowTypeInfo,
+        data: Option<&[u8]>,
+    ) -> bool {
+        let Some(state) = self
+            .outputs
+            .get_mut(&(operator_id.clone(), output_id.clone()))
+        else {
+            return true;
+        };
+
+        let now = Instant::now();
+        if let (Some(min_interval), Some(last_sent)) = (state.min_interval, state.last_sent) {
+            if now.duration_since(last_sent) < min_interval {
+                return false;
+            }
+        }
+        if state.limits.skip_if_unchanged {
+            let mut hasher = DefaultHasher::new();
+            data.unwrap_or_default().hash(&mut hasher);
+            let hash = hasher.finish();
+            if let Some((last_type_info, last_hash)) = &state.last_data {
+                if *last_hash == hash && last_type_info == type_info {
+                    return false;
+                }
+            }
+            state.last_data = Some((type_info.clone(), hash));
+        }
+        state.last_sent = Some(now);
+        true
+    }
+}
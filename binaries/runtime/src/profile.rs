@@ -0,0 +1,305 @@
+//! Profiling of operator callbacks, enabled through the `_unstable_profile`
+//! option of runtime nodes.
+//!
+//! The operator backends mark each callback through their [`CallbackTimer`],
+//! which records the callback duration and the allocations of the operator
+//! thread into an [`OperatorProfile`] when profiling is enabled. The results are
+//! written to `out/<dataflow_id>/profile_<node_id>.json` when the runtime exits,
+//! together with a `.folded` file that can be passed to flamegraph tools such as
+//! `inferno-flamegraph` to compare the operators of the node.
+//!
+//! Allocations are counted by the global allocator of the runtime, so only
+//! allocations through the Rust allocator are included. Memory that the Python
+//! interpreter or native libraries allocate through `malloc` directly is not
+//! counted.
+//!
+//! [`CallbackTimer`]: crate::operator::watchdog::CallbackTimer
+
+use dora_core::{
+    config::{NodeId, OperatorId},
+    daemon_messages::DataflowId,
+    descriptor::{ProfileSampler, RuntimeProfile},
+};
+use eyre::Context;
+use serde::Serialize;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Number of histogram buckets, the last bucket also counts all longer callbacks.
+const BUCKETS: usize = 31;
+
+#[derive(Clone)]
+pub struct Profiler {
+    started: Instant,
+    /// Shared because operators can be loaded while the runtime is running.
+    operators: Arc<Mutex<BTreeMap<OperatorId, OperatorProfile>>>,
+}
+
+impl Profiler {
+    /// Enables allocation counting and starts the configured sampling profiler.
+    pub fn start(config: &RuntimeProfile, dataflow_id: &DataflowId, node_id: &NodeId) -> Self {
+        COUNT_ALLOCATIONS.store(true, Ordering::Relaxed);
+        if let Some(sampler) = config.sampler {
+            if let Err(err) = spawn_sampler(sampler, dataflow_id, node_id) {
+                tracing::warn!("failed to start sampling profiler: {err:?}");
+            }
+        }
+        Self {
+            started: Instant::now(),
+            operators: Default::default(),
+        }
+    }
+
+    pub fn operator(&self, operator_id: &OperatorId) -> OperatorProfile {
+        let mut operators = self.operators.lock().unwrap_or_else(|err| err.into_inner());
+        operators.entry(operator_id.clone()).or_default().clone()
+    }
+
+    /// Writes the collected profile to the `out` directory of the dataflow.
+    pub fn write(&self, dataflow_id: &DataflowId, node_id: &NodeId) -> eyre::Result<()> {
+        let operators: BTreeMap<_, _> = self
+            .operators
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .map(|(id, profile)| (id.clone(), profile.report()))
+            .collect();
+
+        let dir = out_dir(dataflow_id);
+        std::fs::create_dir_all(&dir).wrap_err("failed to create profile directory")?;
+
+        let mut folded = String::new();
+        for (operator_id, report) in &operators {
+            let _ = writeln!(folded, "{node_id};{operator_id} {}", report.total_us);
+        }
+        std::fs::write(dir.join(format!("profile_{node_id}.folded")), folded)
+            .wrap_err("failed to write folded profile")?;
+
+        let report = ProfileReport {
+            node_id,
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            operators,
+        };
+        let path = dir.join(format!("profile_{node_id}.json"));
+        let json = serde_json::to_vec_pretty(&report)?;
+        std::fs::write(&path, json)
+            .wrap_err_with(|| format!("failed to write profile to `{}`", path.display()))?;
+        tracing::info!("wrote operator profile to `{}`", path.display());
+        Ok(())
+    }
+}
+
+/// Callback statistics of a single operator.
+#[derive(Debug, Clone, Default)]
+pub struct OperatorProfile(Arc<Mutex<CallbackStats>>);
+
+#[derive(Debug, Clone, Default)]
+struct CallbackStats {
+    callbacks: u64,
+    total: Duration,
+    max: Duration,
+    allocations: Allocations,
+    /// Bucket `i` counts the callbacks that took less than `2^i` microseconds.
+    histogram: [u64; BUCKETS],
+}
+
+impl OperatorProfile {
+    pub fn record(&self, duration: Duration, allocations: Allocations) {
+        let Ok(mut stats) = self.0.lock() else {
+            return;
+        };
+        stats.callbacks += 1;
+        stats.total += duration;
+        stats.max = stats.max.max(duration);
+        stats.allocations.count += allocations.count;
+        stats.allocations.bytes += allocations.bytes;
+        let micros = duration.as_micros() as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        stats.histogram[bucket.min(BUCKETS - 1)] += 1;
+    }
+
+    fn report(&self) -> OperatorReport {
+        let stats = self.0.lock().map(|s| s.clone()).unwrap_or_default();
+        let percentile = |p: f64| {
+            let rank = (stats.callbacks as f64 * p).ceil() as u64;
+            let mut seen = 0;
+            for (bucket, count) in stats.histogram.iter().enumerate() {
+                seen += count;
+                if seen >= rank && *count > 0 {
+                    return 1u64 << bucket;
+                }
+            }
+            0
+        };
+        OperatorReport {
+            callbacks: stats.callbacks,
+            total_us: stats.total.as_micros() as u64,
+            mean_us: stats
+                .total
+                .as_micros()
+                .checked_div(stats.callbacks as u128)
+                .unwrap_or_default() as u64,
+            p50_us: percentile(0.5),
+            p99_us: percentile(0.99),
+            max_us: stats.max.as_micros() as u64,
+            allocations: stats.allocations.count,
+            allocated_bytes: stats.allocations.bytes,
+            histogram: stats
+                .histogram
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(|(bucket, count)| HistogramBucket {
+                    below_us: 1 << bucket,
+                    count: *count,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProfileReport<'a> {
+    node_id: &'a NodeId,
+    duration_ms: u64,
+    operators: BTreeMap<OperatorId, OperatorReport>,
+}
+
+#[derive(Serialize)]
+struct OperatorReport {
+    callbacks: u64,
+    total_us: u64,
+    mean_us: u64,
+    /// Upper bound of the median callback duration, based on the histogram.
+    p50_us: u64,
+    /// Upper bound of the 99th percentile, based on the histogram.
+    p99_us: u64,
+    max_us: u64,
+    allocations: u64,
+    allocated_bytes: u64,
+    histogram: Vec<HistogramBucket>,
+}
+
+#[derive(Serialize)]
+struct HistogramBucket {
+    below_us: u64,
+    count: u64,
+}
+
+fn out_dir(dataflow_id: &DataflowId) -> PathBuf {
+    Path::new("out").join(dataflow_id.to_string())
+}
+
+/// Attaches a sampling profiler to the runtime process.
+///
+/// The profilers stop and write their results on their own when the runtime
+/// process exits.
+fn spawn_sampler(
+    sampler: ProfileSampler,
+    dataflow_id: &DataflowId,
+    node_id: &NodeId,
+) -> eyre::Result<()> {
+    let dir = out_dir(dataflow_id);
+    std::fs::create_dir_all(&dir).wrap_err("failed to create profile directory")?;
+    let pid = std::process::id().to_string();
+    let mut command = match sampler {
+        ProfileSampler::Perf => {
+            let mut command = Command::new("perf");
+            command
+                .args(["record", "-g", "-F", "99", "-p", &pid, "-o"])
+                .arg(dir.join(format!("perf_{node_id}.data")));
+            command
+        }
+        ProfileSampler::PySpy => {
+            let mut command = Command::new("py-spy");
+            command
+                .args(["record", "--nonblocking", "--pid", &pid, "--output"])
+                .arg(dir.join(format!("flamegraph_{node_id}.svg")));
+            command
+        }
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .wrap_err_with(|| format!("failed to spawn `{:?}`", command.get_program()))?;
+    Ok(())
+}
+
+/// Number and total size of allocations.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Allocations {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+impl Allocations {
+    /// Returns the allocations of the current thread so far.
+    pub fn current_thread() -> Self {
+        Self {
+            count: ALLOCATION_COUNT.try_with(Cell::get).unwrap_or_default(),
+            bytes: ALLOCATED_BYTES.try_with(Cell::get).unwrap_or_default(),
+        }
+    }
+
+    pub fn since(self, earlier: Self) -> Self {
+        Self {
+            count: self.count.saturating_sub(earlier.count),
+            bytes: self.bytes.saturating_sub(earlier.bytes),
+        }
+    }
+}
+
+static COUNT_ALLOCATIONS: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static ALLOCATION_COUNT: Cell<u64> = const { Cell::new(0) };
+    static ALLOCATED_BYTES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Counts the allocations of each thread while profiling is enabled.
+struct CountingAllocator;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+impl CountingAllocator {
+    fn count(size: usize) {
+        if COUNT_ALLOCATIONS.load(Ordering::Relaxed) {
+            let _ = ALLOCATION_COUNT.try_with(|count| count.set(count.get() + 1));
+            let _ = ALLOCATED_BYTES.try_with(|bytes| bytes.set(bytes.get() + size as u64));
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count(new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
@@ -1,6 +1,7 @@
 use arrow::{
     array::{Array, AsArray, PrimitiveArray, StringArray},
     datatypes::ArrowPrimitiveType,
+    record_batch::RecordBatch,
 };
 use eyre::ContextCompat;
 
@@ -163,6 +164,58 @@ impl<'a> TryFrom<&'a ArrowData> for Vec<u8> {
     }
 }
 
+macro_rules! impl_slice_conversions {
+    ($($native:ty => $arrow_type:ty),* $(,)?) => {
+        $(
+            impl<'a> TryFrom<&'a ArrowData> for &'a [$native] {
+                type Error = eyre::Report;
+                fn try_from(value: &'a ArrowData) -> Result<Self, Self::Error> {
+                    let array: &PrimitiveArray<$arrow_type> = value
+                        .as_primitive_opt()
+                        .wrap_err(concat!("not a primitive ", stringify!($arrow_type), " array"))?;
+                    if array.null_count() != 0 {
+                        eyre::bail!("array has nulls");
+                    }
+                    Ok(array.values())
+                }
+            }
+
+            impl<'a> TryFrom<&'a ArrowData> for Vec<$native> {
+                type Error = eyre::Report;
+                fn try_from(value: &'a ArrowData) -> Result<Self, Self::Error> {
+                    value.try_into().map(|slice: &'a [$native]| slice.to_vec())
+                }
+            }
+        )*
+    };
+}
+
+impl_slice_conversions!(
+    u16 => arrow::datatypes::UInt16Type,
+    u32 => arrow::datatypes::UInt32Type,
+    u64 => arrow::datatypes::UInt64Type,
+    i8 => arrow::datatypes::Int8Type,
+    i16 => arrow::datatypes::Int16Type,
+    i32 => arrow::datatypes::Int32Type,
+    i64 => arrow::datatypes::Int64Type,
+    f32 => arrow::datatypes::Float32Type,
+    f64 => arrow::datatypes::Float64Type,
+);
+
+/// Tabular data is sent as a struct array with one field per column.
+///
+/// The columns share the buffers of the received data, so no data is copied.
+impl TryFrom<&ArrowData> for RecordBatch {
+    type Error = eyre::Report;
+    fn try_from(value: &ArrowData) -> Result<Self, Self::Error> {
+        let array = value.as_struct_opt().wrap_err("not a struct array")?;
+        if array.null_count() != 0 {
+            eyre::bail!("struct array has null rows");
+        }
+        Ok(RecordBatch::from(array))
+    }
+}
+
 fn extract_single_primitive<T>(array: &PrimitiveArray<T>) -> Result<T::Native, eyre::Error>
 where
     T: ArrowPrimitiveType,
@@ -181,9 +234,14 @@ where
 
 #[cfg(test)]
 mod tests {
-    use arrow::array::{make_array, PrimitiveArray};
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{make_array, Array, Float32Array, PrimitiveArray, StringArray},
+        record_batch::RecordBatch,
+    };
 
-    use crate::ArrowData;
+    use crate::{ArrowData, IntoArrow};
 
     #[test]
     fn test_u8() {
@@ -193,4 +251,24 @@ mod tests {
         let value: u8 = (&data).try_into().unwrap();
         assert_eq!(value, 42);
     }
+
+    #[test]
+    fn test_f32_slice() {
+        let data: ArrowData = make_array(Float32Array::from(vec![1.0, 2.5]).into()).into();
+        let value: &[f32] = (&data).try_into().unwrap();
+        assert_eq!(value, &[1.0, 2.5]);
+        assert!(TryInto::<&[f64]>::try_into(&data).is_err());
+    }
+
+    #[test]
+    fn test_record_batch_roundtrip() {
+        let batch = RecordBatch::try_from_iter([
+            ("x", Arc::new(Float32Array::from(vec![0.5, 1.5])) as _),
+            ("label", Arc::new(StringArray::from(vec!["a", "b"])) as _),
+        ])
+        .unwrap();
+        let data: ArrowData = make_array(batch.clone().into_arrow().into_data()).into();
+        let value: RecordBatch = (&data).try_into().unwrap();
+        assert_eq!(value, batch);
+    }
 }
@@ -0,0 +1,183 @@
+//! Payload type for passing CUDA device memory between operators.
+//!
+//! Instead of copying a GPU tensor to host memory, the sender only transmits a
+//! CUDA IPC handle together with the tensor layout. The receiver opens the
+//! handle to access the same device memory. This only works between processes
+//! on the same machine, so the daemon refuses to forward such payloads to
+//! other machines.
+//!
+//! The sender must keep the tensor alive until all receivers are done with it.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{
+        Array, AsArray, BinaryArray, Int32Array, ListArray, StringArray, StructArray, UInt32Array,
+        UInt64Array,
+    },
+    datatypes::{DataType, Field, Int64Type, UInt32Type, UInt64Type},
+};
+use eyre::{Context, ContextCompat};
+
+use crate::{ArrowData, IntoArrow};
+
+/// A tensor in CUDA device memory, shared through a CUDA IPC handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CudaIpcTensor {
+    /// The `cudaIpcMemHandle_t` of the allocation that contains the tensor.
+    pub ipc_handle: Vec<u8>,
+    /// Byte offset of the tensor data within the allocation.
+    pub offset: u64,
+    pub device_id: i32,
+    /// Element type in NumPy's type string format, e.g. `<f4`.
+    pub dtype: String,
+    pub shape: Vec<i64>,
+    /// Strides in bytes.
+    pub strides: Vec<i64>,
+    /// ID of the sending process.
+    ///
+    /// CUDA doesn't allow opening IPC handles in the process that created
+    /// them, so receivers in the same process use `device_ptr` instead.
+    pub pid: u32,
+    /// Device pointer of the tensor data, only valid in the sending process.
+    pub device_ptr: u64,
+}
+
+const IPC_HANDLE: &str = "ipc_handle";
+const DEVICE_PTR: &str = "device_ptr";
+
+/// Checks whether the given data type is the type of a [`CudaIpcTensor`] payload.
+pub fn is_cuda_ipc_tensor(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Struct(fields) => {
+            fields.find(IPC_HANDLE).is_some() && fields.find(DEVICE_PTR).is_some()
+        }
+        _ => false,
+    }
+}
+
+fn list_array(values: &[i64]) -> ListArray {
+    ListArray::from_iter_primitive::<Int64Type, _, _>([Some(values.iter().copied().map(Some))])
+}
+
+impl IntoArrow for CudaIpcTensor {
+    type A = StructArray;
+
+    fn into_arrow(self) -> Self::A {
+        let list_type = DataType::List(Arc::new(Field::new("item", DataType::Int64, true)));
+        StructArray::from(vec![
+            (
+                Arc::new(Field::new(IPC_HANDLE, DataType::Binary, false)),
+                Arc::new(BinaryArray::from_vec(vec![self.ipc_handle.as_slice()])) as _,
+            ),
+            (
+                Arc::new(Field::new("offset", DataType::UInt64, false)),
+                Arc::new(UInt64Array::from(vec![self.offset])) as _,
+            ),
+            (
+                Arc::new(Field::new("device_id", DataType::Int32, false)),
+                Arc::new(Int32Array::from(vec![self.device_id])) as _,
+            ),
+            (
+                Arc::new(Field::new("dtype", DataType::Utf8, false)),
+                Arc::new(StringArray::from(vec![self.dtype])) as _,
+            ),
+            (
+                Arc::new(Field::new("shape", list_type.clone(), false)),
+                Arc::new(list_array(&self.shape)) as _,
+            ),
+            (
+                Arc::new(Field::new("strides", list_type, false)),
+                Arc::new(list_array(&self.strides)) as _,
+            ),
+            (
+                Arc::new(Field::new("pid", DataType::UInt32, false)),
+                Arc::new(UInt32Array::from(vec![self.pid])) as _,
+            ),
+            (
+                Arc::new(Field::new(DEVICE_PTR, DataType::UInt64, false)),
+                Arc::new(UInt64Array::from(vec![self.device_ptr])) as _,
+            ),
+        ])
+    }
+}
+
+impl TryFrom<&ArrowData> for CudaIpcTensor {
+    type Error = eyre::Report;
+
+    fn try_from(value: &ArrowData) -> Result<Self, Self::Error> {
+        let array = value.as_struct_opt().context("not a struct array")?;
+        if array.len() != 1 {
+            eyre::bail!("expected length 1");
+        }
+        let column = |name: &str| {
+            array
+                .column_by_name(name)
+                .with_context(|| format!("missing field `{name}`"))
+        };
+        let list = |name: &str| -> eyre::Result<Vec<i64>> {
+            let values = column(name)?
+                .as_list_opt::<i32>()
+                .with_context(|| format!("field `{name}` is not a list"))?
+                .value(0);
+            let values = values
+                .as_primitive_opt::<Int64Type>()
+                .with_context(|| format!("field `{name}` is not an int64 list"))?;
+            Ok(values.values().to_vec())
+        };
+
+        Ok(Self {
+            ipc_handle: column(IPC_HANDLE)?
+                .as_binary_opt::<i32>()
+                .context("`ipc_handle` is not binary")?
+                .value(0)
+                .to_owned(),
+            offset: column("offset")?
+                .as_primitive_opt::<UInt64Type>()
+                .context("`offset` is not a uint64")?
+                .value(0),
+            device_id: column("device_id")?
+                .as_primitive_opt::<arrow::datatypes::Int32Type>()
+                .context("`device_id` is not an int32")?
+                .value(0),
+            dtype: column("dtype")?
+                .as_string_opt::<i32>()
+                .context("`dtype` is not a string")?
+                .value(0)
+                .to_owned(),
+            shape: list("shape").wrap_err("invalid shape")?,
+            strides: list("strides").wrap_err("invalid strides")?,
+            pid: column("pid")?
+                .as_primitive_opt::<UInt32Type>()
+                .context("`pid` is not a uint32")?
+                .value(0),
+            device_ptr: column(DEVICE_PTR)?
+                .as_primitive_opt::<UInt64Type>()
+                .context("`device_ptr` is not a uint64")?
+                .value(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let tensor = CudaIpcTensor {
+            ipc_handle: vec![7; 64],
+            offset: 512,
+            device_id: 0,
+            dtype: "<f4".into(),
+            shape: vec![2, 3],
+            strides: vec![12, 4],
+            pid: 42,
+            device_ptr: 0xdead_beef,
+        };
+        let array = tensor.clone().into_arrow();
+        assert!(is_cuda_ipc_tensor(array.data_type()));
+        let data = ArrowData(Arc::new(array));
+        assert_eq!(CudaIpcTensor::try_from(&data).unwrap(), tensor);
+    }
+}
@@ -145,3 +145,12 @@ impl IntoArrow for () {
         arrow::array::NullArray::new(0)
     }
 }
+
+/// Sent as a struct array with one field per column.
+impl IntoArrow for arrow::record_batch::RecordBatch {
+    type A = arrow::array::StructArray;
+
+    fn into_arrow(self) -> Self::A {
+        self.into()
+    }
+}
@@ -3,6 +3,7 @@ use std::ops::{Deref, DerefMut};
 use arrow::array::Array;
 
 mod from_impls;
+pub mod gpu;
 mod into_impls;
 
 pub trait IntoArrow {
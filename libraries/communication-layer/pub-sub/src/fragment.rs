@@ -0,0 +1,452 @@
+//! Provides [`FragmentingLayer`], which splits large messages into fragments.
+//!
+//! Some transports limit the size of a single message. The fragmenting layer
+//! wraps another [`CommunicationLayer`] and splits messages that exceed the
+//! configured maximum size into multiple fragments, which the subscriber
+//! reassembles. Each fragment starts with a header that identifies the
+//! publisher, the message sequence number, and the position of the fragment.
+//!
+//! Publishers and subscribers of a topic must both use the fragmenting layer,
+//! since every message carries the header. Incomplete messages are discarded
+//! once a newer message of the same publisher arrives, so transports that drop
+//! or reorder messages lose the affected messages instead of delivering corrupt
+//! data.
+
+use crate::{
+    copy_parts, BoxError, Capabilities, CommunicationLayer, Priority, PublishSample, Publisher,
+    ReceivedSample, Reliability, Subscriber,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// Size of the header that is prepended to each fragment.
+pub const HEADER_LEN: usize = 40;
+
+/// Wraps a [`CommunicationLayer`] to support messages of arbitrary size.
+pub struct FragmentingLayer<L> {
+    inner: L,
+    max_message_size: usize,
+}
+
+impl<L: CommunicationLayer> FragmentingLayer<L> {
+    /// Creates a layer that publishes messages of at most `max_message_size`
+    /// bytes on the given layer, including the fragment header.
+    ///
+    /// Returns an error if `max_message_size` doesn't leave room for any data.
+    pub fn new(inner: L, max_message_size: usize) -> Result<Self, BoxError> {
+        if max_message_size <= HEADER_LEN {
+            return Err(format!(
+                "max message size must be larger than the fragment header ({HEADER_LEN} bytes)"
+            )
+            .into());
+        }
+        Ok(Self {
+            inner,
+            max_message_size,
+        })
+    }
+
+    fn fragmenting(&self, inner: Box<dyn Publisher>) -> Box<dyn Publisher> {
+        Box::new(FragmentingPublisher {
+            inner: inner.into(),
+            sender_id: random_id(),
+            sequence: Default::default(),
+            max_message_size: self.max_message_size,
+        })
+    }
+}
+
+impl<L: CommunicationLayer> CommunicationLayer for FragmentingLayer<L> {
+    fn publisher(&mut self, topic: &str) -> Result<Box<dyn Publisher>, BoxError> {
+        let inner = self.inner.publisher(topic)?;
+        Ok(self.fragmenting(inner))
+    }
+
+    fn publisher_with_priority(
+        &mut self,
+        topic: &str,
+        priority: Priority,
+    ) -> Result<Box<dyn Publisher>, BoxError> {
+        let inner = self.inner.publisher_with_priority(topic, priority)?;
+        Ok(self.fragmenting(inner))
+    }
+
+    fn publisher_with_qos(
+        &mut self,
+        topic: &str,
+        priority: Priority,
+        reliability: Reliability,
+    ) -> Result<Box<dyn Publisher>, BoxError> {
+        let inner = self
+            .inner
+            .publisher_with_qos(topic, priority, reliability)?;
+        Ok(self.fragmenting(inner))
+    }
+
+    fn subscribe(&mut self, topic: &str) -> Result<Box<dyn Subscriber>, BoxError> {
+        let inner = self.inner.subscribe(topic)?;
+        Ok(Box::new(ReassemblingSubscriber {
+            inner,
+            partial: HashMap::new(),
+        }))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        // reassembled messages are copies
+        self.inner
+            .capabilities()
+            .with_max_message_size(None)
+            .with_zero_copy(false)
+    }
+}
+
+struct FragmentingPublisher {
+    inner: Arc<dyn Publisher>,
+    sender_id: u64,
+    sequence: Arc<AtomicU64>,
+    max_message_size: usize,
+}
+
+impl FragmentingPublisher {
+    fn next_header(&self, len: usize) -> Header {
+        Header {
+            sender_id: self.sender_id,
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            index: 0,
+            count: 1,
+            total_len: len as u64,
+            offset: 0,
+        }
+    }
+
+    /// Publishes the concatenated `parts` as a series of fragments.
+    fn publish_fragments(&self, header: Header, parts: &[&[u8]]) -> Result<(), BoxError> {
+        let len = usize::try_from(header.total_len)?;
+        let chunk_size = self.max_message_size - HEADER_LEN;
+        let count = len.div_ceil(chunk_size);
+        let count = u32::try_from(count).map_err(|_| "message has too many fragments")?;
+        for index in 0..count {
+            let offset = index as usize * chunk_size;
+            let chunk_len = chunk_size.min(len - offset);
+            let header = Header {
+                index,
+                count,
+                offset: offset as u64,
+                ..header
+            };
+            let mut sample = self.inner.prepare(HEADER_LEN + chunk_len)?;
+            let slice = sample.as_mut_slice();
+            header.write(&mut slice[..HEADER_LEN]);
+            copy_parts(parts, offset, &mut slice[HEADER_LEN..]);
+            sample.publish()?;
+        }
+        Ok(())
+    }
+}
+
+impl Publisher for FragmentingPublisher {
+    fn prepare(&self, len: usize) -> Result<Box<dyn PublishSample + '_>, BoxError> {
+        let header = self.next_header(len);
+        if len + HEADER_LEN <= self.max_message_size {
+            // fits into a single message -> construct it in place
+            let mut sample = self.inner.prepare(len + HEADER_LEN)?;
+            header.write(&mut sample.as_mut_slice()[..HEADER_LEN]);
+            Ok(Box::new(SingleSample { sample }))
+        } else {
+            Ok(Box::new(FragmentedSample {
+                data: vec![0; len],
+                header,
+                publisher: self,
+            }))
+        }
+    }
+
+    fn dyn_clone(&self) -> Box<dyn Publisher> {
+        Box::new(Self {
+            inner: self.inner.clone(),
+            sender_id: self.sender_id,
+            sequence: self.sequence.clone(),
+            max_message_size: self.max_message_size,
+        })
+    }
+
+    fn publish_vectored(&self, parts: &[&[u8]]) -> Result<(), BoxError> {
+        let len = parts.iter().map(|part| part.len()).sum();
+        let header = self.next_header(len);
+        if len + HEADER_LEN <= self.max_message_size {
+            let mut sample = self.inner.prepare(len + HEADER_LEN)?;
+            let slice = sample.as_mut_slice();
+            header.write(&mut slice[..HEADER_LEN]);
+            copy_parts(parts, 0, &mut slice[HEADER_LEN..]);
+            sample.publish()
+        } else {
+            // split the parts directly instead of joining them into one buffer first
+            self.publish_fragments(header, parts)
+        }
+    }
+}
+
+struct SingleSample<'a> {
+    sample: Box<dyn PublishSample<'a> + 'a>,
+}
+
+impl<'a> PublishSample<'a> for SingleSample<'a> {
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.sample.as_mut_slice()[HEADER_LEN..]
+    }
+
+    fn publish(self: Box<Self>) -> Result<(), BoxError> {
+        self.sample.publish()
+    }
+}
+
+struct FragmentedSample<'a> {
+    data: Vec<u8>,
+    header: Header,
+    publisher: &'a FragmentingPublisher,
+}
+
+impl<'a> PublishSample<'a> for FragmentedSample<'a> {
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    fn publish(self: Box<Self>) -> Result<(), BoxError> {
+        self.publisher
+            .publish_fragments(self.header, &[self.data.as_slice()])
+    }
+}
+
+struct ReassemblingSubscriber {
+    inner: Box<dyn Subscriber>,
+    /// Incomplete messages, by sender ID.
+    partial: HashMap<u64, PartialMessage>,
+}
+
+struct PartialMessage {
+    sequence: u64,
+    data: Vec<u8>,
+    received: u32,
+}
+
+impl Subscriber for ReassemblingSubscriber {
+    fn recv(&mut self) -> Result<Option<Box<dyn ReceivedSample>>, BoxError> {
+        loop {
+            let Some(sample) = self.inner.recv()? else {
+                return Ok(None);
+            };
+            let header = Header::read(&sample.get())?;
+            if header.count == 1 {
+                return Ok(Some(Box::new(UnfragmentedSample { sample })));
+            }
+
+            let total_len = usize::try_from(header.total_len)?;
+            let partial = self
+                .partial
+                .entry(header.sender_id)
+                .or_insert_with(|| PartialMessage {
+                    sequence: header.sequence,
+                    data: vec![0; total_len],
+                    received: 0,
+                });
+            if partial.sequence != header.sequence {
+                // a fragment of the previous message was lost
+                *partial = PartialMessage {
+                    sequence: header.sequence,
+                    data: vec![0; total_len],
+                    received: 0,
+                };
+            }
+
+            let data = sample.get();
+            let chunk = &data[HEADER_LEN..];
+            let offset = usize::try_from(header.offset)?;
+            let Some(target) = partial.data.get_mut(offset..offset + chunk.len()) else {
+                return Err("fragment exceeds the message length".into());
+            };
+            target.copy_from_slice(chunk);
+            partial.received += 1;
+
+            if partial.received == header.count {
+                let message = self.partial.remove(&header.sender_id).unwrap();
+                return Ok(Some(Box::new(ReassembledSample(message.data))));
+            }
+        }
+    }
+}
+
+struct UnfragmentedSample {
+    sample: Box<dyn ReceivedSample>,
+}
+
+impl ReceivedSample for UnfragmentedSample {
+    fn get(&self) -> Cow<[u8]> {
+        match self.sample.get() {
+            Cow::Borrowed(data) => Cow::Borrowed(&data[HEADER_LEN..]),
+            Cow::Owned(data) => Cow::Owned(data[HEADER_LEN..].to_vec()),
+        }
+    }
+}
+
+struct ReassembledSample(Vec<u8>);
+
+impl ReceivedSample for ReassembledSample {
+    fn get(&self) -> Cow<[u8]> {
+        Cow::Borrowed(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    sender_id: u64,
+    sequence: u64,
+    index: u32,
+    count: u32,
+    total_len: u64,
+    offset: u64,
+}
+
+impl Header {
+    fn write(&self, target: &mut [u8]) {
+        target[0..8].copy_from_slice(&self.sender_id.to_le_bytes());
+        target[8..16].copy_from_slice(&self.sequence.to_le_bytes());
+        target[16..20].copy_from_slice(&self.index.to_le_bytes());
+        target[20..24].copy_from_slice(&self.count.to_le_bytes());
+        target[24..32].copy_from_slice(&self.total_len.to_le_bytes());
+        target[32..40].copy_from_slice(&self.offset.to_le_bytes());
+    }
+
+    fn read(data: &[u8]) -> Result<Self, BoxError> {
+        let Some(header) = data.get(..HEADER_LEN) else {
+            return Err("message is shorter than the fragment header".into());
+        };
+        let u64_at = |i: usize| u64::from_le_bytes(header[i..i + 8].try_into().unwrap());
+        let u32_at = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        Ok(Self {
+            sender_id: u64_at(0),
+            sequence: u64_at(8),
+            index: u32_at(16),
+            count: u32_at(20),
+            total_len: u64_at(24),
+            offset: u64_at(32),
+        })
+    }
+}
+
+/// Creates a random ID to distinguish the publishers of a topic.
+fn random_id() -> u64 {
+    // `RandomState` is seeded randomly for each instance
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(std::process::id().into());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// In-memory layer that delivers all published messages to one subscriber.
+    #[derive(Default, Clone)]
+    struct Loopback(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl CommunicationLayer for Loopback {
+        fn publisher(&mut self, _topic: &str) -> Result<Box<dyn Publisher>, BoxError> {
+            Ok(Box::new(self.clone()))
+        }
+
+        fn subscribe(&mut self, _topic: &str) -> Result<Box<dyn Subscriber>, BoxError> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    impl Publisher for Loopback {
+        fn prepare(&self, len: usize) -> Result<Box<dyn PublishSample + '_>, BoxError> {
+            Ok(Box::new(LoopbackSample {
+                data: vec![0; len],
+                messages: self.0.clone(),
+            }))
+        }
+
+        fn dyn_clone(&self) -> Box<dyn Publisher> {
+            Box::new(self.clone())
+        }
+    }
+
+    struct LoopbackSample {
+        data: Vec<u8>,
+        messages: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl PublishSample<'_> for LoopbackSample {
+        fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut self.data
+        }
+
+        fn publish(self: Box<Self>) -> Result<(), BoxError> {
+            self.messages.lock().unwrap().push(self.data);
+            Ok(())
+        }
+    }
+
+    impl Subscriber for Loopback {
+        fn recv(&mut self) -> Result<Option<Box<dyn ReceivedSample>>, BoxError> {
+            let mut messages = self.0.lock().unwrap();
+            if messages.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(Box::new(ReassembledSample(messages.remove(0)))))
+        }
+    }
+
+    #[test]
+    fn fragment_roundtrip() {
+        let loopback = Loopback::default();
+        let mut layer = FragmentingLayer::new(loopback.clone(), HEADER_LEN + 10).unwrap();
+        let publisher = layer.publisher("topic").unwrap();
+        let mut subscriber = layer.subscribe("topic").unwrap();
+
+        let small = b"hello".to_vec();
+        let large: Vec<u8> = (0..95).collect();
+        publisher.publish(&small).unwrap();
+        publisher.publish(&large).unwrap();
+        assert_eq!(loopback.0.lock().unwrap().len(), 1 + 10);
+
+        let received = subscriber.recv().unwrap().unwrap();
+        assert_eq!(received.get(), small.as_slice());
+        let received = subscriber.recv().unwrap().unwrap();
+        assert_eq!(received.get(), large.as_slice());
+        assert!(subscriber.recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn vectored_roundtrip() {
+        let loopback = Loopback::default();
+        let mut layer = FragmentingLayer::new(loopback.clone(), HEADER_LEN + 10).unwrap();
+        let publisher = layer.publisher("topic").unwrap();
+        let mut subscriber = layer.subscribe("topic").unwrap();
+
+        let header = b"head".to_vec();
+        let payload: Vec<u8> = (0..45).collect();
+        publisher
+            .publish_vectored(&[header.as_slice(), &[], b"!"])
+            .unwrap();
+        publisher
+            .publish_vectored(&[header.as_slice(), payload.as_slice()])
+            .unwrap();
+        assert_eq!(loopback.0.lock().unwrap().len(), 1 + 5);
+
+        let received = subscriber.recv().unwrap().unwrap();
+        assert_eq!(received.get(), b"head!".as_slice());
+        let received = subscriber.recv().unwrap().unwrap();
+        assert_eq!(received.get(), [header, payload].concat().as_slice());
+        assert!(subscriber.recv().unwrap().is_none());
+    }
+}
@@ -0,0 +1,190 @@
+//! Provides [`IceoryxCommunicationLayer`] to communicate over `iceoryx2`.
+//!
+//! Messages are written directly into shared memory that is loaned from the
+//! publisher and the subscribers read them from the same memory, so no copies
+//! are made on the local host. Subscribers are woken up through an `iceoryx2`
+//! event service with the same name as the topic.
+
+use super::{Capabilities, CommunicationLayer, Publisher, Subscriber};
+use crate::{BoxError, PublishSample, ReceivedSample};
+use iceoryx2::{
+    port::{listener::Listener, notifier::Notifier},
+    prelude::*,
+    sample::Sample,
+    sample_mut::SampleMut,
+};
+use std::{borrow::Cow, sync::Arc};
+
+type Service = ipc_threadsafe::Service;
+
+/// Allows communication over `iceoryx2` shared memory on the local host.
+pub struct IceoryxCommunicationLayer {
+    node: Arc<Node<Service>>,
+    topic_prefix: String,
+}
+
+impl IceoryxCommunicationLayer {
+    /// Creates a new `iceoryx2` node with the given name.
+    ///
+    /// The `prefix` is added to all topic names when using the [`publisher`][Self::publisher]
+    /// and [`subscriber`][Self::subscribe] methods. Pass an empty string if no prefix is
+    /// desired.
+    pub fn init(name: &str, prefix: String) -> Result<Self, BoxError> {
+        let node = NodeBuilder::new()
+            .name(&NodeName::new(name).map_err(error)?)
+            .create::<Service>()
+            .map_err(error)?;
+        Ok(Self {
+            node: Arc::new(node),
+            topic_prefix: prefix,
+        })
+    }
+
+    fn service_name(&self, topic: &str) -> Result<ServiceName, BoxError> {
+        let name = if self.topic_prefix.is_empty() {
+            topic.to_owned()
+        } else {
+            format!("{}/{topic}", self.topic_prefix)
+        };
+        ServiceName::new(&name).map_err(error)
+    }
+}
+
+impl CommunicationLayer for IceoryxCommunicationLayer {
+    fn publisher(&mut self, topic: &str) -> Result<Box<dyn Publisher>, BoxError> {
+        let name = self.service_name(topic)?;
+        let publisher = self
+            .node
+            .service_builder(&name)
+            .publish_subscribe::<[u8]>()
+            .open_or_create()
+            .map_err(error)?
+            .publisher_builder()
+            // grow the shared memory when larger messages are published
+            .allocation_strategy(AllocationStrategy::PowerOfTwo)
+            .create()
+            .map_err(error)?;
+        let notifier = self
+            .node
+            .service_builder(&name)
+            .event()
+            .open_or_create()
+            .map_err(error)?
+            .notifier_builder()
+            .create()
+            .map_err(error)?;
+
+        Ok(Box::new(IceoryxPublisher {
+            publisher: Arc::new(publisher),
+            notifier: Arc::new(notifier),
+            _node: self.node.clone(),
+        }))
+    }
+
+    fn subscribe(&mut self, topic: &str) -> Result<Box<dyn Subscriber>, BoxError> {
+        let name = self.service_name(topic)?;
+        let subscriber = self
+            .node
+            .service_builder(&name)
+            .publish_subscribe::<[u8]>()
+            .open_or_create()
+            .map_err(error)?
+            .subscriber_builder()
+            .create()
+            .map_err(error)?;
+        let listener = self
+            .node
+            .service_builder(&name)
+            .event()
+            .open_or_create()
+            .map_err(error)?
+            .listener_builder()
+            .create()
+            .map_err(error)?;
+
+        Ok(Box::new(IceoryxSubscriber {
+            subscriber,
+            listener,
+            _node: self.node.clone(),
+        }))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default().with_zero_copy(true)
+    }
+}
+
+#[derive(Clone)]
+struct IceoryxPublisher {
+    publisher: Arc<iceoryx2::port::publisher::Publisher<Service, [u8], ()>>,
+    notifier: Arc<Notifier<Service>>,
+    _node: Arc<Node<Service>>,
+}
+
+impl Publisher for IceoryxPublisher {
+    fn prepare(&self, len: usize) -> Result<Box<dyn PublishSample + '_>, BoxError> {
+        let sample = self
+            .publisher
+            .loan_slice_uninit(len)
+            .map_err(error)?
+            .write_from_fn(|_| 0);
+        Ok(Box::new(IceoryxPublishSample {
+            sample,
+            notifier: &self.notifier,
+        }))
+    }
+
+    fn dyn_clone(&self) -> Box<dyn Publisher> {
+        Box::new(self.clone())
+    }
+}
+
+struct IceoryxPublishSample<'a> {
+    sample: SampleMut<Service, [u8], ()>,
+    notifier: &'a Notifier<Service>,
+}
+
+impl<'a> PublishSample<'a> for IceoryxPublishSample<'a> {
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.sample.payload_mut()
+    }
+
+    fn publish(self: Box<Self>) -> Result<(), BoxError> {
+        self.sample.send().map_err(error)?;
+        self.notifier.notify().map_err(error)?;
+        Ok(())
+    }
+}
+
+struct IceoryxSubscriber {
+    subscriber: iceoryx2::port::subscriber::Subscriber<Service, [u8], ()>,
+    listener: Listener<Service>,
+    _node: Arc<Node<Service>>,
+}
+
+impl Subscriber for IceoryxSubscriber {
+    fn recv(&mut self) -> Result<Option<Box<dyn ReceivedSample>>, BoxError> {
+        loop {
+            if let Some(sample) = self.subscriber.receive().map_err(error)? {
+                return Ok(Some(Box::new(IceoryxReceivedSample { sample })));
+            }
+            // notifications are queued, so we don't miss messages that are
+            // published between the `receive` call and this wait
+            self.listener.blocking_wait_one().map_err(error)?;
+        }
+    }
+}
+
+struct IceoryxReceivedSample {
+    sample: Sample<Service, [u8], ()>,
+}
+
+impl ReceivedSample for IceoryxReceivedSample {
+    fn get(&self) -> Cow<[u8]> {
+        Cow::Borrowed(self.sample.payload())
+    }
+}
+
+fn error(err: impl std::fmt::Debug) -> BoxError {
+    format!("{err:?}").into()
+}
@@ -9,21 +9,184 @@
 //! - **[Zenoh](https://zenoh.io/):** The zenoh project implements a distributed
 //!   publisher/subscriber system with automated routing. To use zenoh, use the
 //!   [`ZenohCommunicationLayer`][zenoh::ZenohCommunicationLayer] struct.
+//! - **[iceoryx2](https://iceoryx.io/):** Zero-copy shared memory communication
+//!   between processes on the same host, e.g. for large camera or lidar frames.
+//!   Requires the `iceoryx` feature. To use iceoryx2, use the
+//!   [`IceoryxCommunicationLayer`][iceoryx::IceoryxCommunicationLayer] struct.
+//!
+//! Transports that limit the message size can be wrapped in a
+//! [`FragmentingLayer`][fragment::FragmentingLayer], which splits large
+//! messages into fragments and reassembles them on the receiver.
+//!
+//! Other crates can provide their own backends by implementing
+//! [`CommunicationLayer`] and adding them to the [`registry`], which makes them
+//! selectable by name, e.g. from a dataflow configuration.
 
 use std::borrow::Cow;
 
+pub mod fragment;
+#[cfg(feature = "iceoryx")]
+pub mod iceoryx;
+pub mod registry;
 #[cfg(feature = "zenoh")]
 pub mod zenoh;
 
-type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+/// Error type of the communication layer.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
 /// Abstraction trait for different publisher/subscriber implementations.
 pub trait CommunicationLayer: Send + Sync {
     /// Creates a publisher for the given topic.
     fn publisher(&mut self, topic: &str) -> Result<Box<dyn Publisher>, BoxError>;
 
+    /// Creates a publisher whose messages are sent with the given priority.
+    ///
+    /// Backends that don't support prioritization, see
+    /// [`Capabilities::priorities`], ignore the priority. Messages of
+    /// different priorities might be reordered.
+    fn publisher_with_priority(
+        &mut self,
+        topic: &str,
+        priority: Priority,
+    ) -> Result<Box<dyn Publisher>, BoxError> {
+        let _ = priority;
+        self.publisher(topic)
+    }
+
+    /// Creates a publisher with the given priority and delivery guarantee.
+    ///
+    /// Backends without congestion control ignore the reliability and use
+    /// their native delivery guarantee.
+    fn publisher_with_qos(
+        &mut self,
+        topic: &str,
+        priority: Priority,
+        reliability: Reliability,
+    ) -> Result<Box<dyn Publisher>, BoxError> {
+        let _ = reliability;
+        self.publisher_with_priority(topic, priority)
+    }
+
     /// Subscribe to the given topic.
     fn subscribe(&mut self, topic: &str) -> Result<Box<dyn Subscriber>, BoxError>;
+
+    /// Describes the properties of this backend.
+    ///
+    /// The default implementation returns [`Capabilities::default`], which
+    /// makes no guarantees.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+impl<L: CommunicationLayer + ?Sized> CommunicationLayer for Box<L> {
+    fn publisher(&mut self, topic: &str) -> Result<Box<dyn Publisher>, BoxError> {
+        (**self).publisher(topic)
+    }
+
+    fn publisher_with_priority(
+        &mut self,
+        topic: &str,
+        priority: Priority,
+    ) -> Result<Box<dyn Publisher>, BoxError> {
+        (**self).publisher_with_priority(topic, priority)
+    }
+
+    fn publisher_with_qos(
+        &mut self,
+        topic: &str,
+        priority: Priority,
+        reliability: Reliability,
+    ) -> Result<Box<dyn Publisher>, BoxError> {
+        (**self).publisher_with_qos(topic, priority, reliability)
+    }
+
+    fn subscribe(&mut self, topic: &str) -> Result<Box<dyn Subscriber>, BoxError> {
+        (**self).subscribe(topic)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        (**self).capabilities()
+    }
+}
+
+/// Properties of a [`CommunicationLayer`] backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Maximum size of a single message in bytes, if limited.
+    ///
+    /// Larger messages can be sent by wrapping the backend in a
+    /// [`FragmentingLayer`][fragment::FragmentingLayer].
+    pub max_message_size: Option<usize>,
+    /// Whether the backend can reach subscribers on other hosts.
+    pub inter_host: bool,
+    /// Whether received samples refer to the publisher's memory instead of copies.
+    pub zero_copy: bool,
+    /// Whether messages are delivered reliably and in order.
+    pub reliable: bool,
+    /// Whether the backend sends messages of higher [`Priority`] first.
+    pub priorities: bool,
+}
+
+impl Capabilities {
+    /// Sets the maximum message size.
+    pub fn with_max_message_size(mut self, max_message_size: Option<usize>) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Sets whether the backend can reach subscribers on other hosts.
+    pub fn with_inter_host(mut self, inter_host: bool) -> Self {
+        self.inter_host = inter_host;
+        self
+    }
+
+    /// Sets whether the backend supports zero-copy delivery.
+    pub fn with_zero_copy(mut self, zero_copy: bool) -> Self {
+        self.zero_copy = zero_copy;
+        self
+    }
+
+    /// Sets whether messages are delivered reliably and in order.
+    pub fn with_reliable(mut self, reliable: bool) -> Self {
+        self.reliable = reliable;
+        self
+    }
+
+    /// Sets whether the backend honors the priority of publishers.
+    pub fn with_priorities(mut self, priorities: bool) -> Self {
+        self.priorities = priorities;
+        self
+    }
+}
+
+/// Priority of the messages of a publisher.
+///
+/// See [`CommunicationLayer::publisher_with_priority`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Bulk data that can wait, e.g. logs or recordings.
+    Low,
+    /// Regular data.
+    #[default]
+    Normal,
+    /// Data that should be sent before regular data.
+    High,
+    /// Time-critical data, e.g. control commands.
+    RealTime,
+}
+
+/// Delivery guarantee of a publisher.
+///
+/// See [`CommunicationLayer::publisher_with_qos`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Reliability {
+    /// Wait for congested links instead of dropping messages.
+    #[default]
+    Reliable,
+    /// Drop messages when links are congested.
+    BestEffort,
 }
 
 /// Allows publishing messages to subscribers.
@@ -53,6 +216,40 @@ pub trait Publisher: Send + Sync {
         sample.publish()?;
         Ok(())
     }
+
+    /// Publishes a message that consists of the given parts, in order.
+    ///
+    /// The parts are written directly into the message buffer, so a header can
+    /// be placed in front of a large payload without concatenating the two
+    /// first.
+    fn publish_vectored(&self, parts: &[&[u8]]) -> Result<(), BoxError> {
+        let len = parts.iter().map(|part| part.len()).sum();
+        let mut sample = self.prepare(len)?;
+        copy_parts(parts, 0, sample.as_mut_slice());
+        sample.publish()
+    }
+}
+
+/// Fills `target` with the bytes of the concatenated `parts`, starting at
+/// `offset`.
+///
+/// Panics if the parts are too short to fill `target`.
+pub(crate) fn copy_parts(parts: &[&[u8]], mut offset: usize, mut target: &mut [u8]) {
+    for part in parts {
+        if target.is_empty() {
+            break;
+        }
+        let Some(part) = part.get(offset..) else {
+            offset -= part.len();
+            continue;
+        };
+        offset = 0;
+        let len = part.len().min(target.len());
+        let (head, rest) = std::mem::take(&mut target).split_at_mut(len);
+        head.copy_from_slice(&part[..len]);
+        target = rest;
+    }
+    assert!(target.is_empty(), "parts are shorter than the target");
 }
 
 /// A prepared message constructed by [`Publisher::prepare`].
@@ -0,0 +1,116 @@
+//! Registry of [`CommunicationLayer`] backends, by name.
+//!
+//! Backends are created from a name and a set of string options, so they can
+//! be selected through configuration files. The built-in backends are
+//! registered automatically:
+//!
+//! - `zenoh` (requires the `zenoh` feature) opens a zenoh session with the
+//!   default configuration. The `prefix` option sets the topic prefix, which
+//!   defaults to `dora`.
+//! - `iceoryx` (requires the `iceoryx` feature) creates an iceoryx2 node. The
+//!   `name` option sets the node name and the `prefix` option the topic prefix.
+//!
+//! Crates that provide other transports, e.g. CAN gateways or vendor
+//! middleware, register them through [`register_backend`] before the backend
+//! is used:
+//!
+//! ```no_run
+//! use communication_layer_pub_sub::registry;
+//!
+//! # fn init_can_gateway(
+//! #     interface: Option<&String>,
+//! # ) -> Result<Box<dyn communication_layer_pub_sub::CommunicationLayer>, communication_layer_pub_sub::BoxError> { todo!() }
+//! registry::register_backend("can-gateway", |options| {
+//!     init_can_gateway(options.get("interface"))
+//! })?;
+//! # Ok::<(), communication_layer_pub_sub::BoxError>(())
+//! ```
+
+use crate::{BoxError, CommunicationLayer};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// Options that are passed to a backend factory.
+pub type BackendOptions = BTreeMap<String, String>;
+
+type BackendFactory =
+    Arc<dyn Fn(&BackendOptions) -> Result<Box<dyn CommunicationLayer>, BoxError> + Send + Sync>;
+
+fn backends() -> &'static Mutex<BTreeMap<String, BackendFactory>> {
+    static BACKENDS: OnceLock<Mutex<BTreeMap<String, BackendFactory>>> = OnceLock::new();
+    BACKENDS.get_or_init(|| Mutex::new(builtin_backends()))
+}
+
+/// Registers a backend under the given name.
+///
+/// Returns an error if a backend with the same name is already registered.
+pub fn register_backend<F>(name: &str, factory: F) -> Result<(), BoxError>
+where
+    F: Fn(&BackendOptions) -> Result<Box<dyn CommunicationLayer>, BoxError> + Send + Sync + 'static,
+{
+    let mut backends = backends().lock().map_err(|_| "backend registry poisoned")?;
+    if backends.contains_key(name) {
+        return Err(format!("communication backend `{name}` is already registered").into());
+    }
+    backends.insert(name.to_owned(), Arc::new(factory));
+    Ok(())
+}
+
+/// Creates an instance of the backend with the given name.
+pub fn create_backend(
+    name: &str,
+    options: &BackendOptions,
+) -> Result<Box<dyn CommunicationLayer>, BoxError> {
+    let factory = backends()
+        .lock()
+        .map_err(|_| "backend registry poisoned")?
+        .get(name)
+        .cloned();
+    match factory {
+        // call the factory without holding the lock, it might register other backends
+        Some(factory) => factory(options),
+        None => Err(format!(
+            "unknown communication backend `{name}` (registered: {})",
+            registered_backends().join(", ")
+        )
+        .into()),
+    }
+}
+
+/// Returns the names of all registered backends.
+pub fn registered_backends() -> Vec<String> {
+    backends()
+        .lock()
+        .map(|backends| backends.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[allow(unused_mut, clippy::let_and_return)]
+fn builtin_backends() -> BTreeMap<String, BackendFactory> {
+    let mut backends = BTreeMap::<String, BackendFactory>::new();
+    #[cfg(feature = "zenoh")]
+    backends.insert(
+        "zenoh".to_owned(),
+        Arc::new(|options: &BackendOptions| {
+            let prefix = options
+                .get("prefix")
+                .cloned()
+                .unwrap_or_else(|| "dora".to_owned());
+            let layer = crate::zenoh::ZenohCommunicationLayer::init(Default::default(), prefix)?;
+            Ok(Box::new(layer) as Box<dyn CommunicationLayer>)
+        }),
+    );
+    #[cfg(feature = "iceoryx")]
+    backends.insert(
+        "iceoryx".to_owned(),
+        Arc::new(|options: &BackendOptions| {
+            let name = options.get("name").map(String::as_str).unwrap_or("dora");
+            let prefix = options.get("prefix").cloned().unwrap_or_default();
+            let layer = crate::iceoryx::IceoryxCommunicationLayer::init(name, prefix)?;
+            Ok(Box::new(layer) as Box<dyn CommunicationLayer>)
+        }),
+    );
+    backends
+}
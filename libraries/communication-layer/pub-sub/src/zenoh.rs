@@ -1,6 +1,6 @@
 //! Provides [`ZenohCommunicationLayer`] to communicate over `zenoh`.
 
-use super::{CommunicationLayer, Publisher, Subscriber};
+use super::{Capabilities, CommunicationLayer, Publisher, Subscriber};
 use crate::{BoxError, ReceivedSample};
 use std::{borrow::Cow, sync::Arc, time::Duration};
 use zenoh::{
@@ -38,11 +38,38 @@ impl ZenohCommunicationLayer {
 
 impl CommunicationLayer for ZenohCommunicationLayer {
     fn publisher(&mut self, topic: &str) -> Result<Box<dyn Publisher>, BoxError> {
+        self.publisher_with_priority(topic, crate::Priority::RealTime)
+    }
+
+    fn publisher_with_priority(
+        &mut self,
+        topic: &str,
+        priority: crate::Priority,
+    ) -> Result<Box<dyn Publisher>, BoxError> {
+        self.publisher_with_qos(topic, priority, crate::Reliability::Reliable)
+    }
+
+    fn publisher_with_qos(
+        &mut self,
+        topic: &str,
+        priority: crate::Priority,
+        reliability: crate::Reliability,
+    ) -> Result<Box<dyn Publisher>, BoxError> {
+        let congestion_control = match reliability {
+            crate::Reliability::Reliable => CongestionControl::Block,
+            crate::Reliability::BestEffort => CongestionControl::Drop,
+        };
+        let priority = match priority {
+            crate::Priority::Low => Priority::DataLow,
+            crate::Priority::Normal => Priority::Data,
+            crate::Priority::High => Priority::InteractiveHigh,
+            crate::Priority::RealTime => Priority::RealTime,
+        };
         let publisher = self
             .zenoh
             .declare_publisher(self.prefixed(topic))
-            .congestion_control(CongestionControl::Block)
-            .priority(Priority::RealTime)
+            .congestion_control(congestion_control)
+            .priority(priority)
             .res_sync()
             .map_err(BoxError::from)?;
 
@@ -59,6 +86,13 @@ impl CommunicationLayer for ZenohCommunicationLayer {
 
         Ok(Box::new(ZenohReceiver(subscriber)))
     }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+            .with_inter_host(true)
+            .with_reliable(true)
+            .with_priorities(true)
+    }
 }
 
 impl Drop for ZenohCommunicationLayer {
@@ -1,3 +1,4 @@
+use dora_message::Compression;
 use once_cell::sync::OnceCell;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -6,6 +7,7 @@ use std::{
     collections::{BTreeMap, BTreeSet},
     convert::Infallible,
     fmt,
+    num::NonZeroU32,
     str::FromStr,
     time::Duration,
 };
@@ -275,10 +277,164 @@ pub struct NodeRunConfig {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
-#[serde(deny_unknown_fields, from = "InputDef", into = "InputDef")]
+#[serde(deny_unknown_fields, try_from = "InputDef", into = "InputDef")]
 pub struct Input {
     pub mapping: InputMapping,
+    /// Further outputs that are delivered to this input, in addition to `mapping`.
+    ///
+    /// Set through the `sources` list. The daemon adds the [`SOURCE_PARAMETER`]
+    /// to the metadata of each message, so the receiver can tell the sources
+    /// apart. The `queue_size` applies to each source separately, so that a
+    /// fast source can't push the messages of a slower source out of the
+    /// queue. The input is closed once all of its sources are closed.
+    ///
+    /// [`SOURCE_PARAMETER`]: dora_message::SOURCE_PARAMETER
+    #[serde(default)]
+    pub additional_sources: Vec<InputMapping>,
     pub queue_size: Option<usize>,
+    pub priority: Option<InputPriority>,
+    pub compression: Option<InputCompression>,
+    pub rate_limit: Option<RateLimit>,
+    pub downsample: Option<Downsample>,
+    pub qos: Option<QosPreset>,
+}
+
+impl Input {
+    /// Returns the primary `mapping`, followed by the `additional_sources`.
+    pub fn sources(&self) -> impl Iterator<Item = &InputMapping> {
+        std::iter::once(&self.mapping).chain(&self.additional_sources)
+    }
+
+    /// Returns the `queue_size`, falling back to the queue size of the `qos`
+    /// preset.
+    pub fn effective_queue_size(&self) -> Option<usize> {
+        self.queue_size
+            .or_else(|| self.qos.and_then(|qos| qos.queue_size()))
+    }
+}
+
+/// Compresses the data of an input when it is sent from another machine.
+///
+/// Has no effect if the source node runs on the same machine as the receiver.
+/// If multiple inputs on the same machine receive the same output, the first
+/// of them in the dataflow that enables compression determines the settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InputCompression {
+    #[schemars(with = "String")]
+    pub algorithm: Compression,
+    /// Compression level, the default level of the algorithm is used if not set.
+    ///
+    /// Higher levels compress better, but take more CPU time. For `lz4`, levels
+    /// above 0 select the high compression mode.
+    #[serde(default)]
+    pub level: Option<i32>,
+}
+
+/// Limits the rate at which messages are delivered to an input.
+///
+/// The daemon enforces the limit with a token bucket that allows bursts of up
+/// to one second worth of messages. Messages that exceed the limit are dropped
+/// for this input, other receivers of the same output are not affected. If the
+/// source node runs on another machine, the sending daemon drops the messages
+/// already, so that they don't use network bandwidth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Maximum number of data bytes per second, measured before compression.
+    #[serde(default)]
+    pub bytes_per_second: Option<u64>,
+    /// Maximum number of messages per second.
+    #[serde(default)]
+    pub messages_per_second: Option<u32>,
+}
+
+/// Delivers only a subset of the messages of an input, e.g. 1 Hz snapshots
+/// of a 60 Hz stream.
+///
+/// The daemon drops the other messages before they reach the node. If the
+/// source node runs on another machine, the sending daemon drops them already,
+/// as long as all inputs on the receiving machine that read the same output
+/// use the same settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Downsample {
+    /// Deliver only every n-th message, starting with the first one.
+    #[serde(default)]
+    pub every: Option<NonZeroU32>,
+    /// Maximum number of delivered messages per second.
+    #[serde(default)]
+    pub max_frequency: Option<NonZeroU32>,
+}
+
+/// Quality-of-service preset of an input.
+///
+/// Presets give an input the same delivery semantics on all transports. The
+/// daemon maps them onto the native settings of the communication backend,
+/// e.g. the congestion control of zenoh. Explicit settings of the input, such
+/// as `queue_size`, take precedence over the preset.
+///
+/// If multiple inputs on the same machine receive the same output from a
+/// remote machine, the output is sent reliably if one of them is reliable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QosPreset {
+    /// Don't drop messages on congested network links, wait for them instead.
+    Reliable,
+    /// Drop messages when network links are congested.
+    BestEffort,
+    /// Best-effort delivery of only the latest message, for high-rate sensor
+    /// streams. Sets the `queue_size` to 1.
+    SensorData,
+    /// Reliable delivery of rarely changing data. The source output is latched,
+    /// so the input receives the last value even if the node starts after it
+    /// was sent.
+    LatchedConfig,
+}
+
+impl QosPreset {
+    pub fn is_reliable(&self) -> bool {
+        matches!(self, Self::Reliable | Self::LatchedConfig)
+    }
+
+    pub fn is_latched(&self) -> bool {
+        matches!(self, Self::LatchedConfig)
+    }
+
+    /// Queue size that is used if the input doesn't set one.
+    pub fn queue_size(&self) -> Option<usize> {
+        match self {
+            Self::SensorData => Some(1),
+            Self::Reliable | Self::BestEffort | Self::LatchedConfig => None,
+        }
+    }
+}
+
+/// Scheduling priority of an input.
+///
+/// When multiple inputs are queued for a node, the daemon delivers inputs with
+/// a higher priority first. Inputs with the same priority are delivered in the
+/// order they were received.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum InputPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -288,6 +444,20 @@ pub enum InputDef {
     WithOptions {
         source: InputMapping,
         queue_size: Option<usize>,
+        priority: Option<InputPriority>,
+        compression: Option<InputCompression>,
+        rate_limit: Option<RateLimit>,
+        downsample: Option<Downsample>,
+        qos: Option<QosPreset>,
+    },
+    MultipleSources {
+        sources: Vec<InputMapping>,
+        queue_size: Option<usize>,
+        priority: Option<InputPriority>,
+        compression: Option<InputCompression>,
+        rate_limit: Option<RateLimit>,
+        downsample: Option<Downsample>,
+        qos: Option<QosPreset>,
     },
 }
 
@@ -296,31 +466,113 @@ impl From<Input> for InputDef {
         match input {
             Input {
                 mapping,
+                additional_sources,
                 queue_size: None,
-            } => Self::MappingOnly(mapping),
+                priority: None,
+                compression: None,
+                rate_limit: None,
+                downsample: None,
+                qos: None,
+            } if additional_sources.is_empty() => Self::MappingOnly(mapping),
             Input {
                 mapping,
+                additional_sources,
                 queue_size,
-            } => Self::WithOptions {
+                priority,
+                compression,
+                rate_limit,
+                downsample,
+                qos,
+            } if additional_sources.is_empty() => Self::WithOptions {
                 source: mapping,
                 queue_size,
+                priority,
+                compression,
+                rate_limit,
+                downsample,
+                qos,
+            },
+            Input {
+                mapping,
+                additional_sources,
+                queue_size,
+                priority,
+                compression,
+                rate_limit,
+                downsample,
+                qos,
+            } => Self::MultipleSources {
+                sources: std::iter::once(mapping).chain(additional_sources).collect(),
+                queue_size,
+                priority,
+                compression,
+                rate_limit,
+                downsample,
+                qos,
             },
         }
     }
 }
 
-impl From<InputDef> for Input {
-    fn from(value: InputDef) -> Self {
-        match value {
+impl TryFrom<InputDef> for Input {
+    type Error = String;
+
+    fn try_from(value: InputDef) -> Result<Self, Self::Error> {
+        let input = match value {
             InputDef::MappingOnly(mapping) => Self {
                 mapping,
+                additional_sources: Vec::new(),
                 queue_size: None,
+                priority: None,
+                compression: None,
+                rate_limit: None,
+                downsample: None,
+                qos: None,
             },
-            InputDef::WithOptions { source, queue_size } => Self {
+            InputDef::WithOptions {
+                source,
+                queue_size,
+                priority,
+                compression,
+                rate_limit,
+                downsample,
+                qos,
+            } => Self {
                 mapping: source,
+                additional_sources: Vec::new(),
                 queue_size,
+                priority,
+                compression,
+                rate_limit,
+                downsample,
+                qos,
             },
-        }
+            InputDef::MultipleSources {
+                sources,
+                queue_size,
+                priority,
+                compression,
+                rate_limit,
+                downsample,
+                qos,
+            } => {
+                let mut sources = sources.into_iter();
+                let mapping = sources
+                    .next()
+                    .ok_or_else(|| "input `sources` must not be empty".to_owned())?;
+                Self {
+                    mapping,
+                    additional_sources: sources.collect(),
+                    queue_size,
+                    priority,
+                    compression,
+                    rate_limit,
+                    downsample,
+                    qos,
+                }
+            }
+        };
+        Ok(input)
     }
 }
 
@@ -342,6 +594,24 @@ pub struct CommunicationConfig {
     )]
     #[schemars(with = "String")]
     pub remote: RemoteCommunicationConfig,
+    /// Encrypts and authenticates the data that is exchanged between machines.
+    #[serde(default, rename = "_unstable_encryption")]
+    #[schemars(skip)]
+    pub encryption: Option<EncryptionConfig>,
+}
+
+/// Encryption of the data that is sent between the daemons of a dataflow.
+///
+/// All daemons of the dataflow need the same pre-shared key. Messages with an
+/// invalid authentication tag or without encryption are dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptionConfig {
+    /// Path to a file that contains the 256-bit key as 64 hex digits.
+    ///
+    /// Relative paths are resolved against the working directory of the
+    /// dataflow on each machine. The key itself is never sent over the network.
+    pub key_file: std::path::PathBuf,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -360,11 +630,36 @@ impl Default for LocalCommunicationConfig {
 #[serde(deny_unknown_fields, rename_all = "lowercase")]
 pub enum RemoteCommunicationConfig {
     Tcp,
-    // TODO:a
-    // Zenoh {
-    //     config: Option<serde_yaml::Value>,
-    //     prefix: String,
-    // },
+    /// Exchange data between machines over [zenoh](https://zenoh.io/).
+    ///
+    /// Zenoh discovers the other daemons and routes the messages, so the
+    /// machines don't need to reach each other directly. Requires daemons
+    /// that were built with the `zenoh` feature.
+    Zenoh {
+        /// Zenoh configuration, in the format of zenoh's configuration file.
+        ///
+        /// The default zenoh configuration is used if not set.
+        #[serde(default)]
+        config: Option<serde_yaml::Value>,
+        /// Prefix of the zenoh key expressions used by the dataflow.
+        #[serde(default = "default_zenoh_prefix")]
+        prefix: String,
+    },
+    /// Exchange data between machines over a backend of the pub-sub
+    /// communication layer, selected by its registered name.
+    ///
+    /// Backends that are not built into dora need to be registered in the
+    /// daemon before the dataflow is started.
+    Custom {
+        backend: String,
+        /// Backend-specific options.
+        #[serde(default)]
+        options: BTreeMap<String, String>,
+    },
+}
+
+fn default_zenoh_prefix() -> String {
+    "dora".to_owned()
 }
 
 impl Default for RemoteCommunicationConfig {
@@ -1,11 +1,16 @@
-use crate::{config::NodeId, daemon_messages::DataflowId, topics::DataflowDaemonResult};
+use crate::{
+    config::NodeId,
+    daemon_messages::{DataflowId, OperatorError},
+    topics::DataflowDaemonResult,
+    version::Version,
+};
 use eyre::eyre;
 pub use log::Level;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum CoordinatorRequest {
     Register {
-        dora_version: String,
+        dora_version: Version,
         machine_id: String,
         listen_port: u16,
     },
@@ -40,6 +45,12 @@ pub enum DaemonEvent {
     },
     Heartbeat,
     Log(LogMessage),
+    /// An operator of a runtime node failed.
+    OperatorError {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        error: OperatorError,
+    },
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -2,16 +2,20 @@ use std::{
     collections::{BTreeMap, BTreeSet},
     fmt,
     net::SocketAddr,
+    num::NonZeroUsize,
     path::PathBuf,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use crate::{
     config::{DataId, NodeId, NodeRunConfig, OperatorId},
-    descriptor::{Descriptor, OperatorDefinition, ResolvedNode},
+    descriptor::{Descriptor, OperatorDefinition, ResolvedNode, RuntimeProfile},
+    record::RecordingInfo,
+    topics::AttachedNode,
+    version::Version,
 };
 use aligned_vec::{AVec, ConstAlign};
-use dora_message::{uhlc, Metadata};
+use dora_message::{uhlc, Metadata, Parameter};
 use uuid::{NoContext, Timestamp, Uuid};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -41,6 +45,20 @@ pub enum DaemonCommunication {
 pub struct RuntimeConfig {
     pub node: NodeConfig,
     pub operators: Vec<OperatorDefinition>,
+    /// Maximum number of operators that process events at the same time.
+    ///
+    /// Defaults to one, i.e. the operators process their events serially.
+    #[serde(default)]
+    pub workers: Option<NonZeroUsize>,
+    #[serde(default)]
+    pub profile: Option<RuntimeProfile>,
+    /// Commands that start the Python interpreters of isolated operators,
+    /// e.g. `["/path/to/venv/bin/python"]`.
+    ///
+    /// Only set for operators whose environment differs from the one of the
+    /// runtime. Other isolated operators use the interpreter of the runtime.
+    #[serde(default)]
+    pub isolated_python: BTreeMap<OperatorId, Vec<String>>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -48,7 +66,7 @@ pub enum DaemonRequest {
     Register {
         dataflow_id: DataflowId,
         node_id: NodeId,
-        dora_version: String,
+        dora_version: Version,
     },
     Subscribe,
     SendMessage {
@@ -72,6 +90,14 @@ pub enum DaemonRequest {
     NodeConfig {
         node_id: NodeId,
     },
+    /// Periodic liveness signal of the node, see [`NodeHealth`].
+    Heartbeat {
+        health: NodeHealth,
+    },
+    /// Sent by runtime nodes when one of their operators fails.
+    ReportOperatorError(OperatorError),
+    /// Structured log record of the node or one of its operators.
+    Log(NodeLogRecord),
 }
 
 impl DaemonRequest {
@@ -80,7 +106,10 @@ impl DaemonRequest {
         match self {
             DaemonRequest::SendMessage { .. }
             | DaemonRequest::NodeConfig { .. }
-            | DaemonRequest::ReportDropTokens { .. } => false,
+            | DaemonRequest::ReportDropTokens { .. }
+            | DaemonRequest::Heartbeat { .. }
+            | DaemonRequest::ReportOperatorError(_)
+            | DaemonRequest::Log(_) => false,
             DaemonRequest::Register { .. }
             | DaemonRequest::Subscribe
             | DaemonRequest::CloseOutputs(_)
@@ -105,11 +134,115 @@ impl DaemonRequest {
             | DaemonRequest::NextFinishedDropTokens
             | DaemonRequest::ReportDropTokens { .. }
             | DaemonRequest::SendMessage { .. }
+            | DaemonRequest::Heartbeat { .. }
+            | DaemonRequest::ReportOperatorError(_)
+            | DaemonRequest::Log(_)
             | DaemonRequest::EventStreamDropped => false,
         }
     }
 }
 
+/// Health that a node reports to its daemon.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct NodeHealth {
+    pub status: HealthStatus,
+    /// Optional details, e.g. the reason for a degraded status.
+    pub message: Option<String>,
+}
+
+/// Log record that a node emits through the daemon instead of printing it to
+/// stdout.
+///
+/// The daemon tags the record with the dataflow and node ID, writes it to the
+/// log file of the node, and forwards it to the coordinator.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NodeLogRecord {
+    pub level: log::Level,
+    pub message: String,
+    /// The operator that emitted the record, for records of runtime nodes.
+    pub operator_id: Option<OperatorId>,
+    /// Key/value context of the record, e.g. the number of a camera frame.
+    pub fields: BTreeMap<String, Parameter>,
+}
+
+impl NodeLogRecord {
+    /// Source of the record in `node` or `node/operator` form.
+    pub fn target(&self, node_id: &NodeId) -> String {
+        match &self.operator_id {
+            Some(operator_id) => format!("{node_id}/{operator_id}"),
+            None => node_id.to_string(),
+        }
+    }
+
+    /// The message followed by the fields in `key=value` form.
+    pub fn message_with_fields(&self) -> String {
+        let mut message = self.message.clone();
+        for (key, value) in &self.fields {
+            let value = match value {
+                Parameter::Bool(value) => value.to_string(),
+                Parameter::Integer(value) => value.to_string(),
+                Parameter::Float(value) => value.to_string(),
+                Parameter::String(value) => format!("{value:?}"),
+                Parameter::Bytes(value) => format!("<{} bytes>", value.len()),
+                Parameter::Timestamp(nanos) => format!("{nanos}ns"),
+            };
+            message.push_str(&format!(" {key}={value}"));
+        }
+        message
+    }
+
+    /// Formats the record as a line of a node log file.
+    pub fn log_line(&self, dataflow_id: &DataflowId, node_id: &NodeId) -> String {
+        format!(
+            "{:<5} [{dataflow_id}/{}] {}\n",
+            self.level,
+            self.target(node_id),
+            self.message_with_fields()
+        )
+    }
+}
+
+/// Structured description of an error raised by an operator, e.g. an
+/// exception of a Python operator.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OperatorError {
+    pub operator_id: OperatorId,
+    /// The input that the operator was processing when the error occurred.
+    pub input_id: Option<DataId>,
+    /// Type of the error, e.g. the exception class `ValueError`.
+    pub kind: Option<String>,
+    pub message: String,
+    pub traceback: Option<String>,
+}
+
+impl fmt::Display for OperatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(traceback) = &self.traceback {
+            writeln!(f, "{traceback}")?;
+        }
+        match &self.kind {
+            Some(kind) => write!(f, "{kind}: {}", self.message)?,
+            None => write!(f, "{}", self.message)?,
+        }
+        if let Some(input_id) = &self.input_id {
+            write!(f, " (while processing input `{input_id}`)")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for OperatorError {}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum HealthStatus {
+    #[default]
+    Healthy,
+    /// The node works, but with reduced functionality or performance.
+    Degraded,
+    /// The node can't do its work, e.g. because a sensor is disconnected.
+    Unhealthy,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub enum DataMessage {
     Vec(AVec<u8, ConstAlign<128>>),
@@ -127,6 +260,17 @@ impl DataMessage {
             DataMessage::SharedMemory { drop_token, .. } => Some(*drop_token),
         }
     }
+
+    pub fn len(&self) -> usize {
+        match self {
+            DataMessage::Vec(v) => v.len(),
+            DataMessage::SharedMemory { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl fmt::Debug for DataMessage {
@@ -175,6 +319,16 @@ pub enum NodeEvent {
     Reload {
         operator_id: Option<OperatorId>,
     },
+    /// Only sent to runtime nodes.
+    LoadOperator {
+        /// JSON-serialized [`OperatorDefinition`]. Node events are encoded with
+        /// bincode, which doesn't support the flattened operator config.
+        operator: String,
+    },
+    /// Only sent to runtime nodes.
+    UnloadOperator {
+        operator_id: OperatorId,
+    },
     Input {
         id: DataId,
         metadata: Metadata,
@@ -245,9 +399,77 @@ pub enum DaemonCoordinatorEvent {
         node_id: NodeId,
         operator_id: Option<OperatorId>,
     },
+    /// Stop the given local node and spawn it again once it exited.
+    RestartNode {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        grace_duration: Option<Duration>,
+    },
+    LoadOperator {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        /// Machine that runs the runtime node.
+        machine: String,
+        operator: OperatorDefinition,
+    },
+    UnloadOperator {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        machine: String,
+        operator_id: OperatorId,
+    },
+    AttachNode {
+        dataflow_id: DataflowId,
+        node: AttachedNode,
+        descriptor: Descriptor,
+        /// Machines of the nodes whose inputs the attached node sends to.
+        target_machines: BTreeMap<NodeId, String>,
+    },
+    DetachNode {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+    },
     Logs {
         dataflow_id: DataflowId,
         node_id: NodeId,
+        /// Only return the log messages that were written after this time, at
+        /// a resolution of one second.
+        #[serde(default)]
+        since: Option<SystemTime>,
+        /// Return the log file contents starting at this byte offset, e.g.
+        /// the end of a previously returned chunk. Takes precedence over
+        /// `since`.
+        #[serde(default)]
+        offset: Option<u64>,
+    },
+    NodeStats {
+        dataflow_id: DataflowId,
+    },
+    /// Starts recording the given outputs (all outputs if empty) of the
+    /// dataflow's local nodes.
+    StartRecording {
+        dataflow_id: DataflowId,
+        outputs: BTreeSet<String>,
+    },
+    StopRecording {
+        dataflow_id: DataflowId,
+    },
+    ListRecordings,
+    /// Stage the given daemon executable, then replace the current executable
+    /// with it and restart once all running dataflows are finished.
+    ///
+    /// Fails if the new executable doesn't run on the machine or if its version
+    /// is not compatible with `coordinator_version`.
+    Upgrade {
+        binary: Vec<u8>,
+        coordinator_version: Version,
+    },
+    /// Extract a dataflow bundle, replacing a previous deployment of the same
+    /// name. Fails if a running dataflow uses the target directory.
+    Deploy {
+        name: String,
+        bundle: Vec<u8>,
+        target_dir: Option<PathBuf>,
     },
     Destroy,
     Heartbeat,
@@ -270,6 +492,37 @@ pub enum InterDaemonEvent {
     InputsClosed {
         dataflow_id: DataflowId,
         inputs: BTreeSet<(NodeId, DataId)>,
+        /// The closed outputs, used to keep inputs with multiple sources open
+        /// until all of their sources are closed.
+        sources: BTreeSet<(NodeId, DataId)>,
+    },
+    /// An event of a dataflow that uses encryption, e.g. an `Output`.
+    ///
+    /// The ciphertext is the bincode-serialized event, encrypted and
+    /// authenticated with the pre-shared key of the dataflow. Clock sync
+    /// events are encrypted too, for the clock reference of the dataflow.
+    Encrypted {
+        dataflow_id: DataflowId,
+        /// Machine ID of the sending daemon, which is authenticated too.
+        sender: String,
+        /// Whether the encrypted event may be dropped if the connection is
+        /// congested, i.e. it's not an `InputsClosed` event.
+        droppable: bool,
+        /// Random prefix, followed by a big-endian counter that receivers use
+        /// to detect replayed events.
+        nonce: [u8; 24],
+        ciphertext: Vec<u8>,
+    },
+    /// Starts a clock offset measurement, timestamps are nanoseconds since the UNIX epoch.
+    ClockSyncRequest {
+        machine_id: String,
+        request_sent: u64,
+    },
+    ClockSyncReply {
+        machine_id: String,
+        request_sent: u64,
+        request_received: u64,
+        reply_sent: u64,
     },
 }
 
@@ -277,13 +530,84 @@ pub enum InterDaemonEvent {
 pub enum DaemonCoordinatorReply {
     SpawnResult(Result<(), String>),
     ReloadResult(Result<(), String>),
+    OperatorResult(Result<(), String>),
+    AttachResult(Result<(), String>),
     StopResult(Result<(), String>),
     DestroyResult {
         result: Result<(), String>,
         #[serde(skip)]
         notify: Option<tokio::sync::oneshot::Sender<()>>,
     },
-    Logs(Result<Vec<u8>, String>),
+    Logs(Result<LogChunk, String>),
+    NodeStats(Result<BTreeMap<NodeId, NodeStats>, String>),
+    /// Version of the staged executable.
+    UpgradeResult(Result<Version, String>),
+    RestartNodeResult(Result<(), String>),
+    RecordingResult(Result<(), String>),
+    Recordings(Result<Vec<RecordingInfo>, String>),
+    DeployResult(Result<PathBuf, String>),
+}
+
+/// A part of the log file of a node.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct LogChunk {
+    /// Byte offset of `data` in the log file.
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+impl LogChunk {
+    /// Offset at which the next chunk starts.
+    pub fn end(&self) -> u64 {
+        self.offset + self.data.len() as u64
+    }
+}
+
+/// Runtime statistics of a single node, as tracked by its daemon.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct NodeStats {
+    /// Messages sent per output.
+    pub outputs: BTreeMap<DataId, EdgeStats>,
+    /// Messages delivered per input.
+    pub inputs: BTreeMap<DataId, EdgeStats>,
+    /// Number of events that are waiting to be received by the node.
+    pub queue_depth: usize,
+    /// Number of inputs that were dropped because the input queue was full.
+    pub dropped_inputs: u64,
+    /// Mean time between sending an input and handing it to the receiving node.
+    ///
+    /// Covers the inputs that were delivered since the statistics were last
+    /// requested. `None` if no input was delivered in that time.
+    pub mean_latency: Option<Duration>,
+    /// Health reported with the last heartbeat of the node.
+    #[serde(default)]
+    pub health: Option<NodeHealth>,
+    /// Time since the last heartbeat of the node.
+    #[serde(default)]
+    pub since_last_heartbeat: Option<Duration>,
+    /// CPU usage of the node process in percent of one core, sampled when the
+    /// statistics are requested.
+    #[serde(default)]
+    pub cpu_usage: Option<f32>,
+    /// Resident memory of the node process in bytes.
+    #[serde(default)]
+    pub memory: Option<u64>,
+    /// Set once the node exited, to the error message if it failed.
+    #[serde(default)]
+    pub exit: Option<Result<(), String>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct EdgeStats {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+impl EdgeStats {
+    pub fn add(&mut self, bytes: usize) {
+        self.messages += 1;
+        self.bytes += bytes as u64;
+    }
 }
 
 pub type DataflowId = Uuid;
@@ -0,0 +1,516 @@
+//! Filter expressions for operator inputs.
+//!
+//! A filter is a list of comparisons, combined with `&&` and `||` (`&&` binds
+//! stronger) and grouped with parentheses. Each comparison compares a field to
+//! a literal:
+//!
+//! ```text
+//! data.confidence > 0.5 && (metadata.len == 1 || data.label == "person")
+//! ```
+//!
+//! The supported fields are:
+//!
+//! - `metadata.watermark`, `metadata.deadline`, and `metadata.len` (the number
+//!   of elements of the input array)
+//! - `data` for the first element of a primitive array
+//! - `data.<field>` for a field of the first row of a struct array
+//!
+//! Literals are numbers, double-quoted strings, `true`, and `false`. A
+//! comparison with a missing field or a value of a different type is false.
+
+use eyre::{bail, eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt, iter::Peekable, str::FromStr};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct InputFilter {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    /// Expressions that are combined with `||`.
+    Any(Vec<Expr>),
+    /// Expressions that are combined with `&&`.
+    All(Vec<Expr>),
+    Compare(Comparison),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Comparison {
+    field: FilterField,
+    op: CompareOp,
+    value: FilterValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterField {
+    Watermark,
+    Deadline,
+    Len,
+    /// The first element of the input data, or a field of its first row if
+    /// the data is a struct array.
+    Data(Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    Number(f64),
+    String(String),
+    Bool(bool),
+}
+
+impl InputFilter {
+    /// Evaluates the filter, looking up field values through the given function.
+    pub fn matches(&self, mut lookup: impl FnMut(&FilterField) -> Option<FilterValue>) -> bool {
+        self.expr.matches(&mut lookup)
+    }
+}
+
+impl Expr {
+    fn matches(&self, lookup: &mut impl FnMut(&FilterField) -> Option<FilterValue>) -> bool {
+        match self {
+            Expr::Any(exprs) => exprs.iter().any(|expr| expr.matches(lookup)),
+            Expr::All(exprs) => exprs.iter().all(|expr| expr.matches(lookup)),
+            Expr::Compare(comparison) => {
+                lookup(&comparison.field).is_some_and(|value| comparison.matches(&value))
+            }
+        }
+    }
+}
+
+impl Comparison {
+    fn matches(&self, value: &FilterValue) -> bool {
+        let ordering = match (value, &self.value) {
+            (FilterValue::Number(a), FilterValue::Number(b)) => a.partial_cmp(b),
+            (FilterValue::String(a), FilterValue::String(b)) => Some(a.cmp(b)),
+            (FilterValue::Bool(a), FilterValue::Bool(b)) => {
+                return match self.op {
+                    CompareOp::Eq => a == b,
+                    CompareOp::Ne => a != b,
+                    _ => false,
+                }
+            }
+            _ => None,
+        };
+        let Some(ordering) = ordering else {
+            return false;
+        };
+        match self.op {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Ne => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Le => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl FromStr for InputFilter {
+    type Err = eyre::Report;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let mut tokens = tokenize(source)?.into_iter().peekable();
+        let expr = parse_any(&mut tokens)?;
+        match tokens.next() {
+            Some(other) => bail!("expected `&&` or `||`, found {other}"),
+            None => Ok(Self {
+                source: source.to_owned(),
+                expr,
+            }),
+        }
+    }
+}
+
+type Tokens = Peekable<std::vec::IntoIter<Token>>;
+
+/// Parses expressions that are combined with `||`.
+fn parse_any(tokens: &mut Tokens) -> Result<Expr> {
+    let mut any_of = vec![parse_all(tokens)?];
+    while tokens.next_if(|t| matches!(t, Token::Or)).is_some() {
+        any_of.push(parse_all(tokens)?);
+    }
+    Ok(if any_of.len() == 1 {
+        any_of.remove(0)
+    } else {
+        Expr::Any(any_of)
+    })
+}
+
+/// Parses expressions that are combined with `&&`.
+fn parse_all(tokens: &mut Tokens) -> Result<Expr> {
+    let mut all_of = vec![parse_operand(tokens)?];
+    while tokens.next_if(|t| matches!(t, Token::And)).is_some() {
+        all_of.push(parse_operand(tokens)?);
+    }
+    Ok(if all_of.len() == 1 {
+        all_of.remove(0)
+    } else {
+        Expr::All(all_of)
+    })
+}
+
+/// Parses a comparison or a parenthesized expression.
+fn parse_operand(tokens: &mut Tokens) -> Result<Expr> {
+    let field = match tokens.next() {
+        Some(Token::Open) => {
+            let expr = parse_any(tokens)?;
+            return match tokens.next() {
+                Some(Token::Close) => Ok(expr),
+                Some(other) => bail!("expected `)`, found {other}"),
+                None => bail!("missing closing `)`"),
+            };
+        }
+        Some(Token::Ident(ident)) => parse_field(&ident)?,
+        Some(other) => bail!("expected field, found {other}"),
+        None => bail!("unexpected end of filter expression"),
+    };
+    let op = match tokens.next() {
+        Some(Token::Op(op)) => op,
+        Some(other) => bail!("expected comparison operator, found {other}"),
+        None => bail!("expected comparison operator after field"),
+    };
+    let value = match tokens.next() {
+        Some(Token::Value(value)) => value,
+        Some(Token::Ident(ident)) if ident == "true" => FilterValue::Bool(true),
+        Some(Token::Ident(ident)) if ident == "false" => FilterValue::Bool(false),
+        Some(other) => bail!("expected literal, found {other}"),
+        None => bail!("expected literal after comparison operator"),
+    };
+    Ok(Expr::Compare(Comparison { field, op, value }))
+}
+
+impl TryFrom<String> for InputFilter {
+    type Error = eyre::Report;
+
+    fn try_from(source: String) -> Result<Self> {
+        source
+            .parse()
+            .map_err(|err| eyre!("invalid input filter `{source}`: {err}"))
+    }
+}
+
+impl From<InputFilter> for String {
+    fn from(filter: InputFilter) -> Self {
+        filter.source
+    }
+}
+
+impl fmt::Display for InputFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn parse_field(ident: &str) -> Result<FilterField> {
+    let field = match ident.split_once('.') {
+        None if ident == "data" => FilterField::Data(None),
+        Some(("data", field)) => FilterField::Data(Some(field.to_owned())),
+        Some(("metadata", "watermark")) => FilterField::Watermark,
+        Some(("metadata", "deadline")) => FilterField::Deadline,
+        Some(("metadata", "len")) => FilterField::Len,
+        Some(("metadata", other)) => {
+            bail!("unknown metadata field `{other}` (expected `watermark`, `deadline`, or `len`)")
+        }
+        _ => bail!("unknown field `{ident}` (expected `data` or `metadata.<field>`)"),
+    };
+    Ok(field)
+}
+
+#[derive(Debug)]
+enum Token {
+    Ident(String),
+    Value(FilterValue),
+    Op(CompareOp),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{ident}`"),
+            Token::Value(value) => write!(f, "literal `{value:?}`"),
+            Token::Op(op) => write!(f, "operator `{op:?}`"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Open => write!(f, "`(`"),
+            Token::Close => write!(f, "`)`"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => value.push(c),
+                            None => bail!("unterminated string literal"),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => bail!("unterminated string literal"),
+                    }
+                }
+                Token::Value(FilterValue::String(value))
+            }
+            '&' | '|' => {
+                if chars.next_if(|&(_, next)| next == c).is_none() {
+                    bail!("expected `{c}{c}`");
+                }
+                if c == '&' {
+                    Token::And
+                } else {
+                    Token::Or
+                }
+            }
+            '=' | '!' | '<' | '>' => {
+                let followed_by_eq = chars.next_if(|&(_, next)| next == '=').is_some();
+                let op = match (c, followed_by_eq) {
+                    ('=', true) => CompareOp::Eq,
+                    ('!', true) => CompareOp::Ne,
+                    ('<', false) => CompareOp::Lt,
+                    ('<', true) => CompareOp::Le,
+                    ('>', false) => CompareOp::Gt,
+                    ('>', true) => CompareOp::Ge,
+                    _ => bail!("invalid operator `{c}`, expected one of == != < <= > >="),
+                };
+                Token::Op(op)
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, next)) = chars.next_if(|&(_, next)| {
+                    next.is_ascii_alphanumeric() || matches!(next, '.' | '-' | '+' | '_')
+                }) {
+                    end = i + next.len_utf8();
+                }
+                let literal = &source[start..end];
+                let number = literal
+                    .parse()
+                    .map_err(|_| eyre!("invalid number `{literal}`"))?;
+                Token::Value(FilterValue::Number(number))
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, next)) =
+                    chars.next_if(|&(_, next)| next.is_alphanumeric() || matches!(next, '_' | '.'))
+                {
+                    end = i + next.len_utf8();
+                }
+                Token::Ident(source[start..end].to_owned())
+            }
+            other => bail!("unexpected character `{other}`"),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn filter(source: &str) -> InputFilter {
+        source.parse().unwrap()
+    }
+
+    fn error(source: &str) -> String {
+        source.parse::<InputFilter>().unwrap_err().to_string()
+    }
+
+    /// Evaluates the filter against the given numeric `data.<field>` values.
+    fn matches(filter: &InputFilter, fields: &[(&str, f64)]) -> bool {
+        let fields: BTreeMap<_, _> = fields.iter().copied().collect();
+        filter.matches(|field| {
+            match field {
+                FilterField::Data(Some(name)) => fields.get(name.as_str()).copied(),
+                _ => None,
+            }
+            .map(FilterValue::Number)
+        })
+    }
+
+    fn matches_data(filter: &InputFilter, value: FilterValue) -> bool {
+        filter.matches(|field| (*field == FilterField::Data(None)).then(|| value.clone()))
+    }
+
+    #[test]
+    fn and_binds_stronger_than_or() {
+        let f = filter("data.a == 1 || data.b == 1 && data.c == 1");
+        assert!(matches(&f, &[("a", 1.0), ("b", 0.0), ("c", 0.0)]));
+        assert!(matches(&f, &[("a", 0.0), ("b", 1.0), ("c", 1.0)]));
+        assert!(!matches(&f, &[("a", 0.0), ("b", 1.0), ("c", 0.0)]));
+
+        let f = filter("data.a == 1 && data.b == 1 || data.c == 1");
+        assert!(matches(&f, &[("a", 0.0), ("b", 0.0), ("c", 1.0)]));
+        assert!(!matches(&f, &[("a", 1.0), ("b", 0.0), ("c", 0.0)]));
+    }
+
+    #[test]
+    fn parentheses() {
+        let f = filter("(data.a == 1 || data.b == 1) && data.c == 1");
+        assert!(!matches(&f, &[("a", 1.0), ("b", 0.0), ("c", 0.0)]));
+        assert!(matches(&f, &[("a", 0.0), ("b", 1.0), ("c", 1.0)]));
+
+        let f = filter("((data.a == 1)) && (data.b == 1 || (data.c == 1 && data.d == 1))");
+        assert!(matches(&f, &[("a", 1.0), ("c", 1.0), ("d", 1.0)]));
+        assert!(!matches(&f, &[("a", 1.0), ("c", 1.0)]));
+        assert!(!matches(&f, &[("b", 1.0), ("c", 1.0), ("d", 1.0)]));
+    }
+
+    #[test]
+    fn string_escapes() {
+        let f = filter(r#"data == "say \"hi\" \\ now""#);
+        let expected = FilterValue::String(r#"say "hi" \ now"#.to_owned());
+        assert!(matches_data(&f, expected));
+        assert!(!matches_data(&f, FilterValue::String("say hi".to_owned())));
+    }
+
+    #[test]
+    fn numbers() {
+        let f = filter("data > -1e-3");
+        assert!(matches_data(&f, FilterValue::Number(0.0)));
+        assert!(matches_data(&f, FilterValue::Number(-0.0005)));
+        assert!(!matches_data(&f, FilterValue::Number(-0.01)));
+
+        assert!(matches_data(
+            &filter("data == .5"),
+            FilterValue::Number(0.5)
+        ));
+        assert!(matches_data(
+            &filter("data<=2.5E+2"),
+            FilterValue::Number(250.0)
+        ));
+    }
+
+    #[test]
+    fn comparison_operators() {
+        let cases = [
+            ("data == 1", [false, true, false]),
+            ("data != 1", [true, false, true]),
+            ("data < 1", [true, false, false]),
+            ("data <= 1", [true, true, false]),
+            ("data > 1", [false, false, true]),
+            ("data >= 1", [false, true, true]),
+        ];
+        for (source, expected) in cases {
+            let f = filter(source);
+            for (value, expected) in [0.0, 1.0, 2.0].into_iter().zip(expected) {
+                assert_eq!(
+                    matches_data(&f, FilterValue::Number(value)),
+                    expected,
+                    "`{source}` with {value}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn missing_or_mismatched_values() {
+        let f = filter("data.a == 1");
+        assert!(!matches(&f, &[("b", 1.0)]));
+        assert!(!matches_data(
+            &filter("data == 1"),
+            FilterValue::String("1".to_owned())
+        ));
+        assert!(matches_data(
+            &filter("data == true"),
+            FilterValue::Bool(true)
+        ));
+        assert!(!matches_data(
+            &filter("data < true"),
+            FilterValue::Bool(false)
+        ));
+        assert!(!matches_data(
+            &filter("data != 1"),
+            FilterValue::Bool(false)
+        ));
+    }
+
+    #[test]
+    fn fields() {
+        let f = filter("metadata.watermark > 1 && metadata.deadline > 1 && metadata.len > 1");
+        let mut seen = Vec::new();
+        f.matches(|field| {
+            seen.push(field.clone());
+            Some(FilterValue::Number(2.0))
+        });
+        assert_eq!(
+            seen,
+            [
+                FilterField::Watermark,
+                FilterField::Deadline,
+                FilterField::Len
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_expressions() {
+        let cases = [
+            ("", "unexpected end of filter expression"),
+            ("data", "expected comparison operator after field"),
+            ("data ==", "expected literal after comparison operator"),
+            ("data = 1", "invalid operator `=`"),
+            ("data == 1 &", "expected `&&`"),
+            ("data == 1 | data == 2", "expected `||`"),
+            ("data == 1 data == 2", "expected `&&` or `||`, found `data`"),
+            ("data == 1 &&", "unexpected end of filter expression"),
+            ("data == data", "expected literal, found `data`"),
+            ("== 1", "expected field, found operator `Eq`"),
+            ("foo == 1", "unknown field `foo`"),
+            ("metadata.foo == 1", "unknown metadata field `foo`"),
+            ("data == 1-2", "invalid number `1-2`"),
+            (r#"data == "abc"#, "unterminated string literal"),
+            ("data == #", "unexpected character `#`"),
+            ("(data == 1", "missing closing `)`"),
+            ("(data == 1 data", "expected `)`, found `data`"),
+            ("data == 1)", "expected `&&` or `||`, found `)`"),
+            ("()", "expected field, found `)`"),
+        ];
+        for (source, expected) in cases {
+            let err = error(source);
+            assert!(err.contains(expected), "`{source}`: {err}");
+        }
+    }
+
+    #[test]
+    fn keeps_source() {
+        let source = "data.a == 1 || (data.b != \"x\")";
+        let f: InputFilter = source.to_owned().try_into().unwrap();
+        assert_eq!(f.to_string(), source);
+        assert_eq!(String::from(f), source);
+
+        let err = InputFilter::try_from("data ==".to_owned()).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("invalid input filter `data ==`"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use super::{CoreNodeKind, Descriptor, ResolvedNode};
+use crate::config::{DataId, Input, NodeId, OperatorId};
+use eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The dataflow as seen by one of its nodes or operators.
+///
+/// Lets generic nodes, e.g. recorders or visualizers, configure themselves
+/// from the dataflow instead of repeating parts of the YAML file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataflowGraph {
+    pub node_id: NodeId,
+    /// Set if the graph was created for an operator of a runtime node.
+    pub operator_id: Option<OperatorId>,
+    /// Declared inputs of the node or operator.
+    ///
+    /// The inputs of runtime nodes are prefixed with the operator ID.
+    pub inputs: BTreeMap<DataId, Input>,
+    /// Declared outputs of the node or operator.
+    pub outputs: BTreeSet<DataId>,
+    /// All nodes of the dataflow, with aliases resolved and defaults applied.
+    pub nodes: Vec<ResolvedNode>,
+}
+
+impl DataflowGraph {
+    pub fn for_node(descriptor: &Descriptor, node_id: &NodeId) -> eyre::Result<Self> {
+        let nodes = descriptor
+            .resolve_aliases_and_set_defaults()
+            .context("failed to resolve dataflow nodes")?;
+        let node = find_node(&nodes, node_id)?;
+        let run_config = node.kind.run_config();
+        Ok(Self {
+            node_id: node_id.clone(),
+            operator_id: None,
+            inputs: run_config.inputs,
+            outputs: run_config.outputs,
+            nodes,
+        })
+    }
+
+    pub fn for_operator(
+        descriptor: &Descriptor,
+        node_id: &NodeId,
+        operator_id: &OperatorId,
+    ) -> eyre::Result<Self> {
+        let nodes = descriptor
+            .resolve_aliases_and_set_defaults()
+            .context("failed to resolve dataflow nodes")?;
+        let CoreNodeKind::Runtime(runtime) = &find_node(&nodes, node_id)?.kind else {
+            eyre::bail!("node `{node_id}` has no operators");
+        };
+        let operator = runtime
+            .operators
+            .iter()
+            .find(|op| &op.id == operator_id)
+            .ok_or_else(|| eyre!("no operator `{operator_id}` in node `{node_id}`"))?;
+        Ok(Self {
+            node_id: node_id.clone(),
+            operator_id: Some(operator_id.clone()),
+            inputs: operator.config.inputs.clone(),
+            outputs: operator.config.outputs.clone(),
+            nodes,
+        })
+    }
+}
+
+fn find_node<'a>(nodes: &'a [ResolvedNode], node_id: &NodeId) -> eyre::Result<&'a ResolvedNode> {
+    nodes
+        .iter()
+        .find(|n| &n.id == node_id)
+        .ok_or_else(|| eyre!("no node `{node_id}` in dataflow"))
+}
@@ -2,6 +2,8 @@ use crate::config::{
     CommunicationConfig, DataId, Input, InputMapping, NodeId, NodeRunConfig, OperatorId,
 };
 use eyre::{bail, eyre, Context, OptionExt, Result};
+pub use filter::{FilterField, FilterValue, InputFilter};
+pub use graph::DataflowGraph;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_with_expand_env::with_expand_envs;
@@ -9,10 +11,14 @@ use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     env::consts::EXE_EXTENSION,
     fmt,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use tracing::warn;
 pub use visualize::collect_dora_timers;
+mod filter;
+mod graph;
 mod validate;
 mod visualize;
 pub const SHELL_SOURCE: &str = "shell";
@@ -29,6 +35,26 @@ pub struct Descriptor {
     #[schemars(skip)]
     #[serde(default, rename = "_unstable_deploy")]
     pub deploy: Deploy,
+    #[schemars(skip)]
+    #[serde(default, rename = "_unstable_record")]
+    pub record: RecordConfig,
+    #[schemars(skip)]
+    #[serde(default, rename = "_unstable_replay")]
+    pub replay: ReplayConfig,
+    #[schemars(skip)]
+    #[serde(default, rename = "_unstable_resource_limits")]
+    pub resource_limits: ResourceLimits,
+    /// Outputs in `node/output` form whose last value is retained.
+    ///
+    /// Nodes that subscribe after a value was sent on a latched output receive
+    /// the retained value right away instead of waiting for the next one. This
+    /// is useful for data that is published rarely, e.g. maps or calibrations.
+    #[schemars(skip)]
+    #[serde(default, rename = "_unstable_latched_outputs")]
+    pub latched_outputs: BTreeSet<String>,
+    #[schemars(skip)]
+    #[serde(default, rename = "_unstable_logging")]
+    pub logging: LoggingConfig,
     pub nodes: Vec<Node>,
 }
 
@@ -64,7 +90,8 @@ impl Descriptor {
             };
             for mapping in input_mappings
                 .into_iter()
-                .filter_map(|i| match &mut i.mapping {
+                .flat_map(|i| std::iter::once(&mut i.mapping).chain(&mut i.additional_sources))
+                .filter_map(|mapping| match mapping {
                     InputMapping::Timer { .. } => None,
                     InputMapping::User(m) => Some(m),
                 })
@@ -103,6 +130,10 @@ impl Descriptor {
                 description: node.description,
                 env: node.env,
                 deploy: ResolvedDeploy::new(node.deploy, self),
+                container: node.container,
+                operator_workers: node.operator_workers,
+                profile: node.profile,
+                heartbeat: node.heartbeat,
                 kind,
             });
         }
@@ -152,12 +183,153 @@ impl Descriptor {
         )
         .wrap_err("Dataflow could not be validated.")
     }
+
+    pub fn is_latched(&self, node_id: &NodeId, output_id: &DataId) -> bool {
+        self.latched_outputs
+            .contains(&format!("{node_id}/{output_id}"))
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Deploy {
     pub machine: Option<String>,
+    /// Rust target triple of the machine, e.g. `aarch64-unknown-linux-gnu`.
+    ///
+    /// `dora build` cross-compiles the nodes of the machine for this target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// Configures the daemon-side recording of node outputs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RecordConfig {
+    /// Whether the outputs of this dataflow should be recorded.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Outputs to record, in `node/output` form. All outputs are recorded if empty.
+    #[serde(default)]
+    pub outputs: BTreeSet<String>,
+    /// Maximum size of a single record segment file in bytes.
+    pub segment_size: Option<u64>,
+}
+
+/// Replaces nodes by replaying their outputs from an earlier recording.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayConfig {
+    /// Path to the `record` directory of a previous run.
+    pub path: Option<PathBuf>,
+    /// Nodes whose recorded outputs are replayed instead of spawning them.
+    #[serde(default)]
+    pub nodes: BTreeSet<NodeId>,
+    /// Playback speed relative to the original timing, e.g. `2.0` to replay twice as fast.
+    pub speed: Option<f64>,
+}
+
+impl ReplayConfig {
+    pub fn replays(&self, node_id: &NodeId) -> bool {
+        self.path.is_some() && self.nodes.contains(node_id)
+    }
+}
+
+/// Exports telemetry of the dataflow to an OpenTelemetry collector.
+///
+/// If an `otlp_endpoint` is set, each daemon exports the throughput, input
+/// latency, and queue depth of its local nodes as OTLP metrics.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// OTLP gRPC endpoint of the collector, e.g. `http://localhost:4317`.
+    pub otlp_endpoint: Option<String>,
+    /// Whether node metrics are exported to the `otlp_endpoint`.
+    #[serde(default = "LoggingConfig::default_metrics")]
+    pub metrics: bool,
+    /// Interval between two metric exports in milliseconds.
+    #[serde(default = "LoggingConfig::default_metrics_interval_ms")]
+    pub metrics_interval_ms: u64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            metrics: Self::default_metrics(),
+            metrics_interval_ms: Self::default_metrics_interval_ms(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    fn default_metrics() -> bool {
+        true
+    }
+
+    fn default_metrics_interval_ms() -> u64 {
+        10_000
+    }
+
+    /// The endpoint that metrics are exported to, if enabled.
+    pub fn metrics_endpoint(&self) -> Option<&str> {
+        self.otlp_endpoint.as_deref().filter(|_| self.metrics)
+    }
+
+    pub fn metrics_interval(&self) -> Duration {
+        Duration::from_millis(self.metrics_interval_ms)
+    }
+}
+
+/// Thresholds that protect the machine running the daemon.
+///
+/// The daemon checks the free disk space of the working directory and its own
+/// memory usage periodically. While a threshold is exceeded, the configured
+/// actions are applied.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceLimits {
+    /// Minimum free disk space in bytes.
+    pub min_free_disk: Option<u64>,
+    /// Maximum memory usage of the daemon process in bytes.
+    pub max_daemon_memory: Option<u64>,
+    #[serde(default)]
+    pub actions: BTreeSet<ResourceLimitAction>,
+    /// Outputs in `node/output` form that may be dropped under resource pressure.
+    #[serde(default)]
+    pub best_effort_outputs: BTreeSet<String>,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceLimitAction {
+    /// Drop messages of the `best_effort_outputs`.
+    DropBestEffort,
+    /// Stop writing recorded messages to disk.
+    PauseRecording,
+    /// Report an error log message to the coordinator.
+    Alert,
+}
+
+impl ResourceLimits {
+    pub fn is_set(&self) -> bool {
+        self.min_free_disk.is_some() || self.max_daemon_memory.is_some()
+    }
+
+    pub fn is_best_effort(&self, node_id: &NodeId, output_id: &DataId) -> bool {
+        self.actions.contains(&ResourceLimitAction::DropBestEffort)
+            && self
+                .best_effort_outputs
+                .contains(&format!("{node_id}/{output_id}"))
+    }
+}
+
+impl RecordConfig {
+    pub fn records(&self, node_id: &NodeId, output_id: &DataId) -> bool {
+        self.enabled
+            && (self.outputs.is_empty() || self.outputs.contains(&format!("{node_id}/{output_id}")))
+    }
 }
 
 /// Dora Node
@@ -178,6 +350,43 @@ pub struct Node {
     #[serde(default, rename = "_unstable_deploy")]
     pub deploy: Deploy,
 
+    /// Unstable container configuration
+    #[schemars(skip)]
+    #[serde(
+        default,
+        rename = "_unstable_container",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub container: Option<ContainerConfig>,
+
+    /// Unstable limit on the number of operators of a runtime node that
+    /// process events concurrently
+    #[schemars(skip)]
+    #[serde(
+        default,
+        rename = "_unstable_operator_workers",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub operator_workers: Option<NonZeroUsize>,
+
+    /// Unstable profiling of the operator callbacks of a runtime node
+    #[schemars(skip)]
+    #[serde(
+        default,
+        rename = "_unstable_profile",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub profile: Option<RuntimeProfile>,
+
+    /// Unstable detection of unresponsive nodes through heartbeats
+    #[schemars(skip)]
+    #[serde(
+        default,
+        rename = "_unstable_heartbeat",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub heartbeat: Option<NodeHeartbeat>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     operators: Option<RuntimeNode>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -281,6 +490,18 @@ pub struct ResolvedNode {
     #[serde(default)]
     pub deploy: ResolvedDeploy,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<ContainerConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_workers: Option<NonZeroUsize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<RuntimeProfile>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<NodeHeartbeat>,
+
     #[serde(flatten)]
     pub kind: CoreNodeKind,
 }
@@ -312,9 +533,64 @@ impl ResolvedNode {
     }
 }
 
+/// Records the duration of the operator callbacks of a runtime node.
+///
+/// The runtime writes the results to `out/<dataflow_id>/profile_<node_id>.json`
+/// when it exits.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeProfile {
+    /// Sampling profiler to attach to the runtime process, for flamegraphs of
+    /// the operator code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampler: Option<ProfileSampler>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProfileSampler {
+    /// Linux `perf record`, which samples native code.
+    Perf,
+    /// `py-spy record`, which samples Python code.
+    PySpy,
+}
+
+/// Runs a custom node inside a container instead of directly on the host.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ContainerConfig {
+    /// Container image to run the node in.
+    pub image: String,
+    /// Container engine to use, defaults to `docker`.
+    #[serde(default)]
+    pub engine: ContainerEngine,
+    /// Additional arguments passed to the `run` command of the container engine.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerEngine {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerEngine {
+    pub fn command(&self) -> &'static str {
+        match self {
+            ContainerEngine::Docker => "docker",
+            ContainerEngine::Podman => "podman",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ResolvedDeploy {
     pub machine: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
 }
 impl ResolvedDeploy {
     fn new(deploy: Deploy, descriptor: &Descriptor) -> Self {
@@ -323,7 +599,8 @@ impl ResolvedDeploy {
             Some(m) => m,
             None => default_machine.to_owned(),
         };
-        Self { machine }
+        let target = deploy.target.or_else(|| descriptor.deploy.target.clone());
+        Self { machine, target }
     }
 }
 
@@ -420,6 +697,309 @@ pub struct OperatorConfig {
     pub build: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub send_stdout_as: Option<String>,
+
+    /// Configuration that is passed to the operator when it's created.
+    ///
+    /// Python operators receive the entries as keyword arguments of their
+    /// `Operator` constructor. Shared library operators receive them as a
+    /// JSON object through the optional `dora_init_operator_with_config`
+    /// function.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub config: BTreeMap<String, serde_json::Value>,
+
+    /// Run the operator in a separate process, so that a crash doesn't affect
+    /// the other operators of the node.
+    ///
+    /// Supported for shared library and Python operators. Isolated Python
+    /// operators have their own interpreter, so they don't compete with the
+    /// other Python operators of the node for the GIL.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub isolate: bool,
+
+    /// What to do when the operator returns an error or panics.
+    #[serde(default, skip_serializing_if = "OperatorErrorPolicy::is_default")]
+    pub on_error: OperatorErrorPolicy,
+
+    /// Output on which structured errors of the operator are published as
+    /// JSON strings, e.g. for monitoring nodes.
+    ///
+    /// Must be one of the `outputs` of the operator.
+    #[serde(
+        default,
+        rename = "_unstable_error_output",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(skip)]
+    pub error_output: Option<DataId>,
+
+    #[serde(
+        default,
+        rename = "_unstable_watchdog",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(skip)]
+    pub watchdog: Option<OperatorWatchdog>,
+
+    /// Passed to the `on_configure` hook of the operator.
+    #[serde(
+        default,
+        rename = "_unstable_parameters",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    #[schemars(skip)]
+    pub parameters: BTreeMap<String, serde_json::Value>,
+
+    /// Rate limits and deduplication for individual outputs.
+    #[serde(
+        default,
+        rename = "_unstable_output_limits",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    #[schemars(skip)]
+    pub output_limits: BTreeMap<DataId, OutputLimits>,
+
+    /// Filter expressions for inputs, see [`InputFilter`].
+    ///
+    /// Inputs that don't match their filter are dropped by the runtime before
+    /// they reach the operator.
+    #[serde(
+        default,
+        rename = "_unstable_input_filters",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    #[schemars(skip)]
+    pub input_filters: BTreeMap<DataId, InputFilter>,
+
+    /// Timers that invoke the `on_timer` callback of the operator at a fixed
+    /// rate, independently of its inputs.
+    #[serde(
+        default,
+        rename = "_unstable_timers",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    #[schemars(skip)]
+    pub timers: BTreeMap<String, OperatorTimer>,
+
+    /// Deliver all pending inputs in a single `on_inputs` call instead of
+    /// calling `on_event` for each of them.
+    ///
+    /// Only supported for Python operators.
+    #[serde(
+        default,
+        rename = "_unstable_batch_inputs",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    #[schemars(skip)]
+    pub batch_inputs: bool,
+
+    /// Maximum time in milliseconds that the operator may take to process its
+    /// queued inputs after the dataflow is stopped.
+    ///
+    /// Inputs that are still queued afterwards are dropped, so that the
+    /// operator receives the stop event. Without a timeout, all queued inputs
+    /// are delivered before the stop event.
+    #[serde(
+        default,
+        rename = "_unstable_drain_timeout_ms",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(skip)]
+    pub drain_timeout_ms: Option<u64>,
+
+    /// Scheduling class, priority, and CPU affinity of the operator thread.
+    #[serde(
+        default,
+        rename = "_unstable_scheduling",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(skip)]
+    pub scheduling: Option<OperatorScheduling>,
+}
+
+impl OperatorConfig {
+    pub fn drain_timeout(&self) -> Option<Duration> {
+        self.drain_timeout_ms.map(Duration::from_millis)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorTimer {
+    /// Interval between two `on_timer` calls in milliseconds.
+    ///
+    /// Ticks are skipped if the operator is busy when they are due.
+    pub interval_ms: u64,
+}
+
+impl OperatorTimer {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+}
+
+/// Scheduling settings for the thread that runs an operator, e.g. to give a
+/// control loop deterministic latency.
+///
+/// The runtime applies the settings on a best-effort basis: if they can't be
+/// applied, e.g. because real-time scheduling requires privileges, it logs a
+/// warning and runs the operator with the default settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorScheduling {
+    #[serde(default)]
+    pub policy: SchedulingPolicy,
+    /// Priority of the thread.
+    ///
+    /// For the real-time policies, this is the static priority between 1
+    /// (lowest) and 99 (highest), with a default of 1. For the `other` policy,
+    /// it's the nice value between -20 (highest) and 19 (lowest).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Indices of the CPU cores that the thread may run on. All cores are
+    /// allowed if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cpu_affinity: Vec<usize>,
+}
+
+/// Scheduling policy of an operator thread.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulingPolicy {
+    /// The default time-sharing scheduling of the operating system.
+    #[default]
+    Other,
+    /// Real-time scheduling: the thread runs until it blocks or a thread with a
+    /// higher priority becomes ready.
+    Fifo,
+    /// Like `fifo`, but threads of the same priority share the CPU in time
+    /// slices.
+    RoundRobin,
+}
+
+impl SchedulingPolicy {
+    pub fn is_real_time(&self) -> bool {
+        matches!(self, Self::Fifo | Self::RoundRobin)
+    }
+}
+
+/// Limits on how often the runtime forwards an operator output.
+///
+/// Outputs that exceed a limit are dropped silently.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputLimits {
+    /// Maximum number of messages per second.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rate: Option<f64>,
+    /// Drop messages that are identical to the previously sent message.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip_if_unchanged: bool,
+}
+
+impl OutputLimits {
+    /// Minimum time between two messages, based on `max_rate`.
+    ///
+    /// Returns an error if `max_rate` is not positive or so small that the
+    /// interval is not representable.
+    pub fn min_interval(&self) -> eyre::Result<Option<Duration>> {
+        let Some(rate) = self.max_rate else {
+            return Ok(None);
+        };
+        if !(rate > 0.0 && rate.is_finite()) {
+            bail!("invalid `max_rate` {rate}, must be positive");
+        }
+        Duration::try_from_secs_f64(1.0 / rate)
+            .map(Some)
+            .map_err(|_| eyre!("invalid `max_rate` {rate}, must not be that small"))
+    }
+}
+
+/// How the runtime handles a failed operator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperatorErrorPolicy {
+    /// Stop the runtime node with an error, which fails the dataflow.
+    #[default]
+    FailDataflow,
+    /// Log the error and stop only the failed operator. The other operators of
+    /// the node keep running.
+    Ignore,
+    /// Log the error and start a new instance of the operator, which receives
+    /// the subsequent inputs.
+    Restart,
+}
+
+impl OperatorErrorPolicy {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Detects event callbacks of an operator that take too long.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorWatchdog {
+    /// Maximum duration of a single event callback, in milliseconds.
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub action: WatchdogAction,
+}
+
+impl OperatorWatchdog {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// What happens when an operator callback exceeds its timeout or when a node
+/// stops sending heartbeats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchdogAction {
+    /// Log an error and keep waiting.
+    #[default]
+    Report,
+    /// Kill the node process.
+    ///
+    /// For operators, this reports an operator error that is handled according
+    /// to the `on_error` policy of the operator. Hung operators are stopped
+    /// instead of restarted, except for WASM operators, whose callbacks are
+    /// interrupted.
+    Kill,
+}
+
+/// Detects nodes that stopped responding.
+///
+/// The node API sends a heartbeat to the daemon every `interval_ms`, together
+/// with the health that the node reported last. The daemon considers the node
+/// unresponsive if it receives no heartbeat for `timeout_ms`, or if queued
+/// inputs are not received by the node within `timeout_ms`. The latter also
+/// detects hung event loops of nodes that publish rarely.
+///
+/// The daemon checks the nodes every few seconds, starting with their first
+/// heartbeat.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeHeartbeat {
+    #[serde(default = "NodeHeartbeat::default_interval_ms")]
+    pub interval_ms: u64,
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub action: WatchdogAction,
+}
+
+impl NodeHeartbeat {
+    fn default_interval_ms() -> u64 {
+        1000
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
@@ -427,8 +1007,48 @@ pub struct OperatorConfig {
 pub enum OperatorSource {
     SharedLibrary(String),
     Python(PythonSource),
-    #[schemars(skip)]
     Wasm(String),
+    /// Path to a Julia source file, see the `julia` module of `dora-runtime`.
+    Julia(String),
+    /// Path to a MATLAB function file that is run in a MATLAB engine session,
+    /// see the `matlab` module of `dora-runtime`.
+    Matlab(String),
+    /// Inline Lua code or the path to a `.lua` file, see the `lua` module of
+    /// `dora-runtime`.
+    Lua(String),
+    /// Built-in operator that runs an ONNX model, see the `onnx` module of
+    /// `dora-runtime`.
+    Onnx(OnnxSource),
+}
+
+/// An ONNX model and the mapping between its tensors and the operator's
+/// inputs and outputs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OnnxSource {
+    /// Path to the `.onnx` model file.
+    pub model: String,
+    /// Model inputs, by tensor name.
+    #[serde(default)]
+    pub inputs: BTreeMap<String, OnnxInput>,
+    /// Operator output that each model output is sent as, by tensor name.
+    ///
+    /// Model outputs that are not listed here are discarded.
+    #[serde(default)]
+    pub outputs: BTreeMap<String, DataId>,
+}
+
+/// Operator input that feeds a model input tensor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OnnxInput {
+    /// ID of the operator input.
+    pub input: DataId,
+    /// Shape of the tensor. The flat input array is reshaped to it, so the
+    /// number of elements must match. A one-dimensional tensor is assumed if
+    /// empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shape: Vec<usize>,
 }
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(
@@ -439,6 +1059,18 @@ pub enum OperatorSource {
 pub struct PythonSource {
     pub source: String,
     pub conda_env: Option<String>,
+    /// Path to a virtual environment that the operator is run in.
+    pub venv: Option<String>,
+    /// Path to a `requirements.txt` file that is installed into a separate
+    /// virtual environment for the operator.
+    ///
+    /// The environment is created by the daemon when the dataflow is started
+    /// and reused as long as the requirements don't change. It has access to
+    /// the packages of the system environment, e.g. to `dora-rs`.
+    pub requirements: Option<String>,
+    /// Reload the operator when its source file changes.
+    #[serde(default)]
+    pub hot_reload: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
@@ -448,6 +1080,12 @@ pub enum PythonSourceDef {
     WithOptions {
         source: String,
         conda_env: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        venv: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        requirements: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        hot_reload: bool,
     },
 }
 
@@ -457,8 +1095,23 @@ impl From<PythonSource> for PythonSourceDef {
             PythonSource {
                 source,
                 conda_env: None,
+                venv: None,
+                requirements: None,
+                hot_reload: false,
             } => Self::SourceOnly(source),
-            PythonSource { source, conda_env } => Self::WithOptions { source, conda_env },
+            PythonSource {
+                source,
+                conda_env,
+                venv,
+                requirements,
+                hot_reload,
+            } => Self::WithOptions {
+                source,
+                conda_env,
+                venv,
+                requirements,
+                hot_reload,
+            },
         }
     }
 }
@@ -469,8 +1122,23 @@ impl From<PythonSourceDef> for PythonSource {
             PythonSourceDef::SourceOnly(source) => Self {
                 source,
                 conda_env: None,
+                venv: None,
+                requirements: None,
+                hot_reload: false,
+            },
+            PythonSourceDef::WithOptions {
+                source,
+                conda_env,
+                venv,
+                requirements,
+                hot_reload,
+            } => Self {
+                source,
+                conda_env,
+                venv,
+                requirements,
+                hot_reload,
             },
-            PythonSourceDef::WithOptions { source, conda_env } => Self { source, conda_env },
         }
     }
 }
@@ -479,6 +1147,13 @@ pub fn source_is_url(source: &str) -> bool {
     source.contains("://")
 }
 
+/// Returns the path of the Lua script if the given `lua` operator source refers
+/// to a file instead of containing the code inline.
+pub fn lua_source_path(source: &str) -> Option<&str> {
+    let source = source.trim();
+    (!source.contains('\n') && source.ends_with(".lua")).then_some(source)
+}
+
 pub fn resolve_path(source: &str, working_dir: &Path) -> Result<PathBuf> {
     let path = Path::new(&source);
     let path = if path.extension().is_none() {
@@ -558,3 +1233,27 @@ impl fmt::Display for EnvValue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_rate: f64) -> OutputLimits {
+        OutputLimits {
+            max_rate: Some(max_rate),
+            skip_if_unchanged: false,
+        }
+    }
+
+    #[test]
+    fn min_interval() {
+        assert_eq!(OutputLimits::default().min_interval().unwrap(), None);
+        assert_eq!(
+            limits(4.0).min_interval().unwrap(),
+            Some(Duration::from_millis(250))
+        );
+        for invalid in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e-300] {
+            assert!(limits(invalid).min_interval().is_err(), "{invalid}");
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use crate::{
     adjust_shared_library_path,
     config::{DataId, Input, InputMapping, OperatorId, UserInputMapping},
-    descriptor::{self, source_is_url, CoreNodeKind, OperatorSource, EXE_EXTENSION},
+    descriptor::{
+        self, lua_source_path, source_is_url, CoreNodeKind, OnnxSource, OperatorSource,
+        PythonSource, EXE_EXTENSION,
+    },
     get_python_path,
 };
 
@@ -19,15 +22,27 @@ pub fn check_dataflow(
     coordinator_is_remote: bool,
 ) -> eyre::Result<()> {
     let nodes = dataflow.resolve_aliases_and_set_defaults()?;
-    let mut has_python_operator = false;
+    let mut uses_python = false;
 
     // check that nodes and operators exist
     for node in &nodes {
+        if node.profile.is_some() && !matches!(node.kind, descriptor::CoreNodeKind::Runtime(_)) {
+            bail!(
+                "node `{}`: profiling is only supported for runtime nodes",
+                node.id
+            );
+        }
         match &node.kind {
             descriptor::CoreNodeKind::Custom(custom) => match custom.source.as_str() {
                 SHELL_SOURCE => (),
                 DYNAMIC_SOURCE => (),
+                _ if node.container.is_some() => {
+                    info!("skipping path check for containerized node `{}`", node.id);
+                }
                 source => {
+                    if Path::new(source).extension().is_some_and(|ext| ext == "py") {
+                        uses_python = true;
+                    }
                     if source_is_url(source) {
                         info!("{source} is a URL."); // TODO: Implement url check.
                     } else if let Some(remote_daemon_id) = remote_daemon_id {
@@ -58,8 +73,19 @@ pub fn check_dataflow(
                     };
                 }
             },
-            descriptor::CoreNodeKind::Runtime(node) => {
-                for operator_definition in &node.operators {
+            descriptor::CoreNodeKind::Runtime(runtime) => {
+                if node.container.is_some() {
+                    bail!(
+                        "node `{}`: containers are only supported for custom nodes",
+                        node.id
+                    );
+                }
+                for operator_definition in &runtime.operators {
+                    check_output_limits(operator_definition)?;
+                    check_error_output(operator_definition)?;
+                    check_timers(operator_definition)?;
+                    check_input_filters(operator_definition)?;
+                    check_scheduling(operator_definition)?;
                     match &operator_definition.config.source {
                         OperatorSource::SharedLibrary(path) => {
                             if source_is_url(path) {
@@ -72,13 +98,14 @@ pub fn check_dataflow(
                             }
                         }
                         OperatorSource::Python(python_source) => {
-                            has_python_operator = true;
+                            uses_python = true;
                             let path = &python_source.source;
                             if source_is_url(path) {
                                 info!("{path} is a URL."); // TODO: Implement url check.
                             } else if !working_dir.join(path).exists() {
                                 bail!("no Python library at `{path}`");
                             }
+                            check_python_env(python_source, working_dir)?;
                         }
                         OperatorSource::Wasm(path) => {
                             if source_is_url(path) {
@@ -87,6 +114,30 @@ pub fn check_dataflow(
                                 bail!("no WASM library at `{path}`");
                             }
                         }
+                        OperatorSource::Julia(path) => {
+                            if source_is_url(path) {
+                                info!("{path} is a URL."); // TODO: Implement url check.
+                            } else if !working_dir.join(path).exists() {
+                                bail!("no Julia file at `{path}`");
+                            }
+                        }
+                        OperatorSource::Matlab(path) => {
+                            if source_is_url(path) {
+                                info!("{path} is a URL."); // TODO: Implement url check.
+                            } else if !working_dir.join(path).exists() {
+                                bail!("no MATLAB file at `{path}`");
+                            }
+                        }
+                        OperatorSource::Lua(source) => {
+                            if let Some(path) = lua_source_path(source) {
+                                if !working_dir.join(path).exists() {
+                                    bail!("no Lua file at `{path}`");
+                                }
+                            }
+                        }
+                        OperatorSource::Onnx(onnx) => {
+                            check_onnx(operator_definition, onnx, working_dir)?;
+                        }
                     }
                 }
             }
@@ -109,6 +160,16 @@ pub fn check_dataflow(
                             &nodes,
                             &format!("{}/{}/{input_id}", operator_definition.id, node.id),
                         )?;
+                        if !input.additional_sources.is_empty()
+                            && input.sources().any(|m| m.source() == &node.id)
+                        {
+                            bail!(
+                                "input `{}/{}/{input_id}` has multiple sources, which is not \
+                                supported for operators of the same runtime node",
+                                operator_definition.id,
+                                node.id
+                            );
+                        }
                     }
                 }
             }
@@ -121,19 +182,142 @@ pub fn check_dataflow(
             .context("Could not resolve `send_stdout_as` configuration")?;
     }
 
-    if has_python_operator {
+    if uses_python {
         check_python_runtime()?;
     }
 
     Ok(())
 }
 
+fn check_timers(operator: &descriptor::OperatorDefinition) -> eyre::Result<()> {
+    for (timer_id, timer) in &operator.config.timers {
+        if timer.interval_ms == 0 {
+            bail!(
+                "invalid interval for timer `{}/{timer_id}`, must be at least 1ms",
+                operator.id
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_input_filters(operator: &descriptor::OperatorDefinition) -> eyre::Result<()> {
+    for input_id in operator.config.input_filters.keys() {
+        if !operator.config.inputs.contains_key(input_id) {
+            bail!(
+                "operator `{}` has a filter for unknown input `{input_id}`",
+                operator.id
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_onnx(
+    operator: &descriptor::OperatorDefinition,
+    onnx: &OnnxSource,
+    working_dir: &Path,
+) -> eyre::Result<()> {
+    if !working_dir.join(&onnx.model).exists() {
+        bail!("no ONNX model at `{}`", onnx.model);
+    }
+    if onnx.inputs.is_empty() {
+        bail!("ONNX operator `{}` has no model inputs", operator.id);
+    }
+    for (tensor, mapping) in &onnx.inputs {
+        if !operator.config.inputs.contains_key(&mapping.input) {
+            bail!(
+                "model input `{tensor}` of operator `{}` refers to unknown input `{}`",
+                operator.id,
+                mapping.input
+            );
+        }
+    }
+    for (tensor, output_id) in &onnx.outputs {
+        if !operator.config.outputs.contains(output_id) {
+            bail!(
+                "model output `{tensor}` of operator `{}` refers to unknown output `{output_id}`",
+                operator.id
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_scheduling(operator: &descriptor::OperatorDefinition) -> eyre::Result<()> {
+    let Some(scheduling) = &operator.config.scheduling else {
+        return Ok(());
+    };
+    if let Some(priority) = scheduling.priority {
+        let (range, policy) = if scheduling.policy.is_real_time() {
+            (1..=99, "real-time policies")
+        } else {
+            (-20..=19, "the `other` policy")
+        };
+        if !range.contains(&priority) {
+            bail!(
+                "invalid priority {priority} for operator `{}`, must be between {} and {} \
+                for {policy}",
+                operator.id,
+                range.start(),
+                range.end(),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_output_limits(operator: &descriptor::OperatorDefinition) -> eyre::Result<()> {
+    for (output_id, limits) in &operator.config.output_limits {
+        if !operator.config.outputs.contains(output_id) {
+            bail!(
+                "operator `{}` has output limits for unknown output `{output_id}`",
+                operator.id
+            );
+        }
+        limits
+            .min_interval()
+            .wrap_err_with(|| format!("invalid limits for output `{}/{output_id}`", operator.id))?;
+    }
+    Ok(())
+}
+
+fn check_error_output(operator: &descriptor::OperatorDefinition) -> eyre::Result<()> {
+    if let Some(output_id) = &operator.config.error_output {
+        if !operator.config.outputs.contains(output_id) {
+            bail!(
+                "`_unstable_error_output` of operator `{}` is not one of its outputs: `{output_id}`",
+                operator.id
+            );
+        }
+    }
+    Ok(())
+}
+
 fn check_input(
     input: &Input,
     nodes: &[super::ResolvedNode],
     input_id_str: &str,
 ) -> Result<(), eyre::ErrReport> {
-    match &input.mapping {
+    if input
+        .additional_sources
+        .iter()
+        .any(|m| matches!(m, InputMapping::Timer { .. }))
+    {
+        bail!("input `{input_id_str}` must not combine timers with other sources");
+    }
+    for mapping in input.sources() {
+        check_input_mapping(mapping, nodes, input_id_str)?;
+    }
+    Ok(())
+}
+
+fn check_input_mapping(
+    mapping: &InputMapping,
+    nodes: &[super::ResolvedNode],
+    input_id_str: &str,
+) -> Result<(), eyre::ErrReport> {
+    match mapping {
         InputMapping::Timer { interval: _ } => {}
         InputMapping::User(UserInputMapping { source, output }) => {
             let source_node = nodes.iter().find(|n| &n.id == source).ok_or_else(|| {
@@ -204,3 +388,23 @@ assert dora.__version__=='{VERSION}',  'Python dora-rs should be {VERSION}, but
 
     Ok(())
 }
+
+fn check_python_env(python_source: &PythonSource, working_dir: &Path) -> eyre::Result<()> {
+    let envs = [
+        python_source.conda_env.is_some(),
+        python_source.venv.is_some(),
+        python_source.requirements.is_some(),
+    ];
+    if envs.into_iter().filter(|set| *set).count() > 1 {
+        bail!(
+            "Python operator `{}` may only set one of `conda_env`, `venv`, and `requirements`",
+            python_source.source
+        );
+    }
+    if let Some(requirements) = &python_source.requirements {
+        if !working_dir.join(requirements).exists() {
+            bail!("no requirements file at `{requirements}`");
+        }
+    }
+    Ok(())
+}
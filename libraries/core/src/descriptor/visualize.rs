@@ -132,12 +132,9 @@ fn visualize_node_inputs(
 ) {
     let node_id = &node.id;
     match &node.kind {
-        CoreNodeKind::Custom(node) => visualize_inputs(
-            &node_id.to_string(),
-            &node.run_config.inputs,
-            flowchart,
-            nodes,
-        ),
+        CoreNodeKind::Custom(node) => {
+            visualize_inputs(node_id.as_ref(), &node.run_config.inputs, flowchart, nodes)
+        }
         CoreNodeKind::Runtime(RuntimeNode { operators, .. }) => {
             for operator in operators {
                 visualize_inputs(
@@ -158,12 +155,14 @@ fn visualize_inputs(
     nodes: &HashMap<&NodeId, &ResolvedNode>,
 ) {
     for (input_id, input) in inputs {
-        match &input.mapping {
-            mapping @ InputMapping::Timer { .. } => {
-                writeln!(flowchart, "  {} -- {input_id} --> {target}", mapping).unwrap();
-            }
-            InputMapping::User(mapping) => {
-                visualize_user_mapping(mapping, target, nodes, input_id, flowchart)
+        for mapping in input.sources() {
+            match mapping {
+                InputMapping::Timer { .. } => {
+                    writeln!(flowchart, "  {} -- {input_id} --> {target}", mapping).unwrap();
+                }
+                InputMapping::User(mapping) => {
+                    visualize_user_mapping(mapping, target, nodes, input_id, flowchart)
+                }
             }
         }
     }
@@ -6,14 +6,42 @@ use std::{
 };
 
 pub use dora_message as message;
+pub use serde_yaml;
 
 pub mod config;
 pub mod coordinator_messages;
 pub mod daemon_messages;
 pub mod descriptor;
+pub mod node_template;
+pub mod record;
 pub mod topics;
+pub mod version;
 
 pub fn adjust_shared_library_path(path: &Path) -> Result<std::path::PathBuf, eyre::ErrReport> {
+    shared_library_path(path, DLL_PREFIX, DLL_SUFFIX)
+}
+
+/// Like [`adjust_shared_library_path`], but uses the file name conventions of
+/// the platform of the given Rust target triple instead of the host platform.
+pub fn adjust_shared_library_path_for_target(
+    path: &Path,
+    target: &str,
+) -> Result<std::path::PathBuf, eyre::ErrReport> {
+    let (prefix, suffix) = if target.contains("windows") {
+        ("", ".dll")
+    } else if target.contains("apple") {
+        ("lib", ".dylib")
+    } else {
+        ("lib", ".so")
+    };
+    shared_library_path(path, prefix, suffix)
+}
+
+fn shared_library_path(
+    path: &Path,
+    prefix: &str,
+    suffix: &str,
+) -> Result<std::path::PathBuf, eyre::ErrReport> {
     let file_name = path
         .file_name()
         .ok_or_else(|| eyre!("shared library path has no file name"))?
@@ -26,23 +54,43 @@ pub fn adjust_shared_library_path(path: &Path) -> Result<std::path::PathBuf, eyr
         bail!("Shared library file name must have no extension, it is added automatically");
     }
 
-    let library_filename = format!("{DLL_PREFIX}{file_name}{DLL_SUFFIX}");
+    let library_filename = format!("{prefix}{file_name}{suffix}");
 
     let path = path.with_file_name(library_filename);
     Ok(path)
 }
 
 // Search for python binary.
-// Match `python` for windows and `python3` for other platforms.
+// Prefer `python3`, as `python` can resolve to Python 2 on older systems. On
+// windows, the official installer only provides `python` and `python3` might be
+// the stub that opens the Microsoft Store, so `python` is tried first there.
 pub fn get_python_path() -> Result<std::path::PathBuf, eyre::ErrReport> {
-    let python = if cfg!(windows) {
-        which::which("python")
-            .context("failed to find `python` or `python3`. Make sure that python is available.")?
+    let candidates = if cfg!(windows) {
+        ["python", "python3"]
     } else {
-        which::which("python3")
-            .context("failed to find `python` or `python3`. Make sure that python is available.")?
+        ["python3", "python"]
     };
-    Ok(python)
+    for candidate in candidates {
+        if let Ok(path) = which::which(candidate) {
+            if is_python3(&path) {
+                return Ok(path);
+            }
+        }
+    }
+    bail!("failed to find `python3` or `python`. Make sure that Python 3 is installed and in your PATH.")
+}
+
+fn is_python3(path: &Path) -> bool {
+    let Ok(output) = std::process::Command::new(path).arg("--version").output() else {
+        return false;
+    };
+    // Python 2 prints its version to stderr
+    let version = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+    output.status.success() && String::from_utf8_lossy(&version).starts_with("Python 3")
 }
 
 // Search for pip binary.
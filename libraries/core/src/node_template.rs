@@ -0,0 +1,389 @@
+//! Scaffolding for custom nodes with a given set of inputs and outputs, as
+//! used by `dora new --kind custom-node --input ... --output ...`.
+//!
+//! The generated nodes handle each input in a separate branch and answer it
+//! with an example message on every output, so that they can be started right
+//! away. The matching descriptor entry is available for copying it into an
+//! existing dataflow.
+
+use std::fmt::Write;
+
+/// Inputs and outputs of a generated custom node.
+#[derive(Debug, Clone, Default)]
+pub struct NodeIo {
+    /// Input IDs and their sources, e.g. `tick` and `dora/timer/millis/100`.
+    pub inputs: Vec<(String, String)>,
+    /// Output IDs.
+    pub outputs: Vec<String>,
+}
+
+/// Parses an input given as `ID=SOURCE`, e.g. `tick=dora/timer/millis/100`.
+pub fn parse_input(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((id, source)) if !id.is_empty() && !source.is_empty() => {
+            Ok((id.to_owned(), source.to_owned()))
+        }
+        _ => Err(format!("invalid input `{s}`, expected `ID=SOURCE`")),
+    }
+}
+
+impl NodeIo {
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty() && self.outputs.is_empty()
+    }
+
+    /// Returns the entry of the node for the `nodes` list of a dataflow YAML file.
+    ///
+    /// The `build` command is optional.
+    pub fn descriptor_snippet(&self, id: &str, source: &str, build: Option<&str>) -> String {
+        let mut snippet = format!("  - id: {id}\n    custom:\n");
+        if let Some(build) = build {
+            writeln!(snippet, "      build: {build}").unwrap();
+        }
+        writeln!(snippet, "      source: {source}").unwrap();
+        if !self.inputs.is_empty() {
+            snippet.push_str("      inputs:\n");
+            for (input, source) in &self.inputs {
+                writeln!(snippet, "        {input}: {source}").unwrap();
+            }
+        }
+        if !self.outputs.is_empty() {
+            snippet.push_str("      outputs:\n");
+            for output in &self.outputs {
+                writeln!(snippet, "        - {output}").unwrap();
+            }
+        }
+        snippet
+    }
+
+    /// Generates the `main.rs` file of a Rust node.
+    pub fn rust_node(&self) -> String {
+        let mut arms = String::new();
+        for (input, _) in &self.inputs {
+            writeln!(arms, "                {input:?} => {{").unwrap();
+            writeln!(
+                arms,
+                "                    println!(\"received input `{input}`\");"
+            )
+            .unwrap();
+            for output in &self.outputs {
+                writeln!(
+                    arms,
+                    "                    node.send_output(\n\
+                    \x20                       DataId::from({output:?}.to_owned()),\n\
+                    \x20                       metadata.parameters.clone(),\n\
+                    \x20                       String::from(\"handled {input}\").into_arrow(),\n\
+                    \x20                   )?;"
+                )
+                .unwrap();
+            }
+            arms.push_str("                }\n");
+        }
+
+        let (imports, metadata) = if self.outputs.is_empty() || self.inputs.is_empty() {
+            ("DoraNode, Event", "metadata: _")
+        } else {
+            (
+                "dora_core::config::DataId, DoraNode, Event, IntoArrow",
+                "metadata",
+            )
+        };
+        let node = if self.outputs.is_empty() || self.inputs.is_empty() {
+            "_node"
+        } else {
+            "mut node"
+        };
+        format!(
+            "use dora_node_api::{{{imports}}};
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {{
+    let ({node}, mut events) = DoraNode::init_from_env()?;
+
+    while let Some(event) = events.recv() {{
+        match event {{
+            Event::Input {{
+                id,
+                {metadata},
+                data: _,
+            }} => match id.as_str() {{
+{arms}                other => eprintln!(\"received unexpected input `{{other}}`\"),
+            }},
+            Event::Stop => break,
+            _ => {{}}
+        }}
+    }}
+
+    Ok(())
+}}
+"
+        )
+    }
+
+    /// Generates the script of a Python node.
+    pub fn python_node(&self) -> String {
+        let mut branches = String::new();
+        for (input, _) in &self.inputs {
+            let keyword = if branches.is_empty() { "if" } else { "elif" };
+            writeln!(branches, "        {keyword} event[\"id\"] == {input:?}:").unwrap();
+            writeln!(branches, "            print(\"received input `{input}`\")").unwrap();
+            for output in &self.outputs {
+                writeln!(
+                    branches,
+                    "            node.send_output({output:?}, pa.array([\"handled {input}\"]), event[\"metadata\"])"
+                )
+                .unwrap();
+            }
+        }
+        if branches.is_empty() {
+            branches.push_str("        print(f\"received input `{event['id']}`\")\n");
+        } else {
+            branches.push_str(
+                "        else:\n            print(f\"received unexpected input `{event['id']}`\")\n",
+            );
+        }
+
+        format!(
+            "#!/usr/bin/env python3
+# -*- coding: utf-8 -*-
+
+from dora import Node
+import pyarrow as pa
+
+node = Node()
+
+for event in node:
+    if event[\"type\"] == \"INPUT\":
+{branches}    elif event[\"type\"] == \"STOP\":
+        break
+"
+        )
+    }
+
+    /// Generates the source file of a C node.
+    pub fn c_node(&self) -> String {
+        let mut branches = String::new();
+        for (input, _) in &self.inputs {
+            let keyword = if branches.is_empty() {
+                "            if"
+            } else {
+                "            else if"
+            };
+            writeln!(branches, "{keyword} (is_input(id, id_len, {input:?}))").unwrap();
+            branches.push_str("            {\n");
+            writeln!(
+                branches,
+                "                printf(\"received input `{input}`\\n\");"
+            )
+            .unwrap();
+            if !self.outputs.is_empty() {
+                writeln!(
+                    branches,
+                    "                const char out_data[] = \"handled {input}\";"
+                )
+                .unwrap();
+            }
+            for output in &self.outputs {
+                writeln!(
+                    branches,
+                    "                dora_send_output(dora_context, {output:?}, strlen({output:?}), out_data, strlen(out_data));"
+                )
+                .unwrap();
+            }
+            branches.push_str("            }\n");
+        }
+        let unexpected = "printf(\"received input `%.*s`\\n\", (int)id_len, id);";
+        if branches.is_empty() {
+            writeln!(branches, "            {unexpected}").unwrap();
+        } else {
+            writeln!(
+                branches,
+                "            else\n            {{\n                {}\n            }}",
+                unexpected.replace("received input", "received unexpected input")
+            )
+            .unwrap();
+        }
+
+        let helper = if self.inputs.is_empty() {
+            ""
+        } else {
+            "static int is_input(const char *id, size_t id_len, const char *expected)
+{
+    return id_len == strlen(expected) && strncmp(id, expected, id_len) == 0;
+}
+
+"
+        };
+
+        format!(
+            "#include <stdio.h>
+#include <string.h>
+#include \"node_api.h\"
+
+{helper}int main()
+{{
+    void *dora_context = init_dora_context_from_env();
+    if (dora_context == NULL)
+    {{
+        fprintf(stderr, \"failed to init dora context\\n\");
+        return -1;
+    }}
+
+    while (1)
+    {{
+        void *event = dora_next_event(dora_context);
+        if (event == NULL)
+        {{
+            break;
+        }}
+
+        enum DoraEventType ty = read_dora_event_type(event);
+        if (ty == DoraEventType_Input)
+        {{
+            char *id;
+            size_t id_len;
+            read_dora_input_id(event, &id, &id_len);
+
+{branches}        }}
+        free_dora_event(event); // do not use `id` after freeing
+
+        if (ty == DoraEventType_Stop)
+        {{
+            break;
+        }}
+    }}
+
+    free_dora_context(dora_context);
+
+    return 0;
+}}
+"
+        )
+    }
+
+    /// Generates the source file of a C++ node that uses the `dora-node-api.h`
+    /// header of `dora-node-api-cxx`.
+    pub fn cxx_node(&self) -> String {
+        let mut branches = String::new();
+        for (input, _) in &self.inputs {
+            let keyword = if branches.is_empty() {
+                "            if"
+            } else {
+                "            else if"
+            };
+            writeln!(branches, "{keyword} (id == {input:?})").unwrap();
+            branches.push_str("            {\n");
+            writeln!(
+                branches,
+                "                std::cout << \"received input `{input}`\" << std::endl;"
+            )
+            .unwrap();
+            if !self.outputs.is_empty() {
+                writeln!(
+                    branches,
+                    "                std::string out_data{{\"handled {input}\"}};"
+                )
+                .unwrap();
+            }
+            for output in &self.outputs {
+                writeln!(
+                    branches,
+                    "                if (!send(dora_node, {output:?}, out_data))\n\
+                    \x20               {{\n\
+                    \x20                   return -1;\n\
+                    \x20               }}"
+                )
+                .unwrap();
+            }
+            branches.push_str("            }\n");
+        }
+        let unexpected = "std::cout << \"received input `\" << id << \"`\" << std::endl;";
+        if branches.is_empty() {
+            writeln!(branches, "            {unexpected}").unwrap();
+        } else {
+            writeln!(
+                branches,
+                "            else\n            {{\n                {}\n            }}",
+                unexpected.replace("received input", "received unexpected input")
+            )
+            .unwrap();
+        }
+
+        let helper = if self.outputs.is_empty() || self.inputs.is_empty() {
+            ""
+        } else {
+            "static bool send(DoraNode &dora_node, const char *output, const std::string &data)
+{
+    rust::Slice<const uint8_t> slice{reinterpret_cast<const uint8_t *>(data.data()), data.size()};
+    auto error = std::string(send_output(dora_node.send_output, output, slice).error);
+    if (!error.empty())
+    {
+        std::cerr << \"failed to send `\" << output << \"`: \" << error << std::endl;
+        return false;
+    }
+    return true;
+}
+
+"
+        };
+
+        format!(
+            "#include \"dora-node-api.h\" // adjust this path if necessary
+
+#include <iostream>
+#include <string>
+
+{helper}int main()
+{{
+    auto dora_node = init_dora_node();
+
+    while (1)
+    {{
+        auto event = dora_node.events->next();
+        auto ty = event_type(event);
+
+        if (ty == DoraEventType::Stop || ty == DoraEventType::AllInputsClosed)
+        {{
+            break;
+        }}
+        else if (ty == DoraEventType::Input)
+        {{
+            auto id = std::string(event_input_id(event));
+
+{branches}        }}
+    }}
+
+    return 0;
+}}
+"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::Descriptor;
+
+    #[test]
+    fn generated_nodes_match_descriptor() {
+        let io = NodeIo {
+            inputs: vec![
+                parse_input("tick=dora/timer/millis/100").unwrap(),
+                parse_input("image=camera/image").unwrap(),
+            ],
+            outputs: vec!["status".to_owned()],
+        };
+        assert!(parse_input("tick").is_err());
+
+        let snippet = io.descriptor_snippet("node", "bin/node", None);
+        let descriptor = Descriptor::parse(format!("nodes:\n{snippet}").into_bytes()).unwrap();
+        assert_eq!(descriptor.nodes[0].id.to_string(), "node");
+
+        for source in [io.rust_node(), io.python_node(), io.c_node(), io.cxx_node()] {
+            assert!(source.contains("\"tick\""), "{source}");
+            assert!(source.contains("\"image\""), "{source}");
+            assert!(source.contains("\"status\""), "{source}");
+        }
+    }
+}
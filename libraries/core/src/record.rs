@@ -0,0 +1,459 @@
+//! On-disk format for recorded dataflow messages.
+//!
+//! A recording consists of a directory of numbered segment files. Each segment
+//! starts with a short header, followed by length-prefixed entries. Segments
+//! are rotated once they exceed a configurable size, so that long-running
+//! recordings can be copied or pruned piece by piece.
+
+use crate::config::{DataId, NodeId};
+use aligned_vec::{AVec, ConstAlign};
+use dora_message::Metadata;
+use eyre::{bail, Context};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+const SEGMENT_MAGIC: &[u8; 8] = b"DORAREC1";
+const SEGMENT_EXTENSION: &str = "drec";
+pub const DEFAULT_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A single recorded output message.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecordedMessage {
+    pub node_id: NodeId,
+    pub output_id: DataId,
+    pub metadata: Metadata,
+    pub data: Option<AVec<u8, ConstAlign<128>>>,
+}
+
+/// Directory that contains the recording of the given dataflow.
+pub fn record_dir(working_dir: &Path, dataflow_id: &Uuid) -> PathBuf {
+    working_dir
+        .join("out")
+        .join(dataflow_id.to_string())
+        .join("record")
+}
+
+/// Returns the directory for a new recording of the given dataflow.
+///
+/// The first recording of a dataflow uses its `record_dir`. Later recordings,
+/// e.g. after stopping and restarting the recording through `dora record`, are
+/// stored next to it with a numbered suffix.
+pub fn new_record_dir(working_dir: &Path, dataflow_id: &Uuid) -> PathBuf {
+    let dir = record_dir(working_dir, dataflow_id);
+    if !dir.exists() {
+        return dir;
+    }
+    (2..)
+        .map(|i| dir.with_file_name(format!("record-{i}")))
+        .find(|dir| !dir.exists())
+        .expect("no free record dir name")
+}
+
+/// Returns the recordings of the given dataflow, see `new_record_dir`.
+pub fn recording_dirs(working_dir: &Path, dataflow_id: &Uuid) -> eyre::Result<Vec<PathBuf>> {
+    let dataflow_dir = working_dir.join("out").join(dataflow_id.to_string());
+    let entries = match std::fs::read_dir(&dataflow_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err)
+                .wrap_err_with(|| format!("failed to read `{}`", dataflow_dir.display()))
+        }
+    };
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let path = entry.wrap_err("failed to read dataflow dir entry")?.path();
+        let index = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(recording_index);
+        if let Some(index) = index {
+            if path.is_dir() {
+                dirs.push((index, path));
+            }
+        }
+    }
+    // sort numerically, so that `record-10` comes after `record-2`
+    dirs.sort_by_key(|(index, _)| *index);
+    Ok(dirs.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Parses the number of a recording dir name, as created by `new_record_dir`.
+fn recording_index(name: &str) -> Option<u32> {
+    if name == "record" {
+        Some(1)
+    } else {
+        name.strip_prefix("record-")?.parse().ok()
+    }
+}
+
+/// Summary of a recording on one of the machines of a dataflow.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecordingInfo {
+    pub machine: String,
+    pub dataflow_id: Uuid,
+    /// Directory of the recording on the machine.
+    pub path: PathBuf,
+    pub segments: usize,
+    /// Total size of the segments in bytes.
+    pub size: u64,
+    /// Whether the dataflow is still being recorded into this directory.
+    pub active: bool,
+}
+
+impl RecordingInfo {
+    pub fn read(
+        machine: String,
+        dataflow_id: Uuid,
+        path: PathBuf,
+        active: bool,
+    ) -> eyre::Result<Self> {
+        let mut segments = 0;
+        let mut size = 0;
+        for entry in std::fs::read_dir(&path)
+            .wrap_err_with(|| format!("failed to read record dir `{}`", path.display()))?
+        {
+            let entry = entry.wrap_err("failed to read record dir entry")?;
+            if entry.path().extension().and_then(|e| e.to_str()) == Some(SEGMENT_EXTENSION) {
+                segments += 1;
+                size += entry.metadata().map(|m| m.len()).unwrap_or_default();
+            }
+        }
+        Ok(Self {
+            machine,
+            dataflow_id,
+            path,
+            segments,
+            size,
+            active,
+        })
+    }
+}
+
+fn segment_path(dir: &Path, index: u32) -> PathBuf {
+    dir.join(format!("segment-{index:05}.{SEGMENT_EXTENSION}"))
+}
+
+/// Writes recorded messages into a segmented log.
+pub struct RecordWriter {
+    dir: PathBuf,
+    max_segment_size: u64,
+    segment_index: u32,
+    segment_size: u64,
+    file: BufWriter<File>,
+}
+
+impl RecordWriter {
+    pub fn create(dir: &Path, max_segment_size: u64) -> eyre::Result<Self> {
+        std::fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("failed to create record dir `{}`", dir.display()))?;
+        let file = Self::create_segment(dir, 0)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            max_segment_size,
+            segment_index: 0,
+            segment_size: SEGMENT_MAGIC.len() as u64,
+            file,
+        })
+    }
+
+    fn create_segment(dir: &Path, index: u32) -> eyre::Result<BufWriter<File>> {
+        let path = segment_path(dir, index);
+        let mut file = BufWriter::new(
+            File::create(&path)
+                .wrap_err_with(|| format!("failed to create segment `{}`", path.display()))?,
+        );
+        file.write_all(SEGMENT_MAGIC)
+            .wrap_err("failed to write segment header")?;
+        Ok(file)
+    }
+
+    pub fn write(&mut self, message: &RecordedMessage) -> eyre::Result<()> {
+        let serialized =
+            bincode::serialize(message).wrap_err("failed to serialize recorded message")?;
+        let entry_len = serialized.len() as u64 + 8;
+
+        if self.segment_size > SEGMENT_MAGIC.len() as u64
+            && self.segment_size + entry_len > self.max_segment_size
+        {
+            self.rotate()?;
+        }
+
+        self.file
+            .write_all(&(serialized.len() as u64).to_le_bytes())
+            .and_then(|()| self.file.write_all(&serialized))
+            .wrap_err("failed to write recorded message")?;
+        self.segment_size += entry_len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> eyre::Result<()> {
+        self.file.flush().wrap_err("failed to flush segment")?;
+        self.segment_index += 1;
+        self.file = Self::create_segment(&self.dir, self.segment_index)?;
+        self.segment_size = SEGMENT_MAGIC.len() as u64;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> eyre::Result<()> {
+        self.file.flush().wrap_err("failed to flush segment")
+    }
+}
+
+/// Reads the messages of a recording in the order they were written.
+pub struct RecordReader {
+    segments: std::vec::IntoIter<PathBuf>,
+    current: Option<Segment>,
+}
+
+struct Segment {
+    file: BufReader<File>,
+    /// Number of bytes left to read in the segment file.
+    remaining: u64,
+}
+
+impl RecordReader {
+    pub fn open(dir: &Path) -> eyre::Result<Self> {
+        let mut segments = Vec::new();
+        for entry in std::fs::read_dir(dir)
+            .wrap_err_with(|| format!("failed to read record dir `{}`", dir.display()))?
+        {
+            let path = entry.wrap_err("failed to read record dir entry")?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some(SEGMENT_EXTENSION) {
+                segments.push(path);
+            }
+        }
+        if segments.is_empty() {
+            bail!("no recorded segments found in `{}`", dir.display());
+        }
+        segments.sort();
+
+        Ok(Self {
+            segments: segments.into_iter(),
+            current: None,
+        })
+    }
+
+    fn next_segment(&mut self) -> eyre::Result<bool> {
+        let Some(path) = self.segments.next() else {
+            return Ok(false);
+        };
+        let file = File::open(&path)
+            .wrap_err_with(|| format!("failed to open segment `{}`", path.display()))?;
+        let len = file
+            .metadata()
+            .wrap_err_with(|| format!("failed to read metadata of `{}`", path.display()))?
+            .len();
+        let mut file = BufReader::new(file);
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)
+            .wrap_err_with(|| format!("failed to read header of `{}`", path.display()))?;
+        if &magic != SEGMENT_MAGIC {
+            bail!("`{}` is not a dora record segment", path.display());
+        }
+        self.current = Some(Segment {
+            file,
+            remaining: len.saturating_sub(SEGMENT_MAGIC.len() as u64),
+        });
+        Ok(true)
+    }
+
+    pub fn read_next(&mut self) -> eyre::Result<Option<RecordedMessage>> {
+        loop {
+            let Some(Segment { file, remaining }) = &mut self.current else {
+                if self.next_segment()? {
+                    continue;
+                }
+                return Ok(None);
+            };
+
+            let mut len_raw = [0; 8];
+            match file.read_exact(&mut len_raw) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                    self.current = None;
+                    continue;
+                }
+                Err(err) => return Err(err).wrap_err("failed to read entry length"),
+            }
+            *remaining = remaining.saturating_sub(len_raw.len() as u64);
+            let len = u64::from_le_bytes(len_raw);
+            if len > *remaining {
+                // the recording was interrupted while writing this entry, or
+                // the length is corrupt, so don't allocate a buffer for it
+                tracing::warn!(
+                    "skipping entry of {len} bytes at end of record segment, \
+                    which has only {remaining} bytes left"
+                );
+                self.current = None;
+                continue;
+            }
+            let mut raw = vec![0; len as usize];
+            file.read_exact(&mut raw).wrap_err("failed to read entry")?;
+            *remaining -= len;
+            let message =
+                bincode::deserialize(&raw).wrap_err("failed to deserialize recorded message")?;
+            return Ok(Some(message));
+        }
+    }
+}
+
+impl Iterator for RecordReader {
+    type Item = eyre::Result<RecordedMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_next().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_message::{uhlc, ArrowTypeInfo};
+
+    fn message(index: u8) -> RecordedMessage {
+        let clock = uhlc::HLC::default();
+        RecordedMessage {
+            node_id: NodeId::from("node".to_owned()),
+            output_id: DataId::from("out".to_owned()),
+            metadata: Metadata::new(clock.new_timestamp(), ArrowTypeInfo::byte_array(100)),
+            data: Some(AVec::from_slice(128, &[index; 100])),
+        }
+    }
+
+    fn read_all(dir: &Path) -> Vec<u8> {
+        RecordReader::open(dir)
+            .unwrap()
+            .map(|message| message.unwrap().data.unwrap()[0])
+            .collect()
+    }
+
+    fn segments(dir: &Path) -> Vec<PathBuf> {
+        let mut segments: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        segments.sort();
+        segments
+    }
+
+    #[test]
+    fn recording_dirs_are_sorted_numerically() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataflow_id = Uuid::now_v7();
+        for _ in 0..11 {
+            std::fs::create_dir_all(new_record_dir(dir.path(), &dataflow_id)).unwrap();
+        }
+        std::fs::create_dir_all(
+            dir.path()
+                .join("out")
+                .join(dataflow_id.to_string())
+                .join("other"),
+        )
+        .unwrap();
+
+        let names: Vec<_> = recording_dirs(dir.path(), &dataflow_id)
+            .unwrap()
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        let expected: Vec<_> = std::iter::once("record".to_owned())
+            .chain((2..=11).map(|i| format!("record-{i}")))
+            .collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn rotates_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = RecordWriter::create(dir.path(), 300).unwrap();
+        for i in 0..5 {
+            writer.write(&message(i)).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        // each entry is larger than half of the segment size
+        assert_eq!(segments(dir.path()).len(), 5);
+        assert_eq!(read_all(dir.path()), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn oversized_entry_gets_own_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = RecordWriter::create(dir.path(), 10).unwrap();
+        writer.write(&message(0)).unwrap();
+        writer.write(&message(1)).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        // no empty segment is created before the first entry
+        assert_eq!(segments(dir.path()).len(), 2);
+        assert_eq!(read_all(dir.path()), [0, 1]);
+    }
+
+    #[test]
+    fn skips_truncated_trailing_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = RecordWriter::create(dir.path(), 300).unwrap();
+        for i in 0..3 {
+            writer.write(&message(i)).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        // cut off the end of the second segment, like an interrupted write
+        let segments = segments(dir.path());
+        let second = &segments[1];
+        let len = std::fs::metadata(second).unwrap().len();
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(second)
+            .unwrap();
+        file.set_len(len - 10).unwrap();
+
+        assert_eq!(read_all(dir.path()), [0, 2]);
+    }
+
+    #[test]
+    fn skips_truncated_length_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = RecordWriter::create(dir.path(), DEFAULT_SEGMENT_SIZE).unwrap();
+        writer.write(&message(0)).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let segment = &segments(dir.path())[0];
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(segment)
+            .unwrap();
+        file.write_all(&[1, 2, 3]).unwrap();
+
+        assert_eq!(read_all(dir.path()), [0]);
+    }
+
+    #[test]
+    fn skips_entry_longer_than_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = RecordWriter::create(dir.path(), DEFAULT_SEGMENT_SIZE).unwrap();
+        writer.write(&message(0)).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        // a corrupt length prefix must not lead to a huge allocation
+        let segment = &segments(dir.path())[0];
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(segment)
+            .unwrap();
+        file.write_all(&u64::MAX.to_le_bytes()).unwrap();
+        file.write_all(&[0; 16]).unwrap();
+
+        assert_eq!(read_all(dir.path()), [0]);
+    }
+}
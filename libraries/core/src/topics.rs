@@ -5,13 +5,16 @@ use std::{
     fmt::Display,
     net::{IpAddr, Ipv4Addr},
     path::PathBuf,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use uuid::Uuid;
 
 use crate::{
-    config::{NodeId, OperatorId},
-    descriptor::Descriptor,
+    config::{DataId, NodeId, OperatorId},
+    daemon_messages::{LogChunk, NodeStats},
+    descriptor::{Descriptor, OperatorDefinition, ResolvedNode},
+    record::RecordingInfo,
+    version::Version,
 };
 
 pub const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
@@ -21,6 +24,41 @@ pub const DORA_COORDINATOR_PORT_CONTROL_DEFAULT: u16 = 0x177C;
 
 pub const MANUAL_STOP: &str = "dora/stop";
 
+/// Environment variable that points dynamic nodes to the unix socket of the local daemon.
+pub const DORA_DAEMON_LOCAL_SOCKET_ENV: &str = "DORA_DAEMON_LOCAL_SOCKET";
+
+/// Unix socket address of the local daemon listener.
+///
+/// Addresses starting with `@` refer to the abstract socket namespace (Linux only),
+/// all other addresses are interpreted as file system paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalSocketAddr {
+    Path(PathBuf),
+    Abstract(String),
+}
+
+impl std::str::FromStr for LocalSocketAddr {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('@') {
+            Some("") => eyre::bail!("abstract socket name must not be empty"),
+            Some(name) => Ok(Self::Abstract(name.to_owned())),
+            None if s.is_empty() => eyre::bail!("socket path must not be empty"),
+            None => Ok(Self::Path(s.into())),
+        }
+    }
+}
+
+impl Display for LocalSocketAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocalSocketAddr::Path(path) => write!(f, "{}", path.display()),
+            LocalSocketAddr::Abstract(name) => write!(f, "@{name}"),
+        }
+    }
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub enum ControlRequest {
     Start {
@@ -35,6 +73,27 @@ pub enum ControlRequest {
         node_id: NodeId,
         operator_id: Option<OperatorId>,
     },
+    /// Stops a single node of a running dataflow and starts it again.
+    ///
+    /// The inputs that the node sends to stay open while it restarts.
+    RestartNode {
+        dataflow_uuid: Uuid,
+        node_id: NodeId,
+        /// Kill the node if it doesn't stop after the given duration.
+        grace_duration: Option<Duration>,
+    },
+    /// Loads an additional operator into a running runtime node.
+    LoadOperator {
+        dataflow_id: Uuid,
+        node_id: NodeId,
+        operator: OperatorDefinition,
+    },
+    /// Stops the given operator of a running runtime node and removes it.
+    UnloadOperator {
+        dataflow_id: Uuid,
+        node_id: NodeId,
+        operator_id: OperatorId,
+    },
     Check {
         dataflow_uuid: Uuid,
     },
@@ -49,7 +108,15 @@ pub enum ControlRequest {
     Logs {
         uuid: Option<Uuid>,
         name: Option<String>,
-        node: String,
+        /// Only return the logs of this node (default: all nodes).
+        node: Option<String>,
+        /// Only return the log messages that were written after this time.
+        #[serde(default)]
+        since: Option<SystemTime>,
+        /// Continue reading the logs of the given nodes at these offsets,
+        /// e.g. to follow the logs. Takes precedence over `since`.
+        #[serde(default)]
+        offsets: BTreeMap<NodeId, u64>,
     },
     Destroy,
     List,
@@ -59,6 +126,73 @@ pub enum ControlRequest {
         dataflow_id: Uuid,
         level: log::LevelFilter,
     },
+    NodeStats {
+        dataflow_uuid: Uuid,
+    },
+    /// Returns the nodes of a running dataflow together with their current
+    /// statistics.
+    Topology {
+        dataflow_uuid: Uuid,
+    },
+    /// Starts recording the given outputs (in `node/output` form, all outputs
+    /// if empty) of a running dataflow.
+    StartRecording {
+        dataflow_uuid: Uuid,
+        outputs: BTreeSet<String>,
+    },
+    StopRecording {
+        dataflow_uuid: Uuid,
+    },
+    /// Lists the recordings on all connected machines.
+    ListRecordings,
+    /// Returns a detailed report of a running or finished dataflow.
+    Inspect {
+        dataflow_uuid: Uuid,
+    },
+    /// Upgrades the given daemons (all daemons if empty) to the given
+    /// executable. Each daemon restarts after its running dataflows finished.
+    UpgradeDaemons {
+        binary: Vec<u8>,
+        machine_ids: BTreeSet<String>,
+    },
+    /// Adds a dynamic node to a running dataflow, e.g. to print the messages
+    /// of an output or to inject messages into an input.
+    AttachNode {
+        dataflow_uuid: Uuid,
+        node: AttachedNode,
+    },
+    /// Removes a node that was added through `AttachNode`.
+    DetachNode {
+        dataflow_uuid: Uuid,
+        node_id: NodeId,
+    },
+    /// Extracts a dataflow bundle (a `.tar.gz` archive created by
+    /// `dora bundle`) on the given daemons (all daemons if empty).
+    Deploy {
+        name: String,
+        bundle: Vec<u8>,
+        /// Directory to extract the bundle to, defaults to
+        /// `~/.dora/bundles/<name>` on each machine. Must be empty or contain
+        /// a previous deployment.
+        target_dir: Option<PathBuf>,
+        machine_ids: BTreeSet<String>,
+    },
+}
+
+/// A dynamic node that is added to a running dataflow, see
+/// `ControlRequest::AttachNode`.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct AttachedNode {
+    pub id: NodeId,
+    /// Machine of the daemon that the node connects to.
+    pub machine: String,
+    /// Outputs of the dataflow that the node receives, by input ID.
+    pub inputs: BTreeMap<DataId, (NodeId, DataId)>,
+    /// Inputs of the dataflow that the node sends to, by output ID.
+    ///
+    /// The targeted inputs keep their existing sources and stay open when the
+    /// node is detached.
+    pub outputs: BTreeMap<DataId, BTreeSet<(NodeId, DataId)>>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -91,14 +225,54 @@ pub enum DataflowStatus {
 pub enum ControlRequestReply {
     Error(String),
     CoordinatorStopped,
-    DataflowStarted { uuid: Uuid },
-    DataflowReloaded { uuid: Uuid },
-    DataflowStopped { uuid: Uuid, result: DataflowResult },
+    DataflowStarted {
+        uuid: Uuid,
+    },
+    DataflowReloaded {
+        uuid: Uuid,
+    },
+    NodeRestarting {
+        uuid: Uuid,
+        node_id: NodeId,
+    },
+    OperatorLoaded {
+        uuid: Uuid,
+    },
+    OperatorUnloaded {
+        uuid: Uuid,
+    },
+    DataflowStopped {
+        uuid: Uuid,
+        result: DataflowResult,
+    },
     DataflowList(DataflowList),
     DestroyOk,
     DaemonConnected(bool),
     ConnectedMachines(BTreeSet<String>),
-    Logs(Vec<u8>),
+    Logs(BTreeMap<NodeId, LogChunk>),
+    NodeStats(BTreeMap<NodeId, NodeStats>),
+    Topology {
+        nodes: Vec<ResolvedNode>,
+        stats: BTreeMap<NodeId, NodeStats>,
+    },
+    /// Version that the daemons will be upgraded to, by machine.
+    DaemonsUpgrading(BTreeMap<String, Version>),
+    RecordingStarted {
+        uuid: Uuid,
+    },
+    RecordingStopped {
+        uuid: Uuid,
+    },
+    Recordings(Vec<RecordingInfo>),
+    Inspect(Box<DataflowInspection>),
+    NodeAttached {
+        uuid: Uuid,
+    },
+    NodeDetached {
+        uuid: Uuid,
+    },
+    /// Directory that the bundle was extracted to, by machine.
+    Deployed(BTreeMap<String, PathBuf>),
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -117,6 +291,48 @@ impl Display for DataflowId {
     }
 }
 
+/// Detailed state of a running or finished dataflow, see `ControlRequest::Inspect`.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct DataflowInspection {
+    pub id: DataflowId,
+    pub status: DataflowStatus,
+    pub descriptor: Descriptor,
+    pub nodes: Vec<NodeInspection>,
+    /// The latest error messages of the dataflow, oldest first.
+    pub recent_errors: Vec<ErrorRecord>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct NodeInspection {
+    /// The node with aliases resolved and defaults applied, including the
+    /// machine that it is deployed to.
+    pub node: ResolvedNode,
+    pub state: NodeState,
+    /// How often the node was restarted through `ControlRequest::RestartNode`
+    /// or operators of the node were restarted because of their
+    /// `on_error: restart` policy.
+    pub restarts: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeState {
+    /// The daemon reported no state for the node, e.g. because it is still
+    /// being spawned.
+    Unknown,
+    Running,
+    Finished,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ErrorRecord {
+    /// Time at which the coordinator received the error.
+    pub time: SystemTime,
+    pub node_id: Option<NodeId>,
+    pub message: String,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct DataflowResult {
     pub uuid: Uuid,
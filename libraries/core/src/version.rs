@@ -0,0 +1,68 @@
+//! Compatibility checks between the dora versions of nodes, daemons, and the
+//! coordinator.
+
+pub use semver::Version;
+
+/// The dora version that this crate is part of.
+pub fn current() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("crate version is not valid semver")
+}
+
+/// Checks whether two dora versions use compatible message formats.
+///
+/// Follows the semver rules of Cargo: versions are compatible if they have the
+/// same major version, or the same minor version for `0.x` releases. `0.0.x`
+/// releases and pre-releases are only compatible with the exact same version.
+pub fn compatible(a: &Version, b: &Version) -> bool {
+    if !a.pre.is_empty() || !b.pre.is_empty() {
+        return (a.major, a.minor, a.patch, &a.pre) == (b.major, b.minor, b.patch, &b.pre);
+    }
+    match (a.major, a.minor) {
+        (0, 0) => (b.major, b.minor, b.patch) == (0, 0, a.patch),
+        (0, minor) => b.major == 0 && b.minor == minor,
+        (major, _) => b.major == major,
+    }
+}
+
+/// Like [`compatible`], but returns an error message naming both sides on a
+/// mismatch, e.g. `daemon` and `coordinator`.
+pub fn check_compatible(
+    (name, version): (&str, &Version),
+    (other_name, other_version): (&str, &Version),
+) -> Result<(), String> {
+    if compatible(version, other_version) {
+        Ok(())
+    } else {
+        Err(format!(
+            "version mismatch: {name} v{version} is not compatible with \
+            {other_name} v{other_version} (versions must match up to the \
+            first non-zero component, e.g. `0.3.x`)"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compatible(a: &str, b: &str) -> bool {
+        let (a, b) = (Version::parse(a).unwrap(), Version::parse(b).unwrap());
+        assert_eq!(super::compatible(&a, &b), super::compatible(&b, &a));
+        super::compatible(&a, &b)
+    }
+
+    #[test]
+    fn semver_compatibility() {
+        assert!(compatible("0.3.5", "0.3.5"));
+        assert!(compatible("0.3.5", "0.3.9"));
+        assert!(!compatible("0.3.5", "0.4.0"));
+        assert!(!compatible("0.0.1", "0.0.2"));
+        assert!(compatible("1.2.0", "1.7.3"));
+        assert!(!compatible("1.2.0", "2.0.0"));
+        assert!(!compatible("0.3.5", "1.3.5"));
+        assert!(compatible("0.4.0-rc.1", "0.4.0-rc.1"));
+        assert!(!compatible("0.4.0-rc.1", "0.4.0-rc.2"));
+        assert!(!compatible("0.4.0-rc.1", "0.4.0"));
+        assert!(compatible("0.3.5+build1", "0.3.5+build2"));
+    }
+}
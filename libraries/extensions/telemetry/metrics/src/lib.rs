@@ -17,6 +17,10 @@ use opentelemetry::metrics::{self, MeterProvider as _};
 use opentelemetry_otlp::{ExportConfig, WithExportConfig};
 use opentelemetry_sdk::{metrics::SdkMeterProvider, runtime};
 use opentelemetry_system_metrics::init_process_observer;
+
+pub use opentelemetry;
+pub use opentelemetry_sdk::metrics::SdkMeterProvider as MeterProvider;
+
 /// Init opentelemetry meter
 ///
 /// Use the default Opentelemetry exporter with default config
@@ -25,6 +29,21 @@ use opentelemetry_system_metrics::init_process_observer;
 pub fn init_metrics() -> metrics::Result<SdkMeterProvider> {
     let endpoint = std::env::var("OTEL_EXPORTER_OTLP_METRICS_ENDPOINT")
         .unwrap_or_else(|_| "http://localhost:4317".to_string());
+    init_metrics_with_endpoint(endpoint)
+}
+
+/// Init opentelemetry meter that exports to the given OTLP gRPC endpoint.
+///
+/// Metrics are exported periodically and on [`SdkMeterProvider::force_flush`].
+pub fn init_metrics_with_endpoint(endpoint: String) -> metrics::Result<SdkMeterProvider> {
+    init_metrics_with_period(endpoint, Duration::from_secs(10))
+}
+
+/// Like [`init_metrics_with_endpoint`], but exports every `period`.
+pub fn init_metrics_with_period(
+    endpoint: String,
+    period: Duration,
+) -> metrics::Result<SdkMeterProvider> {
     let export_config = ExportConfig {
         endpoint,
         ..ExportConfig::default()
@@ -37,12 +56,17 @@ pub fn init_metrics() -> metrics::Result<SdkMeterProvider> {
                 .tonic()
                 .with_export_config(export_config),
         )
-        .with_period(Duration::from_secs(10))
+        .with_period(period)
         .build()
 }
 
+/// Init the meter provider and start observing the metrics of the current process.
+///
+/// The provider is also installed as the global meter provider, so that
+/// [`opentelemetry::global::meter`] can be used to record custom metrics.
 pub fn init_meter_provider(meter_id: String) -> Result<SdkMeterProvider> {
     let meter_provider = init_metrics().context("Could not create opentelemetry meter")?;
+    opentelemetry::global::set_meter_provider(meter_provider.clone());
     let meter = meter_provider.meter(meter_id);
     init_process_observer(meter).context("could not initiale system metrics observer")?;
     Ok(meter_provider)
@@ -2,6 +2,7 @@
 //!
 //! This module init a tracing propagator for Rust code that requires tracing, and is
 //! able to serialize and deserialize context that has been sent via the middleware.
+//! With the `metrics` feature, node metrics can be exported over OTLP as well.
 
 use std::path::Path;
 
@@ -13,6 +14,8 @@ use tracing_subscriber::{
 
 use eyre::ContextCompat;
 use tracing_subscriber::Registry;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod telemetry;
 
 pub fn set_up_tracing(name: &str) -> eyre::Result<()> {
@@ -0,0 +1,203 @@
+//! Export of node metrics over OTLP, next to the spans of [`crate::telemetry`].
+//!
+//! [`DataflowMetrics`] exports the metrics of the local nodes of a dataflow to
+//! an OpenTelemetry collector. All metrics carry a `dataflow` and a `node`
+//! attribute:
+//!
+//! - `dora_node_output_messages` and `dora_node_output_bytes`: sent messages,
+//!   per `output`.
+//! - `dora_node_input_messages` and `dora_node_input_bytes`: messages that
+//!   were delivered to the node, per `input`.
+//! - `dora_node_dropped_inputs`: inputs that were dropped because the input
+//!   queue of the node was full.
+//! - `dora_node_input_latency`: histogram of the time between sending an
+//!   input and handing it to the node, in seconds.
+//! - `dora_node_queue_depth`: number of events that wait to be received by
+//!   the node.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use dora_metrics::{
+    opentelemetry::{
+        metrics::{Counter, Histogram, MeterProvider as _, ObservableGauge, Unit},
+        KeyValue,
+    },
+    MeterProvider,
+};
+use eyre::Context;
+
+/// Last reported queue depth per node, read when the gauge is observed.
+type QueueDepths = Arc<Mutex<BTreeMap<String, u64>>>;
+
+/// OTLP metrics pipeline of a dataflow.
+///
+/// The metrics are exported periodically and once more when this value is
+/// dropped.
+pub struct DataflowMetrics {
+    provider: MeterProvider,
+    dataflow_id: String,
+    instruments: Instruments,
+    queue_depths: QueueDepths,
+    _queue_depth: ObservableGauge<u64>,
+}
+
+#[derive(Clone)]
+struct Instruments {
+    output_messages: Counter<u64>,
+    output_bytes: Counter<u64>,
+    input_messages: Counter<u64>,
+    input_bytes: Counter<u64>,
+    dropped_inputs: Counter<u64>,
+    input_latency: Histogram<f64>,
+}
+
+impl DataflowMetrics {
+    /// Starts exporting to the given OTLP gRPC endpoint every `interval`.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn new(dataflow_id: String, endpoint: &str, interval: Duration) -> eyre::Result<Self> {
+        let provider = dora_metrics::init_metrics_with_period(endpoint.to_owned(), interval)
+            .wrap_err_with(|| format!("failed to set up OTLP metrics export to `{endpoint}`"))?;
+        let meter = provider.meter("dora");
+        let instruments = Instruments {
+            output_messages: meter
+                .u64_counter("dora_node_output_messages")
+                .with_description("Messages sent by the node")
+                .init(),
+            output_bytes: meter
+                .u64_counter("dora_node_output_bytes")
+                .with_description("Data sent by the node")
+                .with_unit(Unit::new("By"))
+                .init(),
+            input_messages: meter
+                .u64_counter("dora_node_input_messages")
+                .with_description("Messages delivered to the node")
+                .init(),
+            input_bytes: meter
+                .u64_counter("dora_node_input_bytes")
+                .with_description("Data delivered to the node")
+                .with_unit(Unit::new("By"))
+                .init(),
+            dropped_inputs: meter
+                .u64_counter("dora_node_dropped_inputs")
+                .with_description("Inputs dropped because the input queue was full")
+                .init(),
+            input_latency: meter
+                .f64_histogram("dora_node_input_latency")
+                .with_description("Time between sending an input and handing it to the node")
+                .with_unit(Unit::new("s"))
+                .init(),
+        };
+
+        let queue_depths = QueueDepths::default();
+        let observed = queue_depths.clone();
+        let dataflow = KeyValue::new("dataflow", dataflow_id.clone());
+        let queue_depth = meter
+            .u64_observable_gauge("dora_node_queue_depth")
+            .with_description("Events that wait to be received by the node")
+            .with_callback(move |observer| {
+                let Ok(depths) = observed.lock() else {
+                    return;
+                };
+                for (node_id, depth) in depths.iter() {
+                    observer.observe(
+                        *depth,
+                        &[dataflow.clone(), KeyValue::new("node", node_id.clone())],
+                    );
+                }
+            })
+            .init();
+
+        Ok(Self {
+            provider,
+            dataflow_id,
+            instruments,
+            queue_depths,
+            _queue_depth: queue_depth,
+        })
+    }
+
+    /// Returns a handle that records the metrics of the given node.
+    pub fn node(&self, node_id: &str) -> NodeMetrics {
+        NodeMetrics {
+            node_id: node_id.to_owned(),
+            attributes: vec![
+                KeyValue::new("dataflow", self.dataflow_id.clone()),
+                KeyValue::new("node", node_id.to_owned()),
+            ],
+            instruments: self.instruments.clone(),
+            queue_depths: self.queue_depths.clone(),
+        }
+    }
+}
+
+impl Drop for DataflowMetrics {
+    fn drop(&mut self) {
+        // the pipeline is shut down when the provider is dropped, so export
+        // the final values first
+        if let Err(err) = self.provider.force_flush() {
+            tracing::warn!(
+                "failed to export metrics of dataflow {}: {err}",
+                self.dataflow_id
+            );
+        }
+    }
+}
+
+/// Records the metrics of a single node, see [`DataflowMetrics::node`].
+#[derive(Clone)]
+pub struct NodeMetrics {
+    node_id: String,
+    attributes: Vec<KeyValue>,
+    instruments: Instruments,
+    queue_depths: QueueDepths,
+}
+
+impl NodeMetrics {
+    pub fn output_sent(&self, output_id: &str, bytes: usize) {
+        let attributes = self.with_attribute("output", output_id);
+        self.instruments.output_messages.add(1, &attributes);
+        self.instruments.output_bytes.add(bytes as u64, &attributes);
+    }
+
+    pub fn input_delivered(&self, input_id: &str, bytes: usize) {
+        let attributes = self.with_attribute("input", input_id);
+        self.instruments.input_messages.add(1, &attributes);
+        self.instruments.input_bytes.add(bytes as u64, &attributes);
+    }
+
+    pub fn inputs_dropped(&self, count: u64) {
+        self.instruments.dropped_inputs.add(count, &self.attributes);
+    }
+
+    pub fn input_latency(&self, latency: Duration) {
+        self.instruments
+            .input_latency
+            .record(latency.as_secs_f64(), &self.attributes);
+    }
+
+    pub fn set_queue_depth(&self, depth: usize) {
+        if let Ok(mut depths) = self.queue_depths.lock() {
+            depths.insert(self.node_id.clone(), depth as u64);
+        }
+    }
+
+    fn with_attribute(&self, key: &'static str, value: &str) -> Vec<KeyValue> {
+        let mut attributes = self.attributes.clone();
+        attributes.push(KeyValue::new(key, value.to_owned()));
+        attributes
+    }
+}
+
+impl fmt::Debug for NodeMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeMetrics")
+            .field("node_id", &self.node_id)
+            .finish_non_exhaustive()
+    }
+}
@@ -1,8 +1,9 @@
 use opentelemetry::propagation::Extractor;
 use opentelemetry::sdk::{propagation::TraceContextPropagator, trace as sdktrace};
-use opentelemetry::trace::TraceError;
-use opentelemetry::{global, Context};
+use opentelemetry::trace::{Span, TraceContextExt, TraceError, Tracer};
+use opentelemetry::{global, Context, KeyValue};
 use std::collections::HashMap;
+use std::time::SystemTime;
 
 struct MetadataMap<'a>(HashMap<&'a str, &'a str>);
 
@@ -53,6 +54,39 @@ pub fn serialize_context(context: &Context) -> String {
     string_context
 }
 
+/// Records a span that started at `start` and ends now.
+///
+/// The span becomes a child of the serialized `parent` context, or the root of
+/// a new trace if `parent` is empty. Returns the serialized context of the new
+/// span so that following operations can continue the trace from it. If no
+/// tracer is installed, `parent` is returned unchanged.
+pub fn record_span(
+    name: &'static str,
+    parent: &str,
+    start: SystemTime,
+    attributes: &[(&'static str, String)],
+) -> String {
+    let parent_cx = deserialize_context(parent);
+    let tracer = global::tracer("dora");
+    let mut span = tracer
+        .span_builder(name)
+        .with_start_time(start)
+        .with_attributes(
+            attributes
+                .iter()
+                .map(|(key, value)| KeyValue::new(*key, value.clone()))
+                .collect::<Vec<_>>(),
+        )
+        .start_with_context(&tracer, &parent_cx);
+    span.end();
+    let cx = parent_cx.with_span(span);
+    if cx.span().span_context().is_valid() {
+        serialize_context(&cx)
+    } else {
+        parent.to_owned()
+    }
+}
+
 pub fn deserialize_context(string_context: &str) -> Context {
     let map = MetadataMap(deserialize_to_hashmap(string_context));
     global::get_text_map_propagator(|prop| prop.extract(&map))
@@ -7,14 +7,107 @@ use arrow_data::ArrayData;
 use arrow_schema::DataType;
 use eyre::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 pub use uhlc;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Version of the [`Metadata`] encoding.
+///
+/// Version 1 added the typed [`MetadataParameters::entries`]. Version 2 added
+/// [`MetadataParameters::priority`]. Version 3 added the [`SendTime`].
+pub const METADATA_VERSION: u16 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Metadata {
     metadata_version: u16,
     timestamp: uhlc::Timestamp,
     pub type_info: ArrowTypeInfo,
     pub parameters: MetadataParameters,
+    /// Timestamp normalized to the reference clock of the dataflow, in
+    /// nanoseconds since the UNIX epoch.
+    ///
+    /// Set by the daemon when the message is sent out. Can be used to correlate
+    /// messages of different machines.
+    dataflow_timestamp: Option<u64>,
+    /// Compression of the data, if it was compressed for a cross-machine edge.
+    ///
+    /// Set by the sending daemon. The receiving daemon decompresses the data
+    /// and clears this field before delivering the message to nodes.
+    #[serde(default)]
+    compression: Option<Compression>,
+    /// Time at which the message was sent by the node.
+    #[serde(default)]
+    send_time: Option<SendTime>,
+}
+
+/// Time at which a message was sent, used to measure the latency of edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SendTime {
+    /// Nanoseconds since the UNIX epoch.
+    pub wall_clock: u64,
+    /// Nanoseconds of the system-wide monotonic clock, if available.
+    ///
+    /// The monotonic clock is not affected by clock adjustments, but its
+    /// values are only comparable on the same machine. The daemon removes it
+    /// from messages that are sent to other machines.
+    pub monotonic: Option<u64>,
+}
+
+impl SendTime {
+    pub fn now() -> Self {
+        Self {
+            wall_clock: wall_clock_now(),
+            monotonic: monotonic_now(),
+        }
+    }
+
+    /// Time elapsed since the message was sent.
+    ///
+    /// Uses the monotonic clock if possible. Otherwise, the result depends on
+    /// the synchronization of the wall clocks of the sending and receiving
+    /// machines. Returns zero if the send time appears to be in the future.
+    pub fn elapsed(&self) -> std::time::Duration {
+        let nanos = match (self.monotonic, monotonic_now()) {
+            (Some(sent), Some(now)) => now.saturating_sub(sent),
+            _ => wall_clock_now().saturating_sub(self.wall_clock),
+        };
+        std::time::Duration::from_nanos(nanos)
+    }
+}
+
+fn wall_clock_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos().try_into().unwrap_or(u64::MAX))
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn monotonic_now() -> Option<u64> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // `Instant` uses the same clock, but doesn't expose its value
+    let result = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+    if result != 0 {
+        return None;
+    }
+    let secs = u64::try_from(time.tv_sec).ok()?;
+    let nanos = u64::try_from(time.tv_nsec).ok()?;
+    Some(secs * 1_000_000_000 + nanos)
+}
+
+#[cfg(not(unix))]
+fn monotonic_now() -> Option<u64> {
+    None
+}
+
+/// Compression algorithm for data that is sent between machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Lz4,
+    Zstd,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -105,11 +198,199 @@ pub struct BufferOffset {
     pub len: usize,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+/// Metadata entry that identifies the sending output as `node/output`.
+///
+/// Set by the daemon for inputs that receive messages from multiple sources.
+pub const SOURCE_PARAMETER: &str = "dora.source";
+
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
 pub struct MetadataParameters {
     pub watermark: u64,
+    /// Time after which the message is no longer useful, in nanoseconds since
+    /// the UNIX epoch. `0` means that the message has no deadline.
+    ///
+    /// Messages are dropped instead of delivered once their deadline has
+    /// passed. The deadline is compared against the system clock of the
+    /// receiving machine, so the clocks of the machines should be synchronized.
+    ///
+    /// Earlier versions did not interpret this field. Senders that used it for
+    /// other values, e.g. relative durations, need to switch to absolute times
+    /// since their messages are dropped otherwise.
     pub deadline: u64,
     pub open_telemetry_context: String,
+    /// Custom key/value entries, e.g. the frame ID or the encoding of an image.
+    #[serde(default)]
+    pub entries: BTreeMap<String, Parameter>,
+    /// Delivery priority of the message.
+    ///
+    /// Queued messages of higher priority are delivered first and transports
+    /// that support it send them with a higher QoS priority.
+    #[serde(default)]
+    pub priority: MessagePriority,
+}
+
+/// Delivery priority of a single message.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MessagePriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    /// For time-critical messages, e.g. control commands.
+    RealTime,
+}
+
+impl std::str::FromStr for MessagePriority {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Self::Low),
+            "normal" => Ok(Self::Normal),
+            "high" => Ok(Self::High),
+            "realtime" => Ok(Self::RealTime),
+            other => eyre::bail!(
+                "unknown message priority `{other}` (expected `low`, `normal`, `high`, or `realtime`)"
+            ),
+        }
+    }
+}
+
+/// Typed value of a custom metadata entry.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Parameter {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    /// Nanoseconds since the UNIX epoch.
+    Timestamp(u64),
+}
+
+impl Parameter {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Parameter::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Parameter::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of `Float` entries, and of `Integer` entries converted to `f64`.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Parameter::Float(value) => Some(*value),
+            Parameter::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Parameter::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Parameter::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_timestamp(&self) -> Option<std::time::SystemTime> {
+        match self {
+            Parameter::Timestamp(nanos) => {
+                Some(std::time::UNIX_EPOCH + std::time::Duration::from_nanos(*nanos))
+            }
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_parameter_from {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$ty> for Parameter {
+                fn from(value: $ty) -> Self {
+                    Parameter::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_parameter_from!(
+    bool => Bool,
+    i32 => Integer,
+    i64 => Integer,
+    u32 => Integer,
+    f32 => Float,
+    f64 => Float,
+    String => String,
+    &str => String,
+    Vec<u8> => Bytes,
+    &[u8] => Bytes,
+);
+
+impl From<std::time::SystemTime> for Parameter {
+    /// Times before the UNIX epoch are clamped to the epoch.
+    fn from(time: std::time::SystemTime) -> Self {
+        let nanos = time
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos().try_into().unwrap_or(u64::MAX))
+            .unwrap_or(0);
+        Parameter::Timestamp(nanos)
+    }
+}
+
+impl MetadataParameters {
+    /// Returns the custom entry with the given key.
+    pub fn get(&self, key: &str) -> Option<&Parameter> {
+        self.entries.get(key)
+    }
+
+    /// Sets a custom entry, returning the previous value.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<Parameter>,
+    ) -> Option<Parameter> {
+        self.entries.insert(key.into(), value.into())
+    }
+
+    /// Returns the deadline of the message, if it has one.
+    pub fn deadline_time(&self) -> Option<std::time::SystemTime> {
+        (self.deadline != 0)
+            .then(|| std::time::UNIX_EPOCH + std::time::Duration::from_nanos(self.deadline))
+    }
+
+    /// Sets the deadline of the message.
+    pub fn set_deadline(&mut self, deadline: std::time::SystemTime) {
+        self.deadline = deadline
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos().try_into().unwrap_or(u64::MAX))
+            // `0` means no deadline, so deadlines in the past must stay non-zero
+            .unwrap_or(1)
+            .max(1);
+    }
+
+    /// Returns whether the deadline of the message has passed.
+    pub fn is_expired(&self) -> bool {
+        self.deadline_time()
+            .is_some_and(|deadline| deadline < std::time::SystemTime::now())
+    }
 }
 
 impl MetadataParameters {
@@ -132,14 +413,52 @@ impl Metadata {
         parameters: MetadataParameters,
     ) -> Self {
         Self {
-            metadata_version: 0,
+            metadata_version: METADATA_VERSION,
             timestamp,
             parameters,
             type_info,
+            dataflow_timestamp: None,
+            compression: None,
+            send_time: None,
         }
     }
 
+    pub fn metadata_version(&self) -> u16 {
+        self.metadata_version
+    }
+
     pub fn timestamp(&self) -> uhlc::Timestamp {
         self.timestamp
     }
+
+    pub fn dataflow_timestamp(&self) -> Option<u64> {
+        self.dataflow_timestamp
+    }
+
+    pub fn set_dataflow_timestamp(&mut self, timestamp: Option<u64>) {
+        self.dataflow_timestamp = timestamp;
+    }
+
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    pub fn set_compression(&mut self, compression: Option<Compression>) {
+        self.compression = compression;
+    }
+
+    pub fn send_time(&self) -> Option<SendTime> {
+        self.send_time
+    }
+
+    pub fn set_send_time(&mut self, send_time: Option<SendTime>) {
+        self.send_time = send_time;
+    }
+
+    /// Time elapsed since the message was sent, see [`SendTime::elapsed`].
+    ///
+    /// Returns `None` for messages without send time, e.g. timer ticks.
+    pub fn latency(&self) -> Option<std::time::Duration> {
+        self.send_time.map(|t| t.elapsed())
+    }
 }